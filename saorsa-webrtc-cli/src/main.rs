@@ -18,6 +18,14 @@ struct Cli {
     #[arg(short, long, env = "SAORSA_IDENTITY")]
     identity: Option<String>,
 
+    /// Only send audio while talking is toggled on with the space bar
+    #[arg(long, global = true)]
+    push_to_talk: bool,
+
+    /// Drop microphone audio quieter than this level in dBFS (e.g. -45)
+    #[arg(long, global = true, allow_hyphen_values = true)]
+    noise_gate: Option<f32>,
+
     #[command(subcommand)]
 command: Commands,
 }
@@ -53,6 +61,17 @@ auto_accept: bool,
 display: CliDisplayMode,
 },
 
+/// Run a pre-call microphone self-test
+Nettest {
+    /// Raw mono 16-bit little-endian PCM recorded from the microphone
+    #[arg(long)]
+    input: std::path::PathBuf,
+
+    /// Sample rate of the input in Hz
+    #[arg(long, default_value = "48000")]
+    sample_rate: u32,
+},
+
 /// Show status and available commands
 Status,
 }
@@ -74,12 +93,21 @@ async fn main() -> Result<()> {
 
     println!("🔗 Using identity: {}", identity);
 
+    let config = WebRtcConfig {
+        push_to_talk: cli.push_to_talk,
+        noise_gate_dbfs: cli.noise_gate,
+        ..Default::default()
+    };
+
     match cli.command {
         Commands::Call { peer, video, audio, display } => {
-            handle_call(&identity, &peer, video, audio, display).await?;
+            handle_call(&identity, &peer, video, audio, display, config).await?;
         }
         Commands::Listen { auto_accept, display } => {
-            handle_listen(&identity, auto_accept, display).await?;
+            handle_listen(&identity, auto_accept, display, config).await?;
+        }
+        Commands::Nettest { input, sample_rate } => {
+            handle_nettest(&input, sample_rate)?;
         }
         Commands::Status => {
             handle_status().await?;
@@ -95,6 +123,7 @@ async fn handle_call(
     video: bool,
     audio: bool,
     display: CliDisplayMode,
+    config: WebRtcConfig,
 ) -> Result<()> {
     println!("📞 Calling {}...", peer);
     println!("   Video: {} | Audio: {} | Display: {:?}", video, audio, display);
@@ -110,6 +139,7 @@ async fn handle_call(
 
     // Create WebRTC service
     let service = Arc::new(WebRtcService::builder(signaling)
+    .with_config(config)
     .build()
     .await?);
 
@@ -122,9 +152,16 @@ async fn handle_call(
     audio,
     video,
     screen_share: false,
+    ..MediaConstraints::audio_only()
     };
 
-    // Initiate call
+    // Pick up the answer and candidates, then send the offer
+    let receiver = Arc::clone(&service);
+    tokio::spawn(async move {
+        if let Err(e) = receiver.receive_signaling().await {
+            tracing::error!("Signaling stopped: {}", e);
+        }
+    });
     let peer_identity = PeerIdentityString::new(peer);
     let call_id = service.initiate_call(peer_identity, constraints).await?;
     println!("📞 Call initiated with ID: {}", call_id);
@@ -138,9 +175,10 @@ async fn handle_call(
 }
 
 async fn handle_listen(
-    _identity: &str,
+    identity: &str,
     auto_accept: bool,
     display: CliDisplayMode,
+    config: WebRtcConfig,
 ) -> Result<()> {
     println!("👂 Listening for incoming calls...");
     if auto_accept {
@@ -159,6 +197,8 @@ async fn handle_listen(
 
     // Create WebRTC service
     let service = Arc::new(WebRtcService::builder(signaling)
+        .with_config(config)
+        .with_identity(PeerIdentityString::new(identity))
         .build()
         .await?);
 
@@ -166,18 +206,24 @@ async fn handle_listen(
     service.start().await?;
     println!("✅ WebRTC service started");
 
-    // Subscribe to events
+    // Subscribe to events, then pick up inbound offers
     let mut events = service.subscribe_events();
+    let receiver = Arc::clone(&service);
+    tokio::spawn(async move {
+        if let Err(e) = receiver.receive_signaling().await {
+            tracing::error!("Signaling stopped: {}", e);
+        }
+    });
 
     loop {
         tokio::select! {
             event = events.recv() => {
                 match event {
-                    Ok(WebRtcEvent::Call(CallEvent::IncomingCall { offer })) => {
-                        println!("📞 Incoming call from {}", offer.caller);
+                    Ok(WebRtcEvent::IncomingCall { call_id, from, constraints }) => {
+                        println!("📞 Incoming call from {}", from);
                         println!("   Video: {} | Audio: {}",
-                            offer.media_types.contains(&saorsa_webrtc_core::types::MediaType::Video),
-                            offer.media_types.contains(&saorsa_webrtc_core::types::MediaType::Audio)
+                            constraints.has_video(),
+                            constraints.has_audio()
                         );
 
                         let should_accept = if auto_accept {
@@ -191,22 +237,43 @@ async fn handle_listen(
 
                         if should_accept {
                             println!("✅ Accepting call...");
-                            // Convert media types back to constraints
-                            let constraints = MediaConstraints {
-                                audio: offer.media_types.contains(&saorsa_webrtc_core::types::MediaType::Audio),
-                                video: offer.media_types.contains(&saorsa_webrtc_core::types::MediaType::Video),
-                                screen_share: offer.media_types.contains(&saorsa_webrtc_core::types::MediaType::ScreenShare),
-                            };
-                            service.accept_call(offer.call_id, constraints).await?;
+                            service.accept_call(call_id, constraints).await?;
 
                             // Start terminal UI
                             let mut ui = TerminalUI::new(display.into())?;
-                            ui.run(Arc::clone(&service), offer.call_id).await?;
+                            ui.run(Arc::clone(&service), call_id).await?;
                         } else {
                             println!("❌ Rejecting call...");
-                            service.reject_call(offer.call_id).await?;
+                            service.reject_call(call_id).await?;
                         }
                     }
+                    Ok(WebRtcEvent::QualityAlert(alert)) => {
+                        println!("⚠️  Call {}: {}", alert.call_id, alert.message);
+                    }
+                    Ok(WebRtcEvent::RemoteMediaState { call_id, audio_muted, video_enabled }) => {
+                        println!(
+                            "🔈 Call {}: remote audio {}, video {}",
+                            call_id,
+                            if audio_muted { "muted" } else { "on" },
+                            if video_enabled { "on" } else { "off" }
+                        );
+                    }
+                    Ok(WebRtcEvent::CongestionDetected { call_id, severity }) => {
+                        println!("📶 Call {}: connection {:?}", call_id, severity);
+                    }
+                    Ok(WebRtcEvent::Degradation(change)) => {
+                        println!("📉 Call {}: {}", change.call_id, change.message());
+                    }
+                    Ok(WebRtcEvent::EncodeLoad(change)) => {
+                        println!("🐢 Call {}: {}", change.call_id, change.message());
+                    }
+                    Ok(WebRtcEvent::VoicemailReceived(voicemail)) => {
+                        println!(
+                            "📨 Voicemail from {} ({:.1}s)",
+                            voicemail.sender,
+                            voicemail.duration_ms as f64 / 1000.0
+                        );
+                    }
                     Ok(other) => {
                         tracing::debug!("Received event: {:?}", other);
                     }
@@ -222,6 +289,41 @@ async fn handle_listen(
     Ok(())
 }
 
+fn handle_nettest(input: &std::path::Path, sample_rate: u32) -> Result<()> {
+    use saorsa_webrtc_core::mic_test::{analyze, EchoRisk, MicTestConfig};
+
+    println!("🎙️  Microphone self-test: {}", input.display());
+
+    let bytes = std::fs::read(input)?;
+    let samples: Vec<i16> = bytes
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    let report = analyze(&samples, sample_rate, None, &MicTestConfig::default());
+
+    println!("   Duration:    {} ms", report.duration_ms);
+    println!("   Noise floor: {:.1} dBFS", report.noise_floor_dbfs);
+    println!("   Peak level:  {:.1} dBFS", report.peak_dbfs);
+    println!("   Clipping:    {:.2}%", report.clipping_ratio * 100.0);
+    if report.echo_risk != EchoRisk::Unknown {
+        println!("   Echo risk:   {:?}", report.echo_risk);
+    }
+    println!(
+        "   Noise suppression: {}",
+        if report.recommend_noise_suppression { "recommended" } else { "not needed" }
+    );
+
+    if report.recommendations.is_empty() {
+        println!("✅ Microphone looks good");
+    } else {
+        for recommendation in &report.recommendations {
+            println!("⚠️  {}", recommendation);
+        }
+    }
+
+    Ok(())
+}
+
 async fn handle_status() -> Result<()> {
     println!("📊 Saorsa WebRTC CLI Status");
     println!("==========================");
@@ -234,6 +336,7 @@ async fn handle_status() -> Result<()> {
     println!("Available commands:");
     println!("  saorsa call <peer> [options]  - Initiate a call");
     println!("  saorsa listen [options]       - Listen for calls");
+    println!("  saorsa nettest --input <file> - Microphone self-test");
     println!("  saorsa status                 - Show this status");
     println!();
     println!("Use 'saorsa --help' for detailed options");
@@ -22,6 +22,9 @@ use anyhow::Result;
 
 use saorsa_webrtc_core::{prelude::*, types::CallId};
 
+/// How often connection statistics are refreshed
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Display mode for video
 #[derive(Debug, Clone, Copy)]
 pub enum DisplayMode {
@@ -39,6 +42,8 @@ pub struct TerminalUI {
     terminal: Terminal<CrosstermBackend<Stdout>>,
     start_time: Instant,
     stats: ConnectionStats,
+    stats_polled: Option<Instant>,
+    frames_decoded: Option<u64>,
     muted: bool,
     video_enabled: bool,
 }
@@ -65,7 +70,7 @@ impl Default for ConnectionStats {
 }
 
 /// Static UI drawing function for closures
-fn draw_ui_static(f: &mut Frame, display_mode: DisplayMode, stats: ConnectionStats, muted: bool, video_enabled: bool, start_time: Instant) {
+fn draw_ui_static(f: &mut Frame, display_mode: DisplayMode, stats: ConnectionStats, muted: bool, video_enabled: bool, talk: Option<bool>, start_time: Instant) {
     let size = f.size();
 
     // Split the screen vertically
@@ -85,7 +90,7 @@ fn draw_ui_static(f: &mut Frame, display_mode: DisplayMode, stats: ConnectionSta
     draw_stats_area_static(f, chunks[1], stats, start_time);
 
     // Controls area
-    draw_controls_area_static(f, chunks[2], muted, video_enabled);
+    draw_controls_area_static(f, chunks[2], muted, video_enabled, talk);
 }
 
 /// Draw the video display area (static)
@@ -157,25 +162,32 @@ fn draw_stats_area_static(f: &mut Frame, area: Rect, stats: ConnectionStats, sta
 }
 
 /// Draw the controls area (static)
-fn draw_controls_area_static(f: &mut Frame, area: Rect, muted: bool, video_enabled: bool) {
+///
+/// `talk` is whether audio is being sent, or `None` without push-to-talk.
+fn draw_controls_area_static(f: &mut Frame, area: Rect, muted: bool, video_enabled: bool, talk: Option<bool>) {
     let block = Block::default()
         .title("🎮 Controls")
         .borders(Borders::ALL);
 
-    let controls = vec![
-        Line::from(vec![
-            Span::styled("(q/Esc)", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-            Span::raw(" Quit | "),
-            Span::styled("(m)", Style::default().fg(if muted { Color::Red } else { Color::Green })),
-            Span::raw(" Mute | "),
-            Span::styled("(v)", Style::default().fg(if video_enabled { Color::Green } else { Color::Yellow })),
-            Span::raw(" Video | "),
-            Span::styled("(s)", Style::default().fg(Color::Blue)),
-            Span::raw(" Stats | "),
-            Span::styled("(h)", Style::default().fg(Color::Blue)),
-            Span::raw(" Help"),
-        ]),
+    let mut spans = vec![
+        Span::styled("(q/Esc)", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Span::raw(" Quit | "),
+        Span::styled("(m)", Style::default().fg(if muted { Color::Red } else { Color::Green })),
+        Span::raw(" Mute | "),
+        Span::styled("(v)", Style::default().fg(if video_enabled { Color::Green } else { Color::Yellow })),
+        Span::raw(" Video | "),
     ];
+    if let Some(transmitting) = talk {
+        spans.push(Span::styled("(space)", Style::default().fg(if transmitting { Color::Green } else { Color::Red })));
+        spans.push(Span::raw(if transmitting { " Talking | " } else { " Talk | " }));
+    }
+    spans.extend([
+        Span::styled("(s)", Style::default().fg(Color::Blue)),
+        Span::raw(" Stats | "),
+        Span::styled("(h)", Style::default().fg(Color::Blue)),
+        Span::raw(" Help"),
+    ]);
+    let controls = vec![Line::from(spans)];
 
     let paragraph = Paragraph::new(controls).block(block);
     f.render_widget(paragraph, area);
@@ -195,6 +207,8 @@ impl TerminalUI {
             terminal,
             start_time: Instant::now(),
             stats: ConnectionStats::default(),
+            stats_polled: None,
+            frames_decoded: None,
             muted: false,
             video_enabled: true,
         })
@@ -203,9 +217,10 @@ impl TerminalUI {
     /// Run the terminal UI main loop
     pub async fn run(
         &mut self,
-        _service: Arc<WebRtcService<PeerIdentityString, AntQuicTransport>>,
-        _call_id: CallId,
+        service: Arc<WebRtcService<PeerIdentityString, AntQuicTransport>>,
+        call_id: CallId,
     ) -> Result<()> {
+        let talk_switch = service.transmit_switch();
         loop {
             // Handle input
             if event::poll(Duration::from_millis(100))? {
@@ -213,12 +228,23 @@ impl TerminalUI {
                     match key.code {
                         KeyCode::Char('q') | KeyCode::Esc => break,
                         KeyCode::Char('m') => {
-                            self.muted = !self.muted;
-                            // TODO: service.toggle_audio_mute(&call_id).await?;
+                            let muted = !self.muted;
+                            match service.set_audio_muted(call_id, muted).await {
+                                Ok(()) => self.muted = muted,
+                                Err(e) => tracing::warn!("Failed to switch mute: {}", e),
+                            }
                         }
                         KeyCode::Char('v') => {
-                            self.video_enabled = !self.video_enabled;
-                            // TODO: service.toggle_video(&call_id).await?;
+                            let enabled = !self.video_enabled;
+                            match service.set_video_enabled(call_id, enabled).await {
+                                Ok(()) => self.video_enabled = enabled,
+                                Err(e) => tracing::warn!("Failed to switch video: {}", e),
+                            }
+                        }
+                        KeyCode::Char(' ') if talk_switch.is_push_to_talk() => {
+                            // Terminals rarely report key releases, so the
+                            // talk key latches instead of being held
+                            talk_switch.set_transmit(!talk_switch.is_transmitting());
                         }
                         KeyCode::Char('s') => {
                             // Show detailed stats
@@ -232,16 +258,19 @@ impl TerminalUI {
             }
 
             // Update stats
-            self.update_stats().await;
+            self.update_stats(&service, call_id).await;
 
             // Render UI
             let stats = self.stats.clone();
             let muted = self.muted;
             let video_enabled = self.video_enabled;
+            let talk = talk_switch
+                .is_push_to_talk()
+                .then(|| talk_switch.is_transmitting());
             let start_time = self.start_time;
             let display_mode = self.display_mode;
             self.terminal.draw(|f| {
-                draw_ui_static(f, display_mode, stats.clone(), muted, video_enabled, start_time)
+                draw_ui_static(f, display_mode, stats.clone(), muted, video_enabled, talk, start_time)
             })?;
 
             // Small delay to prevent excessive CPU usage
@@ -251,18 +280,43 @@ impl TerminalUI {
         Ok(())
     }
 
-    /// Update connection statistics
-    async fn update_stats(&mut self) {
-        // TODO: Get real stats from the service
-        // For now, simulate some stats
-        let elapsed = self.start_time.elapsed().as_secs();
+    /// Refresh connection statistics from the service, once a second
+    async fn update_stats(
+        &mut self,
+        service: &WebRtcService<PeerIdentityString, AntQuicTransport>,
+        call_id: CallId,
+    ) {
+        let now = Instant::now();
+        if self.stats_polled.is_some_and(|at| now.duration_since(at) < STATS_INTERVAL) {
+            return;
+        }
+        let stats = match service.get_call_stats(call_id).await {
+            Ok(stats) => stats,
+            Err(e) => {
+                tracing::debug!("No stats for call {}: {}", call_id, e);
+                return;
+            }
+        };
+
+        // Frame rate of remote video since the last refresh
+        let frames_decoded = stats.video.map(|video| video.frames_decoded);
+        let fps = match (self.stats_polled, self.frames_decoded, frames_decoded) {
+            (Some(at), Some(before), Some(after)) => {
+                let secs = now.duration_since(at).as_secs_f64();
+                Some((after.saturating_sub(before) as f64 / secs).round() as u32)
+            }
+            _ => None,
+        };
+        self.stats_polled = Some(now);
+        self.frames_decoded = frames_decoded;
 
+        let count = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
         self.stats = ConnectionStats {
-            rtt_ms: Some(23 + (elapsed % 10) as u32),
-            bitrate_kbps: Some(1500 + (elapsed % 500) as u32),
-            fps: Some(30),
-            packets_lost: Some((elapsed / 10) as u32),
-            packets_sent: Some((elapsed * 100) as u32),
+            rtt_ms: stats.transport.rtt_ms,
+            bitrate_kbps: stats.send_bitrate_kbps,
+            fps,
+            packets_lost: Some(count(stats.transport.packets_lost)),
+            packets_sent: Some(count(stats.transport.packets_sent)),
         };
     }
 
@@ -0,0 +1,610 @@
+//! AV1 codec implementation
+//!
+//! With the `av1` feature frames are encoded by rav1e and decoded by dav1d.
+//! AV1's palette and intra block copy tools make it far better than H.264
+//! at text-heavy desktop capture, so screen shares should use
+//! [`Av1EncoderConfig::screen_share`]. Each temporal unit carries the frame
+//! timestamp in an unregistered metadata OBU. Without the feature the
+//! constructors return [`CodecError::NotImplemented`].
+
+use crate::yuv::YuvFrame;
+use crate::{
+    CodecError, EncoderPreset, Result, VideoDecoder, VideoEncoder, VideoFrame, TIMESTAMP_UUID,
+};
+use crate::{MAX_HEIGHT, MAX_RGB_SIZE, MAX_WIDTH};
+use bytes::Bytes;
+
+/// OBU types
+const OBU_FRAME_HEADER: u8 = 3;
+const OBU_METADATA: u8 = 5;
+const OBU_FRAME: u8 = 6;
+
+/// First metadata type reserved for unregistered user private data
+const METADATA_TYPE_USER_PRIVATE: u8 = 6;
+
+/// What the encoder is tuned for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Av1ContentMode {
+    /// Camera video: perceptual tuning
+    Camera,
+    /// Desktop capture: palette coding and sharp text over smooth motion
+    Screen,
+}
+
+/// AV1 encoder configuration
+#[derive(Debug, Clone)]
+pub struct Av1EncoderConfig {
+    pub width: u32,
+    pub height: u32,
+    pub content: Av1ContentMode,
+    /// Target bitrate in bits per second
+    pub bitrate_bps: u32,
+    pub frame_rate: u32,
+    /// Speed preset from 0 (slowest) to 10 (fastest)
+    pub speed: u8,
+    /// Maximum frames between keyframes
+    pub keyframe_interval: u64,
+    /// Latency and quality trade-off
+    pub preset: EncoderPreset,
+}
+
+impl Default for Av1EncoderConfig {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+            content: Av1ContentMode::Camera,
+            bitrate_bps: 1_000_000,
+            frame_rate: 30,
+            speed: 10,
+            keyframe_interval: 240,
+            preset: EncoderPreset::RealtimeLowLatency,
+        }
+    }
+}
+
+impl Av1EncoderConfig {
+    /// Settings tuned for screen sharing
+    ///
+    /// Desktop content changes rarely but must stay legible, so it gets a
+    /// lower frame rate, more bits per frame and sparse keyframes.
+    pub fn screen_share(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            content: Av1ContentMode::Screen,
+            bitrate_bps: 1_500_000,
+            frame_rate: 15,
+            keyframe_interval: 600,
+            ..Default::default()
+        }
+    }
+}
+
+/// AV1 video encoder
+pub struct Av1Encoder {
+    config: Av1EncoderConfig,
+    pending_keyframe: bool,
+    // The rav1e context is not Sync; the mutex lets the encoder satisfy
+    // `VideoEncoder` without unsafe code
+    #[cfg(feature = "av1")]
+    inner: std::sync::Mutex<native::Encoder>,
+}
+
+impl Av1Encoder {
+    pub fn new() -> Result<Self> {
+        Self::with_config(Av1EncoderConfig::default())
+    }
+
+    pub fn with_config(config: Av1EncoderConfig) -> Result<Self> {
+        let (width, height) = (config.width, config.height);
+        if width == 0 || height == 0 || width > MAX_WIDTH || height > MAX_HEIGHT {
+            return Err(CodecError::InvalidDimensions(width, height));
+        }
+        let rgb_size = width
+            .checked_mul(height)
+            .and_then(|px| px.checked_mul(3))
+            .ok_or(CodecError::Overflow)?;
+        if rgb_size as usize > MAX_RGB_SIZE {
+            return Err(CodecError::SizeExceeded {
+                actual: rgb_size as usize,
+                max: MAX_RGB_SIZE,
+            });
+        }
+        if config.bitrate_bps == 0 || config.frame_rate == 0 {
+            return Err(CodecError::InvalidData(
+                "bitrate and frame rate must be positive",
+            ));
+        }
+        if config.speed > 10 {
+            return Err(CodecError::InvalidData("speed preset must be 0-10"));
+        }
+
+        #[cfg(feature = "av1")]
+        {
+            Ok(Self {
+                inner: std::sync::Mutex::new(native::Encoder::new(&config)?),
+                config,
+                pending_keyframe: false,
+            })
+        }
+        #[cfg(not(feature = "av1"))]
+        {
+            Err(CodecError::NotImplemented("av1 feature is disabled"))
+        }
+    }
+
+    /// Encoder configuration
+    pub fn config(&self) -> &Av1EncoderConfig {
+        &self.config
+    }
+}
+
+impl VideoEncoder for Av1Encoder {
+    /// Encode a frame
+    ///
+    /// The encoder may hold frames back briefly; an empty payload means
+    /// nothing is ready to send yet.
+    fn encode(&mut self, frame: &VideoFrame) -> Result<Bytes> {
+        if frame.width != self.config.width || frame.height != self.config.height {
+            return Err(CodecError::DimensionMismatch {
+                frame_width: frame.width,
+                frame_height: frame.height,
+                cfg_width: self.config.width,
+                cfg_height: self.config.height,
+            });
+        }
+
+        let encoded = self.encode_frame(frame)?;
+        self.pending_keyframe = false;
+        Ok(encoded)
+    }
+
+    fn request_keyframe(&mut self) {
+        self.pending_keyframe = true;
+    }
+}
+
+impl Av1Encoder {
+    #[cfg(feature = "av1")]
+    fn encode_frame(&mut self, frame: &VideoFrame) -> Result<Bytes> {
+        let yuv = YuvFrame::from_rgb(frame)?;
+        let mut encoder = self
+            .inner
+            .lock()
+            .map_err(|_| CodecError::EncodingFailed("encoder lock poisoned".to_string()))?;
+        let packets = encoder.encode(&yuv, self.pending_keyframe)?;
+
+        let mut out = Vec::new();
+        for (data, timestamp) in packets {
+            out.extend(insert_timestamp_metadata(data, timestamp));
+        }
+        Ok(Bytes::from(out))
+    }
+
+    #[cfg(not(feature = "av1"))]
+    fn encode_frame(&mut self, _frame: &VideoFrame) -> Result<Bytes> {
+        Err(CodecError::NotImplemented("av1 feature is disabled"))
+    }
+}
+
+/// AV1 video decoder
+pub struct Av1Decoder {
+    #[cfg(feature = "av1")]
+    inner: std::sync::Mutex<::dav1d::Decoder>,
+}
+
+impl Av1Decoder {
+    pub fn new() -> Result<Self> {
+        #[cfg(feature = "av1")]
+        {
+            let mut settings = ::dav1d::Settings::new();
+            // Return each picture as soon as its temporal unit arrives
+            settings.set_max_frame_delay(1);
+            let decoder = ::dav1d::Decoder::with_settings(&settings)
+                .map_err(|e| CodecError::InitFailed(e.to_string()))?;
+            Ok(Self {
+                inner: std::sync::Mutex::new(decoder),
+            })
+        }
+        #[cfg(not(feature = "av1"))]
+        {
+            Err(CodecError::NotImplemented("av1 feature is disabled"))
+        }
+    }
+
+    /// Decode a temporal unit to planar YUV 4:2:0
+    ///
+    /// If `data` holds several temporal units the last picture is returned.
+    #[cfg(feature = "av1")]
+    pub fn decode_yuv(&mut self, data: &[u8]) -> Result<YuvFrame> {
+        let timestamp = read_timestamp_metadata(data).unwrap_or(0);
+        let mut decoder = self
+            .inner
+            .lock()
+            .map_err(|_| CodecError::DecodingFailed("decoder lock poisoned".to_string()))?;
+        let picture = native::decode(&mut decoder, data)?;
+        native::to_yuv_frame(&picture, timestamp)
+    }
+
+    /// Decode a temporal unit to planar YUV 4:2:0
+    #[cfg(not(feature = "av1"))]
+    pub fn decode_yuv(&mut self, _data: &[u8]) -> Result<YuvFrame> {
+        Err(CodecError::NotImplemented("av1 feature is disabled"))
+    }
+}
+
+impl VideoDecoder for Av1Decoder {
+    fn decode(&mut self, data: &[u8]) -> Result<VideoFrame> {
+        self.decode_yuv(data)?.to_rgb()
+    }
+}
+
+#[cfg(feature = "av1")]
+mod native {
+    use super::{Av1ContentMode, Av1EncoderConfig};
+    use crate::yuv::{copy_plane, YuvFrame};
+    use crate::{CodecError, Result};
+    use ::dav1d::{PixelLayout, PlanarImageComponent};
+    use ::rav1e::prelude::{
+        ChromaSampling, Config, Context, EncoderConfig, EncoderStatus, FrameParameters,
+        FrameTypeOverride, Rational, SpeedSettings, Tune,
+    };
+    use std::collections::HashMap;
+
+    pub(super) struct Encoder {
+        context: Context<u8>,
+        next_frameno: u64,
+        /// Timestamps of frames still inside the encoder, by input frame
+        timestamps: HashMap<u64, u64>,
+    }
+
+    impl Encoder {
+        pub(super) fn new(config: &Av1EncoderConfig) -> Result<Self> {
+            let mut encoder = EncoderConfig::with_speed_preset(config.speed);
+            encoder.width = config.width as usize;
+            encoder.height = config.height as usize;
+            encoder.bit_depth = 8;
+            encoder.chroma_sampling = ChromaSampling::Cs420;
+            encoder.time_base = Rational::new(1, u64::from(config.frame_rate));
+            encoder.bitrate = i32::try_from(config.bitrate_bps).unwrap_or(i32::MAX);
+            encoder.max_key_frame_interval = config.keyframe_interval;
+            // Low latency mode disables frame reordering, rav1e's B-frames;
+            // the smallest reservoir holds the bitrate close to constant
+            let preset = config.preset;
+            encoder.low_latency = preset.is_realtime();
+            if preset.is_realtime() {
+                encoder.reservoir_frame_delay = Some(12);
+            }
+            encoder.speed_settings = SpeedSettings::from_preset(config.speed);
+            encoder.speed_settings.rdo_lookahead_frames = preset.lookahead_frames().max(1) as usize;
+            match config.content {
+                Av1ContentMode::Camera => encoder.tune = Tune::Psychovisual,
+                Av1ContentMode::Screen => {
+                    // Palette coding suits the few flat colours of UI and text,
+                    // and PSNR tuning keeps glyph edges sharp
+                    encoder.tune = Tune::Psnr;
+                    encoder.speed_settings.palette = true;
+                }
+            }
+
+            let context = Config::new()
+                .with_encoder_config(encoder)
+                .new_context()
+                .map_err(|e| CodecError::InitFailed(e.to_string()))?;
+            Ok(Self {
+                context,
+                next_frameno: 0,
+                timestamps: HashMap::new(),
+            })
+        }
+
+        /// Submit a frame and collect `(packet, timestamp)` pairs now ready
+        pub(super) fn encode(
+            &mut self,
+            yuv: &YuvFrame,
+            keyframe: bool,
+        ) -> Result<Vec<(Vec<u8>, u64)>> {
+            let (chroma_width, _) = yuv.chroma_dimensions();
+            let mut frame = self.context.new_frame();
+            frame.planes[0].copy_from_raw_u8(&yuv.y, yuv.width as usize, 1);
+            frame.planes[1].copy_from_raw_u8(&yuv.u, chroma_width, 1);
+            frame.planes[2].copy_from_raw_u8(&yuv.v, chroma_width, 1);
+
+            let parameters = FrameParameters {
+                frame_type_override: if keyframe {
+                    FrameTypeOverride::Key
+                } else {
+                    FrameTypeOverride::No
+                },
+                ..Default::default()
+            };
+            self.context
+                .send_frame((frame, parameters))
+                .map_err(|e| CodecError::EncodingFailed(e.to_string()))?;
+            self.timestamps.insert(self.next_frameno, yuv.timestamp);
+            self.next_frameno += 1;
+
+            let mut packets = Vec::new();
+            loop {
+                match self.context.receive_packet() {
+                    Ok(packet) => {
+                        let timestamp = self
+                            .timestamps
+                            .remove(&packet.input_frameno)
+                            .unwrap_or_default();
+                        packets.push((packet.data, timestamp));
+                    }
+                    Err(EncoderStatus::Encoded) => continue,
+                    Err(EncoderStatus::NeedMoreData) => break,
+                    Err(e) => return Err(CodecError::EncodingFailed(e.to_string())),
+                }
+            }
+            Ok(packets)
+        }
+    }
+
+    /// Feed a temporal unit to dav1d and return the newest picture
+    pub(super) fn decode(decoder: &mut ::dav1d::Decoder, data: &[u8]) -> Result<::dav1d::Picture> {
+        let mut sent = decoder.send_data(data.to_vec(), None, None, None);
+        let mut latest = None;
+        loop {
+            match sent {
+                Ok(()) | Err(::dav1d::Error::Again) => {}
+                Err(e) => return Err(CodecError::DecodingFailed(e.to_string())),
+            }
+            match decoder.get_picture() {
+                Ok(picture) => latest = Some(picture),
+                Err(::dav1d::Error::Again) if matches!(sent, Err(::dav1d::Error::Again)) => {
+                    // Input was only partly consumed; push the rest
+                    sent = decoder.send_pending_data();
+                }
+                Err(::dav1d::Error::Again) => break,
+                Err(e) => return Err(CodecError::DecodingFailed(e.to_string())),
+            }
+        }
+        latest.ok_or(CodecError::InvalidData("no complete frame in bitstream"))
+    }
+
+    pub(super) fn to_yuv_frame(picture: &::dav1d::Picture, timestamp: u64) -> Result<YuvFrame> {
+        if picture.bit_depth() != 8 {
+            return Err(CodecError::NotImplemented("only 8-bit AV1 is supported"));
+        }
+        if picture.pixel_layout() != PixelLayout::I420 {
+            return Err(CodecError::NotImplemented("only 4:2:0 AV1 is supported"));
+        }
+
+        let (width, height) = (picture.width(), picture.height());
+        let (luma_width, luma_height) = (width as usize, height as usize);
+        let (chroma_width, chroma_height) = (luma_width.div_ceil(2), luma_height.div_ceil(2));
+        let plane = |component: PlanarImageComponent, w: usize, h: usize| {
+            copy_plane(
+                &picture.plane(component),
+                picture.stride(component) as usize,
+                w,
+                h,
+            )
+        };
+        Ok(YuvFrame {
+            width,
+            height,
+            y: plane(PlanarImageComponent::Y, luma_width, luma_height)?,
+            u: plane(PlanarImageComponent::U, chroma_width, chroma_height)?,
+            v: plane(PlanarImageComponent::V, chroma_width, chroma_height)?,
+            timestamp,
+        })
+    }
+}
+
+/// Read an unsigned LEB128 value, returning it and its encoded length
+fn read_leb128(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(8).enumerate() {
+        value |= u64::from(byte & 0x7F) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// OBUs in a temporal unit as `(start, obu_type, payload)`
+fn obus(data: &[u8]) -> Vec<(usize, u8, &[u8])> {
+    let mut units = Vec::new();
+    let mut pos = 0;
+    while let Some(&header) = data.get(pos) {
+        let obu_type = (header >> 3) & 0x0F;
+        let has_extension = header & 0x04 != 0;
+        let has_size = header & 0x02 != 0;
+        let mut offset = pos + 1 + usize::from(has_extension);
+        let size = if has_size {
+            let Some((size, len)) = data.get(offset..).and_then(read_leb128) else {
+                break;
+            };
+            offset += len;
+            usize::try_from(size).unwrap_or(usize::MAX)
+        } else {
+            data.len().saturating_sub(offset)
+        };
+        let Some(payload) = offset
+            .checked_add(size)
+            .and_then(|end| data.get(offset..end))
+        else {
+            break;
+        };
+        units.push((pos, obu_type, payload));
+        pos = offset + size;
+    }
+    units
+}
+
+/// Build a metadata OBU carrying `timestamp`
+fn timestamp_metadata(timestamp: u64) -> Vec<u8> {
+    let mut payload = vec![METADATA_TYPE_USER_PRIVATE];
+    payload.extend_from_slice(&TIMESTAMP_UUID);
+    payload.extend_from_slice(&timestamp.to_be_bytes());
+    // trailing_bits
+    payload.push(0x80);
+
+    // obu_has_size_field set; the payload is short enough for one LEB128 byte
+    let mut obu = vec![(OBU_METADATA << 3) | 0x02, payload.len() as u8];
+    obu.extend(payload);
+    obu
+}
+
+/// Insert the timestamp metadata before the first frame of a temporal unit
+#[cfg_attr(not(feature = "av1"), allow(dead_code))]
+fn insert_timestamp_metadata(mut data: Vec<u8>, timestamp: u64) -> Vec<u8> {
+    let position = obus(&data)
+        .into_iter()
+        .find(|&(_, obu_type, _)| matches!(obu_type, OBU_FRAME_HEADER | OBU_FRAME))
+        .map_or(data.len(), |(start, _, _)| start);
+    data.splice(position..position, timestamp_metadata(timestamp));
+    data
+}
+
+/// Read the timestamp of the last temporal unit that carries one
+#[cfg_attr(not(feature = "av1"), allow(dead_code))]
+fn read_timestamp_metadata(data: &[u8]) -> Option<u64> {
+    obus(data)
+        .into_iter()
+        .rev()
+        .filter(|&(_, obu_type, _)| obu_type == OBU_METADATA)
+        .find_map(|(_, _, payload)| {
+            if payload.first() != Some(&METADATA_TYPE_USER_PRIVATE)
+                || payload.get(1..17)? != TIMESTAMP_UUID
+            {
+                return None;
+            }
+            Some(u64::from_be_bytes(payload.get(17..25)?.try_into().ok()?))
+        })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    /// Temporal delimiter, sequence header and frame OBUs with dummy payloads
+    fn temporal_unit() -> Vec<u8> {
+        vec![
+            0x12, 0x00, 0x0A, 0x02, 0xAA, 0xBB, 0x32, 0x03, 0x01, 0x02, 0x03,
+        ]
+    }
+
+    #[test]
+    fn test_timestamp_metadata_roundtrip() {
+        let tagged = insert_timestamp_metadata(temporal_unit(), u64::MAX - 7);
+        assert_eq!(read_timestamp_metadata(&tagged), Some(u64::MAX - 7));
+        assert_eq!(read_timestamp_metadata(&temporal_unit()), None);
+
+        let types: Vec<u8> = obus(&tagged).iter().map(|&(_, t, _)| t).collect();
+        assert_eq!(types, vec![2, 1, OBU_METADATA, OBU_FRAME]);
+    }
+
+    #[test]
+    fn test_last_timestamp_wins() {
+        let mut data = insert_timestamp_metadata(temporal_unit(), 1);
+        data.extend(insert_timestamp_metadata(temporal_unit(), 2));
+        assert_eq!(read_timestamp_metadata(&data), Some(2));
+    }
+
+    #[test]
+    fn test_truncated_obus_ignored() {
+        let mut tagged = insert_timestamp_metadata(temporal_unit(), 9);
+        tagged.truncate(10);
+        assert_eq!(read_timestamp_metadata(&tagged), None);
+        assert_eq!(read_timestamp_metadata(&[0x2A, 0xFF]), None);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        for config in [
+            Av1EncoderConfig {
+                width: 0,
+                ..Default::default()
+            },
+            Av1EncoderConfig {
+                frame_rate: 0,
+                ..Default::default()
+            },
+            Av1EncoderConfig {
+                speed: 11,
+                ..Default::default()
+            },
+        ] {
+            assert!(Av1Encoder::with_config(config).is_err());
+        }
+
+        let screen = Av1EncoderConfig::screen_share(1920, 1080);
+        assert_eq!(screen.content, Av1ContentMode::Screen);
+        assert!(screen.frame_rate < Av1EncoderConfig::default().frame_rate);
+    }
+
+    #[cfg(not(feature = "av1"))]
+    #[test]
+    fn test_disabled_without_feature() {
+        assert!(matches!(
+            Av1Encoder::new(),
+            Err(CodecError::NotImplemented(_))
+        ));
+        assert!(matches!(
+            Av1Decoder::new(),
+            Err(CodecError::NotImplemented(_))
+        ));
+    }
+
+    #[cfg(feature = "av1")]
+    fn frames(width: u32, height: u32, count: u64) -> impl Iterator<Item = VideoFrame> {
+        (0..count).map(move |i| VideoFrame {
+            data: (0..width * height * 3)
+                .map(|p| ((p as u64 + i * 7) % 251) as u8)
+                .collect(),
+            width,
+            height,
+            timestamp: 1000 + i * 33,
+        })
+    }
+
+    #[cfg(feature = "av1")]
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let mut encoder = Av1Encoder::with_config(Av1EncoderConfig {
+            width: 64,
+            height: 48,
+            ..Default::default()
+        })
+        .unwrap();
+        let mut decoder = Av1Decoder::new().unwrap();
+
+        let mut decoded = Vec::new();
+        for frame in frames(64, 48, 6) {
+            let encoded = encoder.encode(&frame).unwrap();
+            if !encoded.is_empty() {
+                decoded.push(decoder.decode(&encoded).unwrap());
+            }
+        }
+
+        assert!(!decoded.is_empty());
+        for frame in &decoded {
+            assert_eq!((frame.width, frame.height), (64, 48));
+            assert_eq!(frame.data.len(), 64 * 48 * 3);
+            assert!(frame.timestamp >= 1000);
+        }
+        // Timestamps come out in order
+        assert!(decoded.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+    }
+
+    #[cfg(feature = "av1")]
+    #[test]
+    fn test_screen_share_encodes() {
+        let mut encoder = Av1Encoder::with_config(Av1EncoderConfig::screen_share(64, 64)).unwrap();
+        encoder.request_keyframe();
+        let mut produced = 0;
+        for frame in frames(64, 64, 4) {
+            produced += encoder.encode(&frame).unwrap().len();
+        }
+        assert!(produced > 0);
+        assert!(!encoder.pending_keyframe);
+    }
+}
@@ -0,0 +1,451 @@
+//! Pixel format conversion, scaling and cropping
+//!
+//! Capture hands out packed RGB, RGBA or BGRA; encoders take I420, and
+//! hardware encoders often prefer NV12. Conversions use the same BT.601
+//! limited range fixed-point arithmetic as [`YuvFrame`].
+//!
+//! Inner loops walk whole rows in fixed-size pixel chunks, with the pixel
+//! layout known at compile time, so the compiler vectorizes them. There is
+//! no hand-written SIMD: that would need `unsafe`, which this crate forbids.
+
+use crate::yuv::{chroma_dimensions, YuvFrame};
+use crate::{CodecError, Result, MAX_HEIGHT, MAX_WIDTH};
+
+/// Packed pixel layouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackedFormat {
+    /// 8-bit red, green, blue
+    Rgb24,
+    /// 8-bit red, green, blue, alpha
+    Rgba32,
+    /// 8-bit blue, green, red, alpha
+    Bgra32,
+}
+
+impl PackedFormat {
+    /// Bytes per pixel
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgb24 => 3,
+            Self::Rgba32 | Self::Bgra32 => 4,
+        }
+    }
+}
+
+/// Compile-time byte layout of a packed format
+trait Layout {
+    const BPP: usize;
+    const R: usize;
+    const G: usize;
+    const B: usize;
+    /// Alpha offset, written opaque; `None` for formats without alpha
+    const A: Option<usize>;
+}
+
+struct Rgb;
+struct Rgba;
+struct Bgra;
+
+impl Layout for Rgb {
+    const BPP: usize = 3;
+    const R: usize = 0;
+    const G: usize = 1;
+    const B: usize = 2;
+    const A: Option<usize> = None;
+}
+
+impl Layout for Rgba {
+    const BPP: usize = 4;
+    const R: usize = 0;
+    const G: usize = 1;
+    const B: usize = 2;
+    const A: Option<usize> = Some(3);
+}
+
+impl Layout for Bgra {
+    const BPP: usize = 4;
+    const R: usize = 2;
+    const G: usize = 1;
+    const B: usize = 0;
+    const A: Option<usize> = Some(3);
+}
+
+/// Semi-planar YUV 4:2:0 (NV12) frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nv12Frame {
+    pub width: u32,
+    pub height: u32,
+    /// Luma plane, `width * height` bytes
+    pub y: Vec<u8>,
+    /// Interleaved Cb/Cr pairs, `2 * ceil(width / 2) * ceil(height / 2)` bytes
+    pub uv: Vec<u8>,
+    pub timestamp: u64,
+}
+
+impl Nv12Frame {
+    /// Interleave the chroma planes of an I420 frame
+    pub fn from_i420(frame: &YuvFrame) -> Result<Self> {
+        frame.validate()?;
+        let uv = frame
+            .u
+            .iter()
+            .zip(&frame.v)
+            .flat_map(|(&u, &v)| [u, v])
+            .collect();
+        Ok(Self {
+            width: frame.width,
+            height: frame.height,
+            y: frame.y.clone(),
+            uv,
+            timestamp: frame.timestamp,
+        })
+    }
+
+    /// Split the chroma pairs back into I420 planes
+    pub fn to_i420(&self) -> Result<YuvFrame> {
+        let (cw, ch) = chroma_dimensions(self.width, self.height);
+        if self.y.len() != self.width as usize * self.height as usize
+            || self.uv.len() != 2 * cw * ch
+        {
+            return Err(CodecError::InvalidData(
+                "nv12 planes do not match dimensions",
+            ));
+        }
+        let (u, v) = self
+            .uv
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .unzip();
+        Ok(YuvFrame {
+            width: self.width,
+            height: self.height,
+            y: self.y.clone(),
+            u,
+            v,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
+/// Convert packed pixels to I420, averaging chroma over each 2x2 block
+pub fn packed_to_i420(
+    data: &[u8],
+    format: PackedFormat,
+    width: u32,
+    height: u32,
+    timestamp: u64,
+) -> Result<YuvFrame> {
+    match format {
+        PackedFormat::Rgb24 => to_i420::<Rgb>(data, width, height, timestamp),
+        PackedFormat::Rgba32 => to_i420::<Rgba>(data, width, height, timestamp),
+        PackedFormat::Bgra32 => to_i420::<Bgra>(data, width, height, timestamp),
+    }
+}
+
+/// Convert an I420 frame to packed pixels, with opaque alpha
+pub fn i420_to_packed(frame: &YuvFrame, format: PackedFormat) -> Result<Vec<u8>> {
+    frame.validate()?;
+    Ok(match format {
+        PackedFormat::Rgb24 => from_i420::<Rgb>(frame),
+        PackedFormat::Rgba32 => from_i420::<Rgba>(frame),
+        PackedFormat::Bgra32 => from_i420::<Bgra>(frame),
+    })
+}
+
+/// Cut out a `width` x `height` region whose top-left corner is at `x, y`
+///
+/// The corner must be on even coordinates so chroma samples stay aligned.
+pub fn crop(frame: &YuvFrame, x: u32, y: u32, width: u32, height: u32) -> Result<YuvFrame> {
+    frame.validate()?;
+    check_dimensions(width, height)?;
+    let fits = |origin: u32, size: u32, limit: u32| {
+        origin.checked_add(size).is_some_and(|end| end <= limit)
+    };
+    if !fits(x, width, frame.width) || !fits(y, height, frame.height) {
+        return Err(CodecError::InvalidDimensions(width, height));
+    }
+    if !x.is_multiple_of(2) || !y.is_multiple_of(2) {
+        return Err(CodecError::InvalidData("crop origin must be even"));
+    }
+
+    let (x, y, w, h) = (x as usize, y as usize, width as usize, height as usize);
+    let (src_cw, _) = frame.chroma_dimensions();
+    let (cw, ch) = chroma_dimensions(width, height);
+    Ok(YuvFrame {
+        width,
+        height,
+        y: crop_plane(&frame.y, frame.width as usize, x, y, w, h),
+        u: crop_plane(&frame.u, src_cw, x / 2, y / 2, cw, ch),
+        v: crop_plane(&frame.v, src_cw, x / 2, y / 2, cw, ch),
+        timestamp: frame.timestamp,
+    })
+}
+
+/// Resize to `width` x `height` with bilinear filtering
+///
+/// Bilinear filtering aliases when shrinking by more than half; scale in
+/// steps for large reductions.
+pub fn scale(frame: &YuvFrame, width: u32, height: u32) -> Result<YuvFrame> {
+    frame.validate()?;
+    check_dimensions(width, height)?;
+    if (width, height) == (frame.width, frame.height) {
+        return Ok(frame.clone());
+    }
+
+    let src = (frame.width as usize, frame.height as usize);
+    let src_chroma = frame.chroma_dimensions();
+    let dst = (width as usize, height as usize);
+    let dst_chroma = chroma_dimensions(width, height);
+    Ok(YuvFrame {
+        width,
+        height,
+        y: scale_plane(&frame.y, src, dst),
+        u: scale_plane(&frame.u, src_chroma, dst_chroma),
+        v: scale_plane(&frame.v, src_chroma, dst_chroma),
+        timestamp: frame.timestamp,
+    })
+}
+
+fn to_i420<L: Layout>(data: &[u8], width: u32, height: u32, timestamp: u64) -> Result<YuvFrame> {
+    let (w, h) = (width as usize, height as usize);
+    let stride = w.checked_mul(L::BPP).ok_or(CodecError::Overflow)?;
+    let expected = stride.checked_mul(h).ok_or(CodecError::Overflow)?;
+    if w == 0 || h == 0 {
+        return Err(CodecError::InvalidDimensions(width, height));
+    }
+    if data.len() != expected {
+        return Err(CodecError::InvalidData(
+            "pixel data does not match dimensions",
+        ));
+    }
+
+    let (cw, ch) = chroma_dimensions(width, height);
+    let mut y = vec![0u8; w * h];
+    let mut u = vec![0u8; cw * ch];
+    let mut v = vec![0u8; cw * ch];
+
+    for (src, dst) in data.chunks_exact(stride).zip(y.chunks_exact_mut(w)) {
+        for (px, luma) in src.chunks_exact(L::BPP).zip(dst) {
+            let (r, g, b) = rgb::<L>(px);
+            *luma = clamp(((66 * r + 129 * g + 25 * b + 128) >> 8) + 16);
+        }
+    }
+
+    // Odd edges reuse the last row or column, which averages the same as
+    // leaving the missing pixels out
+    let rows: Vec<&[u8]> = data.chunks_exact(stride).collect();
+    for (crow, (u_row, v_row)) in u
+        .chunks_exact_mut(cw)
+        .zip(v.chunks_exact_mut(cw))
+        .enumerate()
+    {
+        let top = rows[crow * 2];
+        let bottom = rows.get(crow * 2 + 1).copied().unwrap_or(top);
+        let blocks = top.chunks(2 * L::BPP).zip(bottom.chunks(2 * L::BPP));
+        for ((top, bottom), (cb, cr)) in blocks.zip(u_row.iter_mut().zip(v_row.iter_mut())) {
+            let right = if top.len() == 2 * L::BPP { L::BPP } else { 0 };
+            let (r0, g0, b0) = rgb::<L>(top);
+            let (r1, g1, b1) = rgb::<L>(&top[right..]);
+            let (r2, g2, b2) = rgb::<L>(bottom);
+            let (r3, g3, b3) = rgb::<L>(&bottom[right..]);
+            let r = (r0 + r1 + r2 + r3) / 4;
+            let g = (g0 + g1 + g2 + g3) / 4;
+            let b = (b0 + b1 + b2 + b3) / 4;
+            *cb = clamp(((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128);
+            *cr = clamp(((112 * r - 94 * g - 18 * b + 128) >> 8) + 128);
+        }
+    }
+
+    Ok(YuvFrame {
+        width,
+        height,
+        y,
+        u,
+        v,
+        timestamp,
+    })
+}
+
+fn from_i420<L: Layout>(frame: &YuvFrame) -> Vec<u8> {
+    let w = frame.width as usize;
+    let (cw, _) = frame.chroma_dimensions();
+    let mut data = vec![0u8; w * frame.height as usize * L::BPP];
+
+    for (row, (dst, y_row)) in data
+        .chunks_exact_mut(w * L::BPP)
+        .zip(frame.y.chunks_exact(w))
+        .enumerate()
+    {
+        let u_row = &frame.u[(row / 2) * cw..][..cw];
+        let v_row = &frame.v[(row / 2) * cw..][..cw];
+        for (col, (px, &luma)) in dst.chunks_exact_mut(L::BPP).zip(y_row).enumerate() {
+            let c = i32::from(luma) - 16;
+            let d = i32::from(u_row[col / 2]) - 128;
+            let e = i32::from(v_row[col / 2]) - 128;
+            px[L::R] = clamp((298 * c + 409 * e + 128) >> 8);
+            px[L::G] = clamp((298 * c - 100 * d - 208 * e + 128) >> 8);
+            px[L::B] = clamp((298 * c + 516 * d + 128) >> 8);
+            if let Some(a) = L::A {
+                px[a] = 255;
+            }
+        }
+    }
+    data
+}
+
+fn crop_plane(plane: &[u8], stride: usize, x: usize, y: usize, w: usize, h: usize) -> Vec<u8> {
+    plane
+        .chunks_exact(stride)
+        .skip(y)
+        .take(h)
+        .flat_map(|row| &row[x..x + w])
+        .copied()
+        .collect()
+}
+
+/// Source sample positions for each destination index, in 1/256ths
+///
+/// Pixel centres are aligned, so edges do not shift when scaling.
+fn sample_positions(src: usize, dst: usize) -> Vec<(usize, usize, i32)> {
+    let (src, dst) = (src as i64, dst as i64);
+    let max = (src - 1) << 8;
+    (0..dst)
+        .map(|i| {
+            let pos = ((((2 * i + 1) * src) << 8) / (2 * dst) - 128).clamp(0, max);
+            let first = (pos >> 8) as usize;
+            let second = (first + 1).min(src as usize - 1);
+            (first, second, (pos & 0xff) as i32)
+        })
+        .collect()
+}
+
+fn scale_plane(src: &[u8], (sw, sh): (usize, usize), (dw, dh): (usize, usize)) -> Vec<u8> {
+    let columns = sample_positions(sw, dw);
+    let rows = sample_positions(sh, dh);
+    let mut out = vec![0u8; dw * dh];
+
+    for (dst, &(y0, y1, fy)) in out.chunks_exact_mut(dw).zip(&rows) {
+        let top = &src[y0 * sw..][..sw];
+        let bottom = &src[y1 * sw..][..sw];
+        for (px, &(x0, x1, fx)) in dst.iter_mut().zip(&columns) {
+            let lerp = |row: &[u8]| i32::from(row[x0]) * (256 - fx) + i32::from(row[x1]) * fx;
+            let value = lerp(top) * (256 - fy) + lerp(bottom) * fy;
+            *px = clamp((value + (1 << 15)) >> 16);
+        }
+    }
+    out
+}
+
+fn rgb<L: Layout>(px: &[u8]) -> (i32, i32, i32) {
+    (
+        i32::from(px[L::R]),
+        i32::from(px[L::G]),
+        i32::from(px[L::B]),
+    )
+}
+
+fn check_dimensions(width: u32, height: u32) -> Result<()> {
+    if width == 0 || height == 0 || width > MAX_WIDTH || height > MAX_HEIGHT {
+        return Err(CodecError::InvalidDimensions(width, height));
+    }
+    Ok(())
+}
+
+fn clamp(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> YuvFrame {
+        let (cw, ch) = chroma_dimensions(width, height);
+        YuvFrame {
+            width,
+            height,
+            y: (0..width * height)
+                .map(|i| (16 + (i % width) * 200 / width) as u8)
+                .collect(),
+            u: vec![100; cw * ch],
+            v: vec![150; cw * ch],
+            timestamp: 9,
+        }
+    }
+
+    #[test]
+    fn test_packed_formats_roundtrip() {
+        let rgb = [200u8, 30, 90];
+        for format in [
+            PackedFormat::Rgb24,
+            PackedFormat::Rgba32,
+            PackedFormat::Bgra32,
+        ] {
+            let pixel: Vec<u8> = match format {
+                PackedFormat::Rgb24 => rgb.to_vec(),
+                PackedFormat::Rgba32 => vec![rgb[0], rgb[1], rgb[2], 255],
+                PackedFormat::Bgra32 => vec![rgb[2], rgb[1], rgb[0], 255],
+            };
+            let data = pixel.repeat(5 * 3);
+
+            let yuv = packed_to_i420(&data, format, 5, 3, 1).unwrap();
+            let back = i420_to_packed(&yuv, format).unwrap();
+            assert_eq!(back.len(), data.len());
+            for (a, b) in back.iter().zip(&data) {
+                assert!((i32::from(*a) - i32::from(*b)).abs() <= 3, "{:?}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bgra_matches_rgb() {
+        let rgb = packed_to_i420(&[10, 20, 30, 40, 50, 60], PackedFormat::Rgb24, 2, 1, 0).unwrap();
+        let bgra = packed_to_i420(
+            &[30, 20, 10, 0, 60, 50, 40, 0],
+            PackedFormat::Bgra32,
+            2,
+            1,
+            0,
+        )
+        .unwrap();
+        assert_eq!(rgb, bgra);
+    }
+
+    #[test]
+    fn test_nv12_roundtrip() {
+        let frame = gradient(7, 5);
+        let nv12 = Nv12Frame::from_i420(&frame).unwrap();
+        assert_eq!(nv12.uv.len(), 2 * 4 * 3);
+        assert_eq!(&nv12.uv[..2], &[100, 150]);
+        assert_eq!(nv12.to_i420().unwrap(), frame);
+    }
+
+    #[test]
+    fn test_crop() {
+        let frame = gradient(8, 6);
+        let cropped = crop(&frame, 2, 2, 3, 4).unwrap();
+        assert_eq!((cropped.width, cropped.height), (3, 4));
+        assert_eq!(&cropped.y[..3], &frame.y[2 * 8 + 2..2 * 8 + 5]);
+        assert_eq!(cropped.u.len(), 2 * 2);
+
+        assert!(crop(&frame, 1, 0, 2, 2).is_err());
+        assert!(crop(&frame, 6, 0, 4, 2).is_err());
+    }
+
+    #[test]
+    fn test_scale() {
+        let frame = gradient(64, 48);
+        let small = scale(&frame, 32, 24).unwrap();
+        assert_eq!(small.y.len(), 32 * 24);
+        assert_eq!(small.u.len(), 16 * 12);
+        assert!(small.u.iter().all(|&u| u == 100));
+        // Left-to-right gradient survives
+        assert!(small.y[0] < small.y[31]);
+
+        let large = scale(&small, 640, 480).unwrap();
+        assert_eq!(large.y.len(), 640 * 480);
+        assert_eq!(scale(&frame, 64, 48).unwrap(), frame);
+        assert!(scale(&frame, 0, 48).is_err());
+    }
+}
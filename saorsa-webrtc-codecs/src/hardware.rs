@@ -0,0 +1,509 @@
+//! Hardware-accelerated H.264 encoding
+//!
+//! [`HardwareEncoder`] drives a platform encoder through GStreamer:
+//! VideoToolbox on macOS, NVENC on NVIDIA GPUs and VA-API on Linux. It
+//! opens the first backend in the caller's preference order that works on
+//! this machine and otherwise falls back to [`OpenH264Encoder`], so callers
+//! always get an H.264 encoder. A hardware encoder that fails mid-stream
+//! (device reset, driver error) is swapped for software the same way.
+//! Every backend emits the same Annex-B bitstream with the timestamp SEI,
+//! so receivers cannot tell them apart. Hardware backends need the
+//! `hardware` feature; without it every encoder runs in software.
+
+use crate::openh264::{H264EncoderConfig, OpenH264Encoder};
+use crate::{Result, VideoEncoder, VideoFrame};
+use bytes::Bytes;
+
+/// Where H.264 encoding runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HardwareBackend {
+    /// Apple VideoToolbox (macOS)
+    VideoToolbox,
+    /// NVIDIA NVENC
+    Nvenc,
+    /// VA-API (Intel and AMD GPUs on Linux)
+    Vaapi,
+    /// OpenH264 on the CPU
+    Software,
+}
+
+impl HardwareBackend {
+    /// Every backend, hardware first; the default preference order
+    pub const ALL: [HardwareBackend; 4] = [
+        HardwareBackend::VideoToolbox,
+        HardwareBackend::Nvenc,
+        HardwareBackend::Vaapi,
+        HardwareBackend::Software,
+    ];
+
+    /// Short name for logs and settings
+    pub fn name(self) -> &'static str {
+        match self {
+            HardwareBackend::VideoToolbox => "videotoolbox",
+            HardwareBackend::Nvenc => "nvenc",
+            HardwareBackend::Vaapi => "vaapi",
+            HardwareBackend::Software => "software",
+        }
+    }
+
+    /// Whether the backend can be opened on this machine
+    ///
+    /// Hardware backends need the `hardware` feature and the matching
+    /// GStreamer plugin; software is always available.
+    pub fn is_available(self) -> bool {
+        match self {
+            HardwareBackend::Software => true,
+            #[cfg(feature = "hardware")]
+            _ => native::element(self).is_some(),
+            #[cfg(not(feature = "hardware"))]
+            _ => false,
+        }
+    }
+}
+
+/// H.264 encoder that prefers hardware
+pub struct HardwareEncoder {
+    backend: HardwareBackend,
+    config: H264EncoderConfig,
+    inner: Backend,
+}
+
+enum Backend {
+    #[cfg(feature = "hardware")]
+    Native(native::Pipeline),
+    Software(OpenH264Encoder),
+}
+
+impl HardwareEncoder {
+    /// Open the first backend in `preference` that works here
+    ///
+    /// Backends listed after [`HardwareBackend::Software`] are never tried.
+    /// Software is used when no listed hardware backend opens, even if the
+    /// list leaves it out.
+    pub fn new(config: H264EncoderConfig, preference: &[HardwareBackend]) -> Result<Self> {
+        config.validate()?;
+
+        #[cfg(feature = "hardware")]
+        for &backend in preference {
+            if backend == HardwareBackend::Software {
+                break;
+            }
+            if let Ok(pipeline) = native::Pipeline::open(backend, &config) {
+                return Ok(Self {
+                    backend,
+                    config,
+                    inner: Backend::Native(pipeline),
+                });
+            }
+        }
+        #[cfg(not(feature = "hardware"))]
+        let _ = preference;
+
+        Ok(Self {
+            backend: HardwareBackend::Software,
+            inner: Backend::Software(OpenH264Encoder::with_config(config.clone())?),
+            config,
+        })
+    }
+
+    /// Backend doing the encoding
+    pub fn backend(&self) -> HardwareBackend {
+        self.backend
+    }
+
+    /// Whether encoding is offloaded from the CPU
+    pub fn is_hardware(&self) -> bool {
+        self.backend != HardwareBackend::Software
+    }
+
+    /// Encoder configuration
+    pub fn config(&self) -> &H264EncoderConfig {
+        &self.config
+    }
+
+    /// Replace a failed hardware encoder with software
+    #[cfg(feature = "hardware")]
+    fn fall_back(&mut self) -> Result<()> {
+        let mut encoder = OpenH264Encoder::with_config(self.config.clone())?;
+        // Receivers lost the reference frames along with the old encoder
+        encoder.request_keyframe();
+        self.inner = Backend::Software(encoder);
+        self.backend = HardwareBackend::Software;
+        Ok(())
+    }
+}
+
+impl VideoEncoder for HardwareEncoder {
+    fn encode(&mut self, frame: &VideoFrame) -> Result<Bytes> {
+        match &mut self.inner {
+            #[cfg(feature = "hardware")]
+            Backend::Native(pipeline) => {
+                if frame.width != self.config.width || frame.height != self.config.height {
+                    return Err(crate::CodecError::DimensionMismatch {
+                        frame_width: frame.width,
+                        frame_height: frame.height,
+                        cfg_width: self.config.width,
+                        cfg_height: self.config.height,
+                    });
+                }
+                match pipeline.encode(frame) {
+                    Ok(encoded) => Ok(encoded),
+                    Err(_) => {
+                        self.fall_back()?;
+                        self.encode(frame)
+                    }
+                }
+            }
+            Backend::Software(encoder) => encoder.encode(frame),
+        }
+    }
+
+    fn request_keyframe(&mut self) {
+        match &mut self.inner {
+            #[cfg(feature = "hardware")]
+            Backend::Native(pipeline) => pipeline.request_keyframe(),
+            Backend::Software(encoder) => encoder.request_keyframe(),
+        }
+    }
+}
+
+#[cfg(feature = "hardware")]
+mod native {
+    use super::HardwareBackend;
+    use crate::openh264::{insert_timestamp_sei, BitrateMode, H264EncoderConfig, H264Profile};
+    use crate::yuv::YuvFrame;
+    use crate::{CodecError, Result, VideoFrame};
+    use bytes::Bytes;
+    use gst::prelude::*;
+    use gstreamer as gst;
+    use gstreamer_app as gst_app;
+    use gstreamer_video as gst_video;
+    use std::collections::HashMap;
+
+    /// How long to wait for an encoded frame before sending nothing
+    const PULL_TIMEOUT: gst::ClockTime = gst::ClockTime::from_mseconds(5);
+
+    /// How long opening a device may take
+    const OPEN_TIMEOUT: gst::ClockTime = gst::ClockTime::from_seconds(2);
+
+    /// GStreamer encoder elements for a backend, most preferred first
+    fn candidates(backend: HardwareBackend) -> &'static [&'static str] {
+        match backend {
+            // The `_hw` variant refuses to fall back to Apple's software encoder
+            HardwareBackend::VideoToolbox => &["vtenc_h264_hw"],
+            HardwareBackend::Nvenc => &["nvh264enc"],
+            HardwareBackend::Vaapi => &["vah264enc", "vah264lpenc", "vaapih264enc"],
+            HardwareBackend::Software => &[],
+        }
+    }
+
+    /// First installed encoder element for a backend
+    pub(super) fn element(backend: HardwareBackend) -> Option<&'static str> {
+        gst::init().ok()?;
+        candidates(backend)
+            .iter()
+            .copied()
+            .find(|name| gst::ElementFactory::find(name).is_some())
+    }
+
+    /// Element properties for the configured preset and rate control
+    fn properties(element: &str, config: &H264EncoderConfig) -> String {
+        let kbps = (config.bitrate_bps / 1000).max(1);
+        let gop = config.keyframe_interval;
+        let preset = config.preset;
+        let bframes = preset.b_frames();
+        let lookahead = preset.lookahead_frames();
+        let vbr = config.bitrate_mode == BitrateMode::Quality;
+        let (mut props, gop_property) = match element {
+            // VideoToolbox has no lookahead control; realtime mode keeps its
+            // internal queue short
+            "vtenc_h264_hw" => (
+                format!(
+                    "realtime={} allow-frame-reordering={} bitrate={kbps}",
+                    preset.is_realtime(),
+                    bframes > 0
+                ),
+                "max-keyframe-interval",
+            ),
+            "nvh264enc" => (
+                format!(
+                    "zerolatency={} bframes={bframes} rc-lookahead={lookahead} rc-mode={} \
+                     bitrate={kbps}",
+                    lookahead == 0,
+                    if vbr { "vbr" } else { "cbr" }
+                ),
+                "gop-size",
+            ),
+            "vaapih264enc" => (
+                format!(
+                    "max-bframes={bframes} rate-control={} bitrate={kbps}",
+                    if vbr { "vbr" } else { "cbr" }
+                ),
+                "keyframe-period",
+            ),
+            _ => (
+                format!(
+                    "b-frames={bframes} rate-control={} bitrate={kbps}",
+                    if vbr { "vbr" } else { "cbr" }
+                ),
+                "key-int-max",
+            ),
+        };
+        if gop > 0 {
+            props.push_str(&format!(" {gop_property}={gop}"));
+        }
+        props
+    }
+
+    fn profile(profile: H264Profile) -> &'static str {
+        match profile {
+            H264Profile::Baseline => "constrained-baseline",
+            H264Profile::Main => "main",
+            H264Profile::High => "high",
+        }
+    }
+
+    fn init_failed(e: impl std::fmt::Display) -> CodecError {
+        CodecError::InitFailed(e.to_string())
+    }
+
+    fn encoding_failed(e: impl std::fmt::Display) -> CodecError {
+        CodecError::EncodingFailed(e.to_string())
+    }
+
+    /// `appsrc ! videoconvert ! <encoder> ! appsink`
+    pub(super) struct Pipeline {
+        pipeline: gst::Pipeline,
+        src: gst_app::AppSrc,
+        sink: gst_app::AppSink,
+        info: gst_video::VideoInfo,
+        frame_duration: u64,
+        frames: u64,
+        /// Caller timestamps by buffer PTS, for frames still in the encoder
+        timestamps: HashMap<u64, u64>,
+        pending_keyframe: bool,
+    }
+
+    impl Pipeline {
+        pub(super) fn open(backend: HardwareBackend, config: &H264EncoderConfig) -> Result<Self> {
+            let element = element(backend).ok_or_else(|| {
+                CodecError::InitFailed(format!("no {} encoder installed", backend.name()))
+            })?;
+            let description = format!(
+                "appsrc name=src is-live=true format=time ! videoconvert ! {element} {} ! \
+                 video/x-h264,stream-format=byte-stream,alignment=au,profile={} ! \
+                 appsink name=sink sync=false",
+                properties(element, config),
+                profile(config.profile),
+            );
+            let pipeline = gst::parse::launch(&description)
+                .map_err(init_failed)?
+                .downcast::<gst::Pipeline>()
+                .map_err(|_| CodecError::InitFailed("not a pipeline".to_string()))?;
+            let src = pipeline
+                .by_name("src")
+                .and_then(|e| e.downcast::<gst_app::AppSrc>().ok())
+                .ok_or_else(|| CodecError::InitFailed("missing appsrc".to_string()))?;
+            let sink = pipeline
+                .by_name("sink")
+                .and_then(|e| e.downcast::<gst_app::AppSink>().ok())
+                .ok_or_else(|| CodecError::InitFailed("missing appsink".to_string()))?;
+
+            let fps = gst::Fraction::approximate_f32(config.frame_rate)
+                .unwrap_or_else(|| gst::Fraction::new(30, 1));
+            let info = gst_video::VideoInfo::builder(
+                gst_video::VideoFormat::I420,
+                config.width,
+                config.height,
+            )
+            .fps(fps)
+            .build()
+            .map_err(init_failed)?;
+            src.set_caps(Some(&info.to_caps().map_err(init_failed)?));
+
+            let pipeline = Self {
+                pipeline,
+                src,
+                sink,
+                info,
+                frame_duration: (1e9 / f64::from(config.frame_rate)) as u64,
+                frames: 0,
+                timestamps: HashMap::new(),
+                pending_keyframe: false,
+            };
+            // Opening the device happens here, so a missing or busy GPU
+            // fails now rather than on the first frame
+            pipeline
+                .pipeline
+                .set_state(gst::State::Playing)
+                .map_err(init_failed)?;
+            let (result, _, _) = pipeline.pipeline.state(OPEN_TIMEOUT);
+            result.map_err(init_failed)?;
+            pipeline.check_bus().map_err(init_failed)?;
+            Ok(pipeline)
+        }
+
+        pub(super) fn request_keyframe(&mut self) {
+            self.pending_keyframe = true;
+        }
+
+        /// Encode a frame; empty while the encoder is still filling up
+        pub(super) fn encode(&mut self, frame: &VideoFrame) -> Result<Bytes> {
+            self.check_bus()?;
+            let yuv = YuvFrame::from_rgb(frame)?;
+            let pts = self.frame_duration.saturating_mul(self.frames);
+            self.frames += 1;
+
+            if std::mem::take(&mut self.pending_keyframe) {
+                // Travels upstream from the sink to the encoder, which makes
+                // the next frame it sees an IDR
+                let event = gst_video::UpstreamForceKeyUnitEvent::builder()
+                    .all_headers(true)
+                    .build();
+                self.sink.send_event(event);
+            }
+
+            self.src
+                .push_buffer(self.buffer(&yuv, pts)?)
+                .map_err(encoding_failed)?;
+            self.timestamps.insert(pts, frame.timestamp);
+
+            let Some(sample) = self.sink.try_pull_sample(PULL_TIMEOUT) else {
+                return Ok(Bytes::new());
+            };
+            let buffer = sample.buffer().ok_or(CodecError::EncodingFailed(
+                "sample without buffer".to_string(),
+            ))?;
+            let timestamp = buffer
+                .pts()
+                .map(gst::ClockTime::nseconds)
+                .and_then(|pts| {
+                    let timestamp = self.timestamps.remove(&pts);
+                    // Frames the encoder dropped will never come out
+                    self.timestamps.retain(|&pending, _| pending > pts);
+                    timestamp
+                })
+                .unwrap_or(frame.timestamp);
+            let map = buffer.map_readable().map_err(encoding_failed)?;
+            Ok(Bytes::from(insert_timestamp_sei(
+                map.as_slice().to_vec(),
+                timestamp,
+            )))
+        }
+
+        /// Copy a frame into a GStreamer buffer laid out as the caps say
+        fn buffer(&self, yuv: &YuvFrame, pts: u64) -> Result<gst::Buffer> {
+            let mut buffer = gst::Buffer::with_size(self.info.size()).map_err(encoding_failed)?;
+            let buffer_ref = buffer.get_mut().ok_or(CodecError::EncodingFailed(
+                "buffer not writable".to_string(),
+            ))?;
+            buffer_ref.set_pts(gst::ClockTime::from_nseconds(pts));
+            buffer_ref.set_duration(gst::ClockTime::from_nseconds(self.frame_duration));
+
+            let mut video =
+                gst_video::VideoFrameRef::from_buffer_ref_writable(buffer_ref, &self.info)
+                    .map_err(encoding_failed)?;
+            let (chroma_width, chroma_height) = yuv.chroma_dimensions();
+            let planes = [
+                (&yuv.y, yuv.width as usize, yuv.height as usize),
+                (&yuv.u, chroma_width, chroma_height),
+                (&yuv.v, chroma_width, chroma_height),
+            ];
+            for (index, (plane, width, height)) in planes.into_iter().enumerate() {
+                let stride = video
+                    .plane_stride()
+                    .get(index)
+                    .map(|&stride| stride as usize)
+                    .ok_or(CodecError::InvalidData("missing plane"))?;
+                let dest = video
+                    .plane_data_mut(index as u32)
+                    .map_err(encoding_failed)?;
+                for row in 0..height {
+                    let line = plane
+                        .get(row * width..(row + 1) * width)
+                        .ok_or(CodecError::InvalidData("plane too small"))?;
+                    dest.get_mut(row * stride..row * stride + width)
+                        .ok_or(CodecError::InvalidData("buffer plane too small"))?
+                        .copy_from_slice(line);
+                }
+            }
+            drop(video);
+            Ok(buffer)
+        }
+
+        /// Surface errors the encoder posted since the last frame
+        fn check_bus(&self) -> Result<()> {
+            let Some(bus) = self.pipeline.bus() else {
+                return Ok(());
+            };
+            match bus.pop_filtered(&[gst::MessageType::Error]) {
+                Some(message) => match message.view() {
+                    gst::MessageView::Error(err) => Err(encoding_failed(err.error())),
+                    _ => Ok(()),
+                },
+                None => Ok(()),
+            }
+        }
+    }
+
+    impl Drop for Pipeline {
+        fn drop(&mut self) {
+            let _ = self.src.end_of_stream();
+            let _ = self.pipeline.set_state(gst::State::Null);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32) -> VideoFrame {
+        VideoFrame {
+            data: vec![128; (width * height * 3) as usize],
+            width,
+            height,
+            timestamp: 42,
+        }
+    }
+
+    fn config(width: u32, height: u32) -> H264EncoderConfig {
+        H264EncoderConfig {
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_software_preference_encodes() {
+        let mut encoder =
+            HardwareEncoder::new(config(64, 48), &[HardwareBackend::Software]).unwrap();
+        assert_eq!(encoder.backend(), HardwareBackend::Software);
+        assert!(!encoder.is_hardware());
+        assert!(!encoder.encode(&frame(64, 48)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unavailable_backends_fall_back_to_software() {
+        let unavailable: Vec<_> = HardwareBackend::ALL
+            .into_iter()
+            .filter(|backend| !backend.is_available())
+            .collect();
+        let encoder = HardwareEncoder::new(config(64, 48), &unavailable).unwrap();
+        assert_eq!(encoder.backend(), HardwareBackend::Software);
+        assert!(HardwareBackend::Software.is_available());
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        assert!(HardwareEncoder::new(config(0, 48), &HardwareBackend::ALL).is_err());
+    }
+
+    #[test]
+    fn test_dimension_mismatch() {
+        let mut encoder = HardwareEncoder::new(config(64, 48), &HardwareBackend::ALL).unwrap();
+        assert!(encoder.encode(&frame(32, 32)).is_err());
+    }
+}
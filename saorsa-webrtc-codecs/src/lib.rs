@@ -5,8 +5,12 @@
 
 //! Video and audio codec implementations
 
+pub mod av1;
+pub mod convert;
+pub mod hardware;
 pub mod openh264;
 pub mod opus;
+pub mod yuv;
 
 use bytes::Bytes;
 
@@ -32,6 +36,10 @@ pub enum CodecError {
     InvalidDimensions(u32, u32),
     #[error("Data size exceeds maximum allowed: {actual} > {max}")]
     SizeExceeded { actual: usize, max: usize },
+    #[error("Encoding failed: {0}")]
+    EncodingFailed(String),
+    #[error("Decoding failed: {0}")]
+    DecodingFailed(String),
 }
 
 /// Codec result type
@@ -42,14 +50,18 @@ pub const MAX_WIDTH: u32 = 8192;
 pub const MAX_HEIGHT: u32 = 8192;
 pub const MAX_RGB_SIZE: usize = 100 * 1024 * 1024; // 100MB
 
+/// Tags the frame timestamp carried in H.264 SEI and AV1 metadata
+pub(crate) const TIMESTAMP_UUID: [u8; 16] = *b"saorsa-webrtc-ts";
+
 /// Video codec selection
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum VideoCodec {
     H264,
+    Av1,
 }
 
 /// Audio codec selection
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AudioCodec {
     Opus,
 }
@@ -63,10 +75,115 @@ pub struct VideoFrame {
     pub timestamp: u64,
 }
 
+/// Temporal layer structure of an encoded stream
+///
+/// A frame only references frames of its own or a lower layer, so a
+/// receiver that drops every layer above some `N` still decodes cleanly, at
+/// a lower frame rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TemporalLayers {
+    /// Single layer
+    #[default]
+    L1T1,
+    /// Two layers: base at half the frame rate
+    L1T2,
+    /// Three layers: base at a quarter of the frame rate
+    L1T3,
+}
+
+impl TemporalLayers {
+    /// Number of layers
+    pub const fn count(self) -> u8 {
+        match self {
+            Self::L1T1 => 1,
+            Self::L1T2 => 2,
+            Self::L1T3 => 3,
+        }
+    }
+
+    /// Layer of the `index`th frame after a keyframe
+    pub const fn layer_of(self, index: u64) -> u8 {
+        match self {
+            Self::L1T1 => 0,
+            Self::L1T2 => (index % 2) as u8,
+            Self::L1T3 => match index % 4 {
+                0 => 0,
+                2 => 1,
+                _ => 2,
+            },
+        }
+    }
+}
+
+/// Trade-off between latency and compression for a video encoder
+///
+/// Live calls need each frame sent as soon as it is captured. Recordings
+/// can afford to hold frames back, reorder them around B-frames and look
+/// ahead before spending bits, for better quality at the same bitrate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EncoderPreset {
+    /// No B-frames, no lookahead, constant bitrate
+    #[default]
+    RealtimeLowLatency,
+    /// No B-frames, a short lookahead for rate control
+    Balanced,
+    /// B-frames, a long lookahead and quality-driven rate control
+    Quality,
+}
+
+impl EncoderPreset {
+    /// B-frames between reference frames
+    pub const fn b_frames(self) -> u32 {
+        match self {
+            Self::RealtimeLowLatency | Self::Balanced => 0,
+            Self::Quality => 2,
+        }
+    }
+
+    /// Frames rate control examines before encoding one
+    pub const fn lookahead_frames(self) -> u32 {
+        match self {
+            Self::RealtimeLowLatency => 0,
+            Self::Balanced => 8,
+            Self::Quality => 40,
+        }
+    }
+
+    /// Whether frames leave the encoder in capture order without delay
+    pub const fn is_realtime(self) -> bool {
+        !matches!(self, Self::Quality)
+    }
+
+    /// Rate control that suits the preset
+    pub const fn bitrate_mode(self) -> BitrateMode {
+        match self {
+            Self::RealtimeLowLatency | Self::Balanced => BitrateMode::Bitrate,
+            Self::Quality => BitrateMode::Quality,
+        }
+    }
+}
+
 /// Video encoder trait
 pub trait VideoEncoder: Send + Sync {
     fn encode(&mut self, frame: &VideoFrame) -> Result<Bytes>;
     fn request_keyframe(&mut self);
+
+    /// Temporal layers this encoder can produce
+    fn temporal_layers(&self) -> TemporalLayers {
+        TemporalLayers::L1T1
+    }
+
+    /// Encode a frame as part of temporal layer `layer`
+    ///
+    /// The default encodes normally, which is only correct for encoders
+    /// whose frames never reference enhancement-layer frames; others that
+    /// claim more than one layer must override it.
+    fn encode_with_layer(&mut self, frame: &VideoFrame, layer: u8) -> Result<Bytes> {
+        if layer >= self.temporal_layers().count() {
+            return Err(CodecError::NotImplemented("temporal layer"));
+        }
+        self.encode(frame)
+    }
 }
 
 /// Video decoder trait
@@ -74,7 +191,34 @@ pub trait VideoDecoder: Send + Sync {
     fn decode(&mut self, data: &[u8]) -> Result<VideoFrame>;
 }
 
-pub use openh264::{OpenH264Decoder, OpenH264Encoder};
+/// Audio encoder trait
+///
+/// Only `Send`: native codec handles may not be shared between threads.
+pub trait AudioEncoder: Send {
+    /// Encode one frame; an empty payload means there is nothing to send
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Bytes>;
+    /// Change the target bitrate in bits per second
+    fn set_bitrate(&mut self, bitrate: u32) -> Result<()>;
+    /// Adapt loss protection to the packet loss measured on the link, in
+    /// percent (0 - 100)
+    fn set_packet_loss(&mut self, percent: u8) -> Result<()>;
+}
+
+/// Audio decoder trait
+pub trait AudioDecoder: Send {
+    fn decode(&mut self, data: &[u8]) -> Result<AudioFrame>;
+    /// Produce audio for a lost packet, using `next` for recovery if available
+    fn conceal(&mut self, next: Option<&[u8]>) -> Result<AudioFrame>;
+}
+
+pub use av1::{Av1ContentMode, Av1Decoder, Av1Encoder, Av1EncoderConfig};
+pub use convert::{Nv12Frame, PackedFormat};
+pub use hardware::{HardwareBackend, HardwareEncoder};
+pub use openh264::{
+    BitrateMode, H264EncoderConfig, H264Profile, OpenH264Decoder, OpenH264Encoder,
+};
 pub use opus::{
     AudioFrame, Channels, OpusDecoder, OpusEncoder, OpusEncoderConfig, SampleRate,
+    FEC_MIN_PACKET_LOSS,
 };
+pub use yuv::YuvFrame;
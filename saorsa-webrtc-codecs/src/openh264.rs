@@ -1,49 +1,165 @@
 //! OpenH264 codec implementation
-
-use crate::{CodecError, Result, VideoDecoder, VideoEncoder, VideoFrame};
+//!
+//! With the `h264` feature (on by default) frames are encoded by Cisco's
+//! OpenH264 into an H.264 Annex-B bitstream. Each access unit carries the
+//! frame timestamp in a user data SEI message, which decoders that do not
+//! know it simply ignore. Without the feature a run-length stand-in is used
+//! so the pipeline can be exercised without a native build.
+
+use crate::yuv::YuvFrame;
+use crate::{
+    CodecError, EncoderPreset, Result, VideoDecoder, VideoEncoder, VideoFrame, TIMESTAMP_UUID,
+};
 use crate::{MAX_HEIGHT, MAX_RGB_SIZE, MAX_WIDTH};
 use bytes::Bytes;
 
+#[cfg(not(feature = "h264"))]
 const HEADER_SIZE: usize = 16;
 
-/// OpenH264 video encoder (stub implementation for now)
-pub struct OpenH264Encoder {
-    width: u32,
-    height: u32,
-    pending_keyframe: bool,
+/// SEI payload type for user data unregistered
+const SEI_USER_DATA_UNREGISTERED: u8 = 5;
+
+/// NAL unit types
+const NAL_SLICE: u8 = 1;
+const NAL_IDR_SLICE: u8 = 5;
+const NAL_SEI: u8 = 6;
+
+/// H.264 profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum H264Profile {
+    /// Widest decoder support; what OpenH264 encodes best
+    Baseline,
+    Main,
+    High,
 }
 
-impl OpenH264Encoder {
-    pub fn new() -> Result<Self> {
-        Self::with_dimensions(640, 480)
+/// Encoder rate control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitrateMode {
+    /// Hold the target bitrate
+    Bitrate,
+    /// Hold quality, letting bitrate vary
+    Quality,
+    /// Bitrate control driven by frame timestamps
+    Timestamp,
+    /// No rate control
+    Off,
+}
+
+/// H.264 encoder configuration
+#[derive(Debug, Clone)]
+pub struct H264EncoderConfig {
+    pub width: u32,
+    pub height: u32,
+    pub profile: H264Profile,
+    pub bitrate_mode: BitrateMode,
+    /// Target bitrate in bits per second
+    pub bitrate_bps: u32,
+    /// Maximum frame rate
+    pub frame_rate: f32,
+    /// Frames between IDR frames; 0 lets the encoder decide
+    pub keyframe_interval: u32,
+    /// Latency and quality trade-off
+    pub preset: EncoderPreset,
+}
+
+impl H264EncoderConfig {
+    /// Use `preset` along with the rate control that suits it
+    pub fn with_preset(mut self, preset: EncoderPreset) -> Self {
+        self.preset = preset;
+        self.bitrate_mode = preset.bitrate_mode();
+        self
     }
 
-    pub fn with_dimensions(width: u32, height: u32) -> Result<Self> {
+    /// Check the configuration is one an encoder can be opened with
+    pub fn validate(&self) -> Result<()> {
+        let (width, height) = (self.width, self.height);
         if width == 0 || height == 0 {
             return Err(CodecError::InvalidDimensions(width, height));
         }
         if width > MAX_WIDTH || height > MAX_HEIGHT {
             return Err(CodecError::InvalidDimensions(width, height));
         }
-        
+
         let rgb_size = width
             .checked_mul(height)
             .and_then(|px| px.checked_mul(3))
             .ok_or(CodecError::Overflow)?;
-        
+
         if rgb_size as usize > MAX_RGB_SIZE {
             return Err(CodecError::SizeExceeded {
                 actual: rgb_size as usize,
                 max: MAX_RGB_SIZE,
             });
         }
+        if self.bitrate_bps == 0 {
+            return Err(CodecError::InvalidData("bitrate must be positive"));
+        }
+        if !self.frame_rate.is_finite() || self.frame_rate <= 0.0 {
+            return Err(CodecError::InvalidData("frame rate must be positive"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for H264EncoderConfig {
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+            profile: H264Profile::Baseline,
+            bitrate_mode: BitrateMode::Bitrate,
+            bitrate_bps: 1_000_000,
+            frame_rate: 30.0,
+            keyframe_interval: 0,
+            preset: EncoderPreset::RealtimeLowLatency,
+        }
+    }
+}
+
+/// OpenH264 video encoder
+pub struct OpenH264Encoder {
+    width: u32,
+    height: u32,
+    pending_keyframe: bool,
+    config: H264EncoderConfig,
+    // The native handle is not Sync; the mutex lets the encoder satisfy
+    // `VideoEncoder` without unsafe code
+    #[cfg(feature = "h264")]
+    inner: std::sync::Mutex<::openh264::encoder::Encoder>,
+}
+
+impl OpenH264Encoder {
+    pub fn new() -> Result<Self> {
+        Self::with_config(H264EncoderConfig::default())
+    }
+
+    pub fn with_dimensions(width: u32, height: u32) -> Result<Self> {
+        Self::with_config(H264EncoderConfig {
+            width,
+            height,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_config(config: H264EncoderConfig) -> Result<Self> {
+        config.validate()?;
+        let (width, height) = (config.width, config.height);
 
         Ok(Self {
             width,
             height,
             pending_keyframe: false,
+            #[cfg(feature = "h264")]
+            inner: std::sync::Mutex::new(native::encoder(&config)?),
+            config,
         })
     }
+
+    /// Encoder configuration
+    pub fn config(&self) -> &H264EncoderConfig {
+        &self.config
+    }
 }
 
 impl VideoEncoder for OpenH264Encoder {
@@ -57,6 +173,39 @@ impl VideoEncoder for OpenH264Encoder {
             });
         }
 
+        let encoded = self.encode_frame(frame)?;
+        self.pending_keyframe = false;
+        Ok(encoded)
+    }
+
+    fn request_keyframe(&mut self) {
+        self.pending_keyframe = true;
+    }
+}
+
+impl OpenH264Encoder {
+    #[cfg(feature = "h264")]
+    fn encode_frame(&mut self, frame: &VideoFrame) -> Result<Bytes> {
+        let yuv = YuvFrame::from_rgb(frame)?;
+        let mut encoder = self
+            .inner
+            .lock()
+            .map_err(|_| CodecError::EncodingFailed("encoder lock poisoned".to_string()))?;
+        if self.pending_keyframe {
+            encoder.force_intra_frame();
+        }
+        let bitstream = encoder
+            .encode(&yuv)
+            .map_err(|e| CodecError::EncodingFailed(e.to_string()))?
+            .to_vec();
+        Ok(Bytes::from(insert_timestamp_sei(
+            bitstream,
+            frame.timestamp,
+        )))
+    }
+
+    #[cfg(not(feature = "h264"))]
+    fn encode_frame(&mut self, frame: &VideoFrame) -> Result<Bytes> {
         let original_size = frame.data.len();
         let compressed_size = original_size / 4;
 
@@ -79,30 +228,60 @@ impl VideoEncoder for OpenH264Encoder {
             i += count;
         }
 
-        self.pending_keyframe = false;
         Ok(Bytes::from(compressed))
     }
-
-    fn request_keyframe(&mut self) {
-        self.pending_keyframe = true;
-    }
 }
 
-/// OpenH264 video decoder (stub implementation for now)
-pub struct OpenH264Decoder;
+/// OpenH264 video decoder
+pub struct OpenH264Decoder {
+    #[cfg(feature = "h264")]
+    inner: std::sync::Mutex<::openh264::decoder::Decoder>,
+}
 
 impl OpenH264Decoder {
     pub fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self {
+            #[cfg(feature = "h264")]
+            inner: std::sync::Mutex::new(
+                ::openh264::decoder::Decoder::new()
+                    .map_err(|e| CodecError::InitFailed(e.to_string()))?,
+            ),
+        })
+    }
+
+    /// Decode an access unit to planar YUV 4:2:0
+    #[cfg(feature = "h264")]
+    pub fn decode_yuv(&mut self, data: &[u8]) -> Result<YuvFrame> {
+        let timestamp = read_timestamp_sei(data).unwrap_or(0);
+        let mut decoder = self
+            .inner
+            .lock()
+            .map_err(|_| CodecError::DecodingFailed("decoder lock poisoned".to_string()))?;
+        let decoded = decoder
+            .decode(data)
+            .map_err(|e| CodecError::DecodingFailed(e.to_string()))?
+            .ok_or(CodecError::InvalidData("no complete frame in bitstream"))?;
+        native::to_yuv_frame(&decoded, timestamp)
+    }
+
+    /// Decode an access unit to planar YUV 4:2:0
+    #[cfg(not(feature = "h264"))]
+    pub fn decode_yuv(&mut self, data: &[u8]) -> Result<YuvFrame> {
+        YuvFrame::from_rgb(&self.decode(data)?)
     }
 }
 
 impl VideoDecoder for OpenH264Decoder {
+    #[cfg(feature = "h264")]
+    fn decode(&mut self, data: &[u8]) -> Result<VideoFrame> {
+        self.decode_yuv(data)?.to_rgb()
+    }
+
+    #[cfg(not(feature = "h264"))]
     fn decode(&mut self, data: &[u8]) -> Result<VideoFrame> {
         if data.len() < HEADER_SIZE {
             return Err(CodecError::InvalidData("data too small for header"));
         }
-
         let width_bytes: [u8; 4] = data
             .get(0..4)
             .and_then(|s| s.try_into().ok())
@@ -170,6 +349,190 @@ impl VideoDecoder for OpenH264Decoder {
     }
 }
 
+#[cfg(feature = "h264")]
+mod native {
+    use super::{BitrateMode, H264EncoderConfig, H264Profile};
+    use crate::yuv::{copy_plane, YuvFrame};
+    use crate::{CodecError, Result};
+    use ::openh264::encoder::{
+        BitRate, Encoder, EncoderConfig, FrameRate, IntraFramePeriod, Profile, RateControlMode,
+        UsageType,
+    };
+    use ::openh264::formats::YUVSource;
+    use ::openh264::OpenH264API;
+
+    impl YUVSource for YuvFrame {
+        fn dimensions(&self) -> (usize, usize) {
+            (self.width as usize, self.height as usize)
+        }
+
+        fn strides(&self) -> (usize, usize, usize) {
+            let (chroma_width, _) = self.chroma_dimensions();
+            (self.width as usize, chroma_width, chroma_width)
+        }
+
+        fn y(&self) -> &[u8] {
+            &self.y
+        }
+
+        fn u(&self) -> &[u8] {
+            &self.u
+        }
+
+        fn v(&self) -> &[u8] {
+            &self.v
+        }
+    }
+
+    pub(super) fn encoder(config: &H264EncoderConfig) -> Result<Encoder> {
+        let profile = match config.profile {
+            H264Profile::Baseline => Profile::Baseline,
+            H264Profile::Main => Profile::Main,
+            H264Profile::High => Profile::High,
+        };
+        let mode = match config.bitrate_mode {
+            BitrateMode::Bitrate => RateControlMode::Bitrate,
+            BitrateMode::Quality => RateControlMode::Quality,
+            BitrateMode::Timestamp => RateControlMode::Timestamp,
+            BitrateMode::Off => RateControlMode::Off,
+        };
+        // OpenH264 never emits B-frames; the preset only picks how much
+        // effort goes into each frame
+        let usage = if config.preset.is_realtime() {
+            UsageType::CameraVideoRealTime
+        } else {
+            UsageType::CameraVideoNonRealTime
+        };
+        let mut encoder_config = EncoderConfig::new()
+            .usage_type(usage)
+            .profile(profile)
+            .rate_control_mode(mode)
+            .bitrate(BitRate::from_bps(config.bitrate_bps))
+            .max_frame_rate(FrameRate::from_hz(config.frame_rate));
+        if config.keyframe_interval > 0 {
+            encoder_config = encoder_config
+                .intra_frame_period(IntraFramePeriod::from_num_frames(config.keyframe_interval));
+        }
+        Encoder::with_api_config(OpenH264API::from_source(), encoder_config)
+            .map_err(|e| CodecError::InitFailed(e.to_string()))
+    }
+
+    /// Copy a decoded picture out of the decoder's strided buffers
+    pub(super) fn to_yuv_frame(decoded: &impl YUVSource, timestamp: u64) -> Result<YuvFrame> {
+        let (width, height) = decoded.dimensions();
+        let (y_stride, u_stride, v_stride) = decoded.strides();
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        Ok(YuvFrame {
+            width: u32::try_from(width).map_err(|_| CodecError::Overflow)?,
+            height: u32::try_from(height).map_err(|_| CodecError::Overflow)?,
+            y: copy_plane(decoded.y(), y_stride, width, height)?,
+            u: copy_plane(decoded.u(), u_stride, chroma_width, chroma_height)?,
+            v: copy_plane(decoded.v(), v_stride, chroma_width, chroma_height)?,
+            timestamp,
+        })
+    }
+}
+
+/// Byte ranges `(start_code_start, payload_start)` of each NAL unit
+fn nal_units(stream: &[u8]) -> Vec<(usize, usize)> {
+    let mut units = Vec::new();
+    let mut i = 0;
+    while i + 3 <= stream.len() {
+        if stream[i] == 0 && stream[i + 1] == 0 && stream[i + 2] == 1 {
+            let start = if i > 0 && stream[i - 1] == 0 {
+                i - 1
+            } else {
+                i
+            };
+            units.push((start, i + 3));
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    units
+}
+
+/// Insert emulation prevention bytes into an RBSP
+fn escape_rbsp(rbsp: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rbsp.len() + 4);
+    let mut zeros = 0;
+    for &byte in rbsp {
+        if zeros >= 2 && byte <= 3 {
+            out.push(3);
+            zeros = 0;
+        }
+        out.push(byte);
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+    }
+    out
+}
+
+/// Strip emulation prevention bytes from a NAL payload
+fn unescape_rbsp(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len());
+    let mut zeros = 0;
+    for &byte in payload {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        out.push(byte);
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+    }
+    out
+}
+
+/// Build a user data SEI NAL unit carrying `timestamp`
+fn timestamp_sei(timestamp: u64) -> Vec<u8> {
+    let mut rbsp = vec![SEI_USER_DATA_UNREGISTERED, 24];
+    rbsp.extend_from_slice(&TIMESTAMP_UUID);
+    rbsp.extend_from_slice(&timestamp.to_be_bytes());
+    // rbsp_trailing_bits
+    rbsp.push(0x80);
+
+    let mut nal = vec![0, 0, 0, 1, NAL_SEI];
+    nal.extend(escape_rbsp(&rbsp));
+    nal
+}
+
+/// Insert the timestamp SEI before the first slice of an access unit
+#[cfg_attr(not(any(feature = "h264", feature = "hardware")), allow(dead_code))]
+pub(crate) fn insert_timestamp_sei(mut stream: Vec<u8>, timestamp: u64) -> Vec<u8> {
+    let position = nal_units(&stream)
+        .into_iter()
+        .find(|&(_, payload)| {
+            stream
+                .get(payload)
+                .is_some_and(|header| matches!(header & 0x1F, NAL_SLICE | NAL_IDR_SLICE))
+        })
+        .map_or(0, |(start, _)| start);
+    stream.splice(position..position, timestamp_sei(timestamp));
+    stream
+}
+
+/// Read the timestamp from our SEI message, if the access unit has one
+#[cfg_attr(not(feature = "h264"), allow(dead_code))]
+fn read_timestamp_sei(stream: &[u8]) -> Option<u64> {
+    let units = nal_units(stream);
+    units.iter().enumerate().find_map(|(index, &(_, payload))| {
+        if stream.get(payload)? & 0x1F != NAL_SEI {
+            return None;
+        }
+        let end = units
+            .get(index + 1)
+            .map_or(stream.len(), |&(start, _)| start);
+        let rbsp = unescape_rbsp(stream.get(payload + 1..end)?);
+        if rbsp.first() != Some(&SEI_USER_DATA_UNREGISTERED)
+            || rbsp.get(1) != Some(&24)
+            || rbsp.get(2..18)? != TIMESTAMP_UUID
+        {
+            return None;
+        }
+        Some(u64::from_be_bytes(rbsp.get(18..26)?.try_into().ok()?))
+    })
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used)]
 mod tests {
@@ -302,6 +665,8 @@ mod tests {
         assert!(result.is_err());
     }
 
+    // The stand-in accepts any payload after a valid header
+    #[cfg(not(feature = "h264"))]
     #[test]
     fn test_decoder_random_noise() {
         let mut decoder = OpenH264Decoder::new().unwrap();
@@ -377,6 +742,116 @@ mod tests {
         assert_eq!(decoded.height, frame.height);
         assert_eq!(decoded.timestamp, frame.timestamp);
     }
+
+    #[test]
+    fn test_encoder_config_validation() {
+        let config = H264EncoderConfig {
+            profile: H264Profile::Main,
+            bitrate_mode: BitrateMode::Quality,
+            frame_rate: 15.0,
+            ..Default::default()
+        };
+        let encoder = OpenH264Encoder::with_config(config).unwrap();
+        assert_eq!(encoder.config().profile, H264Profile::Main);
+        assert_eq!(encoder.config().bitrate_mode, BitrateMode::Quality);
+
+        let config = H264EncoderConfig::default().with_preset(EncoderPreset::Quality);
+        assert_eq!(config.bitrate_mode, BitrateMode::Quality);
+        assert!(OpenH264Encoder::with_config(config).is_ok());
+
+        for config in [
+            H264EncoderConfig {
+                bitrate_bps: 0,
+                ..Default::default()
+            },
+            H264EncoderConfig {
+                frame_rate: 0.0,
+                ..Default::default()
+            },
+            H264EncoderConfig {
+                frame_rate: f32::NAN,
+                ..Default::default()
+            },
+        ] {
+            assert!(OpenH264Encoder::with_config(config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_yuv() {
+        let mut encoder = OpenH264Encoder::with_dimensions(64, 48).unwrap();
+        let mut decoder = OpenH264Decoder::new().unwrap();
+        let frame = VideoFrame {
+            data: vec![90; 64 * 48 * 3],
+            width: 64,
+            height: 48,
+            timestamp: 7,
+        };
+
+        let yuv = decoder.decode_yuv(&encoder.encode(&frame).unwrap()).unwrap();
+        assert_eq!((yuv.width, yuv.height), (64, 48));
+        assert_eq!(yuv.y.len(), 64 * 48);
+        assert_eq!(yuv.u.len(), 32 * 24);
+        assert_eq!(yuv.timestamp, 7);
+    }
+
+    #[test]
+    fn test_timestamp_sei_roundtrip() {
+        // Fake SPS followed by an IDR slice
+        let stream = vec![0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x65, 0x88, 0x84];
+        // Contains 00 00 00 runs that need emulation prevention
+        let timestamp = 0x0000_0001_0000_0300;
+
+        let tagged = insert_timestamp_sei(stream.clone(), timestamp);
+        assert_eq!(read_timestamp_sei(&tagged), Some(timestamp));
+        assert_eq!(read_timestamp_sei(&stream), None);
+
+        // The SEI sits between the SPS and the slice
+        let types: Vec<u8> = nal_units(&tagged)
+            .iter()
+            .map(|&(_, payload)| tagged[payload] & 0x1F)
+            .collect();
+        assert_eq!(types, vec![7, NAL_SEI, NAL_IDR_SLICE]);
+        assert!(tagged.ends_with(&[0, 0, 0, 1, 0x65, 0x88, 0x84]));
+    }
+
+    #[test]
+    fn test_foreign_sei_ignored() {
+        let mut stream = vec![0, 0, 0, 1, NAL_SEI, SEI_USER_DATA_UNREGISTERED, 24];
+        stream.extend_from_slice(&[0x11; 24]);
+        stream.push(0x80);
+        assert_eq!(read_timestamp_sei(&stream), None);
+    }
+
+    #[cfg(feature = "h264")]
+    fn has_idr(stream: &[u8]) -> bool {
+        nal_units(stream)
+            .iter()
+            .any(|&(_, payload)| stream[payload] & 0x1F == NAL_IDR_SLICE)
+    }
+
+    #[cfg(feature = "h264")]
+    #[test]
+    fn test_keyframe_request_forces_idr() {
+        let mut encoder = OpenH264Encoder::with_dimensions(64, 48).unwrap();
+        let mut frame = VideoFrame {
+            data: vec![128; 64 * 48 * 3],
+            width: 64,
+            height: 48,
+            timestamp: 0,
+        };
+
+        let first = encoder.encode(&frame).unwrap();
+        assert!(first.starts_with(&[0, 0, 0, 1]));
+        assert!(has_idr(&first));
+
+        frame.timestamp = 33;
+        assert!(!has_idr(&encoder.encode(&frame).unwrap()));
+
+        encoder.request_keyframe();
+        frame.timestamp = 66;
+        assert!(has_idr(&encoder.encode(&frame).unwrap()));
+    }
 }
 
 #[cfg(test)]
@@ -386,6 +861,8 @@ mod proptests {
     use proptest::prelude::*;
 
     proptest! {
+        // Odd sizes and single pixels are only meaningful for the stand-in
+        #[cfg(not(feature = "h264"))]
         #[test]
         fn prop_encode_decode_preserves_metadata(
             width in 1u32..=1920,
@@ -468,6 +945,7 @@ mod proptests {
             }
         }
 
+        #[cfg(not(feature = "h264"))]
         #[test]
         fn prop_keyframe_flag_cleared_after_encode(
             width in 1u32..=640,
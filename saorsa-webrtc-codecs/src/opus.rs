@@ -1,6 +1,6 @@
 //! Opus audio codec implementation
 
-use crate::{CodecError, Result};
+use crate::{AudioDecoder, AudioEncoder, CodecError, Result};
 use bytes::Bytes;
 
 /// Opus audio sample rates (Hz)
@@ -52,6 +52,15 @@ pub struct OpusEncoderConfig {
     pub channels: Channels,
     /// Bitrate in bits per second (6000 - 510000)
     pub bitrate: u32,
+    /// Embed forward error correction data for the previous frame
+    ///
+    /// Once loss is reported through [`AudioEncoder::set_packet_loss`], FEC
+    /// is only sent while the measured loss calls for it.
+    pub fec: bool,
+    /// Expected packet loss in percent (0 - 100), tunes how much FEC is sent
+    pub expected_packet_loss: u8,
+    /// Discontinuous transmission: skip silent frames
+    pub dtx: bool,
 }
 
 impl Default for OpusEncoderConfig {
@@ -60,42 +69,151 @@ impl Default for OpusEncoderConfig {
             sample_rate: SampleRate::Hz48000,
             channels: Channels::Mono,
             bitrate: 64000, // 64 kbps
+            fec: true,
+            expected_packet_loss: 10,
+            dtx: false,
         }
     }
 }
 
-/// Opus audio encoder (stub implementation)
+/// Largest Opus packet we produce
+#[cfg(feature = "opus")]
+const MAX_PACKET_SIZE: usize = 4000;
+
+/// Longest frame Opus can decode, in milliseconds
+#[cfg(feature = "opus")]
+const MAX_FRAME_MS: usize = 120;
+
+/// Measured loss, in percent, from which in-band FEC is sent
+pub const FEC_MIN_PACKET_LOSS: u8 = 1;
+
+/// Peak amplitude below which a frame counts as silence for DTX
+const DTX_SILENCE_THRESHOLD: u16 = 64;
+
+/// During DTX, still send one frame this often so the far end keeps
+/// generating comfort noise
+const DTX_KEEPALIVE_MS: u64 = 400;
+
+fn validate_bitrate(bitrate: u32) -> Result<()> {
+    if !(6000..=510000).contains(&bitrate) {
+        return Err(CodecError::InvalidData("bitrate out of range (6000-510000)"));
+    }
+    Ok(())
+}
+
+/// Opus audio encoder
+///
+/// Uses libopus when the `opus` feature is enabled. Without it, frames are
+/// carried as uncompressed PCM so the rest of the pipeline can be exercised
+/// without a native build.
 pub struct OpusEncoder {
     config: OpusEncoderConfig,
+    #[cfg(feature = "opus")]
+    inner: ::opus::Encoder,
+    silent_ms: u64,
+    fec_active: bool,
 }
 
 impl OpusEncoder {
     pub fn new(config: OpusEncoderConfig) -> Result<Self> {
-        // Validate bitrate
-        if config.bitrate < 6000 || config.bitrate > 510000 {
-            return Err(CodecError::InvalidData("bitrate out of range (6000-510000)"));
+        validate_bitrate(config.bitrate)?;
+        if config.expected_packet_loss > 100 {
+            return Err(CodecError::InvalidData("expected packet loss out of range (0-100)"));
         }
-        
-        Ok(Self { config })
+
+        #[cfg(feature = "opus")]
+        let inner = {
+            let channels = match config.channels {
+                Channels::Mono => ::opus::Channels::Mono,
+                Channels::Stereo => ::opus::Channels::Stereo,
+            };
+            let mut inner = ::opus::Encoder::new(
+                config.sample_rate.as_hz(),
+                channels,
+                ::opus::Application::Voip,
+            )
+            .map_err(|e| CodecError::InitFailed(e.to_string()))?;
+            inner
+                .set_bitrate(::opus::Bitrate::Bits(config.bitrate as i32))
+                .map_err(|e| CodecError::InitFailed(e.to_string()))?;
+            inner
+                .set_inband_fec(config.fec)
+                .map_err(|e| CodecError::InitFailed(e.to_string()))?;
+            inner
+                .set_packet_loss_perc(i32::from(config.expected_packet_loss))
+                .map_err(|e| CodecError::InitFailed(e.to_string()))?;
+            inner
+        };
+
+        Ok(Self {
+            fec_active: config.fec,
+            config,
+            #[cfg(feature = "opus")]
+            inner,
+            silent_ms: 0,
+        })
     }
 
-    /// Encode PCM audio data to Opus
-    pub fn encode(&mut self, frame: &AudioFrame) -> Result<Bytes> {
-        // Validate frame matches encoder config
-        if frame.sample_rate != self.config.sample_rate {
-            return Err(CodecError::InvalidData("sample rate mismatch"));
+    /// Encoder configuration
+    pub fn config(&self) -> &OpusEncoderConfig {
+        &self.config
+    }
+
+    /// Whether in-band FEC is being sent
+    pub fn fec_active(&self) -> bool {
+        self.fec_active
+    }
+
+    /// Returns true if this frame should be skipped under DTX
+    fn suppress(&mut self, frame: &AudioFrame) -> bool {
+        if !self.config.dtx {
+            return false;
         }
-        if frame.channels != self.config.channels {
-            return Err(CodecError::InvalidData("channel count mismatch"));
+        let silent = frame
+            .data
+            .iter()
+            .all(|s| s.unsigned_abs() < DTX_SILENCE_THRESHOLD);
+        if !silent {
+            self.silent_ms = 0;
+            return false;
         }
-        
-        // Validate frame size (must have data)
-        if frame.data.is_empty() {
-            return Err(CodecError::InvalidData("empty audio frame"));
+
+        let samples_per_channel = (frame.data.len() / frame.channels.count()) as u64;
+        let frame_ms = samples_per_channel * 1000 / u64::from(frame.sample_rate.as_hz());
+        // Always send the first silent frame, then one per keepalive period
+        let send = self.silent_ms == 0 || self.silent_ms >= DTX_KEEPALIVE_MS;
+        if send && self.silent_ms != 0 {
+            self.silent_ms = 0;
         }
-        
-        // TODO: Replace with real Opus encoding
-        // For now, create a simple compressed representation
+        self.silent_ms += frame_ms.max(1);
+        !send
+    }
+
+    #[cfg(feature = "opus")]
+    fn encode_payload(&mut self, frame: &AudioFrame) -> Result<Bytes> {
+        let samples_per_channel = frame.data.len() / frame.channels.count();
+        // libopus accepts 2.5, 5, 10, 20, 40 and 60 ms frames (in 2.5 ms units)
+        let rate = frame.sample_rate.as_hz() as usize;
+        let valid = [1, 2, 4, 8, 16, 24]
+            .iter()
+            .any(|units| samples_per_channel * 400 == rate * units);
+        if !valid || !frame.data.len().is_multiple_of(frame.channels.count()) {
+            return Err(CodecError::InvalidData(
+                "frame duration must be 2.5, 5, 10, 20, 40 or 60 ms",
+            ));
+        }
+
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        let len = self
+            .inner
+            .encode(&frame.data, &mut packet)
+            .map_err(|e| CodecError::EncodingFailed(e.to_string()))?;
+        packet.truncate(len);
+        Ok(Bytes::from(packet))
+    }
+
+    #[cfg(not(feature = "opus"))]
+    fn encode_payload(&mut self, frame: &AudioFrame) -> Result<Bytes> {
         let mut compressed = Vec::new();
         
         // Header: sample_rate (4 bytes), channels (1 byte), timestamp (8 bytes)
@@ -115,24 +233,147 @@ impl OpusEncoder {
     }
 }
 
-/// Opus audio decoder (stub implementation)
+impl AudioEncoder for OpusEncoder {
+    /// Encode PCM audio data to Opus
+    ///
+    /// Returns an empty payload when DTX suppresses a silent frame; nothing
+    /// needs to be sent for it.
+    fn encode(&mut self, frame: &AudioFrame) -> Result<Bytes> {
+        // Validate frame matches encoder config
+        if frame.sample_rate != self.config.sample_rate {
+            return Err(CodecError::InvalidData("sample rate mismatch"));
+        }
+        if frame.channels != self.config.channels {
+            return Err(CodecError::InvalidData("channel count mismatch"));
+        }
+        
+        // Validate frame size (must have data)
+        if frame.data.is_empty() {
+            return Err(CodecError::InvalidData("empty audio frame"));
+        }
+
+        if self.suppress(frame) {
+            return Ok(Bytes::new());
+        }
+        self.encode_payload(frame)
+    }
+
+    fn set_bitrate(&mut self, bitrate: u32) -> Result<()> {
+        validate_bitrate(bitrate)?;
+        #[cfg(feature = "opus")]
+        self.inner
+            .set_bitrate(::opus::Bitrate::Bits(bitrate as i32))
+            .map_err(|e| CodecError::EncodingFailed(e.to_string()))?;
+        self.config.bitrate = bitrate;
+        Ok(())
+    }
+
+    /// Send in-band FEC sized for `percent` loss when FEC is enabled and
+    /// loss reaches [`FEC_MIN_PACKET_LOSS`], and stop sending it otherwise
+    fn set_packet_loss(&mut self, percent: u8) -> Result<()> {
+        if percent > 100 {
+            return Err(CodecError::InvalidData("packet loss out of range (0-100)"));
+        }
+        let fec = self.config.fec && percent >= FEC_MIN_PACKET_LOSS;
+        #[cfg(feature = "opus")]
+        {
+            self.inner
+                .set_inband_fec(fec)
+                .map_err(|e| CodecError::EncodingFailed(e.to_string()))?;
+            self.inner
+                .set_packet_loss_perc(i32::from(percent))
+                .map_err(|e| CodecError::EncodingFailed(e.to_string()))?;
+        }
+        self.config.expected_packet_loss = percent;
+        self.fec_active = fec;
+        Ok(())
+    }
+}
+
+/// Opus audio decoder
+///
+/// Uses libopus when the `opus` feature is enabled, otherwise decodes the
+/// uncompressed framing produced by [`OpusEncoder`] without it.
 pub struct OpusDecoder {
-    #[allow(dead_code)]
     sample_rate: SampleRate,
-    #[allow(dead_code)]
     channels: Channels,
+    #[cfg(feature = "opus")]
+    inner: ::opus::Decoder,
+    /// Samples per channel of the last decoded frame, used for concealment
+    last_frame_samples: usize,
+    /// Playout position in milliseconds
+    position_ms: u64,
 }
 
 impl OpusDecoder {
     pub fn new(sample_rate: SampleRate, channels: Channels) -> Result<Self> {
+        #[cfg(feature = "opus")]
+        let inner = {
+            let opus_channels = match channels {
+                Channels::Mono => ::opus::Channels::Mono,
+                Channels::Stereo => ::opus::Channels::Stereo,
+            };
+            ::opus::Decoder::new(sample_rate.as_hz(), opus_channels)
+                .map_err(|e| CodecError::InitFailed(e.to_string()))?
+        };
+
         Ok(Self {
             sample_rate,
             channels,
+            #[cfg(feature = "opus")]
+            inner,
+            // 20 ms until a real frame tells us otherwise
+            last_frame_samples: sample_rate.as_hz() as usize / 50,
+            position_ms: 0,
         })
     }
 
+    /// Record a decoded frame's length and advance the playout position
+    fn advance(&mut self, samples_per_channel: usize, timestamp: u64) {
+        if samples_per_channel > 0 {
+            self.last_frame_samples = samples_per_channel;
+        }
+        let duration_ms =
+            samples_per_channel as u64 * 1000 / u64::from(self.sample_rate.as_hz());
+        self.position_ms = timestamp.saturating_add(duration_ms);
+    }
+
+    #[cfg(feature = "opus")]
+    fn decode_packet(&mut self, data: &[u8], fec: bool) -> Result<AudioFrame> {
+        let max_samples = self.sample_rate.as_hz() as usize * MAX_FRAME_MS / 1000;
+        let mut pcm = vec![0i16; max_samples * self.channels.count()];
+        let samples = self
+            .inner
+            .decode(data, &mut pcm, fec)
+            .map_err(|e| CodecError::DecodingFailed(e.to_string()))?;
+        pcm.truncate(samples * self.channels.count());
+        let timestamp = self.position_ms;
+        self.advance(samples, timestamp);
+        Ok(AudioFrame {
+            data: pcm,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            timestamp,
+        })
+    }
+}
+
+impl AudioDecoder for OpusDecoder {
     /// Decode Opus data to PCM audio
-    pub fn decode(&mut self, data: &[u8]) -> Result<AudioFrame> {
+    ///
+    /// With libopus the returned timestamp is the decoder's playout position,
+    /// since Opus packets carry no timing of their own.
+    #[cfg(feature = "opus")]
+    fn decode(&mut self, data: &[u8]) -> Result<AudioFrame> {
+        if data.is_empty() {
+            return Err(CodecError::InvalidData("empty opus packet"));
+        }
+        self.decode_packet(data, false)
+    }
+
+    /// Decode Opus data to PCM audio
+    #[cfg(not(feature = "opus"))]
+    fn decode(&mut self, data: &[u8]) -> Result<AudioFrame> {
         // Minimum size: 4 (sample_rate) + 1 (channels) + 8 (timestamp) + 4 (length)
         const HEADER_SIZE: usize = 17;
         
@@ -189,7 +430,8 @@ impl OpusDecoder {
         if pcm_data.len() != data_len {
             return Err(CodecError::InvalidData("pcm data length mismatch"));
         }
-        
+
+        self.advance(pcm_data.len() / channels.count(), timestamp);
         Ok(AudioFrame {
             data: pcm_data,
             sample_rate,
@@ -197,6 +439,35 @@ impl OpusDecoder {
             timestamp,
         })
     }
+
+    /// Produce audio for a lost packet
+    ///
+    /// With libopus, when `next` is the packet after the lost one and carries
+    /// FEC data, the lost frame is recovered from it; otherwise packet loss
+    /// concealment extrapolates from the previous frame.
+    #[cfg(feature = "opus")]
+    fn conceal(&mut self, next: Option<&[u8]>) -> Result<AudioFrame> {
+        match next {
+            Some(packet) if !packet.is_empty() => self.decode_packet(packet, true),
+            _ => self.decode_packet(&[], false),
+        }
+    }
+
+    /// Produce audio for a lost packet
+    ///
+    /// Without libopus the lost frame is replaced with silence.
+    #[cfg(not(feature = "opus"))]
+    fn conceal(&mut self, _next: Option<&[u8]>) -> Result<AudioFrame> {
+        let samples = self.last_frame_samples;
+        let timestamp = self.position_ms;
+        self.advance(samples, timestamp);
+        Ok(AudioFrame {
+            data: vec![0i16; samples * self.channels.count()],
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            timestamp,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -217,6 +488,7 @@ mod tests {
             sample_rate: SampleRate::Hz16000,
             channels: Channels::Stereo,
             bitrate: 96000,
+            ..Default::default()
         };
         let result = OpusEncoder::new(config);
         assert!(result.is_ok());
@@ -243,6 +515,8 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    // Lossless passthrough framing only
+    #[cfg(not(feature = "opus"))]
     #[test]
     fn test_encode_decode_roundtrip_mono() {
         let config = OpusEncoderConfig::default();
@@ -274,12 +548,15 @@ mod tests {
         assert_eq!(decoded.data, frame.data);
     }
 
+    // Lossless passthrough framing only
+    #[cfg(not(feature = "opus"))]
     #[test]
     fn test_encode_decode_roundtrip_stereo() {
         let config = OpusEncoderConfig {
             sample_rate: SampleRate::Hz48000,
             channels: Channels::Stereo,
             bitrate: 128000,
+            ..Default::default()
         };
         let mut encoder = OpusEncoder::new(config).unwrap();
         let mut decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Stereo).unwrap();
@@ -355,6 +632,8 @@ mod tests {
         assert!(encoder.encode(&frame).is_err());
     }
 
+    // Lossless passthrough framing only
+    #[cfg(not(feature = "opus"))]
     #[test]
     fn test_decoder_corrupted_data() {
         let mut decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
@@ -364,6 +643,8 @@ mod tests {
         assert!(decoder.decode(&corrupted).is_err());
     }
 
+    // Lossless passthrough framing only
+    #[cfg(not(feature = "opus"))]
     #[test]
     fn test_decoder_invalid_sample_rate() {
         let mut decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
@@ -378,6 +659,8 @@ mod tests {
         assert!(decoder.decode(&data).is_err());
     }
 
+    // Lossless passthrough framing only
+    #[cfg(not(feature = "opus"))]
     #[test]
     fn test_decoder_invalid_channels() {
         let mut decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
@@ -392,6 +675,8 @@ mod tests {
         assert!(decoder.decode(&data).is_err());
     }
 
+    // Lossless passthrough framing only
+    #[cfg(not(feature = "opus"))]
     #[test]
     fn test_different_sample_rates() {
         for &sample_rate in &[
@@ -423,6 +708,8 @@ mod tests {
         }
     }
 
+    // Lossless passthrough framing only
+    #[cfg(not(feature = "opus"))]
     #[test]
     fn test_timestamp_preservation() {
         let config = OpusEncoderConfig::default();
@@ -445,6 +732,144 @@ mod tests {
             assert_eq!(decoded.timestamp, ts);
         }
     }
+
+    fn tone(samples: usize, amplitude: f32) -> Vec<i16> {
+        (0..samples)
+            .map(|i| {
+                ((i as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.0).sin() * amplitude) as i16
+            })
+            .collect()
+    }
+
+    fn frame_20ms(data: Vec<i16>, timestamp: u64) -> AudioFrame {
+        AudioFrame {
+            data,
+            sample_rate: SampleRate::Hz48000,
+            channels: Channels::Mono,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_dtx_skips_silence_with_keepalive() {
+        let config = OpusEncoderConfig {
+            dtx: true,
+            ..Default::default()
+        };
+        let mut encoder = OpusEncoder::new(config).unwrap();
+        let silence = frame_20ms(vec![0; 960], 0);
+
+        let sent: Vec<bool> = (0..41)
+            .map(|_| !encoder.encode(&silence).unwrap().is_empty())
+            .collect();
+        // First silent frame, then one every 400 ms (20 frames)
+        assert!(sent[0] && sent[20] && sent[40]);
+        assert_eq!(sent.iter().filter(|s| **s).count(), 3);
+
+        let speech = frame_20ms(tone(960, 8000.0), 0);
+        assert!(!encoder.encode(&speech).unwrap().is_empty());
+        assert!(!encoder.encode(&silence).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dtx_disabled_encodes_silence() {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        let silence = frame_20ms(vec![0; 960], 0);
+        for _ in 0..5 {
+            assert!(!encoder.encode(&silence).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_set_bitrate() {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        encoder.set_bitrate(24000).unwrap();
+        assert_eq!(encoder.config().bitrate, 24000);
+        assert!(encoder.set_bitrate(1000).is_err());
+        assert_eq!(encoder.config().bitrate, 24000);
+    }
+
+    #[test]
+    fn test_set_packet_loss_adapts_fec() {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        encoder.set_packet_loss(0).unwrap();
+        assert!(!encoder.fec_active());
+        encoder.set_packet_loss(7).unwrap();
+        assert!(encoder.fec_active());
+        assert_eq!(encoder.config().expected_packet_loss, 7);
+        assert!(encoder.set_packet_loss(101).is_err());
+        assert_eq!(encoder.config().expected_packet_loss, 7);
+
+        let config = OpusEncoderConfig {
+            fec: false,
+            ..Default::default()
+        };
+        let mut encoder = OpusEncoder::new(config).unwrap();
+        encoder.set_packet_loss(20).unwrap();
+        assert!(!encoder.fec_active());
+    }
+
+    #[test]
+    fn test_invalid_packet_loss() {
+        let config = OpusEncoderConfig {
+            expected_packet_loss: 101,
+            ..Default::default()
+        };
+        assert!(OpusEncoder::new(config).is_err());
+    }
+
+    #[cfg(not(feature = "opus"))]
+    #[test]
+    fn test_conceal_fills_silence() {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        let mut decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+
+        let packet = encoder.encode(&frame_20ms(tone(480, 8000.0), 1000)).unwrap();
+        decoder.decode(&packet).unwrap();
+
+        let lost = decoder.conceal(None).unwrap();
+        assert_eq!(lost.data, vec![0; 480]);
+        assert_eq!(lost.timestamp, 1010);
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn test_opus_compresses_and_decodes() {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        let mut decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+
+        let packet = encoder.encode(&frame_20ms(tone(960, 8000.0), 0)).unwrap();
+        assert!(!packet.is_empty());
+        assert!(packet.len() < 960 * 2);
+
+        let decoded = decoder.decode(&packet).unwrap();
+        assert_eq!(decoded.data.len(), 960);
+        assert_eq!(decoded.timestamp, 0);
+        assert_eq!(decoder.decode(&packet).unwrap().timestamp, 20);
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn test_opus_rejects_invalid_frame_duration() {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        assert!(encoder.encode(&frame_20ms(vec![1; 1000], 0)).is_err());
+    }
+
+    #[cfg(feature = "opus")]
+    #[test]
+    fn test_opus_fec_recovers_lost_frame() {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        let mut decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+
+        let packets: Vec<Bytes> = (0..3)
+            .map(|i| encoder.encode(&frame_20ms(tone(960, 8000.0), i * 20)).unwrap())
+            .collect();
+        decoder.decode(&packets[0]).unwrap();
+        // packets[1] is lost; recover it from the FEC data in packets[2]
+        let recovered = decoder.conceal(Some(&packets[2])).unwrap();
+        assert_eq!(recovered.data.len(), 960);
+        assert_eq!(decoder.conceal(None).unwrap().data.len(), 960);
+    }
 }
 
 #[cfg(test)]
@@ -471,6 +896,7 @@ mod proptests {
     }
 
     proptest! {
+        #[cfg(not(feature = "opus"))]
         #[test]
         fn prop_encode_decode_roundtrip(
             sample_rate in sample_rate_strategy(),
@@ -479,7 +905,7 @@ mod proptests {
             timestamp in any::<u64>(),
             audio_len in 1usize..=10000,
         ) {
-            let config = OpusEncoderConfig { sample_rate, channels, bitrate };
+            let config = OpusEncoderConfig { sample_rate, channels, bitrate, ..Default::default() };
             let mut encoder = OpusEncoder::new(config)?;
             let mut decoder = OpusDecoder::new(sample_rate, channels)?;
             
@@ -512,6 +938,7 @@ mod proptests {
                     sample_rate: encoder_rate,
                     channels: encoder_channels,
                     bitrate: 64000,
+                    ..Default::default()
                 };
                 let mut encoder = OpusEncoder::new(config)?;
                 
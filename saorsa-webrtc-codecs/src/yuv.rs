@@ -0,0 +1,123 @@
+//! Planar YUV 4:2:0 frames and RGB conversion
+
+use crate::convert::{self, PackedFormat};
+use crate::{CodecError, Result, VideoFrame};
+
+/// Planar YUV 4:2:0 (I420) frame, BT.601 limited range
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YuvFrame {
+    pub width: u32,
+    pub height: u32,
+    /// Luma plane, `width * height` bytes
+    pub y: Vec<u8>,
+    /// Cb plane, `ceil(width / 2) * ceil(height / 2)` bytes
+    pub u: Vec<u8>,
+    /// Cr plane, same size as `u`
+    pub v: Vec<u8>,
+    pub timestamp: u64,
+}
+
+impl YuvFrame {
+    /// Width and height of the chroma planes
+    pub fn chroma_dimensions(&self) -> (usize, usize) {
+        chroma_dimensions(self.width, self.height)
+    }
+
+    /// Check that the plane sizes match the dimensions
+    pub fn validate(&self) -> Result<()> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (cw, ch) = self.chroma_dimensions();
+        if self.y.len() != width * height || self.u.len() != cw * ch || self.v.len() != cw * ch {
+            return Err(CodecError::InvalidData(
+                "yuv planes do not match dimensions",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Convert an RGB24 frame, averaging chroma over each 2x2 block
+    pub fn from_rgb(frame: &VideoFrame) -> Result<Self> {
+        convert::packed_to_i420(
+            &frame.data,
+            PackedFormat::Rgb24,
+            frame.width,
+            frame.height,
+            frame.timestamp,
+        )
+    }
+
+    /// Convert to an RGB24 frame
+    pub fn to_rgb(&self) -> Result<VideoFrame> {
+        Ok(VideoFrame {
+            data: convert::i420_to_packed(self, PackedFormat::Rgb24)?,
+            width: self.width,
+            height: self.height,
+            timestamp: self.timestamp,
+        })
+    }
+}
+
+/// Copy a plane out of a strided buffer, dropping row padding
+#[cfg_attr(not(any(feature = "h264", feature = "av1")), allow(dead_code))]
+pub(crate) fn copy_plane(
+    plane: &[u8],
+    stride: usize,
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(width * height);
+    for row in 0..height {
+        let start = row * stride;
+        let line = plane
+            .get(start..start + width)
+            .ok_or(CodecError::InvalidData("decoded plane too small"))?;
+        out.extend_from_slice(line);
+    }
+    Ok(out)
+}
+
+pub(crate) fn chroma_dimensions(width: u32, height: u32) -> (usize, usize) {
+    (width.div_ceil(2) as usize, height.div_ceil(2) as usize)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn rgb_frame(width: u32, height: u32, pixel: [u8; 3]) -> VideoFrame {
+        VideoFrame {
+            data: pixel.repeat((width * height) as usize),
+            width,
+            height,
+            timestamp: 42,
+        }
+    }
+
+    #[test]
+    fn test_rgb_yuv_roundtrip_is_close() {
+        for pixel in [[0, 0, 0], [255, 255, 255], [200, 30, 90], [12, 180, 240]] {
+            let frame = rgb_frame(4, 4, pixel);
+            let back = YuvFrame::from_rgb(&frame).unwrap().to_rgb().unwrap();
+            assert_eq!(back.timestamp, 42);
+            for (a, b) in back.data.iter().zip(&frame.data) {
+                assert!((i32::from(*a) - i32::from(*b)).abs() <= 3, "{:?}", pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn test_odd_dimensions() {
+        let yuv = YuvFrame::from_rgb(&rgb_frame(5, 3, [10, 20, 30])).unwrap();
+        assert_eq!(yuv.y.len(), 15);
+        assert_eq!(yuv.u.len(), 6);
+        assert_eq!(yuv.to_rgb().unwrap().data.len(), 45);
+    }
+
+    #[test]
+    fn test_rejects_mismatched_data() {
+        let mut frame = rgb_frame(4, 4, [0, 0, 0]);
+        frame.data.pop();
+        assert!(YuvFrame::from_rgb(&frame).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+//! Active speaker detection and simulcast layer selection
+//!
+//! In SFU mode each receiver gets the dominant speaker's high-resolution
+//! simulcast layer and low-resolution thumbnails of a bounded number of
+//! other participants, so downlink usage stays flat as conferences grow.
+//! The dominant speaker is picked from RFC 6464 audio levels with
+//! smoothing and a hold time so brief noises do not cause switching.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// RFC 6464 level for silence (-127 dBov)
+const SILENT_LEVEL: u8 = 127;
+
+/// Simulcast encoding layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SimulcastLayer {
+    /// Thumbnail resolution
+    Low,
+    /// Half resolution
+    Medium,
+    /// Full resolution
+    High,
+}
+
+impl SimulcastLayer {
+    /// RTP stream ID conventionally used for this layer
+    #[must_use]
+    pub const fn rid(&self) -> &'static str {
+        match self {
+            Self::Low => "q",
+            Self::Medium => "h",
+            Self::High => "f",
+        }
+    }
+}
+
+/// Active speaker detection settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActiveSpeakerConfig {
+    /// Loudness (127 minus RFC 6464 level) below which a participant is silent
+    pub speech_threshold: u8,
+    /// Minimum time between dominant speaker switches
+    pub hold_ms: u64,
+    /// How far a challenger must exceed the current speaker to take over
+    pub switch_margin: f32,
+    /// Weight of the newest sample in the smoothed level (0..1)
+    pub smoothing: f32,
+    /// Maximum thumbnails forwarded to each receiver
+    pub max_thumbnails: usize,
+}
+
+impl Default for ActiveSpeakerConfig {
+    fn default() -> Self {
+        Self {
+            speech_threshold: 40,
+            hold_ms: 1500,
+            switch_margin: 6.0,
+            smoothing: 0.3,
+            max_thumbnails: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SpeakerLevel {
+    loudness: f32,
+    last_spoke_ms: u64,
+}
+
+/// Tracks audio levels and picks the dominant speaker
+#[derive(Debug, Clone, Default)]
+pub struct ActiveSpeakerDetector {
+    config: ActiveSpeakerConfig,
+    levels: HashMap<String, SpeakerLevel>,
+    dominant: Option<String>,
+    last_switch_ms: Option<u64>,
+}
+
+impl ActiveSpeakerDetector {
+    /// Create a detector
+    #[must_use]
+    pub fn new(config: ActiveSpeakerConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Settings in use
+    #[must_use]
+    pub fn config(&self) -> &ActiveSpeakerConfig {
+        &self.config
+    }
+
+    /// Current dominant speaker
+    #[must_use]
+    pub fn dominant(&self) -> Option<&str> {
+        self.dominant.as_deref()
+    }
+
+    /// Feed an RFC 6464 audio level (0 loudest, 127 silent)
+    ///
+    /// Returns the new dominant speaker if it changed.
+    pub fn observe(&mut self, participant_id: &str, level: u8, now_ms: u64) -> Option<String> {
+        let loudness = f32::from(SILENT_LEVEL.saturating_sub(level.min(SILENT_LEVEL)));
+        let alpha = self.config.smoothing.clamp(0.0, 1.0);
+        let entry = self
+            .levels
+            .entry(participant_id.to_string())
+            .or_insert(SpeakerLevel {
+                loudness: 0.0,
+                last_spoke_ms: 0,
+            });
+        entry.loudness = alpha * loudness + (1.0 - alpha) * entry.loudness;
+        if entry.loudness >= f32::from(self.config.speech_threshold) {
+            entry.last_spoke_ms = now_ms;
+        }
+        self.reselect(now_ms)
+    }
+
+    /// Forget a participant that left
+    pub fn remove(&mut self, participant_id: &str) {
+        self.levels.remove(participant_id);
+        if self.dominant.as_deref() == Some(participant_id) {
+            self.dominant = None;
+        }
+    }
+
+    /// Participants ordered by most recent speech
+    #[must_use]
+    pub fn recent_speakers(&self) -> Vec<String> {
+        let mut speakers: Vec<_> = self.levels.iter().collect();
+        speakers.sort_by(|a, b| {
+            b.1.last_spoke_ms
+                .cmp(&a.1.last_spoke_ms)
+                .then_with(|| a.0.cmp(b.0))
+        });
+        speakers.into_iter().map(|(id, _)| id.clone()).collect()
+    }
+
+    fn reselect(&mut self, now_ms: u64) -> Option<String> {
+        let threshold = f32::from(self.config.speech_threshold);
+        let (candidate, loudness) = self
+            .levels
+            .iter()
+            .filter(|(_, level)| level.loudness >= threshold)
+            .max_by(|a, b| a.1.loudness.total_cmp(&b.1.loudness))
+            .map(|(id, level)| (id.clone(), level.loudness))?;
+
+        if self.dominant.as_deref() == Some(candidate.as_str()) {
+            return None;
+        }
+        if let Some(current) = self.dominant.as_ref().and_then(|id| self.levels.get(id)) {
+            let held = self
+                .last_switch_ms
+                .is_some_and(|at| now_ms.saturating_sub(at) < self.config.hold_ms);
+            if held || loudness < current.loudness + self.config.switch_margin {
+                return None;
+            }
+        }
+
+        self.dominant = Some(candidate.clone());
+        self.last_switch_ms = Some(now_ms);
+        Some(candidate)
+    }
+}
+
+/// Choose which layer of each sender to forward to `receiver_id`
+///
+/// `senders` are the participants allowed to publish video, in order of
+/// most recent speech. The dominant speaker gets [`SimulcastLayer::High`];
+/// when the receiver is the dominant speaker, the next most recent speaker
+/// is shown large instead. Up to `max_thumbnails` others get
+/// [`SimulcastLayer::Low`] and the rest are not forwarded.
+#[must_use]
+pub fn select_layers(
+    receiver_id: &str,
+    dominant: Option<&str>,
+    senders: &[String],
+    max_thumbnails: usize,
+) -> Vec<(String, SimulcastLayer)> {
+    let others: Vec<&String> = senders
+        .iter()
+        .filter(|id| id.as_str() != receiver_id)
+        .collect();
+    let featured = dominant
+        .filter(|id| *id != receiver_id)
+        .and_then(|id| others.iter().find(|s| s.as_str() == id).copied())
+        .or_else(|| others.first().copied());
+
+    let mut layers = Vec::with_capacity(others.len().min(max_thumbnails + 1));
+    if let Some(featured) = featured {
+        layers.push((featured.clone(), SimulcastLayer::High));
+    }
+    layers.extend(
+        others
+            .iter()
+            .filter(|id| Some(**id) != featured)
+            .take(max_thumbnails)
+            .map(|id| ((*id).clone(), SimulcastLayer::Low)),
+    );
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOUD: u8 = 20;
+    const QUIET: u8 = 120;
+
+    fn speak(detector: &mut ActiveSpeakerDetector, id: &str, level: u8, from_ms: u64, to_ms: u64) {
+        for t in (from_ms..to_ms).step_by(20) {
+            detector.observe(id, level, t);
+        }
+    }
+
+    #[test]
+    fn test_detects_dominant_speaker() {
+        let mut detector = ActiveSpeakerDetector::default();
+        speak(&mut detector, "alice", LOUD, 0, 500);
+        speak(&mut detector, "bob", QUIET, 0, 500);
+        assert_eq!(detector.dominant(), Some("alice"));
+    }
+
+    #[test]
+    fn test_brief_noise_does_not_switch() {
+        let mut detector = ActiveSpeakerDetector::default();
+        speak(&mut detector, "alice", LOUD, 0, 500);
+
+        // Bob coughs once while alice keeps talking
+        assert_eq!(detector.observe("bob", 0, 520), None);
+        assert_eq!(detector.dominant(), Some("alice"));
+    }
+
+    #[test]
+    fn test_switches_after_hold_time() {
+        let mut detector = ActiveSpeakerDetector::default();
+        speak(&mut detector, "alice", LOUD, 0, 500);
+        let mut switched_at = None;
+        for t in (500..4000).step_by(20) {
+            detector.observe("alice", QUIET, t);
+            detector.observe("bob", LOUD, t);
+            if detector.dominant() == Some("bob") {
+                switched_at = Some(t);
+                break;
+            }
+        }
+
+        let switched_at = switched_at.unwrap();
+        assert!(switched_at >= 1500);
+        assert_eq!(detector.dominant(), Some("bob"));
+        assert_eq!(detector.recent_speakers()[0], "bob");
+    }
+
+    #[test]
+    fn test_select_layers_bounds_thumbnails() {
+        let senders: Vec<String> = (0..12).map(|i| format!("p{}", i)).collect();
+        let layers = select_layers("p0", Some("p5"), &senders, 4);
+
+        assert_eq!(layers.len(), 5);
+        assert_eq!(layers[0], ("p5".to_string(), SimulcastLayer::High));
+        assert!(layers[1..].iter().all(|(_, l)| *l == SimulcastLayer::Low));
+        assert!(layers.iter().all(|(id, _)| id != "p0"));
+    }
+
+    #[test]
+    fn test_dominant_speaker_sees_someone_else() {
+        let senders = vec!["alice".to_string(), "bob".to_string(), "carol".to_string()];
+        let layers = select_layers("alice", Some("alice"), &senders, 8);
+        assert_eq!(layers[0], ("bob".to_string(), SimulcastLayer::High));
+        assert_eq!(layers[1], ("carol".to_string(), SimulcastLayer::Low));
+    }
+}
@@ -0,0 +1,344 @@
+//! Screen share annotations
+//!
+//! Each screen share can be paired with a data channel labelled
+//! [`annotation_channel_label`] that carries the sharer's and viewers'
+//! cursor positions and drawings, so every frontend renders remote
+//! pointers and markup the same way.
+//!
+//! # Wire format
+//!
+//! One [`Annotation`] per data channel message, fields little-endian:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 1 | version, [`ANNOTATION_WIRE_VERSION`] |
+//! | 1 | kind: 1 cursor, 2 cursor hidden, 3 stroke, 4 erase, 5 clear |
+//! | .. | body |
+//!
+//! Bodies by kind:
+//!
+//! - cursor: `x: u16, y: u16`
+//! - stroke: `id: u32, color: u32, width: u16, count: u16`, then `count`
+//!   points of `x: u16, y: u16`
+//! - erase: `id: u32`
+//! - cursor hidden and clear have no body
+//!
+//! Coordinates are normalized to the shared surface: 0 is the left or top
+//! edge and 65535 the right or bottom edge, so they survive scaling.
+//! Stroke width uses the same units along the x axis and colors are
+//! `0xRRGGBBAA`. Long strokes are sent in segments as they are drawn; a
+//! stroke whose `id` was already seen continues that stroke. Bytes after
+//! the body are ignored so later versions can extend a message.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::wire::{Truncated, WireReader};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Version written in the first byte of every message
+pub const ANNOTATION_WIRE_VERSION: u8 = 1;
+
+/// Label prefix reserved for annotation data channels
+pub const ANNOTATION_CHANNEL_PREFIX: &str = "saorsa-annotations/";
+
+/// Most points a single stroke segment can carry
+pub const MAX_STROKE_POINTS: usize = u16::MAX as usize;
+
+const KIND_CURSOR: u8 = 1;
+const KIND_CURSOR_HIDDEN: u8 = 2;
+const KIND_STROKE: u8 = 3;
+const KIND_ERASE: u8 = 4;
+const KIND_CLEAR: u8 = 5;
+
+/// Annotation encoding and decoding errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum AnnotationError {
+    /// Message ended before its body did
+    #[error("Annotation message truncated")]
+    Truncated,
+
+    /// Message written by an incompatible version
+    #[error("Unsupported annotation version: {0}")]
+    UnsupportedVersion(u8),
+
+    /// Message kind not known to this version
+    #[error("Unknown annotation kind: {0}")]
+    UnknownKind(u8),
+
+    /// Stroke segment has more points than fit in one message
+    #[error("Stroke has {0} points, more than {MAX_STROKE_POINTS}")]
+    TooManyPoints(usize),
+}
+
+impl CodedError for AnnotationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::TooManyPoints(_) => ErrorCode::PacketTooLarge,
+            Self::Truncated | Self::UnsupportedVersion(_) | Self::UnknownKind(_) => {
+                ErrorCode::MalformedMessage
+            }
+        }
+    }
+}
+
+impl From<Truncated> for AnnotationError {
+    fn from(_: Truncated) -> Self {
+        Self::Truncated
+    }
+}
+
+/// Position on the shared surface in normalized units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AnnotationPoint {
+    /// 0 at the left edge, 65535 at the right
+    pub x: u16,
+    /// 0 at the top edge, 65535 at the bottom
+    pub y: u16,
+}
+
+impl AnnotationPoint {
+    /// Point from fractions of the surface size, clamped to its edges
+    #[must_use]
+    pub fn from_normalized(x: f32, y: f32) -> Self {
+        let scale = |v: f32| (v.clamp(0.0, 1.0) * f32::from(u16::MAX)).round() as u16;
+        Self {
+            x: scale(x),
+            y: scale(y),
+        }
+    }
+
+    /// Point as fractions of the surface size
+    #[must_use]
+    pub fn to_normalized(self) -> (f32, f32) {
+        let max = f32::from(u16::MAX);
+        (f32::from(self.x) / max, f32::from(self.y) / max)
+    }
+}
+
+/// One annotation message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Annotation {
+    /// Pointer moved
+    Cursor(AnnotationPoint),
+    /// Pointer left the shared surface
+    CursorHidden,
+    /// Points drawn on a stroke, continuing it if `id` was seen before
+    Stroke {
+        /// Stroke identifier, unique per sender
+        id: u32,
+        /// Color as `0xRRGGBBAA`
+        color: u32,
+        /// Line width in normalized units
+        width: u16,
+        /// Points in drawing order
+        points: Vec<AnnotationPoint>,
+    },
+    /// Remove one stroke
+    Erase {
+        /// Stroke to remove
+        id: u32,
+    },
+    /// Remove every stroke from the sender
+    Clear,
+}
+
+impl Annotation {
+    /// Encode as one data channel message
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a stroke has more than [`MAX_STROKE_POINTS`] points
+    pub fn encode(&self) -> Result<Vec<u8>, AnnotationError> {
+        let mut buf = Vec::with_capacity(16);
+        buf.push(ANNOTATION_WIRE_VERSION);
+        match self {
+            Self::Cursor(point) => {
+                buf.push(KIND_CURSOR);
+                put_point(&mut buf, *point);
+            }
+            Self::CursorHidden => buf.push(KIND_CURSOR_HIDDEN),
+            Self::Stroke {
+                id,
+                color,
+                width,
+                points,
+            } => {
+                let count = u16::try_from(points.len())
+                    .map_err(|_| AnnotationError::TooManyPoints(points.len()))?;
+                buf.reserve(12 + 4 * points.len());
+                buf.push(KIND_STROKE);
+                buf.extend_from_slice(&id.to_le_bytes());
+                buf.extend_from_slice(&color.to_le_bytes());
+                buf.extend_from_slice(&width.to_le_bytes());
+                buf.extend_from_slice(&count.to_le_bytes());
+                for point in points {
+                    put_point(&mut buf, *point);
+                }
+            }
+            Self::Erase { id } => {
+                buf.push(KIND_ERASE);
+                buf.extend_from_slice(&id.to_le_bytes());
+            }
+            Self::Clear => buf.push(KIND_CLEAR),
+        }
+        Ok(buf)
+    }
+
+    /// Decode one data channel message
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the message is truncated, from another wire
+    /// version or of an unknown kind
+    pub fn decode(data: &[u8]) -> Result<Self, AnnotationError> {
+        let mut reader = WireReader::new(data);
+        let version = reader.u8()?;
+        if version != ANNOTATION_WIRE_VERSION {
+            return Err(AnnotationError::UnsupportedVersion(version));
+        }
+        match reader.u8()? {
+            KIND_CURSOR => Ok(Self::Cursor(read_point(&mut reader)?)),
+            KIND_CURSOR_HIDDEN => Ok(Self::CursorHidden),
+            KIND_STROKE => {
+                let id = reader.u32()?;
+                let color = reader.u32()?;
+                let width = reader.u16()?;
+                let count = usize::from(reader.u16()?);
+                if reader.remaining() < count * 4 {
+                    return Err(AnnotationError::Truncated);
+                }
+                let points = (0..count)
+                    .map(|_| read_point(&mut reader))
+                    .collect::<Result<_, _>>()?;
+                Ok(Self::Stroke {
+                    id,
+                    color,
+                    width,
+                    points,
+                })
+            }
+            KIND_ERASE => Ok(Self::Erase { id: reader.u32()? }),
+            KIND_CLEAR => Ok(Self::Clear),
+            kind => Err(AnnotationError::UnknownKind(kind)),
+        }
+    }
+}
+
+/// Label of the annotation data channel paired with a screen share track
+#[must_use]
+pub fn annotation_channel_label(track_id: &str) -> String {
+    format!("{ANNOTATION_CHANNEL_PREFIX}{track_id}")
+}
+
+/// Screen share track an annotation channel label belongs to, if it is one
+#[must_use]
+pub fn annotated_track(label: &str) -> Option<&str> {
+    label
+        .strip_prefix(ANNOTATION_CHANNEL_PREFIX)
+        .filter(|track_id| !track_id.is_empty())
+}
+
+fn put_point(buf: &mut Vec<u8>, point: AnnotationPoint) {
+    buf.extend_from_slice(&point.x.to_le_bytes());
+    buf.extend_from_slice(&point.y.to_le_bytes());
+}
+
+fn read_point(reader: &mut WireReader<'_>) -> Result<AnnotationPoint, Truncated> {
+    Ok(AnnotationPoint {
+        x: reader.u16()?,
+        y: reader.u16()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: u16, y: u16) -> AnnotationPoint {
+        AnnotationPoint { x, y }
+    }
+
+    #[test]
+    fn test_annotations_round_trip() {
+        let annotations = [
+            Annotation::Cursor(point(100, 65535)),
+            Annotation::CursorHidden,
+            Annotation::Stroke {
+                id: 7,
+                color: 0xff00_00ff,
+                width: 300,
+                points: vec![point(1, 2), point(3, 4)],
+            },
+            Annotation::Erase { id: 7 },
+            Annotation::Clear,
+        ];
+        for annotation in annotations {
+            let encoded = annotation.encode().unwrap();
+            assert_eq!(Annotation::decode(&encoded).unwrap(), annotation);
+        }
+    }
+
+    #[test]
+    fn test_wire_format_is_stable() {
+        let cursor = Annotation::Cursor(point(0x0102, 0x0304));
+        assert_eq!(cursor.encode().unwrap(), [1, 1, 0x02, 0x01, 0x04, 0x03]);
+
+        let stroke = Annotation::Stroke {
+            id: 1,
+            color: 0x1122_3344,
+            width: 5,
+            points: vec![point(6, 7)],
+        };
+        assert_eq!(
+            stroke.encode().unwrap(),
+            [1, 3, 1, 0, 0, 0, 0x44, 0x33, 0x22, 0x11, 5, 0, 1, 0, 6, 0, 7, 0]
+        );
+
+        // Trailing bytes from a later version are ignored
+        assert_eq!(
+            Annotation::decode(&[1, 5, 0xaa]).unwrap(),
+            Annotation::Clear
+        );
+    }
+
+    #[test]
+    fn test_malformed_messages_are_rejected() {
+        assert_eq!(Annotation::decode(&[]), Err(AnnotationError::Truncated));
+        assert_eq!(
+            Annotation::decode(&[2, 5]),
+            Err(AnnotationError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            Annotation::decode(&[1, 9]),
+            Err(AnnotationError::UnknownKind(9))
+        );
+        assert_eq!(
+            Annotation::decode(&[1, 3, 1, 0, 0, 0, 0, 0, 0, 0, 5, 0, 2, 0, 6, 0, 7, 0]),
+            Err(AnnotationError::Truncated)
+        );
+
+        let huge = Annotation::Stroke {
+            id: 1,
+            color: 0,
+            width: 1,
+            points: vec![point(0, 0); MAX_STROKE_POINTS + 1],
+        };
+        assert_eq!(
+            huge.encode(),
+            Err(AnnotationError::TooManyPoints(MAX_STROKE_POINTS + 1))
+        );
+    }
+
+    #[test]
+    fn test_channel_labels_pair_with_tracks() {
+        let label = annotation_channel_label("screen-2");
+        assert_eq!(label, "saorsa-annotations/screen-2");
+        assert_eq!(annotated_track(&label), Some("screen-2"));
+        assert_eq!(annotated_track("saorsa-annotations/"), None);
+        assert_eq!(annotated_track("chat"), None);
+
+        let corner = AnnotationPoint::from_normalized(1.5, -0.2);
+        assert_eq!(corner, point(u16::MAX, 0));
+        assert_eq!(corner.to_normalized(), (1.0, 0.0));
+    }
+}
@@ -0,0 +1,664 @@
+//! Microphone capture
+//!
+//! With the `audio-capture` feature, input devices are listed and opened
+//! through cpal at the [`AudioClock`]'s rate, 48 kHz by default, or at
+//! their own rate if they cannot run at it. Captured samples are downmixed
+//! to mono, converted to the clock's rate and cut into frames of its size
+//! by an [`AudioFramer`], passed through a [`PushToTalkGate`],
+//! Opus-encoded and written to a local audio track until the
+//! [`AudioCapture`] handle is dropped. A [`MediaWatchdog`] watches the
+//! device and encoder, and the capture reopens or resets whichever stalls.
+//! [`AudioCapture::switch_device`] moves a running capture to another
+//! microphone, so the track and encoder carry on across the change.
+//!
+//! cpal has no stable device IDs, so a device's name doubles as its ID.
+//! Without the feature no devices are listed and capture fails.
+
+use crate::audio_clock::AudioClock;
+use crate::audio_level::AudioLevelNode;
+use crate::media::{AudioDevice, MediaError};
+use crate::pipeline::{PacketLossFeedback, PushToTalkGate};
+use crate::watchdog::MediaWatchdog;
+use saorsa_webrtc_codecs::{AudioFrame, Channels};
+use std::sync::Arc;
+use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
+
+/// Rate microphones are opened at by default, in Hz
+pub const CAPTURE_SAMPLE_RATE: u32 = 48_000;
+
+/// Default length of each encoded frame, in milliseconds
+pub const CAPTURE_FRAME_MS: u64 = 20;
+
+/// Sample buffers queued between the audio thread and the encoder
+#[cfg(feature = "audio-capture")]
+const CAPTURE_QUEUE: usize = 64;
+
+/// Cuts interleaved capture buffers into mono frames
+#[derive(Debug, Clone)]
+pub struct AudioFramer {
+    channels: usize,
+    clock: AudioClock,
+    /// Rate buffers arrive at, in Hz
+    input_rate: u32,
+    /// Position of the next output sample between `previous` and the next
+    /// input sample, in input samples
+    phase: f64,
+    previous: Option<f32>,
+    pending: Vec<i16>,
+    timestamp_ms: u64,
+}
+
+impl AudioFramer {
+    /// Create a framer for buffers with `channels` interleaved channels,
+    /// cutting 20 ms frames at 48 kHz
+    #[must_use]
+    pub fn new(channels: u16) -> Self {
+        Self::with_clock(channels, AudioClock::default())
+    }
+
+    /// Create a framer cutting frames of `clock`'s size and rate
+    #[must_use]
+    pub fn with_clock(channels: u16, clock: AudioClock) -> Self {
+        Self {
+            channels: usize::from(channels.max(1)),
+            clock,
+            input_rate: clock.sample_rate.as_hz(),
+            phase: 0.0,
+            previous: None,
+            pending: Vec::with_capacity(clock.samples_per_frame()),
+            timestamp_ms: 0,
+        }
+    }
+
+    /// Take buffers captured at `rate` Hz, converting them to the clock's
+    /// rate
+    #[must_use]
+    pub fn with_input_rate(mut self, rate: u32) -> Self {
+        self.input_rate = rate.max(1);
+        self
+    }
+
+    /// Add a capture buffer, returning every frame it completes
+    ///
+    /// Channels are averaged into one; a trailing partial sample is dropped.
+    pub fn push(&mut self, interleaved: &[i16]) -> Vec<AudioFrame> {
+        let mut frames = Vec::new();
+        let output_rate = self.clock.sample_rate.as_hz();
+        let step = f64::from(self.input_rate) / f64::from(output_rate);
+        for sample in interleaved.chunks_exact(self.channels) {
+            let sum: i32 = sample.iter().map(|s| i32::from(*s)).sum();
+            // The mean of i16 values always fits in an i16
+            let mono = (sum / self.channels as i32) as i16;
+            if self.input_rate == output_rate {
+                self.emit(mono, &mut frames);
+                continue;
+            }
+            // Interpolate between consecutive input samples
+            let current = f32::from(mono);
+            let Some(previous) = self.previous.replace(current) else {
+                continue;
+            };
+            while self.phase < 1.0 {
+                let value = previous + (current - previous) * self.phase as f32;
+                self.emit(value.round() as i16, &mut frames);
+                self.phase += step;
+            }
+            self.phase -= 1.0;
+        }
+        frames
+    }
+
+    /// Take buffers from a device with `channels` channels at `rate` Hz
+    ///
+    /// Partly filled frames are kept and timestamps carry on, so the
+    /// encoder sees no gap across a device change.
+    #[cfg_attr(not(feature = "audio-capture"), allow(dead_code))]
+    fn switch_input(mut self, channels: u16, rate: u32) -> Self {
+        self.channels = usize::from(channels.max(1));
+        self.phase = 0.0;
+        self.previous = None;
+        self.with_input_rate(rate)
+    }
+
+    fn emit(&mut self, sample: i16, frames: &mut Vec<AudioFrame>) {
+        let frame_samples = self.clock.samples_per_frame();
+        self.pending.push(sample);
+        if self.pending.len() == frame_samples {
+            frames.push(AudioFrame {
+                data: std::mem::replace(&mut self.pending, Vec::with_capacity(frame_samples)),
+                sample_rate: self.clock.sample_rate,
+                channels: Channels::Mono,
+                timestamp: self.timestamp_ms,
+            });
+            self.timestamp_ms += self.clock.frame_size.millis();
+        }
+    }
+}
+
+/// Request to move a capture to another device, answered with the device
+/// opened
+type SwitchRequest = (
+    Option<String>,
+    tokio::sync::oneshot::Sender<Result<AudioDevice, MediaError>>,
+);
+
+/// Running microphone capture; capture stops when this is dropped
+pub struct AudioCapture {
+    device: AudioDevice,
+    track_id: String,
+    switch: tokio::sync::mpsc::Sender<SwitchRequest>,
+    /// Dropping this tells the capture task to close the stream
+    _stop: tokio::sync::oneshot::Sender<()>,
+}
+
+impl AudioCapture {
+    /// Device being captured
+    #[must_use]
+    pub fn device(&self) -> &AudioDevice {
+        &self.device
+    }
+
+    /// Track the encoded audio is written to
+    #[must_use]
+    pub fn track_id(&self) -> &str {
+        &self.track_id
+    }
+
+    /// Capture from `device_id`, or the default microphone, instead
+    ///
+    /// The track, encoder and frame timing carry on; only the device
+    /// changes, at whatever rate it runs. If the new device cannot be
+    /// opened the current one is kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the device cannot be found or opened, or capture
+    /// has stopped
+    pub async fn switch_device(
+        &mut self,
+        device_id: Option<&str>,
+    ) -> Result<&AudioDevice, MediaError> {
+        let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+        let stopped = || MediaError::Capture(format!("capture of {} stopped", self.track_id));
+        self.switch
+            .send((device_id.map(str::to_string), reply_tx))
+            .await
+            .map_err(|_| stopped())?;
+        self.device = reply_rx.await.map_err(|_| stopped())??;
+        Ok(&self.device)
+    }
+
+    /// Stop capturing
+    pub fn stop(self) {}
+}
+
+impl std::fmt::Debug for AudioCapture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioCapture")
+            .field("device", &self.device)
+            .field("track_id", &self.track_id)
+            .finish()
+    }
+}
+
+/// Microphones attached to this machine
+///
+/// # Errors
+///
+/// Returns error if the audio host cannot list its devices
+#[cfg(feature = "audio-capture")]
+pub fn input_devices() -> Result<Vec<AudioDevice>, MediaError> {
+    backend::input_devices()
+}
+
+/// Microphones attached to this machine
+///
+/// Always empty without the `audio-capture` feature.
+///
+/// # Errors
+///
+/// Never fails without the `audio-capture` feature
+#[cfg(not(feature = "audio-capture"))]
+pub fn input_devices() -> Result<Vec<AudioDevice>, MediaError> {
+    Ok(Vec::new())
+}
+
+/// A device stream owned by its capture thread
+#[cfg(feature = "audio-capture")]
+struct CaptureStream {
+    samples: tokio::sync::mpsc::Receiver<Vec<i16>>,
+    /// Dropping this tells the capture thread to close the stream
+    _stop: std::sync::mpsc::Sender<()>,
+}
+
+/// Open `device_id`, or the default microphone, on its own thread,
+/// preferring `sample_rate`
+///
+/// Returns the stream, device, channel count and the rate it runs at.
+#[cfg(feature = "audio-capture")]
+async fn open_stream(
+    device_id: Option<String>,
+    sample_rate: u32,
+) -> Result<(CaptureStream, AudioDevice, u16, u32), MediaError> {
+    use tokio::sync::{mpsc, oneshot};
+
+    let (sample_tx, samples) = mpsc::channel::<Vec<i16>>(CAPTURE_QUEUE);
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+
+    // cpal streams cannot move between threads, so one thread owns it
+    std::thread::Builder::new()
+        .name("audio-capture".to_string())
+        .spawn(
+            move || match backend::open(device_id.as_deref(), sample_rate, sample_tx) {
+                Ok((device, channels, rate, stream)) => {
+                    let _ = ready_tx.send(Ok((device, channels, rate)));
+                    // Returns once the stream's sender is dropped
+                    let _ = stop_rx.recv();
+                    drop(stream);
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                }
+            },
+        )
+        .map_err(|e| MediaError::Capture(e.to_string()))?;
+    let (device, channels, rate) = ready_rx
+        .await
+        .map_err(|_| MediaError::Capture("capture thread exited".to_string()))??;
+    let stream = CaptureStream {
+        samples,
+        _stop: stop_tx,
+    };
+    Ok((stream, device, channels, rate))
+}
+
+/// Capture `device_id`, or the default microphone, into `track`
+#[cfg(feature = "audio-capture")]
+pub(crate) async fn start(
+    device_id: Option<&str>,
+    track: Arc<TrackLocalStaticSample>,
+    track_id: String,
+    level: AudioLevelNode,
+    gate: PushToTalkGate,
+    loss: PacketLossFeedback,
+    clock: AudioClock,
+    watchdog: Arc<MediaWatchdog>,
+) -> Result<AudioCapture, MediaError> {
+    use crate::pipeline::{
+        AudioEncoderNode, MediaPayload, MediaPipeline, PipelineStage, AUDIO_LEVEL_NODE,
+        ENCODER_NODE, PUSH_TO_TALK_NODE,
+    };
+    use crate::watchdog::{watch_encoder, Heartbeat, Recovery, WatchTarget};
+    use saorsa_webrtc_codecs::{OpusEncoder, OpusEncoderConfig};
+    use tokio::sync::{mpsc, oneshot};
+    use webrtc::media::Sample;
+
+    let encoder_node = move |loss: PacketLossFeedback| {
+        let config = OpusEncoderConfig {
+            sample_rate: clock.sample_rate,
+            ..OpusEncoderConfig::default()
+        };
+        OpusEncoder::new(config)
+            .map(|encoder| AudioEncoderNode::new(Box::new(encoder)).with_loss_feedback(loss))
+            .map_err(|e| MediaError::Capture(e.to_string()))
+    };
+    let encoder = encoder_node(loss.clone())?;
+    let encoder_beat = Heartbeat::new();
+    let mut pipeline = MediaPipeline::new();
+    // Metered ahead of the gate so speaking while muted still shows
+    pipeline
+        .add(PipelineStage::Process, AUDIO_LEVEL_NODE, Box::new(level))
+        .and_then(|()| pipeline.add(PipelineStage::Process, PUSH_TO_TALK_NODE, Box::new(gate)))
+        .and_then(|()| pipeline.add(PipelineStage::Encode, ENCODER_NODE, Box::new(encoder)))
+        .and_then(|()| watch_encoder(&mut pipeline, &encoder_beat))
+        .map_err(|e| MediaError::Capture(e.to_string()))?;
+    let mut device_id = device_id.map(str::to_string);
+    let sample_rate = clock.sample_rate.as_hz();
+    let (stream, device, channels, rate) = open_stream(device_id.clone(), sample_rate).await?;
+    tracing::info!(
+        "Capturing {} ({} channels at {} Hz) into track {}",
+        device.name,
+        channels,
+        rate,
+        track_id
+    );
+
+    // Recoveries are carried out by the capture task, which owns the stream
+    // and pipeline
+    let (recover_tx, mut recover_rx) = mpsc::channel::<Recovery>(4);
+    let capture_beat = Heartbeat::new();
+    let capture_target = WatchTarget::Capture(track_id.clone());
+    let encoder_target = WatchTarget::Encoder(track_id.clone());
+    for (target, heartbeat) in [
+        (capture_target.clone(), capture_beat.clone()),
+        (encoder_target.clone(), encoder_beat),
+    ] {
+        let recover_tx = recover_tx.clone();
+        watchdog.watch(target, heartbeat, move |recovery| {
+            let _ = recover_tx.try_send(recovery);
+        });
+    }
+    drop(recover_tx);
+
+    let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+    let (switch_tx, mut switch_rx) = mpsc::channel::<SwitchRequest>(1);
+    let task_track_id = track_id.clone();
+    tokio::spawn(async move {
+        let mut stream = Some(stream);
+        let mut framer = AudioFramer::with_clock(channels, clock).with_input_rate(rate);
+        loop {
+            let samples = tokio::select! {
+                // Resolves once the handle is dropped
+                _ = &mut stop_rx => break,
+                Some((switch_to, reply)) = switch_rx.recv() => {
+                    // Release the current device first, as some hosts
+                    // cannot open two streams of one device
+                    let previous = stream.take();
+                    match open_stream(switch_to.clone(), sample_rate).await {
+                        Ok((opened, device, channels, rate)) => {
+                            tracing::info!(
+                                "Switched {} to {} ({} channels at {} Hz)",
+                                task_track_id,
+                                device.name,
+                                channels,
+                                rate
+                            );
+                            drop(previous);
+                            stream = Some(opened);
+                            framer = framer.switch_input(channels, rate);
+                            device_id = switch_to;
+                            let _ = reply.send(Ok(device));
+                        }
+                        Err(e) => {
+                            stream = previous;
+                            let _ = reply.send(Err(e));
+                        }
+                    }
+                    continue;
+                }
+                Some(recovery) = recover_rx.recv() => {
+                    match recovery {
+                        Recovery::ReopenDevice => {
+                            // Release the device before opening it again
+                            stream = None;
+                            match open_stream(device_id.clone(), sample_rate).await {
+                                Ok((reopened, device, channels, rate)) => {
+                                    tracing::info!(
+                                        "Reopened {} for {}",
+                                        device.name,
+                                        task_track_id
+                                    );
+                                    stream = Some(reopened);
+                                    framer = framer.switch_input(channels, rate);
+                                }
+                                Err(e) => {
+                                    tracing::warn!(
+                                        "Failed to reopen microphone for {}: {}",
+                                        task_track_id,
+                                        e
+                                    );
+                                }
+                            }
+                        }
+                        _ => {
+                            pipeline.remove(ENCODER_NODE);
+                            let reset = encoder_node(loss.clone()).and_then(|encoder| {
+                                pipeline
+                                    .add(PipelineStage::Encode, ENCODER_NODE, Box::new(encoder))
+                                    .map_err(|e| MediaError::Capture(e.to_string()))
+                            });
+                            if let Err(e) = reset {
+                                tracing::warn!(
+                                    "Failed to reset encoder of {}: {}",
+                                    task_track_id,
+                                    e
+                                );
+                            }
+                        }
+                    }
+                    continue;
+                }
+                samples = async { stream.as_mut()?.samples.recv().await },
+                    if stream.is_some() => samples,
+            };
+            let Some(samples) = samples else {
+                // The stream died; the watchdog reopens it
+                stream = None;
+                continue;
+            };
+            capture_beat.frame_out();
+            for frame in framer.push(&samples) {
+                let encoded = match pipeline.push(MediaPayload::Audio(frame)) {
+                    Ok(Some(MediaPayload::Encoded(encoded))) => encoded,
+                    // Gated, or nothing to send under DTX
+                    Ok(_) => continue,
+                    Err(e) => {
+                        tracing::warn!("Failed to encode captured audio: {}", e);
+                        continue;
+                    }
+                };
+                let sample = Sample {
+                    data: encoded.data,
+                    duration: clock.frame_duration(),
+                    ..Default::default()
+                };
+                if let Err(e) = track.write_sample(&sample).await {
+                    tracing::warn!("Failed to write audio to {}: {}", task_track_id, e);
+                }
+            }
+        }
+        watchdog.unwatch(&capture_target);
+        watchdog.unwatch(&encoder_target);
+        tracing::debug!("Audio capture for {} stopped", task_track_id);
+    });
+
+    Ok(AudioCapture {
+        device,
+        track_id,
+        switch: switch_tx,
+        _stop: stop_tx,
+    })
+}
+
+/// Capture `device_id`, or the default microphone, into `track`
+#[cfg(not(feature = "audio-capture"))]
+pub(crate) async fn start(
+    _device_id: Option<&str>,
+    _track: Arc<TrackLocalStaticSample>,
+    _track_id: String,
+    _level: AudioLevelNode,
+    _gate: PushToTalkGate,
+    _loss: PacketLossFeedback,
+    _clock: AudioClock,
+    _watchdog: Arc<MediaWatchdog>,
+) -> Result<AudioCapture, MediaError> {
+    Err(MediaError::Capture(
+        "built without the `audio-capture` feature".to_string(),
+    ))
+}
+
+#[cfg(feature = "audio-capture")]
+mod backend {
+    use crate::media::{AudioDevice, MediaError};
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, Sample, SizedSample};
+    use tokio::sync::mpsc;
+
+    fn capture_error(e: impl std::fmt::Display) -> MediaError {
+        MediaError::Capture(e.to_string())
+    }
+
+    pub(super) fn input_devices() -> Result<Vec<AudioDevice>, MediaError> {
+        let devices = cpal::default_host()
+            .input_devices()
+            .map_err(capture_error)?;
+        Ok(devices
+            .filter_map(|device| device.name().ok())
+            .map(|name| AudioDevice {
+                id: name.clone(),
+                name,
+            })
+            .collect())
+    }
+
+    fn find_device(device_id: Option<&str>) -> Result<cpal::Device, MediaError> {
+        let host = cpal::default_host();
+        match device_id {
+            None => host
+                .default_input_device()
+                .ok_or_else(|| MediaError::DeviceNotFound("default input".to_string())),
+            Some(id) => host
+                .input_devices()
+                .map_err(capture_error)?
+                .find(|device| device.name().is_ok_and(|name| name == id))
+                .ok_or_else(|| MediaError::DeviceNotFound(id.to_string())),
+        }
+    }
+
+    /// Open a device, sending its samples to `samples`
+    ///
+    /// Runs at `sample_rate` Hz if the device can, otherwise at its default
+    /// rate. Returns the device, its channel count, the rate and the running
+    /// stream.
+    pub(super) fn open(
+        device_id: Option<&str>,
+        sample_rate: u32,
+        samples: mpsc::Sender<Vec<i16>>,
+    ) -> Result<(AudioDevice, u16, u32, cpal::Stream), MediaError> {
+        let device = find_device(device_id)?;
+        let name = device.name().map_err(capture_error)?;
+        let rate = cpal::SampleRate(sample_rate);
+        let preferred = device
+            .supported_input_configs()
+            .map_err(capture_error)?
+            .find(|range| range.min_sample_rate() <= rate && rate <= range.max_sample_rate())
+            .map(|range| range.with_sample_rate(rate));
+        let supported = match preferred {
+            Some(supported) => supported,
+            // Headsets often only run at 44.1 kHz or 16 kHz
+            None => device.default_input_config().map_err(capture_error)?,
+        };
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build::<f32>(&device, &config, samples),
+            cpal::SampleFormat::I16 => build::<i16>(&device, &config, samples),
+            cpal::SampleFormat::U16 => build::<u16>(&device, &config, samples),
+            format => Err(MediaError::Capture(format!(
+                "unsupported sample format {format:?}"
+            ))),
+        }?;
+        stream.play().map_err(capture_error)?;
+        Ok((
+            AudioDevice {
+                id: name.clone(),
+                name,
+            },
+            config.channels,
+            config.sample_rate.0,
+            stream,
+        ))
+    }
+
+    fn build<S>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        samples: mpsc::Sender<Vec<i16>>,
+    ) -> Result<cpal::Stream, MediaError>
+    where
+        S: SizedSample,
+        i16: FromSample<S>,
+    {
+        device
+            .build_input_stream(
+                config,
+                move |data: &[S], _: &cpal::InputCallbackInfo| {
+                    // Never block the audio thread; drop audio if encoding
+                    // falls behind
+                    let _ = samples.try_send(data.iter().map(|s| i16::from_sample(*s)).collect());
+                },
+                |e| tracing::warn!("Audio capture error: {}", e),
+                None,
+            )
+            .map_err(capture_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_framer_downmixes_into_20ms_frames() {
+        let mut framer = AudioFramer::new(2);
+        // 15 ms of stereo: left 100, right 300
+        let buffer: Vec<i16> = [100, 300].repeat(720);
+        assert!(framer.push(&buffer).is_empty());
+
+        let frames = framer.push(&buffer);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].data.len(), 960);
+        assert!(frames[0].data.iter().all(|s| *s == 200));
+        assert_eq!(frames[0].channels, Channels::Mono);
+        assert_eq!(frames[0].timestamp, 0);
+
+        let frames = framer.push(&buffer);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].timestamp, CAPTURE_FRAME_MS);
+    }
+
+    #[test]
+    fn test_framer_keeps_extremes_in_range() {
+        let mut framer = AudioFramer::new(2);
+        let frames = framer.push(&[i16::MIN, i16::MIN].repeat(960));
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0].data.iter().all(|s| *s == i16::MIN));
+    }
+
+    #[test]
+    fn test_framer_follows_clock() {
+        use crate::audio_clock::OpusFrameSize;
+        use saorsa_webrtc_codecs::SampleRate;
+
+        let clock = AudioClock {
+            sample_rate: SampleRate::Hz16000,
+            frame_size: OpusFrameSize::Ms10,
+        };
+        let mut framer = AudioFramer::with_clock(1, clock);
+        let frames = framer.push(&[7; 480]);
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|f| f.data.len() == 160));
+        assert!(frames.iter().all(|f| f.sample_rate == SampleRate::Hz16000));
+        assert_eq!(frames[2].timestamp, 20);
+    }
+
+    #[test]
+    fn test_framer_converts_device_rate() {
+        // A 44.1 kHz headset feeding a 48 kHz clock
+        let mut framer = AudioFramer::new(1).with_input_rate(44_100);
+        let frames = framer.push(&[1000; 44_100]);
+        // One second of audio, less the sample held for interpolation
+        assert!((49..=50).contains(&frames.len()));
+        assert!(frames.iter().all(|f| f.data.len() == 960));
+        assert!(frames.iter().flat_map(|f| &f.data).all(|s| *s == 1000));
+        assert_eq!(frames[1].timestamp, 20);
+    }
+
+    #[test]
+    fn test_framer_keeps_timing_across_devices() {
+        let framer = AudioFramer::new(1);
+        let mut framer = framer.switch_input(2, 16_000);
+        let frames = framer.push(&[500; 2 * 16_000]);
+        assert!((49..=50).contains(&frames.len()));
+        let mut framer = framer.switch_input(1, 48_000);
+        let next = framer.push(&[0; 960]);
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].timestamp, frames.last().unwrap().timestamp + 20);
+    }
+
+    #[test]
+    #[cfg(not(feature = "audio-capture"))]
+    fn test_no_devices_without_feature() {
+        assert!(input_devices().unwrap().is_empty());
+    }
+}
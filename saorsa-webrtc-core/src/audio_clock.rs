@@ -0,0 +1,151 @@
+//! Audio frame size and clock configuration
+//!
+//! An [`AudioClock`] fixes the rate microphones are captured at and how
+//! long each Opus frame is, and everything that counts audio in frames
+//! takes it from there: the capture framer, the encoder, RTP timestamps
+//! handed to the [`Packetizer`](crate::quic_bridge::Packetizer) and the
+//! depth of the [`AudioJitterBuffer`](crate::jitter::AudioJitterBuffer).
+//! Shorter frames cut delay at the cost of more packets and header
+//! overhead; longer ones do the opposite.
+
+use saorsa_webrtc_codecs::SampleRate;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// RTP clock rate of Opus, whatever rate it is captured at (RFC 7587)
+pub const OPUS_RTP_CLOCK_HZ: u32 = 48_000;
+
+/// Length of each Opus frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OpusFrameSize {
+    /// 10 ms, for the lowest delay
+    Ms10,
+    /// 20 ms, the usual trade-off
+    #[default]
+    Ms20,
+    /// 40 ms
+    Ms40,
+    /// 60 ms, for the least overhead
+    Ms60,
+}
+
+impl OpusFrameSize {
+    /// Every frame size, shortest first
+    pub const ALL: [Self; 4] = [Self::Ms10, Self::Ms20, Self::Ms40, Self::Ms60];
+
+    /// Frame size lasting `millis`, if there is one
+    #[must_use]
+    pub fn from_millis(millis: u64) -> Option<Self> {
+        Self::ALL.into_iter().find(|size| size.millis() == millis)
+    }
+
+    /// Frame length in milliseconds
+    #[must_use]
+    pub fn millis(self) -> u64 {
+        match self {
+            Self::Ms10 => 10,
+            Self::Ms20 => 20,
+            Self::Ms40 => 40,
+            Self::Ms60 => 60,
+        }
+    }
+
+    /// Frame length
+    #[must_use]
+    pub fn duration(self) -> Duration {
+        Duration::from_millis(self.millis())
+    }
+}
+
+/// Capture rate and frame size shared by the audio path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioClock {
+    /// Rate microphones are captured and encoded at
+    pub sample_rate: SampleRate,
+    /// Length of each frame
+    pub frame_size: OpusFrameSize,
+}
+
+impl AudioClock {
+    /// 48 kHz clock with frames of `frame_size`
+    #[must_use]
+    pub fn new(frame_size: OpusFrameSize) -> Self {
+        Self {
+            sample_rate: SampleRate::Hz48000,
+            frame_size,
+        }
+    }
+
+    /// Length of each frame
+    #[must_use]
+    pub fn frame_duration(&self) -> Duration {
+        self.frame_size.duration()
+    }
+
+    /// Samples per channel in one frame
+    #[must_use]
+    pub fn samples_per_frame(&self) -> usize {
+        (self.sample_rate.as_hz() as usize / 1000) * self.frame_size.millis() as usize
+    }
+
+    /// Frames needed to cover `duration`, at least one
+    #[must_use]
+    pub fn frames_in(&self, duration: Duration) -> usize {
+        let millis = duration.as_millis() as u64;
+        (millis.div_ceil(self.frame_size.millis()) as usize).max(1)
+    }
+
+    /// RTP timestamp of audio captured `capture_ms` into the stream
+    ///
+    /// Wraps like RTP timestamps do.
+    #[must_use]
+    pub fn rtp_timestamp(&self, capture_ms: u64) -> u32 {
+        capture_ms.wrapping_mul(u64::from(OPUS_RTP_CLOCK_HZ / 1000)) as u32
+    }
+
+    /// RTP timestamp advance from one frame to the next
+    #[must_use]
+    pub fn rtp_ticks_per_frame(&self) -> u32 {
+        self.rtp_timestamp(self.frame_size.millis())
+    }
+}
+
+impl Default for AudioClock {
+    fn default() -> Self {
+        Self::new(OpusFrameSize::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_sizes_in_samples_and_ticks() {
+        let expected = [(480, 480), (960, 960), (1920, 1920), (2880, 2880)];
+        for (size, (samples, ticks)) in OpusFrameSize::ALL.into_iter().zip(expected) {
+            let clock = AudioClock::new(size);
+            assert_eq!(clock.samples_per_frame(), samples);
+            assert_eq!(clock.rtp_ticks_per_frame(), ticks);
+            assert_eq!(OpusFrameSize::from_millis(size.millis()), Some(size));
+        }
+        assert_eq!(OpusFrameSize::from_millis(30), None);
+
+        // RTP ticks stay at 48 kHz when capturing at a lower rate
+        let narrowband = AudioClock {
+            sample_rate: SampleRate::Hz16000,
+            frame_size: OpusFrameSize::Ms20,
+        };
+        assert_eq!(narrowband.samples_per_frame(), 320);
+        assert_eq!(narrowband.rtp_ticks_per_frame(), 960);
+    }
+
+    #[test]
+    fn test_frames_in_rounds_up() {
+        let clock = AudioClock::new(OpusFrameSize::Ms40);
+        assert_eq!(clock.frames_in(Duration::from_millis(60)), 2);
+        assert_eq!(clock.frames_in(Duration::from_millis(80)), 2);
+        assert_eq!(clock.frames_in(Duration::ZERO), 1);
+        assert_eq!(clock.rtp_timestamp(u64::from(u32::MAX)), 4_294_967_248);
+    }
+}
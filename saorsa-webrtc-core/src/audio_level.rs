@@ -0,0 +1,225 @@
+//! Audio level metering and voice activity detection
+//!
+//! An [`AudioLevelMeter`] measures the RMS level of each audio frame and
+//! decides whether someone is speaking, holding the decision for a few
+//! frames after the level drops so pauses between words do not flicker.
+//! Levels are reported a few times a second, and straight away when voice
+//! activity starts or stops, as [`MediaEvent::AudioLevel`]: microphones
+//! through an [`AudioLevelNode`] in their pipeline, received audio as it
+//! is played.
+//!
+//! [`MediaEvent::AudioLevel`]: crate::media::MediaEvent::AudioLevel
+
+use crate::pipeline::{level_dbfs, MediaPayload, PipelineError, PipelineNode};
+use crate::types::CallId;
+use serde::{Deserialize, Serialize};
+
+/// Level reported for digital silence
+pub const SILENCE_DBFS: f32 = -96.0;
+
+/// Voice activity detection and reporting settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioLevelConfig {
+    /// RMS level, in dBFS, at or above which a frame counts as speech
+    pub speech_threshold_dbfs: f32,
+    /// Frames voice activity is held after the level drops
+    pub hangover_frames: u32,
+    /// Frames between level reports while voice activity is unchanged
+    pub report_every: u32,
+}
+
+impl Default for AudioLevelConfig {
+    fn default() -> Self {
+        Self {
+            speech_threshold_dbfs: -50.0,
+            // 300 ms and 100 ms of 20 ms frames
+            hangover_frames: 15,
+            report_every: 5,
+        }
+    }
+}
+
+/// Where metered audio comes from
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AudioLevelSource {
+    /// One of our audio tracks
+    Local(String),
+    /// Audio received on a call
+    Remote(CallId),
+}
+
+/// One level report
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AudioLevel {
+    /// Loudest frame RMS since the last report, in dBFS
+    pub level_dbfs: f32,
+    /// Whether someone is speaking
+    pub voice_active: bool,
+}
+
+impl AudioLevel {
+    /// Level as RFC 6464 expresses it (0 loudest, 127 silent), as fed to
+    /// [`ActiveSpeakerDetector::observe`](crate::active_speaker::ActiveSpeakerDetector::observe)
+    #[must_use]
+    pub fn rfc6464(&self) -> u8 {
+        (-self.level_dbfs).round().clamp(0.0, 127.0) as u8
+    }
+}
+
+/// Measures frames and decides when a level is due
+#[derive(Debug, Clone)]
+pub struct AudioLevelMeter {
+    config: AudioLevelConfig,
+    voice_active: bool,
+    quiet_frames: u32,
+    frames_since_report: u32,
+    peak_dbfs: f32,
+}
+
+impl AudioLevelMeter {
+    /// Create a meter that starts out silent
+    #[must_use]
+    pub fn new(config: AudioLevelConfig) -> Self {
+        Self {
+            config,
+            voice_active: false,
+            quiet_frames: 0,
+            frames_since_report: 0,
+            peak_dbfs: SILENCE_DBFS,
+        }
+    }
+
+    /// Whether someone is speaking as of the last frame
+    #[must_use]
+    pub fn voice_active(&self) -> bool {
+        self.voice_active
+    }
+
+    /// Measure a frame of samples, returning a level if one is due
+    pub fn measure(&mut self, samples: &[i16]) -> Option<AudioLevel> {
+        let level = level_dbfs(samples);
+        self.peak_dbfs = self.peak_dbfs.max(level);
+        self.frames_since_report += 1;
+
+        let was_active = self.voice_active;
+        if level >= self.config.speech_threshold_dbfs {
+            self.voice_active = true;
+            self.quiet_frames = 0;
+        } else if self.voice_active {
+            self.quiet_frames += 1;
+            if self.quiet_frames > self.config.hangover_frames {
+                self.voice_active = false;
+            }
+        }
+
+        if self.voice_active == was_active && self.frames_since_report < self.config.report_every {
+            return None;
+        }
+        let report = AudioLevel {
+            level_dbfs: self.peak_dbfs,
+            voice_active: self.voice_active,
+        };
+        self.frames_since_report = 0;
+        self.peak_dbfs = SILENCE_DBFS;
+        Some(report)
+    }
+}
+
+impl Default for AudioLevelMeter {
+    fn default() -> Self {
+        Self::new(AudioLevelConfig::default())
+    }
+}
+
+/// Meters raw audio passing through a pipeline, leaving it untouched
+pub struct AudioLevelNode {
+    meter: AudioLevelMeter,
+    report: Box<dyn FnMut(AudioLevel) + Send>,
+}
+
+impl AudioLevelNode {
+    /// Meter with `config`, handing each due level to `report`
+    #[must_use]
+    pub fn new(config: AudioLevelConfig, report: impl FnMut(AudioLevel) + Send + 'static) -> Self {
+        Self {
+            meter: AudioLevelMeter::new(config),
+            report: Box::new(report),
+        }
+    }
+}
+
+impl PipelineNode for AudioLevelNode {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        if let MediaPayload::Audio(frame) = &payload {
+            if let Some(level) = self.meter.measure(&frame.data) {
+                (self.report)(level);
+            }
+        }
+        Ok(Some(payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_webrtc_codecs::{AudioFrame, Channels, SampleRate};
+    use std::sync::{Arc, Mutex};
+
+    const SPEECH: [i16; 960] = [8000; 960];
+    const QUIET: [i16; 960] = [0; 960];
+
+    #[test]
+    fn test_voice_activity_holds_through_short_pauses() {
+        let mut meter = AudioLevelMeter::new(AudioLevelConfig {
+            hangover_frames: 2,
+            report_every: 100,
+            ..AudioLevelConfig::default()
+        });
+        assert_eq!(meter.measure(&QUIET), None);
+
+        let onset = meter.measure(&SPEECH).unwrap();
+        assert!(onset.voice_active);
+        assert!((onset.level_dbfs - -12.25).abs() < 0.1);
+        assert_eq!(onset.rfc6464(), 12);
+
+        assert_eq!(meter.measure(&QUIET), None);
+        assert_eq!(meter.measure(&QUIET), None);
+        let offset = meter.measure(&QUIET).unwrap();
+        assert!(!offset.voice_active);
+        assert_eq!(offset.level_dbfs, SILENCE_DBFS);
+        assert_eq!(offset.rfc6464(), 96);
+    }
+
+    #[test]
+    fn test_levels_are_reported_periodically_with_the_peak() {
+        let mut meter = AudioLevelMeter::new(AudioLevelConfig {
+            speech_threshold_dbfs: 0.0,
+            report_every: 3,
+            ..AudioLevelConfig::default()
+        });
+        assert_eq!(meter.measure(&QUIET), None);
+        assert_eq!(meter.measure(&SPEECH), None);
+        let level = meter.measure(&QUIET).unwrap();
+        assert!(!level.voice_active);
+        assert!(level.level_dbfs > -13.0);
+    }
+
+    #[test]
+    fn test_node_reports_and_passes_audio_through() {
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&levels);
+        let mut node = AudioLevelNode::new(AudioLevelConfig::default(), move |level| {
+            sink.lock().unwrap().push(level)
+        });
+
+        let frame = AudioFrame {
+            data: SPEECH.to_vec(),
+            sample_rate: SampleRate::Hz48000,
+            channels: Channels::Mono,
+            timestamp: 0,
+        };
+        let out = node.process(MediaPayload::Audio(frame.clone())).unwrap();
+        assert!(matches!(out, Some(MediaPayload::Audio(f)) if f.data == frame.data));
+        assert_eq!(levels.lock().unwrap().len(), 1);
+    }
+}
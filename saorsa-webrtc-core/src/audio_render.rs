@@ -0,0 +1,592 @@
+//! Playback of received audio
+//!
+//! Decoded remote frames, e.g. from an
+//! [`AudioJitterBuffer`](crate::jitter::AudioJitterBuffer), are pushed into
+//! an [`AudioRenderer`] per call. Each call's audio is resampled to the
+//! output device's rate and channel layout, scaled by the call's volume and
+//! mixed with the others in an [`AudioMixer`], which the device drains.
+//! [`AudioRenderer::switch_device`] moves playback to another device,
+//! reformatting the mixer to its rate and layout, without losing volumes.
+//!
+//! With the `audio-playback` feature output devices are listed and opened
+//! through cpal; cpal has no stable device IDs, so a device's name doubles
+//! as its ID. Without the feature no devices are listed and playback fails.
+
+use crate::media::{AudioDevice, MediaError};
+use crate::types::CallId;
+use parking_lot::Mutex;
+use saorsa_webrtc_codecs::AudioFrame;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// Most audio buffered per call, in milliseconds; older audio is dropped
+/// so a stalled device cannot build up delay
+pub const MAX_PLAYBACK_BUFFER_MS: u32 = 200;
+
+/// Loudest volume a call can be set to
+pub const MAX_VOLUME: f32 = 2.0;
+
+/// Linear-interpolation resampler to a fixed output rate and layout
+///
+/// Keeps its position between frames so consecutive frames join without
+/// clicks. Mono input is duplicated to every output channel; stereo input
+/// is averaged down to mono or copied to the first two channels.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    out_rate: u32,
+    out_channels: usize,
+    in_rate: u32,
+    /// Position of the next output sample, in input samples after `prev`
+    position: f64,
+    /// Last input sample of the previous frame, per output channel
+    prev: Option<Vec<f32>>,
+}
+
+impl Resampler {
+    /// Resample to `out_rate` Hz with `out_channels` interleaved channels
+    #[must_use]
+    pub fn new(out_rate: u32, out_channels: u16) -> Self {
+        Self {
+            out_rate: out_rate.max(1),
+            out_channels: usize::from(out_channels.max(1)),
+            in_rate: 0,
+            position: 0.0,
+            prev: None,
+        }
+    }
+
+    /// Convert a frame to interleaved `f32` samples in the output format
+    pub fn process(&mut self, frame: &AudioFrame) -> Vec<f32> {
+        let in_rate = frame.sample_rate.as_hz();
+        if in_rate != self.in_rate {
+            self.in_rate = in_rate;
+            self.position = 0.0;
+            self.prev = None;
+        }
+
+        let in_channels = frame.channels.count();
+        let mut samples: Vec<Vec<f32>> = self.prev.take().into_iter().collect();
+        samples.extend(frame.data.chunks_exact(in_channels).map(|sample| {
+            let sample: Vec<f32> = sample
+                .iter()
+                .map(|s| f32::from(*s) / f32::from(i16::MAX))
+                .collect();
+            self.map_channels(&sample)
+        }));
+        let Some(last) = samples.last().cloned() else {
+            return Vec::new();
+        };
+
+        let step = f64::from(in_rate) / f64::from(self.out_rate);
+        let mut out = Vec::new();
+        while self.position + 1.0 < samples.len() as f64 {
+            let index = self.position as usize;
+            let t = (self.position - index as f64) as f32;
+            for (a, b) in samples[index].iter().zip(&samples[index + 1]) {
+                out.push(a + (b - a) * t);
+            }
+            self.position += step;
+        }
+        self.position -= (samples.len() - 1) as f64;
+        self.prev = Some(last);
+        out
+    }
+
+    fn map_channels(&self, sample: &[f32]) -> Vec<f32> {
+        match (sample.len(), self.out_channels) {
+            (n, m) if n == m => sample.to_vec(),
+            (_, 1) => vec![sample.iter().sum::<f32>() / sample.len() as f32],
+            (1, m) => vec![sample[0]; m],
+            (n, m) => (0..m)
+                .map(|c| if c < n { sample[c] } else { 0.0 })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Source {
+    resampler: Resampler,
+    queue: VecDeque<f32>,
+    volume: f32,
+}
+
+/// Mixes the audio of every call into one output stream
+#[derive(Debug)]
+pub struct AudioMixer {
+    sample_rate: u32,
+    channels: u16,
+    max_buffered: usize,
+    sources: HashMap<CallId, Source>,
+}
+
+impl AudioMixer {
+    /// Mix to `sample_rate` Hz with `channels` interleaved channels
+    #[must_use]
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1);
+        Self {
+            sample_rate,
+            channels,
+            max_buffered: Self::max_buffered(sample_rate, channels),
+            sources: HashMap::new(),
+        }
+    }
+
+    fn max_buffered(sample_rate: u32, channels: u16) -> usize {
+        (sample_rate * MAX_PLAYBACK_BUFFER_MS / 1000) as usize * usize::from(channels)
+    }
+
+    /// Rate, in Hz, and channel count being mixed to
+    #[must_use]
+    pub fn format(&self) -> (u32, u16) {
+        (self.sample_rate, self.channels)
+    }
+
+    /// Mix to `sample_rate` Hz with `channels` interleaved channels from now
+    /// on
+    ///
+    /// Volumes are kept; audio queued in the old format is dropped.
+    pub fn set_format(&mut self, sample_rate: u32, channels: u16) {
+        let channels = channels.max(1);
+        if (sample_rate, channels) == self.format() {
+            return;
+        }
+        self.sample_rate = sample_rate;
+        self.channels = channels;
+        self.max_buffered = Self::max_buffered(sample_rate, channels);
+        for source in self.sources.values_mut() {
+            source.resampler = Resampler::new(sample_rate, channels);
+            source.queue.clear();
+        }
+    }
+
+    fn source(&mut self, call_id: CallId) -> &mut Source {
+        let (sample_rate, channels) = (self.sample_rate, self.channels);
+        self.sources.entry(call_id).or_insert_with(|| Source {
+            resampler: Resampler::new(sample_rate, channels),
+            queue: VecDeque::new(),
+            volume: 1.0,
+        })
+    }
+
+    /// Queue a decoded frame of a call's audio
+    pub fn push(&mut self, call_id: CallId, frame: &AudioFrame) {
+        let max_buffered = self.max_buffered;
+        let source = self.source(call_id);
+        let samples = source.resampler.process(frame);
+        source.queue.extend(samples);
+        let excess = source.queue.len().saturating_sub(max_buffered);
+        source.queue.drain(..excess);
+    }
+
+    /// Set a call's volume, from 0 (muted) to [`MAX_VOLUME`]
+    pub fn set_volume(&mut self, call_id: CallId, volume: f32) {
+        let volume = if volume.is_finite() {
+            volume.clamp(0.0, MAX_VOLUME)
+        } else {
+            1.0
+        };
+        self.source(call_id).volume = volume;
+    }
+
+    /// A call's volume; 1 unless set
+    #[must_use]
+    pub fn volume(&self, call_id: CallId) -> f32 {
+        self.sources.get(&call_id).map_or(1.0, |s| s.volume)
+    }
+
+    /// Stop playing a call and forget its volume
+    pub fn remove(&mut self, call_id: CallId) {
+        self.sources.remove(&call_id);
+    }
+
+    /// Samples queued for a call
+    #[must_use]
+    pub fn buffered(&self, call_id: CallId) -> usize {
+        self.sources.get(&call_id).map_or(0, |s| s.queue.len())
+    }
+
+    /// Fill an interleaved output buffer, padding with silence when calls
+    /// have nothing queued
+    pub fn fill(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        for source in self.sources.values_mut() {
+            let available = source.queue.len().min(out.len());
+            for (slot, sample) in out.iter_mut().zip(source.queue.drain(..available)) {
+                *slot += sample * source.volume;
+            }
+        }
+        for slot in out.iter_mut() {
+            *slot = slot.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// Plays received call audio on an output device; playback stops when
+/// this is dropped
+pub struct AudioRenderer {
+    device: AudioDevice,
+    mixer: Arc<Mutex<AudioMixer>>,
+    /// Dropping this tells the playback thread to close the stream
+    _stop: std::sync::mpsc::Sender<()>,
+}
+
+impl AudioRenderer {
+    /// Device being played to
+    #[must_use]
+    pub fn device(&self) -> &AudioDevice {
+        &self.device
+    }
+
+    /// Queue a decoded frame of a call's audio for playback
+    pub fn push(&self, call_id: CallId, frame: &AudioFrame) {
+        self.mixer.lock().push(call_id, frame);
+    }
+
+    /// Set a call's volume, from 0 (muted) to [`MAX_VOLUME`]
+    pub fn set_volume(&self, call_id: CallId, volume: f32) {
+        self.mixer.lock().set_volume(call_id, volume);
+    }
+
+    /// A call's volume; 1 unless set
+    #[must_use]
+    pub fn volume(&self, call_id: CallId) -> f32 {
+        self.mixer.lock().volume(call_id)
+    }
+
+    /// Stop playing a call
+    pub fn remove_call(&self, call_id: CallId) {
+        self.mixer.lock().remove(call_id);
+    }
+
+    /// Play to `device_id`, or the default output device, instead
+    ///
+    /// Calls and their volumes carry on, resampled to the new device's
+    /// format. If the new device cannot be opened the current one is kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the device cannot be found or opened
+    pub async fn switch_device(
+        &mut self,
+        device_id: Option<&str>,
+    ) -> Result<&AudioDevice, MediaError> {
+        let (device, stop) = open_output(device_id, Arc::clone(&self.mixer)).await?;
+        tracing::info!("Switched call audio to {}", device.name);
+        self.device = device;
+        // Replacing the sender closes the old device's stream
+        self._stop = stop;
+        Ok(&self.device)
+    }
+
+    /// Stop playback
+    pub fn stop(self) {}
+}
+
+impl std::fmt::Debug for AudioRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AudioRenderer")
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+/// Speakers and headsets attached to this machine
+///
+/// # Errors
+///
+/// Returns error if the audio host cannot list its devices
+#[cfg(feature = "audio-playback")]
+pub fn output_devices() -> Result<Vec<AudioDevice>, MediaError> {
+    backend::output_devices()
+}
+
+/// Speakers and headsets attached to this machine
+///
+/// Always empty without the `audio-playback` feature.
+///
+/// # Errors
+///
+/// Never fails without the `audio-playback` feature
+#[cfg(not(feature = "audio-playback"))]
+pub fn output_devices() -> Result<Vec<AudioDevice>, MediaError> {
+    Ok(Vec::new())
+}
+
+/// Play to `device_id`, or the default output device
+pub(crate) async fn start(device_id: Option<&str>) -> Result<AudioRenderer, MediaError> {
+    // Reformatted to the device's own format once it opens
+    let mixer = Arc::new(Mutex::new(AudioMixer::new(48_000, 2)));
+    let (device, stop) = open_output(device_id, Arc::clone(&mixer)).await?;
+    tracing::info!("Playing call audio on {}", device.name);
+
+    Ok(AudioRenderer {
+        device,
+        mixer,
+        _stop: stop,
+    })
+}
+
+/// Open `device_id`, or the default output device, on its own thread,
+/// draining `mixer` until the returned sender is dropped
+#[cfg(feature = "audio-playback")]
+async fn open_output(
+    device_id: Option<&str>,
+    mixer: Arc<Mutex<AudioMixer>>,
+) -> Result<(AudioDevice, std::sync::mpsc::Sender<()>), MediaError> {
+    use tokio::sync::oneshot;
+
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+    let device_id = device_id.map(str::to_string);
+
+    // cpal streams cannot move between threads, so one thread owns it
+    std::thread::Builder::new()
+        .name("audio-playback".to_string())
+        .spawn(move || match backend::open(device_id.as_deref(), &mixer) {
+            Ok((device, stream)) => {
+                let _ = ready_tx.send(Ok(device));
+                // Returns once the renderer, and with it the sender, is dropped
+                let _ = stop_rx.recv();
+                drop(stream);
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+            }
+        })
+        .map_err(|e| MediaError::Capture(e.to_string()))?;
+    let device = ready_rx
+        .await
+        .map_err(|_| MediaError::Capture("playback thread exited".to_string()))??;
+    Ok((device, stop_tx))
+}
+
+/// Open `device_id`, or the default output device, on its own thread,
+/// draining `mixer` until the returned sender is dropped
+#[cfg(not(feature = "audio-playback"))]
+async fn open_output(
+    _device_id: Option<&str>,
+    _mixer: Arc<Mutex<AudioMixer>>,
+) -> Result<(AudioDevice, std::sync::mpsc::Sender<()>), MediaError> {
+    Err(MediaError::Capture(
+        "built without the `audio-playback` feature".to_string(),
+    ))
+}
+
+#[cfg(feature = "audio-playback")]
+mod backend {
+    use super::AudioMixer;
+    use crate::media::{AudioDevice, MediaError};
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use cpal::{FromSample, Sample, SizedSample};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    fn playback_error(e: impl std::fmt::Display) -> MediaError {
+        MediaError::Capture(e.to_string())
+    }
+
+    pub(super) fn output_devices() -> Result<Vec<AudioDevice>, MediaError> {
+        let devices = cpal::default_host()
+            .output_devices()
+            .map_err(playback_error)?;
+        Ok(devices
+            .filter_map(|device| device.name().ok())
+            .map(|name| AudioDevice {
+                id: name.clone(),
+                name,
+            })
+            .collect())
+    }
+
+    fn find_device(device_id: Option<&str>) -> Result<cpal::Device, MediaError> {
+        let host = cpal::default_host();
+        match device_id {
+            None => host
+                .default_output_device()
+                .ok_or_else(|| MediaError::DeviceNotFound("default output".to_string())),
+            Some(id) => host
+                .output_devices()
+                .map_err(playback_error)?
+                .find(|device| device.name().is_ok_and(|name| name == id))
+                .ok_or_else(|| MediaError::DeviceNotFound(id.to_string())),
+        }
+    }
+
+    /// Open a device in its default format, playing what the mixer holds
+    ///
+    /// The mixer is switched to the device's format once the stream is
+    /// built, and switched back if it fails to start.
+    pub(super) fn open(
+        device_id: Option<&str>,
+        mixer: &Arc<Mutex<AudioMixer>>,
+    ) -> Result<(AudioDevice, cpal::Stream), MediaError> {
+        let device = find_device(device_id)?;
+        let name = device.name().map_err(playback_error)?;
+        let supported = device.default_output_config().map_err(playback_error)?;
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build::<f32>(&device, &config, Arc::clone(mixer)),
+            cpal::SampleFormat::I16 => build::<i16>(&device, &config, Arc::clone(mixer)),
+            cpal::SampleFormat::U16 => build::<u16>(&device, &config, Arc::clone(mixer)),
+            format => Err(MediaError::Capture(format!(
+                "unsupported sample format {format:?}"
+            ))),
+        }?;
+        let previous = mixer.lock().format();
+        mixer
+            .lock()
+            .set_format(config.sample_rate.0, config.channels);
+        if let Err(e) = stream.play() {
+            mixer.lock().set_format(previous.0, previous.1);
+            return Err(playback_error(e));
+        }
+        Ok((
+            AudioDevice {
+                id: name.clone(),
+                name,
+            },
+            stream,
+        ))
+    }
+
+    fn build<S>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        mixer: Arc<Mutex<AudioMixer>>,
+    ) -> Result<cpal::Stream, MediaError>
+    where
+        S: SizedSample + FromSample<f32>,
+    {
+        let mut mixed = Vec::new();
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [S], _: &cpal::OutputCallbackInfo| {
+                    mixed.resize(data.len(), 0.0);
+                    // Never block the audio thread; play silence if a push
+                    // holds the mixer
+                    match mixer.try_lock() {
+                        Some(mut mixer) => mixer.fill(&mut mixed),
+                        None => mixed.fill(0.0),
+                    }
+                    for (out, sample) in data.iter_mut().zip(&mixed) {
+                        *out = S::from_sample(*sample);
+                    }
+                },
+                |e| tracing::warn!("Audio playback error: {}", e),
+                None,
+            )
+            .map_err(playback_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_webrtc_codecs::{Channels, SampleRate};
+
+    fn frame(rate: SampleRate, channels: Channels, data: Vec<i16>) -> AudioFrame {
+        AudioFrame {
+            data,
+            sample_rate: rate,
+            channels,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_resampler_converts_rate_and_layout() {
+        // 10 ms of mono at 16 kHz becomes 10 ms of stereo at 48 kHz
+        let mut resampler = Resampler::new(48_000, 2);
+        let out = resampler.process(&frame(
+            SampleRate::Hz16000,
+            Channels::Mono,
+            vec![16384; 160],
+        ));
+        let second = resampler.process(&frame(
+            SampleRate::Hz16000,
+            Channels::Mono,
+            vec![16384; 160],
+        ));
+        assert!((out.len() as i64 - 2 * 477).abs() <= 6);
+        assert!((second.len() as i64 - 2 * 480).abs() <= 6);
+        assert!(second.iter().all(|s| (s - 0.5).abs() < 0.01));
+
+        // Stereo down to mono averages the channels
+        let mut resampler = Resampler::new(48_000, 1);
+        let out = resampler.process(&frame(
+            SampleRate::Hz48000,
+            Channels::Stereo,
+            [16384, 0].repeat(480),
+        ));
+        assert_eq!(out.len(), 479);
+        assert!(out.iter().all(|s| (s - 0.25).abs() < 0.01));
+    }
+
+    #[test]
+    fn test_mixer_applies_per_call_volume() {
+        let (alice, bob) = (CallId::new(), CallId::new());
+        let mut mixer = AudioMixer::new(48_000, 1);
+        mixer.set_volume(bob, 0.5);
+        assert_eq!(mixer.volume(alice), 1.0);
+        assert_eq!(mixer.volume(bob), 0.5);
+
+        let loud = frame(SampleRate::Hz48000, Channels::Mono, vec![16384; 480]);
+        mixer.push(alice, &loud);
+        mixer.push(bob, &loud);
+        let mut out = vec![0.0; 100];
+        mixer.fill(&mut out);
+        assert!(out.iter().all(|s| (s - 0.75).abs() < 0.01));
+
+        // Muted and drained calls add nothing
+        mixer.set_volume(alice, 0.0);
+        mixer.remove(bob);
+        mixer.fill(&mut out);
+        assert!(out.iter().all(|s| *s == 0.0));
+    }
+
+    #[test]
+    fn test_mixer_bounds_buffering_and_clips() {
+        let call = CallId::new();
+        let mut mixer = AudioMixer::new(48_000, 1);
+        mixer.set_volume(call, 100.0);
+        assert_eq!(mixer.volume(call), MAX_VOLUME);
+
+        let full = frame(SampleRate::Hz48000, Channels::Mono, vec![i16::MAX; 48_000]);
+        mixer.push(call, &full);
+        assert_eq!(mixer.buffered(call), 48 * MAX_PLAYBACK_BUFFER_MS as usize);
+
+        let mut out = vec![0.0; 10];
+        mixer.fill(&mut out);
+        assert!(out.iter().all(|s| *s == 1.0));
+    }
+
+    #[test]
+    fn test_mixer_reformat_keeps_volumes() {
+        let call = CallId::new();
+        let mut mixer = AudioMixer::new(48_000, 2);
+        mixer.set_volume(call, 0.5);
+        let audio = frame(SampleRate::Hz48000, Channels::Mono, vec![16384; 480]);
+        mixer.push(call, &audio);
+        assert!(mixer.buffered(call) > 0);
+
+        // A 44.1 kHz mono headset is plugged in
+        mixer.set_format(44_100, 1);
+        assert_eq!(mixer.format(), (44_100, 1));
+        assert_eq!(mixer.volume(call), 0.5);
+        assert_eq!(mixer.buffered(call), 0);
+        mixer.push(call, &audio);
+        assert!((438..=441).contains(&mixer.buffered(call)));
+        let mut out = vec![0.0; 100];
+        mixer.fill(&mut out);
+        assert!(out.iter().all(|s| (s - 0.25).abs() < 0.01));
+    }
+
+    #[test]
+    #[cfg(not(feature = "audio-playback"))]
+    fn test_no_output_devices_without_feature() {
+        assert!(output_devices().unwrap().is_empty());
+    }
+}
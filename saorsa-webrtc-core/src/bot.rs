@@ -0,0 +1,453 @@
+//! Headless (bot) participants
+//!
+//! A [`HeadlessParticipant`] joins calls programmatically, without capture
+//! devices or a UI. Decoded media received on its calls is handed to the
+//! registered [`MediaSink`]s, and media it publishes is pushed through a
+//! [`PushSource`] into a queue drained by the encoding pipeline. This is the
+//! building block for recording bots, assistants and gateways.
+
+use crate::identity::PeerIdentity;
+use crate::service::{ServiceError, WebRtcEvent, WebRtcService};
+use crate::signaling::SignalingTransport;
+use crate::types::{CallEvent, CallId, MediaConstraints};
+use parking_lot::Mutex;
+use saorsa_webrtc_codecs::{AudioFrame, VideoFrame};
+use std::collections::HashSet;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc};
+
+/// Headless participant errors
+#[derive(Error, Debug)]
+pub enum BotError {
+    /// Underlying service error
+    #[error("Service error: {0}")]
+    ServiceError(#[from] ServiceError),
+
+    /// The participant has not joined the call
+    #[error("Not in call: {0}")]
+    NotInCall(String),
+
+    /// The outbound media queue was closed
+    #[error("Push source closed")]
+    SourceClosed,
+}
+
+/// Decoded media payload
+#[derive(Debug, Clone)]
+pub enum BotMedia {
+    /// PCM audio
+    Audio(AudioFrame),
+    /// RGB24 video
+    Video(VideoFrame),
+}
+
+/// Decoded media tagged with its call and track
+#[derive(Debug, Clone)]
+pub struct BotFrame {
+    /// Call the media belongs to
+    pub call_id: CallId,
+    /// Track the media belongs to
+    pub track_id: String,
+    /// Media payload
+    pub media: BotMedia,
+}
+
+/// Consumer of media received by a headless participant
+pub trait MediaSink: Send {
+    /// Handle a decoded frame
+    fn on_frame(&mut self, frame: &BotFrame);
+
+    /// A call ended and no more frames will arrive for it
+    fn on_call_ended(&mut self, _call_id: CallId) {}
+}
+
+/// Sink that forwards frames into a channel, dropping them when it is full
+pub struct ChannelSink {
+    sender: mpsc::Sender<BotFrame>,
+}
+
+impl ChannelSink {
+    /// Create a sink and the receiver its frames arrive on
+    #[must_use]
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<BotFrame>) {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        (Self { sender }, receiver)
+    }
+}
+
+impl MediaSink for ChannelSink {
+    fn on_frame(&mut self, frame: &BotFrame) {
+        // Never block the media pipeline on a slow consumer
+        if self.sender.try_send(frame.clone()).is_err() {
+            tracing::debug!("Dropping frame for slow sink on {}", frame.track_id);
+        }
+    }
+}
+
+/// Handle used to publish media into a call
+#[derive(Debug, Clone)]
+pub struct PushSource {
+    call_id: CallId,
+    track_id: String,
+    sender: mpsc::Sender<BotFrame>,
+}
+
+impl PushSource {
+    /// Call this source publishes into
+    #[must_use]
+    pub fn call_id(&self) -> CallId {
+        self.call_id
+    }
+
+    /// Track this source publishes on
+    #[must_use]
+    pub fn track_id(&self) -> &str {
+        &self.track_id
+    }
+
+    /// Publish an audio frame, waiting if the outbound queue is full
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the outbound queue was closed
+    pub async fn push_audio(&self, frame: AudioFrame) -> Result<(), BotError> {
+        self.push(BotMedia::Audio(frame)).await
+    }
+
+    /// Publish a video frame, waiting if the outbound queue is full
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the outbound queue was closed
+    pub async fn push_video(&self, frame: VideoFrame) -> Result<(), BotError> {
+        self.push(BotMedia::Video(frame)).await
+    }
+
+    async fn push(&self, media: BotMedia) -> Result<(), BotError> {
+        self.sender
+            .send(BotFrame {
+                call_id: self.call_id,
+                track_id: self.track_id.clone(),
+                media,
+            })
+            .await
+            .map_err(|_| BotError::SourceClosed)
+    }
+}
+
+/// Routes media between the media pipeline and a headless participant
+///
+/// The pipeline calls [`MediaHub::deliver`] with decoded inbound frames and
+/// drains [`MediaHub::take_outbound`] to encode and send pushed frames.
+pub struct MediaHub {
+    calls: Mutex<HashSet<CallId>>,
+    sinks: Mutex<Vec<Box<dyn MediaSink>>>,
+    outbound: mpsc::Sender<BotFrame>,
+    outbound_rx: Mutex<Option<mpsc::Receiver<BotFrame>>>,
+}
+
+impl MediaHub {
+    /// Create a hub with a bounded outbound queue
+    #[must_use]
+    pub fn new(queue_capacity: usize) -> Self {
+        let (outbound, outbound_rx) = mpsc::channel(queue_capacity.max(1));
+        Self {
+            calls: Mutex::new(HashSet::new()),
+            sinks: Mutex::new(Vec::new()),
+            outbound,
+            outbound_rx: Mutex::new(Some(outbound_rx)),
+        }
+    }
+
+    /// Register a sink for inbound media
+    pub fn add_sink(&self, sink: Box<dyn MediaSink>) {
+        self.sinks.lock().push(sink);
+    }
+
+    /// Start routing media for a call
+    pub fn attach(&self, call_id: CallId) {
+        self.calls.lock().insert(call_id);
+    }
+
+    /// Stop routing media for a call and tell the sinks
+    pub fn detach(&self, call_id: CallId) {
+        if self.calls.lock().remove(&call_id) {
+            for sink in self.sinks.lock().iter_mut() {
+                sink.on_call_ended(call_id);
+            }
+        }
+    }
+
+    /// Whether media is routed for a call
+    #[must_use]
+    pub fn is_attached(&self, call_id: CallId) -> bool {
+        self.calls.lock().contains(&call_id)
+    }
+
+    /// Calls media is routed for
+    #[must_use]
+    pub fn calls(&self) -> Vec<CallId> {
+        self.calls.lock().iter().copied().collect()
+    }
+
+    /// Hand a decoded inbound frame to every sink
+    ///
+    /// Frames for calls that are not attached are ignored.
+    pub fn deliver(&self, frame: &BotFrame) {
+        if !self.is_attached(frame.call_id) {
+            return;
+        }
+        for sink in self.sinks.lock().iter_mut() {
+            sink.on_frame(frame);
+        }
+    }
+
+    /// Create a source for publishing on a track of an attached call
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call is not attached
+    pub fn push_source(&self, call_id: CallId, track_id: &str) -> Result<PushSource, BotError> {
+        if !self.is_attached(call_id) {
+            return Err(BotError::NotInCall(call_id.to_string()));
+        }
+        Ok(PushSource {
+            call_id,
+            track_id: track_id.to_string(),
+            sender: self.outbound.clone(),
+        })
+    }
+
+    /// Take the queue of pushed frames, once, for the encoding pipeline
+    #[must_use]
+    pub fn take_outbound(&self) -> Option<mpsc::Receiver<BotFrame>> {
+        self.outbound_rx.lock().take()
+    }
+}
+
+/// Headless participant settings
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    /// Media used when joining or accepting calls
+    pub constraints: MediaConstraints,
+    /// Accept incoming calls automatically
+    pub auto_accept: bool,
+    /// Capacity of the outbound media queue
+    pub queue_capacity: usize,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            constraints: MediaConstraints::audio_only(),
+            auto_accept: true,
+            queue_capacity: 64,
+        }
+    }
+}
+
+/// A participant driven by code rather than a user
+pub struct HeadlessParticipant<I: PeerIdentity, T: SignalingTransport> {
+    service: Arc<WebRtcService<I, T>>,
+    config: BotConfig,
+    media: MediaHub,
+}
+
+impl<I: PeerIdentity, T: SignalingTransport> HeadlessParticipant<I, T> {
+    /// Create a headless participant on top of a service
+    #[must_use]
+    pub fn new(service: Arc<WebRtcService<I, T>>, config: BotConfig) -> Self {
+        let media = MediaHub::new(config.queue_capacity);
+        Self {
+            service,
+            config,
+            media,
+        }
+    }
+
+    /// Media routing for this participant
+    #[must_use]
+    pub fn media(&self) -> &MediaHub {
+        &self.media
+    }
+
+    /// Register a sink for inbound media
+    pub fn add_sink(&self, sink: Box<dyn MediaSink>) {
+        self.media.add_sink(sink);
+    }
+
+    /// Create a source for publishing on a track of a joined call
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the participant has not joined the call
+    pub fn push_source(&self, call_id: CallId, track_id: &str) -> Result<PushSource, BotError> {
+        self.media.push_source(call_id, track_id)
+    }
+
+    /// Call a peer
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call cannot be initiated
+    pub async fn join(&self, peer: I) -> Result<CallId, BotError> {
+        let call_id = self
+            .service
+            .initiate_call(peer, self.config.constraints.clone())
+            .await?;
+        self.media.attach(call_id);
+        Ok(call_id)
+    }
+
+    /// Accept an incoming call
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call cannot be accepted
+    pub async fn accept(&self, call_id: CallId) -> Result<(), BotError> {
+        self.service
+            .accept_call(call_id, self.config.constraints.clone())
+            .await?;
+        self.media.attach(call_id);
+        Ok(())
+    }
+
+    /// Hang up a call
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call cannot be ended
+    pub async fn leave(&self, call_id: CallId) -> Result<(), BotError> {
+        self.media.detach(call_id);
+        self.service.end_call(call_id).await?;
+        Ok(())
+    }
+
+    /// React to a service event
+    ///
+    /// Accepts incoming calls when configured to and stops routing media
+    /// for calls that ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if accepting a call fails
+    pub async fn handle_event(&self, event: &WebRtcEvent<I>) -> Result<(), BotError> {
+        match event {
+            WebRtcEvent::IncomingCall { call_id, from, .. } if self.config.auto_accept => {
+                tracing::info!("Bot accepting call {} from {}", call_id, from);
+                self.accept(*call_id).await?;
+            }
+            WebRtcEvent::Call(
+                CallEvent::CallEnded { call_id, .. } | CallEvent::CallRejected { call_id },
+            ) => self.media.detach(*call_id),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Process service events until the service shuts down
+    pub async fn run(&self) {
+        let mut events = self.service.subscribe_events();
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Err(e) = self.handle_event(&event).await {
+                        tracing::warn!("Bot failed to handle event: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Bot skipped {} events", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_webrtc_codecs::{Channels, SampleRate};
+
+    fn audio() -> AudioFrame {
+        AudioFrame {
+            data: vec![0; 960],
+            sample_rate: SampleRate::Hz48000,
+            channels: Channels::Mono,
+            timestamp: 0,
+        }
+    }
+
+    struct EndedSink(Arc<Mutex<Vec<CallId>>>);
+
+    impl MediaSink for EndedSink {
+        fn on_frame(&mut self, _frame: &BotFrame) {}
+
+        fn on_call_ended(&mut self, call_id: CallId) {
+            self.0.lock().push(call_id);
+        }
+    }
+
+    #[test]
+    fn test_deliver_only_for_attached_calls() {
+        let hub = MediaHub::new(8);
+        let (sink, mut frames) = ChannelSink::new(8);
+        hub.add_sink(Box::new(sink));
+
+        let call_id = CallId::new();
+        let frame = BotFrame {
+            call_id,
+            track_id: "audio-1".to_string(),
+            media: BotMedia::Audio(audio()),
+        };
+        hub.deliver(&frame);
+        assert!(frames.try_recv().is_err());
+
+        hub.attach(call_id);
+        hub.deliver(&frame);
+        let received = frames.try_recv().unwrap();
+        assert_eq!(received.track_id, "audio-1");
+    }
+
+    #[tokio::test]
+    async fn test_push_source_feeds_outbound_queue() {
+        let hub = MediaHub::new(8);
+        let call_id = CallId::new();
+        assert!(matches!(
+            hub.push_source(call_id, "audio-1"),
+            Err(BotError::NotInCall(_))
+        ));
+
+        hub.attach(call_id);
+        let source = hub.push_source(call_id, "audio-1").unwrap();
+        let mut outbound = hub.take_outbound().unwrap();
+        assert!(hub.take_outbound().is_none());
+
+        source.push_audio(audio()).await.unwrap();
+        let frame = outbound.recv().await.unwrap();
+        assert_eq!(frame.call_id, call_id);
+        assert!(matches!(frame.media, BotMedia::Audio(_)));
+
+        drop(outbound);
+        assert!(matches!(
+            source.push_audio(audio()).await,
+            Err(BotError::SourceClosed)
+        ));
+    }
+
+    #[test]
+    fn test_detach_notifies_sinks_once() {
+        let hub = MediaHub::new(8);
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        hub.add_sink(Box::new(EndedSink(Arc::clone(&ended))));
+
+        let call_id = CallId::new();
+        hub.attach(call_id);
+        hub.detach(call_id);
+        hub.detach(call_id);
+
+        assert_eq!(*ended.lock(), vec![call_id]);
+        assert!(hub.calls().is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+//! One-to-many broadcast
+//!
+//! A [`Broadcast`] streams one publisher's media to many subscribers
+//! without a call per viewer: a subscriber has no peer connection, SDP
+//! exchange or call state, only an entry in the subscriber list. Viewers
+//! join and leave over signaling, and nothing comes back from them but
+//! signaling, so media flows one way over QUIC.
+//!
+//! The publisher encodes each video layer once and hands every packet to
+//! [`Broadcast::route`], which returns a copy for each subscriber watching
+//! that layer. Copies are rewritten by a [`PacketRelay`], so a subscriber
+//! moved to another layer keeps seeing one continuous stream. Audio goes
+//! to everyone.
+//!
+//! Each subscriber's layer follows the loss it reports. Loss at the
+//! critical threshold of [`CongestionConfig`] drops it a layer at once;
+//! [`BroadcastConfig::upgrade_after`] reports below the warning threshold
+//! in a row raise it one.
+
+use crate::active_speaker::SimulcastLayer;
+use crate::congestion::{CongestionConfig, CongestionSeverity};
+use crate::error::{CodedError, ErrorCode};
+use crate::quic_bridge::RtpPacket;
+use crate::relay::{PacketRelay, RelayStats};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// SSRC subscribers receive broadcast audio on
+pub const BROADCAST_AUDIO_SSRC: u32 = 0xB0AD_0001;
+/// SSRC subscribers receive broadcast video on, whatever its layer
+pub const BROADCAST_VIDEO_SSRC: u32 = 0xB0AD_0002;
+
+/// Unique identifier for a broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BroadcastId(pub Uuid);
+
+impl BroadcastId {
+    /// Create a new random broadcast ID
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for BroadcastId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for BroadcastId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for BroadcastId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(Self)
+    }
+}
+
+/// Broadcast errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BroadcastError {
+    /// No broadcast with this ID
+    #[error("Broadcast not found: {0}")]
+    NotFound(BroadcastId),
+
+    /// Broadcast has as many subscribers as it takes
+    #[error("Broadcast is full at {0} subscribers")]
+    Full(usize),
+}
+
+impl CodedError for BroadcastError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::Full(_) => ErrorCode::BroadcastFailed,
+        }
+    }
+}
+
+/// Subscriber limit and layer selection for a broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BroadcastConfig {
+    /// Most subscribers at once; `None` for no limit
+    pub max_subscribers: Option<usize>,
+    /// Layer new subscribers start on, or the nearest one below it
+    pub start_layer: SimulcastLayer,
+    /// Loss thresholds for moving subscribers between layers
+    pub congestion: CongestionConfig,
+    /// Calm loss reports in a row before a subscriber moves up a layer
+    pub upgrade_after: u32,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            max_subscribers: None,
+            start_layer: SimulcastLayer::Medium,
+            congestion: CongestionConfig::default(),
+            upgrade_after: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Subscriber {
+    /// Layer asked for; served by the nearest layer being published
+    layer: SimulcastLayer,
+    /// Calm loss reports in a row
+    calm: u32,
+}
+
+/// Publisher side of a broadcast
+#[derive(Debug)]
+pub struct Broadcast {
+    id: BroadcastId,
+    config: BroadcastConfig,
+    /// Input SSRC of each video layer being published
+    layers: BTreeMap<SimulcastLayer, u32>,
+    audio_ssrc: Option<u32>,
+    subscribers: HashMap<String, Subscriber>,
+    relay: PacketRelay,
+}
+
+impl Broadcast {
+    /// Create a broadcast with no layers or subscribers yet
+    #[must_use]
+    pub fn new(config: BroadcastConfig) -> Self {
+        Self {
+            id: BroadcastId::new(),
+            config,
+            layers: BTreeMap::new(),
+            audio_ssrc: None,
+            subscribers: HashMap::new(),
+            relay: PacketRelay::new(),
+        }
+    }
+
+    /// ID subscribers join with
+    #[must_use]
+    pub fn id(&self) -> BroadcastId {
+        self.id
+    }
+
+    /// Settings in use
+    #[must_use]
+    pub fn config(&self) -> &BroadcastConfig {
+        &self.config
+    }
+
+    /// Publish video `layer` from the stream with SSRC `ssrc`
+    pub fn set_layer(&mut self, layer: SimulcastLayer, ssrc: u32) {
+        self.layers.insert(layer, ssrc);
+        let subscribers: Vec<String> = self.subscribers.keys().cloned().collect();
+        for subscriber in subscribers {
+            self.route_subscriber(&subscriber);
+        }
+    }
+
+    /// Publish audio from the stream with SSRC `ssrc`
+    pub fn set_audio(&mut self, ssrc: u32) {
+        self.audio_ssrc = Some(ssrc);
+        for subscriber in self.subscribers.keys() {
+            self.relay.subscribe(subscriber, ssrc, BROADCAST_AUDIO_SSRC);
+        }
+    }
+
+    /// Add a subscriber, returning the layer it starts on
+    ///
+    /// Joining again is harmless and keeps the current layer.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the broadcast is at its subscriber limit
+    pub fn join(&mut self, subscriber: &str) -> Result<SimulcastLayer, BroadcastError> {
+        if !self.subscribers.contains_key(subscriber) {
+            if let Some(max) = self
+                .config
+                .max_subscribers
+                .filter(|max| self.subscribers.len() >= *max)
+            {
+                return Err(BroadcastError::Full(max));
+            }
+            self.subscribers.insert(
+                subscriber.to_string(),
+                Subscriber {
+                    layer: self.config.start_layer,
+                    calm: 0,
+                },
+            );
+            if let Some(ssrc) = self.audio_ssrc {
+                self.relay.subscribe(subscriber, ssrc, BROADCAST_AUDIO_SSRC);
+            }
+            self.route_subscriber(subscriber);
+        }
+        Ok(self.layer(subscriber).unwrap_or(self.config.start_layer))
+    }
+
+    /// Remove a subscriber
+    pub fn leave(&mut self, subscriber: &str) -> bool {
+        self.relay.remove_receiver(subscriber);
+        self.subscribers.remove(subscriber).is_some()
+    }
+
+    /// Feed the loss a subscriber reports, returning its new layer if it
+    /// moved
+    pub fn report_loss(&mut self, subscriber: &str, loss_percent: f32) -> Option<SimulcastLayer> {
+        let severity = self.config.congestion.loss_severity(loss_percent);
+        let current = self.layer(subscriber)?;
+        let upgrade_after = self.config.upgrade_after;
+        let state = self.subscribers.get_mut(subscriber)?;
+        let target = match severity {
+            CongestionSeverity::Critical => {
+                state.calm = 0;
+                self.layers.range(..current).next_back()
+            }
+            CongestionSeverity::Warning => {
+                state.calm = 0;
+                None
+            }
+            CongestionSeverity::Clear => {
+                state.calm += 1;
+                if state.calm < upgrade_after {
+                    return None;
+                }
+                state.calm = 0;
+                self.layers.range(current..).nth(1)
+            }
+        };
+        let (&layer, _) = target?;
+        state.layer = layer;
+        self.route_subscriber(subscriber);
+        Some(layer)
+    }
+
+    /// Layer a subscriber is receiving, or would once it is published
+    #[must_use]
+    pub fn layer(&self, subscriber: &str) -> Option<SimulcastLayer> {
+        let wanted = self.subscribers.get(subscriber)?.layer;
+        Some(self.serving(wanted).map_or(wanted, |(layer, _)| layer))
+    }
+
+    /// Subscribers and the layer each receives
+    #[must_use]
+    pub fn subscribers(&self) -> Vec<(String, SimulcastLayer)> {
+        self.subscribers
+            .keys()
+            .filter_map(|id| Some((id.clone(), self.layer(id)?)))
+            .collect()
+    }
+
+    /// Number of subscribers
+    #[must_use]
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// Copies of a published packet for every subscriber that gets it
+    #[must_use]
+    pub fn route(&mut self, packet: &RtpPacket) -> Vec<(String, RtpPacket)> {
+        self.relay.forward(packet)
+    }
+
+    /// Forwarding counters
+    #[must_use]
+    pub fn stats(&self) -> RelayStats {
+        self.relay.stats()
+    }
+
+    /// Published layer serving a subscriber that wants `wanted`: the best
+    /// one not above it, or the lowest there is
+    fn serving(&self, wanted: SimulcastLayer) -> Option<(SimulcastLayer, u32)> {
+        self.layers
+            .range(..=wanted)
+            .next_back()
+            .or_else(|| self.layers.iter().next())
+            .map(|(layer, ssrc)| (*layer, *ssrc))
+    }
+
+    fn route_subscriber(&mut self, subscriber: &str) {
+        let Some(state) = self.subscribers.get(subscriber) else {
+            return;
+        };
+        if let Some((_, ssrc)) = self.serving(state.layer) {
+            self.relay.subscribe(subscriber, ssrc, BROADCAST_VIDEO_SSRC);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic_bridge::StreamType;
+
+    fn packet(ssrc: u32, seq: u16, stream_type: StreamType) -> RtpPacket {
+        RtpPacket::new(
+            96,
+            seq,
+            u32::from(seq) * 3000,
+            ssrc,
+            vec![0xAB; 50],
+            stream_type,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_subscribers_get_audio_and_their_layer() {
+        let mut broadcast = Broadcast::new(BroadcastConfig {
+            max_subscribers: Some(2),
+            ..BroadcastConfig::default()
+        });
+        broadcast.set_audio(10);
+        broadcast.set_layer(SimulcastLayer::Low, 1);
+        assert_eq!(broadcast.join("alice"), Ok(SimulcastLayer::Low));
+        // A higher layer appearing later is picked up
+        broadcast.set_layer(SimulcastLayer::Medium, 2);
+        broadcast.set_layer(SimulcastLayer::High, 3);
+        assert_eq!(broadcast.layer("alice"), Some(SimulcastLayer::Medium));
+        assert_eq!(broadcast.join("bob"), Ok(SimulcastLayer::Medium));
+        assert_eq!(broadcast.join("carol"), Err(BroadcastError::Full(2)));
+        assert_eq!(BroadcastError::Full(2).code(), ErrorCode::BroadcastFailed);
+        let unknown = BroadcastError::NotFound(BroadcastId::new());
+        assert_eq!(unknown.code(), ErrorCode::NotFound);
+
+        let audio = broadcast.route(&packet(10, 1, StreamType::Audio));
+        assert_eq!(audio.len(), 2);
+        assert!(audio.iter().all(|(_, p)| p.ssrc == BROADCAST_AUDIO_SSRC));
+        assert!(broadcast.route(&packet(3, 1, StreamType::Video)).is_empty());
+        let video = broadcast.route(&packet(2, 1, StreamType::Video));
+        assert_eq!(video.len(), 2);
+        assert!(video.iter().all(|(_, p)| p.ssrc == BROADCAST_VIDEO_SSRC));
+
+        assert!(broadcast.leave("bob"));
+        assert_eq!(broadcast.route(&packet(2, 2, StreamType::Video)).len(), 1);
+        assert_eq!(broadcast.join("carol"), Ok(SimulcastLayer::Medium));
+    }
+
+    #[test]
+    fn test_layer_follows_reported_loss() {
+        let mut broadcast = Broadcast::new(BroadcastConfig {
+            upgrade_after: 2,
+            ..BroadcastConfig::default()
+        });
+        broadcast.set_layer(SimulcastLayer::Low, 1);
+        broadcast.set_layer(SimulcastLayer::Medium, 2);
+        broadcast.set_layer(SimulcastLayer::High, 3);
+        broadcast.join("alice").unwrap();
+        let first = broadcast.route(&packet(2, 100, StreamType::Video));
+
+        assert_eq!(
+            broadcast.report_loss("alice", 12.0),
+            Some(SimulcastLayer::Low)
+        );
+        assert_eq!(broadcast.report_loss("alice", 12.0), None);
+        // The stream carries on where it left off on the new layer
+        let switched = broadcast.route(&packet(1, 7, StreamType::Video));
+        assert_eq!(
+            switched[0].1.sequence_number,
+            first[0].1.sequence_number.wrapping_add(1)
+        );
+
+        // Warnings hold the layer and restart the calm count
+        assert_eq!(broadcast.report_loss("alice", 0.0), None);
+        assert_eq!(broadcast.report_loss("alice", 5.0), None);
+        assert_eq!(broadcast.report_loss("alice", 0.0), None);
+        assert_eq!(
+            broadcast.report_loss("alice", 0.0),
+            Some(SimulcastLayer::Medium)
+        );
+        assert_eq!(broadcast.report_loss("alice", 0.0), None);
+        assert_eq!(
+            broadcast.report_loss("alice", 0.0),
+            Some(SimulcastLayer::High)
+        );
+        assert_eq!(broadcast.report_loss("alice", 0.0), None);
+        assert_eq!(broadcast.report_loss("alice", 0.0), None);
+        assert_eq!(broadcast.report_loss("mallory", 50.0), None);
+    }
+}
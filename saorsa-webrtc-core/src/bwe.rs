@@ -0,0 +1,587 @@
+//! Bandwidth estimation
+//!
+//! A Google-congestion-control style estimator combining a delay-based
+//! controller (trendline filter, adaptive overuse detector and AIMD rate
+//! control) with a loss-based controller. The final estimate is the minimum
+//! of the two. Estimators implement [`BandwidthEstimator`] so alternative
+//! strategies can be swapped in without touching the adaptation loop.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Packets sent within this interval are treated as one group
+const BURST_INTERVAL_MS: u64 = 5;
+/// Number of samples used by the trendline linear regression
+const TRENDLINE_WINDOW: usize = 20;
+/// Exponential smoothing applied to accumulated delay
+const TRENDLINE_SMOOTHING: f64 = 0.9;
+/// Gain applied to the trendline slope before comparing to the threshold
+const TRENDLINE_THRESHOLD_GAIN: f64 = 4.0;
+/// Window over which the acknowledged bitrate is measured
+const ACKED_BITRATE_WINDOW_MS: u64 = 500;
+
+/// Per-packet transport feedback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacketFeedback {
+    /// Transport-wide sequence number
+    pub sequence_number: u16,
+    /// Local send time in milliseconds
+    pub send_time_ms: u64,
+    /// Remote arrival time in milliseconds, `None` if the packet was lost
+    pub arrival_time_ms: Option<u64>,
+    /// Packet size in bytes
+    pub size_bytes: usize,
+}
+
+/// Network usage signal from the delay-based detector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BandwidthUsage {
+    /// Queues are stable
+    Normal,
+    /// Queues are building up
+    Overusing,
+    /// Queues are draining
+    Underusing,
+}
+
+/// Estimator configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BweConfig {
+    /// Starting estimate in bits per second
+    pub initial_bitrate_bps: u64,
+    /// Lower bound for the estimate
+    pub min_bitrate_bps: u64,
+    /// Upper bound for the estimate
+    pub max_bitrate_bps: u64,
+}
+
+impl Default for BweConfig {
+    fn default() -> Self {
+        Self {
+            initial_bitrate_bps: 300_000,
+            min_bitrate_bps: 30_000,
+            max_bitrate_bps: 5_000_000,
+        }
+    }
+}
+
+/// Bandwidth estimation strategy
+pub trait BandwidthEstimator: Send + Sync {
+    /// Feed a batch of transport feedback received at `now_ms`
+    fn on_feedback(&mut self, feedback: &[PacketFeedback], now_ms: u64);
+
+    /// Current estimate in bits per second
+    fn estimate_bps(&self) -> u64;
+}
+
+/// Trendline filter over inter-group delay variation
+#[derive(Debug)]
+struct TrendlineEstimator {
+    samples: VecDeque<(f64, f64)>,
+    first_arrival_ms: Option<u64>,
+    accumulated_delay: f64,
+    smoothed_delay: f64,
+    num_deltas: usize,
+    trend: f64,
+}
+
+impl TrendlineEstimator {
+    fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(TRENDLINE_WINDOW),
+            first_arrival_ms: None,
+            accumulated_delay: 0.0,
+            smoothed_delay: 0.0,
+            num_deltas: 0,
+            trend: 0.0,
+        }
+    }
+
+    fn update(&mut self, delay_variation_ms: f64, arrival_ms: u64) {
+        self.num_deltas = (self.num_deltas + 1).min(1000);
+        let first = *self.first_arrival_ms.get_or_insert(arrival_ms);
+
+        self.accumulated_delay += delay_variation_ms;
+        self.smoothed_delay = TRENDLINE_SMOOTHING * self.smoothed_delay
+            + (1.0 - TRENDLINE_SMOOTHING) * self.accumulated_delay;
+
+        self.samples
+            .push_back(((arrival_ms - first) as f64, self.smoothed_delay));
+        if self.samples.len() > TRENDLINE_WINDOW {
+            self.samples.pop_front();
+        }
+        if self.samples.len() == TRENDLINE_WINDOW {
+            if let Some(slope) = linear_fit_slope(&self.samples) {
+                self.trend = slope;
+            }
+        }
+    }
+
+    fn modified_trend(&self) -> f64 {
+        self.num_deltas.min(60) as f64 * self.trend * TRENDLINE_THRESHOLD_GAIN
+    }
+}
+
+fn linear_fit_slope(points: &VecDeque<(f64, f64)>) -> Option<f64> {
+    let n = points.len() as f64;
+    let (sum_x, sum_y) = points
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (mean_x, mean_y) = (sum_x / n, sum_y / n);
+
+    let (num, den) = points.iter().fold((0.0, 0.0), |(num, den), (x, y)| {
+        (num + (x - mean_x) * (y - mean_y), den + (x - mean_x) * (x - mean_x))
+    });
+
+    if den == 0.0 {
+        None
+    } else {
+        Some(num / den)
+    }
+}
+
+/// Overuse detector with an adaptive threshold
+#[derive(Debug)]
+struct OveruseDetector {
+    threshold: f64,
+    last_update_ms: Option<u64>,
+    time_over_using_ms: Option<f64>,
+    overuse_counter: u32,
+    prev_trend: f64,
+    state: BandwidthUsage,
+}
+
+impl OveruseDetector {
+    const K_UP: f64 = 0.0087;
+    const K_DOWN: f64 = 0.039;
+    const OVERUSE_TIME_THRESHOLD_MS: f64 = 10.0;
+
+    fn new() -> Self {
+        Self {
+            threshold: 12.5,
+            last_update_ms: None,
+            time_over_using_ms: None,
+            overuse_counter: 0,
+            prev_trend: 0.0,
+            state: BandwidthUsage::Normal,
+        }
+    }
+
+    fn detect(&mut self, modified_trend: f64, trend: f64, send_delta_ms: f64, now_ms: u64) {
+        if modified_trend > self.threshold {
+            let time = match self.time_over_using_ms {
+                Some(t) => t + send_delta_ms,
+                None => send_delta_ms / 2.0,
+            };
+            self.time_over_using_ms = Some(time);
+            self.overuse_counter += 1;
+            if time > Self::OVERUSE_TIME_THRESHOLD_MS
+                && self.overuse_counter > 1
+                && trend >= self.prev_trend
+            {
+                self.time_over_using_ms = Some(0.0);
+                self.overuse_counter = 0;
+                self.state = BandwidthUsage::Overusing;
+            }
+        } else if modified_trend < -self.threshold {
+            self.time_over_using_ms = None;
+            self.overuse_counter = 0;
+            self.state = BandwidthUsage::Underusing;
+        } else {
+            self.time_over_using_ms = None;
+            self.overuse_counter = 0;
+            self.state = BandwidthUsage::Normal;
+        }
+        self.prev_trend = trend;
+        self.update_threshold(modified_trend, now_ms);
+    }
+
+    fn update_threshold(&mut self, modified_trend: f64, now_ms: u64) {
+        let last = *self.last_update_ms.get_or_insert(now_ms);
+        let magnitude = modified_trend.abs();
+
+        // Ignore large spikes so the threshold is not dragged by outliers
+        if magnitude > self.threshold + 15.0 {
+            self.last_update_ms = Some(now_ms);
+            return;
+        }
+
+        let k = if magnitude < self.threshold {
+            Self::K_DOWN
+        } else {
+            Self::K_UP
+        };
+        let dt = (now_ms.saturating_sub(last)).min(100) as f64;
+        self.threshold = (self.threshold + k * (magnitude - self.threshold) * dt).clamp(6.0, 600.0);
+        self.last_update_ms = Some(now_ms);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RateControlState {
+    Hold,
+    Increase,
+    Decrease,
+}
+
+/// Additive-increase multiplicative-decrease rate controller
+#[derive(Debug)]
+struct AimdRateControl {
+    rate_bps: f64,
+    state: RateControlState,
+    last_update_ms: Option<u64>,
+}
+
+impl AimdRateControl {
+    const BETA: f64 = 0.85;
+
+    fn new(initial_bps: u64) -> Self {
+        Self {
+            rate_bps: initial_bps as f64,
+            state: RateControlState::Hold,
+            last_update_ms: None,
+        }
+    }
+
+    fn update(&mut self, usage: BandwidthUsage, acked_bps: Option<f64>, now_ms: u64) -> f64 {
+        self.state = match (usage, self.state) {
+            (BandwidthUsage::Overusing, _) => RateControlState::Decrease,
+            (BandwidthUsage::Underusing, _) => RateControlState::Hold,
+            (BandwidthUsage::Normal, RateControlState::Hold) => RateControlState::Increase,
+            (BandwidthUsage::Normal, state) => state,
+        };
+
+        let dt_ms = self
+            .last_update_ms
+            .map_or(0, |last| now_ms.saturating_sub(last))
+            .min(1000);
+        self.last_update_ms = Some(now_ms);
+
+        match self.state {
+            RateControlState::Increase => {
+                // Do not run away from what the network is actually delivering
+                let capped = acked_bps.is_some_and(|acked| self.rate_bps > 1.5 * acked);
+                if !capped {
+                    let factor = 1.08_f64.powf(dt_ms as f64 / 1000.0);
+                    self.rate_bps += (self.rate_bps * (factor - 1.0)).max(1000.0 * dt_ms as f64 / 1000.0);
+                }
+            }
+            RateControlState::Decrease => {
+                let base = acked_bps.unwrap_or(self.rate_bps);
+                self.rate_bps = (Self::BETA * base).min(self.rate_bps);
+                self.state = RateControlState::Hold;
+            }
+            RateControlState::Hold => {}
+        }
+
+        self.rate_bps
+    }
+}
+
+/// Sliding-window measurement of the received bitrate
+#[derive(Debug, Default)]
+struct AckedBitrate {
+    packets: VecDeque<(u64, usize)>,
+}
+
+impl AckedBitrate {
+    fn add(&mut self, arrival_ms: u64, size_bytes: usize) {
+        self.packets.push_back((arrival_ms, size_bytes));
+        while let Some(&(oldest, _)) = self.packets.front() {
+            if arrival_ms.saturating_sub(oldest) > ACKED_BITRATE_WINDOW_MS {
+                self.packets.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn bitrate_bps(&self) -> Option<f64> {
+        let (first, _) = *self.packets.front()?;
+        let (last, _) = *self.packets.back()?;
+        let span_ms = last.saturating_sub(first);
+        if span_ms == 0 {
+            return None;
+        }
+        let bytes: usize = self.packets.iter().map(|(_, size)| size).sum();
+        Some(bytes as f64 * 8.0 * 1000.0 / span_ms as f64)
+    }
+}
+
+/// Group of packets sent within one burst interval
+#[derive(Debug, Clone, Copy)]
+struct PacketGroup {
+    first_send_ms: u64,
+    last_send_ms: u64,
+    last_arrival_ms: u64,
+}
+
+/// Delay- and loss-based estimator in the style of Google congestion control
+#[derive(Debug)]
+pub struct GccEstimator {
+    config: BweConfig,
+    trendline: TrendlineEstimator,
+    detector: OveruseDetector,
+    rate_control: AimdRateControl,
+    acked: AckedBitrate,
+    current_group: Option<PacketGroup>,
+    previous_group: Option<PacketGroup>,
+    delay_based_bps: f64,
+    loss_based_bps: f64,
+    last_loss_fraction: f32,
+}
+
+impl GccEstimator {
+    /// Create a new estimator
+    #[must_use]
+    pub fn new(config: BweConfig) -> Self {
+        let initial = config
+            .initial_bitrate_bps
+            .clamp(config.min_bitrate_bps, config.max_bitrate_bps);
+        Self {
+            trendline: TrendlineEstimator::new(),
+            detector: OveruseDetector::new(),
+            rate_control: AimdRateControl::new(initial),
+            acked: AckedBitrate::default(),
+            current_group: None,
+            previous_group: None,
+            delay_based_bps: initial as f64,
+            loss_based_bps: initial as f64,
+            last_loss_fraction: 0.0,
+            config,
+        }
+    }
+
+    /// Latest signal from the delay-based detector
+    #[must_use]
+    pub fn usage(&self) -> BandwidthUsage {
+        self.detector.state
+    }
+
+    /// Loss fraction (0.0 - 1.0) of the most recent feedback batch
+    #[must_use]
+    pub fn loss_fraction(&self) -> f32 {
+        self.last_loss_fraction
+    }
+
+    /// Current delay-based estimate in bits per second
+    #[must_use]
+    pub fn delay_based_bps(&self) -> u64 {
+        self.delay_based_bps as u64
+    }
+
+    /// Current loss-based estimate in bits per second
+    #[must_use]
+    pub fn loss_based_bps(&self) -> u64 {
+        self.loss_based_bps as u64
+    }
+
+    fn on_received_packet(&mut self, send_ms: u64, arrival_ms: u64, now_ms: u64) {
+        match self.current_group.as_mut() {
+            Some(group) if send_ms.saturating_sub(group.first_send_ms) <= BURST_INTERVAL_MS => {
+                group.last_send_ms = group.last_send_ms.max(send_ms);
+                group.last_arrival_ms = group.last_arrival_ms.max(arrival_ms);
+                return;
+            }
+            _ => {}
+        }
+
+        // A new group starts; compare the completed group to the one before it
+        if let (Some(prev), Some(current)) = (self.previous_group, self.current_group) {
+            let send_delta = current.last_send_ms as f64 - prev.last_send_ms as f64;
+            let arrival_delta = current.last_arrival_ms as f64 - prev.last_arrival_ms as f64;
+            self.trendline
+                .update(arrival_delta - send_delta, current.last_arrival_ms);
+            self.detector.detect(
+                self.trendline.modified_trend(),
+                self.trendline.trend,
+                send_delta,
+                now_ms,
+            );
+        }
+
+        self.previous_group = self.current_group;
+        self.current_group = Some(PacketGroup {
+            first_send_ms: send_ms,
+            last_send_ms: send_ms,
+            last_arrival_ms: arrival_ms,
+        });
+    }
+
+    fn update_loss_based(&mut self, loss_fraction: f32) {
+        if loss_fraction < 0.02 {
+            self.loss_based_bps *= 1.05;
+        } else if loss_fraction > 0.10 {
+            self.loss_based_bps *= 1.0 - 0.5 * f64::from(loss_fraction);
+        }
+        self.loss_based_bps = self.clamp(self.loss_based_bps);
+    }
+
+    fn clamp(&self, bps: f64) -> f64 {
+        bps.clamp(
+            self.config.min_bitrate_bps as f64,
+            self.config.max_bitrate_bps as f64,
+        )
+    }
+}
+
+impl Default for GccEstimator {
+    fn default() -> Self {
+        Self::new(BweConfig::default())
+    }
+}
+
+impl BandwidthEstimator for GccEstimator {
+    fn on_feedback(&mut self, feedback: &[PacketFeedback], now_ms: u64) {
+        if feedback.is_empty() {
+            return;
+        }
+
+        let mut received: Vec<&PacketFeedback> = feedback
+            .iter()
+            .filter(|packet| packet.arrival_time_ms.is_some())
+            .collect();
+        received.sort_by_key(|packet| packet.send_time_ms);
+
+        for packet in &received {
+            if let Some(arrival_ms) = packet.arrival_time_ms {
+                self.acked.add(arrival_ms, packet.size_bytes);
+                self.on_received_packet(packet.send_time_ms, arrival_ms, now_ms);
+            }
+        }
+
+        let lost = feedback.len() - received.len();
+        self.last_loss_fraction = lost as f32 / feedback.len() as f32;
+        self.update_loss_based(self.last_loss_fraction);
+
+        let rate = self
+            .rate_control
+            .update(self.detector.state, self.acked.bitrate_bps(), now_ms);
+        self.delay_based_bps = self.clamp(rate);
+        self.rate_control.rate_bps = self.delay_based_bps;
+    }
+
+    fn estimate_bps(&self) -> u64 {
+        self.delay_based_bps.min(self.loss_based_bps) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET_INTERVAL_MS: u64 = 20;
+    const PACKET_SIZE: usize = 1200;
+    const FEEDBACK_INTERVAL_MS: u64 = 100;
+
+    /// Build a trace of packets every 20 ms where `delay_fn` gives the
+    /// one-way delay of packet `i` and `lost_fn` marks dropped packets.
+    fn trace(
+        packets: u64,
+        delay_fn: impl Fn(u64) -> u64,
+        lost_fn: impl Fn(u64) -> bool,
+    ) -> Vec<PacketFeedback> {
+        (0..packets)
+            .map(|i| {
+                let send = i * PACKET_INTERVAL_MS;
+                PacketFeedback {
+                    sequence_number: i as u16,
+                    send_time_ms: send,
+                    arrival_time_ms: (!lost_fn(i)).then(|| send + delay_fn(i)),
+                    size_bytes: PACKET_SIZE,
+                }
+            })
+            .collect()
+    }
+
+    /// Replay a trace in feedback batches, returning the estimate after each
+    fn replay(estimator: &mut GccEstimator, trace: &[PacketFeedback]) -> Vec<u64> {
+        let per_batch = (FEEDBACK_INTERVAL_MS / PACKET_INTERVAL_MS) as usize;
+        trace
+            .chunks(per_batch)
+            .map(|batch| {
+                let now = batch.last().map_or(0, |p| p.send_time_ms) + 50;
+                estimator.on_feedback(batch, now);
+                estimator.estimate_bps()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_stable_network_ramps_up() {
+        let mut bwe = GccEstimator::default();
+        let estimates = replay(&mut bwe, &trace(500, |_| 40, |_| false));
+
+        assert_eq!(bwe.usage(), BandwidthUsage::Normal);
+        assert!(estimates.last().copied().unwrap() > BweConfig::default().initial_bitrate_bps);
+    }
+
+    #[test]
+    fn test_queue_buildup_triggers_overuse() {
+        let mut bwe = GccEstimator::new(BweConfig {
+            initial_bitrate_bps: 2_000_000,
+            ..Default::default()
+        });
+
+        // Stable for 2 seconds, then each packet queues 3 ms behind the last
+        let stable = trace(100, |_| 40, |_| false);
+        replay(&mut bwe, &stable);
+        let before = bwe.estimate_bps();
+
+        let congested: Vec<PacketFeedback> = trace(100, |i| 40 + i * 3, |_| false)
+            .into_iter()
+            .map(|mut p| {
+                p.send_time_ms += 2000;
+                p.arrival_time_ms = p.arrival_time_ms.map(|a| a + 2000);
+                p
+            })
+            .collect();
+        replay(&mut bwe, &congested);
+
+        assert!(bwe.delay_based_bps() < before);
+        assert!(bwe.estimate_bps() < before);
+    }
+
+    #[test]
+    fn test_heavy_loss_reduces_estimate() {
+        let mut bwe = GccEstimator::new(BweConfig {
+            initial_bitrate_bps: 1_000_000,
+            ..Default::default()
+        });
+        replay(&mut bwe, &trace(200, |_| 40, |i| i % 5 == 0));
+
+        assert!((bwe.loss_fraction() - 0.2).abs() < 0.01);
+        assert!(bwe.loss_based_bps() < 1_000_000);
+        assert!(bwe.estimate_bps() < 1_000_000);
+    }
+
+    #[test]
+    fn test_estimate_respects_bounds() {
+        let config = BweConfig {
+            initial_bitrate_bps: 100_000,
+            min_bitrate_bps: 50_000,
+            max_bitrate_bps: 150_000,
+        };
+        let mut bwe = GccEstimator::new(config.clone());
+        replay(&mut bwe, &trace(1000, |_| 40, |_| false));
+        assert!(bwe.estimate_bps() <= config.max_bitrate_bps);
+
+        let mut bwe = GccEstimator::new(config.clone());
+        replay(&mut bwe, &trace(500, |_| 40, |i| i % 2 == 0));
+        assert!(bwe.estimate_bps() >= config.min_bitrate_bps);
+    }
+
+    #[test]
+    fn test_empty_feedback_is_ignored() {
+        let mut bwe = GccEstimator::default();
+        bwe.on_feedback(&[], 100);
+        assert_eq!(bwe.estimate_bps(), BweConfig::default().initial_bitrate_bps);
+    }
+
+    #[test]
+    fn test_linear_fit_slope() {
+        let points: VecDeque<(f64, f64)> = (0..10).map(|x| (x as f64, 2.0 * x as f64 + 1.0)).collect();
+        let slope = linear_fit_slope(&points).unwrap();
+        assert!((slope - 2.0).abs() < 1e-9);
+    }
+}
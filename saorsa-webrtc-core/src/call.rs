@@ -1,14 +1,48 @@
 //! Call management for WebRTC
 
+use crate::annotation::{annotated_track, annotation_channel_label, Annotation, AnnotationError};
+use crate::audio_capture::AudioCapture;
+use crate::call_stats::TransportStats;
+use crate::capabilities::MaxResolution;
+use crate::deadline::{Deadline, Interrupted};
+use crate::dtmf::{DtmfError, DtmfTone, DTMF_CHANNEL_LABEL};
+use crate::error::{BoxError, CodedError, ErrorCode};
+use crate::events::{DEFAULT_EVENT_QUEUE_CAPACITY, EventSubscription, TopicChannel};
 use crate::identity::PeerIdentity;
-use crate::media::{MediaStreamManager, WebRtcTrack};
-use crate::types::{CallEvent, CallId, CallState, MediaConstraints};
+use crate::media::{MediaStream, MediaStreamManager, RemoteTrack, VideoTrack, WebRtcTrack};
+use crate::messaging::{MessageError, TextMessage, MESSAGE_CHANNEL_LABEL};
+use crate::path_policy::{PathError, PathPolicy};
+use crate::playback_sync::{sync_channel_label, synced_session, SyncMessage};
+use crate::remote_control::{
+    controlled_track, remote_control_channel_label, RemoteControlError, RemoteControlGrants,
+    RemoteInput,
+};
+use crate::resume::CallSnapshot;
+use crate::screen_capture::{ScreenCaptureConfig, ScreenCapturer, ScreenShare, ScreenSource};
+use crate::types::{
+    ByeReason, CallEvent, CallId, CallOffer, CallState, FailureReason, MediaConstraints, MediaType,
+};
+use bytes::Bytes;
+use dashmap::DashMap;
+use saorsa_webrtc_codecs::VideoCodec;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::{RwLock, broadcast};
+use uuid::Uuid;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
+use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::rtp_transceiver::rtp_codec::RTPCodecType;
+use webrtc::rtp_transceiver::rtp_sender::RTCRtpSender;
+use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
+use webrtc::track::track_local::TrackLocalWriter;
+use webrtc::track::track_remote::TrackRemote;
 
 /// Call management errors
 #[derive(Error, Debug)]
@@ -21,9 +55,99 @@ pub enum CallError {
     #[error("Invalid call state")]
     InvalidState,
 
-    /// Configuration error
-    #[error("Configuration error: {0}")]
-    ConfigError(String),
+    /// Concurrent call limit reached
+    #[error("Maximum concurrent calls limit reached: {0}")]
+    CallLimitReached(usize),
+
+    /// Peer connection could not be created
+    #[error("Failed to create peer connection")]
+    PeerConnection(#[source] webrtc::Error),
+
+    /// Local media track could not be created or attached
+    #[error("Failed to set up {kind:?} track")]
+    TrackSetup {
+        /// Kind of track being set up
+        kind: MediaType,
+        /// Underlying failure
+        #[source]
+        source: BoxError,
+    },
+
+    /// Outgoing media could not be paused or resumed
+    #[error("Failed to switch outgoing track")]
+    TrackSwitch(#[source] webrtc::Error),
+
+    /// SDP was empty
+    #[error("SDP cannot be empty")]
+    EmptySdp,
+
+    /// SDP offer/answer exchange failed
+    #[error("SDP negotiation failed")]
+    Negotiation(#[source] webrtc::Error),
+
+    /// ICE candidate was rejected
+    #[error("Failed to add ICE candidate")]
+    IceCandidate(#[source] webrtc::Error),
+
+    /// ICE candidate is for a path the path policy rules out
+    #[error(transparent)]
+    PathRejected(#[from] PathError),
+
+    /// Timed out or cancelled
+    #[error(transparent)]
+    Interrupted(#[from] Interrupted),
+
+    /// Reserved data channel is not open, by label
+    #[error("Data channel not open: {0}")]
+    DataChannelNotFound(String),
+
+    /// Annotation could not be encoded
+    #[error(transparent)]
+    Annotation(#[from] AnnotationError),
+
+    /// Remote control input was refused or could not be encoded
+    #[error(transparent)]
+    RemoteControl(#[from] RemoteControlError),
+
+    /// Text message could not be encoded
+    #[error(transparent)]
+    Message(#[from] MessageError),
+
+    /// DTMF digits could not be encoded
+    #[error(transparent)]
+    Dtmf(#[from] DtmfError),
+
+    /// Data channel could not be opened or written
+    #[error("Data channel failed")]
+    DataChannel(#[source] webrtc::Error),
+
+    /// Remote track is not part of the call, by ID
+    #[error("Track not found: {0}")]
+    TrackNotFound(String),
+}
+
+impl CodedError for CallError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::CallNotFound(_) => ErrorCode::CallNotFound,
+            Self::InvalidState => ErrorCode::InvalidCallState,
+            Self::CallLimitReached(_) => ErrorCode::CallLimitReached,
+            Self::PeerConnection(_) => ErrorCode::PeerConnectionFailed,
+            Self::TrackSetup { .. } | Self::TrackSwitch(_) => ErrorCode::TrackFailed,
+            Self::EmptySdp => ErrorCode::InvalidInput,
+            Self::Negotiation(_) => ErrorCode::NegotiationFailed,
+            Self::IceCandidate(_) => ErrorCode::IceFailed,
+            Self::PathRejected(e) => e.code(),
+            Self::Interrupted(e) => e.code(),
+            Self::DataChannelNotFound(_) => ErrorCode::StreamNotFound,
+            Self::Annotation(e) => e.code(),
+            Self::RemoteControl(e) => e.code(),
+            Self::Message(e) => e.code(),
+            Self::Dtmf(e) => e.code(),
+            Self::DataChannel(_) => ErrorCode::SendFailed,
+            Self::TrackNotFound(_) => ErrorCode::StreamNotFound,
+        }
+    }
 }
 
 /// Call manager configuration
@@ -31,12 +155,125 @@ pub enum CallError {
 pub struct CallManagerConfig {
     /// Maximum concurrent calls
     pub max_concurrent_calls: usize,
+    /// How long offer creation may take before failing with a timeout
+    pub negotiation_timeout: Duration,
+    /// Network paths calls may take
+    #[serde(default)]
+    pub path_policy: PathPolicy,
+    /// How long a peer connection set up by [`CallManager::prewarm`] waits
+    /// for the call before being dropped
+    #[serde(default = "default_prewarm_ttl")]
+    pub prewarm_ttl: Duration,
+    /// How long a call we place waits to be answered before failing with
+    /// [`FailureReason::NoAnswer`]; `None` to wait for ever
+    #[serde(default = "default_ring_timeout")]
+    pub ring_timeout: Option<Duration>,
+    /// How long an incoming call rings before failing with
+    /// [`FailureReason::NoAnswer`]; `None` to ring for ever
+    #[serde(default = "default_answer_timeout")]
+    pub answer_timeout: Option<Duration>,
+    /// How long a call that lost its connection waits in
+    /// [`CallState::Reconnecting`] before failing with
+    /// [`FailureReason::ConnectionLost`]
+    #[serde(default = "default_reconnect_grace")]
+    pub reconnect_grace: Duration,
+    /// Which incoming calls are answered without ringing
+    #[serde(default)]
+    pub answer_policy: AnswerPolicy,
 }
 
 impl Default for CallManagerConfig {
     fn default() -> Self {
         Self {
             max_concurrent_calls: 10,
+            negotiation_timeout: Duration::from_secs(10),
+            path_policy: PathPolicy::default(),
+            prewarm_ttl: default_prewarm_ttl(),
+            ring_timeout: default_ring_timeout(),
+            answer_timeout: default_answer_timeout(),
+            reconnect_grace: default_reconnect_grace(),
+            answer_policy: AnswerPolicy::default(),
+        }
+    }
+}
+
+fn default_prewarm_ttl() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_ring_timeout() -> Option<Duration> {
+    Some(Duration::from_secs(60))
+}
+
+// Shorter than the ring timeout, so the callee gives up first and says so
+fn default_answer_timeout() -> Option<Duration> {
+    Some(Duration::from_secs(45))
+}
+
+fn default_reconnect_grace() -> Duration {
+    Duration::from_secs(15)
+}
+
+/// When to redial a callee that could not be reached
+///
+/// Waits grow by `backoff` from `initial_delay` up to `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RetrySchedule {
+    /// Attempts after the first before the call fails
+    pub retries: u32,
+    /// Wait before the first retry
+    pub initial_delay: Duration,
+    /// Factor each wait grows by
+    pub backoff: f64,
+    /// Longest wait between attempts
+    pub max_delay: Duration,
+}
+
+impl RetrySchedule {
+    /// Wait before retry number `retry`, counted from 1
+    #[must_use]
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponent = i32::try_from(retry.saturating_sub(1)).unwrap_or(i32::MAX);
+        let secs = self.initial_delay.as_secs_f64() * self.backoff.max(1.0).powi(exponent);
+        // Waits too long to represent are capped like any other
+        Duration::try_from_secs_f64(secs).map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl Default for RetrySchedule {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            initial_delay: Duration::from_secs(2),
+            backoff: 2.0,
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Which incoming calls are answered without the application
+///
+/// Kiosks and bots have nobody to press answer. Calls a policy answers
+/// take the media offered to them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnswerPolicy {
+    /// Every call rings until the application answers it
+    #[default]
+    Manual,
+    /// Answer calls from these peer IDs, ring for the rest
+    AutoAcceptKnownPeers(Vec<String>),
+    /// Answer every call
+    AutoAcceptAll,
+}
+
+impl AnswerPolicy {
+    /// Whether calls from `peer` are answered automatically
+    #[must_use]
+    pub fn answers(&self, peer: &str) -> bool {
+        match self {
+            Self::Manual => false,
+            Self::AutoAcceptKnownPeers(peers) => peers.iter().any(|known| known == peer),
+            Self::AutoAcceptAll => true,
         }
     }
 }
@@ -56,17 +293,163 @@ pub struct Call<I: PeerIdentity> {
     pub state: CallState,
     /// Media constraints
     pub constraints: MediaConstraints,
-    /// WebRTC tracks for this call
-    pub tracks: Vec<WebRtcTrack>,
+    /// Tracks we send on this call
+    pub local_stream: MediaStream,
+    /// Senders of the tracks we send, by track ID
+    ///
+    /// Kept rather than looked up by position, as answered calls order
+    /// their transceivers by the remote offer.
+    pub senders: HashMap<String, Arc<RTCRtpSender>>,
+    /// Streams the remote party sends, by stream ID
+    pub remote_streams: HashMap<String, MediaStream<RemoteTrack>>,
+    /// Whether the user muted the call's audio
+    pub audio_muted: bool,
+    /// Whether the user wants the call's video and screen share sent
+    pub video_enabled: bool,
+    /// Whether video is paused to save bandwidth
+    pub video_paused: bool,
+    /// Annotation and remote control data channels by label
+    pub data_channels: HashMap<String, Arc<RTCDataChannel>>,
+    /// Remote control granted to or by the remote party
+    pub remote_control: RemoteControlGrants,
+    /// Whether the remote party called us
+    pub incoming: bool,
+    /// IDs of local tracks shared with other calls, left in place when
+    /// this one ends
+    pub shared_tracks: HashSet<String>,
+    /// Screen shares we send, in the order they were added
+    pub screen_shares: Vec<ScreenShare>,
+    /// Screen shares the remote party says it sends
+    pub remote_screen_shares: Vec<ScreenShare>,
+    /// When the call lost its connection, while it is reconnecting
+    pub reconnecting_since: Option<Instant>,
+}
+
+impl<I: PeerIdentity> Call<I> {
+    /// Whether a track should be sent right now
+    fn is_sending(&self, track: &WebRtcTrack) -> bool {
+        match track.track_type {
+            MediaType::Audio => !self.audio_muted,
+            MediaType::ScreenShare
+                if self
+                    .screen_shares
+                    .iter()
+                    .any(|share| share.track_id == track.id && share.paused) =>
+            {
+                false
+            }
+            _ => self.video_enabled && !self.video_paused,
+        }
+    }
+}
+
+/// Moves calls between states from timers and peer connection handlers,
+/// after the method that set them up has returned
+struct CallWatch<I: PeerIdentity> {
+    // Weak, as calls hold the peer connection handlers holding this
+    calls: Weak<DashMap<CallId, Call<I>>>,
+    event_sender: broadcast::Sender<CallEvent<I>>,
+    event_bus: Arc<TopicChannel<CallEvent<I>>>,
+}
+
+impl<I: PeerIdentity> Clone for CallWatch<I> {
+    fn clone(&self) -> Self {
+        Self {
+            calls: Weak::clone(&self.calls),
+            event_sender: self.event_sender.clone(),
+            event_bus: Arc::clone(&self.event_bus),
+        }
+    }
+}
+
+impl<I: PeerIdentity> CallWatch<I> {
+    fn emit(&self, event: CallEvent<I>) {
+        self.event_bus.publish(event.clone());
+        let _ = self.event_sender.send(event);
+    }
+
+    /// Move a call that is in one of `from` to `to`, returning whether it
+    /// moved
+    fn transition(&self, call_id: CallId, from: &[CallState], to: CallState) -> bool {
+        let Some(calls) = self.calls.upgrade() else {
+            return false;
+        };
+        let left = {
+            let Some(mut call) = calls.get_mut(&call_id) else {
+                return false;
+            };
+            let left = call.state;
+            if !from.contains(&left) {
+                return false;
+            }
+            call.state = to;
+            call.reconnecting_since = (to == CallState::Reconnecting).then(Instant::now);
+            left
+        };
+        self.emit(CallEvent::StateChanged { call_id, from: left, to });
+        true
+    }
+
+    /// Fail a call that is in one of `from`
+    fn fail(&self, call_id: CallId, from: &[CallState], reason: FailureReason, error: String) {
+        if self.transition(call_id, from, CallState::Failed) {
+            tracing::warn!("Call {} failed: {}", call_id, error);
+            self.emit(CallEvent::ConnectionFailed { call_id, reason, error });
+        }
+    }
+
+    /// Move a connected call to Reconnecting, failing it unless it is back
+    /// within `grace`
+    fn connection_lost(&self, call_id: CallId, grace: Duration) -> bool {
+        if !self.transition(call_id, &[CallState::Connected], CallState::Reconnecting) {
+            return false;
+        }
+        tracing::info!("Call {} lost its connection, reconnecting", call_id);
+        let watch = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            // Lost again since, the later loss has its own timer
+            let lost_throughout = watch
+                .calls
+                .upgrade()
+                .and_then(|calls| calls.get(&call_id).and_then(|call| call.reconnecting_since))
+                .is_some_and(|since| since.elapsed() >= grace);
+            if lost_throughout {
+                watch.fail(
+                    call_id,
+                    &[CallState::Reconnecting],
+                    FailureReason::ConnectionLost,
+                    format!("connection not back within {grace:?}"),
+                );
+            }
+        });
+        true
+    }
+
+    fn connection_restored(&self, call_id: CallId) -> bool {
+        let restored = self.transition(call_id, &[CallState::Reconnecting], CallState::Connected);
+        if restored {
+            tracing::info!("Call {} reconnected", call_id);
+        }
+        restored
+    }
 }
 
 /// Call manager
+///
+/// Calls are held in a sharded concurrent map so that work on one call
+/// (stats polling, ICE candidates, SDP handling) does not block others.
+/// Map guards are never held across an `.await`; async peer connection
+/// operations work on a cloned `Arc<RTCPeerConnection>` instead.
 pub struct CallManager<I: PeerIdentity> {
-    calls: Arc<RwLock<HashMap<CallId, Call<I>>>>,
+    calls: Arc<DashMap<CallId, Call<I>>>,
     event_sender: broadcast::Sender<CallEvent<I>>,
+    event_bus: Arc<TopicChannel<CallEvent<I>>>,
     #[allow(dead_code)]
     config: CallManagerConfig,
     media_manager: Arc<RwLock<MediaStreamManager>>,
+    /// Prewarmed peer connections by remote peer, with when they were set up
+    warm: DashMap<String, (Arc<RTCPeerConnection>, Instant)>,
 }
 
 impl<I: PeerIdentity> CallManager<I> {
@@ -76,16 +459,62 @@ impl<I: PeerIdentity> CallManager<I> {
     ///
     /// Returns error if initialization fails
     pub async fn new(config: CallManagerConfig) -> Result<Self, CallError> {
+        Self::with_event_bus(
+            config,
+            Arc::new(TopicChannel::new(DEFAULT_EVENT_QUEUE_CAPACITY)),
+        )
+        .await
+    }
+
+    /// Create new call manager publishing on a shared event bus topic
+    ///
+    /// # Errors
+    ///
+    /// Returns error if initialization fails
+    pub async fn with_event_bus(
+        config: CallManagerConfig,
+        event_bus: Arc<TopicChannel<CallEvent<I>>>,
+    ) -> Result<Self, CallError> {
         let (event_sender, _) = broadcast::channel(100);
         let media_manager = Arc::new(RwLock::new(MediaStreamManager::new()));
         Ok(Self {
-            calls: Arc::new(RwLock::new(HashMap::new())),
+            calls: Arc::new(DashMap::new()),
             event_sender,
+            event_bus,
             config,
             media_manager,
+            warm: DashMap::new(),
         })
     }
 
+    /// Which incoming calls are answered without ringing
+    #[must_use]
+    pub fn answer_policy(&self) -> &AnswerPolicy {
+        &self.config.answer_policy
+    }
+
+    /// Emit a call event on both the broadcast channel and the event bus
+    fn emit(&self, event: CallEvent<I>) {
+        self.event_bus.publish(event.clone());
+        let _ = self.event_sender.send(event);
+    }
+
+    /// Move a call to state `to`, emitting [`CallEvent::StateChanged`]
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call's state cannot move to `to`
+    fn set_state(&self, call: &mut Call<I>, to: CallState) -> Result<(), CallError> {
+        let from = call.state;
+        if !from.can_transition_to(to) {
+            tracing::warn!("Invalid state transition for call {}: {:?} -> {:?}", call.id, from, to);
+            return Err(CallError::InvalidState);
+        }
+        call.state = to;
+        self.emit(CallEvent::StateChanged { call_id: call.id, from, to });
+        Ok(())
+    }
+
     /// Start the call manager
     ///
     /// # Errors
@@ -106,195 +535,1893 @@ impl<I: PeerIdentity> CallManager<I> {
         constraints: MediaConstraints,
     ) -> Result<CallId, CallError> {
         // Enforce max_concurrent_calls limit
-        let calls = self.calls.read().await;
-        if calls.len() >= self.config.max_concurrent_calls {
-            return Err(CallError::ConfigError(format!(
-                "Maximum concurrent calls limit reached: {}",
-                self.config.max_concurrent_calls
-            )));
+        if self.calls.len() >= self.config.max_concurrent_calls {
+            return Err(CallError::CallLimitReached(self.config.max_concurrent_calls));
         }
-        drop(calls);
 
         let call_id = CallId::new();
 
         tracing::info!("Initiating call {} to peer: {}", call_id, callee.to_string_repr());
 
-        // Create WebRTC peer connection
-        let peer_connection = Arc::new(
-            webrtc::api::APIBuilder::new().build().new_peer_connection(
-                webrtc::peer_connection::configuration::RTCConfiguration::default(),
-            ).await.map_err(|e| {
-                tracing::error!("Failed to create peer connection for call {}: {}", call_id, e);
-                CallError::ConfigError(format!("Failed to create peer connection: {}", e))
-            })?
+        let peer_connection = match self.take_prewarmed(&callee).await {
+            Some(peer_connection) => {
+                tracing::debug!("Using prewarmed peer connection for call {}", call_id);
+                peer_connection
+            }
+            None => self.new_peer_connection(&callee.to_string_repr()).await?,
+        };
+        self.watch_data_channels(call_id, &peer_connection);
+        self.watch_remote_tracks(call_id, &peer_connection);
+        self.watch_connection_state(call_id, &peer_connection);
+
+        // Create media tracks based on constraints, grouped so the remote
+        // party sees them as one stream
+        let mut local_stream = MediaStream::new(format!("call-{call_id}"));
+        let mut senders = HashMap::new();
+        let kinds = track_kinds(&constraints, &local_stream);
+        self.add_tracks(&peer_connection, &mut local_stream, &mut senders, &kinds).await?;
+
+        let call = Call {
+            id: call_id,
+            remote_peer: callee.clone(),
+            peer_connection,
+            state: CallState::Calling,
+            constraints: constraints.clone(),
+            local_stream,
+            senders,
+            remote_streams: HashMap::new(),
+            audio_muted: false,
+            video_enabled: true,
+            video_paused: false,
+            data_channels: HashMap::new(),
+            remote_control: RemoteControlGrants::default(),
+            incoming: false,
+            shared_tracks: HashSet::new(),
+            screen_shares: Vec::new(),
+            remote_screen_shares: Vec::new(),
+            reconnecting_since: None,
+        };
+
+        self.calls.insert(call_id, call);
+        self.emit(CallEvent::StateChanged {
+            call_id,
+            from: CallState::Idle,
+            to: CallState::Calling,
+        });
+
+        // Emit call initiated event
+        self.emit(CallEvent::CallInitiated {
+            call_id,
+            callee,
+            constraints,
+        });
+        if let Some(timeout) = self.config.ring_timeout {
+            self.expire_unanswered(call_id, timeout);
+        }
+        
+        Ok(call_id)
+    }
+
+    /// Set up a peer connection for a likely call to `peer` in advance
+    ///
+    /// The next [`Self::initiate_call`] to them picks it up instead of
+    /// creating its own, if it comes within the configured prewarm TTL.
+    /// Prewarming a peer again keeps the connection already set up.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the peer connection cannot be created
+    pub async fn prewarm(&self, peer: &I) -> Result<(), CallError> {
+        self.drop_expired_prewarms().await;
+        let key = peer.to_string_repr();
+        if self.warm.contains_key(&key) {
+            return Ok(());
+        }
+        let peer_connection = self.new_peer_connection(&key).await?;
+        tracing::debug!("Prewarmed peer connection to {}", key);
+        if let Some((_, (raced, _))) = self.warm.remove(&key) {
+            // Prewarmed concurrently; keep the newest
+            let _ = raced.close().await;
+        }
+        self.warm.insert(key, (peer_connection, Instant::now()));
+        Ok(())
+    }
+
+    /// Whether a prewarmed peer connection is waiting for a call to `peer`
+    #[must_use]
+    pub fn is_prewarmed(&self, peer: &I) -> bool {
+        self.warm
+            .get(&peer.to_string_repr())
+            .is_some_and(|entry| entry.1.elapsed() < self.config.prewarm_ttl)
+    }
+
+    /// Take the prewarmed peer connection for `peer`, if still fresh
+    async fn take_prewarmed(&self, peer: &I) -> Option<Arc<RTCPeerConnection>> {
+        self.drop_expired_prewarms().await;
+        self.warm
+            .remove(&peer.to_string_repr())
+            .map(|(_, (peer_connection, _))| peer_connection)
+    }
+
+    /// Close prewarmed peer connections no call picked up in time
+    async fn drop_expired_prewarms(&self) {
+        let ttl = self.config.prewarm_ttl;
+        let expired: Vec<_> = self
+            .warm
+            .iter()
+            .filter(|entry| entry.1.elapsed() >= ttl)
+            .map(|entry| entry.key().clone())
+            .collect();
+        for key in expired {
+            if let Some((_, (peer_connection, _))) = self.warm.remove(&key) {
+                let _ = peer_connection.close().await;
+            }
+        }
+    }
+
+    /// Take an SDP offer from `caller` to `callee`, the local identity
+    ///
+    /// An offer for a new call sets up its peer connection and rings as
+    /// [`CallEvent::IncomingCall`]; answer it with [`Self::accept_call`].
+    /// An offer for a call we already have renegotiates it, such as after
+    /// the remote party's [`Self::add_media`], and the answer to send back
+    /// is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the offer is empty or invalid, an existing call is
+    /// with another peer, or the call limit is reached
+    pub async fn receive_offer(
+        &self,
+        call_id: CallId,
+        caller: I,
+        callee: I,
+        sdp: String,
+    ) -> Result<Option<String>, CallError> {
+        if sdp.trim().is_empty() {
+            return Err(CallError::EmptySdp);
+        }
+        let offer = RTCSessionDescription::offer(sdp.clone()).map_err(CallError::Negotiation)?;
+
+        if let Some(peer_connection) = self.peer_connection(call_id) {
+            let from_caller = self
+                .remote_peer(call_id)
+                .is_some_and(|peer| peer.to_string_repr() == caller.to_string_repr());
+            if !from_caller {
+                tracing::warn!("Ignoring offer for call {} from {}", call_id, caller.to_string_repr());
+                return Err(CallError::InvalidState);
+            }
+            tracing::debug!("Renegotiating call {}", call_id);
+            peer_connection.set_remote_description(offer).await
+                .map_err(CallError::Negotiation)?;
+            let deadline = Deadline::after(self.config.negotiation_timeout);
+            let answer = self.answer(call_id, &peer_connection, &deadline).await?;
+            return Ok(Some(answer));
+        }
+
+        if self.calls.len() >= self.config.max_concurrent_calls {
+            return Err(CallError::CallLimitReached(self.config.max_concurrent_calls));
+        }
+
+        tracing::info!("Incoming call {} from peer: {}", call_id, caller.to_string_repr());
+
+        let peer_connection = self.new_peer_connection(&caller.to_string_repr()).await?;
+        self.watch_data_channels(call_id, &peer_connection);
+        self.watch_remote_tracks(call_id, &peer_connection);
+        self.watch_connection_state(call_id, &peer_connection);
+        if let Err(e) = peer_connection.set_remote_description(offer).await {
+            tracing::error!("Failed to set remote description for call {}: {}", call_id, e);
+            let _ = peer_connection.close().await;
+            return Err(CallError::Negotiation(e));
+        }
+
+        let call = Call {
+            id: call_id,
+            remote_peer: caller.clone(),
+            peer_connection,
+            state: CallState::Ringing,
+            constraints: MediaConstraints::data_only(),
+            local_stream: MediaStream::new(format!("call-{call_id}")),
+            senders: HashMap::new(),
+            remote_streams: HashMap::new(),
+            audio_muted: false,
+            video_enabled: true,
+            video_paused: false,
+            data_channels: HashMap::new(),
+            remote_control: RemoteControlGrants::default(),
+            incoming: true,
+            shared_tracks: HashSet::new(),
+            screen_shares: Vec::new(),
+            remote_screen_shares: Vec::new(),
+            reconnecting_since: None,
+        };
+        self.calls.insert(call_id, call);
+        self.emit(CallEvent::StateChanged {
+            call_id,
+            from: CallState::Idle,
+            to: CallState::Ringing,
+        });
+
+        self.emit(CallEvent::IncomingCall {
+            offer: CallOffer {
+                call_id,
+                caller,
+                callee,
+                media_types: offered_media(&sdp),
+                sdp,
+                timestamp: chrono::Utc::now(),
+            },
+        });
+        if let Some(timeout) = self.config.answer_timeout {
+            self.expire_unanswered(call_id, timeout);
+        }
+        Ok(None)
+    }
+
+    /// Fail a call with [`FailureReason::NoAnswer`] if it is still
+    /// unanswered after `timeout`
+    fn expire_unanswered(&self, call_id: CallId, timeout: Duration) {
+        let watch = self.watch();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            watch.fail(
+                call_id,
+                &[CallState::Calling, CallState::Retrying, CallState::Ringing],
+                FailureReason::NoAnswer,
+                format!("not answered within {timeout:?}"),
+            );
+        });
+    }
+
+    fn watch(&self) -> CallWatch<I> {
+        CallWatch {
+            calls: Arc::downgrade(&self.calls),
+            event_sender: self.event_sender.clone(),
+            event_bus: Arc::clone(&self.event_bus),
+        }
+    }
+
+    /// Follow a call's peer connection, moving the call to
+    /// [`CallState::Reconnecting`] while it is down
+    fn watch_connection_state(&self, call_id: CallId, peer_connection: &RTCPeerConnection) {
+        let watch = self.watch();
+        let grace = self.config.reconnect_grace;
+        peer_connection.on_peer_connection_state_change(Box::new(
+            move |state: RTCPeerConnectionState| {
+                match state {
+                    RTCPeerConnectionState::Disconnected | RTCPeerConnectionState::Failed => {
+                        watch.connection_lost(call_id, grace);
+                    }
+                    RTCPeerConnectionState::Connected => {
+                        watch.connection_restored(call_id);
+                    }
+                    _ => {}
+                }
+                Box::pin(async {})
+            },
+        ));
+    }
+
+    /// Report that a connected call's connection dropped, such as its QUIC
+    /// connection closing
+    ///
+    /// The call waits in [`CallState::Reconnecting`] and fails with
+    /// [`FailureReason::ConnectionLost`] unless
+    /// [`Self::connection_restored`] comes within
+    /// [`CallManagerConfig::reconnect_grace`]. Returns false if the call
+    /// is not connected.
+    pub fn connection_lost(&self, call_id: CallId) -> bool {
+        self.watch().connection_lost(call_id, self.config.reconnect_grace)
+    }
+
+    /// Report that a reconnecting call's connection is back
+    ///
+    /// Returns false if the call is not reconnecting.
+    pub fn connection_restored(&self, call_id: CallId) -> bool {
+        self.watch().connection_restored(call_id)
+    }
+
+    /// Open a connection to a peer for text messaging only
+    ///
+    /// The connection is set up like a call without media: negotiate it
+    /// with [`Self::create_offer`] and end it with [`Self::end_call`].
+    /// Messages arrive as [`CallEvent::TextReceived`]; add media with
+    /// [`Self::add_media`] to turn it into a call.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the connection cannot be set up
+    pub async fn connect_data(&self, peer: I) -> Result<CallId, CallError> {
+        let call_id = self.initiate_call(peer, MediaConstraints::data_only()).await?;
+        if let Err(e) = self.open_message_channel(call_id).await {
+            let _ = self.end_call(call_id).await;
+            return Err(e);
+        }
+        Ok(call_id)
+    }
+
+    /// Add media to a call, such as a connection opened with
+    /// [`Self::connect_data`]
+    ///
+    /// Kinds the call already sends are left alone. Renegotiate with
+    /// [`Self::create_offer`] for the remote party to receive the new
+    /// tracks; data channels carry on meanwhile.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or a track cannot be set up
+    pub async fn add_media(
+        &self,
+        call_id: CallId,
+        constraints: MediaConstraints,
+    ) -> Result<(), CallError> {
+        let (peer_connection, mut local_stream) = {
+            let call = self
+                .calls
+                .get(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            (Arc::clone(&call.peer_connection), call.local_stream.clone())
+        };
+        let existing = local_stream.len();
+        let mut senders = HashMap::new();
+        let kinds = track_kinds(&constraints, &local_stream);
+        self.add_tracks(&peer_connection, &mut local_stream, &mut senders, &kinds)
+            .await?;
+
+        let Some(mut call) = self.calls.get_mut(&call_id) else {
+            // Ended meanwhile, leaving only the tracks added here
+            let mut media_manager = self.media_manager.write().await;
+            for track in &local_stream.tracks()[existing..] {
+                media_manager.remove_track(&track.id);
+            }
+            return Err(CallError::CallNotFound(call_id.to_string()));
+        };
+        call.local_stream = local_stream;
+        call.senders.extend(senders);
+        if kinds.contains(&MediaType::Audio) {
+            call.constraints.audio = true;
+            call.constraints.audio_constraints = constraints.audio_constraints;
+        }
+        if kinds.contains(&MediaType::Video) {
+            call.constraints.video = true;
+            call.constraints.video_constraints = constraints.video_constraints;
+        }
+        tracing::info!("Added {:?} to call {}", kinds, call_id);
+        Ok(())
+    }
+
+    /// Create tracks of `kinds` to send on several calls at once, such as
+    /// to every participant of a mesh conference
+    ///
+    /// Samples written to a track go out on every call it is shared with.
+    /// Add the tracks to calls with [`Self::share_stream`], and drop them
+    /// with [`Self::release_stream`] once no call sends them.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a track cannot be created
+    pub async fn create_shared_stream(
+        &self,
+        stream_id: &str,
+        kinds: &[MediaType],
+    ) -> Result<MediaStream, CallError> {
+        let mut stream = MediaStream::new(stream_id);
+        let mut media_manager = self.media_manager.write().await;
+        for kind in kinds {
+            let created = match kind {
+                MediaType::Audio => media_manager.create_audio_track_in(stream_id).await,
+                _ => media_manager.create_video_track_in(stream_id).await,
+            };
+            let track = created
+                .map_err(|e| CallError::TrackSetup { kind: kind.clone(), source: e.into() })?
+                .clone();
+            stream.add_track(track);
+        }
+        Ok(stream)
+    }
+
+    /// Send the tracks of a shared stream on a call
+    ///
+    /// Kinds the call already sends are skipped. Ending the call leaves the
+    /// tracks to the other calls sharing them. Renegotiate with
+    /// [`Self::create_offer`] if the call is already connected.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or a track cannot be added
+    pub async fn share_stream(&self, call_id: CallId, stream: &MediaStream) -> Result<(), CallError> {
+        let (peer_connection, local_stream) = {
+            let call = self
+                .calls
+                .get(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            (Arc::clone(&call.peer_connection), call.local_stream.clone())
+        };
+        let mut added = Vec::new();
+        for track in stream.tracks() {
+            let sends_kind = local_stream
+                .tracks()
+                .iter()
+                .any(|sent| sent.track_type == track.track_type);
+            if sends_kind {
+                continue;
+            }
+            let local: Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync> =
+                track.track.clone();
+            let sender = peer_connection.add_track(local).await
+                .map_err(|e| CallError::TrackSetup { kind: track.track_type.clone(), source: e.into() })?;
+            added.push((track.clone(), sender));
+        }
+
+        let mut call = self
+            .calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        for (track, sender) in added {
+            match track.track_type {
+                MediaType::Audio => call.constraints.audio = true,
+                MediaType::Video => call.constraints.video = true,
+                MediaType::ScreenShare => call.constraints.screen_share = true,
+                MediaType::DataChannel => {}
+            }
+            call.shared_tracks.insert(track.id.clone());
+            call.senders.insert(track.id.clone(), sender);
+            call.local_stream.add_track(track);
+        }
+        Ok(())
+    }
+
+    /// Drop the tracks of a stream from [`Self::create_shared_stream`]
+    pub async fn release_stream(&self, stream: &MediaStream) {
+        let mut media_manager = self.media_manager.write().await;
+        for track in stream.tracks() {
+            media_manager.remove_track(&track.id);
+        }
+    }
+
+    /// Create a peer connection to `peer`
+    ///
+    /// Registers the default codecs and gathers only on paths the policy
+    /// allows.
+    async fn new_peer_connection(&self, peer: &str) -> Result<Arc<RTCPeerConnection>, CallError> {
+        let mut media_engine = webrtc::api::media_engine::MediaEngine::default();
+        media_engine.register_default_codecs().map_err(CallError::PeerConnection)?;
+        let api = webrtc::api::APIBuilder::new()
+            .with_media_engine(media_engine)
+            .with_setting_engine(self.config.path_policy.setting_engine())
+            .build();
+        let peer_connection = api.new_peer_connection(
+            webrtc::peer_connection::configuration::RTCConfiguration::default(),
+        ).await.map_err(|e| {
+            tracing::error!("Failed to create peer connection to {}: {}", peer, e);
+            CallError::PeerConnection(e)
+        })?;
+        tracing::debug!("Created peer connection to {}", peer);
+        Ok(Arc::new(peer_connection))
+    }
+
+    /// Create tracks of `kinds` in `stream` and send them on the peer
+    /// connection, recording their senders in `senders`
+    async fn add_tracks(
+        &self,
+        peer_connection: &RTCPeerConnection,
+        stream: &mut MediaStream,
+        senders: &mut HashMap<String, Arc<RTCRtpSender>>,
+        kinds: &[MediaType],
+    ) -> Result<(), CallError> {
+        let mut media_manager = self.media_manager.write().await;
+        for kind in kinds {
+            let created = match kind {
+                MediaType::Audio => media_manager.create_audio_track_in(&stream.id).await,
+                _ => media_manager.create_video_track_in(&stream.id).await,
+            };
+            let track = created
+                .map_err(|e| CallError::TrackSetup { kind: kind.clone(), source: e.into() })?
+                .clone();
+            stream.add_track(track.clone());
+
+            let local: Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync> = track.track;
+            let sender = peer_connection.add_track(local).await
+                .map_err(|e| CallError::TrackSetup { kind: kind.clone(), source: e.into() })?;
+            senders.insert(track.id, sender);
+        }
+        Ok(())
+    }
+
+    /// Accept a call
+    ///
+    /// Accepting an incoming call sends tracks per `constraints` and
+    /// returns the SDP answer for the caller, within the configured
+    /// negotiation timeout. Outgoing calls are marked connected once the
+    /// remote party answered.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if call cannot be accepted or the answer cannot be
+    /// created
+    pub async fn accept_call(
+        &self,
+        call_id: CallId,
+        constraints: MediaConstraints,
+    ) -> Result<Option<String>, CallError> {
+        if self.calls.get(&call_id).is_some_and(|call| call.incoming) {
+            return self.answer_call(call_id, constraints).await.map(Some);
+        }
+        if let Some(mut call) = self.calls.get_mut(&call_id) {
+            // Validate state transition
+            match call.state {
+                CallState::Calling | CallState::Ringing | CallState::Connecting => {
+                    self.set_state(&mut call, CallState::Connected)?;
+                    
+                    // Emit connection established event
+                    self.emit(CallEvent::ConnectionEstablished { call_id });
+                    
+                    tracing::info!("Call {} accepted", call_id);
+                    Ok(None)
+                }
+                _ => {
+                    tracing::warn!("Invalid state transition: cannot accept call {} in state {:?}", call_id, call.state);
+                    Err(CallError::InvalidState)
+                }
+            }
+        } else {
+            tracing::warn!("Attempted to accept non-existent call {}", call_id);
+            Err(CallError::CallNotFound(call_id.to_string()))
+        }
+    }
+
+    /// Answer an incoming call's offer, sending tracks per `constraints`
+    async fn answer_call(
+        &self,
+        call_id: CallId,
+        constraints: MediaConstraints,
+    ) -> Result<String, CallError> {
+        let (peer_connection, mut local_stream) = {
+            let mut call = self
+                .calls
+                .get_mut(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            if call.state != CallState::Ringing {
+                tracing::warn!("Invalid state transition: cannot accept call {} in state {:?}", call_id, call.state);
+                return Err(CallError::InvalidState);
+            }
+            // Claim the call so accepting it twice fails
+            self.set_state(&mut call, CallState::Connecting)?;
+            (Arc::clone(&call.peer_connection), call.local_stream.clone())
+        };
+
+        let deadline = Deadline::after(self.config.negotiation_timeout);
+        let existing = local_stream.len();
+        let mut senders = HashMap::new();
+        let kinds = track_kinds(&constraints, &local_stream);
+        let added = self
+            .add_tracks(&peer_connection, &mut local_stream, &mut senders, &kinds)
+            .await;
+        let answered = match added {
+            Ok(()) => self.answer(call_id, &peer_connection, &deadline).await,
+            Err(e) => Err(e),
+        };
+
+        let Some(mut call) = self.calls.get_mut(&call_id) else {
+            // Ended meanwhile, leaving only the tracks added here
+            let mut media_manager = self.media_manager.write().await;
+            for track in &local_stream.tracks()[existing..] {
+                media_manager.remove_track(&track.id);
+            }
+            return Err(CallError::CallNotFound(call_id.to_string()));
+        };
+        // Kept on failure too, so ending the call removes the tracks
+        call.local_stream = local_stream;
+        call.senders.extend(senders);
+        let answer = match answered {
+            Ok(answer) => answer,
+            Err(e) => {
+                let _ = self.set_state(&mut call, CallState::Failed);
+                drop(call);
+                tracing::error!("Failed to answer call {}: {}", call_id, e);
+                self.emit(CallEvent::ConnectionFailed {
+                    call_id,
+                    reason: FailureReason::Connection,
+                    error: e.to_string(),
+                });
+                return Err(e);
+            }
+        };
+        call.constraints = constraints;
+        self.set_state(&mut call, CallState::Connected)?;
+        drop(call);
+
+        self.emit(CallEvent::ConnectionEstablished { call_id });
+        tracing::info!("Call {} answered with {:?}", call_id, kinds);
+        Ok(answer)
+    }
+
+    /// Create the SDP answer to a remote offer already set on the peer
+    /// connection, bounded by `deadline`
+    ///
+    /// Candidates are not trickled, so the answer waits for ICE gathering
+    /// to finish and carries them all.
+    async fn answer(
+        &self,
+        call_id: CallId,
+        peer_connection: &RTCPeerConnection,
+        deadline: &Deadline,
+    ) -> Result<String, CallError> {
+        tracing::debug!("Creating SDP answer for call {}", call_id);
+        deadline.run(async {
+            let answer = peer_connection.create_answer(None).await
+                .map_err(|e| {
+                    tracing::error!("Failed to create answer for call {}: {}", call_id, e);
+                    CallError::Negotiation(e)
+                })?;
+            let mut gathered = peer_connection.gathering_complete_promise().await;
+            peer_connection.set_local_description(answer).await
+                .map_err(|e| {
+                    tracing::error!("Failed to set local description for call {}: {}", call_id, e);
+                    CallError::Negotiation(e)
+                })?;
+            let _ = gathered.recv().await;
+            let answer = peer_connection.local_description().await
+                .ok_or(CallError::InvalidState)?;
+            Ok::<_, CallError>(answer.sdp)
+        })
+        .await?
+    }
+
+    /// Reject a call
+    ///
+    /// # Errors
+    ///
+    /// Returns error if call cannot be rejected
+    pub async fn reject_call(&self, call_id: CallId) -> Result<(), CallError> {
+        if let Some(mut call) = self.calls.get_mut(&call_id) {
+            // Validate state transition - can only reject calls that are not yet connected/ended
+            match call.state {
+                CallState::Calling | CallState::Ringing | CallState::Connecting => {
+                    self.set_state(&mut call, CallState::Failed)?;
+                    
+                    // Emit call rejected event
+                    self.emit(CallEvent::CallRejected { call_id });
+                    
+                    Ok(())
+                }
+                _ => {
+                    tracing::warn!("Invalid state transition: cannot reject call {} in state {:?}", call_id, call.state);
+                    Err(CallError::InvalidState)
+                }
+            }
+        } else {
+            Err(CallError::CallNotFound(call_id.to_string()))
+        }
+    }
+
+    /// Move a call being placed into or out of waiting to redial
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or is not being placed
+    pub fn set_retrying(&self, call_id: CallId, retrying: bool) -> Result<(), CallError> {
+        let mut call = self
+            .calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        let to = if retrying { CallState::Retrying } else { CallState::Calling };
+        self.set_state(&mut call, to)
+    }
+
+    /// Hang up a call we placed before it is answered; end it to drop it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist, is incoming or was
+    /// already answered
+    pub fn cancel_call(&self, call_id: CallId) -> Result<(), CallError> {
+        let mut call = self
+            .calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        if call.incoming || !matches!(call.state, CallState::Calling | CallState::Retrying) {
+            return Err(CallError::InvalidState);
+        }
+        tracing::info!("Cancelling call {}", call_id);
+        self.set_state(&mut call, CallState::Ending)
+    }
+
+    /// Mark a call failed and report why; end it to drop it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or is already over
+    pub fn fail_call(
+        &self,
+        call_id: CallId,
+        reason: FailureReason,
+        error: String,
+    ) -> Result<(), CallError> {
+        {
+            let mut call = self
+                .calls
+                .get_mut(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            self.set_state(&mut call, CallState::Failed)?;
+        }
+        tracing::error!("Call {} failed: {}", call_id, error);
+        self.emit(CallEvent::ConnectionFailed { call_id, reason, error });
+        Ok(())
+    }
+
+    /// End a call, hanging up
+    ///
+    /// # Errors
+    ///
+    /// Returns error if call cannot be ended
+    pub async fn end_call(&self, call_id: CallId) -> Result<(), CallError> {
+        self.end_call_with(call_id, ByeReason::HungUp).await
+    }
+
+    /// End a call for `reason`, reported in [`CallEvent::CallEnded`]
+    ///
+    /// # Errors
+    ///
+    /// Returns error if call cannot be ended
+    pub async fn end_call_with(&self, call_id: CallId, reason: ByeReason) -> Result<(), CallError> {
+        if let Some((_, call)) = self.calls.remove(&call_id) {
+            // Remove the call's own tracks from media manager
+            let mut media_manager = self.media_manager.write().await;
+            for track in call.local_stream.tracks() {
+                if !call.shared_tracks.contains(&track.id) {
+                    media_manager.remove_track(&track.id);
+                }
+            }
+            drop(media_manager);
+
+            // Close the peer connection
+            let _ = call.peer_connection.close().await;
+
+            if reason == ByeReason::Busy && !call.incoming {
+                self.emit(CallEvent::RemoteBusy { call_id });
+            }
+
+            if !call.state.is_final() {
+                self.emit(CallEvent::StateChanged {
+                    call_id,
+                    from: call.state,
+                    to: CallState::Ended,
+                });
+            }
+            
+            // Emit call ended event
+            self.emit(CallEvent::CallEnded { call_id, reason });
+            
+            tracing::info!("Ended call {} ({:?}) and cleaned up {} tracks", call_id, reason, call.local_stream.len());
+            Ok(())
+        } else {
+            Err(CallError::CallNotFound(call_id.to_string()))
+        }
+    }
+
+    /// Get call state
+    #[must_use]
+    pub async fn get_call_state(&self, call_id: CallId) -> Option<CallState> {
+        self.calls.get(&call_id).map(|call| call.state)
+    }
+
+    /// Get the remote peer of a call
+    #[must_use]
+    pub fn remote_peer(&self, call_id: CallId) -> Option<I> {
+        self.calls.get(&call_id).map(|call| call.remote_peer.clone())
+    }
+
+    /// Whether a call was placed by the remote party
+    #[must_use]
+    pub fn is_incoming(&self, call_id: CallId) -> bool {
+        self.calls.get(&call_id).is_some_and(|call| call.incoming)
+    }
+
+    /// A call carrying audio or video that was answered and has not ended
+    ///
+    /// Data-only connections and calls still ringing do not count.
+    #[must_use]
+    pub fn active_call(&self) -> Option<CallId> {
+        self.calls
+            .iter()
+            .find(|call| {
+                matches!(
+                    call.state,
+                    CallState::Connecting
+                        | CallState::Connected
+                        | CallState::Reconnecting
+                        | CallState::Parked
+                ) && (call.constraints.has_audio() || call.constraints.has_video())
+            })
+            .map(|call| call.id)
+    }
+
+    /// Get the media constraints of a call
+    #[must_use]
+    pub fn constraints(&self, call_id: CallId) -> Option<MediaConstraints> {
+        self.calls.get(&call_id).map(|call| call.constraints.clone())
+    }
+
+    /// Metadata of every call, for persisting across restarts
+    #[must_use]
+    pub fn call_snapshots(&self) -> Vec<CallSnapshot<I>> {
+        self.calls
+            .iter()
+            .map(|call| CallSnapshot {
+                call_id: call.id,
+                remote_peer: call.remote_peer.clone(),
+                state: call.state,
+                constraints: call.constraints.clone(),
+            })
+            .collect()
+    }
+
+    /// Park a connected call, pausing its outgoing media
+    ///
+    /// The peer connection stays up so the call resumes without
+    /// renegotiation.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or is not connected
+    pub async fn park_call(&self, call_id: CallId) -> Result<(), CallError> {
+        let peer_connection = {
+            let mut call = self
+                .calls
+                .get_mut(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            if call.state != CallState::Connected {
+                return Err(CallError::InvalidState);
+            }
+            self.set_state(&mut call, CallState::Parked)?;
+            Arc::clone(&call.peer_connection)
+        };
+
+        for sender in peer_connection.get_senders().await {
+            sender.replace_track(None).await
+                .map_err(CallError::TrackSwitch)?;
+        }
+
+        tracing::info!("Parked call {}", call_id);
+        Ok(())
+    }
+
+    /// Resume a parked call's outgoing media, except tracks that are
+    /// muted or paused
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or is not parked
+    pub async fn resume_call(&self, call_id: CallId) -> Result<(), CallError> {
+        {
+            let mut call = self
+                .calls
+                .get_mut(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            if call.state != CallState::Parked {
+                return Err(CallError::InvalidState);
+            }
+            self.set_state(&mut call, CallState::Connected)?;
+        }
+
+        self.switch_tracks(call_id, |_| true).await?;
+        tracing::info!("Resumed call {}", call_id);
+        Ok(())
+    }
+
+    /// Pause or resume a call's outgoing video and screen share, leaving
+    /// audio running
+    ///
+    /// Parked calls are left alone; resuming them restores every track.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or a track cannot be switched
+    pub async fn set_video_paused(&self, call_id: CallId, paused: bool) -> Result<(), CallError> {
+        self.calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?
+            .video_paused = paused;
+        self.switch_tracks(call_id, |kind| kind != MediaType::Audio).await?;
+
+        tracing::info!("{} video for call {}", if paused { "Paused" } else { "Resumed" }, call_id);
+        Ok(())
+    }
+
+    /// Mute or unmute a call's outgoing audio
+    ///
+    /// A muted call sends no audio until unmuted, even across parking.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or a track cannot be switched
+    pub async fn set_audio_muted(&self, call_id: CallId, muted: bool) -> Result<(), CallError> {
+        self.calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?
+            .audio_muted = muted;
+        self.switch_tracks(call_id, |kind| kind == MediaType::Audio).await?;
+
+        tracing::info!("{} audio for call {}", if muted { "Muted" } else { "Unmuted" }, call_id);
+        Ok(())
+    }
+
+    /// Turn a call's outgoing video and screen share off or on
+    ///
+    /// Unlike [`set_video_paused`](Self::set_video_paused), which follows
+    /// bandwidth, this is the user's choice: video stays off until enabled
+    /// again, whatever the bandwidth does.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or a track cannot be switched
+    pub async fn set_video_enabled(&self, call_id: CallId, enabled: bool) -> Result<(), CallError> {
+        self.calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?
+            .video_enabled = enabled;
+        self.switch_tracks(call_id, |kind| kind != MediaType::Audio).await?;
+
+        tracing::info!("{} video for call {}", if enabled { "Enabled" } else { "Disabled" }, call_id);
+        Ok(())
+    }
+
+    /// Whether a call's outgoing audio is muted
+    #[must_use]
+    pub fn is_audio_muted(&self, call_id: CallId) -> Option<bool> {
+        self.calls.get(&call_id).map(|call| call.audio_muted)
+    }
+
+    /// Whether the user has a call's outgoing video turned on
+    #[must_use]
+    pub fn is_video_enabled(&self, call_id: CallId) -> Option<bool> {
+        self.calls.get(&call_id).map(|call| call.video_enabled)
+    }
+
+    /// Capture `device_id`, or the default microphone, into a call's audio
+    /// track until the returned handle is dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call is not found, has no audio track, or the
+    /// device cannot be opened
+    pub async fn start_audio_capture(
+        &self,
+        call_id: CallId,
+        device_id: Option<&str>,
+    ) -> Result<AudioCapture, CallError> {
+        let track_id = {
+            let call = self
+                .calls
+                .get(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            call.local_stream
+                .audio_tracks()
+                .next()
+                .map(|track| track.id.clone())
+                .ok_or_else(|| CallError::TrackSetup {
+                    kind: MediaType::Audio,
+                    source: format!("call {call_id} has no audio track").into(),
+                })?
+        };
+        self.media_manager
+            .read()
+            .await
+            .start_audio_capture(&track_id, device_id)
+            .await
+            .map_err(|e| CallError::TrackSetup {
+                kind: MediaType::Audio,
+                source: e.into(),
+            })
+    }
+
+    /// Tracks we send on a call, as one stream
+    #[must_use]
+    pub fn local_stream(&self, call_id: CallId) -> Option<MediaStream> {
+        self.calls.get(&call_id).map(|call| call.local_stream.clone())
+    }
+
+    /// Streams the remote party sends on a call; empty for unknown calls
+    #[must_use]
+    pub fn remote_streams(&self, call_id: CallId) -> Vec<MediaStream<RemoteTrack>> {
+        self.calls
+            .get(&call_id)
+            .map(|call| call.remote_streams.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Take an ended remote track out of its stream, e.g. once reading it
+    /// reports end of stream
+    ///
+    /// Emits [`CallEvent::RemoteTrackRemoved`]; a stream left empty is
+    /// dropped.
+    pub fn remove_remote_track(&self, call_id: CallId, track_id: &str) -> Option<RemoteTrack> {
+        let (stream_id, track) = {
+            let mut call = self.calls.get_mut(&call_id)?;
+            let (stream_id, track) = call
+                .remote_streams
+                .iter_mut()
+                .find_map(|(id, stream)| Some((id.clone(), stream.remove_track(track_id)?)))?;
+            if call.remote_streams.get(&stream_id).is_some_and(MediaStream::is_empty) {
+                call.remote_streams.remove(&stream_id);
+            }
+            (stream_id, track)
+        };
+        self.emit(CallEvent::RemoteTrackRemoved {
+            call_id,
+            stream_id,
+            track_id: track_id.to_string(),
+        });
+        Some(track)
+    }
+
+    /// Add a screen share to a call, in a stream of its own, and return the
+    /// track to feed it with
+    ///
+    /// A call carries any number of screen shares, such as one per
+    /// monitor, beside its camera. `label` names the share for the remote
+    /// party. Renegotiate with [`Self::create_offer`] for the remote party
+    /// to receive it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the track cannot be set up
+    pub async fn add_screen_share(
+        &self,
+        call_id: CallId,
+        label: &str,
+        codec: VideoCodec,
+        width: u32,
+        height: u32,
+    ) -> Result<VideoTrack, CallError> {
+        let peer_connection = self
+            .peer_connection(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        let stream_id = format!("screen-{}", Uuid::new_v4().simple());
+        let track = self
+            .media_manager
+            .write()
+            .await
+            .create_screen_share_track_in(&stream_id, codec, width, height)
+            .await
+            .map_err(|e| CallError::TrackSetup { kind: MediaType::ScreenShare, source: e.into() })?;
+
+        let local: Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync> =
+            Arc::clone(&track.webrtc_track);
+        let sender = match peer_connection.add_track(local).await {
+            Ok(sender) => sender,
+            Err(e) => {
+                self.media_manager.write().await.remove_track(&track.id);
+                let kind = MediaType::ScreenShare;
+                return Err(CallError::TrackSetup { kind, source: e.into() });
+            }
+        };
+
+        let sent = WebRtcTrack {
+            track: Arc::clone(&track.webrtc_track),
+            track_type: MediaType::ScreenShare,
+            id: track.id.clone(),
+        };
+        let sending = {
+            let Some(mut call) = self.calls.get_mut(&call_id) else {
+                // Ended meanwhile
+                self.media_manager.write().await.remove_track(&track.id);
+                return Err(CallError::CallNotFound(call_id.to_string()));
+            };
+            call.constraints.screen_share = true;
+            call.senders.insert(track.id.clone(), sender);
+            call.local_stream.add_track(sent.clone());
+            call.screen_shares.push(ScreenShare {
+                track_id: track.id.clone(),
+                stream_id,
+                label: label.to_string(),
+                paused: false,
+            });
+            call.is_sending(&sent)
+        };
+        if !sending {
+            // Held back with the call's other video
+            self.switch_tracks(call_id, |kind| kind == MediaType::ScreenShare).await?;
+        }
+
+        tracing::info!("Sharing {:?} on call {} as track {}", label, call_id, track.id);
+        Ok(track)
+    }
+
+    /// Capture a display or window into a new screen share on a call,
+    /// labelled with the source's name, until the returned handle is
+    /// dropped
+    ///
+    /// See [`Self::add_screen_share`]. The share is sent at up to the
+    /// default maximum resolution, with AV1 tuned for screen content when
+    /// the `av1` feature is on and H.264 otherwise. Dropping the handle
+    /// takes the share off the call as [`Self::remove_screen_share`] does,
+    /// then emits [`CallEvent::ScreenShareEnded`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist, the track cannot be set up
+    /// or the source cannot be captured
+    pub async fn start_screen_share(
+        &self,
+        call_id: CallId,
+        source: &ScreenSource,
+        config: ScreenCaptureConfig,
+    ) -> Result<ScreenCapturer, CallError> {
+        let codec = if cfg!(feature = "av1") { VideoCodec::Av1 } else { VideoCodec::H264 };
+        let size = MaxResolution::default();
+        let track = self
+            .add_screen_share(call_id, &source.name, codec, size.width, size.height)
+            .await?;
+        let track_id = track.id.clone();
+        let captured = self
+            .media_manager
+            .read()
+            .await
+            .start_screen_capture(track, Some(&source.id), config)
+            .await;
+        let capturer = match captured {
+            Ok(capturer) => capturer,
+            Err(e) => {
+                // Nothing will be sent on the share; keep it off the remote's screen
+                if let Err(removal) = self.remove_screen_share(call_id, &track_id).await {
+                    tracing::warn!("Failed to remove screen share {}: {}", track_id, removal);
+                }
+                let kind = MediaType::ScreenShare;
+                return Err(CallError::TrackSetup { kind, source: e.into() });
+            }
+        };
+
+        // Drop can happen anywhere; removal needs the runtime capture began on
+        let runtime = tokio::runtime::Handle::current();
+        let media_manager = Arc::clone(&self.media_manager);
+        let watch = self.watch();
+        Ok(capturer.on_stop(move || {
+            runtime.spawn(async move {
+                let Some(calls) = watch.calls.upgrade() else {
+                    return;
+                };
+                match detach_screen_share(&calls, &media_manager, call_id, &track_id).await {
+                    // Removed by hand already, or the call ended
+                    Err(CallError::CallNotFound(_) | CallError::TrackNotFound(_)) => return,
+                    Err(e) => tracing::warn!("Failed to detach screen share {}: {}", track_id, e),
+                    Ok(()) => {}
+                }
+                watch.emit(CallEvent::ScreenShareEnded { call_id, track_id });
+            });
+        }))
+    }
+
+    /// Stop sending one of a call's screen shares and drop its track,
+    /// leaving the camera and other shares running
+    ///
+    /// The share's sender is detached from the peer connection.
+    /// Renegotiate with [`Self::create_offer`] for the remote party to stop
+    /// receiving it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or screen share does not exist, or the
+    /// sender cannot be detached
+    pub async fn remove_screen_share(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), CallError> {
+        detach_screen_share(&self.calls, &self.media_manager, call_id, track_id).await
+    }
+
+    /// Pause or resume one of a call's screen shares, leaving the others
+    /// and the camera running
+    ///
+    /// A resumed share is still held back while the call's video is off
+    /// or paused.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or screen share does not exist, or the
+    /// track cannot be switched
+    pub async fn set_screen_share_paused(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        paused: bool,
+    ) -> Result<(), CallError> {
+        {
+            let mut call = self
+                .calls
+                .get_mut(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            let share = call
+                .screen_shares
+                .iter_mut()
+                .find(|share| share.track_id == track_id)
+                .ok_or_else(|| CallError::TrackNotFound(track_id.to_string()))?;
+            share.paused = paused;
+        }
+        self.switch_tracks(call_id, |kind| kind == MediaType::ScreenShare).await?;
+
+        tracing::info!("{} screen share {} on call {}", if paused { "Paused" } else { "Resumed" }, track_id, call_id);
+        Ok(())
+    }
+
+    /// Screen shares we send on a call; empty for unknown calls
+    #[must_use]
+    pub fn screen_shares(&self, call_id: CallId) -> Vec<ScreenShare> {
+        self.calls
+            .get(&call_id)
+            .map(|call| call.screen_shares.clone())
+            .unwrap_or_default()
+    }
+
+    /// Screen shares the remote party says it sends on a call; empty for
+    /// unknown calls
+    #[must_use]
+    pub fn remote_screen_shares(&self, call_id: CallId) -> Vec<ScreenShare> {
+        self.calls
+            .get(&call_id)
+            .map(|call| call.remote_screen_shares.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record the screen shares the remote party says it sends, replacing
+    /// what it said before
+    ///
+    /// Returns false if the call does not exist.
+    pub fn set_remote_screen_shares(&self, call_id: CallId, shares: Vec<ScreenShare>) -> bool {
+        let Some(mut call) = self.calls.get_mut(&call_id) else {
+            return false;
+        };
+        call.remote_screen_shares = shares;
+        true
+    }
+
+    /// Open the annotation data channel paired with a screen share track
+    ///
+    /// The remote party picks the channel up by its reserved label and can
+    /// annotate over it too. Opening an already open channel does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// created
+    pub async fn open_annotation_channel(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), CallError> {
+        self.open_data_channel(call_id, annotation_channel_label(track_id))
+            .await
+    }
+
+    /// Send an annotation over a screen share's annotation channel
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or channel does not exist, the annotation
+    /// cannot be encoded or the channel is not open yet
+    pub async fn send_annotation(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        annotation: &Annotation,
+    ) -> Result<(), CallError> {
+        let data = annotation.encode()?;
+        self.send_on_channel(call_id, &annotation_channel_label(track_id), data)
+            .await
+    }
+
+    /// Open the data channel of playback sync session `session_id`
+    ///
+    /// The remote party picks the channel up by its reserved label. Opening
+    /// an already open channel does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// created
+    pub async fn open_sync_channel(
+        &self,
+        call_id: CallId,
+        session_id: &str,
+    ) -> Result<(), CallError> {
+        self.open_data_channel(call_id, sync_channel_label(session_id))
+            .await
+    }
+
+    /// Send a play, pause or seek over a playback sync channel
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or channel does not exist or the channel
+    /// is not open yet
+    pub async fn send_sync(
+        &self,
+        call_id: CallId,
+        session_id: &str,
+        message: &SyncMessage,
+    ) -> Result<(), CallError> {
+        self.send_on_channel(call_id, &sync_channel_label(session_id), message.encode())
+            .await
+    }
+
+    /// Open the text messaging data channel on a call
+    ///
+    /// [`Self::connect_data`] opens it itself. Open it before the offer
+    /// that negotiates the call; the remote party picks it up by its
+    /// reserved label. Opening an already open channel does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// created
+    pub async fn open_message_channel(&self, call_id: CallId) -> Result<(), CallError> {
+        self.open_data_channel(call_id, MESSAGE_CHANNEL_LABEL.to_string())
+            .await
+    }
+
+    /// Send a text message over a call's messaging channel
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or channel does not exist, the message is
+    /// too long or the channel is not open yet
+    pub async fn send_text(&self, call_id: CallId, message: &TextMessage) -> Result<(), CallError> {
+        let data = message.encode()?;
+        self.send_on_channel(call_id, MESSAGE_CHANNEL_LABEL, data)
+            .await
+    }
+
+    /// Open the DTMF data channel on a call
+    ///
+    /// The remote party picks the channel up by its reserved label. Opening
+    /// an already open channel does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// created
+    pub async fn open_dtmf_channel(&self, call_id: CallId) -> Result<(), CallError> {
+        self.open_data_channel(call_id, DTMF_CHANNEL_LABEL.to_string())
+            .await
+    }
+
+    /// Press keys `digits` on a call, one RFC 4733 event each, in order
+    ///
+    /// The remote party receives each as [`CallEvent::DtmfReceived`]. No
+    /// key is sent unless all of `digits` are keypad keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a digit is not a keypad key, the call or channel
+    /// does not exist or the channel is not open yet
+    pub async fn send_dtmf(&self, call_id: CallId, digits: &str) -> Result<(), CallError> {
+        for tone in DtmfTone::parse(digits)? {
+            self.send_on_channel(call_id, DTMF_CHANNEL_LABEL, tone.encode().to_vec())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Let the remote party control one of our screen shares
+    ///
+    /// Opens the share's remote control channel and returns the grant token
+    /// to send the remote party; input carrying any other token is dropped.
+    /// Granting again replaces the token.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// created
+    pub async fn grant_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<u64, CallError> {
+        self.open_data_channel(call_id, remote_control_channel_label(track_id))
+            .await?;
+        let grant = self
+            .calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?
+            .remote_control
+            .grant(track_id);
+        tracing::info!("Granted remote control of {} on call {}", track_id, call_id);
+        Ok(grant)
+    }
+
+    /// Take control of one of our screen shares back; input already in
+    /// flight is dropped
+    ///
+    /// Returns false if the call does not exist or control was not granted.
+    pub fn revoke_remote_control(&self, call_id: CallId, track_id: &str) -> bool {
+        let revoked = self
+            .calls
+            .get_mut(&call_id)
+            .is_some_and(|mut call| call.remote_control.revoke(track_id));
+        if revoked {
+            tracing::info!("Revoked remote control of {} on call {}", track_id, call_id);
+        }
+        revoked
+    }
+
+    /// Whether the remote party controls one of our screen shares
+    #[must_use]
+    pub fn is_remotely_controlled(&self, call_id: CallId, track_id: &str) -> bool {
+        self.calls
+            .get(&call_id)
+            .is_some_and(|call| call.remote_control.is_hosting(track_id))
+    }
+
+    /// Record a grant to control a remote screen share
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist
+    pub fn accept_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        grant: u64,
+    ) -> Result<(), CallError> {
+        self.calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?
+            .remote_control
+            .accept(track_id, grant);
+        Ok(())
+    }
+
+    /// Stop controlling a remote screen share
+    ///
+    /// Returns false if the call does not exist or we were not in control.
+    pub fn release_remote_control(&self, call_id: CallId, track_id: &str) -> bool {
+        self.calls
+            .get_mut(&call_id)
+            .is_some_and(|mut call| call.remote_control.release(track_id))
+    }
+
+    /// Whether we control a remote screen share
+    #[must_use]
+    pub fn is_controlling(&self, call_id: CallId, track_id: &str) -> bool {
+        self.calls
+            .get(&call_id)
+            .is_some_and(|call| call.remote_control.token(track_id).is_some())
+    }
+
+    /// Send keyboard or mouse input to a remote screen share we control
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist, we were not granted
+    /// control, or the input cannot be encoded or sent
+    pub async fn send_remote_input(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        input: &RemoteInput,
+    ) -> Result<(), CallError> {
+        let grant = self
+            .calls
+            .get(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?
+            .remote_control
+            .token(track_id)
+            .ok_or_else(|| RemoteControlError::NotGranted(track_id.to_string()))?;
+        let data = input.encode(grant)?;
+        self.send_on_channel(call_id, &remote_control_channel_label(track_id), data)
+            .await
+    }
+
+    /// Open a reserved data channel, unless it is already open
+    async fn open_data_channel(&self, call_id: CallId, label: String) -> Result<(), CallError> {
+        let peer_connection = {
+            let call = self
+                .calls
+                .get(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            if call.data_channels.contains_key(&label) {
+                return Ok(());
+            }
+            Arc::clone(&call.peer_connection)
+        };
+
+        let channel = peer_connection
+            .create_data_channel(&label, None)
+            .await
+            .map_err(CallError::DataChannel)?;
+        Self::attach_data_channel(
+            &self.calls,
+            &self.event_sender,
+            &self.event_bus,
+            call_id,
+            channel,
         );
+        tracing::debug!("Opened data channel {} on call {}", label, call_id);
+        Ok(())
+    }
+
+    async fn send_on_channel(
+        &self,
+        call_id: CallId,
+        label: &str,
+        data: Vec<u8>,
+    ) -> Result<(), CallError> {
+        let channel = self
+            .calls
+            .get(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?
+            .data_channels
+            .get(label)
+            .cloned()
+            .ok_or_else(|| CallError::DataChannelNotFound(label.to_string()))?;
+        channel
+            .send(&Bytes::from(data))
+            .await
+            .map_err(CallError::DataChannel)?;
+        Ok(())
+    }
 
-        tracing::debug!("Created peer connection for call {}", call_id);
+    /// Pick up reserved data channels the remote party opens
+    fn watch_data_channels(&self, call_id: CallId, peer_connection: &RTCPeerConnection) {
+        let calls = Arc::clone(&self.calls);
+        let event_sender = self.event_sender.clone();
+        let event_bus = Arc::clone(&self.event_bus);
+        peer_connection.on_data_channel(Box::new(move |channel: Arc<RTCDataChannel>| {
+            let label = channel.label();
+            if annotated_track(label).is_some()
+                || controlled_track(label).is_some()
+                || synced_session(label).is_some()
+                || label == MESSAGE_CHANNEL_LABEL
+                || label == DTMF_CHANNEL_LABEL
+            {
+                tracing::debug!("Remote opened data channel {} on call {}", label, call_id);
+                Self::attach_data_channel(&calls, &event_sender, &event_bus, call_id, channel);
+            }
+            Box::pin(async {})
+        }));
+    }
 
-        // Create media tracks based on constraints
-        let mut media_manager = self.media_manager.write().await;
-        let mut tracks = Vec::new();
+    /// Group tracks the remote party sends into its streams as they arrive
+    fn watch_remote_tracks(&self, call_id: CallId, peer_connection: &RTCPeerConnection) {
+        // Weak, as the call holding the peer connection holds this handler too
+        let calls = Arc::downgrade(&self.calls);
+        let event_sender = self.event_sender.clone();
+        let event_bus = Arc::clone(&self.event_bus);
+        peer_connection.on_track(Box::new(move |track: Arc<TrackRemote>, _, _| {
+            let kind = match track.kind() {
+                RTPCodecType::Audio => Some(MediaType::Audio),
+                RTPCodecType::Video => Some(MediaType::Video),
+                _ => None,
+            };
+            let (stream_id, track_id) = (track.stream_id(), track.id());
+            let event = kind.zip(calls.upgrade()).and_then(|(kind, calls)| {
+                let remote = RemoteTrack {
+                    track: Arc::clone(&track),
+                    track_type: kind.clone(),
+                    id: track_id.clone(),
+                };
+                let mut call = calls.get_mut(&call_id)?;
+                let added = call
+                    .remote_streams
+                    .entry(stream_id.clone())
+                    .or_insert_with(|| MediaStream::new(stream_id.clone()))
+                    .add_track(remote);
+                added.then(|| CallEvent::RemoteTrackAdded {
+                    call_id,
+                    stream_id: stream_id.clone(),
+                    track_id: track_id.clone(),
+                    kind,
+                })
+            });
+            if let Some(event) = event {
+                tracing::debug!("Call {} receiving track {} of stream {}", call_id, track_id, stream_id);
+                event_bus.publish(event.clone());
+                let _ = event_sender.send(event);
+            }
+            Box::pin(async {})
+        }));
+    }
 
-        if constraints.has_audio() {
-            let audio_track = media_manager.create_audio_track().await
-                .map_err(|e| CallError::ConfigError(format!("Failed to create audio track: {:?}", e)))?;
-            tracks.push((*audio_track).clone());
+    /// Keep a reserved data channel for sending and publish what arrives
+    /// on it as call events
+    fn attach_data_channel(
+        calls: &Arc<DashMap<CallId, Call<I>>>,
+        event_sender: &broadcast::Sender<CallEvent<I>>,
+        event_bus: &Arc<TopicChannel<CallEvent<I>>>,
+        call_id: CallId,
+        channel: Arc<RTCDataChannel>,
+    ) {
+        let label = channel.label().to_string();
+        // Weak, as the call holding the channel holds this handler too
+        let handler_calls = Arc::downgrade(calls);
+        let handler_label = label.clone();
+        let event_sender = event_sender.clone();
+        let event_bus = Arc::clone(event_bus);
+        channel.on_message(Box::new(move |message: DataChannelMessage| {
+            let event = handler_calls.upgrade().and_then(|calls| {
+                Self::channel_event(&calls, call_id, &handler_label, &message.data)
+            });
+            if let Some(event) = event {
+                event_bus.publish(event.clone());
+                let _ = event_sender.send(event);
+            }
+            Box::pin(async {})
+        }));
 
-            // Add track to peer connection
-            let track: Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync> = audio_track.track.clone();
-            peer_connection.add_track(track).await
-                .map_err(|e| CallError::ConfigError(format!("Failed to add audio track: {}", e)))?;
+        if let Some(mut call) = calls.get_mut(&call_id) {
+            call.data_channels.insert(label, channel);
         }
+    }
 
-        if constraints.has_video() {
-            let video_track = media_manager.create_video_track().await
-                .map_err(|e| CallError::ConfigError(format!("Failed to create video track: {:?}", e)))?;
-            tracks.push((*video_track).clone());
-
-            // Add track to peer connection
-            let track: Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync> = video_track.track.clone();
-            peer_connection.add_track(track).await
-                .map_err(|e| CallError::ConfigError(format!("Failed to add video track: {}", e)))?;
+    /// Decode a message from a reserved data channel into its call event
+    ///
+    /// Remote input only gets through while it carries the live grant for
+    /// one of our screen shares.
+    fn channel_event(
+        calls: &DashMap<CallId, Call<I>>,
+        call_id: CallId,
+        label: &str,
+        data: &[u8],
+    ) -> Option<CallEvent<I>> {
+        if let Some(track_id) = annotated_track(label) {
+            return match Annotation::decode(data) {
+                Ok(annotation) => Some(CallEvent::AnnotationReceived {
+                    call_id,
+                    track_id: track_id.to_string(),
+                    annotation,
+                }),
+                Err(e) => {
+                    tracing::debug!("Dropping annotation on call {}: {}", call_id, e);
+                    None
+                }
+            };
+        }
+        if label == MESSAGE_CHANNEL_LABEL {
+            return match TextMessage::decode(data) {
+                Ok(message) => Some(CallEvent::TextReceived { call_id, message }),
+                Err(e) => {
+                    tracing::debug!("Dropping text message on call {}: {}", call_id, e);
+                    None
+                }
+            };
+        }
+        if label == DTMF_CHANNEL_LABEL {
+            return match DtmfTone::decode(data) {
+                Ok(tone) => Some(CallEvent::DtmfReceived { call_id, tone }),
+                Err(e) => {
+                    tracing::debug!("Dropping DTMF event on call {}: {}", call_id, e);
+                    None
+                }
+            };
+        }
+        if let Some(session_id) = synced_session(label) {
+            return match SyncMessage::decode(data) {
+                Ok(message) => Some(CallEvent::SyncReceived {
+                    call_id,
+                    session_id: session_id.to_string(),
+                    message,
+                }),
+                Err(e) => {
+                    tracing::debug!("Dropping playback sync on call {}: {}", call_id, e);
+                    None
+                }
+            };
+        }
+        let track_id = controlled_track(label)?;
+        match RemoteInput::decode(data) {
+            Ok((grant, input))
+                if calls
+                    .get(&call_id)
+                    .is_some_and(|call| call.remote_control.authorizes(track_id, grant)) =>
+            {
+                Some(CallEvent::RemoteInputReceived {
+                    call_id,
+                    track_id: track_id.to_string(),
+                    input,
+                })
+            }
+            Ok(_) => {
+                tracing::debug!("Dropping unauthorized input for {} on call {}", track_id, call_id);
+                None
+            }
+            Err(e) => {
+                tracing::debug!("Dropping remote input on call {}: {}", call_id, e);
+                None
+            }
         }
+    }
 
-        let call = Call {
-            id: call_id,
-            remote_peer: callee.clone(),
-            peer_connection,
-            state: CallState::Calling,
-            constraints: constraints.clone(),
-            tracks,
+    /// Attach or detach the call's tracks matching `kinds` to follow its
+    /// mute and pause state; parked calls are left alone
+    async fn switch_tracks(
+        &self,
+        call_id: CallId,
+        kinds: impl Fn(MediaType) -> bool,
+    ) -> Result<(), CallError> {
+        let tracks: Vec<_> = {
+            let call = self
+                .calls
+                .get(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            if call.state == CallState::Parked {
+                return Ok(());
+            }
+            call.local_stream
+                .tracks()
+                .iter()
+                .filter(|track| kinds(track.track_type.clone()))
+                .filter_map(|track| {
+                    let sender = call.senders.get(&track.id)?;
+                    Some((Arc::clone(sender), track.clone(), call.is_sending(track)))
+                })
+                .collect()
         };
 
-        let mut calls = self.calls.write().await;
-        calls.insert(call_id, call);
-        
-        // Emit call initiated event
-        let _ = self.event_sender.send(CallEvent::CallInitiated {
-            call_id,
-            callee,
-            constraints,
-        });
-        
-        Ok(call_id)
+        for (sender, track, sending) in tracks {
+            let track: Option<Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync>> =
+                if sending { Some(track.track) } else { None };
+            sender.replace_track(track).await
+                .map_err(CallError::TrackSwitch)?;
+        }
+        Ok(())
     }
 
-    /// Accept a call
+    /// Send what arrives on a remote track straight back to the remote party
+    ///
+    /// The call's local track of the same kind is swapped for the remote
+    /// track's packets, unchanged. Returns the future forwarding them,
+    /// which runs until the remote track ends; spawn it. Loopback peers use
+    /// this to test a pipeline end to end.
     ///
     /// # Errors
     ///
-    /// Returns error if call cannot be accepted
-    pub async fn accept_call(
+    /// Returns error if the call or remote track does not exist, the call
+    /// sends no track of that kind, or the track cannot be swapped
+    pub async fn echo_track(
         &self,
         call_id: CallId,
-        _constraints: MediaConstraints,
-    ) -> Result<(), CallError> {
-        let mut calls = self.calls.write().await;
-        if let Some(call) = calls.get_mut(&call_id) {
-            // Validate state transition
-            match call.state {
-                CallState::Calling | CallState::Connecting => {
-                    call.state = CallState::Connected;
-                    
-                    // Emit connection established event
-                    let _ = self.event_sender.send(CallEvent::ConnectionEstablished { call_id });
-                    
-                    tracing::info!("Call {} accepted", call_id);
-                    Ok(())
-                }
-                _ => {
-                    tracing::warn!("Invalid state transition: cannot accept call {} in state {:?}", call_id, call.state);
-                    Err(CallError::InvalidState)
+        track_id: &str,
+    ) -> Result<impl std::future::Future<Output = ()> + Send + 'static, CallError> {
+        let (remote, sender) = {
+            let call = self
+                .calls
+                .get(&call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            let remote = call
+                .remote_streams
+                .values()
+                .flat_map(|stream| stream.tracks())
+                .find(|track| track.id == track_id)
+                .cloned()
+                .ok_or_else(|| CallError::TrackNotFound(track_id.to_string()))?;
+            let sender = call
+                .local_stream
+                .tracks()
+                .iter()
+                .find(|track| track.track_type == remote.track_type)
+                .and_then(|track| call.senders.get(&track.id))
+                .cloned()
+                .ok_or_else(|| CallError::TrackNotFound(track_id.to_string()))?;
+            (remote, sender)
+        };
+
+        let echo = Arc::new(TrackLocalStaticRTP::new(
+            remote.track.codec().capability,
+            format!("echo-{track_id}"),
+            format!("echo-{call_id}"),
+        ));
+        let local: Arc<dyn webrtc::track::track_local::TrackLocal + Send + Sync> = echo.clone();
+        sender.replace_track(Some(local)).await
+            .map_err(CallError::TrackSwitch)?;
+
+        tracing::info!("Echoing track {} back on call {}", track_id, call_id);
+        let track_id = track_id.to_string();
+        Ok(async move {
+            while let Ok((packet, _)) = remote.track.read_rtp().await {
+                if let Err(e) = echo.write_rtp(&packet).await {
+                    tracing::debug!("Stopped echoing track {}: {}", track_id, e);
+                    break;
                 }
             }
-        } else {
-            tracing::warn!("Attempted to accept non-existent call {}", call_id);
-            Err(CallError::CallNotFound(call_id.to_string()))
-        }
+        })
     }
 
-    /// Reject a call
+    /// Clone the peer connection handle for a call without holding the map guard
+    fn peer_connection(&self, call_id: CallId) -> Option<Arc<RTCPeerConnection>> {
+        self.calls
+            .get(&call_id)
+            .map(|call| Arc::clone(&call.peer_connection))
+    }
+
+    /// Create SDP offer for a call
+    ///
+    /// The offer carries the call's ICE candidates once gathered. Gives up
+    /// after the configured negotiation timeout.
     ///
     /// # Errors
     ///
-    /// Returns error if call cannot be rejected
-    pub async fn reject_call(&self, call_id: CallId) -> Result<(), CallError> {
-        let mut calls = self.calls.write().await;
-        if let Some(call) = calls.get_mut(&call_id) {
-            // Validate state transition - can only reject calls that are not yet connected/ended
-            match call.state {
-                CallState::Calling | CallState::Connecting => {
-                    call.state = CallState::Failed;
-                    
-                    // Emit call rejected event
-                    let _ = self.event_sender.send(CallEvent::CallRejected { call_id });
-                    
-                    Ok(())
-                }
-                _ => {
-                    tracing::warn!("Invalid state transition: cannot reject call {} in state {:?}", call_id, call.state);
-                    Err(CallError::InvalidState)
-                }
-            }
-        } else {
-            Err(CallError::CallNotFound(call_id.to_string()))
-        }
+    /// Returns error if offer cannot be created in time
+    pub async fn create_offer(&self, call_id: CallId) -> Result<String, CallError> {
+        self.create_offer_with(call_id, &Deadline::after(self.config.negotiation_timeout))
+            .await
     }
 
-    /// End a call
+    /// Create SDP offer for a call, bounded by `deadline`
     ///
     /// # Errors
     ///
-    /// Returns error if call cannot be ended
-    pub async fn end_call(&self, call_id: CallId) -> Result<(), CallError> {
-        let mut calls = self.calls.write().await;
-        if let Some(call) = calls.remove(&call_id) {
-            // Remove all tracks associated with this call from media manager
-            let mut media_manager = self.media_manager.write().await;
-            for track in &call.tracks {
-                media_manager.remove_track(&track.id);
-            }
-            drop(media_manager);
-
-            // Close the peer connection
-            let _ = call.peer_connection.close().await;
-            
-            // Emit call ended event
-            let _ = self.event_sender.send(CallEvent::CallEnded { call_id });
-            
-            tracing::info!("Ended call {} and cleaned up {} tracks", call_id, call.tracks.len());
-            Ok(())
-        } else {
-            Err(CallError::CallNotFound(call_id.to_string()))
-        }
-    }
-
-    /// Get call state
-    #[must_use]
-    pub async fn get_call_state(&self, call_id: CallId) -> Option<CallState> {
-        let calls = self.calls.read().await;
-        calls.get(&call_id).map(|call| call.state)
+    /// Returns error if offer cannot be created, or
+    /// [`CallError::Interrupted`] if the deadline passes or is cancelled first
+    pub async fn create_offer_with(
+        &self,
+        call_id: CallId,
+        deadline: &Deadline,
+    ) -> Result<String, CallError> {
+        self.offer(call_id, None, deadline).await
     }
 
-    /// Create SDP offer for a call
+    /// Restart a call's connection after the local network changed, such
+    /// as moving from Wi-Fi to cellular
+    ///
+    /// Creates an offer that gathers candidates afresh under new ICE
+    /// credentials, keeping the call's state, tracks and data channels.
+    /// The remote party takes it with [`Self::receive_offer`] as a
+    /// renegotiation; apply their answer with [`Self::handle_answer`]. A
+    /// call in [`CallState::Reconnecting`] is connected again once the new
+    /// path is up.
     ///
     /// # Errors
     ///
-    /// Returns error if offer cannot be created
-    pub async fn create_offer(&self, call_id: CallId) -> Result<String, CallError> {
-        let calls = self.calls.read().await;
-        if let Some(call) = calls.get(&call_id) {
+    /// Returns error if the call does not exist, is not connected, parked
+    /// or reconnecting, or the offer cannot be created in time
+    pub async fn restart_connection(&self, call_id: CallId) -> Result<String, CallError> {
+        let state = self.calls.get(&call_id)
+            .map(|call| call.state)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        if !matches!(state, CallState::Connected | CallState::Parked | CallState::Reconnecting) {
+            return Err(CallError::InvalidState);
+        }
+        tracing::info!("Restarting connection of call {}", call_id);
+        let options = RTCOfferOptions {
+            ice_restart: true,
+            ..Default::default()
+        };
+        self.offer(call_id, Some(options), &Deadline::after(self.config.negotiation_timeout))
+            .await
+    }
+
+    /// Create an SDP offer for a call, bounded by `deadline`
+    ///
+    /// Candidates are not trickled, so like [`Self::answer`] the offer
+    /// waits for ICE gathering to finish and carries them all.
+    async fn offer(
+        &self,
+        call_id: CallId,
+        options: Option<RTCOfferOptions>,
+        deadline: &Deadline,
+    ) -> Result<String, CallError> {
+        if let Some(peer_connection) = self.peer_connection(call_id) {
             tracing::debug!("Creating SDP offer for call {}", call_id);
-            let offer = call.peer_connection.create_offer(None).await
-                .map_err(|e| {
-                    tracing::error!("Failed to create offer for call {}: {}", call_id, e);
-                    CallError::ConfigError(format!("Failed to create offer: {}", e))
-                })?;
-            call.peer_connection.set_local_description(offer.clone()).await
-                .map_err(|e| {
-                    tracing::error!("Failed to set local description for call {}: {}", call_id, e);
-                    CallError::ConfigError(format!("Failed to set local description: {}", e))
-                })?;
+            let offer = deadline.run(async {
+                let offer = peer_connection.create_offer(options).await
+                    .map_err(|e| {
+                        tracing::error!("Failed to create offer for call {}: {}", call_id, e);
+                        CallError::Negotiation(e)
+                    })?;
+                let mut gathered = peer_connection.gathering_complete_promise().await;
+                peer_connection.set_local_description(offer).await
+                    .map_err(|e| {
+                        tracing::error!("Failed to set local description for call {}: {}", call_id, e);
+                        CallError::Negotiation(e)
+                    })?;
+                let _ = gathered.recv().await;
+                let offer = peer_connection.local_description().await
+                    .ok_or(CallError::InvalidState)?;
+                Ok::<_, CallError>(offer)
+            })
+            .await??;
             tracing::debug!("SDP offer created for call {}", call_id);
             Ok(offer.sdp)
         } else {
@@ -303,24 +2430,34 @@ impl<I: PeerIdentity> CallManager<I> {
         }
     }
 
+    /// Round-trip time and RTP counters of a call's peer connection
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist
+    pub async fn transport_stats(&self, call_id: CallId) -> Result<TransportStats, CallError> {
+        let peer_connection = self.peer_connection(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        Ok(TransportStats::from_report(&peer_connection.get_stats().await))
+    }
+
     /// Handle SDP answer for a call
     ///
     /// # Errors
     ///
     /// Returns error if answer cannot be handled
     pub async fn handle_answer(&self, call_id: CallId, sdp: String) -> Result<(), CallError> {
-        let calls = self.calls.read().await;
-        if let Some(call) = calls.get(&call_id) {
+        if let Some(peer_connection) = self.peer_connection(call_id) {
             // Validate SDP is not empty
             if sdp.trim().is_empty() {
-                return Err(CallError::ConfigError("SDP answer cannot be empty".to_string()));
+                return Err(CallError::EmptySdp);
             }
             
             let answer = webrtc::peer_connection::sdp::session_description::RTCSessionDescription::answer(sdp)
-                .map_err(|e| CallError::ConfigError(format!("Invalid SDP answer: {}", e)))?;
+                .map_err(CallError::Negotiation)?;
             
-            call.peer_connection.set_remote_description(answer).await
-                .map_err(|e| CallError::ConfigError(format!("Failed to set remote description: {}", e)))?;
+            peer_connection.set_remote_description(answer).await
+                .map_err(CallError::Negotiation)?;
             Ok(())
         } else {
             Err(CallError::CallNotFound(call_id.to_string()))
@@ -329,18 +2466,23 @@ impl<I: PeerIdentity> CallManager<I> {
 
     /// Add ICE candidate to a call
     ///
+    /// The candidate is checked against the path policy first, and with
+    /// IPv6 preferred re-ranked by address family.
+    ///
     /// # Errors
     ///
-    /// Returns error if candidate cannot be added
+    /// Returns [`CallError::PathRejected`] if the policy rules out the
+    /// candidate's path, or error if candidate cannot be added
     pub async fn add_ice_candidate(&self, call_id: CallId, candidate: String) -> Result<(), CallError> {
-        let calls = self.calls.read().await;
-        if let Some(call) = calls.get(&call_id) {
+        if let Some(peer_connection) = self.peer_connection(call_id) {
+            let candidate = self.config.path_policy.check_candidate(&candidate)
+                .inspect_err(|e| tracing::debug!("Refusing ICE candidate for call {}: {}", call_id, e))?;
             let rtc_candidate = webrtc::ice_transport::ice_candidate::RTCIceCandidateInit {
                 candidate,
                 ..Default::default()
             };
-            call.peer_connection.add_ice_candidate(rtc_candidate).await
-                .map_err(|e| CallError::ConfigError(format!("Failed to add ICE candidate: {}", e)))?;
+            peer_connection.add_ice_candidate(rtc_candidate).await
+                .map_err(CallError::IceCandidate)?;
             Ok(())
         } else {
             Err(CallError::CallNotFound(call_id.to_string()))
@@ -353,8 +2495,7 @@ impl<I: PeerIdentity> CallManager<I> {
     ///
     /// Returns error if gathering cannot be started
     pub async fn start_ice_gathering(&self, call_id: CallId) -> Result<(), CallError> {
-        let calls = self.calls.read().await;
-        if let Some(_call) = calls.get(&call_id) {
+        if self.calls.contains_key(&call_id) {
             // ICE gathering is typically started automatically when creating offer
             // For now, this is a no-op as gathering happens during offer creation
             Ok(())
@@ -368,6 +2509,76 @@ impl<I: PeerIdentity> CallManager<I> {
     pub fn subscribe_events(&self) -> broadcast::Receiver<CallEvent<I>> {
         self.event_sender.subscribe()
     }
+
+    /// Subscribe to call events through the non-lossy event bus
+    #[must_use]
+    pub fn subscribe_event_queue(&self) -> EventSubscription<CallEvent<I>> {
+        self.event_bus.subscribe()
+    }
+}
+
+/// Kinds of media an SDP offers, from its media sections
+pub(crate) fn offered_media(sdp: &str) -> Vec<MediaType> {
+    let mut kinds = Vec::new();
+    for line in sdp.lines() {
+        let kind = if line.starts_with("m=audio") {
+            MediaType::Audio
+        } else if line.starts_with("m=video") {
+            MediaType::Video
+        } else {
+            continue;
+        };
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    kinds
+}
+
+/// Take a screen share off a call: forget it, detach its sender and drop
+/// its track
+async fn detach_screen_share<I: PeerIdentity>(
+    calls: &DashMap<CallId, Call<I>>,
+    media_manager: &RwLock<MediaStreamManager>,
+    call_id: CallId,
+    track_id: &str,
+) -> Result<(), CallError> {
+    let (peer_connection, sender) = {
+        let mut call = calls
+            .get_mut(&call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        let shared = call.screen_shares.len();
+        call.screen_shares.retain(|share| share.track_id != track_id);
+        if call.screen_shares.len() == shared {
+            return Err(CallError::TrackNotFound(track_id.to_string()));
+        }
+        call.local_stream.remove_track(track_id);
+        call.constraints.screen_share = call
+            .local_stream
+            .tracks()
+            .iter()
+            .any(|track| track.track_type == MediaType::ScreenShare);
+        (Arc::clone(&call.peer_connection), call.senders.remove(track_id))
+    };
+    media_manager.write().await.remove_track(track_id);
+    if let Some(sender) = sender {
+        peer_connection.remove_track(&sender).await.map_err(CallError::TrackSwitch)?;
+    }
+    tracing::info!("Stopped screen share {} on call {}", track_id, call_id);
+    Ok(())
+}
+
+/// Kinds of track `constraints` ask for that `stream` does not have yet
+fn track_kinds(constraints: &MediaConstraints, stream: &MediaStream) -> Vec<MediaType> {
+    let mut kinds = Vec::new();
+    if constraints.has_audio() && stream.audio_tracks().next().is_none() {
+        kinds.push(MediaType::Audio);
+    }
+    let has_camera = stream.tracks().iter().any(|track| track.track_type == MediaType::Video);
+    if constraints.has_video() && !has_camera {
+        kinds.push(MediaType::Video);
+    }
+    kinds
 }
 
 #[cfg(test)]
@@ -375,6 +2586,23 @@ mod tests {
     use super::*;
     use crate::identity::PeerIdentityString;
 
+    #[test]
+    fn test_retry_delays_back_off_to_a_cap() {
+        let schedule = RetrySchedule::default();
+        let delays: Vec<_> = (1..=6).map(|retry| schedule.delay(retry).as_secs()).collect();
+        assert_eq!(delays, vec![2, 4, 8, 16, 30, 30]);
+        assert_eq!(schedule.delay(u32::MAX), schedule.max_delay);
+    }
+
+    #[test]
+    fn test_answer_policy_picks_callers() {
+        let known = AnswerPolicy::AutoAcceptKnownPeers(vec!["kiosk-admin".to_string()]);
+        assert!(known.answers("kiosk-admin"));
+        assert!(!known.answers("stranger"));
+        assert!(AnswerPolicy::AutoAcceptAll.answers("stranger"));
+        assert!(!AnswerPolicy::default().answers("kiosk-admin"));
+    }
+
     #[tokio::test]
     async fn test_call_manager_initiate_call() {
         let config = CallManagerConfig::default();
@@ -389,6 +2617,337 @@ mod tests {
         assert_eq!(state, Some(CallState::Calling));
     }
 
+    #[tokio::test]
+    async fn test_unanswered_calls_time_out() {
+        let config = CallManagerConfig {
+            ring_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let call_manager = CallManager::<PeerIdentityString>::new(config).await.unwrap();
+        let mut events = call_manager.subscribe_events();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+
+        let reason = loop {
+            if let CallEvent::ConnectionFailed { reason, .. } = events.recv().await.unwrap() {
+                break reason;
+            }
+        };
+        assert_eq!(reason, FailureReason::NoAnswer);
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Failed));
+
+        // Cancelled before the timeout, a call stays cancelled
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        call_manager.cancel_call(call_id).unwrap();
+        assert!(matches!(call_manager.cancel_call(call_id), Err(CallError::InvalidState)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Ending));
+    }
+
+    #[tokio::test]
+    async fn test_call_snapshots() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let callee = PeerIdentityString::new("callee");
+        let call_id = call_manager
+            .initiate_call(callee.clone(), MediaConstraints::video_call())
+            .await
+            .unwrap();
+
+        let snapshots = call_manager.call_snapshots();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].call_id, call_id);
+        assert_eq!(snapshots[0].remote_peer, callee);
+        assert_eq!(snapshots[0].state, CallState::Calling);
+    }
+
+    #[tokio::test]
+    async fn test_call_exposes_its_streams() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), MediaConstraints::video_call())
+            .await
+            .unwrap();
+
+        let local = call_manager.local_stream(call_id).unwrap();
+        assert_eq!(local.id, format!("call-{call_id}"));
+        assert_eq!(local.audio_tracks().count(), 1);
+        assert_eq!(local.video_tracks().count(), 1);
+
+        // Nothing received before negotiation
+        assert!(call_manager.remote_streams(call_id).is_empty());
+        assert!(call_manager.remove_remote_track(call_id, "audio-0").is_none());
+        assert!(call_manager.local_stream(CallId::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_data_connection_upgrades_to_call() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let call_id = call_manager
+            .connect_data(PeerIdentityString::new("friend"))
+            .await
+            .unwrap();
+        assert!(call_manager.local_stream(call_id).unwrap().is_empty());
+        assert_eq!(
+            call_manager.constraints(call_id),
+            Some(MediaConstraints::data_only())
+        );
+        // The channel only opens once the connection is negotiated
+        assert!(call_manager
+            .send_text(call_id, &TextMessage::new("hi"))
+            .await
+            .is_err());
+
+        call_manager
+            .add_media(call_id, MediaConstraints::video_call())
+            .await
+            .unwrap();
+        call_manager
+            .add_media(call_id, MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        let local = call_manager.local_stream(call_id).unwrap();
+        assert_eq!(local.audio_tracks().count(), 1);
+        assert_eq!(local.video_tracks().count(), 1);
+        assert!(call_manager.constraints(call_id).unwrap().has_video());
+        assert!(matches!(
+            call_manager
+                .add_media(CallId::new(), MediaConstraints::audio_only())
+                .await,
+            Err(CallError::CallNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_call_carries_several_screen_shares() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+
+        let left = call_manager
+            .add_screen_share(call_id, "Left monitor", VideoCodec::H264, 1280, 720)
+            .await
+            .unwrap();
+        let right = call_manager
+            .add_screen_share(call_id, "Right monitor", VideoCodec::H264, 1280, 720)
+            .await
+            .unwrap();
+        let shares = call_manager.screen_shares(call_id);
+        assert_eq!(shares.len(), 2);
+        assert_eq!(shares[0].track_id, left.id);
+        assert_eq!(shares[1].label, "Right monitor");
+        assert_ne!(shares[0].stream_id, shares[1].stream_id);
+        assert!(call_manager.constraints(call_id).unwrap().has_screen_share());
+
+        // Pausing one leaves the other shared
+        call_manager
+            .set_screen_share_paused(call_id, &left.id, true)
+            .await
+            .unwrap();
+        let shares = call_manager.screen_shares(call_id);
+        assert!(shares[0].paused);
+        assert!(!shares[1].paused);
+        assert!(matches!(
+            call_manager.set_screen_share_paused(call_id, "screen-99", true).await,
+            Err(CallError::TrackNotFound(_))
+        ));
+
+        // A camera is still added beside the screen shares
+        call_manager
+            .add_media(call_id, MediaConstraints::video_call())
+            .await
+            .unwrap();
+        let local = call_manager.local_stream(call_id).unwrap();
+        assert_eq!(local.video_tracks().count(), 3);
+        assert!(local.track(&right.id).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_removed_screen_share_stops_being_sent() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        let left = call_manager
+            .add_screen_share(call_id, "Left monitor", VideoCodec::H264, 1280, 720)
+            .await
+            .unwrap();
+        let right = call_manager
+            .add_screen_share(call_id, "Right monitor", VideoCodec::H264, 1280, 720)
+            .await
+            .unwrap();
+
+        call_manager.remove_screen_share(call_id, &left.id).await.unwrap();
+        let shares = call_manager.screen_shares(call_id);
+        assert_eq!(shares.len(), 1);
+        assert_eq!(shares[0].track_id, right.id);
+        assert!(call_manager.local_stream(call_id).unwrap().track(&left.id).is_none());
+        assert!(!call_manager.calls.get(&call_id).unwrap().senders.contains_key(&left.id));
+        assert!(call_manager.constraints(call_id).unwrap().has_screen_share());
+        assert!(matches!(
+            call_manager.remove_screen_share(call_id, &left.id).await,
+            Err(CallError::TrackNotFound(_))
+        ));
+
+        // The last share gone, the call no longer carries screen share
+        call_manager.remove_screen_share(call_id, &right.id).await.unwrap();
+        assert!(call_manager.screen_shares(call_id).is_empty());
+        assert!(!call_manager.constraints(call_id).unwrap().has_screen_share());
+    }
+
+    #[tokio::test]
+    async fn test_prewarmed_connection_is_used_once() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let friend = PeerIdentityString::new("friend");
+        call_manager.prewarm(&friend).await.unwrap();
+        call_manager.prewarm(&friend).await.unwrap();
+        assert!(call_manager.is_prewarmed(&friend));
+        assert!(!call_manager.is_prewarmed(&PeerIdentityString::new("stranger")));
+
+        call_manager
+            .initiate_call(friend.clone(), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        assert!(!call_manager.is_prewarmed(&friend));
+
+        let config = CallManagerConfig {
+            prewarm_ttl: Duration::ZERO,
+            ..Default::default()
+        };
+        let call_manager = CallManager::<PeerIdentityString>::new(config).await.unwrap();
+        call_manager.prewarm(&friend).await.unwrap();
+        assert!(!call_manager.is_prewarmed(&friend));
+    }
+
+    #[tokio::test]
+    async fn test_incoming_call_is_answered() {
+        let alice = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let bob = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let mut bob_events = bob.subscribe_events();
+
+        let call_id = alice
+            .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        let offer = alice.create_offer(call_id).await.unwrap();
+        let ringing = bob
+            .receive_offer(
+                call_id,
+                PeerIdentityString::new("alice"),
+                PeerIdentityString::new("bob"),
+                offer,
+            )
+            .await
+            .unwrap();
+        assert_eq!(ringing, None);
+        assert!(matches!(
+            bob_events.recv().await.unwrap(),
+            CallEvent::StateChanged { from: CallState::Idle, to: CallState::Ringing, .. }
+        ));
+        match bob_events.recv().await.unwrap() {
+            CallEvent::IncomingCall { offer } => {
+                assert_eq!(offer.call_id, call_id);
+                assert_eq!(offer.caller.to_string_repr(), "alice");
+                assert_eq!(offer.media_types, vec![MediaType::Audio]);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert_eq!(bob.get_call_state(call_id).await, Some(CallState::Ringing));
+
+        let answer = bob
+            .accept_call(call_id, MediaConstraints::audio_only())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(answer.contains("m=audio"));
+        assert_eq!(bob.get_call_state(call_id).await, Some(CallState::Connected));
+        assert_eq!(bob.local_stream(call_id).unwrap().audio_tracks().count(), 1);
+        alice.handle_answer(call_id, answer).await.unwrap();
+
+        // Answered once only
+        assert!(matches!(
+            bob.accept_call(call_id, MediaConstraints::audio_only()).await,
+            Err(CallError::InvalidState)
+        ));
+        // Offers for a call only renegotiate it from its caller
+        let renegotiation = alice.create_offer(call_id).await.unwrap();
+        assert!(matches!(
+            bob.receive_offer(
+                call_id,
+                PeerIdentityString::new("mallory"),
+                PeerIdentityString::new("bob"),
+                renegotiation,
+            )
+            .await,
+            Err(CallError::InvalidState)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_answered_calls_switch_their_own_senders() {
+        let alice = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let bob = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+
+        // Offered video before audio, so bob's transceivers come in that order
+        let video_only = MediaConstraints { audio: false, ..MediaConstraints::video_call() };
+        let call_id = alice
+            .initiate_call(PeerIdentityString::new("bob"), video_only)
+            .await
+            .unwrap();
+        alice.add_media(call_id, MediaConstraints::audio_only()).await.unwrap();
+        let offer = alice.create_offer(call_id).await.unwrap();
+        bob.receive_offer(
+            call_id,
+            PeerIdentityString::new("alice"),
+            PeerIdentityString::new("bob"),
+            offer,
+        )
+        .await
+        .unwrap();
+        bob.accept_call(call_id, MediaConstraints::video_call()).await.unwrap();
+
+        bob.set_audio_muted(call_id, true).await.unwrap();
+        let senders: Vec<_> = {
+            let call = bob.calls.get(&call_id).unwrap();
+            call.local_stream
+                .tracks()
+                .iter()
+                .map(|track| (track.track_type.clone(), Arc::clone(&call.senders[&track.id])))
+                .collect()
+        };
+        assert_eq!(senders.len(), 2);
+        for (kind, sender) in senders {
+            assert_eq!(sender.track().await.is_some(), kind == MediaType::Video);
+        }
+    }
+
     #[tokio::test]
     async fn test_call_manager_accept_call() {
         let config = CallManagerConfig::default();
@@ -405,6 +2964,103 @@ mod tests {
         assert_eq!(state, Some(CallState::Connected));
     }
 
+    #[tokio::test]
+    async fn test_call_manager_park_and_resume_call() {
+        let config = CallManagerConfig::default();
+        let call_manager = CallManager::<PeerIdentityString>::new(config).await.unwrap();
+
+        let callee = PeerIdentityString::new("callee");
+        let constraints = MediaConstraints::audio_only();
+
+        let call_id = call_manager.initiate_call(callee.clone(), constraints.clone()).await.unwrap();
+        assert!(matches!(call_manager.park_call(call_id).await, Err(CallError::InvalidState)));
+
+        call_manager.accept_call(call_id, constraints).await.unwrap();
+        call_manager.park_call(call_id).await.unwrap();
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Parked));
+        assert_eq!(call_manager.remote_peer(call_id), Some(callee));
+
+        call_manager.resume_call(call_id).await.unwrap();
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Connected));
+        assert!(matches!(call_manager.resume_call(call_id).await, Err(CallError::InvalidState)));
+    }
+
+    #[tokio::test]
+    async fn test_lost_connections_fail_after_grace() {
+        let config = CallManagerConfig {
+            reconnect_grace: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let call_manager = CallManager::<PeerIdentityString>::new(config).await.unwrap();
+        let mut events = call_manager.subscribe_events();
+        let constraints = MediaConstraints::audio_only();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), constraints.clone())
+            .await
+            .unwrap();
+        assert!(!call_manager.connection_lost(call_id));
+        call_manager.accept_call(call_id, constraints).await.unwrap();
+
+        // Back within the grace period, the call carries on
+        assert!(call_manager.connection_lost(call_id));
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Reconnecting));
+        assert!(call_manager.connection_restored(call_id));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Connected));
+
+        assert!(call_manager.connection_lost(call_id));
+        let reason = loop {
+            if let CallEvent::ConnectionFailed { reason, .. } = events.recv().await.unwrap() {
+                break reason;
+            }
+        };
+        assert_eq!(reason, FailureReason::ConnectionLost);
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Failed));
+        assert!(!call_manager.connection_restored(call_id));
+    }
+
+    #[tokio::test]
+    async fn test_restart_connection_keeps_the_call() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let constraints = MediaConstraints::audio_only();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), constraints.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            call_manager.restart_connection(call_id).await,
+            Err(CallError::InvalidState)
+        ));
+        call_manager.accept_call(call_id, constraints).await.unwrap();
+        let tracks = call_manager.local_stream(call_id).map(|stream| stream.len());
+
+        let sdp = call_manager.restart_connection(call_id).await.unwrap();
+        assert!(sdp.contains("a=ice-ufrag"));
+        assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Connected));
+        assert_eq!(call_manager.local_stream(call_id).map(|stream| stream.len()), tracks);
+    }
+
+    #[tokio::test]
+    async fn test_send_dtmf_needs_keys_and_channel() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("ivr"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        assert!(matches!(
+            call_manager.send_dtmf(call_id, "1#").await,
+            Err(CallError::DataChannelNotFound(label)) if label == DTMF_CHANNEL_LABEL
+        ));
+        assert!(matches!(
+            call_manager.send_dtmf(call_id, "1p").await,
+            Err(CallError::Dtmf(DtmfError::InvalidDigit('p')))
+        ));
+    }
+
     #[tokio::test]
     async fn test_call_manager_reject_call() {
         let config = CallManagerConfig::default();
@@ -469,7 +3125,24 @@ mod tests {
         let result = call_manager.add_ice_candidate(call_id, candidate).await;
         // This might fail in test environment, but should not panic
         // We just test that the method exists and handles call not found
-        assert!(result.is_ok() || matches!(result, Err(CallError::ConfigError(_))));
+        assert!(result.is_ok() || matches!(result, Err(CallError::IceCandidate(_))));
+    }
+
+    #[tokio::test]
+    async fn test_add_ice_candidate_follows_path_policy() {
+        let config = CallManagerConfig {
+            path_policy: PathPolicy::private(),
+            ..Default::default()
+        };
+        let call_manager = CallManager::<PeerIdentityString>::new(config).await.unwrap();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+
+        let relay = "candidate:4 1 udp 16777215 198.51.100.9 3478 typ relay raddr 0.0.0.0 rport 0";
+        let result = call_manager.add_ice_candidate(call_id, relay.to_string()).await;
+        assert!(matches!(result, Err(CallError::PathRejected(PathError::Relay))));
     }
 
     #[tokio::test]
@@ -483,8 +3156,50 @@ mod tests {
         let call_id = call_manager.initiate_call(callee, constraints).await.unwrap();
 
         let result = call_manager.start_ice_gathering(call_id).await;
-        // This might fail in test environment, but should not panic
-        assert!(result.is_ok() || matches!(result, Err(CallError::ConfigError(_))));
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_call_manager_concurrent_calls_do_not_block() {
+        let config = CallManagerConfig::default();
+        let call_manager = Arc::new(CallManager::<PeerIdentityString>::new(config).await.unwrap());
+
+        let mut handles = Vec::new();
+        for i in 0..5 {
+            let manager = Arc::clone(&call_manager);
+            handles.push(tokio::spawn(async move {
+                let callee = PeerIdentityString::new(format!("callee-{}", i));
+                let call_id = manager
+                    .initiate_call(callee, MediaConstraints::audio_only())
+                    .await
+                    .unwrap();
+                let candidate = "candidate:1 1 UDP 2122260223 192.168.1.1 12345 typ host".to_string();
+                let _ = manager.add_ice_candidate(call_id, candidate).await;
+                manager.accept_call(call_id, MediaConstraints::audio_only()).await.unwrap();
+                call_id
+            }));
+        }
+
+        for handle in handles {
+            let call_id = handle.await.unwrap();
+            assert_eq!(call_manager.get_call_state(call_id).await, Some(CallState::Connected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_offer_cancelled() {
+        let call_manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let call_id = call_manager
+            .initiate_call(PeerIdentityString::new("callee"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+
+        let deadline = Deadline::none();
+        deadline.token().cancel();
+        let result = call_manager.create_offer_with(call_id, &deadline).await;
+        assert!(matches!(result, Err(CallError::Interrupted(Interrupted::Cancelled))));
     }
 
     #[tokio::test]
@@ -0,0 +1,529 @@
+//! Call history and call detail records
+//!
+//! Every call leaves a [`CallRecord`] once it ends: the remote peer, which
+//! way the call went, when it started, was answered and ended, how long it
+//! lasted and why it ended. A [`CallHistory`] follows call events to build
+//! the records and keeps them in a [`CallHistoryStore`]:
+//! [`JsonFileCallHistory`] appends them to a file as JSON lines and
+//! [`MemoryCallHistory`] keeps them until the process exits. Implement the
+//! trait to keep them anywhere else, such as a database.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::identity::PeerIdentity;
+use crate::types::{ByeReason, CallEvent, CallId, CallState, FailureReason};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Call history errors
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    /// History file could not be read or written
+    #[error("Failed to access call history {path:?}")]
+    Io {
+        /// History file
+        path: PathBuf,
+        /// Underlying failure
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Record could not be encoded
+    #[error("Failed to encode call record")]
+    Encode(#[source] serde_json::Error),
+}
+
+impl CodedError for HistoryError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::HistoryFailed
+    }
+}
+
+/// Which way a call went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CallDirection {
+    /// The remote peer called us
+    Incoming,
+    /// We called the remote peer
+    Outgoing,
+}
+
+/// Why a call ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CallEndReason {
+    /// Answered, then hung up by either party
+    HungUp,
+    /// Hung up before it was answered
+    Unanswered,
+    /// Declined by the callee
+    Rejected,
+    /// Failed before or after being answered
+    Failed(FailureReason),
+}
+
+/// What is known about a finished call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallRecord {
+    /// Call identifier
+    pub call_id: CallId,
+    /// The remote peer
+    pub peer: String,
+    /// Which way the call went
+    pub direction: CallDirection,
+    /// When the call was placed or offered to us
+    pub started_at: DateTime<Utc>,
+    /// When the call was first connected, `None` if it never was
+    pub answered_at: Option<DateTime<Utc>>,
+    /// When the call ended
+    pub ended_at: DateTime<Utc>,
+    /// Time spent connected, zero for calls never answered
+    pub duration: Duration,
+    /// Why the call ended
+    pub end_reason: CallEndReason,
+}
+
+/// Which records to return, all of them by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    /// Only calls with this peer
+    pub peer: Option<String>,
+    /// Only calls that went this way
+    pub direction: Option<CallDirection>,
+    /// Only calls started at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// At most this many records, most recent first
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    /// Whether `record` is one of the records asked for
+    #[must_use]
+    pub fn matches(&self, record: &CallRecord) -> bool {
+        self.peer.as_ref().is_none_or(|peer| *peer == record.peer)
+            && self
+                .direction
+                .is_none_or(|direction| direction == record.direction)
+            && self.since.is_none_or(|since| record.started_at >= since)
+    }
+
+    /// The records asked for out of `records`, most recent first
+    #[must_use]
+    pub fn select(&self, records: impl IntoIterator<Item = CallRecord>) -> Vec<CallRecord> {
+        let mut selected: Vec<_> = records
+            .into_iter()
+            .filter(|record| self.matches(record))
+            .collect();
+        selected.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        if let Some(limit) = self.limit {
+            selected.truncate(limit);
+        }
+        selected
+    }
+}
+
+/// Where call records are kept
+pub trait CallHistoryStore: Send + Sync + fmt::Debug {
+    /// Keep the record of a call that ended
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the record cannot be stored
+    fn record(&self, record: &CallRecord) -> Result<(), HistoryError>;
+
+    /// Records matching `query`, most recent first
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the records cannot be read
+    fn records(&self, query: &HistoryQuery) -> Result<Vec<CallRecord>, HistoryError>;
+}
+
+/// Call records kept in memory
+#[derive(Debug, Default)]
+pub struct MemoryCallHistory {
+    records: Mutex<Vec<CallRecord>>,
+}
+
+impl MemoryCallHistory {
+    /// An empty history
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CallHistoryStore for MemoryCallHistory {
+    fn record(&self, record: &CallRecord) -> Result<(), HistoryError> {
+        self.records.lock().push(record.clone());
+        Ok(())
+    }
+
+    fn records(&self, query: &HistoryQuery) -> Result<Vec<CallRecord>, HistoryError> {
+        Ok(query.select(self.records.lock().iter().cloned()))
+    }
+}
+
+/// Call records appended to a file, one JSON object per line
+///
+/// The file is created with the first record. Lines that cannot be read
+/// back, such as one cut short by a crash, are skipped.
+#[derive(Debug)]
+pub struct JsonFileCallHistory {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl JsonFileCallHistory {
+    /// Keep records in the file at `path`
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// The history file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn io_error(&self, source: std::io::Error) -> HistoryError {
+        HistoryError::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+impl CallHistoryStore for JsonFileCallHistory {
+    fn record(&self, record: &CallRecord) -> Result<(), HistoryError> {
+        let mut line = serde_json::to_vec(record).map_err(HistoryError::Encode)?;
+        line.push(b'\n');
+        let _guard = self.lock.lock();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|e| self.io_error(e))
+    }
+
+    fn records(&self, query: &HistoryQuery) -> Result<Vec<CallRecord>, HistoryError> {
+        let contents = {
+            let _guard = self.lock.lock();
+            match std::fs::read_to_string(&self.path) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(self.io_error(e)),
+            }
+        };
+        let records = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    tracing::warn!("Skipping unreadable call record in {:?}: {}", self.path, e);
+                    None
+                }
+            });
+        Ok(query.select(records))
+    }
+}
+
+/// A call that has not ended yet
+#[derive(Debug, Clone)]
+struct OpenCall {
+    peer: String,
+    direction: CallDirection,
+    started_at: DateTime<Utc>,
+    answered_at: Option<DateTime<Utc>>,
+    end_reason: Option<CallEndReason>,
+}
+
+impl OpenCall {
+    fn new(peer: String, direction: CallDirection) -> Self {
+        Self {
+            peer,
+            direction,
+            started_at: Utc::now(),
+            answered_at: None,
+            end_reason: None,
+        }
+    }
+
+    fn finish(self, call_id: CallId, ended_at: DateTime<Utc>) -> CallRecord {
+        let duration = self
+            .answered_at
+            .and_then(|answered_at| (ended_at - answered_at).to_std().ok())
+            .unwrap_or_default();
+        let end_reason = self.end_reason.unwrap_or(match self.answered_at {
+            Some(_) => CallEndReason::HungUp,
+            None => CallEndReason::Unanswered,
+        });
+        CallRecord {
+            call_id,
+            peer: self.peer,
+            direction: self.direction,
+            started_at: self.started_at,
+            answered_at: self.answered_at,
+            ended_at,
+            duration,
+            end_reason,
+        }
+    }
+}
+
+/// Builds call records from call events and keeps them in a store
+#[derive(Debug)]
+pub struct CallHistory {
+    store: Arc<dyn CallHistoryStore>,
+    open: Mutex<HashMap<CallId, OpenCall>>,
+}
+
+impl CallHistory {
+    /// Keep records in `store`
+    #[must_use]
+    pub fn new(store: Arc<dyn CallHistoryStore>) -> Self {
+        Self {
+            store,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Where records are kept
+    #[must_use]
+    pub fn store(&self) -> &Arc<dyn CallHistoryStore> {
+        &self.store
+    }
+
+    /// Follow a call event, returning the call's record once it ends
+    ///
+    /// The record is not stored yet; pass it to [`CallHistoryStore::record`].
+    pub fn observe<I: PeerIdentity>(&self, event: &CallEvent<I>) -> Option<CallRecord> {
+        let mut open = self.open.lock();
+        match event {
+            CallEvent::CallInitiated {
+                call_id, callee, ..
+            } => {
+                open.entry(*call_id).or_insert_with(|| {
+                    OpenCall::new(callee.to_string_repr(), CallDirection::Outgoing)
+                });
+            }
+            CallEvent::IncomingCall { offer } => {
+                // Renegotiations offer an existing call again
+                open.entry(offer.call_id).or_insert_with(|| {
+                    OpenCall::new(offer.caller.to_string_repr(), CallDirection::Incoming)
+                });
+            }
+            CallEvent::StateChanged {
+                call_id,
+                to: CallState::Connected,
+                ..
+            } => {
+                if let Some(call) = open.get_mut(call_id) {
+                    call.answered_at.get_or_insert_with(Utc::now);
+                }
+            }
+            CallEvent::CallRejected { call_id } => {
+                if let Some(call) = open.get_mut(call_id) {
+                    call.end_reason.get_or_insert(CallEndReason::Rejected);
+                }
+            }
+            CallEvent::ConnectionFailed {
+                call_id, reason, ..
+            } => {
+                if let Some(call) = open.get_mut(call_id) {
+                    call.end_reason
+                        .get_or_insert(CallEndReason::Failed(*reason));
+                }
+            }
+            CallEvent::CallEnded { call_id, reason } => {
+                let mut call = open.remove(call_id)?;
+                if matches!(reason, ByeReason::Busy | ByeReason::Declined) {
+                    call.end_reason.get_or_insert(CallEndReason::Rejected);
+                }
+                return Some(call.finish(*call_id, Utc::now()));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Records matching `query`, most recent first
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the store cannot be read
+    pub fn records(&self, query: &HistoryQuery) -> Result<Vec<CallRecord>, HistoryError> {
+        self.store.records(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::PeerIdentityString;
+    use crate::types::{CallOffer, MediaConstraints};
+
+    type Event = CallEvent<PeerIdentityString>;
+
+    fn connected(call_id: CallId) -> Event {
+        CallEvent::StateChanged {
+            call_id,
+            from: CallState::Connecting,
+            to: CallState::Connected,
+        }
+    }
+
+    #[test]
+    fn test_history_records_how_calls_end() {
+        let history = CallHistory::new(Arc::new(MemoryCallHistory::new()));
+        let (answered, missed, failed) = (CallId::new(), CallId::new(), CallId::new());
+
+        let outgoing: Event = CallEvent::CallInitiated {
+            call_id: answered,
+            callee: PeerIdentityString::new("bob"),
+            constraints: MediaConstraints::audio_only(),
+        };
+        assert_eq!(history.observe(&outgoing), None);
+        history.observe(&connected(answered));
+        let record = history
+            .observe(&Event::CallEnded {
+                call_id: answered,
+                reason: ByeReason::HungUp,
+            })
+            .unwrap();
+        assert_eq!(record.peer, "bob");
+        assert_eq!(record.direction, CallDirection::Outgoing);
+        assert_eq!(record.end_reason, CallEndReason::HungUp);
+        assert!(record.answered_at.is_some());
+        history.store().record(&record).unwrap();
+
+        let incoming: Event = CallEvent::IncomingCall {
+            offer: CallOffer {
+                call_id: missed,
+                caller: PeerIdentityString::new("carol"),
+                callee: PeerIdentityString::new("me"),
+                sdp: String::new(),
+                media_types: Vec::new(),
+                timestamp: Utc::now(),
+            },
+        };
+        history.observe(&incoming);
+        let record = history
+            .observe(&Event::CallEnded {
+                call_id: missed,
+                reason: ByeReason::Cancelled,
+            })
+            .unwrap();
+        assert_eq!(record.direction, CallDirection::Incoming);
+        assert_eq!(record.end_reason, CallEndReason::Unanswered);
+        assert_eq!(record.duration, Duration::ZERO);
+        history.store().record(&record).unwrap();
+
+        let outgoing: Event = CallEvent::CallInitiated {
+            call_id: failed,
+            callee: PeerIdentityString::new("bob"),
+            constraints: MediaConstraints::audio_only(),
+        };
+        history.observe(&outgoing);
+        history.observe(&Event::ConnectionFailed {
+            call_id: failed,
+            reason: FailureReason::NoAnswer,
+            error: "no answer".to_string(),
+        });
+        let record = history
+            .observe(&Event::CallEnded {
+                call_id: failed,
+                reason: ByeReason::Timeout,
+            })
+            .unwrap();
+        assert_eq!(
+            record.end_reason,
+            CallEndReason::Failed(FailureReason::NoAnswer)
+        );
+        history.store().record(&record).unwrap();
+
+        // Calls the callee turned down were rejected, not missed
+        let declined = CallId::new();
+        history.observe(&CallEvent::CallInitiated {
+            call_id: declined,
+            callee: PeerIdentityString::new("dave"),
+            constraints: MediaConstraints::audio_only(),
+        });
+        let record = history
+            .observe(&Event::CallEnded {
+                call_id: declined,
+                reason: ByeReason::Declined,
+            })
+            .unwrap();
+        assert_eq!(record.end_reason, CallEndReason::Rejected);
+
+        // Unknown calls leave nothing behind
+        assert_eq!(
+            history.observe(&Event::CallEnded {
+                call_id: CallId::new(),
+                reason: ByeReason::HungUp,
+            }),
+            None
+        );
+
+        let with_bob = HistoryQuery {
+            peer: Some("bob".to_string()),
+            ..Default::default()
+        };
+        let records = history.records(&with_bob).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].call_id, failed);
+        let latest = HistoryQuery {
+            limit: Some(1),
+            direction: Some(CallDirection::Incoming),
+            ..Default::default()
+        };
+        assert_eq!(history.records(&latest).unwrap()[0].call_id, missed);
+    }
+
+    #[test]
+    fn test_json_file_history_round_trips() {
+        let path = std::env::temp_dir().join(format!("call-history-{}.jsonl", CallId::new()));
+        let store = JsonFileCallHistory::new(&path);
+        assert_eq!(store.records(&HistoryQuery::default()).unwrap(), []);
+
+        let now = Utc::now();
+        let record = CallRecord {
+            call_id: CallId::new(),
+            peer: "bob".to_string(),
+            direction: CallDirection::Outgoing,
+            started_at: now,
+            answered_at: Some(now),
+            ended_at: now + chrono::Duration::seconds(90),
+            duration: Duration::from_secs(90),
+            end_reason: CallEndReason::HungUp,
+        };
+        store.record(&record).unwrap();
+        // A record cut short by a crash is skipped
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"call_id\":")
+            .unwrap();
+
+        let records = JsonFileCallHistory::new(&path)
+            .records(&HistoryQuery::default())
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records, [record]);
+    }
+}
@@ -0,0 +1,373 @@
+//! Per-call log capture for bug reports
+//!
+//! [`CallLogBuffer`] keeps the most recent tracing events in memory, each
+//! tagged with the calls it mentions: a `call_id` field on the event or an
+//! enclosing span, or a call ID in the message text. Install its
+//! [`CallLogLayer`] in the application's subscriber, then export one
+//! call's events as a [`CallLogBundle`] to attach to a bug report.
+//!
+//! Secrets are redacted as events are captured, so they never sit in the
+//! buffer: SDP and candidate fields are dropped entirely, ICE credentials,
+//! DTLS fingerprints and crypto attributes are blanked, and long
+//! key-like tokens are masked.
+
+use crate::types::CallId;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::sync::Arc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+use uuid::Uuid;
+
+/// Events kept by default
+pub const DEFAULT_CALL_LOG_CAPACITY: usize = 4096;
+
+/// Replacement for redacted values
+const REDACTED: &str = "[redacted]";
+
+/// Fields whose values are never kept
+const SECRET_FIELDS: &[&str] = &[
+    "sdp",
+    "candidate",
+    "key",
+    "secret",
+    "password",
+    "token",
+    "credential",
+];
+
+/// SDP attributes whose values are blanked in message text
+const SECRET_ATTRIBUTES: &[&str] = &["ice-pwd:", "ice-ufrag:", "fingerprint:", "crypto:"];
+
+/// Tokens at least this long made only of key alphabet characters are masked
+const KEY_LIKE_MIN_LEN: usize = 32;
+
+/// One captured, redacted tracing event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// When the event was recorded
+    pub timestamp: DateTime<Utc>,
+    /// Level, e.g. `INFO`
+    pub level: String,
+    /// Module that logged the event
+    pub target: String,
+    /// Message followed by any other fields
+    pub message: String,
+    /// Calls the event mentions
+    pub call_ids: Vec<CallId>,
+}
+
+/// Logs of one call, ready to attach to a bug report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallLogBundle {
+    /// Call the logs belong to
+    pub call_id: CallId,
+    /// When the bundle was exported
+    pub exported_at: DateTime<Utc>,
+    /// Crate version that produced the logs
+    pub version: String,
+    /// Events mentioning the call, oldest first
+    pub entries: Vec<LogEntry>,
+    /// Whether older events were evicted, so the bundle may be incomplete
+    pub truncated: bool,
+}
+
+#[derive(Debug, Default)]
+struct Ring {
+    entries: VecDeque<LogEntry>,
+    evicted: bool,
+}
+
+/// Bounded in-memory store of recent call events
+#[derive(Debug)]
+pub struct CallLogBuffer {
+    capacity: usize,
+    ring: Mutex<Ring>,
+}
+
+impl Default for CallLogBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_CALL_LOG_CAPACITY)
+    }
+}
+
+impl CallLogBuffer {
+    /// Keep at most `capacity` events
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ring: Mutex::new(Ring::default()),
+        }
+    }
+
+    /// Layer that feeds this buffer from a tracing subscriber
+    #[must_use]
+    pub fn layer(self: &Arc<Self>) -> CallLogLayer {
+        CallLogLayer {
+            buffer: Arc::clone(self),
+        }
+    }
+
+    /// Events currently held
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.ring.lock().entries.len()
+    }
+
+    /// Whether no events are held
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ring.lock().entries.is_empty()
+    }
+
+    /// Add an already redacted entry, evicting the oldest if full
+    pub fn push(&self, entry: LogEntry) {
+        let mut ring = self.ring.lock();
+        if ring.entries.len() == self.capacity {
+            ring.entries.pop_front();
+            ring.evicted = true;
+        }
+        ring.entries.push_back(entry);
+    }
+
+    /// Bundle every held event that mentions `call_id`
+    #[must_use]
+    pub fn export(&self, call_id: CallId) -> CallLogBundle {
+        let ring = self.ring.lock();
+        CallLogBundle {
+            call_id,
+            exported_at: Utc::now(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            entries: ring
+                .entries
+                .iter()
+                .filter(|entry| entry.call_ids.contains(&call_id))
+                .cloned()
+                .collect(),
+            truncated: ring.evicted,
+        }
+    }
+
+    /// Drop every held event that mentions `call_id`
+    pub fn forget(&self, call_id: CallId) {
+        self.ring
+            .lock()
+            .entries
+            .retain(|entry| !entry.call_ids.contains(&call_id));
+    }
+}
+
+/// Tracing layer capturing call events into a [`CallLogBuffer`]
+#[derive(Debug, Clone)]
+pub struct CallLogLayer {
+    buffer: Arc<CallLogBuffer>,
+}
+
+/// Call ID recorded on a span, kept in the span's extensions
+struct SpanCallId(CallId);
+
+impl<S> Layer<S> for CallLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = EventVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(call_id), Some(span)) = (visitor.call_id, ctx.span(id)) {
+            span.extensions_mut().insert(SpanCallId(call_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+
+        let mut call_ids: Vec<CallId> = visitor.call_id.into_iter().collect();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope {
+                if let Some(SpanCallId(call_id)) = span.extensions().get::<SpanCallId>() {
+                    call_ids.push(*call_id);
+                }
+            }
+        }
+        call_ids.extend(mentioned_ids(&visitor.message));
+        call_ids.sort_by_key(|call_id| call_id.0);
+        call_ids.dedup();
+        if call_ids.is_empty() {
+            return;
+        }
+
+        let mut message = redact(&visitor.message);
+        message.push_str(&visitor.fields);
+        let metadata = event.metadata();
+        self.buffer.push(LogEntry {
+            timestamp: Utc::now(),
+            level: metadata.level().to_string(),
+            target: metadata.target().to_string(),
+            message,
+            call_ids,
+        });
+    }
+}
+
+/// Collects an event's message, call ID and redacted fields
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    fields: String,
+    call_id: Option<CallId>,
+}
+
+impl EventVisitor {
+    fn record_value(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            "call_id" => {
+                self.call_id = Uuid::parse_str(value).ok().map(CallId);
+                let _ = write!(self.fields, " call_id={value}");
+            }
+            name if SECRET_FIELDS.iter().any(|secret| name.contains(secret)) => {
+                let _ = write!(self.fields, " {name}={REDACTED}");
+            }
+            name => {
+                let _ = write!(self.fields, " {name}={}", redact(value));
+            }
+        }
+    }
+}
+
+impl Visit for EventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_value(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_value(field, &format!("{value:?}"));
+    }
+}
+
+/// UUIDs appearing as words in `text`
+fn mentioned_ids(text: &str) -> impl Iterator<Item = CallId> + '_ {
+    text.split(|c: char| !(c.is_ascii_hexdigit() || c == '-'))
+        .filter(|word| word.len() == 36)
+        .filter_map(|word| Uuid::parse_str(word).ok())
+        .map(CallId)
+}
+
+/// Blank SDP secrets and key-like tokens in free text
+fn redact(text: &str) -> String {
+    text.lines().map(redact_line).collect::<Vec<_>>().join("\n")
+}
+
+fn redact_line(line: &str) -> String {
+    // Attribute values run to the end of the line, e.g. "sha-256 AB:CD:..."
+    for attribute in SECRET_ATTRIBUTES {
+        if let Some(start) = line.find(attribute) {
+            let end = start + attribute.len();
+            return format!("{}{REDACTED}", &line[..end]);
+        }
+    }
+    line.split(' ')
+        .map(|word| if is_key_like(word) { REDACTED } else { word })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn is_key_like(word: &str) -> bool {
+    word.len() >= KEY_LIKE_MIN_LEN
+        && Uuid::parse_str(word).is_err()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '_' | '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn capture(buffer: &Arc<CallLogBuffer>, log: impl FnOnce()) {
+        let subscriber = tracing_subscriber::registry().with(buffer.layer());
+        tracing::subscriber::with_default(subscriber, log);
+    }
+
+    #[test]
+    fn test_tags_events_by_field_span_and_message() {
+        let buffer = Arc::new(CallLogBuffer::new(16));
+        let call_id = CallId::new();
+        let other = CallId::new();
+
+        capture(&buffer, || {
+            tracing::info!(call_id = %call_id, "Offer created");
+            tracing::info!("Call {} accepted", call_id);
+            let span = tracing::info_span!("negotiate", call_id = %call_id);
+            span.in_scope(|| tracing::debug!("ICE restart"));
+            tracing::info!("Call {} accepted", other);
+            tracing::info!("Unrelated startup message");
+        });
+
+        let bundle = buffer.export(call_id);
+        let messages: Vec<_> = bundle
+            .entries
+            .iter()
+            .map(|entry| entry.message.as_str())
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].starts_with("Offer created"));
+        assert_eq!(messages[2], "ICE restart");
+        assert!(!bundle.truncated);
+        assert_eq!(buffer.len(), 4);
+    }
+
+    #[test]
+    fn test_redacts_secrets() {
+        let buffer = Arc::new(CallLogBuffer::new(16));
+        let call_id = CallId::new();
+        let key = "q3J8fK2mN9pL4rT7vX1zB6cD0eG5hJ8kM2nP";
+
+        capture(&buffer, || {
+            tracing::info!(
+                call_id = %call_id,
+                sdp = "v=0\na=ice-pwd:hunter2",
+                session_key = key,
+                "Negotiating"
+            );
+            tracing::warn!("Call {} bad line a=ice-pwd:hunter2", call_id);
+            tracing::warn!("Call {} derived {}", call_id, key);
+            tracing::warn!("Call {} a=fingerprint:sha-256 AB:CD:EF", call_id);
+        });
+
+        let bundle = buffer.export(call_id);
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(!json.contains("hunter2"));
+        assert!(!json.contains(key));
+        assert!(!json.contains("AB:CD:EF"));
+        assert!(bundle.entries[0].message.contains("sdp=[redacted]"));
+        assert!(bundle.entries[1].message.ends_with("a=ice-pwd:[redacted]"));
+    }
+
+    #[test]
+    fn test_ring_evicts_oldest() {
+        let buffer = Arc::new(CallLogBuffer::new(2));
+        let call_id = CallId::new();
+        capture(&buffer, || {
+            for i in 0..3 {
+                tracing::info!(call_id = %call_id, "event {}", i);
+            }
+        });
+
+        let bundle = buffer.export(call_id);
+        assert!(bundle.truncated);
+        assert!(bundle.entries[0].message.starts_with("event 1"));
+
+        buffer.forget(call_id);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+//! Per-call statistics, modelled on WebRTC `getStats`
+//!
+//! A [`CallStats`] gathers what is known about a call at one moment:
+//! round-trip time and packet counters from the peer connection's
+//! transport, the codecs negotiated for it, and what the remote video
+//! decoders made of the frames received. Bitrates are measured between
+//! consecutive samples of the same call by a [`CallStatsSampler`].
+
+use crate::remote_video::DecodeStats;
+use crate::types::CallId;
+use chrono::{DateTime, Utc};
+use saorsa_webrtc_codecs::{AudioCodec, VideoCodec};
+use std::collections::HashMap;
+use std::time::Instant;
+use webrtc::stats::{StatsReport, StatsReportType};
+
+/// Counters of a call's peer connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransportStats {
+    /// Round-trip time of the selected candidate pair, once measured
+    pub rtt_ms: Option<u32>,
+    /// RTP packets sent across all tracks
+    pub packets_sent: u64,
+    /// RTP packets received across all tracks
+    pub packets_received: u64,
+    /// Packets sent that the remote party reports lost
+    pub packets_lost: u64,
+    /// RTP payload bytes sent
+    pub bytes_sent: u64,
+    /// RTP payload bytes received
+    pub bytes_received: u64,
+}
+
+impl TransportStats {
+    /// Sum the RTP streams of a peer connection's stats report
+    #[must_use]
+    pub fn from_report(report: &StatsReport) -> Self {
+        let mut stats = Self::default();
+        for entry in report.reports.values() {
+            match entry {
+                StatsReportType::CandidatePair(pair) if pair.nominated => {
+                    if pair.current_round_trip_time > 0.0 {
+                        stats.rtt_ms = Some((pair.current_round_trip_time * 1000.0).round() as u32);
+                    }
+                }
+                StatsReportType::OutboundRTP(outbound) => {
+                    stats.packets_sent += outbound.packets_sent;
+                    stats.bytes_sent += outbound.bytes_sent;
+                }
+                StatsReportType::InboundRTP(inbound) => {
+                    stats.packets_received += inbound.packets_received;
+                    stats.bytes_received += inbound.bytes_received;
+                }
+                StatsReportType::RemoteInboundRTP(remote) => {
+                    stats.packets_lost += remote.packets_lost.max(0) as u64;
+                }
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+/// Statistics of a call at one moment
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallStats {
+    /// Call identifier
+    pub call_id: CallId,
+    /// When the stats were gathered
+    pub timestamp: DateTime<Utc>,
+    /// Peer connection counters
+    pub transport: TransportStats,
+    /// Jitter last reported for the call, if any
+    pub jitter_ms: Option<u32>,
+    /// Sending rate since the previous sample, `None` on the first
+    pub send_bitrate_kbps: Option<u32>,
+    /// Receiving rate since the previous sample, `None` on the first
+    pub receive_bitrate_kbps: Option<u32>,
+    /// Negotiated audio codec
+    pub audio_codec: Option<AudioCodec>,
+    /// Negotiated video codec
+    pub video_codec: Option<VideoCodec>,
+    /// Remote video as decoded, `None` before the first frame arrived
+    pub video: Option<DecodeStats>,
+}
+
+/// Bytes counted at a call's previous sample
+#[derive(Debug, Clone, Copy)]
+struct Baseline {
+    at: Instant,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+/// Measures each call's bitrates between consecutive samples
+#[derive(Debug, Default)]
+pub struct CallStatsSampler {
+    baselines: HashMap<CallId, Baseline>,
+}
+
+impl CallStatsSampler {
+    /// Create with no samples taken
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a sample of a call taken at `now`, returning the sending and
+    /// receiving rates in kbps since its previous sample
+    ///
+    /// `None` for the first sample of a call, or when counters went
+    /// backwards as the peer connection was replaced.
+    pub fn sample(
+        &mut self,
+        call_id: CallId,
+        transport: &TransportStats,
+        now: Instant,
+    ) -> Option<(u32, u32)> {
+        let previous = self.baselines.insert(
+            call_id,
+            Baseline {
+                at: now,
+                bytes_sent: transport.bytes_sent,
+                bytes_received: transport.bytes_received,
+            },
+        )?;
+        let elapsed = now.checked_duration_since(previous.at)?.as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        let kbps = |bytes: u64| (bytes as f64 * 8.0 / elapsed / 1000.0).round() as u32;
+        let sent = transport.bytes_sent.checked_sub(previous.bytes_sent)?;
+        let received = transport
+            .bytes_received
+            .checked_sub(previous.bytes_received)?;
+        Some((kbps(sent), kbps(received)))
+    }
+
+    /// Forget a call's previous sample
+    pub fn remove_call(&mut self, call_id: CallId) {
+        self.baselines.remove(&call_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_sampler_measures_bitrate_between_samples() {
+        let mut sampler = CallStatsSampler::new();
+        let call_id = CallId::new();
+        let start = Instant::now();
+        let mut transport = TransportStats {
+            bytes_sent: 10_000,
+            bytes_received: 5_000,
+            ..Default::default()
+        };
+        assert_eq!(sampler.sample(call_id, &transport, start), None);
+
+        // 125 kB sent and 62.5 kB received over two seconds
+        transport.bytes_sent += 125_000;
+        transport.bytes_received += 62_500;
+        let later = start + Duration::from_secs(2);
+        assert_eq!(sampler.sample(call_id, &transport, later), Some((500, 250)));
+
+        // A fresh peer connection starts counting again
+        transport.bytes_sent = 0;
+        let later = later + Duration::from_secs(1);
+        assert_eq!(sampler.sample(call_id, &transport, later), None);
+
+        sampler.remove_call(call_id);
+        let later = later + Duration::from_secs(1);
+        assert_eq!(sampler.sample(call_id, &transport, later), None);
+    }
+}
@@ -0,0 +1,339 @@
+//! Codec capability negotiation
+//!
+//! Each side advertises the codecs it can both encode and decode, most
+//! preferred first, in a [`SignalingMessage::Capabilities`] exchange
+//! alongside the SDP offer and answer. Both sides then pick the same codecs
+//! with [`CodecCapabilities::negotiate`]: the first of the offerer's codecs
+//! that the answerer also supports, as in SDP offer/answer.
+//!
+//! The same exchange picks the media encryption cipher, see
+//! [`MediaCipher::negotiate`].
+//!
+//! Contacts also trade [`PeerCapabilities`] in a
+//! [`SignalingMessage::Hello`] before any call, so a call can be placed
+//! with media the other side will take rather than renegotiated down once
+//! connected; see [`PeerCapabilities::fit`].
+//!
+//! Codecs are named by RTP MIME type rather than by enum, so a peer running
+//! a newer build can advertise codecs this one has never heard of; they are
+//! simply never chosen.
+//!
+//! [`SignalingMessage::Capabilities`]: crate::signaling::SignalingMessage::Capabilities
+//! [`SignalingMessage::Hello`]: crate::signaling::SignalingMessage::Hello
+
+use crate::constraints::ConstrainRange;
+use crate::e2ee::{MediaCipher, E2EE_VERSION};
+use crate::types::MediaConstraints;
+use saorsa_webrtc_codecs::{AudioCodec, VideoCodec};
+use serde::{Deserialize, Serialize};
+
+/// RTP MIME type of H.264
+pub const H264_MIME_TYPE: &str = "video/H264";
+/// RTP MIME type of AV1
+pub const AV1_MIME_TYPE: &str = "video/AV1";
+/// RTP MIME type of Opus
+pub const OPUS_MIME_TYPE: &str = "audio/opus";
+
+/// RTP MIME type of a video codec
+#[must_use]
+pub fn video_mime_type(codec: VideoCodec) -> &'static str {
+    match codec {
+        VideoCodec::H264 => H264_MIME_TYPE,
+        VideoCodec::Av1 => AV1_MIME_TYPE,
+    }
+}
+
+/// RTP MIME type of an audio codec
+#[must_use]
+pub fn audio_mime_type(codec: AudioCodec) -> &'static str {
+    match codec {
+        AudioCodec::Opus => OPUS_MIME_TYPE,
+    }
+}
+
+fn video_codec(mime_type: &str) -> Option<VideoCodec> {
+    [VideoCodec::H264, VideoCodec::Av1]
+        .into_iter()
+        .find(|codec| video_mime_type(*codec).eq_ignore_ascii_case(mime_type))
+}
+
+fn audio_codec(mime_type: &str) -> Option<AudioCodec> {
+    [AudioCodec::Opus]
+        .into_iter()
+        .find(|codec| audio_mime_type(*codec).eq_ignore_ascii_case(mime_type))
+}
+
+/// Codecs a peer supports, most preferred first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodecCapabilities {
+    /// Video codecs by RTP MIME type
+    pub video: Vec<String>,
+    /// Audio codecs by RTP MIME type
+    pub audio: Vec<String>,
+    /// Media encryption cipher that is cheapest for this peer
+    #[serde(default)]
+    pub cipher: MediaCipher,
+}
+
+impl Default for CodecCapabilities {
+    fn default() -> Self {
+        Self::local()
+    }
+}
+
+impl CodecCapabilities {
+    /// Codecs this build can encode and decode
+    ///
+    /// AV1 is preferred when the `av1` feature is enabled, since it needs
+    /// far less bandwidth for the same quality; H.264 is always available.
+    #[must_use]
+    pub fn local() -> Self {
+        let mut video = Vec::new();
+        if cfg!(feature = "av1") {
+            video.push(VideoCodec::Av1);
+        }
+        video.push(VideoCodec::H264);
+        Self::new(&video, &[AudioCodec::Opus])
+    }
+
+    /// Advertise the given codecs, most preferred first, and this CPU's
+    /// preferred cipher
+    #[must_use]
+    pub fn new(video: &[VideoCodec], audio: &[AudioCodec]) -> Self {
+        Self {
+            video: video
+                .iter()
+                .map(|c| video_mime_type(*c).to_string())
+                .collect(),
+            audio: audio
+                .iter()
+                .map(|c| audio_mime_type(*c).to_string())
+                .collect(),
+            cipher: MediaCipher::preferred(),
+        }
+    }
+
+    /// Pick the codecs for a call from both sides' capabilities
+    ///
+    /// Both parties compute the same result: the offerer's most preferred
+    /// codec that the answerer also supports.
+    #[must_use]
+    pub fn negotiate(offerer: &Self, answerer: &Self) -> NegotiatedCodecs {
+        let video = first_common(&offerer.video, &answerer.video, video_codec);
+        let audio = first_common(&offerer.audio, &answerer.audio, audio_codec);
+        let cipher = MediaCipher::negotiate(offerer.cipher, answerer.cipher);
+        NegotiatedCodecs {
+            video,
+            audio,
+            cipher,
+        }
+    }
+}
+
+fn first_common<C>(
+    preferred: &[String],
+    supported: &[String],
+    known: impl Fn(&str) -> Option<C>,
+) -> Option<C> {
+    preferred
+        .iter()
+        .filter(|mime| supported.iter().any(|s| s.eq_ignore_ascii_case(mime)))
+        .find_map(|mime| known(mime))
+}
+
+/// Codecs agreed for a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegotiatedCodecs {
+    /// Video codec, `None` if the peers share none
+    pub video: Option<VideoCodec>,
+    /// Audio codec, `None` if the peers share none
+    pub audio: Option<AudioCodec>,
+    /// Cipher for end-to-end media encryption
+    pub cipher: MediaCipher,
+}
+
+/// Largest video frame a peer takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaxResolution {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
+impl Default for MaxResolution {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+        }
+    }
+}
+
+/// What a contact can do in a call, advertised in presence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerCapabilities {
+    /// Codecs and cipher
+    pub codecs: CodecCapabilities,
+    /// Largest video the peer decodes
+    #[serde(default)]
+    pub max_resolution: MaxResolution,
+    /// Whether the peer shows a screen share track
+    #[serde(default)]
+    pub screen_share: bool,
+    /// Media encryption frame format the peer speaks, 0 for none
+    #[serde(default)]
+    pub e2ee_version: u8,
+}
+
+impl Default for PeerCapabilities {
+    fn default() -> Self {
+        Self::local()
+    }
+}
+
+impl PeerCapabilities {
+    /// Capabilities of this build, with its [`CodecCapabilities::local`]
+    /// codecs
+    #[must_use]
+    pub fn local() -> Self {
+        Self::with_codecs(CodecCapabilities::local())
+    }
+
+    /// Capabilities of this build advertising `codecs`
+    #[must_use]
+    pub fn with_codecs(codecs: CodecCapabilities) -> Self {
+        Self {
+            codecs,
+            max_resolution: MaxResolution::default(),
+            screen_share: true,
+            e2ee_version: E2EE_VERSION,
+        }
+    }
+
+    /// Whether media to this peer can be end-to-end encrypted the way this
+    /// build does it
+    #[must_use]
+    pub fn supports_e2ee(&self) -> bool {
+        self.e2ee_version == E2EE_VERSION
+    }
+
+    /// Trim `constraints` to what this peer takes
+    ///
+    /// Drops audio or video the peer shares no codec for with `ours`, and
+    /// screen share if the peer cannot show it, and caps video at the
+    /// peer's largest resolution.
+    #[must_use]
+    pub fn fit(
+        &self,
+        ours: &CodecCapabilities,
+        mut constraints: MediaConstraints,
+    ) -> MediaConstraints {
+        let codecs = CodecCapabilities::negotiate(ours, &self.codecs);
+        if codecs.audio.is_none() {
+            constraints.audio = false;
+        }
+        if codecs.video.is_none() {
+            constraints.video = false;
+            constraints.screen_share = false;
+        }
+        if !self.screen_share {
+            constraints.screen_share = false;
+        }
+        let video = &mut constraints.video_constraints;
+        cap(&mut video.width, self.max_resolution.width);
+        cap(&mut video.height, self.max_resolution.height);
+        constraints
+    }
+}
+
+/// Keep every bound of `range` at or under `limit`
+fn cap(range: &mut ConstrainRange, limit: u32) {
+    for bound in [&mut range.min, &mut range.max, &mut range.ideal] {
+        if let Some(value) = bound {
+            *value = (*value).min(limit);
+        }
+    }
+    if range.max.is_none() {
+        range.max = Some(limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constraints::VideoConstraints;
+
+    #[test]
+    fn test_offerer_preference_wins() {
+        let offerer =
+            CodecCapabilities::new(&[VideoCodec::Av1, VideoCodec::H264], &[AudioCodec::Opus]);
+        let answerer =
+            CodecCapabilities::new(&[VideoCodec::H264, VideoCodec::Av1], &[AudioCodec::Opus]);
+
+        let codecs = CodecCapabilities::negotiate(&offerer, &answerer);
+        assert_eq!(codecs.video, Some(VideoCodec::Av1));
+        assert_eq!(codecs.audio, Some(AudioCodec::Opus));
+        assert_eq!(
+            CodecCapabilities::negotiate(&answerer, &offerer).video,
+            Some(VideoCodec::H264)
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_missing_codecs() {
+        let offerer = CodecCapabilities {
+            video: vec!["video/VP9".to_string(), "video/h264".to_string()],
+            audio: vec!["audio/G722".to_string()],
+            cipher: MediaCipher::Aes256Gcm,
+        };
+        let answerer = CodecCapabilities::new(&[VideoCodec::H264], &[AudioCodec::Opus]);
+
+        let codecs = CodecCapabilities::negotiate(&offerer, &answerer);
+        assert_eq!(codecs.video, Some(VideoCodec::H264));
+        assert_eq!(codecs.audio, None);
+        assert_eq!(
+            codecs.cipher,
+            MediaCipher::negotiate(MediaCipher::Aes256Gcm, MediaCipher::preferred())
+        );
+    }
+
+    #[test]
+    fn test_local_always_offers_h264_and_opus() {
+        let local = CodecCapabilities::local();
+        assert!(local.video.iter().any(|c| c == H264_MIME_TYPE));
+        assert_eq!(local.audio, vec![OPUS_MIME_TYPE.to_string()]);
+        let json = serde_json::to_string(&local).unwrap();
+        assert_eq!(
+            serde_json::from_str::<CodecCapabilities>(&json).unwrap(),
+            local
+        );
+    }
+
+    #[test]
+    fn test_fit_trims_to_peer_capabilities() {
+        let ours = CodecCapabilities::new(&[VideoCodec::H264], &[AudioCodec::Opus]);
+        let mut peer = PeerCapabilities::with_codecs(CodecCapabilities::new(
+            &[VideoCodec::H264],
+            &[AudioCodec::Opus],
+        ));
+        peer.max_resolution = MaxResolution {
+            width: 1280,
+            height: 720,
+        };
+        peer.screen_share = false;
+
+        let mut wanted = MediaConstraints::video_call();
+        wanted.screen_share = true;
+        wanted.video_constraints = VideoConstraints::ideal(3840, 2160, 30);
+        let fitted = peer.fit(&ours, wanted);
+        assert!(fitted.audio && fitted.video && !fitted.screen_share);
+        assert_eq!(fitted.video_constraints.width.ideal, Some(1280));
+        assert_eq!(fitted.video_constraints.height.max, Some(720));
+
+        peer.codecs.video = vec!["video/VP9".to_string()];
+        let fitted = peer.fit(&ours, MediaConstraints::video_call());
+        assert!(fitted.audio && !fitted.video);
+        assert!(peer.supports_e2ee());
+    }
+}
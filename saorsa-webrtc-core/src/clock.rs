@@ -0,0 +1,190 @@
+//! Peer clock offset estimation
+//!
+//! Round-trip time says how far away a peer is but not how the delay
+//! splits between the two directions, and timestamps from two devices only
+//! line up if their wall clocks do. Peers exchange NTP-style probes over
+//! signaling: the prober records when it sent and received, the responder
+//! when it received and replied, and from those four timestamps
+//!
+//! ```text
+//! offset = ((t1 - t0) + (t2 - t3)) / 2
+//! rtt    = (t3 - t0) - (t2 - t1)
+//! ```
+//!
+//! An asymmetric path skews the offset by up to half the round trip, so
+//! [`ClockEstimator`] keeps a window of recent samples and trusts the one
+//! with the shortest round trip.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Samples kept when picking the best estimate
+pub const CLOCK_SAMPLE_WINDOW: usize = 8;
+
+/// Timestamps of one probe exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockExchange {
+    /// Probe sent, local clock
+    pub sent: DateTime<Utc>,
+    /// Probe received, remote clock
+    pub remote_received: DateTime<Utc>,
+    /// Reply sent, remote clock
+    pub remote_replied: DateTime<Utc>,
+    /// Reply received, local clock
+    pub received: DateTime<Utc>,
+}
+
+impl ClockExchange {
+    fn rtt(&self) -> Duration {
+        (self.received - self.sent) - (self.remote_replied - self.remote_received)
+    }
+
+    fn offset(&self) -> Duration {
+        ((self.remote_received - self.sent) + (self.remote_replied - self.received)) / 2
+    }
+}
+
+/// Estimated relation between a peer's clock and ours
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockEstimate {
+    /// Remote clock minus local clock, in microseconds
+    pub offset_us: i64,
+    /// Round trip of the sample the offset came from, in microseconds
+    pub rtt_us: i64,
+    /// Samples the estimate was chosen from
+    pub samples: usize,
+}
+
+impl ClockEstimate {
+    /// Remote clock minus local clock
+    #[must_use]
+    pub fn offset(&self) -> Duration {
+        Duration::microseconds(self.offset_us)
+    }
+
+    /// Worst-case error of the offset, half the sample's round trip
+    #[must_use]
+    pub fn uncertainty(&self) -> Duration {
+        Duration::microseconds(self.rtt_us / 2)
+    }
+
+    /// Convert a remote timestamp to local time
+    #[must_use]
+    pub fn to_local(&self, remote: DateTime<Utc>) -> DateTime<Utc> {
+        remote - self.offset()
+    }
+
+    /// Delay from the peer to us of something it stamped at `remote_sent`
+    #[must_use]
+    pub fn one_way_delay(&self, remote_sent: DateTime<Utc>, received: DateTime<Utc>) -> Duration {
+        received - self.to_local(remote_sent)
+    }
+}
+
+/// Offset estimate for one peer, refined by each probe exchange
+#[derive(Debug, Clone, Default)]
+pub struct ClockEstimator {
+    samples: VecDeque<(Duration, Duration)>,
+}
+
+impl ClockEstimator {
+    /// Create an estimator with no samples
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a completed exchange and return the updated estimate
+    ///
+    /// Exchanges with a negative round trip are impossible and ignored,
+    /// returning `None`.
+    pub fn add_exchange(&mut self, exchange: &ClockExchange) -> Option<ClockEstimate> {
+        let rtt = exchange.rtt();
+        if rtt < Duration::zero() {
+            return None;
+        }
+        if self.samples.len() == CLOCK_SAMPLE_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((exchange.offset(), rtt));
+        self.estimate()
+    }
+
+    /// Current best estimate, if any exchange has completed
+    #[must_use]
+    pub fn estimate(&self) -> Option<ClockEstimate> {
+        let (offset, rtt) = self.samples.iter().min_by_key(|(_, rtt)| *rtt)?;
+        Some(ClockEstimate {
+            offset_us: offset.num_microseconds().unwrap_or(i64::MAX),
+            rtt_us: rtt.num_microseconds().unwrap_or(i64::MAX),
+            samples: self.samples.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exchange with a remote clock `offset_ms` ahead and the given delays
+    fn exchange(offset_ms: i64, out_ms: i64, back_ms: i64) -> ClockExchange {
+        let sent = Utc::now();
+        let offset = Duration::milliseconds(offset_ms);
+        let remote_received = sent + Duration::milliseconds(out_ms) + offset;
+        let remote_replied = remote_received + Duration::milliseconds(2);
+        ClockExchange {
+            sent,
+            remote_received,
+            remote_replied,
+            received: remote_replied - offset + Duration::milliseconds(back_ms),
+        }
+    }
+
+    #[test]
+    fn test_symmetric_path_recovers_offset() {
+        let mut estimator = ClockEstimator::new();
+        let estimate = estimator.add_exchange(&exchange(-1500, 20, 20)).unwrap();
+
+        assert_eq!(estimate.offset(), Duration::milliseconds(-1500));
+        assert_eq!(estimate.rtt_us, 40_000);
+        assert_eq!(estimate.uncertainty(), Duration::milliseconds(20));
+    }
+
+    #[test]
+    fn test_prefers_shortest_round_trip() {
+        let mut estimator = ClockEstimator::new();
+        estimator.add_exchange(&exchange(300, 150, 10));
+        let estimate = estimator.add_exchange(&exchange(300, 12, 10)).unwrap();
+
+        assert_eq!(estimate.samples, 2);
+        assert_eq!(estimate.rtt_us, 22_000);
+        assert_eq!(estimate.offset(), Duration::milliseconds(301));
+    }
+
+    #[test]
+    fn test_one_way_delay_uses_offset() {
+        let estimate = ClockEstimate {
+            offset_us: 5_000_000,
+            rtt_us: 60_000,
+            samples: 1,
+        };
+        let received = Utc::now();
+        let remote_sent = received + Duration::seconds(5) - Duration::milliseconds(35);
+
+        assert_eq!(
+            estimate.one_way_delay(remote_sent, received),
+            Duration::milliseconds(35)
+        );
+    }
+
+    #[test]
+    fn test_rejects_impossible_exchange() {
+        let mut estimator = ClockEstimator::new();
+        let mut bogus = exchange(0, 10, 10);
+        bogus.received = bogus.sent;
+
+        assert!(estimator.add_exchange(&bogus).is_none());
+        assert!(estimator.estimate().is_none());
+    }
+}
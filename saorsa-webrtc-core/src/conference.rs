@@ -0,0 +1,1068 @@
+//! Group call (conference) state and participant management
+//!
+//! A [`Conference`] tracks who is in a group call and what each
+//! participant is allowed to do. Moderation actions are checked against
+//! the acting participant's role, produce a [`SignalingMessage`] to fan out
+//! to the other participants, and gate which media the forwarding layer
+//! (SFU or mesh) may relay via [`Conference::can_forward`].
+//!
+//! Breakout groups are a routing change within the conference: members
+//! keep their existing QUIC connections and only media forwarding is
+//! narrowed to the participants in the same room.
+
+use crate::active_speaker::{select_layers, ActiveSpeakerDetector, SimulcastLayer};
+use crate::error::{CodedError, ErrorCode};
+use crate::signaling::SignalingMessage;
+use crate::types::MediaType;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Unique identifier for a conference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConferenceId(pub Uuid);
+
+impl ConferenceId {
+    /// Create a new random conference ID
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for ConferenceId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for ConferenceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for ConferenceId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(Self)
+    }
+}
+
+/// Conference errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConferenceError {
+    /// Conference not found
+    #[error("Conference not found: {0}")]
+    NotFound(String),
+
+    /// Participant not found
+    #[error("Participant not found: {0}")]
+    ParticipantNotFound(String),
+
+    /// Participant already in the conference
+    #[error("Participant already joined: {0}")]
+    AlreadyJoined(String),
+
+    /// Acting participant lacks permission
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    /// Conference media is not routed through this node
+    #[error("Media of conference {0} is not routed here")]
+    NotRouted(String),
+}
+
+impl CodedError for ConferenceError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::ParticipantNotFound(_) => ErrorCode::PeerNotFound,
+            Self::AlreadyJoined(_) => ErrorCode::ConferenceFailed,
+            Self::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            Self::NotRouted(_) => ErrorCode::NotFound,
+        }
+    }
+}
+
+/// Participant role within a conference
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParticipantRole {
+    /// Can moderate and publish media
+    Host,
+    /// Can publish media
+    Speaker,
+    /// Receive only
+    Listener,
+}
+
+impl ParticipantRole {
+    /// Whether this role may publish audio and video
+    #[must_use]
+    pub const fn can_publish(&self) -> bool {
+        matches!(self, Self::Host | Self::Speaker)
+    }
+
+    /// Whether this role may mute, remove or change the role of others
+    #[must_use]
+    pub const fn can_moderate(&self) -> bool {
+        matches!(self, Self::Host)
+    }
+}
+
+/// Moderation action carried in [`SignalingMessage::ParticipantControl`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ParticipantAction {
+    /// Mute or unmute the participant's audio
+    Mute {
+        /// New mute state
+        muted: bool,
+    },
+    /// Remove the participant from the conference
+    Remove,
+    /// Change the participant's role
+    SetRole {
+        /// New role
+        role: ParticipantRole,
+    },
+}
+
+/// A breakout group within a conference
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreakoutGroup {
+    /// Group name
+    pub name: String,
+    /// Participants assigned to the group
+    pub members: Vec<String>,
+}
+
+impl BreakoutGroup {
+    /// Create a breakout group
+    #[must_use]
+    pub fn new(name: impl Into<String>, members: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            members,
+        }
+    }
+}
+
+/// A conference participant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Participant {
+    /// Participant identity
+    pub id: String,
+    /// Current role
+    pub role: ParticipantRole,
+    /// Audio muted by a host or by the participant
+    pub audio_muted: bool,
+    /// When the participant joined
+    pub joined_at: DateTime<Utc>,
+}
+
+/// Participant change within a conference
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConferenceEvent {
+    /// Participant joined
+    ParticipantJoined {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Participant that joined
+        participant: Participant,
+    },
+    /// Participant was muted or unmuted
+    ParticipantMuted {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Affected participant
+        participant_id: String,
+        /// New mute state
+        muted: bool,
+    },
+    /// Participant was removed
+    ParticipantRemoved {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Removed participant
+        participant_id: String,
+    },
+    /// Participant role changed
+    RoleChanged {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Affected participant
+        participant_id: String,
+        /// New role
+        role: ParticipantRole,
+    },
+    /// Participant is waiting in the lobby for a host to admit them
+    JoinRequested {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Waiting participant
+        participant_id: String,
+    },
+    /// Host denied a join request
+    JoinDenied {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Denied participant
+        participant_id: String,
+    },
+    /// Dominant speaker changed
+    ActiveSpeakerChanged {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// New dominant speaker
+        participant_id: String,
+    },
+    /// Breakout groups were opened, changed or merged back (empty)
+    BreakoutsChanged {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Current groups; participants not listed are in the main room
+        groups: Vec<BreakoutGroup>,
+    },
+    /// Host answered our own join request
+    JoinAnswered {
+        /// Conference ID
+        conference_id: ConferenceId,
+        /// Whether we were admitted
+        admitted: bool,
+    },
+}
+
+impl ConferenceEvent {
+    /// Conference the event happened in
+    #[must_use]
+    pub fn conference_id(&self) -> ConferenceId {
+        match self {
+            Self::ParticipantJoined { conference_id, .. }
+            | Self::ParticipantMuted { conference_id, .. }
+            | Self::ParticipantRemoved { conference_id, .. }
+            | Self::RoleChanged { conference_id, .. }
+            | Self::JoinRequested { conference_id, .. }
+            | Self::JoinDenied { conference_id, .. }
+            | Self::ActiveSpeakerChanged { conference_id, .. }
+            | Self::BreakoutsChanged { conference_id, .. }
+            | Self::JoinAnswered { conference_id, .. } => *conference_id,
+        }
+    }
+}
+
+/// A participant waiting in the lobby
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinRequest {
+    /// Participant identity
+    pub participant_id: String,
+    /// Parked SDP offer, answered once the participant is admitted
+    pub sdp: String,
+    /// When the request arrived
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Result of a join request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JoinOutcome {
+    /// Joined immediately; the offer can be answered now
+    Joined(ConferenceEvent),
+    /// Parked in the lobby until a host admits or denies
+    Waiting(ConferenceEvent),
+}
+
+/// Conference state
+#[derive(Debug, Clone)]
+pub struct Conference {
+    id: ConferenceId,
+    participants: HashMap<String, Participant>,
+    waiting_room: bool,
+    lobby: HashMap<String, JoinRequest>,
+    speakers: ActiveSpeakerDetector,
+    breakouts: Vec<BreakoutGroup>,
+}
+
+impl Conference {
+    /// Create a conference with `host_id` as its first host
+    #[must_use]
+    pub fn new(id: ConferenceId, host_id: impl Into<String>) -> Self {
+        let host_id = host_id.into();
+        let mut participants = HashMap::new();
+        participants.insert(
+            host_id.clone(),
+            Participant {
+                id: host_id,
+                role: ParticipantRole::Host,
+                audio_muted: false,
+                joined_at: Utc::now(),
+            },
+        );
+        Self {
+            id,
+            participants,
+            waiting_room: false,
+            lobby: HashMap::new(),
+            speakers: ActiveSpeakerDetector::default(),
+            breakouts: Vec::new(),
+        }
+    }
+
+    /// Use a custom active speaker detector
+    #[must_use]
+    pub fn with_speaker_detector(mut self, speakers: ActiveSpeakerDetector) -> Self {
+        self.speakers = speakers;
+        self
+    }
+
+    /// Require host approval before new participants join
+    #[must_use]
+    pub fn with_waiting_room(mut self, enabled: bool) -> Self {
+        self.waiting_room = enabled;
+        self
+    }
+
+    /// Whether new participants wait for host approval
+    #[must_use]
+    pub fn waiting_room(&self) -> bool {
+        self.waiting_room
+    }
+
+    /// Enable or disable the waiting room
+    ///
+    /// Disabling it does not admit participants already waiting.
+    pub fn set_waiting_room(&mut self, enabled: bool) {
+        self.waiting_room = enabled;
+    }
+
+    /// Participants waiting in the lobby
+    pub fn lobby(&self) -> impl Iterator<Item = &JoinRequest> {
+        self.lobby.values()
+    }
+
+    /// Conference ID
+    #[must_use]
+    pub fn id(&self) -> ConferenceId {
+        self.id
+    }
+
+    /// Look up a participant
+    #[must_use]
+    pub fn participant(&self, participant_id: &str) -> Option<&Participant> {
+        self.participants.get(participant_id)
+    }
+
+    /// All participants
+    pub fn participants(&self) -> impl Iterator<Item = &Participant> {
+        self.participants.values()
+    }
+
+    /// Add a participant
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the participant is already in the conference
+    pub fn add_participant(
+        &mut self,
+        participant_id: impl Into<String>,
+        role: ParticipantRole,
+    ) -> Result<ConferenceEvent, ConferenceError> {
+        let participant_id = participant_id.into();
+        if self.participants.contains_key(&participant_id) {
+            return Err(ConferenceError::AlreadyJoined(participant_id));
+        }
+        let participant = Participant {
+            id: participant_id.clone(),
+            role,
+            audio_muted: false,
+            joined_at: Utc::now(),
+        };
+        self.participants
+            .insert(participant_id, participant.clone());
+        Ok(ConferenceEvent::ParticipantJoined {
+            conference_id: self.id,
+            participant,
+        })
+    }
+
+    /// Handle a join request carrying the participant's SDP offer
+    ///
+    /// With the waiting room enabled the offer is parked in the lobby,
+    /// otherwise the participant joins as a speaker straight away.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the participant has already joined
+    pub fn request_join(
+        &mut self,
+        participant_id: &str,
+        sdp: String,
+    ) -> Result<JoinOutcome, ConferenceError> {
+        if self.participants.contains_key(participant_id) {
+            return Err(ConferenceError::AlreadyJoined(participant_id.to_string()));
+        }
+        if !self.waiting_room {
+            return self
+                .add_participant(participant_id, ParticipantRole::Speaker)
+                .map(JoinOutcome::Joined);
+        }
+
+        self.lobby.insert(
+            participant_id.to_string(),
+            JoinRequest {
+                participant_id: participant_id.to_string(),
+                sdp,
+                requested_at: Utc::now(),
+            },
+        );
+        Ok(JoinOutcome::Waiting(ConferenceEvent::JoinRequested {
+            conference_id: self.id,
+            participant_id: participant_id.to_string(),
+        }))
+    }
+
+    /// Admit a waiting participant with the given role
+    ///
+    /// Returns the parked request so its offer can be answered.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host or the participant is not waiting
+    pub fn admit(
+        &mut self,
+        actor_id: &str,
+        participant_id: &str,
+        role: ParticipantRole,
+    ) -> Result<(JoinRequest, ConferenceEvent), ConferenceError> {
+        self.require_host(actor_id)?;
+        let request = self
+            .lobby
+            .remove(participant_id)
+            .ok_or_else(|| ConferenceError::ParticipantNotFound(participant_id.to_string()))?;
+        let event = self.add_participant(participant_id, role)?;
+        Ok((request, event))
+    }
+
+    /// Deny a waiting participant
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host or the participant is not waiting
+    pub fn deny(
+        &mut self,
+        actor_id: &str,
+        participant_id: &str,
+    ) -> Result<ConferenceEvent, ConferenceError> {
+        self.require_host(actor_id)?;
+        self.lobby
+            .remove(participant_id)
+            .ok_or_else(|| ConferenceError::ParticipantNotFound(participant_id.to_string()))?;
+        Ok(ConferenceEvent::JoinDenied {
+            conference_id: self.id,
+            participant_id: participant_id.to_string(),
+        })
+    }
+
+    /// Mute or unmute a participant
+    ///
+    /// Participants may always mute themselves; unmuting yourself requires
+    /// a publishing role, and acting on others requires a host.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if either participant is unknown or the actor lacks
+    /// permission
+    pub fn mute_participant(
+        &mut self,
+        actor_id: &str,
+        participant_id: &str,
+        muted: bool,
+    ) -> Result<ConferenceEvent, ConferenceError> {
+        self.apply(actor_id, participant_id, ParticipantAction::Mute { muted })
+    }
+
+    /// Remove a participant
+    ///
+    /// # Errors
+    ///
+    /// Returns error if either participant is unknown or the actor is not a host
+    pub fn remove_participant(
+        &mut self,
+        actor_id: &str,
+        participant_id: &str,
+    ) -> Result<ConferenceEvent, ConferenceError> {
+        self.apply(actor_id, participant_id, ParticipantAction::Remove)
+    }
+
+    /// Change a participant's role
+    ///
+    /// # Errors
+    ///
+    /// Returns error if either participant is unknown, the actor is not a
+    /// host, or the change would leave the conference without a host
+    pub fn set_participant_role(
+        &mut self,
+        actor_id: &str,
+        participant_id: &str,
+        role: ParticipantRole,
+    ) -> Result<ConferenceEvent, ConferenceError> {
+        self.apply(
+            actor_id,
+            participant_id,
+            ParticipantAction::SetRole { role },
+        )
+    }
+
+    /// Apply a moderation action on behalf of `actor_id`
+    ///
+    /// Used both for local actions and for actions received over signaling,
+    /// so permissions are enforced identically on every participant.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if either participant is unknown or the actor lacks
+    /// permission
+    pub fn apply(
+        &mut self,
+        actor_id: &str,
+        participant_id: &str,
+        action: ParticipantAction,
+    ) -> Result<ConferenceEvent, ConferenceError> {
+        let actor = self
+            .participants
+            .get(actor_id)
+            .ok_or_else(|| ConferenceError::ParticipantNotFound(actor_id.to_string()))?;
+        let target = self
+            .participants
+            .get(participant_id)
+            .ok_or_else(|| ConferenceError::ParticipantNotFound(participant_id.to_string()))?;
+
+        let is_self = actor_id == participant_id;
+        let allowed = match action {
+            ParticipantAction::Mute { muted: true } => is_self || actor.role.can_moderate(),
+            ParticipantAction::Mute { muted: false } => {
+                actor.role.can_moderate() || (is_self && actor.role.can_publish())
+            }
+            ParticipantAction::Remove | ParticipantAction::SetRole { .. } => {
+                actor.role.can_moderate()
+            }
+        };
+        if !allowed {
+            return Err(ConferenceError::PermissionDenied(format!(
+                "{} cannot {:?} {}",
+                actor_id, action, participant_id
+            )));
+        }
+
+        let removes_host = target.role == ParticipantRole::Host
+            && !matches!(
+                action,
+                ParticipantAction::Mute { .. }
+                    | ParticipantAction::SetRole {
+                        role: ParticipantRole::Host
+                    }
+            );
+        if removes_host && self.host_count() == 1 {
+            return Err(ConferenceError::PermissionDenied(
+                "conference must keep at least one host".to_string(),
+            ));
+        }
+
+        let conference_id = self.id;
+        let participant_id = participant_id.to_string();
+        let event = match action {
+            ParticipantAction::Mute { muted } => {
+                if let Some(target) = self.participants.get_mut(&participant_id) {
+                    target.audio_muted = muted;
+                }
+                ConferenceEvent::ParticipantMuted {
+                    conference_id,
+                    participant_id,
+                    muted,
+                }
+            }
+            ParticipantAction::Remove => {
+                self.participants.remove(&participant_id);
+                self.speakers.remove(&participant_id);
+                for group in &mut self.breakouts {
+                    group.members.retain(|id| *id != participant_id);
+                }
+                ConferenceEvent::ParticipantRemoved {
+                    conference_id,
+                    participant_id,
+                }
+            }
+            ParticipantAction::SetRole { role } => {
+                if let Some(target) = self.participants.get_mut(&participant_id) {
+                    target.role = role;
+                    // Demoted listeners stop publishing immediately
+                    if !role.can_publish() {
+                        target.audio_muted = true;
+                    }
+                }
+                ConferenceEvent::RoleChanged {
+                    conference_id,
+                    participant_id,
+                    role,
+                }
+            }
+        };
+        tracing::info!("Conference {}: {:?}", conference_id, event);
+        Ok(event)
+    }
+
+    /// Whether the forwarding layer may relay `media` from a participant
+    #[must_use]
+    pub fn can_forward(&self, participant_id: &str, media: &MediaType) -> bool {
+        self.participants
+            .get(participant_id)
+            .is_some_and(|p| match media {
+                MediaType::Audio => p.role.can_publish() && !p.audio_muted,
+                MediaType::Video | MediaType::ScreenShare => p.role.can_publish(),
+                MediaType::DataChannel => true,
+            })
+    }
+
+    /// Feed a participant's RFC 6464 audio level
+    ///
+    /// Levels from participants whose audio is not forwarded are ignored.
+    /// Returns an event when the dominant speaker changes.
+    pub fn observe_audio_level(
+        &mut self,
+        participant_id: &str,
+        level: u8,
+        now_ms: u64,
+    ) -> Option<ConferenceEvent> {
+        if !self.can_forward(participant_id, &MediaType::Audio) {
+            return None;
+        }
+        self.speakers
+            .observe(participant_id, level, now_ms)
+            .map(|participant_id| ConferenceEvent::ActiveSpeakerChanged {
+                conference_id: self.id,
+                participant_id,
+            })
+    }
+
+    /// Current dominant speaker
+    #[must_use]
+    pub fn active_speaker(&self) -> Option<&str> {
+        self.speakers.dominant()
+    }
+
+    /// Simulcast layers the SFU should forward to `receiver_id`
+    ///
+    /// The dominant speaker is sent at full resolution and a bounded number
+    /// of recent speakers as thumbnails; everyone else is not forwarded.
+    #[must_use]
+    pub fn forwarding_layers(&self, receiver_id: &str) -> Vec<(String, SimulcastLayer)> {
+        let mut senders: Vec<String> = self
+            .speakers
+            .recent_speakers()
+            .into_iter()
+            .filter(|id| self.forwards_video_to(id, receiver_id))
+            .collect();
+        // Participants who have not spoken yet come last, in a stable order
+        let mut silent: Vec<String> = self
+            .participants
+            .keys()
+            .filter(|id| !senders.contains(id) && self.forwards_video_to(id, receiver_id))
+            .cloned()
+            .collect();
+        silent.sort();
+        senders.extend(silent);
+
+        select_layers(
+            receiver_id,
+            self.speakers.dominant(),
+            &senders,
+            self.speakers.config().max_thumbnails,
+        )
+    }
+
+    /// Split the conference into breakout groups, or merge back with none
+    ///
+    /// Replaces any existing groups. Participants not assigned to a group
+    /// stay in the main room.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host, a member is unknown, or a
+    /// participant is assigned to more than one group
+    pub fn set_breakouts(
+        &mut self,
+        actor_id: &str,
+        groups: Vec<BreakoutGroup>,
+    ) -> Result<ConferenceEvent, ConferenceError> {
+        self.require_host(actor_id)?;
+
+        let mut assigned = std::collections::HashSet::new();
+        for member in groups.iter().flat_map(|group| &group.members) {
+            if !self.participants.contains_key(member) {
+                return Err(ConferenceError::ParticipantNotFound(member.clone()));
+            }
+            if !assigned.insert(member.as_str()) {
+                return Err(ConferenceError::PermissionDenied(format!(
+                    "{} is assigned to more than one breakout",
+                    member
+                )));
+            }
+        }
+
+        self.breakouts = groups.clone();
+        tracing::info!(
+            "Conference {}: {} breakout group(s)",
+            self.id,
+            self.breakouts.len()
+        );
+        Ok(ConferenceEvent::BreakoutsChanged {
+            conference_id: self.id,
+            groups,
+        })
+    }
+
+    /// Close all breakout groups and return everyone to the main room
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host
+    pub fn merge_breakouts(&mut self, actor_id: &str) -> Result<ConferenceEvent, ConferenceError> {
+        self.set_breakouts(actor_id, Vec::new())
+    }
+
+    /// Current breakout groups
+    #[must_use]
+    pub fn breakouts(&self) -> &[BreakoutGroup] {
+        &self.breakouts
+    }
+
+    /// Breakout group a participant is in, or `None` for the main room
+    #[must_use]
+    pub fn breakout_of(&self, participant_id: &str) -> Option<&BreakoutGroup> {
+        self.breakouts
+            .iter()
+            .find(|group| group.members.iter().any(|id| id == participant_id))
+    }
+
+    /// Whether two participants are in the same room
+    #[must_use]
+    pub fn same_room(&self, a: &str, b: &str) -> bool {
+        self.breakout_of(a).map(|group| &group.name) == self.breakout_of(b).map(|group| &group.name)
+    }
+
+    fn forwards_video_to(&self, sender_id: &str, receiver_id: &str) -> bool {
+        self.can_forward(sender_id, &MediaType::Video) && self.same_room(sender_id, receiver_id)
+    }
+
+    /// Participants other than `participant_id`, for fanning out signaling
+    #[must_use]
+    pub fn other_participants(&self, participant_id: &str) -> Vec<String> {
+        self.participants
+            .keys()
+            .filter(|id| id.as_str() != participant_id)
+            .cloned()
+            .collect()
+    }
+
+    /// Hosts of the conference, who receive join requests
+    #[must_use]
+    pub fn hosts(&self) -> Vec<String> {
+        self.participants
+            .values()
+            .filter(|p| p.role == ParticipantRole::Host)
+            .map(|p| p.id.clone())
+            .collect()
+    }
+
+    fn require_host(&self, actor_id: &str) -> Result<(), ConferenceError> {
+        match self.participants.get(actor_id) {
+            Some(actor) if actor.role.can_moderate() => Ok(()),
+            Some(_) => Err(ConferenceError::PermissionDenied(format!(
+                "{} is not a host",
+                actor_id
+            ))),
+            None => Err(ConferenceError::ParticipantNotFound(actor_id.to_string())),
+        }
+    }
+
+    fn host_count(&self) -> usize {
+        self.participants
+            .values()
+            .filter(|p| p.role == ParticipantRole::Host)
+            .count()
+    }
+}
+
+/// Build the signaling message announcing a moderation action
+#[must_use]
+pub fn control_message(
+    conference_id: ConferenceId,
+    participant_id: &str,
+    action: ParticipantAction,
+) -> SignalingMessage {
+    SignalingMessage::ParticipantControl {
+        session_id: conference_id.to_string(),
+        participant_id: participant_id.to_string(),
+        action,
+    }
+}
+
+/// Build the message a host sends to answer a join request
+#[must_use]
+pub fn join_decision_message(conference_id: ConferenceId, admitted: bool) -> SignalingMessage {
+    SignalingMessage::JoinDecision {
+        session_id: conference_id.to_string(),
+        admitted,
+    }
+}
+
+/// Build the message announcing the current breakout groups
+#[must_use]
+pub fn breakout_message(
+    conference_id: ConferenceId,
+    groups: Vec<BreakoutGroup>,
+) -> SignalingMessage {
+    SignalingMessage::BreakoutUpdate {
+        session_id: conference_id.to_string(),
+        groups,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conference() -> Conference {
+        let mut conference = Conference::new(ConferenceId::new(), "host");
+        conference
+            .add_participant("alice", ParticipantRole::Speaker)
+            .unwrap();
+        conference
+            .add_participant("bob", ParticipantRole::Listener)
+            .unwrap();
+        conference
+    }
+
+    #[test]
+    fn test_host_can_mute_and_speaker_cannot() {
+        let mut conference = conference();
+
+        assert!(matches!(
+            conference.mute_participant("alice", "host", true),
+            Err(ConferenceError::PermissionDenied(_))
+        ));
+
+        let event = conference.mute_participant("host", "alice", true).unwrap();
+        assert!(matches!(
+            event,
+            ConferenceEvent::ParticipantMuted { muted: true, .. }
+        ));
+        assert!(!conference.can_forward("alice", &MediaType::Audio));
+        assert!(conference.can_forward("alice", &MediaType::Video));
+    }
+
+    #[test]
+    fn test_self_mute_and_unmute() {
+        let mut conference = conference();
+        conference.mute_participant("alice", "alice", true).unwrap();
+        conference
+            .mute_participant("alice", "alice", false)
+            .unwrap();
+        assert!(conference.can_forward("alice", &MediaType::Audio));
+
+        // Listeners cannot unmute themselves into speaking
+        assert!(conference.mute_participant("bob", "bob", false).is_err());
+    }
+
+    #[test]
+    fn test_listener_media_not_forwarded() {
+        let conference = conference();
+        assert!(!conference.can_forward("bob", &MediaType::Audio));
+        assert!(!conference.can_forward("bob", &MediaType::Video));
+        assert!(conference.can_forward("bob", &MediaType::DataChannel));
+        assert!(!conference.can_forward("mallory", &MediaType::DataChannel));
+    }
+
+    #[test]
+    fn test_set_role_and_remove() {
+        let mut conference = conference();
+
+        conference
+            .set_participant_role("host", "bob", ParticipantRole::Speaker)
+            .unwrap();
+        assert!(conference.can_forward("bob", &MediaType::Video));
+
+        conference
+            .set_participant_role("host", "alice", ParticipantRole::Listener)
+            .unwrap();
+        assert!(conference.participant("alice").unwrap().audio_muted);
+
+        conference.remove_participant("host", "bob").unwrap();
+        assert!(conference.participant("bob").is_none());
+        assert_eq!(conference.other_participants("host"), vec!["alice"]);
+    }
+
+    #[test]
+    fn test_last_host_cannot_be_demoted() {
+        let mut conference = conference();
+        assert!(conference
+            .set_participant_role("host", "host", ParticipantRole::Speaker)
+            .is_err());
+
+        conference
+            .set_participant_role("host", "alice", ParticipantRole::Host)
+            .unwrap();
+        conference
+            .set_participant_role("alice", "host", ParticipantRole::Speaker)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_waiting_room_parks_offer_until_admitted() {
+        let mut conference = conference().with_waiting_room(true);
+
+        let outcome = conference
+            .request_join("carol", "offer-sdp".to_string())
+            .unwrap();
+        assert!(matches!(
+            outcome,
+            JoinOutcome::Waiting(ConferenceEvent::JoinRequested { .. })
+        ));
+        assert!(conference.participant("carol").is_none());
+        assert_eq!(conference.lobby().count(), 1);
+
+        assert!(matches!(
+            conference.admit("alice", "carol", ParticipantRole::Speaker),
+            Err(ConferenceError::PermissionDenied(_))
+        ));
+
+        let (request, event) = conference
+            .admit("host", "carol", ParticipantRole::Listener)
+            .unwrap();
+        assert_eq!(request.sdp, "offer-sdp");
+        assert!(matches!(event, ConferenceEvent::ParticipantJoined { .. }));
+        assert_eq!(
+            conference.participant("carol").unwrap().role,
+            ParticipantRole::Listener
+        );
+        assert_eq!(conference.lobby().count(), 0);
+    }
+
+    #[test]
+    fn test_waiting_room_deny() {
+        let mut conference = conference().with_waiting_room(true);
+        conference.request_join("carol", String::new()).unwrap();
+
+        let event = conference.deny("host", "carol").unwrap();
+        assert!(matches!(event, ConferenceEvent::JoinDenied { .. }));
+        assert!(conference.participant("carol").is_none());
+        assert!(conference.deny("host", "carol").is_err());
+    }
+
+    #[test]
+    fn test_join_without_waiting_room() {
+        let mut conference = conference();
+        let outcome = conference.request_join("carol", String::new()).unwrap();
+        assert!(matches!(outcome, JoinOutcome::Joined(_)));
+        assert!(conference.can_forward("carol", &MediaType::Audio));
+        assert!(conference.request_join("carol", String::new()).is_err());
+    }
+
+    #[test]
+    fn test_active_speaker_drives_forwarding() {
+        let mut conference = conference();
+        conference
+            .add_participant("carol", ParticipantRole::Speaker)
+            .unwrap();
+
+        let mut changed = None;
+        for t in (0..500).step_by(20) {
+            changed = conference.observe_audio_level("carol", 10, t).or(changed);
+            // Listeners never become the active speaker
+            assert!(conference.observe_audio_level("bob", 0, t).is_none());
+        }
+        assert!(matches!(
+            changed,
+            Some(ConferenceEvent::ActiveSpeakerChanged { ref participant_id, .. }) if participant_id == "carol"
+        ));
+
+        let layers = conference.forwarding_layers("bob");
+        assert_eq!(layers[0], ("carol".to_string(), SimulcastLayer::High));
+        assert_eq!(layers.len(), 3);
+        assert!(layers.iter().all(|(id, _)| id != "bob"));
+    }
+
+    #[test]
+    fn test_breakouts_split_and_merge() {
+        let mut conference = conference();
+        conference
+            .add_participant("carol", ParticipantRole::Speaker)
+            .unwrap();
+        conference
+            .add_participant("dave", ParticipantRole::Speaker)
+            .unwrap();
+
+        let groups = vec![
+            BreakoutGroup::new("room-1", vec!["alice".to_string(), "bob".to_string()]),
+            BreakoutGroup::new("room-2", vec!["carol".to_string(), "dave".to_string()]),
+        ];
+        assert!(conference.set_breakouts("alice", groups.clone()).is_err());
+        conference.set_breakouts("host", groups).unwrap();
+
+        assert_eq!(conference.breakout_of("bob").unwrap().name, "room-1");
+        assert!(conference.breakout_of("host").is_none());
+        assert!(!conference.same_room("alice", "carol"));
+
+        let layers = conference.forwarding_layers("carol");
+        assert_eq!(layers, vec![("dave".to_string(), SimulcastLayer::High)]);
+
+        let event = conference.merge_breakouts("host").unwrap();
+        assert!(
+            matches!(event, ConferenceEvent::BreakoutsChanged { groups, .. } if groups.is_empty())
+        );
+        assert!(conference.same_room("alice", "carol"));
+        assert_eq!(conference.forwarding_layers("carol").len(), 3);
+    }
+
+    #[test]
+    fn test_breakouts_reject_double_assignment() {
+        let mut conference = conference();
+        let groups = vec![
+            BreakoutGroup::new("a", vec!["alice".to_string()]),
+            BreakoutGroup::new("b", vec!["alice".to_string()]),
+        ];
+        assert!(matches!(
+            conference.set_breakouts("host", groups),
+            Err(ConferenceError::PermissionDenied(_))
+        ));
+        assert!(matches!(
+            conference.set_breakouts(
+                "host",
+                vec![BreakoutGroup::new("a", vec!["zed".to_string()])]
+            ),
+            Err(ConferenceError::ParticipantNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_control_message_serialization() {
+        let id = ConferenceId::new();
+        let message = control_message(
+            id,
+            "alice",
+            ParticipantAction::SetRole {
+                role: ParticipantRole::Listener,
+            },
+        );
+        let json = serde_json::to_string(&message).unwrap();
+        assert!(json.contains("\"action\":\"set_role\""));
+        assert!(json.contains("\"role\":\"listener\""));
+
+        let parsed: SignalingMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(parsed.session_id(), id.to_string());
+    }
+}
@@ -0,0 +1,243 @@
+//! Send-side congestion detection
+//!
+//! Quality alerts fire on stats that are already bad. Congestion shows up
+//! earlier on the sending side: the pacer queue grows as the link stops
+//! keeping up, and loss starts to climb. A [`CongestionMonitor`] watches
+//! both per call and reports when the worst of them crosses into a new
+//! [`CongestionSeverity`], so applications can warn that the connection is
+//! unstable before quality visibly collapses.
+//!
+//! Severity rises as soon as a threshold is crossed but only falls after
+//! [`CongestionConfig::clear_after`] calm samples in a row, so a link
+//! hovering around a threshold does not make a banner flicker.
+
+use crate::types::CallId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How congested a call's uplink is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum CongestionSeverity {
+    /// Below every threshold
+    Clear,
+    /// Queues or loss are building; quality may soon drop
+    Warning,
+    /// The link cannot carry the current send rate
+    Critical,
+}
+
+/// Thresholds at which congestion is reported
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CongestionConfig {
+    /// Pacer queue delay, in milliseconds, that raises a warning
+    pub queue_warning_ms: u32,
+    /// Pacer queue delay, in milliseconds, that is critical
+    pub queue_critical_ms: u32,
+    /// Loss percentage that raises a warning
+    pub loss_warning_percent: f32,
+    /// Loss percentage that is critical
+    pub loss_critical_percent: f32,
+    /// Calm samples in a row before severity is lowered
+    pub clear_after: u32,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            queue_warning_ms: 150,
+            queue_critical_ms: 500,
+            loss_warning_percent: 3.0,
+            loss_critical_percent: 10.0,
+            clear_after: 3,
+        }
+    }
+}
+
+impl CongestionConfig {
+    fn queue_severity(&self, queue_delay_ms: u32) -> CongestionSeverity {
+        if queue_delay_ms >= self.queue_critical_ms {
+            CongestionSeverity::Critical
+        } else if queue_delay_ms >= self.queue_warning_ms {
+            CongestionSeverity::Warning
+        } else {
+            CongestionSeverity::Clear
+        }
+    }
+
+    pub(crate) fn loss_severity(&self, loss_percent: f32) -> CongestionSeverity {
+        if loss_percent >= self.loss_critical_percent {
+            CongestionSeverity::Critical
+        } else if loss_percent >= self.loss_warning_percent {
+            CongestionSeverity::Warning
+        } else {
+            CongestionSeverity::Clear
+        }
+    }
+}
+
+/// Time to drain a pacer queue of `queued_bytes` at `pacing_rate_bps`
+#[must_use]
+pub fn queue_delay_ms(queued_bytes: usize, pacing_rate_bps: u64) -> u32 {
+    if queued_bytes == 0 {
+        return 0;
+    }
+    if pacing_rate_bps == 0 {
+        return u32::MAX;
+    }
+    let ms = queued_bytes as u128 * 8 * 1000 / u128::from(pacing_rate_bps);
+    u32::try_from(ms).unwrap_or(u32::MAX)
+}
+
+#[derive(Debug, Default)]
+struct CallCongestion {
+    queue: Option<CongestionSeverity>,
+    loss: Option<CongestionSeverity>,
+    reported: Option<CongestionSeverity>,
+    calm: u32,
+}
+
+/// Tracks congestion per call and reports severity changes
+#[derive(Debug, Default)]
+pub struct CongestionMonitor {
+    config: CongestionConfig,
+    calls: HashMap<CallId, CallCongestion>,
+}
+
+impl CongestionMonitor {
+    /// Create a monitor with the given thresholds
+    #[must_use]
+    pub fn new(config: CongestionConfig) -> Self {
+        Self {
+            config,
+            calls: HashMap::new(),
+        }
+    }
+
+    /// Get the thresholds in use
+    #[must_use]
+    pub fn config(&self) -> &CongestionConfig {
+        &self.config
+    }
+
+    /// Judge samples against new thresholds from now on
+    ///
+    /// Reported severities are kept until the next samples move them.
+    pub fn set_config(&mut self, config: CongestionConfig) {
+        self.config = config;
+    }
+
+    /// Feed the pacer queue delay of a call, returning the new severity if
+    /// it changed
+    pub fn observe_queue(
+        &mut self,
+        call_id: CallId,
+        queue_delay_ms: u32,
+    ) -> Option<CongestionSeverity> {
+        let severity = self.config.queue_severity(queue_delay_ms);
+        let call = self.calls.entry(call_id).or_default();
+        call.queue = Some(severity);
+        Self::update(call, self.config.clear_after)
+    }
+
+    /// Feed the measured loss of a call, returning the new severity if it
+    /// changed
+    pub fn observe_loss(
+        &mut self,
+        call_id: CallId,
+        loss_percent: f32,
+    ) -> Option<CongestionSeverity> {
+        let severity = self.config.loss_severity(loss_percent);
+        let call = self.calls.entry(call_id).or_default();
+        call.loss = Some(severity);
+        Self::update(call, self.config.clear_after)
+    }
+
+    /// Last severity reported for a call
+    #[must_use]
+    pub fn severity(&self, call_id: CallId) -> CongestionSeverity {
+        self.calls
+            .get(&call_id)
+            .and_then(|call| call.reported)
+            .unwrap_or(CongestionSeverity::Clear)
+    }
+
+    /// Forget state for a call that has ended
+    pub fn remove_call(&mut self, call_id: CallId) {
+        self.calls.remove(&call_id);
+    }
+
+    fn update(call: &mut CallCongestion, clear_after: u32) -> Option<CongestionSeverity> {
+        let current = call.queue.max(call.loss)?;
+        let reported = call.reported.unwrap_or(CongestionSeverity::Clear);
+        if current >= reported {
+            call.calm = 0;
+            if current == reported {
+                return None;
+            }
+        } else {
+            call.calm += 1;
+            if call.calm < clear_after {
+                return None;
+            }
+            call.calm = 0;
+        }
+        call.reported = Some(current);
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_delay_from_pacer_state() {
+        assert_eq!(queue_delay_ms(0, 0), 0);
+        assert_eq!(queue_delay_ms(12_500, 1_000_000), 100);
+        assert_eq!(queue_delay_ms(1, 0), u32::MAX);
+    }
+
+    #[test]
+    fn test_worst_signal_raises_severity_immediately() {
+        let call = CallId::new();
+        let mut monitor = CongestionMonitor::default();
+
+        assert_eq!(monitor.observe_queue(call, 20), None);
+        assert_eq!(
+            monitor.observe_loss(call, 4.0),
+            Some(CongestionSeverity::Warning)
+        );
+        assert_eq!(monitor.observe_loss(call, 5.0), None);
+        assert_eq!(
+            monitor.observe_queue(call, 800),
+            Some(CongestionSeverity::Critical)
+        );
+        assert_eq!(monitor.severity(call), CongestionSeverity::Critical);
+        assert_eq!(monitor.severity(CallId::new()), CongestionSeverity::Clear);
+    }
+
+    #[test]
+    fn test_severity_clears_after_calm_samples() {
+        let call = CallId::new();
+        let mut monitor = CongestionMonitor::new(CongestionConfig {
+            clear_after: 2,
+            ..CongestionConfig::default()
+        });
+
+        assert_eq!(
+            monitor.observe_queue(call, 200),
+            Some(CongestionSeverity::Warning)
+        );
+        assert_eq!(monitor.observe_queue(call, 10), None);
+        // A relapse restarts the count
+        assert_eq!(monitor.observe_queue(call, 200), None);
+        assert_eq!(monitor.observe_queue(call, 10), None);
+        assert_eq!(
+            monitor.observe_queue(call, 10),
+            Some(CongestionSeverity::Clear)
+        );
+
+        monitor.remove_call(call);
+        assert_eq!(monitor.observe_queue(call, 10), None);
+    }
+}
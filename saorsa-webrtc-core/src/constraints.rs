@@ -0,0 +1,465 @@
+//! Capture constraints
+//!
+//! Modelled on getUserMedia: each numeric property is a [`ConstrainRange`]
+//! whose `min` and `max` are required and whose `ideal` is a preference.
+//! A camera mode outside any required bound is ruled out; the rest are
+//! ranked by fitness distance, the sum over constrained properties of
+//! `|actual - ideal| / max(actual, ideal)`, and the closest one wins.
+//! Where no ideal is given the browsers' default of 640x480 at 30 fps
+//! stands in, so an unconstrained request does not open a camera at
+//! 4K.
+//!
+//! A facing mode is a preference too: a camera facing another way, or
+//! not saying, adds 1 to the distance. A device ID is required, since it
+//! comes from the device listing.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::media::VideoDevice;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Width used when no ideal width is given
+pub const DEFAULT_IDEAL_WIDTH: u32 = 640;
+/// Height used when no ideal height is given
+pub const DEFAULT_IDEAL_HEIGHT: u32 = 480;
+/// Frame rate used when no ideal frame rate is given
+pub const DEFAULT_IDEAL_FRAME_RATE: u32 = 30;
+
+/// No device or mode satisfies the required constraints
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConstraintError {
+    /// Constraint that ruled out the last candidates, named as in
+    /// getUserMedia, e.g. `"width"` or `"deviceId"`
+    #[error("Overconstrained: no capture mode satisfies {0}")]
+    Overconstrained(&'static str),
+}
+
+impl CodedError for ConstraintError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::Overconstrained
+    }
+}
+
+/// Required bounds and preferred value of a numeric property
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConstrainRange {
+    /// Smallest acceptable value
+    pub min: Option<u32>,
+    /// Largest acceptable value
+    pub max: Option<u32>,
+    /// Value to get as close to as possible
+    pub ideal: Option<u32>,
+}
+
+impl ConstrainRange {
+    /// Prefer `value` without requiring it
+    #[must_use]
+    pub fn ideal(value: u32) -> Self {
+        Self {
+            ideal: Some(value),
+            ..Self::default()
+        }
+    }
+
+    /// Require exactly `value`
+    #[must_use]
+    pub fn exact(value: u32) -> Self {
+        Self {
+            min: Some(value),
+            max: Some(value),
+            ideal: Some(value),
+        }
+    }
+
+    /// Require a value from `min` to `max` inclusive
+    #[must_use]
+    pub fn between(min: u32, max: u32) -> Self {
+        Self {
+            min: Some(min),
+            max: Some(max),
+            ideal: None,
+        }
+    }
+
+    /// Whether `value` is within the required bounds
+    #[must_use]
+    pub fn admits(&self, value: u32) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+
+    /// Distance from `value` to the ideal, or to `default` without one,
+    /// from 0 for a match to 1
+    fn distance(&self, value: u32, default: u32) -> f64 {
+        let ideal = self.ideal.unwrap_or(default);
+        if value == ideal {
+            return 0.0;
+        }
+        f64::from(value.abs_diff(ideal)) / f64::from(value.max(ideal))
+    }
+}
+
+/// Direction a camera faces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FacingMode {
+    /// Towards the user, e.g. a laptop or selfie camera
+    User,
+    /// Away from the user, e.g. a phone's rear camera
+    Environment,
+    /// To the user's left
+    Left,
+    /// To the user's right
+    Right,
+}
+
+/// Resolution and frame rate a camera can capture at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CameraMode {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Frames per second
+    pub frame_rate: u32,
+}
+
+impl CameraMode {
+    /// Modes nearly every webcam offers, assumed for cameras whose modes
+    /// could not be listed
+    pub const COMMON: [CameraMode; 8] = [
+        CameraMode::new(320, 240, 30),
+        CameraMode::new(640, 480, 15),
+        CameraMode::new(640, 480, 30),
+        CameraMode::new(1280, 720, 15),
+        CameraMode::new(1280, 720, 30),
+        CameraMode::new(1920, 1080, 15),
+        CameraMode::new(1920, 1080, 30),
+        CameraMode::new(1920, 1080, 60),
+    ];
+
+    /// Mode of `width`x`height` at `frame_rate` fps
+    #[must_use]
+    pub const fn new(width: u32, height: u32, frame_rate: u32) -> Self {
+        Self {
+            width,
+            height,
+            frame_rate,
+        }
+    }
+}
+
+/// Constraints on the camera a video track captures from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VideoConstraints {
+    /// Camera to use, from the device listing
+    pub device_id: Option<String>,
+    /// Direction the camera should face
+    pub facing_mode: Option<FacingMode>,
+    /// Frame width in pixels
+    pub width: ConstrainRange,
+    /// Frame height in pixels
+    pub height: ConstrainRange,
+    /// Frames per second
+    pub frame_rate: ConstrainRange,
+}
+
+/// Camera and mode picked for a set of [`VideoConstraints`]
+#[derive(Debug, Clone)]
+pub struct CameraSelection {
+    /// Camera to open
+    pub device: VideoDevice,
+    /// Mode to open it in
+    pub mode: CameraMode,
+}
+
+impl VideoConstraints {
+    /// Prefer `width`x`height` at `frame_rate` fps
+    #[must_use]
+    pub fn ideal(width: u32, height: u32, frame_rate: u32) -> Self {
+        Self {
+            width: ConstrainRange::ideal(width),
+            height: ConstrainRange::ideal(height),
+            frame_rate: ConstrainRange::ideal(frame_rate),
+            ..Self::default()
+        }
+    }
+
+    /// Prefer cameras facing `facing_mode`
+    #[must_use]
+    pub fn with_facing_mode(mut self, facing_mode: FacingMode) -> Self {
+        self.facing_mode = Some(facing_mode);
+        self
+    }
+
+    /// Require the camera `device_id`
+    #[must_use]
+    pub fn with_device(mut self, device_id: impl Into<String>) -> Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    /// Whether `mode` is within every required bound
+    #[must_use]
+    pub fn admits(&self, mode: &CameraMode) -> bool {
+        self.width.admits(mode.width)
+            && self.height.admits(mode.height)
+            && self.frame_rate.admits(mode.frame_rate)
+    }
+
+    /// Fitness distance of `mode`, lower being better
+    #[must_use]
+    pub fn fitness_distance(&self, mode: &CameraMode) -> f64 {
+        self.width.distance(mode.width, DEFAULT_IDEAL_WIDTH)
+            + self.height.distance(mode.height, DEFAULT_IDEAL_HEIGHT)
+            + self
+                .frame_rate
+                .distance(mode.frame_rate, DEFAULT_IDEAL_FRAME_RATE)
+    }
+
+    /// Best of `modes`, the first on a tie
+    ///
+    /// # Errors
+    ///
+    /// Returns error naming the constraint that ruled out the last modes
+    pub fn select_mode(&self, modes: &[CameraMode]) -> Result<CameraMode, ConstraintError> {
+        self.best_mode(modes).map(|(mode, _)| mode)
+    }
+
+    /// Best camera and mode among `devices`
+    ///
+    /// Cameras that list no modes are assumed to offer
+    /// [`CameraMode::COMMON`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error naming the constraint that ruled out the last
+    /// candidates
+    pub fn select_camera(
+        &self,
+        devices: &[VideoDevice],
+    ) -> Result<CameraSelection, ConstraintError> {
+        let candidates: Vec<&VideoDevice> = devices
+            .iter()
+            .filter(|device| self.device_id.as_ref().is_none_or(|id| *id == device.id))
+            .collect();
+        if candidates.is_empty() {
+            return Err(ConstraintError::Overconstrained("deviceId"));
+        }
+
+        let mut best: Option<(CameraSelection, f64)> = None;
+        let mut error = ConstraintError::Overconstrained("width");
+        for device in candidates {
+            let modes = if device.modes.is_empty() {
+                &CameraMode::COMMON[..]
+            } else {
+                &device.modes[..]
+            };
+            let (mode, mut distance) = match self.best_mode(modes) {
+                Ok(best) => best,
+                Err(e) => {
+                    error = e;
+                    continue;
+                }
+            };
+            if self
+                .facing_mode
+                .is_some_and(|facing| device.facing_mode != Some(facing))
+            {
+                distance += 1.0;
+            }
+            if best.as_ref().is_none_or(|(_, best)| distance < *best) {
+                let selection = CameraSelection {
+                    device: device.clone(),
+                    mode,
+                };
+                best = Some((selection, distance));
+            }
+        }
+        best.map(|(selection, _)| selection).ok_or(error)
+    }
+
+    fn best_mode(&self, modes: &[CameraMode]) -> Result<(CameraMode, f64), ConstraintError> {
+        let mut best: Option<(CameraMode, f64)> = None;
+        for mode in modes.iter().filter(|mode| self.admits(mode)) {
+            let distance = self.fitness_distance(mode);
+            if best.is_none_or(|(_, best)| distance < best) {
+                best = Some((*mode, distance));
+            }
+        }
+        best.ok_or_else(|| ConstraintError::Overconstrained(self.unmet(modes)))
+    }
+
+    /// First property, in width, height, frame rate order, that leaves
+    /// none of `modes` once checked along with those before it
+    fn unmet(&self, modes: &[CameraMode]) -> &'static str {
+        if !modes.iter().any(|m| self.width.admits(m.width)) {
+            "width"
+        } else if !modes
+            .iter()
+            .any(|m| self.width.admits(m.width) && self.height.admits(m.height))
+        {
+            "height"
+        } else {
+            "frameRate"
+        }
+    }
+}
+
+/// Constraints on the microphone an audio track captures from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AudioConstraints {
+    /// Microphone to use, from the device listing
+    pub device_id: Option<String>,
+    /// Samples per second
+    pub sample_rate: ConstrainRange,
+    /// Channels captured
+    pub channel_count: ConstrainRange,
+}
+
+impl AudioConstraints {
+    /// Require the microphone `device_id`
+    #[must_use]
+    pub fn with_device(mut self, device_id: impl Into<String>) -> Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    /// Check capture at `sample_rate` Hz with `channel_count` channels
+    /// against the required bounds
+    ///
+    /// # Errors
+    ///
+    /// Returns error naming the first bound not met
+    pub fn check(&self, sample_rate: u32, channel_count: u32) -> Result<(), ConstraintError> {
+        if !self.sample_rate.admits(sample_rate) {
+            return Err(ConstraintError::Overconstrained("sampleRate"));
+        }
+        if !self.channel_count.admits(channel_count) {
+            return Err(ConstraintError::Overconstrained("channelCount"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(id: &str, facing_mode: Option<FacingMode>, modes: &[CameraMode]) -> VideoDevice {
+        VideoDevice {
+            id: id.to_string(),
+            name: id.to_string(),
+            facing_mode,
+            modes: modes.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_select_mode_by_fitness_distance() {
+        let modes = [
+            CameraMode::new(1920, 1080, 30),
+            CameraMode::new(1280, 720, 60),
+            CameraMode::new(1280, 720, 30),
+            CameraMode::new(640, 480, 30),
+        ];
+        let unconstrained = VideoConstraints::default();
+        assert_eq!(
+            unconstrained.select_mode(&modes),
+            Ok(CameraMode::new(640, 480, 30))
+        );
+
+        let hd = VideoConstraints::ideal(1280, 720, 30);
+        assert_eq!(hd.select_mode(&modes), Ok(CameraMode::new(1280, 720, 30)));
+
+        // Required bounds win over the ideal
+        let smooth = VideoConstraints {
+            frame_rate: ConstrainRange {
+                min: Some(50),
+                ..ConstrainRange::default()
+            },
+            ..hd.clone()
+        };
+        assert_eq!(
+            smooth.select_mode(&modes),
+            Ok(CameraMode::new(1280, 720, 60))
+        );
+
+        let too_big = VideoConstraints {
+            width: ConstrainRange::exact(3840),
+            ..VideoConstraints::default()
+        };
+        assert_eq!(
+            too_big.select_mode(&modes),
+            Err(ConstraintError::Overconstrained("width"))
+        );
+        let too_fast = VideoConstraints {
+            height: ConstrainRange::between(700, 1100),
+            frame_rate: ConstrainRange::exact(120),
+            ..VideoConstraints::default()
+        };
+        assert_eq!(
+            too_fast.select_mode(&modes),
+            Err(ConstraintError::Overconstrained("frameRate"))
+        );
+    }
+
+    #[test]
+    fn test_select_camera_prefers_facing_mode() {
+        let devices = [
+            camera("/dev/video0", Some(FacingMode::User), &[]),
+            camera(
+                "/dev/video2",
+                Some(FacingMode::Environment),
+                &[CameraMode::new(1280, 720, 30)],
+            ),
+        ];
+
+        let front = VideoConstraints::default().select_camera(&devices).unwrap();
+        assert_eq!(front.device.id, "/dev/video0");
+        assert_eq!(front.mode, CameraMode::new(640, 480, 30));
+
+        let rear = VideoConstraints::default()
+            .with_facing_mode(FacingMode::Environment)
+            .select_camera(&devices)
+            .unwrap();
+        assert_eq!(rear.device.id, "/dev/video2");
+        assert_eq!(rear.mode, CameraMode::new(1280, 720, 30));
+
+        // Facing is a preference, so a camera that can meet the required
+        // bounds is used whichever way it faces
+        let full_hd = VideoConstraints {
+            width: ConstrainRange::exact(1920),
+            ..VideoConstraints::default()
+        }
+        .with_facing_mode(FacingMode::Environment);
+        assert_eq!(
+            full_hd.select_camera(&devices).unwrap().device.id,
+            "/dev/video0"
+        );
+
+        let missing = VideoConstraints::default().with_device("/dev/video9");
+        assert_eq!(
+            missing.select_camera(&devices).unwrap_err(),
+            ConstraintError::Overconstrained("deviceId")
+        );
+    }
+
+    #[test]
+    fn test_audio_constraints_check() {
+        let constraints = AudioConstraints {
+            sample_rate: ConstrainRange::between(16_000, 48_000),
+            channel_count: ConstrainRange::exact(2),
+            ..AudioConstraints::default()
+        };
+        assert_eq!(
+            constraints.check(96_000, 2),
+            Err(ConstraintError::Overconstrained("sampleRate"))
+        );
+        assert_eq!(
+            constraints.check(48_000, 1),
+            Err(ConstraintError::Overconstrained("channelCount"))
+        );
+        assert_eq!(constraints.check(48_000, 2), Ok(()));
+        assert_eq!(AudioConstraints::default().check(8_000, 1), Ok(()));
+    }
+}
@@ -0,0 +1,129 @@
+//! Deadlines and cancellation for long-running async calls
+//!
+//! Handshake steps wait on the remote peer, which may vanish at any point.
+//! A [`Deadline`] bounds such a wait with an optional timeout and a
+//! [`CancellationToken`], so callers get an [`Interrupted`] error instead of
+//! a future that never resolves.
+
+use crate::error::{CodedError, ErrorCode};
+use std::future::Future;
+use std::time::Duration;
+use thiserror::Error;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Why a bounded call stopped before finishing
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupted {
+    /// The timeout elapsed
+    #[error("Timed out after {0:?}")]
+    Timeout(Duration),
+
+    /// The cancellation token fired
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+impl CodedError for Interrupted {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Timeout(_) => ErrorCode::Timeout,
+            Self::Cancelled => ErrorCode::Cancelled,
+        }
+    }
+}
+
+/// Timeout and cancellation token bounding an async call
+#[derive(Debug, Clone, Default)]
+pub struct Deadline {
+    timeout: Option<Duration>,
+    cancel: CancellationToken,
+}
+
+impl Deadline {
+    /// No timeout; only stops if cancelled
+    #[must_use]
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Give up after `timeout`
+    #[must_use]
+    pub fn after(timeout: Duration) -> Self {
+        Self {
+            timeout: Some(timeout),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Also stop when `cancel` fires
+    #[must_use]
+    pub fn with_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Timeout, if any
+    #[must_use]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Token that cancels calls bounded by this deadline
+    #[must_use]
+    pub fn token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Run `future` until it finishes, times out or is cancelled
+    ///
+    /// An interrupted future is dropped, so this is only as cancel-safe as
+    /// `future` itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Interrupted`] if the timeout elapses or the token fires
+    /// first
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output, Interrupted> {
+        let bounded = async {
+            match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, future)
+                    .await
+                    .map_err(|_| Interrupted::Timeout(timeout)),
+                None => Ok(future.await),
+            }
+        };
+        tokio::select! {
+            biased;
+            () = self.cancel.cancelled() => Err(Interrupted::Cancelled),
+            result = bounded => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_completes_before_deadline() {
+        let deadline = Deadline::after(Duration::from_secs(1));
+        assert_eq!(deadline.run(async { 7 }).await, Ok(7));
+    }
+
+    #[tokio::test]
+    async fn test_times_out() {
+        let deadline = Deadline::after(Duration::from_millis(10));
+        let result = deadline.run(std::future::pending::<()>()).await;
+        assert_eq!(result, Err(Interrupted::Timeout(Duration::from_millis(10))));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled() {
+        let token = CancellationToken::new();
+        let deadline = Deadline::none().with_token(token.clone());
+        token.cancel();
+        let result = deadline.run(std::future::pending::<()>()).await;
+        assert_eq!(result, Err(Interrupted::Cancelled));
+    }
+}
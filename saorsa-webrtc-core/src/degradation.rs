@@ -0,0 +1,746 @@
+//! Video degradation ladder
+//!
+//! Steps outgoing video down through a list of resolutions as the estimated
+//! bandwidth falls, switches to audio-only once video would drop below the
+//! configured minimum bitrate, and climbs back up as bandwidth recovers.
+//! Low-bandwidth mode holds the ladder at audio-only with a reduced audio
+//! bitrate regardless of the estimate, until it is released.
+//!
+//! Slow devices fall behind long before the network does. An
+//! [`EncodeLoadController`] watches how long each frame takes to encode
+//! and, while the encoder cannot keep up, lowers the resolution or frame
+//! rate below the ladder's step as its [`DegradationPreference`] says.
+
+use crate::types::{AdaptationSettings, CallId, VideoResolution};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// One rung of the ladder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LadderStep {
+    /// Video resolution at this step
+    pub resolution: VideoResolution,
+    /// Video bitrate in kilobits per second
+    pub video_bitrate_kbps: u32,
+    /// Video frames per second
+    pub video_fps: u32,
+}
+
+impl LadderStep {
+    /// Create a ladder step
+    #[must_use]
+    pub fn new(resolution: VideoResolution, video_bitrate_kbps: u32, video_fps: u32) -> Self {
+        Self {
+            resolution,
+            video_bitrate_kbps,
+            video_fps,
+        }
+    }
+}
+
+/// Degradation ladder configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DegradationConfig {
+    /// Steps ordered from highest to lowest quality
+    pub steps: Vec<LadderStep>,
+    /// Below this video bitrate the call drops to audio-only
+    pub min_video_bitrate_kbps: u32,
+    /// Bitrate reserved for audio
+    pub audio_bitrate_kbps: u32,
+    /// Extra headroom (percent) required before stepping back up
+    pub upgrade_headroom_percent: u32,
+    /// Audio bitrate while held in low-bandwidth mode
+    #[serde(default = "default_low_bandwidth_audio_kbps")]
+    pub low_bandwidth_audio_kbps: u32,
+}
+
+fn default_low_bandwidth_audio_kbps() -> u32 {
+    16
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        Self {
+            steps: vec![
+                LadderStep::new(VideoResolution::HD1080, 2500, 30),
+                LadderStep::new(VideoResolution::HD720, 1200, 30),
+                LadderStep::new(VideoResolution::SD480, 600, 30),
+                LadderStep::new(VideoResolution::QVGA240, 250, 15),
+            ],
+            min_video_bitrate_kbps: 150,
+            audio_bitrate_kbps: 48,
+            upgrade_headroom_percent: 20,
+            low_bandwidth_audio_kbps: default_low_bandwidth_audio_kbps(),
+        }
+    }
+}
+
+/// Current position on the ladder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DegradationLevel {
+    /// Sending video at the given step
+    Video(LadderStep),
+    /// Video is off
+    AudioOnly,
+}
+
+/// Emitted whenever the ladder changes step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DegradationEvent {
+    /// Call the change applies to
+    pub call_id: CallId,
+    /// Level before the change
+    pub from: DegradationLevel,
+    /// Level after the change
+    pub to: DegradationLevel,
+    /// Bandwidth estimate that caused the change
+    pub estimated_kbps: u32,
+}
+
+impl DegradationEvent {
+    /// Whether this change lowers quality
+    #[must_use]
+    pub fn is_downgrade(&self) -> bool {
+        rank(&self.to) > rank(&self.from)
+    }
+
+    /// Human readable description of the change
+    #[must_use]
+    pub fn message(&self) -> String {
+        let describe = |level: &DegradationLevel| match level {
+            DegradationLevel::Video(step) => format!(
+                "{}x{}",
+                step.resolution.width(),
+                step.resolution.height()
+            ),
+            DegradationLevel::AudioOnly => "audio-only".to_string(),
+        };
+        format!(
+            "{} video from {} to {} at {} kbps",
+            if self.is_downgrade() { "Reducing" } else { "Restoring" },
+            describe(&self.from),
+            describe(&self.to),
+            self.estimated_kbps
+        )
+    }
+}
+
+/// Ordering key: 0 is best quality, audio-only is worst
+fn rank(level: &DegradationLevel) -> u32 {
+    match level {
+        DegradationLevel::Video(step) => u32::MAX - step.video_bitrate_kbps,
+        DegradationLevel::AudioOnly => u32::MAX,
+    }
+}
+
+/// Per-call degradation ladder
+#[derive(Debug, Clone)]
+pub struct DegradationLadder {
+    call_id: CallId,
+    config: DegradationConfig,
+    level: DegradationLevel,
+    /// Level to return to once low-bandwidth mode is released
+    held: Option<DegradationLevel>,
+    last_estimate_kbps: u32,
+}
+
+impl DegradationLadder {
+    /// Create a ladder starting at the highest step
+    #[must_use]
+    pub fn new(call_id: CallId, config: DegradationConfig) -> Self {
+        let level = config
+            .steps
+            .first()
+            .filter(|step| step.video_bitrate_kbps >= config.min_video_bitrate_kbps)
+            .cloned()
+            .map_or(DegradationLevel::AudioOnly, DegradationLevel::Video);
+        Self {
+            call_id,
+            config,
+            level,
+            held: None,
+            last_estimate_kbps: 0,
+        }
+    }
+
+    /// Current level
+    #[must_use]
+    pub fn level(&self) -> &DegradationLevel {
+        &self.level
+    }
+
+    /// Whether low-bandwidth mode is holding the ladder at audio-only
+    #[must_use]
+    pub fn is_low_bandwidth(&self) -> bool {
+        self.held.is_some()
+    }
+
+    /// Hold the ladder at audio-only, or release it to the level it was at
+    ///
+    /// Returns an event if the level changed.
+    pub fn set_low_bandwidth(&mut self, enabled: bool) -> Option<DegradationEvent> {
+        let target = if enabled {
+            if self.held.is_some() {
+                return None;
+            }
+            self.held = Some(self.level.clone());
+            DegradationLevel::AudioOnly
+        } else {
+            self.held.take()?
+        };
+        self.move_to(target)
+    }
+
+    /// Switch to a new ladder, e.g. with other bandwidth caps, and move to
+    /// the level the last estimate allows on it
+    ///
+    /// Returns an event if the level changed. Low-bandwidth mode keeps
+    /// holding the ladder at audio-only.
+    pub fn set_config(&mut self, config: DegradationConfig) -> Option<DegradationEvent> {
+        self.config = config;
+        if self.held.is_some() {
+            return None;
+        }
+        let target = self.target_level(self.last_estimate_kbps);
+        self.move_to(target)
+    }
+
+    /// Encoder settings for the current level
+    #[must_use]
+    pub fn settings(&self) -> AdaptationSettings {
+        if self.held.is_some() {
+            return AdaptationSettings {
+                video_bitrate_kbps: 0,
+                video_resolution: VideoResolution::QVGA240,
+                video_fps: 0,
+                audio_bitrate_kbps: self
+                    .config
+                    .low_bandwidth_audio_kbps
+                    .min(self.config.audio_bitrate_kbps),
+                enable_dtx: true,
+            };
+        }
+        match &self.level {
+            DegradationLevel::Video(step) => AdaptationSettings {
+                video_bitrate_kbps: step.video_bitrate_kbps,
+                video_resolution: step.resolution.clone(),
+                video_fps: step.video_fps,
+                audio_bitrate_kbps: self.config.audio_bitrate_kbps,
+                enable_dtx: false,
+            },
+            DegradationLevel::AudioOnly => AdaptationSettings {
+                video_bitrate_kbps: 0,
+                video_resolution: VideoResolution::QVGA240,
+                video_fps: 0,
+                audio_bitrate_kbps: self.config.audio_bitrate_kbps,
+                enable_dtx: true,
+            },
+        }
+    }
+
+    /// Feed a new bandwidth estimate, returning an event if the level changed
+    ///
+    /// Estimates are only recorded while low-bandwidth mode holds the ladder.
+    pub fn update(&mut self, estimated_bps: u64) -> Option<DegradationEvent> {
+        self.last_estimate_kbps = u32::try_from(estimated_bps / 1000).unwrap_or(u32::MAX);
+        if self.held.is_some() {
+            return None;
+        }
+        let target = self.target_level(self.last_estimate_kbps);
+        self.move_to(target)
+    }
+
+    fn move_to(&mut self, target: DegradationLevel) -> Option<DegradationEvent> {
+        if target == self.level {
+            return None;
+        }
+
+        let from = std::mem::replace(&mut self.level, target.clone());
+        let event = DegradationEvent {
+            call_id: self.call_id,
+            from,
+            to: target,
+            estimated_kbps: self.last_estimate_kbps,
+        };
+        tracing::info!("Call {}: {}", self.call_id, event.message());
+        Some(event)
+    }
+
+    fn target_level(&self, estimated_kbps: u32) -> DegradationLevel {
+        let available = estimated_kbps.saturating_sub(self.config.audio_bitrate_kbps);
+        let current_rank = rank(&self.level);
+
+        self.config
+            .steps
+            .iter()
+            .filter(|step| step.video_bitrate_kbps >= self.config.min_video_bitrate_kbps)
+            .find(|step| {
+                let candidate = DegradationLevel::Video((*step).clone());
+                // Stepping up needs headroom to avoid oscillating between levels
+                let required = if rank(&candidate) < current_rank {
+                    step.video_bitrate_kbps
+                        .saturating_mul(100 + self.config.upgrade_headroom_percent)
+                        / 100
+                } else {
+                    step.video_bitrate_kbps
+                };
+                available >= required
+            })
+            .cloned()
+            .map_or(DegradationLevel::AudioOnly, DegradationLevel::Video)
+    }
+}
+
+/// What to give up when the encoder cannot keep up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DegradationPreference {
+    /// Lower the resolution, for motion such as sports or games
+    MaintainFramerate,
+    /// Lower the frame rate, for detail such as text in screen shares
+    MaintainResolution,
+    /// Alternate between lowering the frame rate and the resolution
+    #[default]
+    Balanced,
+}
+
+/// Encode load thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodeLoadConfig {
+    /// What to give up first
+    pub preference: DegradationPreference,
+    /// Load above which the encoder is overloaded, as encode time in
+    /// percent of the frame interval
+    pub overuse_percent: u32,
+    /// Load the encoder must be expected to stay below once a step is
+    /// restored before it is
+    pub underuse_percent: u32,
+    /// Frames averaged for each decision
+    pub window: usize,
+    /// Frame rate never lowered below
+    pub min_fps: u32,
+}
+
+impl Default for EncodeLoadConfig {
+    fn default() -> Self {
+        Self {
+            preference: DegradationPreference::default(),
+            overuse_percent: 85,
+            underuse_percent: 70,
+            window: 30,
+            min_fps: 10,
+        }
+    }
+}
+
+/// Resolution and frame rate the encoder is held to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodeLimits {
+    /// Video resolution
+    pub resolution: VideoResolution,
+    /// Video frames per second
+    pub fps: u32,
+}
+
+impl EncodeLimits {
+    /// Pixels encoded per second
+    fn pixel_rate(&self) -> u64 {
+        u64::from(self.resolution.width())
+            * u64::from(self.resolution.height())
+            * u64::from(self.fps)
+    }
+}
+
+/// Emitted whenever encode load changes the limits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodeLoadEvent {
+    /// Call the change applies to
+    pub call_id: CallId,
+    /// Limits before the change
+    pub from: EncodeLimits,
+    /// Limits after the change
+    pub to: EncodeLimits,
+    /// Encoder load that caused the change, in percent
+    pub load_percent: u32,
+}
+
+impl EncodeLoadEvent {
+    /// Whether this change lowers quality
+    #[must_use]
+    pub fn is_downgrade(&self) -> bool {
+        self.to.pixel_rate() < self.from.pixel_rate()
+    }
+
+    /// Human readable description of the change
+    #[must_use]
+    pub fn message(&self) -> String {
+        let describe = |limits: &EncodeLimits| {
+            format!(
+                "{}x{}@{}",
+                limits.resolution.width(),
+                limits.resolution.height(),
+                limits.fps
+            )
+        };
+        format!(
+            "{} video from {} to {} at {}% encoder load",
+            if self.is_downgrade() { "Reducing" } else { "Restoring" },
+            describe(&self.from),
+            describe(&self.to),
+            self.load_percent
+        )
+    }
+}
+
+/// One step taken to lower encode cost
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EncodeStep {
+    /// One resolution lower
+    Resolution,
+    /// Two thirds of the frame rate
+    Framerate,
+}
+
+/// The next resolution down, if any
+fn lower_resolution(resolution: &VideoResolution) -> Option<VideoResolution> {
+    match resolution {
+        VideoResolution::HD1080 => Some(VideoResolution::HD720),
+        VideoResolution::HD720 => Some(VideoResolution::SD480),
+        VideoResolution::SD480 => Some(VideoResolution::QVGA240),
+        VideoResolution::QVGA240 => None,
+    }
+}
+
+/// Per-call encode load controller
+///
+/// Feed it how long each frame took to encode along with the ladder's
+/// settings, and apply [`Self::restrict`] to those settings before
+/// configuring the encoder. Steps are taken one window of frames at a
+/// time and undone in reverse order once the load allows.
+#[derive(Debug, Clone)]
+pub struct EncodeLoadController {
+    call_id: CallId,
+    config: EncodeLoadConfig,
+    steps: Vec<EncodeStep>,
+    encode_times: Vec<Duration>,
+}
+
+impl EncodeLoadController {
+    /// Create a controller that restricts nothing yet
+    #[must_use]
+    pub fn new(call_id: CallId, config: EncodeLoadConfig) -> Self {
+        Self {
+            call_id,
+            config,
+            steps: Vec::new(),
+            encode_times: Vec::new(),
+        }
+    }
+
+    /// Whether resolution or frame rate are held below the ladder's
+    #[must_use]
+    pub fn is_degraded(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// Limits applied to `settings`
+    #[must_use]
+    pub fn limits(&self, settings: &AdaptationSettings) -> EncodeLimits {
+        self.limits_with(&self.steps, settings)
+    }
+
+    /// `settings` with the resolution and frame rate lowered as the load
+    /// requires
+    #[must_use]
+    pub fn restrict(&self, mut settings: AdaptationSettings) -> AdaptationSettings {
+        if settings.video_fps == 0 {
+            return settings;
+        }
+        let limits = self.limits(&settings);
+        settings.video_resolution = limits.resolution;
+        settings.video_fps = limits.fps;
+        settings
+    }
+
+    /// Record how long a frame took to encode under `settings`, the
+    /// ladder's settings before [`Self::restrict`]
+    ///
+    /// Returns an event if the limits changed.
+    pub fn record(
+        &mut self,
+        encode_time: Duration,
+        settings: &AdaptationSettings,
+    ) -> Option<EncodeLoadEvent> {
+        if settings.video_fps == 0 {
+            return None;
+        }
+        self.encode_times.push(encode_time);
+        if self.encode_times.len() < self.config.window.max(1) {
+            return None;
+        }
+        let total: Duration = self.encode_times.drain(..).sum();
+        let average = total.as_secs_f64() / self.config.window.max(1) as f64;
+
+        let from = self.limits(settings);
+        let load_percent = (average * f64::from(from.fps) * 100.0).round() as u32;
+        if load_percent > self.config.overuse_percent {
+            let step = self.next_step(settings)?;
+            self.steps.push(step);
+        } else if !self.steps.is_empty() {
+            let restored = self.limits_with(&self.steps[..self.steps.len() - 1], settings);
+            // The load scales with the pixels encoded per second
+            let expected =
+                u64::from(load_percent) * restored.pixel_rate() / from.pixel_rate().max(1);
+            if expected >= u64::from(self.config.underuse_percent) {
+                return None;
+            }
+            self.steps.pop();
+        }
+
+        let to = self.limits(settings);
+        if to == from {
+            return None;
+        }
+        let event = EncodeLoadEvent {
+            call_id: self.call_id,
+            from,
+            to,
+            load_percent,
+        };
+        tracing::info!("Call {}: {}", self.call_id, event.message());
+        Some(event)
+    }
+
+    /// A step that lowers the limits further, as the preference allows
+    fn next_step(&self, settings: &AdaptationSettings) -> Option<EncodeStep> {
+        let candidates: &[EncodeStep] = match self.config.preference {
+            DegradationPreference::MaintainFramerate => &[EncodeStep::Resolution],
+            DegradationPreference::MaintainResolution => &[EncodeStep::Framerate],
+            // Alternate, starting with the frame rate
+            DegradationPreference::Balanced
+                if self.steps.last() == Some(&EncodeStep::Framerate) =>
+            {
+                &[EncodeStep::Resolution, EncodeStep::Framerate]
+            }
+            DegradationPreference::Balanced => &[EncodeStep::Framerate, EncodeStep::Resolution],
+        };
+        let current = self.limits(settings);
+        candidates.iter().copied().find(|step| {
+            let mut steps = self.steps.clone();
+            steps.push(*step);
+            self.limits_with(&steps, settings) != current
+        })
+    }
+
+    fn limits_with(&self, steps: &[EncodeStep], settings: &AdaptationSettings) -> EncodeLimits {
+        let mut limits = EncodeLimits {
+            resolution: settings.video_resolution.clone(),
+            fps: settings.video_fps,
+        };
+        for step in steps {
+            match step {
+                EncodeStep::Resolution => {
+                    if let Some(lower) = lower_resolution(&limits.resolution) {
+                        limits.resolution = lower;
+                    }
+                }
+                EncodeStep::Framerate => {
+                    limits.fps = (limits.fps * 2 / 3)
+                        .max(self.config.min_fps)
+                        .min(limits.fps);
+                }
+            }
+        }
+        limits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ladder() -> DegradationLadder {
+        DegradationLadder::new(CallId::new(), DegradationConfig::default())
+    }
+
+    #[test]
+    fn test_ladder_starts_at_top() {
+        let ladder = ladder();
+        assert!(matches!(
+            ladder.level(),
+            DegradationLevel::Video(step) if step.resolution == VideoResolution::HD1080
+        ));
+    }
+
+    #[test]
+    fn test_ladder_steps_down_to_audio_only() {
+        let mut ladder = ladder();
+
+        let event = ladder.update(1_500_000).unwrap();
+        assert!(event.is_downgrade());
+        assert!(matches!(&event.to, DegradationLevel::Video(s) if s.resolution == VideoResolution::HD720));
+
+        let event = ladder.update(400_000).unwrap();
+        assert!(matches!(&event.to, DegradationLevel::Video(s) if s.resolution == VideoResolution::QVGA240));
+
+        let event = ladder.update(150_000).unwrap();
+        assert_eq!(event.to, DegradationLevel::AudioOnly);
+        assert_eq!(ladder.settings().video_bitrate_kbps, 0);
+        assert!(event.message().contains("audio-only"));
+    }
+
+    #[test]
+    fn test_ladder_recovers_with_headroom() {
+        let mut ladder = ladder();
+        ladder.update(100_000);
+        assert_eq!(ladder.level(), &DegradationLevel::AudioOnly);
+
+        // 250 kbps video needs 300 kbps with 20% headroom, plus 48 kbps audio
+        assert!(ladder.update(320_000).is_none());
+        let event = ladder.update(360_000).unwrap();
+        assert!(!event.is_downgrade());
+        assert!(event.message().starts_with("Restoring"));
+    }
+
+    #[test]
+    fn test_ladder_no_event_when_stable() {
+        let mut ladder = ladder();
+        assert!(ladder.update(10_000_000).is_none());
+        assert!(ladder.update(3_000_000).is_none());
+    }
+
+    #[test]
+    fn test_min_video_bitrate_skips_low_steps() {
+        let config = DegradationConfig {
+            min_video_bitrate_kbps: 500,
+            ..Default::default()
+        };
+        let mut ladder = DegradationLadder::new(CallId::new(), config);
+
+        // 400 kbps would fit the 240p step, but it is below the minimum
+        let event = ladder.update(400_000).unwrap();
+        assert_eq!(event.to, DegradationLevel::AudioOnly);
+    }
+
+    #[test]
+    fn test_low_bandwidth_holds_audio_only_then_restores() {
+        let mut ladder = ladder();
+        ladder.update(1_500_000);
+        let before = ladder.level().clone();
+
+        let event = ladder.set_low_bandwidth(true).unwrap();
+        assert_eq!(event.to, DegradationLevel::AudioOnly);
+        assert!(ladder.set_low_bandwidth(true).is_none());
+        let settings = ladder.settings();
+        assert_eq!(settings.video_bitrate_kbps, 0);
+        assert_eq!(settings.audio_bitrate_kbps, 16);
+        assert!(settings.enable_dtx);
+
+        // Plenty of bandwidth does not lift the hold
+        assert!(ladder.update(10_000_000).is_none());
+        assert_eq!(ladder.level(), &DegradationLevel::AudioOnly);
+
+        let event = ladder.set_low_bandwidth(false).unwrap();
+        assert_eq!(event.to, before);
+        assert!(!ladder.is_low_bandwidth());
+        assert_eq!(ladder.settings().audio_bitrate_kbps, 48);
+        assert!(ladder.set_low_bandwidth(false).is_none());
+    }
+
+    #[test]
+    fn test_new_config_applies_to_last_estimate() {
+        let mut ladder = ladder();
+        assert!(ladder.update(3_000_000).is_none());
+
+        // Cap video at 720p
+        let mut config = DegradationConfig::default();
+        config.steps.remove(0);
+        let event = ladder.set_config(config).unwrap();
+        assert!(event.is_downgrade());
+        assert!(matches!(&event.to, DegradationLevel::Video(s) if s.resolution == VideoResolution::HD720));
+        assert!(ladder.update(3_000_000).is_none());
+    }
+
+    fn feed(
+        controller: &mut EncodeLoadController,
+        encode_ms: u64,
+        settings: &AdaptationSettings,
+    ) -> Option<EncodeLoadEvent> {
+        let window = EncodeLoadConfig::default().window;
+        let events: Vec<_> = (0..window)
+            .filter_map(|_| controller.record(Duration::from_millis(encode_ms), settings))
+            .collect();
+        assert!(events.len() <= 1);
+        events.into_iter().next()
+    }
+
+    #[test]
+    fn test_encode_overload_lowers_resolution_then_restores() {
+        let settings = ladder().settings();
+        let config = EncodeLoadConfig {
+            preference: DegradationPreference::MaintainFramerate,
+            ..Default::default()
+        };
+        let mut controller = EncodeLoadController::new(CallId::new(), config);
+
+        // 30 ms per frame at 30 fps keeps the encoder 90% busy
+        let event = feed(&mut controller, 30, &settings).unwrap();
+        assert!(event.is_downgrade());
+        assert_eq!(event.load_percent, 90);
+        assert_eq!(event.to.resolution, VideoResolution::HD720);
+        assert_eq!(event.to.fps, 30);
+        let restricted = controller.restrict(settings.clone());
+        assert_eq!(restricted.video_resolution, VideoResolution::HD720);
+        assert_eq!(restricted.video_bitrate_kbps, settings.video_bitrate_kbps);
+
+        // 60% at 720p would be 135% at 1080p
+        assert!(feed(&mut controller, 20, &settings).is_none());
+        let event = feed(&mut controller, 10, &settings).unwrap();
+        assert!(!event.is_downgrade());
+        assert!(event.message().starts_with("Restoring"));
+        assert!(!controller.is_degraded());
+    }
+
+    #[test]
+    fn test_encode_preferences_choose_what_to_lower() {
+        let settings = ladder().settings();
+        let limits = |event: Option<EncodeLoadEvent>| {
+            let event = event.unwrap();
+            (event.to.resolution, event.to.fps)
+        };
+
+        let mut balanced = EncodeLoadController::new(CallId::new(), EncodeLoadConfig::default());
+        assert_eq!(
+            limits(feed(&mut balanced, 30, &settings)),
+            (VideoResolution::HD1080, 20)
+        );
+        assert_eq!(
+            limits(feed(&mut balanced, 45, &settings)),
+            (VideoResolution::HD720, 20)
+        );
+        assert_eq!(
+            limits(feed(&mut balanced, 45, &settings)),
+            (VideoResolution::HD720, 13)
+        );
+
+        let config = EncodeLoadConfig {
+            preference: DegradationPreference::MaintainResolution,
+            ..Default::default()
+        };
+        let mut controller = EncodeLoadController::new(CallId::new(), config);
+        for fps in [20, 13, 10] {
+            assert_eq!(
+                limits(feed(&mut controller, 100, &settings)),
+                (VideoResolution::HD1080, fps)
+            );
+        }
+        // Already at the minimum frame rate
+        assert!(feed(&mut controller, 100, &settings).is_none());
+
+        // Nothing to lower while video is off
+        let mut audio_only = ladder();
+        audio_only.update(100_000);
+        assert!(feed(&mut balanced, 100, &audio_only.settings()).is_none());
+    }
+}
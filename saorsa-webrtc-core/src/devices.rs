@@ -0,0 +1,127 @@
+//! Capture device discovery and hot-plug detection
+//!
+//! Operating systems report device changes through very different APIs, so
+//! devices are found by listing them again every few seconds and comparing
+//! against the previous list. [`MediaStreamManager::watch_devices`] runs
+//! that loop and emits [`MediaEvent::DeviceConnected`] and
+//! [`MediaEvent::DeviceDisconnected`] for whatever changed.
+//!
+//! Microphones are listed through the `audio-capture` feature. Cameras are
+//! listed from video4linux on Linux; elsewhere none are found yet. sysfs
+//! gives neither a camera's modes nor which way it faces, so both are left
+//! unknown.
+//!
+//! [`MediaStreamManager::watch_devices`]: crate::media::MediaStreamManager::watch_devices
+//! [`MediaEvent::DeviceConnected`]: crate::media::MediaEvent::DeviceConnected
+//! [`MediaEvent::DeviceDisconnected`]: crate::media::MediaEvent::DeviceDisconnected
+
+use crate::media::VideoDevice;
+use std::time::Duration;
+
+/// How often devices are listed again by default
+pub const DEFAULT_DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Devices added and removed between two listings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceChanges {
+    /// IDs of devices in the new listing only
+    pub connected: Vec<String>,
+    /// IDs of devices in the old listing only
+    pub disconnected: Vec<String>,
+}
+
+impl DeviceChanges {
+    /// Whether nothing changed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.connected.is_empty() && self.disconnected.is_empty()
+    }
+}
+
+/// Compare two listings of device IDs
+#[must_use]
+pub fn diff_devices(old: &[String], new: &[String]) -> DeviceChanges {
+    let missing_from = |list: &[String], other: &[String]| {
+        list.iter()
+            .filter(|id| !other.contains(id))
+            .cloned()
+            .collect()
+    };
+    DeviceChanges {
+        connected: missing_from(new, old),
+        disconnected: missing_from(old, new),
+    }
+}
+
+/// Cameras attached to this machine
+///
+/// Only video4linux capture devices are found, so this is always empty
+/// off Linux.
+#[must_use]
+pub fn video_input_devices() -> Vec<VideoDevice> {
+    #[cfg(target_os = "linux")]
+    {
+        v4l::video_input_devices()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod v4l {
+    use crate::media::VideoDevice;
+    use std::fs;
+    use std::path::Path;
+
+    const SYSFS_CLASS: &str = "/sys/class/video4linux";
+
+    fn read_attr(node: &Path, attr: &str) -> Option<String> {
+        fs::read_to_string(node.join(attr))
+            .ok()
+            .map(|value| value.trim().to_string())
+    }
+
+    pub(super) fn video_input_devices() -> Vec<VideoDevice> {
+        let Ok(entries) = fs::read_dir(SYSFS_CLASS) else {
+            return Vec::new();
+        };
+        let mut devices: Vec<VideoDevice> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("video"))
+            // Cameras also expose metadata nodes; only the first node of
+            // each device captures frames
+            .filter(|entry| read_attr(&entry.path(), "index").as_deref() == Some("0"))
+            .map(|entry| {
+                let node = entry.file_name().to_string_lossy().into_owned();
+                VideoDevice {
+                    id: format!("/dev/{node}"),
+                    name: read_attr(&entry.path(), "name").unwrap_or(node),
+                    facing_mode: None,
+                    modes: Vec::new(),
+                }
+            })
+            .collect();
+        devices.sort_by(|a, b| a.id.cmp(&b.id));
+        devices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_devices_reports_plug_and_unplug() {
+        let old = ["mic".to_string(), "headset".to_string()];
+        let new = ["mic".to_string(), "usb-camera".to_string()];
+
+        let changes = diff_devices(&old, &new);
+        assert_eq!(changes.connected, ["usb-camera"]);
+        assert_eq!(changes.disconnected, ["headset"]);
+        assert!(!changes.is_empty());
+
+        assert!(diff_devices(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! DTMF key presses over a data channel
+//!
+//! Keypad digits travel as RFC 4733 telephone events on the data channel
+//! labelled [`DTMF_CHANNEL_LABEL`], so IVR-style bots built on this crate
+//! can follow menus without decoding audio. Each key press is sent once,
+//! already ended, as the event's RTP payload.
+//!
+//! # Wire format
+//!
+//! One [`DtmfTone`] per data channel message, fields big-endian as in
+//! RFC 4733:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 1 | event: 0-9 for digits, 10 `*`, 11 `#`, 12-15 `A`-`D` |
+//! | 1 | bit 7 end of event, bits 0-5 volume in -dBm0 |
+//! | 2 | duration, 8 kHz timestamp units |
+//!
+//! Bytes after the duration are ignored.
+
+use crate::error::{CodedError, ErrorCode};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Label reserved for the DTMF data channel
+pub const DTMF_CHANNEL_LABEL: &str = "saorsa-dtmf";
+
+/// How long each key is held unless chosen otherwise
+pub const DEFAULT_TONE_DURATION: Duration = Duration::from_millis(100);
+
+/// Volume of tones, in -dBm0, unless chosen otherwise
+pub const DEFAULT_TONE_VOLUME: u8 = 10;
+
+/// Keys in RFC 4733 event code order
+const EVENTS: [char; 16] = [
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '*', '#', 'A', 'B', 'C', 'D',
+];
+
+/// Timestamp units per second of duration
+const CLOCK_RATE: u128 = 8000;
+const END_OF_EVENT: u8 = 0x80;
+const MAX_VOLUME: u8 = 0x3F;
+
+/// DTMF encoding and decoding errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DtmfError {
+    /// Character is not a keypad key
+    #[error("Invalid DTMF digit: {0:?}")]
+    InvalidDigit(char),
+
+    /// Event code is not a keypad key
+    #[error("Unknown DTMF event: {0}")]
+    UnknownEvent(u8),
+
+    /// Message ended before its last field did
+    #[error("DTMF event truncated")]
+    Truncated,
+}
+
+impl CodedError for DtmfError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidDigit(_) => ErrorCode::InvalidInput,
+            Self::UnknownEvent(_) | Self::Truncated => ErrorCode::MalformedMessage,
+        }
+    }
+}
+
+/// One key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DtmfTone {
+    /// Key pressed: `0`-`9`, `*`, `#` or `A`-`D`
+    pub digit: char,
+    /// How long the key was held
+    pub duration: Duration,
+    /// Tone volume in -dBm0, 0 loudest
+    pub volume: u8,
+}
+
+impl DtmfTone {
+    /// A press of `digit` held for [`DEFAULT_TONE_DURATION`]
+    ///
+    /// Letters may be given in either case.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if `digit` is not a keypad key
+    pub fn new(digit: char) -> Result<Self, DtmfError> {
+        let key = digit.to_ascii_uppercase();
+        if !EVENTS.contains(&key) {
+            return Err(DtmfError::InvalidDigit(digit));
+        }
+        Ok(Self {
+            digit: key,
+            duration: DEFAULT_TONE_DURATION,
+            volume: DEFAULT_TONE_VOLUME,
+        })
+    }
+
+    /// The press held for `duration` instead
+    ///
+    /// Durations beyond about 8 seconds are sent as 8 seconds.
+    #[must_use]
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// A press for each key in `digits`, in order
+    ///
+    /// # Errors
+    ///
+    /// Returns error on the first character that is not a keypad key
+    pub fn parse(digits: &str) -> Result<Vec<Self>, DtmfError> {
+        digits.chars().map(Self::new).collect()
+    }
+
+    /// Encode for sending over the DTMF channel
+    #[must_use]
+    pub fn encode(&self) -> [u8; 4] {
+        let event = EVENTS
+            .iter()
+            .position(|&key| key == self.digit)
+            .unwrap_or_default() as u8;
+        let units = self.duration.as_micros() * CLOCK_RATE / 1_000_000;
+        let duration = u16::try_from(units).unwrap_or(u16::MAX).to_be_bytes();
+        [
+            event,
+            END_OF_EVENT | self.volume.min(MAX_VOLUME),
+            duration[0],
+            duration[1],
+        ]
+    }
+
+    /// Decode a key press received over the DTMF channel
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the event is truncated or not a keypad key
+    pub fn decode(data: &[u8]) -> Result<Self, DtmfError> {
+        let [event, flags, high, low, ..] = *data else {
+            return Err(DtmfError::Truncated);
+        };
+        let digit = *EVENTS
+            .get(usize::from(event))
+            .ok_or(DtmfError::UnknownEvent(event))?;
+        let units = u64::from(u16::from_be_bytes([high, low]));
+        Ok(Self {
+            digit,
+            duration: Duration::from_micros(units * 1_000_000 / CLOCK_RATE as u64),
+            volume: flags & MAX_VOLUME,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tones_round_trip() {
+        let tones = DtmfTone::parse("12*#ad").unwrap();
+        let digits: String = tones.iter().map(|tone| tone.digit).collect();
+        assert_eq!(digits, "12*#AD");
+
+        let tone = tones[2].with_duration(Duration::from_millis(250));
+        // RFC 4733: event 10 for '*', ended, 2000 units at 8 kHz
+        assert_eq!(tone.encode(), [10, 0x80 | DEFAULT_TONE_VOLUME, 0x07, 0xD0]);
+        assert_eq!(DtmfTone::decode(&tone.encode()).unwrap(), tone);
+
+        let long = tones[0].with_duration(Duration::from_secs(60));
+        assert_eq!(
+            DtmfTone::decode(&long.encode()).unwrap().duration,
+            Duration::from_micros(u64::from(u16::MAX) * 125)
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_tones() {
+        assert_eq!(DtmfTone::parse("12x"), Err(DtmfError::InvalidDigit('x')));
+        assert_eq!(DtmfTone::decode(&[1, 0x80, 0]), Err(DtmfError::Truncated));
+        assert_eq!(
+            DtmfTone::decode(&[16, 0x80, 0, 0]),
+            Err(DtmfError::UnknownEvent(16))
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Loopback echo peer for trying calls in one process
+//!
+//! An [`EchoPeer`] answers every call it is offered, sends each track it
+//! receives straight back and replies to text messages with the same text.
+//! Services in one process reach each other over a [`LoopbackNetwork`], so
+//! a single program can exercise signaling, negotiation, media and data
+//! channels end to end:
+//!
+//! ```rust,no_run
+//! use saorsa_webrtc_core::echo_peer::{EchoPeer, LoopbackNetwork};
+//! use saorsa_webrtc_core::signaling::SignalingMessage;
+//! use saorsa_webrtc_core::{
+//!     CallManager, MediaConstraints, PeerIdentityString, SignalingHandler, SignalingTransport,
+//!     WebRtcService,
+//! };
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let network = LoopbackNetwork::new();
+//! let signaling = Arc::new(SignalingHandler::new(Arc::new(network.join("echo"))));
+//! let service = WebRtcService::builder(signaling)
+//!     .with_identity(PeerIdentityString::new("echo"))
+//!     .build()
+//!     .await?;
+//! let echo = EchoPeer::new(Arc::new(service));
+//! tokio::spawn(async move { echo.run().await });
+//!
+//! // Call it
+//! let me = network.join("me");
+//! let calls = CallManager::new(Default::default()).await?;
+//! let call_id = calls
+//!     .initiate_call(PeerIdentityString::new("echo"), MediaConstraints::audio_only())
+//!     .await?;
+//! let sdp = calls.create_offer(call_id).await?;
+//! let session_id = call_id.to_string();
+//! let offer = SignalingMessage::Offer { session_id, sdp, quic_endpoint: None };
+//! me.send_message(&"echo".to_string(), offer).await?;
+//! if let (_, SignalingMessage::Answer { sdp, .. }) = me.receive_message().await? {
+//!     calls.handle_answer(call_id, sdp).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{CodedError, ErrorCode};
+use crate::identity::PeerIdentity;
+use crate::messaging::TextMessage;
+use crate::service::{ServiceError, WebRtcEvent, WebRtcService};
+use crate::signaling::{SignalingMessage, SignalingTransport};
+use crate::types::{CallEvent, CallId, MediaConstraints};
+use async_trait::async_trait;
+use dashmap::DashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+/// Signaling messages each loopback peer can have waiting
+const INBOX_CAPACITY: usize = 64;
+
+/// Loopback signaling errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LoopbackError {
+    /// No peer of that name has joined the network, or it was dropped
+    #[error("Unknown loopback peer: {0}")]
+    UnknownPeer(String),
+
+    /// The network let go of this peer, as another joined under its name
+    #[error("Loopback peer left the network")]
+    Closed,
+}
+
+impl CodedError for LoopbackError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnknownPeer(_) => ErrorCode::PeerNotFound,
+            Self::Closed => ErrorCode::TransportNotStarted,
+        }
+    }
+}
+
+type Inbox = mpsc::Sender<(String, SignalingMessage)>;
+
+/// In-memory signaling between services in one process
+#[derive(Clone, Default)]
+pub struct LoopbackNetwork {
+    peers: Arc<DashMap<String, Inbox>>,
+}
+
+impl LoopbackNetwork {
+    /// An empty network
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signaling for a peer named `peer_id`
+    ///
+    /// A later peer joining under the same name takes its messages over.
+    #[must_use]
+    pub fn join(&self, peer_id: impl Into<String>) -> LoopbackSignaling {
+        let peer_id = peer_id.into();
+        let (sender, inbox) = mpsc::channel(INBOX_CAPACITY);
+        self.peers.insert(peer_id.clone(), sender);
+        LoopbackSignaling {
+            peer_id,
+            peers: Arc::clone(&self.peers),
+            inbox: Mutex::new(inbox),
+        }
+    }
+}
+
+/// One peer's signaling over a [`LoopbackNetwork`]
+pub struct LoopbackSignaling {
+    peer_id: String,
+    peers: Arc<DashMap<String, Inbox>>,
+    inbox: Mutex<mpsc::Receiver<(String, SignalingMessage)>>,
+}
+
+impl LoopbackSignaling {
+    /// Name this peer joined under
+    #[must_use]
+    pub fn peer_id(&self) -> &str {
+        &self.peer_id
+    }
+}
+
+#[async_trait]
+impl SignalingTransport for LoopbackSignaling {
+    type PeerId = String;
+    type Error = LoopbackError;
+
+    async fn send_message(
+        &self,
+        peer: &String,
+        message: SignalingMessage,
+    ) -> Result<(), LoopbackError> {
+        let inbox = self
+            .peers
+            .get(peer)
+            .map(|inbox| inbox.clone())
+            .ok_or_else(|| LoopbackError::UnknownPeer(peer.clone()))?;
+        inbox
+            .send((self.peer_id.clone(), message))
+            .await
+            .map_err(|_| LoopbackError::UnknownPeer(peer.clone()))
+    }
+
+    async fn receive_message(&self) -> Result<(String, SignalingMessage), LoopbackError> {
+        self.inbox
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or(LoopbackError::Closed)
+    }
+
+    async fn discover_peer_endpoint(
+        &self,
+        _peer: &String,
+    ) -> Result<Option<SocketAddr>, LoopbackError> {
+        // Peers share the process, not a network address
+        Ok(None)
+    }
+}
+
+/// Peer that answers every call and echoes what it receives
+pub struct EchoPeer<I: PeerIdentity, T: SignalingTransport> {
+    service: Arc<WebRtcService<I, T>>,
+}
+
+impl<I: PeerIdentity, T: SignalingTransport> EchoPeer<I, T> {
+    /// Echo calls made to `service`
+    ///
+    /// The service needs its identity set to take offers.
+    #[must_use]
+    pub fn new(service: Arc<WebRtcService<I, T>>) -> Self {
+        Self { service }
+    }
+
+    /// The service calls are answered on
+    #[must_use]
+    pub fn service(&self) -> &Arc<WebRtcService<I, T>> {
+        &self.service
+    }
+
+    /// Receive signaling, answer calls and echo them until the signaling
+    /// transport fails
+    ///
+    /// # Errors
+    ///
+    /// Returns error once receiving signaling fails
+    pub async fn run(&self) -> Result<(), ServiceError> {
+        let mut events = self.service.subscribe_events();
+        let mut calls = self.service.event_bus().subscribe_calls();
+        let signaling = self.service.receive_signaling();
+        tokio::pin!(signaling);
+        loop {
+            tokio::select! {
+                result = &mut signaling => return result,
+                event = events.recv() => match event {
+                    Ok(WebRtcEvent::IncomingCall { call_id, from, constraints }) => {
+                        self.answer(call_id, &from, constraints).await;
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                Some(event) = calls.recv() => self.echo(event).await,
+            }
+        }
+    }
+
+    /// Accept a call with the media it offered, so every track has one to
+    /// echo on
+    async fn answer(&self, call_id: CallId, from: &I, constraints: MediaConstraints) {
+        match self.service.accept_call(call_id, constraints).await {
+            Ok(()) => tracing::info!("Echoing call {} from {}", call_id, from),
+            Err(e) => tracing::warn!("Echo peer failed to answer call {}: {}", call_id, e),
+        }
+    }
+
+    async fn echo(&self, event: CallEvent<I>) {
+        let result = match event {
+            CallEvent::RemoteTrackAdded {
+                call_id, track_id, ..
+            } => self.service.echo_track(call_id, &track_id).await,
+            CallEvent::TextReceived { call_id, message } => {
+                let mut reply = TextMessage::new(message.text);
+                reply.group = message.group;
+                self.service.send_text(call_id, &reply).await
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Echo peer failed to echo: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call::{CallManager, CallManagerConfig};
+    use crate::identity::PeerIdentityString;
+    use crate::signaling::SignalingHandler;
+
+    #[tokio::test]
+    async fn test_echo_peer_answers_calls() {
+        let network = LoopbackNetwork::new();
+        let me = network.join("me");
+        let done = SignalingMessage::IceComplete {
+            session_id: "s1".to_string(),
+        };
+        assert_eq!(
+            me.send_message(&"nobody".to_string(), done).await,
+            Err(LoopbackError::UnknownPeer("nobody".to_string()))
+        );
+
+        let signaling = Arc::new(SignalingHandler::new(Arc::new(network.join("echo"))));
+        let service = WebRtcService::builder(signaling)
+            .with_identity(PeerIdentityString::new("echo"))
+            .build()
+            .await
+            .unwrap();
+        let echo = EchoPeer::new(Arc::new(service));
+
+        let calls = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        let call_id = calls
+            .initiate_call(
+                PeerIdentityString::new("echo"),
+                MediaConstraints::audio_only(),
+            )
+            .await
+            .unwrap();
+        let offer = SignalingMessage::Offer {
+            session_id: call_id.to_string(),
+            sdp: calls.create_offer(call_id).await.unwrap(),
+            quic_endpoint: None,
+        };
+        me.send_message(&"echo".to_string(), offer).await.unwrap();
+
+        let running = tokio::spawn(async move { echo.run().await });
+        let (from, answer) = me.receive_message().await.unwrap();
+        running.abort();
+        assert_eq!(from, "echo");
+        match answer {
+            SignalingMessage::Answer {
+                session_id, sdp, ..
+            } => {
+                assert_eq!(session_id, call_id.to_string());
+                calls.handle_answer(call_id, sdp).await.unwrap();
+            }
+            other => panic!("expected an answer, got {other:?}"),
+        }
+    }
+}
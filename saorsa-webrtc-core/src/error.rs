@@ -0,0 +1,269 @@
+//! Machine-readable error codes
+//!
+//! Every module keeps its own error enum, but each one maps its variants
+//! onto a stable [`ErrorCode`] through [`CodedError`]. Frontends that can't
+//! see Rust types (the C FFI, Tauri's JSON bridge) get the code plus the
+//! message chain as an [`ErrorReport`].
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// Boxed error used as the `source` of wrapped lower-level failures
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Stable error codes shared by every error type in the crate
+///
+/// Codes are grouped by hundreds per subsystem and never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum ErrorCode {
+    /// Unexpected internal failure
+    Internal = 1,
+    /// Caller passed an invalid argument
+    InvalidInput = 2,
+    /// Operation is not implemented yet
+    NotImplemented = 3,
+    /// Operation did not finish before its deadline
+    Timeout = 4,
+    /// Operation was cancelled by the caller
+    Cancelled = 5,
+    /// Configuration is invalid or cannot be applied
+    InvalidConfig = 6,
+    /// Named resource, such as a conference or room, does not exist
+    NotFound = 7,
+    /// Caller is not allowed to perform the operation
+    PermissionDenied = 8,
+
+    /// No call with the given ID
+    CallNotFound = 100,
+    /// Call is in the wrong state for the operation
+    InvalidCallState = 101,
+    /// Concurrent call limit reached
+    CallLimitReached = 102,
+    /// Peer connection could not be created or updated
+    PeerConnectionFailed = 103,
+    /// SDP offer/answer exchange failed
+    NegotiationFailed = 104,
+    /// ICE candidate was rejected
+    IceFailed = 105,
+
+    /// Capture device not found
+    DeviceNotFound = 200,
+    /// Media track could not be created or found
+    TrackFailed = 201,
+    /// Codec could not be set up
+    CodecFailed = 202,
+    /// Voicemail could not be recorded or decoded
+    VoicemailFailed = 203,
+    /// Snapshot could not be taken
+    SnapshotFailed = 204,
+    /// Recording could not be started or written
+    RecordingFailed = 205,
+    /// Media file could not be played
+    PlaybackFailed = 206,
+    /// No capture device or mode satisfies the constraints
+    Overconstrained = 207,
+
+    /// Transport has not been started
+    TransportNotStarted = 300,
+    /// Remote peer is unknown or not connected
+    PeerNotFound = 301,
+    /// Connection could not be established
+    ConnectionFailed = 302,
+    /// Sending data failed
+    SendFailed = 303,
+    /// Receiving data failed
+    ReceiveFailed = 304,
+    /// Received data could not be decoded
+    MalformedMessage = 305,
+    /// Network path is ruled out by the path policy
+    PathRejected = 306,
+
+    /// No stream with the given ID
+    StreamNotFound = 400,
+    /// Packet exceeds the transport's size limit
+    PacketTooLarge = 401,
+
+    /// Signaling exchange failed
+    SignalingFailed = 500,
+
+    /// Conference operation failed
+    ConferenceFailed = 600,
+    /// Room operation failed
+    RoomFailed = 601,
+    /// Call parking failed
+    ParkingFailed = 602,
+    /// Device handoff failed
+    HandoffFailed = 603,
+    /// Call invitation was refused
+    InvitationFailed = 604,
+    /// Broadcast operation failed
+    BroadcastFailed = 605,
+    /// Call history could not be read or written
+    HistoryFailed = 606,
+}
+
+impl ErrorCode {
+    /// Numeric code, stable across releases
+    #[must_use]
+    pub const fn as_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// Snake-case name, matching the serialized form
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Internal => "internal",
+            Self::InvalidInput => "invalid_input",
+            Self::NotImplemented => "not_implemented",
+            Self::Timeout => "timeout",
+            Self::Cancelled => "cancelled",
+            Self::InvalidConfig => "invalid_config",
+            Self::NotFound => "not_found",
+            Self::PermissionDenied => "permission_denied",
+            Self::CallNotFound => "call_not_found",
+            Self::InvalidCallState => "invalid_call_state",
+            Self::CallLimitReached => "call_limit_reached",
+            Self::PeerConnectionFailed => "peer_connection_failed",
+            Self::NegotiationFailed => "negotiation_failed",
+            Self::IceFailed => "ice_failed",
+            Self::DeviceNotFound => "device_not_found",
+            Self::TrackFailed => "track_failed",
+            Self::CodecFailed => "codec_failed",
+            Self::VoicemailFailed => "voicemail_failed",
+            Self::SnapshotFailed => "snapshot_failed",
+            Self::RecordingFailed => "recording_failed",
+            Self::PlaybackFailed => "playback_failed",
+            Self::Overconstrained => "overconstrained",
+            Self::TransportNotStarted => "transport_not_started",
+            Self::PeerNotFound => "peer_not_found",
+            Self::ConnectionFailed => "connection_failed",
+            Self::SendFailed => "send_failed",
+            Self::ReceiveFailed => "receive_failed",
+            Self::MalformedMessage => "malformed_message",
+            Self::PathRejected => "path_rejected",
+            Self::StreamNotFound => "stream_not_found",
+            Self::PacketTooLarge => "packet_too_large",
+            Self::SignalingFailed => "signaling_failed",
+            Self::ConferenceFailed => "conference_failed",
+            Self::RoomFailed => "room_failed",
+            Self::ParkingFailed => "parking_failed",
+            Self::HandoffFailed => "handoff_failed",
+            Self::InvitationFailed => "invitation_failed",
+            Self::BroadcastFailed => "broadcast_failed",
+            Self::HistoryFailed => "history_failed",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error that carries a machine-readable code
+pub trait CodedError: Error {
+    /// Code identifying this failure
+    fn code(&self) -> ErrorCode;
+
+    /// Serializable summary of this error and its causes
+    fn report(&self) -> ErrorReport
+    where
+        Self: Sized,
+    {
+        ErrorReport::new(self)
+    }
+}
+
+/// Serializable error for frontends without access to Rust types
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// Machine-readable code
+    pub code: ErrorCode,
+    /// Top-level message
+    pub message: String,
+    /// Messages of the underlying causes, outermost first
+    pub causes: Vec<String>,
+}
+
+impl ErrorReport {
+    /// Build a report by walking the error's `source()` chain
+    #[must_use]
+    pub fn new<E: CodedError + ?Sized>(error: &E) -> Self {
+        let mut causes = Vec::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+        Self {
+            code: error.code(),
+            message: error.to_string(),
+            causes,
+        }
+    }
+
+    /// Build a report for a failure with no underlying error
+    #[must_use]
+    pub fn with_message(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            causes: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code, self.message)?;
+        for cause in &self.causes {
+            write!(f, ": {cause}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ErrorReport {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("outer")]
+    struct Outer(#[source] std::io::Error);
+
+    impl CodedError for Outer {
+        fn code(&self) -> ErrorCode {
+            ErrorCode::SendFailed
+        }
+    }
+
+    #[test]
+    fn test_report_walks_source_chain() {
+        let error = Outer(std::io::Error::other("pipe closed"));
+        let report = error.report();
+        assert_eq!(report.code, ErrorCode::SendFailed);
+        assert_eq!(report.message, "outer");
+        assert_eq!(report.causes, vec!["pipe closed".to_string()]);
+        assert_eq!(report.to_string(), "[send_failed] outer: pipe closed");
+    }
+
+    #[test]
+    fn test_code_serialization_matches_name() {
+        for code in [
+            ErrorCode::Internal,
+            ErrorCode::CallLimitReached,
+            ErrorCode::MalformedMessage,
+        ] {
+            let json = serde_json::to_string(&code).unwrap();
+            assert_eq!(json, format!("\"{}\"", code.as_str()));
+        }
+        assert_eq!(ErrorCode::PeerNotFound.as_u32(), 301);
+    }
+}
@@ -0,0 +1,401 @@
+//! Non-lossy typed event bus
+//!
+//! `tokio::sync::broadcast` drops the oldest events when a receiver lags,
+//! which can silently lose things like `IncomingCall`. The bus here gives
+//! every subscriber its own bounded queue, counts overflow per subscriber,
+//! and never drops events that are marked as critical for their topic.
+
+use crate::identity::PeerIdentity;
+use crate::media::MediaEvent;
+use crate::remote_control::RemoteInput;
+use crate::transport::TransportEvent;
+use crate::types::CallEvent;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Default per-subscriber queue capacity
+pub const DEFAULT_EVENT_QUEUE_CAPACITY: usize = 256;
+
+/// Event topics carried by the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventTopic {
+    /// Call lifecycle events
+    Call,
+    /// Media device and stream events
+    Media,
+    /// Transport connectivity events
+    Transport,
+}
+
+/// An event that can be published on the bus
+pub trait TopicEvent: Clone + Send + Sync + 'static {
+    /// Topic this event type belongs to
+    const TOPIC: EventTopic;
+
+    /// Whether this event must never be dropped on overflow
+    fn is_critical(&self) -> bool {
+        false
+    }
+}
+
+impl<I: PeerIdentity> TopicEvent for CallEvent<I> {
+    const TOPIC: EventTopic = EventTopic::Call;
+
+    fn is_critical(&self) -> bool {
+        !matches!(
+            self,
+            Self::QualityChanged { .. }
+                | Self::AnnotationReceived { .. }
+                | Self::RemoteInputReceived {
+                    input: RemoteInput::PointerMove(_),
+                    ..
+                }
+        )
+    }
+}
+
+impl TopicEvent for MediaEvent {
+    const TOPIC: EventTopic = EventTopic::Media;
+
+    fn is_critical(&self) -> bool {
+        // A stall the watchdog gave up on needs the user
+        matches!(
+            self,
+            Self::DeviceDisconnected { .. } | Self::PipelineStalled { recovery: None, .. }
+        )
+    }
+}
+
+impl TopicEvent for TransportEvent {
+    const TOPIC: EventTopic = EventTopic::Transport;
+
+    fn is_critical(&self) -> bool {
+        matches!(self, Self::PeerDisconnected { .. })
+    }
+}
+
+/// Queue shared between a topic channel and one subscriber
+struct SubscriberQueue<E> {
+    events: Mutex<VecDeque<E>>,
+    notify: Notify,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl<E> SubscriberQueue<E> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            notify: Notify::new(),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Receiving side of a topic subscription
+pub struct EventSubscription<E> {
+    queue: Arc<SubscriberQueue<E>>,
+}
+
+impl<E> EventSubscription<E> {
+    /// Wait for the next event
+    ///
+    /// Returns `None` once the bus has been dropped and the queue is drained.
+    pub async fn recv(&mut self) -> Option<E> {
+        loop {
+            if let Some(event) = self.queue.events.lock().pop_front() {
+                return Some(event);
+            }
+            if self.queue.closed.load(Ordering::Acquire) {
+                return None;
+            }
+            self.queue.notify.notified().await;
+        }
+    }
+
+    /// Take the next event if one is queued
+    pub fn try_recv(&mut self) -> Option<E> {
+        self.queue.events.lock().pop_front()
+    }
+
+    /// Number of events currently queued for this subscriber
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.queue.events.lock().len()
+    }
+
+    /// Check whether no events are queued
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of non-critical events dropped because this subscriber lagged
+    #[must_use]
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<E> Drop for EventSubscription<E> {
+    fn drop(&mut self) {
+        self.queue.closed.store(true, Ordering::Release);
+    }
+}
+
+/// A single typed topic with per-subscriber bounded queues
+pub struct TopicChannel<E: TopicEvent> {
+    subscribers: Mutex<Vec<Arc<SubscriberQueue<E>>>>,
+    capacity: usize,
+    overflow: AtomicU64,
+}
+
+impl<E: TopicEvent> TopicChannel<E> {
+    /// Create a channel whose subscribers each buffer up to `capacity` events
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            subscribers: Mutex::new(Vec::new()),
+            capacity: capacity.max(1),
+            overflow: AtomicU64::new(0),
+        }
+    }
+
+    /// Subscribe to this topic
+    #[must_use]
+    pub fn subscribe(&self) -> EventSubscription<E> {
+        let queue = Arc::new(SubscriberQueue::new(self.capacity));
+        self.subscribers.lock().push(Arc::clone(&queue));
+        EventSubscription { queue }
+    }
+
+    /// Publish an event to every live subscriber
+    ///
+    /// Non-critical events are dropped (and counted) for subscribers whose
+    /// queue is full. Critical events are always enqueued, even past the
+    /// capacity, so they are never lost. Returns the number of subscribers
+    /// the event was delivered to.
+    pub fn publish(&self, event: E) -> usize {
+        let critical = event.is_critical();
+        let mut delivered = 0;
+        let mut subscribers = self.subscribers.lock();
+        subscribers.retain(|queue| !queue.closed.load(Ordering::Acquire));
+
+        for queue in subscribers.iter() {
+            let mut events = queue.events.lock();
+            if events.len() >= self.capacity && !critical {
+                queue.dropped.fetch_add(1, Ordering::Relaxed);
+                self.overflow.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    "Event queue full on {:?} topic, dropping non-critical event",
+                    E::TOPIC
+                );
+                continue;
+            }
+            events.push_back(event.clone());
+            drop(events);
+            queue.notify.notify_one();
+            delivered += 1;
+        }
+
+        delivered
+    }
+
+    /// Number of live subscribers
+    #[must_use]
+    pub fn subscriber_count(&self) -> usize {
+        let mut subscribers = self.subscribers.lock();
+        subscribers.retain(|queue| !queue.closed.load(Ordering::Acquire));
+        subscribers.len()
+    }
+
+    /// Total number of events dropped on this topic across all subscribers
+    #[must_use]
+    pub fn overflow_count(&self) -> u64 {
+        self.overflow.load(Ordering::Relaxed)
+    }
+}
+
+impl<E: TopicEvent> Drop for TopicChannel<E> {
+    fn drop(&mut self) {
+        for queue in self.subscribers.lock().iter() {
+            queue.closed.store(true, Ordering::Release);
+            queue.notify.notify_one();
+        }
+    }
+}
+
+/// Overflow counters for each topic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventBusStats {
+    /// Call events dropped
+    pub call_dropped: u64,
+    /// Media events dropped
+    pub media_dropped: u64,
+    /// Transport events dropped
+    pub transport_dropped: u64,
+}
+
+/// Typed event bus with call, media and transport topics
+pub struct EventBus<I: PeerIdentity> {
+    call: Arc<TopicChannel<CallEvent<I>>>,
+    media: Arc<TopicChannel<MediaEvent>>,
+    transport: Arc<TopicChannel<TransportEvent>>,
+}
+
+impl<I: PeerIdentity> EventBus<I> {
+    /// Create a bus with the given per-subscriber queue capacity
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            call: Arc::new(TopicChannel::new(capacity)),
+            media: Arc::new(TopicChannel::new(capacity)),
+            transport: Arc::new(TopicChannel::new(capacity)),
+        }
+    }
+
+    /// Call topic
+    #[must_use]
+    pub fn call(&self) -> &Arc<TopicChannel<CallEvent<I>>> {
+        &self.call
+    }
+
+    /// Media topic
+    #[must_use]
+    pub fn media(&self) -> &Arc<TopicChannel<MediaEvent>> {
+        &self.media
+    }
+
+    /// Transport topic
+    #[must_use]
+    pub fn transport(&self) -> &Arc<TopicChannel<TransportEvent>> {
+        &self.transport
+    }
+
+    /// Subscribe to call events
+    #[must_use]
+    pub fn subscribe_calls(&self) -> EventSubscription<CallEvent<I>> {
+        self.call.subscribe()
+    }
+
+    /// Subscribe to media events
+    #[must_use]
+    pub fn subscribe_media(&self) -> EventSubscription<MediaEvent> {
+        self.media.subscribe()
+    }
+
+    /// Subscribe to transport events
+    #[must_use]
+    pub fn subscribe_transport(&self) -> EventSubscription<TransportEvent> {
+        self.transport.subscribe()
+    }
+
+    /// Overflow counters for all topics
+    #[must_use]
+    pub fn stats(&self) -> EventBusStats {
+        EventBusStats {
+            call_dropped: self.call.overflow_count(),
+            media_dropped: self.media.overflow_count(),
+            transport_dropped: self.transport.overflow_count(),
+        }
+    }
+}
+
+impl<I: PeerIdentity> Default for EventBus<I> {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_QUEUE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::PeerIdentityString;
+    use crate::types::{ByeReason, CallId};
+
+    #[tokio::test]
+    async fn test_event_bus_delivers_to_all_subscribers() {
+        let bus = EventBus::<PeerIdentityString>::new(8);
+        let mut first = bus.subscribe_calls();
+        let mut second = bus.subscribe_calls();
+
+        let call_id = CallId::new();
+        let ended = CallEvent::CallEnded {
+            call_id,
+            reason: ByeReason::HungUp,
+        };
+        assert_eq!(bus.call().publish(ended), 2);
+
+        assert!(matches!(first.recv().await, Some(CallEvent::CallEnded { .. })));
+        assert!(matches!(second.recv().await, Some(CallEvent::CallEnded { .. })));
+    }
+
+    #[test]
+    fn test_event_bus_counts_overflow() {
+        let channel = TopicChannel::<MediaEvent>::new(2);
+        let mut sub = channel.subscribe();
+
+        for i in 0..5 {
+            channel.publish(MediaEvent::StreamStarted {
+                stream_id: format!("stream-{}", i),
+            });
+        }
+
+        assert_eq!(sub.len(), 2);
+        assert_eq!(sub.dropped(), 3);
+        assert_eq!(channel.overflow_count(), 3);
+        assert!(matches!(sub.try_recv(), Some(MediaEvent::StreamStarted { .. })));
+    }
+
+    #[test]
+    fn test_event_bus_never_drops_critical_events() {
+        let bus = EventBus::<PeerIdentityString>::new(1);
+        let mut sub = bus.subscribe_calls();
+
+        for _ in 0..3 {
+            bus.call().publish(CallEvent::CallEnded {
+                call_id: CallId::new(),
+                reason: ByeReason::HungUp,
+            });
+        }
+
+        assert_eq!(sub.len(), 3);
+        assert_eq!(sub.dropped(), 0);
+        assert_eq!(bus.stats(), EventBusStats::default());
+        assert!(sub.try_recv().is_some());
+    }
+
+    #[test]
+    fn test_event_bus_prunes_dropped_subscribers() {
+        let channel = TopicChannel::<TransportEvent>::new(4);
+        let sub = channel.subscribe();
+        assert_eq!(channel.subscriber_count(), 1);
+
+        drop(sub);
+        assert_eq!(channel.subscriber_count(), 0);
+        assert_eq!(
+            channel.publish(TransportEvent::PeerConnected {
+                peer_id: "peer".to_string(),
+            }),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_subscription_ends_when_bus_dropped() {
+        let channel = TopicChannel::<MediaEvent>::new(4);
+        let mut sub = channel.subscribe();
+        channel.publish(MediaEvent::StreamStopped {
+            stream_id: "s".to_string(),
+        });
+        drop(channel);
+
+        assert!(sub.recv().await.is_some());
+        assert!(sub.recv().await.is_none());
+    }
+}
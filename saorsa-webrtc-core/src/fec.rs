@@ -0,0 +1,420 @@
+//! Forward error correction for media packets
+//!
+//! Flexfec-style XOR parity: the sender splits each stream into protection
+//! groups of consecutive packets and sends one repair packet per group
+//! carrying the XOR of every packet in it. A receiver missing exactly one
+//! packet of a group rebuilds it from the rest of the group and the repair
+//! packet, so isolated losses cost no retransmission round trip.
+//!
+//! Repair packets travel on the media SSRC with payload type
+//! [`FEC_PAYLOAD_TYPE`] and the sequence number of the first packet they
+//! protect.
+
+use crate::quic_bridge::{RtpPacket, StreamType};
+use std::collections::{HashMap, VecDeque};
+
+/// Payload type marking repair packets
+pub const FEC_PAYLOAD_TYPE: u8 = 118;
+
+/// Largest protection group, i.e. the lowest useful repair ratio
+pub const MAX_GROUP_SIZE: usize = 48;
+
+/// Repair header: count, payload type, marker, layer, length, timestamp
+const HEADER_LEN: usize = 10;
+
+/// Media packets kept for rebuilding lost ones
+const HISTORY_LEN: usize = 256;
+
+/// Repair packets waiting for enough of their group to arrive
+const MAX_PENDING_REPAIRS: usize = 32;
+
+/// Whether `packet` is a repair packet rather than media
+#[must_use]
+pub fn is_repair(packet: &RtpPacket) -> bool {
+    packet.payload_type == FEC_PAYLOAD_TYPE
+}
+
+/// XOR of the recoverable fields of a group of packets
+#[derive(Debug, Clone, Default)]
+struct Parity {
+    payload_type: u8,
+    marker: bool,
+    temporal_layer: u8,
+    length: u16,
+    timestamp: u32,
+    data: Vec<u8>,
+}
+
+impl Parity {
+    fn absorb(&mut self, packet: &RtpPacket) {
+        self.payload_type ^= packet.payload_type;
+        self.marker ^= packet.marker;
+        self.temporal_layer ^= packet.temporal_layer;
+        self.length ^= packet.payload.len() as u16;
+        self.timestamp ^= packet.timestamp;
+        if self.data.len() < packet.payload.len() {
+            self.data.resize(packet.payload.len(), 0);
+        }
+        for (parity, byte) in self.data.iter_mut().zip(&packet.payload) {
+            *parity ^= byte;
+        }
+    }
+
+    fn to_payload(&self, count: u8) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(HEADER_LEN + self.data.len());
+        payload.push(count);
+        payload.push(self.payload_type);
+        payload.push(u8::from(self.marker));
+        payload.push(self.temporal_layer);
+        payload.extend_from_slice(&self.length.to_be_bytes());
+        payload.extend_from_slice(&self.timestamp.to_be_bytes());
+        payload.extend_from_slice(&self.data);
+        payload
+    }
+}
+
+/// A received repair packet
+#[derive(Debug, Clone)]
+struct Repair {
+    base_seq: u16,
+    count: u8,
+    ssrc: u32,
+    stream_type: StreamType,
+    parity: Parity,
+}
+
+impl Repair {
+    fn parse(packet: &RtpPacket) -> Option<Self> {
+        let payload = &packet.payload;
+        if payload.len() < HEADER_LEN || payload[0] == 0 {
+            return None;
+        }
+        Some(Self {
+            base_seq: packet.sequence_number,
+            count: payload[0],
+            ssrc: packet.ssrc,
+            stream_type: packet.stream_type,
+            parity: Parity {
+                payload_type: payload[1],
+                marker: payload[2] != 0,
+                temporal_layer: payload[3],
+                length: u16::from_be_bytes([payload[4], payload[5]]),
+                timestamp: u32::from_be_bytes([payload[6], payload[7], payload[8], payload[9]]),
+                data: payload[HEADER_LEN..].to_vec(),
+            },
+        })
+    }
+
+    fn covers(&self, seq: u16) -> bool {
+        seq.wrapping_sub(self.base_seq) < u16::from(self.count)
+    }
+
+    fn sequences(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..u16::from(self.count)).map(|i| self.base_seq.wrapping_add(i))
+    }
+}
+
+/// Sender side: emits one repair packet per protection group
+#[derive(Debug, Clone)]
+pub struct FecEncoder {
+    group_size: usize,
+    base_seq: u16,
+    protected: usize,
+    parity: Parity,
+}
+
+impl FecEncoder {
+    /// Protect every `group_size` packets with one repair packet
+    #[must_use]
+    pub fn new(group_size: usize) -> Self {
+        Self {
+            group_size: group_size.clamp(1, MAX_GROUP_SIZE),
+            base_seq: 0,
+            protected: 0,
+            parity: Parity::default(),
+        }
+    }
+
+    /// Encoder sending `ratio` repair packets per media packet
+    ///
+    /// Returns `None` if `ratio` is zero or negative, i.e. FEC is off.
+    #[must_use]
+    pub fn from_ratio(ratio: f32) -> Option<Self> {
+        if ratio.is_nan() || ratio <= 0.0 {
+            return None;
+        }
+        let group_size = (1.0 / ratio.min(1.0)).round() as usize;
+        Some(Self::new(group_size))
+    }
+
+    /// Media packets per protection group
+    #[must_use]
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// Add a sent packet to the current group
+    ///
+    /// Returns the group's repair packet once the group is full. A gap in
+    /// sequence numbers starts a new group, since parity only helps
+    /// receivers that can name every packet it covers.
+    pub fn protect(&mut self, packet: &RtpPacket) -> Option<RtpPacket> {
+        if is_repair(packet) {
+            return None;
+        }
+        let expected = self.base_seq.wrapping_add(self.protected as u16);
+        if self.protected > 0 && packet.sequence_number != expected {
+            self.protected = 0;
+            self.parity = Parity::default();
+        }
+        if self.protected == 0 {
+            self.base_seq = packet.sequence_number;
+        }
+
+        self.parity.absorb(packet);
+        self.protected += 1;
+        if self.protected < self.group_size {
+            return None;
+        }
+
+        let parity = std::mem::take(&mut self.parity);
+        let count = self.protected as u8;
+        self.protected = 0;
+        Some(RtpPacket {
+            version: 2,
+            padding: false,
+            extension: false,
+            csrc_count: 0,
+            marker: false,
+            payload_type: FEC_PAYLOAD_TYPE,
+            sequence_number: self.base_seq,
+            timestamp: packet.timestamp,
+            ssrc: packet.ssrc,
+            payload: parity.to_payload(count),
+            stream_type: packet.stream_type,
+            temporal_layer: 0,
+            timing: None,
+        })
+    }
+}
+
+/// Receiver side: passes media through and rebuilds lost packets
+#[derive(Debug, Default)]
+pub struct FecDecoder {
+    history: HashMap<u16, RtpPacket>,
+    order: VecDeque<u16>,
+    repairs: VecDeque<Repair>,
+    recovered: u64,
+}
+
+impl FecDecoder {
+    /// Create an empty decoder
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Packets rebuilt so far
+    #[must_use]
+    pub fn recovered(&self) -> u64 {
+        self.recovered
+    }
+
+    /// Process a received packet
+    ///
+    /// Returns the media packets to deliver: the packet itself unless it is
+    /// a repair packet or a duplicate, followed by any packets it allowed
+    /// the decoder to rebuild.
+    pub fn receive(&mut self, packet: RtpPacket) -> Vec<RtpPacket> {
+        let mut out = Vec::new();
+        if is_repair(&packet) {
+            match Repair::parse(&packet) {
+                Some(repair) => {
+                    if self.repairs.len() == MAX_PENDING_REPAIRS {
+                        self.repairs.pop_front();
+                    }
+                    self.repairs.push_back(repair);
+                }
+                None => tracing::debug!("Dropping malformed repair packet"),
+            }
+        } else {
+            if self.history.contains_key(&packet.sequence_number) {
+                return out;
+            }
+            self.remember(packet.clone());
+            out.push(packet);
+        }
+        self.recover(&mut out);
+        out
+    }
+
+    fn remember(&mut self, packet: RtpPacket) {
+        if self.order.len() == HISTORY_LEN {
+            if let Some(evicted) = self.order.pop_front() {
+                self.history.remove(&evicted);
+                // A group missing an evicted packet would look short by one
+                // more packet than it is
+                self.repairs.retain(|repair| !repair.covers(evicted));
+            }
+        }
+        self.order.push_back(packet.sequence_number);
+        self.history.insert(packet.sequence_number, packet);
+    }
+
+    fn recover(&mut self, out: &mut Vec<RtpPacket>) {
+        let mut i = 0;
+        while i < self.repairs.len() {
+            let missing: Vec<u16> = self.repairs[i]
+                .sequences()
+                .filter(|seq| !self.history.contains_key(seq))
+                .take(2)
+                .collect();
+            match *missing.as_slice() {
+                [] => {
+                    self.repairs.remove(i);
+                }
+                [seq] => {
+                    if let Some(repair) = self.repairs.remove(i) {
+                        let packet = self.rebuild(&repair, seq);
+                        self.remember(packet.clone());
+                        self.recovered += 1;
+                        out.push(packet);
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    fn rebuild(&self, repair: &Repair, seq: u16) -> RtpPacket {
+        let mut parity = repair.parity.clone();
+        for other in repair.sequences().filter(|&s| s != seq) {
+            if let Some(packet) = self.history.get(&other) {
+                parity.absorb(packet);
+            }
+        }
+        parity.data.truncate(usize::from(parity.length));
+        RtpPacket {
+            version: 2,
+            padding: false,
+            extension: false,
+            csrc_count: 0,
+            marker: parity.marker,
+            payload_type: parity.payload_type,
+            sequence_number: seq,
+            timestamp: parity.timestamp,
+            ssrc: repair.ssrc,
+            payload: parity.data,
+            stream_type: repair.stream_type,
+            temporal_layer: parity.temporal_layer,
+            timing: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media(seq: u16) -> RtpPacket {
+        let len = 20 + usize::from(seq % 7) * 13;
+        RtpPacket::new(
+            96,
+            seq,
+            u32::from(seq) * 3000,
+            0xF00D,
+            vec![seq as u8; len],
+            StreamType::Video,
+        )
+        .unwrap()
+        .with_temporal_layer((seq % 3) as u8)
+    }
+
+    /// Send `count` packets through an encoder, dropping those in `lost`
+    fn transmit(encoder: &mut FecEncoder, count: u16, lost: &[u16]) -> Vec<RtpPacket> {
+        let mut wire = Vec::new();
+        for seq in 0..count {
+            let packet = media(seq);
+            let repair = encoder.protect(&packet);
+            if !lost.contains(&seq) {
+                wire.push(packet);
+            }
+            wire.extend(repair);
+        }
+        wire
+    }
+
+    #[test]
+    fn test_ratio_sets_group_size() {
+        assert!(FecEncoder::from_ratio(0.0).is_none());
+        assert_eq!(FecEncoder::from_ratio(0.2).unwrap().group_size(), 5);
+        assert_eq!(FecEncoder::from_ratio(4.0).unwrap().group_size(), 1);
+        assert_eq!(
+            FecEncoder::from_ratio(0.001).unwrap().group_size(),
+            MAX_GROUP_SIZE
+        );
+    }
+
+    #[test]
+    fn test_one_repair_per_group() {
+        let mut encoder = FecEncoder::new(4);
+        let repairs: Vec<_> = (0..12)
+            .filter_map(|seq| encoder.protect(&media(seq)))
+            .collect();
+        assert_eq!(repairs.len(), 3);
+        assert!(repairs.iter().all(is_repair));
+        assert_eq!(repairs[1].sequence_number, 4);
+    }
+
+    #[test]
+    fn test_recovers_single_loss_per_group() {
+        let mut encoder = FecEncoder::new(5);
+        // 5% loss, spread so each group loses at most one packet
+        let lost = [3, 24, 47, 61, 88];
+        let mut decoder = FecDecoder::new();
+        let delivered: Vec<_> = transmit(&mut encoder, 100, &lost)
+            .into_iter()
+            .flat_map(|packet| decoder.receive(packet))
+            .collect();
+
+        assert_eq!(delivered.len(), 100);
+        assert_eq!(decoder.recovered(), lost.len() as u64);
+        for seq in lost {
+            let rebuilt = delivered
+                .iter()
+                .find(|packet| packet.sequence_number == seq)
+                .unwrap();
+            let original = media(seq);
+            assert_eq!(rebuilt.payload, original.payload);
+            assert_eq!(rebuilt.timestamp, original.timestamp);
+            assert_eq!(rebuilt.temporal_layer, original.temporal_layer);
+        }
+    }
+
+    #[test]
+    fn test_two_losses_in_group_are_not_rebuilt() {
+        let mut encoder = FecEncoder::new(5);
+        let mut decoder = FecDecoder::new();
+        let delivered: Vec<_> = transmit(&mut encoder, 5, &[1, 2])
+            .into_iter()
+            .flat_map(|packet| decoder.receive(packet))
+            .collect();
+
+        assert_eq!(delivered.len(), 3);
+        assert_eq!(decoder.recovered(), 0);
+    }
+
+    #[test]
+    fn test_late_original_is_not_delivered_twice() {
+        let mut encoder = FecEncoder::new(3);
+        let mut wire = transmit(&mut encoder, 3, &[0]);
+        wire.push(media(0));
+
+        let mut decoder = FecDecoder::new();
+        let delivered: Vec<_> = wire
+            .into_iter()
+            .flat_map(|packet| decoder.receive(packet))
+            .collect();
+        assert_eq!(delivered.len(), 3);
+    }
+}
@@ -0,0 +1,1123 @@
+//! Playing media files into calls
+//!
+//! A [`FilePlaybackSource`] reads one track out of an MP4, WebM/Matroska
+//! or Ogg file and decodes it, standing in for a camera or microphone as a
+//! [`FrameSource`] or [`AudioSource`]. The track's pipeline encodes the
+//! frames afresh for the call, so files need not match the negotiated
+//! codecs or bitrate. This suits IVR announcements, hold music and
+//! automated tests of the whole media path.
+//!
+//! Opus audio and H.264 or AV1 video can be played; tracks in other codecs
+//! (AAC, Vorbis, VP8, ...) are refused when the file is opened. Files are
+//! read into memory whole, which suits clips rather than films. Fragmented
+//! MP4 and laced Matroska blocks are not supported.
+
+use crate::audio_clock::AudioClock;
+use crate::error::{CodedError, ErrorCode};
+use crate::media_recorder::{
+    AUDIO, BLOCK, BLOCK_GROUP, CHANNELS, CLUSTER, CODEC_ID, CODEC_PRIVATE, EBML, INFO,
+    PIXEL_HEIGHT, PIXEL_WIDTH, SEGMENT, SIMPLE_BLOCK, TIMESTAMP, TIMESTAMP_SCALE, TRACKS,
+    TRACK_ENTRY, TRACK_NUMBER, TRACK_TYPE, VIDEO,
+};
+use crate::media_source::{AudioSource, FrameSource};
+use bytes::Bytes;
+use saorsa_webrtc_codecs::{
+    AudioDecoder, AudioFrame, Av1Decoder, Channels, OpenH264Decoder, OpusDecoder, VideoDecoder,
+    VideoFrame,
+};
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Frame spacing assumed for tracks too short to measure
+const DEFAULT_AUDIO_SPACING_MS: u64 = 20;
+const DEFAULT_VIDEO_SPACING_MS: u64 = 33;
+
+/// Annex B start code put in front of each H.264 NAL unit
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// File playback errors
+#[derive(Error, Debug)]
+pub enum PlaybackError {
+    /// I/O error
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// File is not in a container we can read
+    #[error("Not an MP4, WebM, Matroska or Ogg file")]
+    UnknownContainer,
+
+    /// Container is corrupt or truncated
+    #[error("Invalid media file: {0}")]
+    Malformed(&'static str),
+
+    /// Container uses a feature we cannot read
+    #[error("Unsupported media file: {0}")]
+    Unsupported(&'static str),
+
+    /// File has no track of the kind asked for
+    #[error("No {0} track in file")]
+    NoTrack(&'static str),
+
+    /// Track is in a codec we cannot decode
+    #[error("Unsupported codec: {0}")]
+    UnsupportedCodec(String),
+
+    /// Decoder could not be set up
+    #[error("Decoder failed: {0}")]
+    Codec(String),
+}
+
+impl CodedError for PlaybackError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnsupportedCodec(_) | Self::Codec(_) => ErrorCode::CodecFailed,
+            Self::Io(_)
+            | Self::UnknownContainer
+            | Self::Malformed(_)
+            | Self::Unsupported(_)
+            | Self::NoTrack(_) => ErrorCode::PlaybackFailed,
+        }
+    }
+}
+
+/// Codecs a file track can be decoded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileCodec {
+    Opus,
+    H264,
+    Av1,
+}
+
+/// Encoded frame read from a file
+#[derive(Debug, Clone)]
+struct Packet {
+    timestamp_ms: u64,
+    keyframe: bool,
+    data: Bytes,
+}
+
+/// One track found in a file
+#[derive(Debug, Default)]
+struct FileTrack {
+    video: bool,
+    /// Codec as the container names it, for errors
+    codec_name: String,
+    codec: Option<FileCodec>,
+    /// `avcC` or `av1C` record of a video track
+    config: Vec<u8>,
+    width: u32,
+    height: u32,
+    channels: u8,
+    /// Samples at 48 kHz to drop from the start of Opus audio
+    pre_skip: u16,
+    packets: Vec<Packet>,
+}
+
+impl FileTrack {
+    /// Spacing of the track's frames, averaged over the whole track
+    fn spacing_ms(&self) -> u64 {
+        let default = if self.video {
+            DEFAULT_VIDEO_SPACING_MS
+        } else {
+            DEFAULT_AUDIO_SPACING_MS
+        };
+        match (self.packets.first(), self.packets.last()) {
+            (Some(first), Some(last)) if self.packets.len() > 1 => {
+                let span = last.timestamp_ms.saturating_sub(first.timestamp_ms);
+                match span / (self.packets.len() as u64 - 1) {
+                    0 => default,
+                    spacing => spacing,
+                }
+            }
+            _ => default,
+        }
+    }
+}
+
+/// Decoder for the track being played
+enum Decoder {
+    Audio {
+        decoder: OpusDecoder,
+        /// Samples per channel still to drop from the start
+        skip: usize,
+    },
+    Video {
+        decoder: Box<dyn VideoDecoder>,
+        codec: FileCodec,
+        /// Parameter sets or sequence header put in front of keyframes
+        prefix: Vec<u8>,
+        /// Bytes in the length prefix of each H.264 NAL unit
+        length_size: usize,
+    },
+}
+
+/// Decodes one track of a media file as a frame or audio source
+///
+/// Open it with [`audio`](Self::audio) or [`video`](Self::video) for the
+/// kind of track it will feed; an audio source yields no video and the
+/// other way round. The source ends with the file unless it is
+/// [looped](Self::with_looping).
+pub struct FilePlaybackSource {
+    packets: Vec<Packet>,
+    next: usize,
+    looped: bool,
+    /// Added to packet timestamps for each pass through a looped file
+    loop_offset_ms: u64,
+    duration_ms: u64,
+    spacing_ms: u64,
+    width: u32,
+    height: u32,
+    decoder: Decoder,
+}
+
+impl FilePlaybackSource {
+    /// Play the first Opus track of `path`, decoded at `clock`'s rate
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the file cannot be read or parsed, has no audio
+    /// track, or its audio is not Opus in mono or stereo
+    pub fn audio(path: impl AsRef<Path>, clock: AudioClock) -> Result<Self, PlaybackError> {
+        let track = select_track(demux(&read(path.as_ref())?)?, false)?;
+        let channels = match track.channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            count => {
+                return Err(PlaybackError::UnsupportedCodec(format!(
+                    "Opus with {count} channels"
+                )))
+            }
+        };
+        let decoder = OpusDecoder::new(clock.sample_rate, channels)
+            .map_err(|e| PlaybackError::Codec(e.to_string()))?;
+        let skip = usize::from(track.pre_skip) * clock.sample_rate.as_hz() as usize / 48_000;
+        Ok(Self::new(track, Decoder::Audio { decoder, skip }))
+    }
+
+    /// Play the first H.264 or AV1 track of `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the file cannot be read or parsed, has no video
+    /// track, its video is in another codec or its decoder fails to start
+    pub fn video(path: impl AsRef<Path>) -> Result<Self, PlaybackError> {
+        let track = select_track(demux(&read(path.as_ref())?)?, true)?;
+        let decoder = match track.codec {
+            Some(FileCodec::Av1) => {
+                let decoder = Av1Decoder::new().map_err(|e| PlaybackError::Codec(e.to_string()))?;
+                let prefix = track.config.get(4..).unwrap_or_default().to_vec();
+                Decoder::Video {
+                    decoder: Box::new(decoder),
+                    codec: FileCodec::Av1,
+                    prefix,
+                    length_size: 0,
+                }
+            }
+            _ => {
+                let decoder =
+                    OpenH264Decoder::new().map_err(|e| PlaybackError::Codec(e.to_string()))?;
+                let (prefix, length_size) = avcc_parameter_sets(&track.config);
+                Decoder::Video {
+                    decoder: Box::new(decoder),
+                    codec: FileCodec::H264,
+                    prefix,
+                    length_size,
+                }
+            }
+        };
+        Ok(Self::new(track, decoder))
+    }
+
+    fn new(track: FileTrack, decoder: Decoder) -> Self {
+        let spacing_ms = track.spacing_ms();
+        let duration_ms = match (track.packets.first(), track.packets.last()) {
+            (Some(first), Some(last)) => {
+                last.timestamp_ms.saturating_sub(first.timestamp_ms) + spacing_ms
+            }
+            _ => 0,
+        };
+        Self {
+            packets: track.packets,
+            next: 0,
+            looped: false,
+            loop_offset_ms: 0,
+            duration_ms,
+            spacing_ms,
+            width: track.width,
+            height: track.height,
+            decoder,
+        }
+    }
+
+    /// Start again from the beginning when the file ends, e.g. for hold
+    /// music
+    #[must_use]
+    pub fn with_looping(mut self, looped: bool) -> Self {
+        self.looped = looped;
+        self
+    }
+
+    /// Length of one pass through the track
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+
+    /// Next packet, wrapping around if looped
+    fn next_packet(&mut self) -> Option<Packet> {
+        if self.next == self.packets.len() {
+            if !self.looped || self.packets.is_empty() {
+                return None;
+            }
+            self.next = 0;
+            self.loop_offset_ms += self.duration_ms;
+        }
+        let mut packet = self.packets[self.next].clone();
+        self.next += 1;
+        packet.timestamp_ms += self.loop_offset_ms;
+        Some(packet)
+    }
+
+    /// Decode packets until one yields a frame
+    ///
+    /// Gives up after failing on every packet in the track, so a looped
+    /// file that cannot be decoded still ends.
+    fn decode_next<F>(
+        &mut self,
+        mut decode: impl FnMut(&mut Decoder, Packet) -> Option<F>,
+    ) -> Option<F> {
+        for _ in 0..=self.packets.len() {
+            let packet = self.next_packet()?;
+            if let Some(frame) = decode(&mut self.decoder, packet) {
+                return Some(frame);
+            }
+        }
+        tracing::warn!("Giving up on a file that does not decode");
+        None
+    }
+}
+
+impl AudioSource for FilePlaybackSource {
+    fn next_frame(&mut self) -> Option<AudioFrame> {
+        self.decode_next(|decoder, packet| {
+            let Decoder::Audio { decoder, skip } = decoder else {
+                return None;
+            };
+            let mut frame = match decoder.decode(&packet.data) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    tracing::debug!("Skipping undecodable audio packet: {}", e);
+                    return None;
+                }
+            };
+            if *skip > 0 {
+                let channels = frame.channels.count();
+                let dropped = (*skip).min(frame.data.len() / channels);
+                frame.data.drain(..dropped * channels);
+                *skip -= dropped;
+            }
+            frame.timestamp = packet.timestamp_ms;
+            (!frame.data.is_empty()).then_some(frame)
+        })
+    }
+}
+
+impl FrameSource for FilePlaybackSource {
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn frame_interval(&self) -> Duration {
+        Duration::from_millis(self.spacing_ms)
+    }
+
+    fn next_frame(&mut self) -> Option<VideoFrame> {
+        self.decode_next(|decoder, packet| {
+            let Decoder::Video {
+                decoder,
+                codec,
+                prefix,
+                length_size,
+            } = decoder
+            else {
+                return None;
+            };
+            let mut unit = Vec::with_capacity(prefix.len() + packet.data.len() + 16);
+            if packet.keyframe {
+                unit.extend_from_slice(prefix);
+            }
+            match codec {
+                FileCodec::H264 => unit.extend(annex_b(&packet.data, *length_size)),
+                _ => unit.extend_from_slice(&packet.data),
+            }
+            match decoder.decode(&unit) {
+                Ok(mut frame) => {
+                    frame.timestamp = packet.timestamp_ms;
+                    Some(frame)
+                }
+                Err(e) => {
+                    tracing::debug!("Skipping undecodable video packet: {}", e);
+                    None
+                }
+            }
+        })
+    }
+}
+
+impl std::fmt::Debug for FilePlaybackSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilePlaybackSource")
+            .field("packets", &self.packets.len())
+            .field("next", &self.next)
+            .field("looped", &self.looped)
+            .field("duration_ms", &self.duration_ms)
+            .finish()
+    }
+}
+
+fn read(path: &Path) -> Result<Bytes, PlaybackError> {
+    Ok(Bytes::from(std::fs::read(path)?))
+}
+
+/// First decodable track of the kind asked for
+fn select_track(tracks: Vec<FileTrack>, video: bool) -> Result<FileTrack, PlaybackError> {
+    let kind = if video { "video" } else { "audio" };
+    let mut tracks = tracks.into_iter().filter(|track| track.video == video);
+    let first = tracks.next().ok_or(PlaybackError::NoTrack(kind))?;
+    if first.codec.is_some() {
+        return Ok(first);
+    }
+    tracks
+        .find(|track| track.codec.is_some())
+        .ok_or(PlaybackError::UnsupportedCodec(first.codec_name))
+}
+
+/// Tracks of a file, in whichever container it is
+fn demux(file: &Bytes) -> Result<Vec<FileTrack>, PlaybackError> {
+    if file.starts_with(&EBML.to_be_bytes()) {
+        demux_matroska(file)
+    } else if file.starts_with(b"OggS") {
+        demux_ogg(file)
+    } else if file.get(4..8) == Some(&b"ftyp"[..]) {
+        demux_mp4(file)
+    } else {
+        Err(PlaybackError::UnknownContainer)
+    }
+}
+
+/// Channel count and pre-skip from an Opus identification header
+fn parse_opus_head(head: &[u8]) -> Option<(u8, u16)> {
+    if !head.starts_with(b"OpusHead") {
+        return None;
+    }
+    let channels = *head.get(9)?;
+    let pre_skip = u16::from_le_bytes(head.get(10..12)?.try_into().ok()?);
+    Some((channels, pre_skip))
+}
+
+/// Samples at 48 kHz in an Opus packet, from its TOC byte (RFC 6716)
+fn opus_packet_samples(packet: &[u8]) -> u64 {
+    let Some(&toc) = packet.first() else {
+        return 0;
+    };
+    let config = usize::from(toc >> 3);
+    let frame = match config {
+        // SILK: 10, 20, 40, 60 ms
+        0..=11 => [480, 960, 1920, 2880][config % 4],
+        // Hybrid: 10, 20 ms
+        12..=15 => [480, 960][config % 2],
+        // CELT: 2.5, 5, 10, 20 ms
+        _ => [120, 240, 480, 960][config % 4],
+    };
+    let frames = match toc & 3 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => packet.get(1).map_or(0, |count| u64::from(count & 0x3F)),
+    };
+    frame * frames
+}
+
+/// Tracks of an Ogg file, one per logical stream
+fn demux_ogg(file: &Bytes) -> Result<Vec<FileTrack>, PlaybackError> {
+    let truncated = || PlaybackError::Malformed("truncated Ogg page");
+    // Track, packet being reassembled and position in 48 kHz samples, by
+    // stream serial number
+    let mut streams: Vec<(u32, FileTrack, Vec<u8>, u64)> = Vec::new();
+    let mut pos = 0;
+    while pos < file.len() {
+        let Some(header) = file.get(pos..pos + 27) else {
+            return Err(truncated());
+        };
+        if !header.starts_with(b"OggS") {
+            return Err(PlaybackError::Malformed("lost Ogg page sync"));
+        }
+        let serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+        let segments = usize::from(header[26]);
+        let Some(lacing) = file.get(pos + 27..pos + 27 + segments) else {
+            return Err(truncated());
+        };
+        let index = match streams.iter().position(|(s, ..)| *s == serial) {
+            Some(index) => index,
+            None => {
+                streams.push((serial, FileTrack::default(), Vec::new(), 0));
+                streams.len() - 1
+            }
+        };
+        let (_, track, partial, position) = &mut streams[index];
+        let mut body = pos + 27 + segments;
+        for &len in lacing {
+            let Some(segment) = file.get(body..body + usize::from(len)) else {
+                return Err(truncated());
+            };
+            partial.extend_from_slice(segment);
+            body += usize::from(len);
+            // Packets continue through segments of 255 bytes
+            if len < 255 {
+                ogg_packet(track, std::mem::take(partial), position);
+            }
+        }
+        pos = body;
+    }
+    Ok(streams.into_iter().map(|(_, track, ..)| track).collect())
+}
+
+/// Add a reassembled Ogg packet to its stream's track
+fn ogg_packet(track: &mut FileTrack, packet: Vec<u8>, position: &mut u64) {
+    if track.codec_name.is_empty() {
+        // The first packet identifies the codec
+        if let Some((channels, pre_skip)) = parse_opus_head(&packet) {
+            track.codec = Some(FileCodec::Opus);
+            track.codec_name = "Opus".to_string();
+            track.channels = channels;
+            track.pre_skip = pre_skip;
+        } else if packet.get(1..7) == Some(&b"theora"[..]) {
+            track.video = true;
+            track.codec_name = "Theora".to_string();
+        } else if packet.get(1..7) == Some(&b"vorbis"[..]) {
+            track.codec_name = "Vorbis".to_string();
+        } else {
+            track.codec_name = "unknown".to_string();
+        }
+        return;
+    }
+    if track.codec != Some(FileCodec::Opus) || packet.starts_with(b"OpusTags") {
+        return;
+    }
+    let samples = opus_packet_samples(&packet);
+    track.packets.push(Packet {
+        timestamp_ms: *position * 1000 / 48_000,
+        keyframe: true,
+        data: Bytes::from(packet),
+    });
+    *position += samples;
+}
+
+/// EBML variable-length integer at `pos`: its value with the length marker
+/// still set, and its length
+fn ebml_vint(data: &[u8], pos: usize) -> Option<(u64, usize)> {
+    let first = *data.get(pos)?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+    let bytes = data.get(pos..pos + len)?;
+    let value = bytes
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+    Some((value, len))
+}
+
+/// Big-endian unsigned integer of up to eight bytes
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .take(8)
+        .fold(0u64, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+/// Tracks of a Matroska or WebM file
+///
+/// Elements are walked in one flat pass: the masters we need are entered
+/// rather than skipped, so segments and clusters of unknown size, as live
+/// recordings write, read like any other.
+fn demux_matroska(file: &Bytes) -> Result<Vec<FileTrack>, PlaybackError> {
+    const ENTERED: [u32; 8] = [
+        SEGMENT,
+        INFO,
+        TRACKS,
+        TRACK_ENTRY,
+        VIDEO,
+        AUDIO,
+        CLUSTER,
+        BLOCK_GROUP,
+    ];
+    let mut tracks: Vec<(u64, FileTrack)> = Vec::new();
+    let mut scale_ns = 1_000_000;
+    let mut cluster_ts = 0;
+    let mut pos = 0;
+    while pos < file.len() {
+        let (id, id_len) =
+            ebml_vint(file, pos).ok_or(PlaybackError::Malformed("bad element ID"))?;
+        let (size, size_len) =
+            ebml_vint(file, pos + id_len).ok_or(PlaybackError::Malformed("bad element size"))?;
+        let body = pos + id_len + size_len;
+        let id = id as u32;
+        let marker = 1u64 << (7 * size_len);
+        let size = size & !marker;
+        if ENTERED.contains(&id) {
+            if id == TRACK_ENTRY {
+                tracks.push((0, FileTrack::default()));
+            }
+            pos = body;
+            continue;
+        }
+        if size == marker - 1 {
+            return Err(PlaybackError::Malformed("unknown size outside a master"));
+        }
+        let Some(end) = usize::try_from(size)
+            .ok()
+            .and_then(|size| body.checked_add(size))
+            .filter(|end| *end <= file.len())
+        else {
+            // A recording cut short still plays up to its last whole element
+            tracing::debug!("Matroska file ends inside an element");
+            break;
+        };
+        let value = &file[body..end];
+        pos = end;
+        if id == SIMPLE_BLOCK || id == BLOCK {
+            let (number, len) = ebml_vint(value, 0).ok_or(PlaybackError::Malformed("bad block"))?;
+            let number = number & !(1u64 << (7 * len));
+            let Some(head) = value.get(len..len + 3) else {
+                return Err(PlaybackError::Malformed("bad block"));
+            };
+            if head[2] & 0x06 != 0 {
+                return Err(PlaybackError::Unsupported("laced Matroska blocks"));
+            }
+            let offset = i64::from(i16::from_be_bytes([head[0], head[1]]));
+            let cluster_ts = i64::try_from(cluster_ts).unwrap_or(i64::MAX);
+            let ticks = cluster_ts.saturating_add(offset).max(0) as u64;
+            if let Some((_, track)) = tracks.iter_mut().find(|(n, _)| *n == number) {
+                track.packets.push(Packet {
+                    timestamp_ms: ticks.saturating_mul(scale_ns) / 1_000_000,
+                    // Only simple blocks flag keyframes
+                    keyframe: id == SIMPLE_BLOCK && head[2] & 0x80 != 0,
+                    data: file.slice(body + len + 3..end),
+                });
+            }
+            continue;
+        }
+        match (id, tracks.last_mut()) {
+            (TIMESTAMP_SCALE, _) => scale_ns = be_uint(value),
+            (TIMESTAMP, _) => cluster_ts = be_uint(value),
+            (TRACK_NUMBER, Some((number, _))) => *number = be_uint(value),
+            (TRACK_TYPE, Some((_, track))) => track.video = be_uint(value) == 1,
+            (CODEC_ID, Some((_, track))) => {
+                track.codec_name = String::from_utf8_lossy(value).into_owned();
+                track.codec = match track.codec_name.as_str() {
+                    "A_OPUS" => Some(FileCodec::Opus),
+                    "V_MPEG4/ISO/AVC" => Some(FileCodec::H264),
+                    "V_AV1" => Some(FileCodec::Av1),
+                    _ => None,
+                };
+            }
+            (CODEC_PRIVATE, Some((_, track))) => {
+                if let Some((channels, pre_skip)) = parse_opus_head(value) {
+                    track.channels = channels;
+                    track.pre_skip = pre_skip;
+                }
+                track.config = value.to_vec();
+            }
+            (PIXEL_WIDTH, Some((_, track))) => track.width = be_uint(value) as u32,
+            (PIXEL_HEIGHT, Some((_, track))) => track.height = be_uint(value) as u32,
+            (CHANNELS, Some((_, track))) => track.channels = be_uint(value) as u8,
+            _ => {}
+        }
+    }
+    Ok(tracks.into_iter().map(|(_, track)| track).collect())
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Boxes directly inside `data` as (type, body)
+fn mp4_boxes(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+    while let (Some(size), Some(kind)) = (be_u32(data, pos), data.get(pos + 4..pos + 8)) {
+        let kind = [kind[0], kind[1], kind[2], kind[3]];
+        let (header, size) = match size {
+            0 => (8, (data.len() - pos) as u64),
+            1 => match be_u64(data, pos + 8) {
+                Some(size) => (16, size),
+                None => break,
+            },
+            size => (8, u64::from(size)),
+        };
+        let Some(end) = usize::try_from(size)
+            .ok()
+            .and_then(|size| pos.checked_add(size))
+            .filter(|end| *end <= data.len() && *end >= pos + header)
+        else {
+            break;
+        };
+        boxes.push((kind, &data[pos + header..end]));
+        pos = end;
+    }
+    boxes
+}
+
+/// Body of the first box of `kind` directly inside `data`
+fn mp4_child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    mp4_boxes(data)
+        .into_iter()
+        .find(|(k, _)| k == kind)
+        .map(|(_, body)| body)
+}
+
+/// Tracks of an MP4 file, read from its sample tables
+fn demux_mp4(file: &Bytes) -> Result<Vec<FileTrack>, PlaybackError> {
+    let top = mp4_boxes(file);
+    let Some((_, moov)) = top.iter().find(|(kind, _)| kind == b"moov") else {
+        if top.iter().any(|(kind, _)| kind == b"moof") {
+            return Err(PlaybackError::Unsupported("fragmented MP4"));
+        }
+        return Err(PlaybackError::Malformed("no moov box"));
+    };
+    let mut tracks = Vec::new();
+    for (_, trak) in mp4_boxes(moov).into_iter().filter(|(k, _)| k == b"trak") {
+        if let Some(track) = mp4_track(file, trak)? {
+            tracks.push(track);
+        }
+    }
+    Ok(tracks)
+}
+
+/// Audio or video track described by a `trak` box
+fn mp4_track(file: &Bytes, trak: &[u8]) -> Result<Option<FileTrack>, PlaybackError> {
+    let bad = PlaybackError::Malformed;
+    let mdia = mp4_child(trak, b"mdia").ok_or(bad("no mdia box"))?;
+    let hdlr = mp4_child(mdia, b"hdlr").ok_or(bad("no hdlr box"))?;
+    let video = match hdlr.get(8..12) {
+        Some(b"vide") => true,
+        Some(b"soun") => false,
+        _ => return Ok(None),
+    };
+    let mdhd = mp4_child(mdia, b"mdhd").ok_or(bad("no mdhd box"))?;
+    let timescale = match mdhd.first().copied() {
+        Some(1) => be_u32(mdhd, 20),
+        _ => be_u32(mdhd, 12),
+    }
+    .filter(|scale| *scale > 0)
+    .ok_or(bad("bad mdhd box"))?;
+    let stbl = mp4_child(mdia, b"minf")
+        .and_then(|minf| mp4_child(minf, b"stbl"))
+        .ok_or(bad("no sample table"))?;
+
+    let mut track = FileTrack {
+        video,
+        ..FileTrack::default()
+    };
+    let stsd = mp4_child(stbl, b"stsd").ok_or(bad("no stsd box"))?;
+    let (kind, entry) = mp4_boxes(stsd.get(8..).unwrap_or_default())
+        .into_iter()
+        .next()
+        .ok_or(bad("no sample description"))?;
+    track.codec_name = String::from_utf8_lossy(&kind).into_owned();
+    match &kind {
+        b"avc1" | b"avc3" | b"av01" => {
+            track.width = u32::from(be_u16(entry, 24).unwrap_or(0));
+            track.height = u32::from(be_u16(entry, 26).unwrap_or(0));
+            let (codec, config) = if kind == *b"av01" {
+                (FileCodec::Av1, b"av1C")
+            } else {
+                (FileCodec::H264, b"avcC")
+            };
+            track.codec = Some(codec);
+            track.config = entry
+                .get(78..)
+                .and_then(|children| mp4_child(children, config))
+                .unwrap_or_default()
+                .to_vec();
+        }
+        b"Opus" => {
+            track.codec = Some(FileCodec::Opus);
+            let dops = entry
+                .get(28..)
+                .and_then(|children| mp4_child(children, b"dOps"))
+                .ok_or(bad("no dOps box"))?;
+            track.channels = *dops.get(1).ok_or(bad("bad dOps box"))?;
+            track.pre_skip = be_u16(dops, 2).ok_or(bad("bad dOps box"))?;
+        }
+        _ => {}
+    }
+
+    // Sample sizes
+    let stsz = mp4_child(stbl, b"stsz").ok_or(bad("no stsz box"))?;
+    let fixed = be_u32(stsz, 4).ok_or(bad("bad stsz box"))?;
+    let count = be_u32(stsz, 8).ok_or(bad("bad stsz box"))? as usize;
+    // Bound counts read from the file by the bytes backing them, so a
+    // few bytes cannot ask for billions of samples
+    let backed = match fixed {
+        0 => stsz.len().saturating_sub(12) / 4,
+        size => file.len() / size as usize,
+    };
+    if count > backed {
+        return Err(bad("bad stsz box"));
+    }
+    let sizes = (0..count)
+        .map(|i| match fixed {
+            0 => be_u32(stsz, 12 + 4 * i),
+            size => Some(size),
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(bad("bad stsz box"))?;
+
+    // Chunk offsets and how many samples each chunk holds
+    let offsets = if let Some(stco) = mp4_child(stbl, b"stco") {
+        let chunks = be_u32(stco, 4).unwrap_or(0) as usize;
+        (0..chunks)
+            .map(|i| be_u32(stco, 8 + 4 * i).map(u64::from))
+            .collect::<Option<Vec<_>>>()
+    } else {
+        let co64 = mp4_child(stbl, b"co64").ok_or(bad("no chunk offsets"))?;
+        let chunks = be_u32(co64, 4).unwrap_or(0) as usize;
+        (0..chunks).map(|i| be_u64(co64, 8 + 8 * i)).collect()
+    }
+    .ok_or(bad("bad chunk offsets"))?;
+    let stsc = mp4_child(stbl, b"stsc").ok_or(bad("no stsc box"))?;
+    let runs = (0..be_u32(stsc, 4).unwrap_or(0) as usize)
+        .map(|i| Some((be_u32(stsc, 8 + 12 * i)?, be_u32(stsc, 12 + 12 * i)?)))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(bad("bad stsc box"))?;
+
+    // Decode times, and keyframes if not every sample is one
+    let stts = mp4_child(stbl, b"stts").ok_or(bad("no stts box"))?;
+    let mut deltas = Vec::with_capacity(count);
+    for i in 0..be_u32(stts, 4).unwrap_or(0) as usize {
+        let run = be_u32(stts, 8 + 8 * i).ok_or(bad("bad stts box"))?;
+        let delta = be_u32(stts, 12 + 8 * i).ok_or(bad("bad stts box"))?;
+        // Runs past the last sample time nothing
+        let run = (run as usize).min(count - deltas.len());
+        deltas.extend(std::iter::repeat_n(u64::from(delta), run));
+    }
+    let sync: Option<Vec<u32>> = mp4_child(stbl, b"stss").map(|stss| {
+        (0..be_u32(stss, 4).unwrap_or(0) as usize)
+            .filter_map(|i| be_u32(stss, 8 + 4 * i))
+            .collect()
+    });
+
+    let mut sample = 0;
+    let mut decode_time = 0u64;
+    for (chunk, offset) in offsets.iter().enumerate() {
+        let chunk_number = chunk as u32 + 1;
+        let per_chunk = runs
+            .iter()
+            .take_while(|(first, _)| *first <= chunk_number)
+            .last()
+            .map_or(0, |(_, samples)| *samples);
+        let mut offset = usize::try_from(*offset).map_err(|_| bad("sample outside the file"))?;
+        for _ in 0..per_chunk {
+            let Some(&size) = sizes.get(sample) else {
+                break;
+            };
+            let end = offset
+                .checked_add(size as usize)
+                .filter(|end| *end <= file.len())
+                .ok_or(bad("sample outside the file"))?;
+            track.packets.push(Packet {
+                timestamp_ms: decode_time.saturating_mul(1000) / u64::from(timescale),
+                keyframe: sync
+                    .as_ref()
+                    .is_none_or(|sync| sync.contains(&(sample as u32 + 1))),
+                data: file.slice(offset..end),
+            });
+            decode_time = decode_time.saturating_add(deltas.get(sample).copied().unwrap_or(0));
+            offset = end;
+            sample += 1;
+        }
+    }
+    Ok(Some(track))
+}
+
+/// Parameter sets of an `avcC` record in Annex B form, and the length of
+/// the NAL unit size fields it announces
+///
+/// Without a record, as from a camera that puts parameter sets in band,
+/// four-byte sizes are assumed.
+fn avcc_parameter_sets(avcc: &[u8]) -> (Vec<u8>, usize) {
+    let Some(&sizes) = avcc.get(4) else {
+        return (Vec::new(), 4);
+    };
+    let length_size = usize::from(sizes & 0x03) + 1;
+    let mut prefix = Vec::new();
+    let mut pos = 5;
+    // SPS count is in the low five bits, PPS count is a whole byte
+    for mask in [0x1F, 0xFF] {
+        let Some(&count) = avcc.get(pos) else {
+            break;
+        };
+        pos += 1;
+        for _ in 0..(count & mask) {
+            let Some(len) = be_u16(avcc, pos) else {
+                break;
+            };
+            let Some(unit) = avcc.get(pos + 2..pos + 2 + usize::from(len)) else {
+                break;
+            };
+            prefix.extend_from_slice(&START_CODE);
+            prefix.extend_from_slice(unit);
+            pos += 2 + usize::from(len);
+        }
+    }
+    (prefix, length_size)
+}
+
+/// H.264 frame of length-prefixed NAL units in Annex B form
+fn annex_b(data: &[u8], length_size: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 16);
+    let mut pos = 0;
+    while let Some(len) = data.get(pos..pos + length_size).map(be_uint) {
+        let start = pos + length_size;
+        let Some(unit) = usize::try_from(len)
+            .ok()
+            .and_then(|len| data.get(start..start + len))
+        else {
+            break;
+        };
+        out.extend_from_slice(&START_CODE);
+        out.extend_from_slice(unit);
+        pos = start + unit.len();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media_recorder::{MediaRecorder, RecordingOptions, RecordingSource};
+    use saorsa_webrtc_codecs::{AudioEncoder, OpusEncoder, OpusEncoderConfig, SampleRate};
+
+    fn ogg_page(serial: u32, packets: &[&[u8]]) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        for packet in packets {
+            lacing.extend(std::iter::repeat_n(255, packet.len() / 255));
+            lacing.push((packet.len() % 255) as u8);
+        }
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0; 10]);
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&[0; 8]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+        page
+    }
+
+    #[test]
+    fn test_ogg_opus_packets_are_reassembled_and_timed() {
+        let mut head = b"OpusHead".to_vec();
+        head.extend_from_slice(&[1, 2, 0x38, 0x01, 0x80, 0xBB, 0, 0, 0, 0, 0]);
+        // CELT 20 ms, one frame; then CELT 10 ms, two frames
+        let long = [[0xF8].as_slice(), &[0xAA; 300]].concat();
+        let double = [0xF1, 0xBB];
+        let mut file = ogg_page(7, &[&head]);
+        file.extend(ogg_page(7, &[b"OpusTags"]));
+        file.extend(ogg_page(7, &[&long, &double, &[0xF8, 0xCC]]));
+
+        let tracks = demux(&Bytes::from(file)).unwrap();
+        let track = select_track(tracks, false).unwrap();
+        assert_eq!(track.codec, Some(FileCodec::Opus));
+        assert_eq!((track.channels, track.pre_skip), (2, 312));
+        let timing: Vec<_> = track
+            .packets
+            .iter()
+            .map(|packet| (packet.timestamp_ms, packet.data.len()))
+            .collect();
+        assert_eq!(timing, [(0, 301), (20, 2), (40, 2)]);
+        assert_eq!(track.spacing_ms(), 20);
+        assert!(matches!(
+            demux(&Bytes::from_static(b"RIFF....WAVE")),
+            Err(PlaybackError::UnknownContainer)
+        ));
+    }
+
+    #[test]
+    fn test_recorded_audio_plays_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hold.webm");
+        let options = RecordingOptions {
+            remote: false,
+            ..RecordingOptions::default()
+        };
+        let mut recorder = MediaRecorder::new(&path, options, true, None).unwrap();
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig {
+            channels: Channels::Mono,
+            ..OpusEncoderConfig::default()
+        })
+        .unwrap();
+        for (i, timestamp) in [0, 20, 40].into_iter().enumerate() {
+            let frame = AudioFrame {
+                data: vec![1000 * (i as i16 + 1); 960],
+                sample_rate: SampleRate::Hz48000,
+                channels: Channels::Mono,
+                timestamp,
+            };
+            let encoded = encoder.encode(&frame).unwrap();
+            recorder
+                .write_audio(RecordingSource::Local, timestamp, &encoded)
+                .unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut source = FilePlaybackSource::audio(&path, AudioClock::default())
+            .unwrap()
+            .with_looping(true);
+        assert_eq!(source.duration(), Duration::from_millis(60));
+        // The first frame loses the encoder's pre-skip
+        let first = AudioSource::next_frame(&mut source).unwrap();
+        assert_eq!(first.data.len(), 960 - 312);
+        assert_eq!(AudioSource::next_frame(&mut source).unwrap().timestamp, 20);
+        assert_eq!(AudioSource::next_frame(&mut source).unwrap().timestamp, 40);
+        // Looping comes round to a whole first frame
+        let again = AudioSource::next_frame(&mut source).unwrap();
+        assert_eq!((again.timestamp, again.data.len()), (60, 960));
+
+        assert!(matches!(
+            FilePlaybackSource::video(&path),
+            Err(PlaybackError::NoTrack("video"))
+        ));
+    }
+
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// Full box body: version and flags, then 32-bit fields
+    fn full_box(kind: &[u8; 4], fields: &[u32]) -> Vec<u8> {
+        let body: Vec<u8> = std::iter::once(0)
+            .chain(fields.iter().copied())
+            .flat_map(u32::to_be_bytes)
+            .collect();
+        mp4_box(kind, &body)
+    }
+
+    const SPS: [u8; 4] = [0x67, 0x42, 0xC0, 0x1F];
+    const PPS: [u8; 2] = [0x68, 0xCE];
+
+    /// Three samples of 6 bytes: a keyframe and two deltas
+    const SAMPLES: [[u8; 6]; 3] = [
+        [0, 0, 0, 2, 0x65, 0x88],
+        [0, 0, 0, 2, 0x41, 0x9A],
+        [0, 0, 0, 2, 0x41, 0x9B],
+    ];
+
+    /// An H.264 MP4 of [`SAMPLES`] with the given sample table boxes, the
+    /// chunk offsets made from where the samples start
+    fn h264_mp4(stts: &[u32], stsz: &[u32], chunk_offsets: impl FnOnce(u32) -> Vec<u8>) -> Bytes {
+        let mut avcc = vec![1, 0x42, 0xC0, 0x1F, 0xFF, 0xE1, 0, 4];
+        avcc.extend_from_slice(&SPS);
+        avcc.extend_from_slice(&[1, 0, 2]);
+        avcc.extend_from_slice(&PPS);
+        let mut avc1 = vec![0; 78];
+        avc1[24..28].copy_from_slice(&[0x01, 0x40, 0x00, 0xF0]);
+        avc1.extend(mp4_box(b"avcC", &avcc));
+
+        let ftyp = mp4_box(b"ftyp", b"isomiso2");
+        let mdat_start = ftyp.len() as u32 + 8;
+        let mut mdhd = vec![0; 12];
+        mdhd.extend_from_slice(&90_000u32.to_be_bytes());
+        mdhd.extend_from_slice(&[0; 8]);
+        let mut hdlr = vec![0; 8];
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 13]);
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(b"avc1", &avc1));
+        let stbl = [
+            mp4_box(b"stsd", &stsd),
+            full_box(b"stts", stts),
+            full_box(b"stsc", &[1, 1, 3, 1]),
+            full_box(b"stsz", stsz),
+            chunk_offsets(mdat_start),
+            full_box(b"stss", &[1, 1]),
+        ]
+        .concat();
+        let mdia = [
+            mp4_box(b"mdhd", &mdhd),
+            mp4_box(b"hdlr", &hdlr),
+            mp4_box(b"minf", &mp4_box(b"stbl", &stbl)),
+        ]
+        .concat();
+        let moov = mp4_box(b"moov", &mp4_box(b"trak", &mp4_box(b"mdia", &mdia)));
+        Bytes::from([ftyp, mp4_box(b"mdat", &SAMPLES.concat()), moov].concat())
+    }
+
+    #[test]
+    fn test_mp4_h264_samples_become_annex_b() {
+        // Samples 40 ms apart
+        let file = h264_mp4(&[1, 3, 3600], &[6, 3], |start| {
+            full_box(b"stco", &[1, start])
+        });
+        let track = select_track(demux(&file).unwrap(), true).unwrap();
+        assert_eq!(track.codec, Some(FileCodec::H264));
+        assert_eq!((track.width, track.height), (320, 240));
+        let packets: Vec<_> = track
+            .packets
+            .iter()
+            .map(|packet| (packet.timestamp_ms, packet.keyframe, &packet.data[..]))
+            .collect();
+        assert_eq!(
+            packets,
+            [
+                (0, true, &SAMPLES[0][..]),
+                (40, false, &SAMPLES[1][..]),
+                (80, false, &SAMPLES[2][..]),
+            ]
+        );
+
+        let (prefix, length_size) = avcc_parameter_sets(&track.config);
+        assert_eq!(length_size, 4);
+        assert_eq!(prefix, [&START_CODE[..], &SPS, &START_CODE, &PPS].concat());
+        assert_eq!(annex_b(&SAMPLES[0], length_size), [0, 0, 0, 1, 0x65, 0x88]);
+    }
+
+    #[test]
+    fn test_mp4_counts_are_bounded_by_the_file() {
+        let stco = |start| full_box(b"stco", &[1, start]);
+        // Billions of fixed-size or listed samples in a few bytes
+        for stsz in [[6, u32::MAX], [0, u32::MAX]] {
+            assert!(matches!(
+                demux(&h264_mp4(&[1, 3, 3600], &stsz, stco)),
+                Err(PlaybackError::Malformed(_))
+            ));
+        }
+        // A run of decode times longer than the track times only its samples
+        let track = demux(&h264_mp4(&[1, u32::MAX, 3600], &[6, 3], stco)).unwrap();
+        assert_eq!(select_track(track, true).unwrap().packets.len(), 3);
+        // A chunk offset past the end of memory
+        let co64 = |_| {
+            let mut body = vec![0, 0, 0, 0, 0, 0, 0, 1];
+            body.extend_from_slice(&(u64::MAX - 2).to_be_bytes());
+            mp4_box(b"co64", &body)
+        };
+        assert!(matches!(
+            demux(&h264_mp4(&[1, 3, 3600], &[6, 3], co64)),
+            Err(PlaybackError::Malformed(_))
+        ));
+    }
+}
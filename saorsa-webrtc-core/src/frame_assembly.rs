@@ -0,0 +1,377 @@
+//! Video frame reassembly
+//!
+//! A [`Packetizer`](crate::quic_bridge::Packetizer) splits each encoded
+//! video frame into packets sharing its RTP timestamp, the last one
+//! carrying the marker bit. Over QUIC datagrams they arrive reordered,
+//! late or not at all. The [`VideoFrameAssembler`] collects one stream's
+//! packets, hands out each frame once every packet from its first to its
+//! marker is in, and gives up on a frame still incomplete when its
+//! deadline passes: a decoder can do nothing with part of a frame, and
+//! waiting longer only delays the frames behind it.
+//!
+//! Frames come out in order. Once one is dropped, the next is flagged
+//! [`AssembledFrame::after_loss`], since a decoder of predicted frames
+//! then needs a keyframe. Frames are expected in timestamp order, as
+//! real-time encoders without B-frames produce them.
+
+use crate::latency::FrameTiming;
+use crate::quic_bridge::{RtpPacket, StreamConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Frame reassembly settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameAssemblyConfig {
+    /// How long after its first packet a frame may wait for the rest
+    /// before it is dropped
+    pub max_wait: Duration,
+    /// Packets held at most; the oldest frame is dropped beyond this
+    pub max_packets: usize,
+}
+
+impl FrameAssemblyConfig {
+    /// Wait as long as `stream` tolerates latency
+    #[must_use]
+    pub fn for_stream(stream: &StreamConfig) -> Self {
+        Self {
+            max_wait: Duration::from_millis(u64::from(stream.max_latency_ms)),
+            max_packets: 2048,
+        }
+    }
+}
+
+impl Default for FrameAssemblyConfig {
+    fn default() -> Self {
+        Self::for_stream(&StreamConfig::video())
+    }
+}
+
+/// Frame reassembly counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameAssemblyStats {
+    /// Frames handed out whole
+    pub assembled: u64,
+    /// Frames given up on, past their deadline or to make room
+    pub dropped: u64,
+    /// Packets of frames already handed out or dropped
+    pub late: u64,
+    /// Packets received more than once
+    pub duplicates: u64,
+}
+
+/// An encoded frame put back together from its packets
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembledFrame {
+    /// Stream the frame belongs to
+    pub ssrc: u32,
+    /// RTP timestamp shared by the frame's packets
+    pub timestamp: u32,
+    /// Encoded frame, still encrypted if frame encryption is on
+    pub data: Vec<u8>,
+    /// Temporal layer of the frame
+    pub temporal_layer: u8,
+    /// Stage timestamps carried by the frame's packets
+    pub timing: Option<FrameTiming>,
+    /// Whether frames before this one were dropped
+    pub after_loss: bool,
+}
+
+/// Reassembles the encoded frames of one video stream
+#[derive(Debug)]
+pub struct VideoFrameAssembler {
+    config: FrameAssemblyConfig,
+    packets: HashMap<u16, RtpPacket>,
+    /// When the first packet of each pending frame arrived, by timestamp
+    arrivals: HashMap<u32, Instant>,
+    /// First sequence number of the next frame, when known
+    next_seq: Option<u16>,
+    /// Timestamp of the last frame handed out or dropped
+    last_timestamp: Option<u32>,
+    after_loss: bool,
+    stats: FrameAssemblyStats,
+}
+
+impl VideoFrameAssembler {
+    /// Create an empty assembler
+    #[must_use]
+    pub fn new(config: FrameAssemblyConfig) -> Self {
+        Self {
+            config,
+            packets: HashMap::new(),
+            arrivals: HashMap::new(),
+            next_seq: None,
+            last_timestamp: None,
+            after_loss: false,
+            stats: FrameAssemblyStats::default(),
+        }
+    }
+
+    /// Counters since creation
+    #[must_use]
+    pub fn stats(&self) -> FrameAssemblyStats {
+        self.stats
+    }
+
+    /// Packets waiting for the rest of their frame
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Whether no packets are waiting
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Queue a packet received just now
+    pub fn push(&mut self, packet: RtpPacket) {
+        self.push_at(packet, Instant::now());
+    }
+
+    /// Queue a packet received at `arrived`
+    pub fn push_at(&mut self, packet: RtpPacket, arrived: Instant) {
+        if self.is_past(packet.timestamp) {
+            self.stats.late += 1;
+            return;
+        }
+        if self.packets.contains_key(&packet.sequence_number) {
+            self.stats.duplicates += 1;
+            return;
+        }
+        self.arrivals.entry(packet.timestamp).or_insert(arrived);
+        self.packets.insert(packet.sequence_number, packet);
+
+        while self.packets.len() > self.config.max_packets.max(1) {
+            self.drop_oldest();
+        }
+    }
+
+    /// Next frame to decode, if one is complete
+    ///
+    /// Call after each push and whenever the decoder is idle; frames
+    /// past their deadline are dropped here.
+    pub fn pop(&mut self) -> Option<AssembledFrame> {
+        self.pop_at(Instant::now())
+    }
+
+    /// Next frame to decode as of `now`
+    pub fn pop_at(&mut self, now: Instant) -> Option<AssembledFrame> {
+        loop {
+            let start = self.next_seq.or_else(|| self.oldest())?;
+            if let Some(end) = self.frame_end(start) {
+                return Some(self.take(start, end));
+            }
+
+            let oldest = self.oldest()?;
+            let timestamp = self.packets[&oldest].timestamp;
+            let deadline = self.arrivals.get(&timestamp).copied()? + self.config.max_wait;
+            if now < deadline {
+                return None;
+            }
+            self.drop_oldest();
+        }
+    }
+
+    /// Whether frames with `timestamp` were already handed out or dropped
+    fn is_past(&self, timestamp: u32) -> bool {
+        self.last_timestamp
+            .is_some_and(|last| timestamp.wrapping_sub(last) as i32 <= 0)
+    }
+
+    /// Marker packet of the frame starting at `start`, if every packet of
+    /// it is in
+    fn frame_end(&self, start: u16) -> Option<u16> {
+        let timestamp = self.packets.get(&start)?.timestamp;
+        let mut seq = start;
+        for _ in 0..self.packets.len() {
+            let packet = self.packets.get(&seq)?;
+            if packet.timestamp != timestamp {
+                // The marker of this frame was lost
+                return None;
+            }
+            if packet.marker {
+                return Some(seq);
+            }
+            seq = seq.wrapping_add(1);
+        }
+        None
+    }
+
+    fn take(&mut self, start: u16, end: u16) -> AssembledFrame {
+        let count = end.wrapping_sub(start) as usize + 1;
+        let first = &self.packets[&start];
+        let mut frame = AssembledFrame {
+            ssrc: first.ssrc,
+            timestamp: first.timestamp,
+            data: Vec::new(),
+            temporal_layer: first.temporal_layer,
+            timing: None,
+            after_loss: std::mem::take(&mut self.after_loss),
+        };
+        for seq in (0..count).map(|i| start.wrapping_add(i as u16)) {
+            if let Some(packet) = self.packets.remove(&seq) {
+                frame.data.extend_from_slice(&packet.payload);
+                frame.timing = frame.timing.or(packet.timing);
+            }
+        }
+        self.arrivals.remove(&frame.timestamp);
+        self.next_seq = Some(end.wrapping_add(1));
+        self.last_timestamp = Some(frame.timestamp);
+        self.stats.assembled += 1;
+        frame
+    }
+
+    /// Give up on the oldest pending frame
+    fn drop_oldest(&mut self) {
+        let Some(oldest) = self.oldest() else {
+            return;
+        };
+        let timestamp = self.packets[&oldest].timestamp;
+        let mut marker = None;
+        self.packets.retain(|seq, packet| {
+            if packet.timestamp.wrapping_sub(timestamp) as i32 > 0 {
+                return true;
+            }
+            if packet.marker {
+                marker = Some(*seq);
+            }
+            false
+        });
+        self.arrivals
+            .retain(|pending, _| pending.wrapping_sub(timestamp) as i32 > 0);
+        // Without the marker, where the next frame starts is unknown
+        self.next_seq = marker.map(|seq| seq.wrapping_add(1));
+        self.last_timestamp = Some(timestamp);
+        self.after_loss = true;
+        self.stats.dropped += 1;
+    }
+
+    /// Earliest held sequence number, allowing for wraparound
+    fn oldest(&self) -> Option<u16> {
+        let anchor = self
+            .next_seq
+            .or_else(|| self.packets.keys().next().copied())?;
+        self.packets
+            .keys()
+            .copied()
+            .min_by_key(|seq| seq.wrapping_sub(anchor) as i16)
+    }
+}
+
+impl Default for VideoFrameAssembler {
+    fn default() -> Self {
+        Self::new(FrameAssemblyConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic_bridge::{Packetizer, StreamType};
+
+    /// Packets of `frames` frames of 2500 bytes, three packets each
+    fn frames(frames: u32) -> Vec<Vec<RtpPacket>> {
+        let mut packetizer = Packetizer::new(96, 7, StreamType::Video);
+        (0..frames)
+            .map(|i| {
+                let frame = vec![i as u8; 2500];
+                packetizer.packetize(&frame, i * 3000).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reassembles_reordered_frames() {
+        let mut assembler = VideoFrameAssembler::default();
+        let sent = frames(2);
+        let mut packets: Vec<RtpPacket> = sent.iter().flatten().cloned().collect();
+        packets.reverse();
+
+        let now = Instant::now();
+        for packet in packets {
+            assembler.push_at(packet, now);
+        }
+        let first = assembler.pop_at(now).unwrap();
+        let second = assembler.pop_at(now).unwrap();
+        assert_eq!((first.timestamp, second.timestamp), (0, 3000));
+        assert_eq!(first.data, vec![0u8; 2500]);
+        assert_eq!(second.data, vec![1u8; 2500]);
+        assert!(!first.after_loss && !second.after_loss);
+        assert!(assembler.pop_at(now).is_none());
+        assert!(assembler.is_empty());
+
+        // A straggler of a frame already handed out is late
+        assembler.push_at(sent[0][1].clone(), now);
+        assembler.push_at(sent[1][1].clone(), now);
+        assert_eq!(assembler.stats().late, 2);
+    }
+
+    #[test]
+    fn test_incomplete_frame_waits_for_its_deadline() {
+        let config = FrameAssemblyConfig::default();
+        let mut assembler = VideoFrameAssembler::new(config);
+        let sent = frames(3);
+        let start = Instant::now();
+
+        // The middle packet of the second frame never arrives
+        for (i, frame) in sent.iter().enumerate() {
+            for (j, packet) in frame.iter().enumerate() {
+                if (i, j) != (1, 1) {
+                    assembler.push_at(packet.clone(), start);
+                }
+            }
+        }
+        assert_eq!(assembler.pop_at(start).unwrap().timestamp, 0);
+        // The third frame is whole but waits behind the second
+        assert!(assembler.pop_at(start).is_none());
+
+        let frame = assembler.pop_at(start + config.max_wait).unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert!(frame.after_loss);
+        assert_eq!(
+            assembler.stats(),
+            FrameAssemblyStats {
+                assembled: 2,
+                dropped: 1,
+                ..Default::default()
+            }
+        );
+        assembler.push_at(sent[1][1].clone(), start + config.max_wait);
+        assert_eq!(assembler.stats().late, 1);
+    }
+
+    #[test]
+    fn test_lost_marker_and_overflow_drop_frames() {
+        let sent = frames(3);
+        let start = Instant::now();
+
+        let mut assembler = VideoFrameAssembler::default();
+        for packet in sent
+            .iter()
+            .flatten()
+            .filter(|p| !(p.marker && p.timestamp == 0))
+        {
+            assembler.push_at(packet.clone(), start);
+        }
+        assert!(assembler.pop_at(start).is_none());
+        let deadline = start + FrameAssemblyConfig::default().max_wait;
+        // Where the second frame starts is unknown, so it is taken from
+        // its first packet held
+        assert_eq!(assembler.pop_at(deadline).unwrap().timestamp, 3000);
+        assert_eq!(assembler.pop_at(deadline).unwrap().timestamp, 6000);
+
+        let mut assembler = VideoFrameAssembler::new(FrameAssemblyConfig {
+            max_packets: 4,
+            ..Default::default()
+        });
+        for packet in sent.iter().flatten() {
+            assembler.push_at(packet.clone(), start);
+        }
+        assert_eq!(assembler.stats().dropped, 2);
+        let frame = assembler.pop_at(start).unwrap();
+        assert_eq!(frame.timestamp, 6000);
+        assert!(frame.after_loss);
+    }
+}
@@ -0,0 +1,234 @@
+//! Device-to-device call handoff
+//!
+//! Moves an active call between devices signed in as the same identity
+//! (phone to desktop) without the remote party hanging up:
+//!
+//! 1. The current device serializes the call into a [`HandoffState`] and
+//!    offers it to the identity's other devices.
+//! 2. A device that takes it opens a fresh leg to the remote party with an
+//!    offer naming the call it replaces, so the remote side swaps legs
+//!    instead of ringing. The remote party arbitrates: only the first
+//!    replacement of a live call is accepted.
+//! 3. Once the new leg is up the new device reports completion and the old
+//!    device tears its leg down.
+//!
+//! The old leg keeps running until step 3, so a failed handoff costs
+//! nothing.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::types::{CallId, MediaConstraints};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Handoff errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum HandoffError {
+    /// No handoff with this ID is in progress
+    #[error("Handoff not found: {0}")]
+    NotFound(String),
+
+    /// Handoff completed by a different identity than the one that offered
+    #[error("Identity mismatch: {0}")]
+    IdentityMismatch(String),
+}
+
+impl CodedError for HandoffError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::IdentityMismatch(_) => ErrorCode::PermissionDenied,
+        }
+    }
+}
+
+/// Serialized state of a call being handed to another device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandoffState {
+    /// Random ID naming this handoff
+    pub handoff_id: String,
+    /// Call leg on the offering device
+    pub call_id: CallId,
+    /// Identity moving the call
+    pub owner: String,
+    /// Remote party of the call
+    pub remote_peer: String,
+    /// Media the new leg should carry
+    pub constraints: MediaConstraints,
+    /// When the call was offered for handoff
+    pub offered_at: DateTime<Utc>,
+}
+
+/// Handoffs this device has offered or taken, and calls whose remote
+/// party moved to another device
+#[derive(Debug, Default)]
+pub struct HandoffTracker {
+    outgoing: HashMap<String, HandoffState>,
+    incoming: HashMap<String, (HandoffState, CallId)>,
+    replaced: HashMap<CallId, CallId>,
+}
+
+impl HandoffTracker {
+    /// Create an empty tracker
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Offer a call on this device for handoff
+    pub fn offer(
+        &mut self,
+        call_id: CallId,
+        owner: &str,
+        remote_peer: &str,
+        constraints: MediaConstraints,
+    ) -> HandoffState {
+        let state = HandoffState {
+            handoff_id: uuid::Uuid::new_v4().to_string(),
+            call_id,
+            owner: owner.to_string(),
+            remote_peer: remote_peer.to_string(),
+            constraints,
+            offered_at: Utc::now(),
+        };
+        self.outgoing
+            .insert(state.handoff_id.clone(), state.clone());
+        state
+    }
+
+    /// Whether this device offered the handoff
+    #[must_use]
+    pub fn is_outgoing(&self, handoff_id: &str) -> bool {
+        self.outgoing.contains_key(handoff_id)
+    }
+
+    /// Finish an offered handoff, returning the old leg to tear down
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the handoff is unknown or `requester` is not the
+    /// identity that offered it
+    pub fn complete(&mut self, handoff_id: &str, requester: &str) -> Result<CallId, HandoffError> {
+        let state = self
+            .outgoing
+            .get(handoff_id)
+            .ok_or_else(|| HandoffError::NotFound(handoff_id.to_string()))?;
+        if state.owner != requester {
+            return Err(HandoffError::IdentityMismatch(requester.to_string()));
+        }
+        let call_id = state.call_id;
+        self.outgoing.remove(handoff_id);
+        Ok(call_id)
+    }
+
+    /// Forget a call that ended, withdrawing any offer for it
+    pub fn release(&mut self, call_id: CallId) {
+        self.outgoing.retain(|_, state| state.call_id != call_id);
+        self.replaced.remove(&call_id);
+    }
+
+    /// Record a handoff taken by this device and its new leg
+    pub fn take(&mut self, state: HandoffState, new_call_id: CallId) {
+        self.incoming
+            .insert(state.handoff_id.clone(), (state, new_call_id));
+    }
+
+    /// New leg opened for a taken handoff
+    #[must_use]
+    pub fn new_leg(&self, handoff_id: &str) -> Option<CallId> {
+        self.incoming.get(handoff_id).map(|(_, call_id)| *call_id)
+    }
+
+    /// Stop tracking a taken handoff once its new leg is up
+    ///
+    /// # Errors
+    ///
+    /// Returns error if this device has not taken the handoff
+    pub fn finish(&mut self, handoff_id: &str) -> Result<HandoffState, HandoffError> {
+        self.incoming
+            .remove(handoff_id)
+            .map(|(state, _)| state)
+            .ok_or_else(|| HandoffError::NotFound(handoff_id.to_string()))
+    }
+
+    /// Record that the remote party moved `replaced` to a new leg
+    ///
+    /// Returns `false` if the call was already moved, so only the first
+    /// device to take a handoff wins.
+    pub fn replace(&mut self, replaced: CallId, call_id: CallId) -> bool {
+        if self.replaced.contains_key(&replaced) {
+            return false;
+        }
+        self.replaced.insert(replaced, call_id);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offer_and_complete() {
+        let mut phone = HandoffTracker::new();
+        let call_id = CallId::new();
+        let state = phone.offer(call_id, "alice", "bob", MediaConstraints::video_call());
+        assert!(phone.is_outgoing(&state.handoff_id));
+
+        // The desktop takes the serialized call
+        let json = serde_json::to_string(&state).unwrap();
+        let received: HandoffState = serde_json::from_str(&json).unwrap();
+        assert_eq!(received, state);
+
+        let mut desktop = HandoffTracker::new();
+        let new_leg = CallId::new();
+        desktop.take(received, new_leg);
+        assert_eq!(desktop.new_leg(&state.handoff_id), Some(new_leg));
+        assert_eq!(desktop.finish(&state.handoff_id).unwrap().call_id, call_id);
+
+        assert_eq!(phone.complete(&state.handoff_id, "alice"), Ok(call_id));
+        assert!(!phone.is_outgoing(&state.handoff_id));
+    }
+
+    #[test]
+    fn test_complete_requires_same_identity() {
+        let mut tracker = HandoffTracker::new();
+        let state = tracker.offer(
+            CallId::new(),
+            "alice",
+            "bob",
+            MediaConstraints::audio_only(),
+        );
+
+        assert_eq!(
+            tracker.complete(&state.handoff_id, "mallory"),
+            Err(HandoffError::IdentityMismatch("mallory".to_string()))
+        );
+        assert!(tracker.is_outgoing(&state.handoff_id));
+    }
+
+    #[test]
+    fn test_only_first_replacement_wins() {
+        let mut tracker = HandoffTracker::new();
+        let old_leg = CallId::new();
+        assert!(tracker.replace(old_leg, CallId::new()));
+        assert!(!tracker.replace(old_leg, CallId::new()));
+
+        tracker.release(old_leg);
+        assert!(tracker.replace(old_leg, CallId::new()));
+    }
+
+    #[test]
+    fn test_release_withdraws_offer() {
+        let mut tracker = HandoffTracker::new();
+        let call_id = CallId::new();
+        let state = tracker.offer(call_id, "alice", "bob", MediaConstraints::audio_only());
+
+        tracker.release(call_id);
+        assert!(matches!(
+            tracker.complete(&state.handoff_id, "alice"),
+            Err(HandoffError::NotFound(_))
+        ));
+    }
+}
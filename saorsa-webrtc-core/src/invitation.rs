@@ -0,0 +1,543 @@
+//! Call invitations
+//!
+//! An [`InvitationIssuer`] signs [`CallToken`]s that let someone outside
+//! the issuer's contacts call them until the token expires. Tokens encode
+//! to a short URL-safe string, or a `saorsa-call:` URI for links and QR
+//! codes. The caller presents the token over signaling before calling; the
+//! issuer checks it and admits the caller through its [`CallScreen`] for
+//! as long as the token is valid.
+//!
+//! People with no identity on the network can be handed a guest
+//! invitation instead, from [`InvitationIssuer::issue_guest`]. It carries
+//! an ephemeral peer ID for the holder to take on, binds the invitation to
+//! that ID, and the guest may only reach the issuer until it expires.
+//!
+//! Contacts can also be given an [`AutoAnswer`] policy, which answers
+//! their calls straight away instead of ringing: an intercom or a baby
+//! monitor that should pick up on its own.
+//!
+//! Tokens are signed with a keyed BLAKE3 hash rather than a public-key
+//! signature: only the issuer ever verifies them, so only the issuer needs
+//! the key.
+
+use crate::error::{CodedError, ErrorCode};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use thiserror::Error;
+use uuid::Uuid;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// URI scheme of invitation links
+pub const INVITATION_URI_SCHEME: &str = "saorsa-call";
+
+/// Longest encoded token accepted, to bound work on hostile input
+const MAX_ENCODED_LEN: usize = 1024;
+
+/// Invitation errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum InvitationError {
+    /// Token could not be decoded
+    #[error("Malformed invitation token")]
+    Malformed,
+
+    /// Token was not signed by this issuer, or was modified
+    #[error("Invitation signature is invalid")]
+    BadSignature,
+
+    /// Token has expired
+    #[error("Invitation expired at {0}")]
+    Expired(DateTime<Utc>),
+
+    /// Issuer revoked the token
+    #[error("Invitation {0} was revoked")]
+    Revoked(Uuid),
+
+    /// Single-use token was already redeemed
+    #[error("Invitation {0} was already redeemed")]
+    AlreadyRedeemed(Uuid),
+
+    /// Guest token presented by someone other than its guest
+    #[error("Invitation {0} was issued to another guest")]
+    WrongGuest(Uuid),
+
+    /// Token does not carry a guest identity
+    #[error("Invitation {0} is not a guest invitation")]
+    NotForGuest(Uuid),
+
+    /// A guest tried to reach someone other than its issuer
+    #[error("Guest may not reach {0}")]
+    OutOfScope(String),
+}
+
+impl CodedError for InvitationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Malformed => ErrorCode::MalformedMessage,
+            Self::BadSignature
+            | Self::Expired(_)
+            | Self::Revoked(_)
+            | Self::AlreadyRedeemed(_)
+            | Self::WrongGuest(_)
+            | Self::NotForGuest(_)
+            | Self::OutOfScope(_) => ErrorCode::InvitationFailed,
+        }
+    }
+}
+
+/// Secret key invitations are signed with
+///
+/// Keep it across restarts, e.g. in the platform keychain, or every
+/// outstanding invitation stops working.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct InvitationKey([u8; 32]);
+
+impl InvitationKey {
+    /// Use a stored key
+    #[must_use]
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Random key
+    #[must_use]
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    /// Key bytes, for storing
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for InvitationKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InvitationKey([redacted])")
+    }
+}
+
+/// Signed permission to call the issuer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallToken {
+    /// Token ID, for revocation
+    pub id: Uuid,
+    /// Peer ID of the issuer, who the token lets the holder call
+    pub issuer: String,
+    /// When the token stops working
+    pub expires_at: DateTime<Utc>,
+    /// Whether the token admits only the first caller to redeem it
+    pub single_use: bool,
+    /// Ephemeral peer ID of the guest the token was issued to, who alone
+    /// may redeem it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest: Option<String>,
+    /// Keyed BLAKE3 hash over the other fields
+    signature: [u8; 32],
+}
+
+impl CallToken {
+    /// Whether the token has expired at `now`
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Whether `peer` may be called by, or call, the holder of this token
+    /// at `now`
+    ///
+    /// Holders of a guest token may only reach the issuer, and only until
+    /// it expires; other tokens do not restrict their holder.
+    #[must_use]
+    pub fn permits(&self, peer: &str, now: DateTime<Utc>) -> bool {
+        self.guest.is_none() || (peer == self.issuer && !self.is_expired(now))
+    }
+
+    /// URL-safe string form, for sharing
+    #[must_use]
+    pub fn encode(&self) -> String {
+        // Serializing plain fields cannot fail
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    /// `saorsa-call:` URI, for links and QR codes
+    #[must_use]
+    pub fn to_uri(&self) -> String {
+        format!("{INVITATION_URI_SCHEME}:{}", self.encode())
+    }
+
+    /// Parse a token from its string form or URI
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the input is not a token
+    pub fn decode(encoded: &str) -> Result<Self, InvitationError> {
+        let encoded = encoded.trim();
+        let encoded = encoded
+            .strip_prefix(INVITATION_URI_SCHEME)
+            .and_then(|rest| rest.strip_prefix(':'))
+            .unwrap_or(encoded);
+        if encoded.len() > MAX_ENCODED_LEN {
+            return Err(InvitationError::Malformed);
+        }
+        let json = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| InvitationError::Malformed)?;
+        serde_json::from_slice(&json).map_err(|_| InvitationError::Malformed)
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.issuer.len() + 33);
+        bytes.extend_from_slice(self.id.as_bytes());
+        bytes.extend_from_slice(&self.expires_at.timestamp_micros().to_be_bytes());
+        bytes.push(u8::from(self.single_use));
+        bytes.extend_from_slice(self.issuer.as_bytes());
+        if let Some(guest) = &self.guest {
+            bytes.push(0);
+            bytes.extend_from_slice(guest.as_bytes());
+        }
+        bytes
+    }
+}
+
+/// Issues and redeems call tokens for one identity
+#[derive(Debug)]
+pub struct InvitationIssuer {
+    key: InvitationKey,
+    revoked: HashSet<Uuid>,
+    redeemed: HashSet<Uuid>,
+}
+
+impl InvitationIssuer {
+    /// Sign tokens with `key`
+    #[must_use]
+    pub fn new(key: InvitationKey) -> Self {
+        Self {
+            key,
+            revoked: HashSet::new(),
+            redeemed: HashSet::new(),
+        }
+    }
+
+    /// Issue a token letting its holder call `issuer` for `ttl`
+    #[must_use]
+    pub fn issue(&self, issuer: &str, ttl: Duration, single_use: bool) -> CallToken {
+        let mut token = CallToken {
+            id: Uuid::new_v4(),
+            issuer: issuer.to_string(),
+            expires_at: Utc::now() + ttl,
+            single_use,
+            guest: None,
+            signature: [0; 32],
+        };
+        token.signature = self.sign(&token);
+        token
+    }
+
+    /// Mint a guest identity that may call `issuer` for `ttl`
+    ///
+    /// The token names a fresh peer ID in [`CallToken::guest`]; its holder
+    /// joins the network under that ID and only they can redeem it.
+    #[must_use]
+    pub fn issue_guest(&self, issuer: &str, ttl: Duration) -> CallToken {
+        let mut token = self.issue(issuer, ttl, false);
+        token.guest = Some(format!("guest-{}", Uuid::new_v4().simple()));
+        token.signature = self.sign(&token);
+        token
+    }
+
+    /// Stop a token from being redeemed
+    pub fn revoke(&mut self, id: Uuid) {
+        self.revoked.insert(id);
+    }
+
+    /// Check a token and record that it was used
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the token was not issued here, has expired, was
+    /// revoked, or is single-use and already redeemed
+    pub fn redeem(&mut self, token: &CallToken, now: DateTime<Utc>) -> Result<(), InvitationError> {
+        // blake3::Hash compares in constant time
+        if blake3::Hash::from(self.sign(token)) != blake3::Hash::from(token.signature) {
+            return Err(InvitationError::BadSignature);
+        }
+        if token.is_expired(now) {
+            return Err(InvitationError::Expired(token.expires_at));
+        }
+        if self.revoked.contains(&token.id) {
+            return Err(InvitationError::Revoked(token.id));
+        }
+        if token.single_use && !self.redeemed.insert(token.id) {
+            return Err(InvitationError::AlreadyRedeemed(token.id));
+        }
+        Ok(())
+    }
+
+    /// Check a token presented by `holder` and record that it was used
+    ///
+    /// As [`redeem`](Self::redeem), and guest tokens only redeem for their
+    /// guest.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if [`redeem`](Self::redeem) would, or `holder` is not
+    /// the token's guest
+    pub fn redeem_by(
+        &mut self,
+        token: &CallToken,
+        holder: &str,
+        now: DateTime<Utc>,
+    ) -> Result<(), InvitationError> {
+        if token.guest.as_deref().is_some_and(|guest| guest != holder) {
+            return Err(InvitationError::WrongGuest(token.id));
+        }
+        self.redeem(token, now)
+    }
+
+    fn sign(&self, token: &CallToken) -> [u8; 32] {
+        *blake3::keyed_hash(self.key.as_bytes(), &token.signed_bytes()).as_bytes()
+    }
+}
+
+/// Why an incoming call was let through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreeningDecision {
+    /// Caller is a contact
+    Contact,
+    /// Caller redeemed the invitation with this ID
+    Invited(Uuid),
+    /// Caller is neither; ring only if unknown callers are allowed
+    Unknown,
+}
+
+/// How a contact's calls are answered without ringing
+///
+/// Auto-answered calls are audio only.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoAnswer {
+    /// Answer with our microphone muted, so the caller only listens
+    pub muted: bool,
+}
+
+/// Decides whether incoming callers get through
+#[derive(Debug, Clone, Default)]
+pub struct CallScreen {
+    contacts: HashSet<String>,
+    /// Invitation and expiry by admitted caller
+    invited: HashMap<String, (Uuid, DateTime<Utc>)>,
+    /// Auto-answer policy by contact
+    auto_answer: HashMap<String, AutoAnswer>,
+}
+
+impl CallScreen {
+    /// Create a screen with no contacts
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let `peer` call at any time
+    pub fn add_contact(&mut self, peer: &str) {
+        self.contacts.insert(peer.to_string());
+    }
+
+    /// Stop treating `peer` as a contact, and stop auto-answering them
+    pub fn remove_contact(&mut self, peer: &str) -> bool {
+        self.auto_answer.remove(peer);
+        self.contacts.remove(peer)
+    }
+
+    /// Answer `peer`'s calls automatically, or ring again with `None`
+    ///
+    /// Setting a policy makes `peer` a contact.
+    pub fn set_auto_answer(&mut self, peer: &str, policy: Option<AutoAnswer>) {
+        match policy {
+            Some(policy) => {
+                self.contacts.insert(peer.to_string());
+                self.auto_answer.insert(peer.to_string(), policy);
+            }
+            None => {
+                self.auto_answer.remove(peer);
+            }
+        }
+    }
+
+    /// Auto-answer policy for `peer`'s calls, if they are answered
+    /// automatically
+    #[must_use]
+    pub fn auto_answer(&self, peer: &str) -> Option<AutoAnswer> {
+        self.auto_answer.get(peer).copied()
+    }
+
+    /// Let `peer` call until the redeemed `token` expires
+    pub fn admit(&mut self, peer: &str, token: &CallToken) {
+        self.invited
+            .insert(peer.to_string(), (token.id, token.expires_at));
+    }
+
+    /// Classify a caller, forgetting invitations that have expired
+    pub fn screen(&mut self, peer: &str, now: DateTime<Utc>) -> ScreeningDecision {
+        if self.contacts.contains(peer) {
+            return ScreeningDecision::Contact;
+        }
+        self.invited.retain(|_, (_, expires_at)| now < *expires_at);
+        match self.invited.get(peer) {
+            Some((id, _)) => ScreeningDecision::Invited(*id),
+            None => ScreeningDecision::Unknown,
+        }
+    }
+
+    /// Drop admissions granted by a revoked invitation
+    pub fn revoke(&mut self, id: Uuid) {
+        self.invited.retain(|_, (token_id, _)| *token_id != id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_roundtrips_through_uri() {
+        let issuer = InvitationIssuer::new(InvitationKey::generate());
+        let token = issuer.issue("alice", Duration::hours(24), false);
+
+        let uri = token.to_uri();
+        assert!(uri.starts_with("saorsa-call:"));
+        assert!(uri
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_:".contains(c)));
+        assert_eq!(CallToken::decode(&uri).unwrap(), token);
+        assert_eq!(CallToken::decode(&token.encode()).unwrap(), token);
+        assert_eq!(
+            CallToken::decode("saorsa-call:not base64!"),
+            Err(InvitationError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_redeem_checks_signature_expiry_and_revocation() {
+        let key = InvitationKey::generate();
+        let mut issuer = InvitationIssuer::new(key.clone());
+        let now = Utc::now();
+        let token = issuer.issue("alice", Duration::hours(1), false);
+        assert_eq!(issuer.redeem(&token, now), Ok(()));
+        assert_eq!(issuer.redeem(&token, now), Ok(()));
+
+        let mut forged = token.clone();
+        forged.expires_at += Duration::days(365);
+        assert_eq!(
+            issuer.redeem(&forged, now),
+            Err(InvitationError::BadSignature)
+        );
+        let mut other = InvitationIssuer::new(InvitationKey::generate());
+        assert_eq!(
+            other.redeem(&token, now),
+            Err(InvitationError::BadSignature)
+        );
+
+        assert!(matches!(
+            issuer.redeem(&token, now + Duration::hours(2)),
+            Err(InvitationError::Expired(_))
+        ));
+        issuer.revoke(token.id);
+        assert_eq!(
+            issuer.redeem(&token, now),
+            Err(InvitationError::Revoked(token.id))
+        );
+    }
+
+    #[test]
+    fn test_single_use_token_redeems_once() {
+        let mut issuer = InvitationIssuer::new(InvitationKey::generate());
+        let token = issuer.issue("alice", Duration::minutes(10), true);
+        let now = Utc::now();
+
+        assert_eq!(issuer.redeem(&token, now), Ok(()));
+        assert_eq!(
+            issuer.redeem(&token, now),
+            Err(InvitationError::AlreadyRedeemed(token.id))
+        );
+    }
+
+    #[test]
+    fn test_guest_tokens_bind_their_guest() {
+        let mut issuer = InvitationIssuer::new(InvitationKey::generate());
+        let token = issuer.issue_guest("alice", Duration::minutes(30));
+        let guest = token.guest.clone().unwrap();
+        assert!(guest.starts_with("guest-"));
+        let now = Utc::now();
+
+        assert_eq!(
+            issuer.redeem_by(&token, "mallory", now),
+            Err(InvitationError::WrongGuest(token.id))
+        );
+        assert_eq!(issuer.redeem_by(&token, &guest, now), Ok(()));
+        let mut stolen = CallToken::decode(&token.to_uri()).unwrap();
+        stolen.guest = Some("mallory".to_string());
+        assert_eq!(
+            issuer.redeem_by(&stolen, "mallory", now),
+            Err(InvitationError::BadSignature)
+        );
+
+        assert!(token.permits("alice", now));
+        assert!(!token.permits("bob", now));
+        assert!(!token.permits("alice", now + Duration::hours(1)));
+        let plain = issuer.issue("alice", Duration::minutes(30), false);
+        assert!(plain.permits("bob", now));
+    }
+
+    #[test]
+    fn test_screen_admits_contacts_and_invited_callers() {
+        let issuer = InvitationIssuer::new(InvitationKey::generate());
+        let token = issuer.issue("alice", Duration::minutes(30), false);
+        let now = Utc::now();
+        let mut screen = CallScreen::new();
+        screen.add_contact("bob");
+        screen.admit("carol", &token);
+
+        assert_eq!(screen.screen("bob", now), ScreeningDecision::Contact);
+        assert_eq!(
+            screen.screen("carol", now),
+            ScreeningDecision::Invited(token.id)
+        );
+        assert_eq!(screen.screen("mallory", now), ScreeningDecision::Unknown);
+        assert_eq!(
+            screen.screen("carol", now + Duration::hours(1)),
+            ScreeningDecision::Unknown
+        );
+
+        screen.admit("carol", &token);
+        screen.revoke(token.id);
+        assert_eq!(screen.screen("carol", now), ScreeningDecision::Unknown);
+    }
+
+    #[test]
+    fn test_auto_answer_is_kept_for_contacts_only() {
+        let now = Utc::now();
+        let mut screen = CallScreen::new();
+        let monitor = AutoAnswer { muted: true };
+        screen.set_auto_answer("nursery", Some(monitor));
+
+        assert_eq!(screen.screen("nursery", now), ScreeningDecision::Contact);
+        assert_eq!(screen.auto_answer("nursery"), Some(monitor));
+        assert_eq!(screen.auto_answer("mallory"), None);
+
+        screen.set_auto_answer("nursery", None);
+        assert_eq!(screen.auto_answer("nursery"), None);
+        assert_eq!(screen.screen("nursery", now), ScreeningDecision::Contact);
+
+        screen.set_auto_answer("nursery", Some(monitor));
+        assert!(screen.remove_contact("nursery"));
+        assert_eq!(screen.auto_answer("nursery"), None);
+    }
+}
@@ -0,0 +1,571 @@
+//! Adaptive audio jitter buffer with packet loss concealment
+//!
+//! Packets arrive over QUIC datagrams late, out of order or not at all,
+//! while playout needs one frame every packet interval. The
+//! [`AudioJitterBuffer`] holds a few packets back to absorb jitter and
+//! hands out exactly one frame per [`AudioJitterBuffer::pop`]. When the
+//! next packet is missing it asks the decoder to conceal the gap, using
+//! the packet after it for Opus in-band FEC recovery when that has
+//! already arrived, so a lost datagram costs a concealed frame rather
+//! than a hole in the audio.
+//!
+//! How much audio to hold back adapts to the path. The buffer estimates
+//! interarrival jitter the way RTP receivers do (RFC 3550, section 6.4.1)
+//! and, once it has seen a second of packets, aims to hold three times
+//! that, within [`MIN_TARGET_DELAY`] and [`MAX_TARGET_DELAY`] unless
+//! narrowed. It moves towards a new target a frame at a time, growing by
+//! playing a concealed frame before the next packet and shrinking by
+//! skipping one, so delay follows the network without jumps.
+//!
+//! [`AudioJitterBuffer::playout_stats`] summarises what that did to the
+//! audio for [`CallQualityMetrics::audio`], so quality scoring and alerts
+//! follow what listeners hear rather than only network counters.
+//!
+//! [`CallQualityMetrics::audio`]: crate::types::CallQualityMetrics::audio
+
+use crate::audio_clock::{AudioClock, OPUS_RTP_CLOCK_HZ};
+use crate::quic_bridge::RtpPacket;
+use saorsa_webrtc_codecs::{AudioDecoder, AudioFrame, CodecError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Lowest playout delay the buffer adapts to
+pub const MIN_TARGET_DELAY: Duration = Duration::from_millis(20);
+
+/// Highest playout delay the buffer adapts to
+pub const MAX_TARGET_DELAY: Duration = Duration::from_millis(200);
+
+/// Audio buffered before playout starts, until jitter has been measured
+const TARGET_DELAY: Duration = Duration::from_millis(60);
+
+/// Audio held at most
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+/// Concealment before playout stops; past that silence is more honest
+const MAX_CONCEALMENT: Duration = Duration::from_millis(100);
+
+/// Packets measured before the target starts following the jitter
+const ADAPT_AFTER: u32 = 50;
+
+/// Jitter estimates the target delay covers
+const JITTER_HEADROOM: f64 = 3.0;
+
+/// Jitter buffer settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JitterBufferConfig {
+    /// Packets buffered before playout starts, until measured jitter
+    /// moves the target
+    pub target_depth: usize,
+    /// Fewest packets the target adapts down to
+    pub min_target_depth: usize,
+    /// Most packets the target adapts up to
+    pub max_target_depth: usize,
+    /// Packets held at most; older ones are dropped beyond this
+    pub max_depth: usize,
+    /// Consecutive concealed frames before playout stops and rebuffers
+    pub max_concealed: usize,
+    /// Audio carried by each packet
+    pub frame_duration: Duration,
+}
+
+impl JitterBufferConfig {
+    /// Buffer 60 ms to start, adapt between 20 and 200 ms, hold up to a
+    /// second and conceal up to 100 ms of audio in frames of `clock`
+    #[must_use]
+    pub fn for_clock(clock: &AudioClock) -> Self {
+        Self {
+            target_depth: clock.frames_in(TARGET_DELAY),
+            min_target_depth: clock.frames_in(MIN_TARGET_DELAY),
+            max_target_depth: clock.frames_in(MAX_TARGET_DELAY),
+            max_depth: clock.frames_in(MAX_DELAY),
+            max_concealed: clock.frames_in(MAX_CONCEALMENT),
+            frame_duration: clock.frame_duration(),
+        }
+    }
+
+    /// Adapt the target delay between `min` and `max` only
+    ///
+    /// Both are kept within [`MIN_TARGET_DELAY`] and [`MAX_TARGET_DELAY`].
+    #[must_use]
+    pub fn with_target_range(mut self, min: Duration, max: Duration) -> Self {
+        let min = min.clamp(MIN_TARGET_DELAY, MAX_TARGET_DELAY);
+        let max = max.clamp(min, MAX_TARGET_DELAY);
+        self.min_target_depth = self.frames_in(min);
+        self.max_target_depth = self.frames_in(max);
+        self.target_depth = self
+            .target_depth
+            .clamp(self.min_target_depth, self.max_target_depth);
+        self
+    }
+
+    /// Packets needed to cover `duration`, at least one
+    fn frames_in(&self, duration: Duration) -> usize {
+        let frame = self.frame_duration.as_millis().max(1);
+        (duration.as_millis().div_ceil(frame) as usize).max(1)
+    }
+}
+
+impl Default for JitterBufferConfig {
+    fn default() -> Self {
+        Self::for_clock(&AudioClock::default())
+    }
+}
+
+/// Jitter buffer counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JitterStats {
+    /// Frames decoded from received packets
+    pub decoded: u64,
+    /// Frames concealed from surrounding audio
+    pub concealed: u64,
+    /// Separate runs of concealed frames, each a glitch listeners may hear
+    #[serde(default)]
+    pub concealment_events: u64,
+    /// Packets that arrived after their playout slot
+    pub late: u64,
+    /// Packets received more than once
+    pub duplicates: u64,
+    /// Packets dropped because the buffer was full
+    pub overflowed: u64,
+    /// Concealed frames played to grow the delay
+    pub stretched: u64,
+    /// Frames skipped to shrink the delay
+    pub skipped: u64,
+}
+
+/// What listeners hear of an audio stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioPlayoutStats {
+    /// Audio held back for playout, in milliseconds
+    pub jitter_buffer_delay_ms: u32,
+    /// Share of played frames made up rather than decoded, by concealment
+    /// or expansion, in percent
+    pub concealed_percent: f32,
+    /// Separate runs of concealed frames
+    pub concealment_events: u64,
+    /// Frames made up to grow the delay
+    pub expand_count: u64,
+    /// Frames skipped to shrink the delay
+    pub accelerate_count: u64,
+}
+
+/// Reorders audio packets and conceals the ones that never arrive
+pub struct AudioJitterBuffer {
+    decoder: Box<dyn AudioDecoder>,
+    config: JitterBufferConfig,
+    packets: HashMap<u16, RtpPacket>,
+    next_seq: Option<u16>,
+    concealed_run: usize,
+    stats: JitterStats,
+    /// Packets to buffer, following the measured jitter
+    target_depth: usize,
+    /// Frames still to add to the delay, or to remove if negative
+    pending: isize,
+    /// Smoothed interarrival jitter in milliseconds
+    jitter_ms: f64,
+    /// Arrival time and RTP timestamp of the last packet
+    last_arrival: Option<(Instant, u32)>,
+    arrivals: u32,
+}
+
+impl AudioJitterBuffer {
+    /// Create a buffer decoding with `decoder`
+    #[must_use]
+    pub fn new(decoder: Box<dyn AudioDecoder>, config: JitterBufferConfig) -> Self {
+        Self {
+            decoder,
+            config,
+            packets: HashMap::new(),
+            next_seq: None,
+            concealed_run: 0,
+            stats: JitterStats::default(),
+            target_depth: config.target_depth,
+            pending: 0,
+            jitter_ms: 0.0,
+            last_arrival: None,
+            arrivals: 0,
+        }
+    }
+
+    /// Counters since creation
+    #[must_use]
+    pub fn stats(&self) -> JitterStats {
+        self.stats
+    }
+
+    /// Packets waiting for playout
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Whether no packets are waiting
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Whether frames are being played out, as opposed to buffering
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.next_seq.is_some()
+    }
+
+    /// Packets the buffer currently aims to hold
+    #[must_use]
+    pub fn target_depth(&self) -> usize {
+        self.target_depth
+    }
+
+    /// Smoothed interarrival jitter
+    #[must_use]
+    pub fn jitter(&self) -> Duration {
+        Duration::from_secs_f64(self.jitter_ms / 1000.0)
+    }
+
+    /// Audio waiting for playout
+    #[must_use]
+    pub fn delay(&self) -> Duration {
+        self.config.frame_duration * self.packets.len() as u32
+    }
+
+    /// Playout quality since creation, for [`CallQualityMetrics::audio`]
+    ///
+    /// [`CallQualityMetrics::audio`]: crate::types::CallQualityMetrics::audio
+    #[must_use]
+    pub fn playout_stats(&self) -> AudioPlayoutStats {
+        let stats = self.stats;
+        let made_up = stats.concealed + stats.stretched;
+        let played = stats.decoded + made_up;
+        AudioPlayoutStats {
+            jitter_buffer_delay_ms: u32::try_from(self.delay().as_millis()).unwrap_or(u32::MAX),
+            concealed_percent: if played == 0 {
+                0.0
+            } else {
+                (made_up as f64 * 100.0 / played as f64) as f32
+            },
+            concealment_events: stats.concealment_events,
+            expand_count: stats.stretched,
+            accelerate_count: stats.skipped,
+        }
+    }
+
+    /// Queue a packet received just now
+    pub fn push(&mut self, packet: RtpPacket) {
+        self.push_at(packet, Instant::now());
+    }
+
+    /// Queue a packet received at `arrived`
+    pub fn push_at(&mut self, packet: RtpPacket, arrived: Instant) {
+        self.measure(packet.timestamp, arrived);
+        let seq = packet.sequence_number;
+        if let Some(next) = self.next_seq {
+            if seq.wrapping_sub(next) >= 0x8000 {
+                self.stats.late += 1;
+                return;
+            }
+        }
+        if self.packets.contains_key(&seq) {
+            self.stats.duplicates += 1;
+            return;
+        }
+        self.packets.insert(seq, packet);
+
+        while self.packets.len() > self.config.max_depth.max(1) {
+            let Some(oldest) = self.oldest() else { break };
+            self.packets.remove(&oldest);
+            self.next_seq = self.next_seq.map(|_| oldest.wrapping_add(1));
+            self.stats.overflowed += 1;
+        }
+    }
+
+    /// Next frame of playout, called once per packet interval
+    ///
+    /// Returns `None` while buffering, which the caller should play as
+    /// silence.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the decoder fails on a packet or cannot conceal
+    pub fn pop(&mut self) -> Result<Option<AudioFrame>, CodecError> {
+        let mut next = match self.next_seq {
+            Some(next) => next,
+            None => {
+                if self.packets.len() < self.target_depth.max(1) {
+                    return Ok(None);
+                }
+                match self.oldest() {
+                    Some(oldest) => oldest,
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        if self.pending > 0 && self.packets.contains_key(&next) {
+            // Hold the packet a frame longer, concealing in its slot
+            self.pending -= 1;
+            self.stats.stretched += 1;
+            return self.decoder.conceal(None).map(Some);
+        }
+        if self.pending < 0 && self.packets.contains_key(&next.wrapping_add(1)) {
+            if let Some(packet) = self.packets.remove(&next) {
+                // Decoded but not played, so the decoder's state stays
+                // continuous
+                self.decoder.decode(&packet.payload)?;
+                self.pending += 1;
+                self.stats.skipped += 1;
+                next = next.wrapping_add(1);
+            }
+        }
+
+        if let Some(packet) = self.packets.remove(&next) {
+            self.next_seq = Some(next.wrapping_add(1));
+            self.concealed_run = 0;
+            self.stats.decoded += 1;
+            return self.decoder.decode(&packet.payload).map(Some);
+        }
+
+        if self.concealed_run >= self.config.max_concealed {
+            // The stream stalled rather than lost a packet; start over once
+            // enough audio has arrived again
+            self.next_seq = None;
+            self.concealed_run = 0;
+            self.pending = 0;
+            return Ok(None);
+        }
+
+        self.next_seq = Some(next.wrapping_add(1));
+        if self.concealed_run == 0 {
+            self.stats.concealment_events += 1;
+        }
+        self.concealed_run += 1;
+        self.stats.concealed += 1;
+        let recovery = self
+            .packets
+            .get(&next.wrapping_add(1))
+            .map(|packet| packet.payload.as_slice());
+        self.decoder.conceal(recovery).map(Some)
+    }
+
+    /// Update the jitter estimate and the target following it
+    fn measure(&mut self, timestamp: u32, arrived: Instant) {
+        if let Some((last_arrived, last_timestamp)) = self.last_arrival {
+            let elapsed_ms = arrived
+                .saturating_duration_since(last_arrived)
+                .as_secs_f64()
+                * 1000.0;
+            let sent_ms = f64::from(timestamp.wrapping_sub(last_timestamp) as i32)
+                / f64::from(OPUS_RTP_CLOCK_HZ / 1000);
+            self.jitter_ms += ((elapsed_ms - sent_ms).abs() - self.jitter_ms) / 16.0;
+            self.arrivals = self.arrivals.saturating_add(1);
+        }
+        self.last_arrival = Some((arrived, timestamp));
+        if self.arrivals < ADAPT_AFTER {
+            return;
+        }
+
+        let frame_ms = (self.config.frame_duration.as_secs_f64() * 1000.0).max(1.0);
+        let min = self.config.min_target_depth.max(1);
+        let target = ((JITTER_HEADROOM * self.jitter_ms / frame_ms).ceil() as usize)
+            .clamp(min, self.config.max_target_depth.max(min));
+        if self.is_playing() {
+            self.pending += target as isize - self.target_depth as isize;
+        }
+        self.target_depth = target;
+    }
+
+    /// Earliest buffered sequence number, allowing for wraparound
+    fn oldest(&self) -> Option<u16> {
+        let anchor = self
+            .next_seq
+            .or_else(|| self.packets.keys().next().copied())?;
+        self.packets
+            .keys()
+            .copied()
+            .min_by_key(|seq| seq.wrapping_sub(anchor) as i16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic_bridge::StreamType;
+    use saorsa_webrtc_codecs::{
+        AudioEncoder, Channels, OpusDecoder, OpusEncoder, OpusEncoderConfig, SampleRate,
+    };
+
+    fn packets(count: u16, first_seq: u16) -> Vec<RtpPacket> {
+        let mut encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        (0..count)
+            .map(|i| {
+                let frame = AudioFrame {
+                    data: vec![1000; 480],
+                    sample_rate: SampleRate::Hz48000,
+                    channels: Channels::Mono,
+                    timestamp: u64::from(i) * 10,
+                };
+                let payload = encoder.encode(&frame).unwrap().to_vec();
+                let seq = first_seq.wrapping_add(i);
+                RtpPacket::new(111, seq, u32::from(i) * 480, 1, payload, StreamType::Audio).unwrap()
+            })
+            .collect()
+    }
+
+    fn buffer() -> AudioJitterBuffer {
+        let decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+        AudioJitterBuffer::new(Box::new(decoder), JitterBufferConfig::default())
+    }
+
+    fn push_all(jitter: &mut AudioJitterBuffer, packets: impl IntoIterator<Item = RtpPacket>) {
+        for packet in packets {
+            jitter.push(packet);
+        }
+    }
+
+    #[test]
+    fn test_depth_follows_frame_size() {
+        use crate::audio_clock::OpusFrameSize;
+
+        let default = JitterBufferConfig::default();
+        assert_eq!(
+            (
+                default.target_depth,
+                default.max_depth,
+                default.max_concealed
+            ),
+            (3, 50, 5)
+        );
+        let short = JitterBufferConfig::for_clock(&AudioClock::new(OpusFrameSize::Ms10));
+        assert_eq!((short.target_depth, short.max_concealed), (6, 10));
+        let long = JitterBufferConfig::for_clock(&AudioClock::new(OpusFrameSize::Ms60));
+        assert_eq!(
+            (long.target_depth, long.max_depth, long.max_concealed),
+            (1, 17, 2)
+        );
+    }
+
+    #[test]
+    fn test_waits_for_target_depth() {
+        let mut jitter = buffer();
+        let mut sent = packets(3, 0).into_iter();
+        jitter.push(sent.next().unwrap());
+        assert!(jitter.pop().unwrap().is_none());
+        push_all(&mut jitter, sent);
+        assert!(jitter.pop().unwrap().is_some());
+        assert!(jitter.is_playing());
+    }
+
+    #[test]
+    fn test_reorders_and_conceals_loss() {
+        let mut jitter = buffer();
+        let mut sent = packets(6, u16::MAX - 2);
+        sent.swap(0, 1);
+        sent.remove(3); // lost
+
+        push_all(&mut jitter, sent);
+        let frames: Vec<_> = (0..6).map(|_| jitter.pop().unwrap().unwrap()).collect();
+
+        // Every slot produced a full frame, the lost one included
+        assert!(frames.iter().all(|frame| frame.data.len() == 480));
+        assert_eq!(
+            jitter.stats(),
+            JitterStats {
+                decoded: 5,
+                concealed: 1,
+                concealment_events: 1,
+                ..Default::default()
+            }
+        );
+        let playout = jitter.playout_stats();
+        assert_eq!(playout.concealment_events, 1);
+        assert!((playout.concealed_percent - 100.0 / 6.0).abs() < 0.01);
+        assert_eq!(playout.jitter_buffer_delay_ms, 0);
+    }
+
+    #[test]
+    fn test_late_and_duplicate_packets_are_dropped() {
+        let mut jitter = buffer();
+        let sent = packets(4, 10);
+        push_all(&mut jitter, sent[1..].iter().cloned());
+        jitter.pop().unwrap();
+
+        jitter.push(sent[0].clone());
+        jitter.push(sent[2].clone());
+        assert_eq!(jitter.stats().late, 1);
+        assert_eq!(jitter.stats().duplicates, 1);
+    }
+
+    #[test]
+    fn test_target_follows_measured_jitter() {
+        use crate::audio_clock::OpusFrameSize;
+
+        let config = JitterBufferConfig::for_clock(&AudioClock::new(OpusFrameSize::Ms10));
+        let decoder = OpusDecoder::new(SampleRate::Hz48000, Channels::Mono).unwrap();
+        let mut jitter = AudioJitterBuffer::new(Box::new(decoder), config);
+        let start = Instant::now();
+        let mut sent = packets(120, 0).into_iter().enumerate();
+
+        // A steady path needs little buffering, so the delay shrinks to
+        // the minimum
+        for (i, packet) in sent.by_ref().take(60) {
+            jitter.push_at(packet, start + Duration::from_millis(i as u64 * 10));
+            jitter.pop().unwrap();
+        }
+        assert_eq!(jitter.target_depth(), config.min_target_depth);
+        assert_eq!(
+            jitter.stats().skipped,
+            (config.target_depth - config.min_target_depth) as u64
+        );
+
+        // Every other packet 60 ms late grows it again
+        for (i, packet) in sent {
+            let delay = if i % 2 == 1 { 60 } else { 0 };
+            jitter.push_at(packet, start + Duration::from_millis(i as u64 * 10 + delay));
+            jitter.pop().unwrap();
+        }
+        assert!(jitter.jitter() > Duration::from_millis(20));
+        assert!(jitter.target_depth() > config.target_depth);
+        assert!(jitter.target_depth() <= config.max_target_depth);
+        assert!(jitter.stats().stretched > 0);
+    }
+
+    #[test]
+    fn test_target_range_is_bounded() {
+        let config = JitterBufferConfig::default()
+            .with_target_range(Duration::from_millis(5), Duration::from_millis(80));
+        // 20 ms frames: 20 ms is the floor, and the 60 ms start fits
+        assert_eq!(
+            (
+                config.min_target_depth,
+                config.max_target_depth,
+                config.target_depth
+            ),
+            (1, 4, 3)
+        );
+        let narrow = JitterBufferConfig::default()
+            .with_target_range(Duration::from_millis(100), Duration::from_secs(1));
+        assert_eq!(
+            (
+                narrow.min_target_depth,
+                narrow.max_target_depth,
+                narrow.target_depth
+            ),
+            (5, 10, 5)
+        );
+    }
+
+    #[test]
+    fn test_rebuffers_after_long_outage() {
+        let mut jitter = buffer();
+        push_all(&mut jitter, packets(3, 0));
+        for _ in 0..3 {
+            jitter.pop().unwrap();
+        }
+        for _ in 0..JitterBufferConfig::default().max_concealed {
+            assert!(jitter.pop().unwrap().is_some());
+        }
+        assert!(jitter.pop().unwrap().is_none());
+        assert!(!jitter.is_playing());
+    }
+}
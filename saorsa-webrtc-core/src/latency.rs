@@ -0,0 +1,289 @@
+//! End-to-end latency instrumentation
+//!
+//! A video frame passes six stages between camera and screen, and each
+//! stamps the wall-clock time it finished in the frame's [`FrameTiming`]:
+//!
+//! * capture and encode: [`MediaPipeline`](crate::pipeline::MediaPipeline)
+//! * send and receive: [`WebRtcQuicBridge`](crate::quic_bridge::WebRtcQuicBridge),
+//!   which carries the sender's stamps to the receiver in the RTP packet
+//! * decode and render: the application, once the frame is on screen
+//!
+//! Completed timings go to a per-call [`LatencyTracker`], whose
+//! [`LatencyBreakdown`] shows which stage a "video feels laggy" report
+//! should be blamed on.
+//!
+//! The first three stages are stamped by the sender's clock. They are
+//! converted with the peer's [`ClockEstimate`] when one is known; without
+//! one the network figure also contains the offset between the clocks.
+
+use crate::clock::ClockEstimate;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Rendered frames averaged in a breakdown
+pub const LATENCY_WINDOW: usize = 120;
+
+/// Point a frame passes on its way from camera to screen, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LatencyStage {
+    /// Frame entered the sender's pipeline
+    Capture,
+    /// Encoder produced the frame
+    Encode,
+    /// Frame handed to the transport
+    Send,
+    /// Frame arrived at the receiver
+    Receive,
+    /// Decoder produced the frame
+    Decode,
+    /// Frame shown on screen
+    Render,
+}
+
+impl LatencyStage {
+    /// Every stage, in the order frames pass them
+    pub const ALL: [Self; 6] = [
+        Self::Capture,
+        Self::Encode,
+        Self::Send,
+        Self::Receive,
+        Self::Decode,
+        Self::Render,
+    ];
+
+    /// Whether the stage is stamped by the sender's clock
+    #[must_use]
+    pub fn is_sender(self) -> bool {
+        matches!(self, Self::Capture | Self::Encode | Self::Send)
+    }
+}
+
+/// Wall-clock time a frame finished each stage
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameTiming {
+    /// Microseconds since the Unix epoch, by [`LatencyStage`]
+    stamps: [Option<i64>; 6],
+}
+
+impl FrameTiming {
+    /// Create a timing with no stages stamped
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamp `stage` with the current time
+    pub fn mark(&mut self, stage: LatencyStage) {
+        self.mark_at(stage, Utc::now());
+    }
+
+    /// Stamp `stage` with `at`
+    pub fn mark_at(&mut self, stage: LatencyStage, at: DateTime<Utc>) {
+        self.stamps[stage as usize] = Some(at.timestamp_micros());
+    }
+
+    /// When the frame finished `stage`, if stamped
+    #[must_use]
+    pub fn get(&self, stage: LatencyStage) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp_micros(self.stamps[stage as usize]?)
+    }
+
+    /// Whether every stage is stamped
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.stamps.iter().all(Option::is_some)
+    }
+}
+
+/// Mean time spent in each stage over recent frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyBreakdown {
+    /// Capture to encoded, in microseconds
+    pub encode_us: i64,
+    /// Encoded to sent: packetization, encryption and queueing
+    pub send_us: i64,
+    /// Sent to received
+    pub network_us: i64,
+    /// Received to decoded, including the jitter buffer
+    pub decode_us: i64,
+    /// Decoded to on screen
+    pub render_us: i64,
+    /// Capture to on screen
+    pub total_us: i64,
+    /// Frames averaged
+    pub frames: usize,
+    /// Whether sender stamps were corrected for the peer clock offset
+    pub clock_corrected: bool,
+}
+
+impl LatencyBreakdown {
+    /// Time spent reaching `stage` from the one before it
+    ///
+    /// Zero for [`LatencyStage::Capture`], where frames start.
+    #[must_use]
+    pub fn stage_us(&self, stage: LatencyStage) -> i64 {
+        match stage {
+            LatencyStage::Capture => 0,
+            LatencyStage::Encode => self.encode_us,
+            LatencyStage::Send => self.send_us,
+            LatencyStage::Receive => self.network_us,
+            LatencyStage::Decode => self.decode_us,
+            LatencyStage::Render => self.render_us,
+        }
+    }
+
+    /// Stage that takes the longest to reach
+    #[must_use]
+    pub fn slowest_stage(&self) -> LatencyStage {
+        LatencyStage::ALL
+            .into_iter()
+            .max_by_key(|stage| self.stage_us(*stage))
+            .unwrap_or(LatencyStage::Capture)
+    }
+}
+
+/// Latency of a call's recently rendered frames
+#[derive(Debug, Clone, Default)]
+pub struct LatencyTracker {
+    /// Per-stage durations of each frame, in microseconds
+    frames: VecDeque<[i64; 5]>,
+    clock_corrected: bool,
+}
+
+impl LatencyTracker {
+    /// Create a tracker with no frames
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rendered frame's timing
+    ///
+    /// `clock` converts the sender's stamps to local time. Returns `false`,
+    /// ignoring the frame, if any stage is missing.
+    pub fn record(&mut self, timing: &FrameTiming, clock: Option<&ClockEstimate>) -> bool {
+        if !timing.is_complete() {
+            return false;
+        }
+        let offset = clock.map_or(0, |clock| clock.offset_us);
+        let local = |stage: LatencyStage| {
+            let stamp = timing.stamps[stage as usize].unwrap_or_default();
+            if stage.is_sender() {
+                stamp.saturating_sub(offset)
+            } else {
+                stamp
+            }
+        };
+
+        let mut durations = [0; 5];
+        for (duration, pair) in durations.iter_mut().zip(LatencyStage::ALL.windows(2)) {
+            *duration = local(pair[1]).saturating_sub(local(pair[0]));
+        }
+        if self.frames.len() == LATENCY_WINDOW {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(durations);
+        self.clock_corrected = clock.is_some();
+        true
+    }
+
+    /// Mean breakdown of the recorded frames, if any
+    #[must_use]
+    pub fn breakdown(&self) -> Option<LatencyBreakdown> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let count = self.frames.len() as i64;
+        let mut sums = [0i64; 5];
+        for frame in &self.frames {
+            for (sum, duration) in sums.iter_mut().zip(frame) {
+                *sum = sum.saturating_add(*duration);
+            }
+        }
+        let [encode_us, send_us, network_us, decode_us, render_us] = sums.map(|sum| sum / count);
+        Some(LatencyBreakdown {
+            encode_us,
+            send_us,
+            network_us,
+            decode_us,
+            render_us,
+            total_us: encode_us + send_us + network_us + decode_us + render_us,
+            frames: self.frames.len(),
+            clock_corrected: self.clock_corrected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    /// Timing with the given milliseconds spent reaching each stage
+    fn timing(start: DateTime<Utc>, stages_ms: [i64; 5]) -> FrameTiming {
+        let mut timing = FrameTiming::new();
+        let mut at = start;
+        timing.mark_at(LatencyStage::Capture, at);
+        for (stage, ms) in LatencyStage::ALL[1..].iter().zip(stages_ms) {
+            at += Duration::milliseconds(ms);
+            timing.mark_at(*stage, at);
+        }
+        timing
+    }
+
+    #[test]
+    fn test_breakdown_averages_stages() {
+        let mut tracker = LatencyTracker::new();
+        let start = Utc::now();
+        assert!(tracker.record(&timing(start, [10, 2, 40, 20, 8]), None));
+        assert!(tracker.record(&timing(start, [12, 4, 60, 20, 8]), None));
+
+        let breakdown = tracker.breakdown().unwrap();
+        assert_eq!(breakdown.frames, 2);
+        assert_eq!(breakdown.encode_us, 11_000);
+        assert_eq!(breakdown.network_us, 50_000);
+        assert_eq!(breakdown.total_us, 92_000);
+        assert_eq!(breakdown.slowest_stage(), LatencyStage::Receive);
+        assert!(!breakdown.clock_corrected);
+    }
+
+    #[test]
+    fn test_sender_stamps_use_clock_offset() {
+        // The sender's clock runs two seconds ahead of ours
+        let remote = timing(Utc::now(), [5, 1, 30, 10, 5]);
+        let mut timing = remote;
+        for stage in [
+            LatencyStage::Receive,
+            LatencyStage::Decode,
+            LatencyStage::Render,
+        ] {
+            timing.mark_at(stage, remote.get(stage).unwrap() - Duration::seconds(2));
+        }
+        let clock = ClockEstimate {
+            offset_us: 2_000_000,
+            rtt_us: 60_000,
+            samples: 3,
+        };
+
+        let mut tracker = LatencyTracker::new();
+        tracker.record(&timing, Some(&clock));
+        let breakdown = tracker.breakdown().unwrap();
+        assert_eq!(breakdown.network_us, 30_000);
+        assert_eq!(breakdown.total_us, 51_000);
+        assert!(breakdown.clock_corrected);
+    }
+
+    #[test]
+    fn test_incomplete_timing_ignored() {
+        let mut tracker = LatencyTracker::new();
+        let mut timing = FrameTiming::new();
+        timing.mark(LatencyStage::Capture);
+        timing.mark(LatencyStage::Render);
+
+        assert!(!tracker.record(&timing, None));
+        assert!(tracker.breakdown().is_none());
+        assert!(timing.get(LatencyStage::Capture).is_some());
+        assert!(timing.get(LatencyStage::Send).is_none());
+    }
+}
@@ -51,6 +51,12 @@
 /// Core WebRTC types and data structures
 pub mod types;
 
+/// Machine-readable error codes
+pub mod error;
+
+/// Deadlines and cancellation for async calls
+pub mod deadline;
+
 /// WebRTC service and configuration
 pub mod service;
 
@@ -75,19 +81,305 @@ pub mod quic_bridge;
 /// Peer identity abstraction
 pub mod identity;
 
+/// Non-lossy typed event bus
+pub mod events;
+
+/// Call quality alerting
+pub mod quality;
+
+/// Bandwidth estimation
+pub mod bwe;
+
+/// Video degradation ladder
+pub mod degradation;
+
+/// Recording playback and export
+pub mod recording;
+
+/// Voicemail / leave-a-message support
+pub mod voicemail;
+
+/// In-call snapshot capture
+pub mod snapshot;
+
+/// Pre-call microphone self-test
+pub mod mic_test;
+
+/// Group call participant management
+pub mod conference;
+
+/// Active speaker detection and simulcast layer selection
+pub mod active_speaker;
+
+/// Headless participants for bots and gateways
+pub mod bot;
+
+/// Packet-level media relay for SFU and relay roles
+pub mod relay;
+
+/// Persistent rooms layered on conferences
+pub mod room;
+
+/// Call parking and cross-device retrieval
+pub mod parking;
+
+/// Device-to-device call handoff
+pub mod handoff;
+
+/// Media pipeline graph
+pub mod pipeline;
+
+/// Runtime topology for background work
+pub mod runtime;
+
+/// Forward error correction for media packets
+pub mod fec;
+
+/// Adaptive audio jitter buffer with packet loss concealment
+pub mod jitter;
+
+/// Service state snapshots for crash recovery
+pub mod resume;
+
+/// Per-call log capture for bug reports
+pub mod call_logs;
+
+/// Peer clock offset estimation
+pub mod clock;
+
+/// Codec capability negotiation
+pub mod capabilities;
+
+/// End-to-end media encryption
+pub mod e2ee;
+
+/// End-to-end latency instrumentation
+pub mod latency;
+
+/// Signed call invitations and call screening
+pub mod invitation;
+
+/// Microphone capture
+pub mod audio_capture;
+
+/// Screen and window capture
+pub mod screen_capture;
+
+/// Capture device discovery and hot-plug detection
+pub mod devices;
+
+/// Rolling per-call stats history
+pub mod stats_history;
+
+/// Playback of received audio
+pub mod audio_render;
+
+/// Send-side congestion detection
+pub mod congestion;
+
+/// Delivery of decoded remote video
+pub mod remote_video;
+
+/// Screen share annotations
+pub mod annotation;
+
+/// Audio level metering and voice activity detection
+pub mod audio_level;
+
+/// Remote control of screen shares
+pub mod remote_control;
+
+/// Recording calls to media files
+pub mod media_recorder;
+
+/// Media pipeline stall detection and recovery
+pub mod watchdog;
+
+/// Audio frame size and clock configuration
+pub mod audio_clock;
+
+/// Synthetic and custom media sources
+pub mod media_source;
+
+/// Playing media files into calls
+pub mod file_playback;
+
+/// One-to-many broadcast
+pub mod broadcast;
+
+/// getUserMedia-style capture constraints
+pub mod constraints;
+
+/// Synchronized playback for watch-together
+pub mod playback_sync;
+
+/// Configuration reload for long-running nodes
+pub mod reload;
+
+/// Network path policy for ICE and QUIC
+pub mod path_policy;
+
+/// Video frame reassembly from packets
+pub mod frame_assembly;
+
+/// Identities and signaling over saorsa-core's DHT
+#[cfg(feature = "saorsa-core")]
+pub mod saorsa_dht;
+
+/// Text messaging over data channels
+pub mod messaging;
+
+/// Loopback echo peer for trying calls in one process
+pub mod echo_peer;
+
+/// Mesh group calls
+pub mod mesh;
+
+/// Selective forwarding of conference media
+pub mod router;
+
+/// DTMF key presses over a data channel
+pub mod dtmf;
+
+/// Per-call statistics
+pub mod call_stats;
+
+/// Call history and call detail records
+pub mod call_history;
+
+/// Pre-call path probing
+pub mod path_probe;
+
+/// Signaling sessions and reuse protection
+pub mod session;
+
+/// Little-endian reading of data channel wire formats
+pub(crate) mod wire;
+
 // Re-export main types at crate root
-pub use call::{CallManager, CallManagerConfig};
+pub use active_speaker::{ActiveSpeakerConfig, ActiveSpeakerDetector, SimulcastLayer};
+pub use annotation::{Annotation, AnnotationError, AnnotationPoint};
+pub use audio_capture::{AudioCapture, AudioFramer};
+pub use audio_clock::{AudioClock, OpusFrameSize};
+pub use audio_level::{
+    AudioLevel, AudioLevelConfig, AudioLevelMeter, AudioLevelNode, AudioLevelSource,
+};
+pub use audio_render::{AudioMixer, AudioRenderer, Resampler};
+pub use bot::{
+    BotConfig, BotError, BotFrame, BotMedia, ChannelSink, HeadlessParticipant, MediaHub,
+    MediaSink, PushSource,
+};
+pub use broadcast::{
+    Broadcast, BroadcastConfig, BroadcastError, BroadcastId, BROADCAST_AUDIO_SSRC,
+    BROADCAST_VIDEO_SSRC,
+};
+pub use bwe::{BandwidthEstimator, BandwidthUsage, BweConfig, GccEstimator, PacketFeedback};
+pub use call::{AnswerPolicy, CallManager, CallManagerConfig, RetrySchedule};
+pub use call_history::{
+    CallDirection, CallEndReason, CallHistory, CallHistoryStore, CallRecord, HistoryError,
+    HistoryQuery, JsonFileCallHistory, MemoryCallHistory,
+};
+pub use call_logs::{CallLogBuffer, CallLogBundle, CallLogLayer, LogEntry};
+pub use call_stats::{CallStats, CallStatsSampler, TransportStats};
+pub use capabilities::{CodecCapabilities, MaxResolution, NegotiatedCodecs, PeerCapabilities};
+pub use clock::{ClockEstimate, ClockEstimator, ClockExchange};
+pub use conference::{
+    BreakoutGroup, Conference, ConferenceError, ConferenceEvent, ConferenceId, JoinOutcome,
+    JoinRequest, Participant, ParticipantAction, ParticipantRole,
+};
+pub use congestion::{CongestionConfig, CongestionMonitor, CongestionSeverity};
+pub use constraints::{
+    AudioConstraints, CameraMode, CameraSelection, ConstrainRange, ConstraintError, FacingMode,
+    VideoConstraints,
+};
+pub use deadline::{CancellationToken, Deadline, Interrupted};
+pub use degradation::{
+    DegradationConfig, DegradationEvent, DegradationLadder, DegradationLevel,
+    DegradationPreference, EncodeLimits, EncodeLoadConfig, EncodeLoadController, EncodeLoadEvent,
+    LadderStep,
+};
+pub use devices::{DeviceChanges, DEFAULT_DEVICE_POLL_INTERVAL};
+pub use dtmf::{DtmfError, DtmfTone, DTMF_CHANNEL_LABEL};
+pub use e2ee::{
+    E2eeError, ExportConsent, ExportedKey, FrameCipher, FrameEncryptor, KeyProvider, MediaCipher,
+    MediaKey, MediaKeyExport, StaticKeyProvider, E2EE_VERSION,
+};
+pub use echo_peer::{EchoPeer, LoopbackError, LoopbackNetwork, LoopbackSignaling};
+pub use error::{BoxError, CodedError, ErrorCode, ErrorReport};
+pub use events::{EventBus, EventBusStats, EventSubscription, EventTopic, TopicChannel};
+pub use fec::{FecDecoder, FecEncoder, FEC_PAYLOAD_TYPE};
+pub use file_playback::{FilePlaybackSource, PlaybackError};
+pub use frame_assembly::{
+    AssembledFrame, FrameAssemblyConfig, FrameAssemblyStats, VideoFrameAssembler,
+};
+pub use handoff::{HandoffError, HandoffState, HandoffTracker};
 pub use identity::{PeerIdentity, PeerIdentityString};
+pub use invitation::{
+    AutoAnswer, CallScreen, CallToken, InvitationError, InvitationIssuer, InvitationKey,
+    ScreeningDecision,
+};
+pub use jitter::{AudioJitterBuffer, AudioPlayoutStats, JitterBufferConfig, JitterStats};
+pub use latency::{FrameTiming, LatencyBreakdown, LatencyStage, LatencyTracker};
 pub use media::{
-    AudioDevice, AudioTrack, MediaEvent, MediaStream, MediaStreamManager, VideoDevice, VideoTrack,
+    AudioDevice, AudioTrack, MediaEvent, MediaStream, MediaStreamManager, RemoteTrack,
+    StreamTrack, VideoDevice, VideoTrack,
+};
+pub use media_recorder::{ContainerFormat, MediaRecorder, RecordingOptions, RecordingSource};
+pub use media_source::{AudioSource, FrameSource, SineWave, SourceFeed, TestPattern};
+pub use mesh::{ConferenceManager, MeshError, MeshEvent};
+pub use messaging::{MessageError, TextMessage, MESSAGE_CHANNEL_LABEL};
+pub use mic_test::{EchoRisk, MicSelfTest, MicTestConfig, MicTestReport};
+pub use parking::{ParkToken, ParkedCall, ParkingError, ParkingLot};
+pub use path_policy::{PathError, PathPolicy};
+pub use path_probe::{
+    PathProbe, PathProbeReport, PathRecommendation, ProbeConfig, MAX_PROBE_PADDING,
+};
+pub use pipeline::{
+    AudioEncoderNode, BlurNode, EncodedFrame, EncoderNode, KeyframePolicy, MediaPayload,
+    MediaPipeline, MeterNode, PacketLossFeedback, PipelineError, PipelineMeter, PipelineNode,
+    PipelineStage, PushToTalkGate, RecorderNode, TransmitSwitch, VoiceActivityGate,
+};
+pub use playback_sync::{SyncAction, SyncError, SyncMessage, SyncSession, SyncUpdate};
+pub use quality::{
+    AlertSeverity, QualityAlert, QualityCondition, QualityMonitor, QualityPolicy, QualityRule,
+    Remediation,
+};
+pub use quic_bridge::{Packetizer, RtpPacket, StreamConfig, StreamType, WebRtcQuicBridge};
+pub use recording::{
+    ExportFormat, ExportOptions, RecordedFrame, RecordedTrackKind, RecordingError,
+    RecordingPlayer, RecordingReader, RecordingWriter,
+};
+pub use relay::{PacketRelay, RelayStats, StreamRewriter};
+pub use reload::{ConfigError, ReloadableConfig};
+pub use remote_control::{
+    MouseButton, RemoteControlAction, RemoteControlError, RemoteControlGrants, RemoteInput,
+};
+pub use remote_video::{DecodeStats, RemoteVideo, VideoFrameSink, VideoSinkId};
+pub use resume::{CallSnapshot, RestoredCall, ServiceSnapshot, SNAPSHOT_VERSION};
+pub use room::{Room, RoomAdmission, RoomError, RoomId, RoomSettings};
+pub use router::{MediaRouter, RouterError, ROUTER_SSRC_BASE};
+pub use runtime::{
+    DedicatedRuntimes, RuntimeConfig, RuntimeError, RuntimeTopology, ThreadHook,
+};
+#[cfg(feature = "saorsa-core")]
+pub use saorsa_dht::{DhtPeer, DhtSignaling, DhtSignalingError, DhtWebRtcService};
+pub use screen_capture::{
+    ScreenCaptureConfig, ScreenCapturer, ScreenShare, ScreenSource, ScreenSourceKind,
+};
+pub use service::{
+    DynWebRtcService, WebRtcConfig, WebRtcEvent, WebRtcService, WebRtcServiceBuilder,
 };
-pub use quic_bridge::{RtpPacket, StreamConfig, StreamType, WebRtcQuicBridge};
-pub use service::{WebRtcConfig, WebRtcEvent, WebRtcService, WebRtcServiceBuilder};
+pub use session::{SessionConfig, SessionError, SessionRegistry};
 pub use signaling::{
-    SignalingHandler, SignalingMessage as SignalingMessageType, SignalingTransport,
+    BoxedSignalingTransport, DynSignalingTransport, DynTransportError, SignalingHandler,
+    SignalingMessage as SignalingMessageType, SignalingTransport,
 };
-pub use transport::{AntQuicTransport, TransportConfig};
+pub use snapshot::{SnapshotError, SnapshotFormat, SnapshotStore};
+pub use stats_history::{StatsHistory, StatsHistoryConfig, StatsHistoryStore, STATS_CSV_HEADER};
+pub use transport::{AntQuicTransport, TransportConfig, TransportEvent};
 pub use types::*;
+pub use voicemail::{Voicemail, VoicemailConfig, VoicemailError, VoicemailRecorder};
+pub use watchdog::{Heartbeat, HeartbeatNode, MediaWatchdog, Recovery, WatchTarget, WatchdogConfig};
 
 /// Prelude module for convenient imports
 pub mod prelude {
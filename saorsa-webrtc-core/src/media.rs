@@ -2,14 +2,39 @@
 //!
 //! This module handles audio, video, and screen share media streams.
 
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::broadcast;
 use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
+use webrtc::track::track_remote::TrackRemote;
 use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
+use crate::audio_capture::{self, AudioCapture};
+use crate::audio_clock::AudioClock;
+use crate::audio_level::{AudioLevel, AudioLevelConfig, AudioLevelNode, AudioLevelSource};
+use crate::audio_render::{self, AudioRenderer};
+use crate::capabilities::{video_mime_type, OPUS_MIME_TYPE};
+use crate::constraints::{
+    AudioConstraints, CameraMode, CameraSelection, ConstraintError, FacingMode, VideoConstraints,
+};
+use crate::devices::{self, diff_devices};
+use crate::error::{BoxError, CodedError, ErrorCode};
+use crate::events::TopicChannel;
+use crate::media_source::{self, AudioSource, FrameSource, SourceFeed};
+use crate::pipeline::{
+    AudioEncoderNode, EncoderNode, KeyframePolicy, MediaPayload, MediaPipeline, PacketLossFeedback,
+    PipelineStage, PushToTalkGate, TransmitSwitch, AUDIO_LEVEL_NODE, ENCODER_NODE,
+};
+use crate::quic_bridge::StreamType;
+use crate::screen_capture::{self, ScreenCaptureConfig, ScreenCapturer, ScreenSource};
 use crate::types::MediaType;
-use saorsa_webrtc_codecs::{VideoCodec, VideoEncoder, VideoDecoder, VideoFrame, OpenH264Encoder, OpenH264Decoder};
+use crate::watchdog::{MediaWatchdog, Recovery, WatchTarget, WatchdogConfig};
+use saorsa_webrtc_codecs::{VideoCodec, VideoDecoder, VideoEncoder, VideoFrame, OpenH264Encoder, OpenH264Decoder};
+use saorsa_webrtc_codecs::{Av1Decoder, Av1Encoder, Av1EncoderConfig};
+use saorsa_webrtc_codecs::{EncoderPreset, H264EncoderConfig, HardwareBackend, HardwareEncoder};
+use saorsa_webrtc_codecs::{OpusEncoder, OpusEncoderConfig};
 
 /// Media-related errors
 #[derive(Error, Debug)]
@@ -18,13 +43,39 @@ pub enum MediaError {
     #[error("Device not found: {0}")]
     DeviceNotFound(String),
 
-    /// Stream error
-    #[error("Stream error: {0}")]
-    StreamError(String),
+    /// Track missing from the manager
+    #[error("Track not found: {0}")]
+    TrackNotFound(String),
+
+    /// Capture device could not be opened or streamed
+    #[error("Capture failed: {0}")]
+    Capture(String),
+
+    /// Encoder could not be set up for the codec
+    #[error("Failed to set up {codec:?} encoder")]
+    Codec {
+        /// Codec being set up
+        codec: VideoCodec,
+        /// Underlying failure
+        #[source]
+        source: BoxError,
+    },
+
+    /// No device or mode satisfies the capture constraints
+    #[error(transparent)]
+    Overconstrained(#[from] ConstraintError),
+}
 
-    /// Configuration error
-    #[error("Configuration error: {0}")]
-    ConfigError(String),
+impl CodedError for MediaError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::DeviceNotFound(_) => ErrorCode::DeviceNotFound,
+            Self::TrackNotFound(_) => ErrorCode::TrackFailed,
+            Self::Capture(_) => ErrorCode::DeviceNotFound,
+            Self::Codec { .. } => ErrorCode::CodecFailed,
+            Self::Overconstrained(e) => e.code(),
+        }
+    }
 }
 
 /// Media events
@@ -50,6 +101,43 @@ pub enum MediaEvent {
         /// Stream identifier
         stream_id: String,
     },
+    /// Periodic audio level of a local track or a call's received audio
+    AudioLevel {
+        /// Track or call the audio belongs to
+        source: AudioLevelSource,
+        /// Loudest RMS level since the last report, in dBFS
+        level_dbfs: f32,
+        /// Whether someone is speaking
+        voice_active: bool,
+    },
+    /// A pipeline stage stopped producing frames
+    PipelineStalled {
+        /// Stalled stage
+        target: WatchTarget,
+        /// How long it has produced nothing
+        stalled_ms: u64,
+        /// Recovery being attempted; `None` once attempts have run out
+        recovery: Option<Recovery>,
+    },
+    /// A stalled pipeline stage is producing frames again
+    PipelineRecovered {
+        /// Recovered stage
+        target: WatchTarget,
+        /// How long it was stalled
+        stalled_ms: u64,
+    },
+}
+
+impl MediaEvent {
+    /// Event reporting `level` for `source`
+    #[must_use]
+    pub fn audio_level(source: AudioLevelSource, level: AudioLevel) -> Self {
+        Self::AudioLevel {
+            source,
+            level_dbfs: level.level_dbfs,
+            voice_active: level.voice_active,
+        }
+    }
 }
 
 /// Audio device
@@ -68,6 +156,10 @@ pub struct VideoDevice {
     pub id: String,
     /// Device name
     pub name: String,
+    /// Direction the camera faces, if known
+    pub facing_mode: Option<FacingMode>,
+    /// Modes the camera captures in; empty if they could not be listed
+    pub modes: Vec<CameraMode>,
 }
 
 /// Audio track
@@ -83,14 +175,16 @@ pub struct VideoTrack {
 pub id: String,
     /// WebRTC track
     pub webrtc_track: Arc<TrackLocalStaticSample>,
-    /// Video encoder (optional)
-    pub encoder: Option<Box<dyn VideoEncoder>>,
+    /// Outgoing chain of effects, encoder and taps
+    pub pipeline: MediaPipeline,
     /// Video decoder (optional)
     pub decoder: Option<Box<dyn VideoDecoder>>,
     /// Track width
     pub width: u32,
     /// Track height
     pub height: u32,
+    /// Keyframe policy of encoders added to the track
+    pub keyframes: KeyframePolicy,
 }
 
 impl VideoTrack {
@@ -99,20 +193,61 @@ impl VideoTrack {
     Self {
         id,
         webrtc_track,
-        encoder: None,
+        pipeline: MediaPipeline::new(),
         decoder: None,
         width,
             height,
+            keyframes: KeyframePolicy::default(),
         }
     }
 
+    /// Set the keyframe interval and intra refresh of encoders added after
+    /// this call
+    #[must_use]
+    pub fn with_keyframe_policy(mut self, keyframes: KeyframePolicy) -> Self {
+        self.keyframes = keyframes;
+        self
+    }
+
+    fn set_encoder(&mut self, encoder: Box<dyn VideoEncoder>) {
+        self.pipeline.set_encoder_node(
+            EncoderNode::new(encoder).with_intra_refresh(self.keyframes.intra_refresh),
+        );
+    }
+
     /// Add H.264 encoder to this track
     pub fn with_h264_encoder(mut self) -> anyhow::Result<Self> {
         let encoder = OpenH264Encoder::new()?;
         // Configure encoder with track dimensions
         // Note: In the full implementation, this would configure the encoder
         // For now, we assume the encoder can handle the dimensions
-        self.encoder = Some(Box::new(encoder));
+        self.set_encoder(Box::new(encoder));
+        Ok(self)
+    }
+
+    /// Add an H.264 encoder sized to this track, on the first backend in
+    /// `preference` that works here
+    ///
+    /// Falls back to software when no hardware encoder is available.
+    pub fn with_hardware_encoder(
+        mut self,
+        preference: &[HardwareBackend],
+        preset: EncoderPreset,
+    ) -> anyhow::Result<Self> {
+        let config = H264EncoderConfig {
+            width: self.width,
+            height: self.height,
+            keyframe_interval: self.keyframes.interval_frames,
+            ..Default::default()
+        }
+        .with_preset(preset);
+        let encoder = HardwareEncoder::new(config, preference)?;
+        tracing::debug!(
+            "Track {} encoding H.264 with {}",
+            self.id,
+            encoder.backend().name()
+        );
+        self.set_encoder(Box::new(encoder));
         Ok(self)
     }
 
@@ -123,24 +258,69 @@ impl VideoTrack {
         Ok(self)
     }
 
-    /// Encode a video frame
-    pub fn encode_frame(&mut self, frame_data: &[u8]) -> anyhow::Result<Vec<u8>> {
-        if let Some(encoder) = &mut self.encoder {
-            let frame = VideoFrame {
-                data: frame_data.to_vec(),
+    /// Add AV1 encoder to this track, tuned for screen content when
+    /// `stream_type` is [`StreamType::ScreenShare`]
+    ///
+    /// Requires the `av1` feature.
+    pub fn with_av1_encoder(
+        mut self,
+        stream_type: StreamType,
+        preset: EncoderPreset,
+    ) -> anyhow::Result<Self> {
+        let mut config = match stream_type {
+            StreamType::ScreenShare => Av1EncoderConfig::screen_share(self.width, self.height),
+            _ => Av1EncoderConfig {
                 width: self.width,
                 height: self.height,
-                timestamp: 0, // TODO: Add timestamp
-            };
-            let encoded = encoder.encode(&frame)?;
-            Ok(encoded.to_vec())
-        } else {
-            // No encoder - return raw data
-            Ok(frame_data.to_vec())
+                ..Default::default()
+            },
+        };
+        config.preset = preset;
+        if self.keyframes.interval_frames > 0 {
+            config.keyframe_interval = u64::from(self.keyframes.interval_frames);
+        }
+        self.set_encoder(Box::new(Av1Encoder::with_config(config)?));
+        Ok(self)
+    }
+
+    /// Add AV1 decoder to this track
+    ///
+    /// Requires the `av1` feature.
+    pub fn with_av1_decoder(mut self) -> anyhow::Result<Self> {
+        let decoder = Av1Decoder::new()?;
+        self.decoder = Some(Box::new(decoder));
+        Ok(self)
+    }
+
+    /// Run a captured frame through the track's pipeline
+    ///
+    /// Returns the encoded frame, the raw frame if the pipeline has no
+    /// encoder, or nothing if a node dropped it.
+    pub fn encode_frame(&mut self, frame_data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let frame = VideoFrame {
+            data: frame_data.to_vec(),
+            width: self.width,
+            height: self.height,
+            timestamp: 0, // TODO: Add timestamp
+        };
+        match self.pipeline.push(MediaPayload::Video(frame))? {
+            Some(MediaPayload::Encoded(encoded)) => Ok(encoded.data.to_vec()),
+            Some(MediaPayload::Video(raw)) => Ok(raw.data),
+            Some(MediaPayload::Audio(_)) | None => Ok(Vec::new()),
         }
     }
 
+    /// Make the encoder's next frame a keyframe, e.g. because the remote
+    /// decoder asked for one
+    pub fn request_keyframe(&mut self) {
+        self.pipeline.request_keyframe();
+    }
+
     /// Decode a video frame
+    ///
+    /// On failure the decoder has likely lost its reference frames; ask
+    /// the sender for a keyframe with
+    /// [`WebRtcService::request_keyframe`](crate::service::WebRtcService::request_keyframe).
     pub fn decode_frame(&mut self, encoded_data: &[u8]) -> anyhow::Result<Vec<u8>> {
         if let Some(decoder) = &mut self.decoder {
             let frame = decoder.decode(encoded_data)?;
@@ -163,21 +343,135 @@ pub struct WebRtcTrack {
     pub id: String,
 }
 
-/// Media stream
+/// Track received from the remote party of a call
 #[derive(Debug, Clone)]
-pub struct MediaStream {
+pub struct RemoteTrack {
+    /// Received WebRTC track, read for its RTP packets
+    pub track: Arc<TrackRemote>,
+    /// Track type
+    pub track_type: MediaType,
+    /// Track ID
+    pub id: String,
+}
+
+/// Track that can be grouped into a [`MediaStream`]
+pub trait StreamTrack: Clone {
+    /// Track ID, unique within its stream
+    fn id(&self) -> &str;
+    /// Kind of media the track carries
+    fn kind(&self) -> MediaType;
+}
+
+impl StreamTrack for WebRtcTrack {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn kind(&self) -> MediaType {
+        self.track_type.clone()
+    }
+}
+
+impl StreamTrack for RemoteTrack {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn kind(&self) -> MediaType {
+        self.track_type.clone()
+    }
+}
+
+/// Tracks played or sent together, like a browser `MediaStream`
+///
+/// Streams we send hold [`WebRtcTrack`]s; streams received on a call hold
+/// [`RemoteTrack`]s. Tracks keep the order they were added in.
+#[derive(Debug, Clone)]
+pub struct MediaStream<T: StreamTrack = WebRtcTrack> {
     /// Stream identifier
     pub id: String,
+    tracks: Vec<T>,
+}
+
+impl<T: StreamTrack> MediaStream<T> {
+    /// Create an empty stream
+    #[must_use]
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Every track in the stream
+    #[must_use]
+    pub fn tracks(&self) -> &[T] {
+        &self.tracks
+    }
+
+    /// Audio tracks in the stream
+    pub fn audio_tracks(&self) -> impl Iterator<Item = &T> {
+        self.tracks.iter().filter(|t| t.kind() == MediaType::Audio)
+    }
+
+    /// Camera and screen share tracks in the stream
+    pub fn video_tracks(&self) -> impl Iterator<Item = &T> {
+        self.tracks
+            .iter()
+            .filter(|t| matches!(t.kind(), MediaType::Video | MediaType::ScreenShare))
+    }
+
+    /// Track with the given ID
+    #[must_use]
+    pub fn track(&self, track_id: &str) -> Option<&T> {
+        self.tracks.iter().find(|t| t.id() == track_id)
+    }
+
+    /// Add a track, returning false if one with its ID is already here
+    pub fn add_track(&mut self, track: T) -> bool {
+        if self.track(track.id()).is_some() {
+            return false;
+        }
+        self.tracks.push(track);
+        true
+    }
+
+    /// Remove a track, returning it if it was here
+    pub fn remove_track(&mut self, track_id: &str) -> Option<T> {
+        let index = self.tracks.iter().position(|t| t.id() == track_id)?;
+        Some(self.tracks.remove(index))
+    }
+
+    /// Number of tracks
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Whether the stream has no tracks
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
 }
 
 /// Media stream manager
 pub struct MediaStreamManager {
     event_sender: broadcast::Sender<MediaEvent>,
-    #[allow(dead_code)]
-    audio_devices: Vec<AudioDevice>,
-    #[allow(dead_code)]
-    video_devices: Vec<VideoDevice>,
+    event_bus: Option<Arc<TopicChannel<MediaEvent>>>,
+    audio_devices: RwLock<Vec<AudioDevice>>,
+    video_devices: RwLock<Vec<VideoDevice>>,
     webrtc_tracks: Vec<WebRtcTrack>,
+    encoder_preference: Vec<HardwareBackend>,
+    encoder_preset: EncoderPreset,
+    video_keyframes: KeyframePolicy,
+    screen_keyframes: KeyframePolicy,
+    transmit: TransmitSwitch,
+    noise_gate_dbfs: Option<f32>,
+    packet_loss: PacketLossFeedback,
+    audio_levels: AudioLevelConfig,
+    audio_clock: AudioClock,
+    watchdog: Arc<MediaWatchdog>,
 }
 
 impl MediaStreamManager {
@@ -187,10 +481,161 @@ impl MediaStreamManager {
         let (event_sender, _) = broadcast::channel(100);
         Self {
             event_sender,
-            audio_devices: Vec::new(),
-            video_devices: Vec::new(),
+            event_bus: None,
+            audio_devices: RwLock::new(Vec::new()),
+            video_devices: RwLock::new(Vec::new()),
             webrtc_tracks: Vec::new(),
+            encoder_preference: HardwareBackend::ALL.to_vec(),
+            encoder_preset: EncoderPreset::default(),
+            video_keyframes: KeyframePolicy::default(),
+            screen_keyframes: KeyframePolicy::screen_share(),
+            transmit: TransmitSwitch::new(),
+            noise_gate_dbfs: None,
+            packet_loss: PacketLossFeedback::new(),
+            audio_levels: AudioLevelConfig::default(),
+            audio_clock: AudioClock::default(),
+            watchdog: Arc::new(MediaWatchdog::default()),
+        }
+    }
+
+    /// Create a media stream manager that also publishes on the event bus
+    #[must_use]
+    pub fn with_event_bus(event_bus: Arc<TopicChannel<MediaEvent>>) -> Self {
+        Self {
+            event_bus: Some(event_bus),
+            ..Self::new()
+        }
+    }
+
+    /// Set the order in which H.264 encoder backends are tried
+    #[must_use]
+    pub fn with_encoder_preference(mut self, preference: Vec<HardwareBackend>) -> Self {
+        self.encoder_preference = preference;
+        self
+    }
+
+    /// Order in which H.264 encoder backends are tried
+    #[must_use]
+    pub fn encoder_preference(&self) -> &[HardwareBackend] {
+        &self.encoder_preference
+    }
+
+    /// Set the latency and quality trade-off for video encoders
+    #[must_use]
+    pub fn with_encoder_preset(mut self, preset: EncoderPreset) -> Self {
+        self.encoder_preset = preset;
+        self
+    }
+
+    /// Latency and quality trade-off for video encoders
+    #[must_use]
+    pub fn encoder_preset(&self) -> EncoderPreset {
+        self.encoder_preset
+    }
+
+    /// Set the keyframe policy of new tracks of `stream_type`
+    ///
+    /// [`StreamType::ScreenShare`] sets screen share tracks; any other type
+    /// sets camera tracks.
+    #[must_use]
+    pub fn with_keyframe_policy(mut self, stream_type: StreamType, keyframes: KeyframePolicy) -> Self {
+        match stream_type {
+            StreamType::ScreenShare => self.screen_keyframes = keyframes,
+            _ => self.video_keyframes = keyframes,
+        }
+        self
+    }
+
+    /// Keyframe policy of new tracks of `stream_type`
+    #[must_use]
+    pub fn keyframe_policy(&self, stream_type: StreamType) -> KeyframePolicy {
+        match stream_type {
+            StreamType::ScreenShare => self.screen_keyframes,
+            _ => self.video_keyframes,
+        }
+    }
+
+    /// Start with push-to-talk on or off
+    #[must_use]
+    pub fn with_push_to_talk(self, enabled: bool) -> Self {
+        self.transmit.set_push_to_talk(enabled);
+        self
+    }
+
+    /// Drop captured audio quieter than `threshold_dbfs`; `None` sends
+    /// everything
+    #[must_use]
+    pub fn with_noise_gate(mut self, threshold_dbfs: Option<f32>) -> Self {
+        self.noise_gate_dbfs = threshold_dbfs;
+        self
+    }
+
+    /// Set how microphones are metered for [`MediaEvent::AudioLevel`]
+    #[must_use]
+    pub fn with_audio_levels(mut self, config: AudioLevelConfig) -> Self {
+        self.audio_levels = config;
+        self
+    }
+
+    /// Set the rate and frame size microphones are captured and encoded at
+    #[must_use]
+    pub fn with_audio_clock(mut self, clock: AudioClock) -> Self {
+        self.audio_clock = clock;
+        self
+    }
+
+    /// Rate and frame size microphones are captured and encoded at
+    #[must_use]
+    pub fn audio_clock(&self) -> AudioClock {
+        self.audio_clock
+    }
+
+    /// Set how long capture, encoders and decoders may stall before the
+    /// watchdog steps in
+    #[must_use]
+    pub fn with_watchdog(mut self, config: WatchdogConfig) -> Self {
+        self.watchdog = Arc::new(MediaWatchdog::new(config));
+        self
+    }
+
+    /// Watchdog over the pipelines of this manager's captures
+    #[must_use]
+    pub fn watchdog(&self) -> &Arc<MediaWatchdog> {
+        &self.watchdog
+    }
+
+    /// Push-to-talk switch for microphones captured by this manager
+    #[must_use]
+    pub fn transmit_switch(&self) -> &TransmitSwitch {
+        &self.transmit
+    }
+
+    /// Measured packet loss that microphones captured by this manager
+    /// adapt their Opus in-band FEC to
+    #[must_use]
+    pub fn packet_loss_feedback(&self) -> &PacketLossFeedback {
+        &self.packet_loss
+    }
+
+    fn emit(&self, event: MediaEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(event.clone());
         }
+        let _ = self.event_sender.send(event);
+    }
+
+    /// Pipeline node emitting [`MediaEvent::AudioLevel`] for a local track
+    fn audio_level_node(&self, track_id: &str) -> AudioLevelNode {
+        let event_sender = self.event_sender.clone();
+        let event_bus = self.event_bus.clone();
+        let source = AudioLevelSource::Local(track_id.to_string());
+        AudioLevelNode::new(self.audio_levels, move |level| {
+            let event = MediaEvent::audio_level(source.clone(), level);
+            if let Some(bus) = &event_bus {
+                bus.publish(event.clone());
+            }
+            let _ = event_sender.send(event);
+        })
     }
 
     /// Initialize media devices
@@ -199,43 +644,279 @@ impl MediaStreamManager {
     ///
     /// Returns error if device initialization fails
     pub async fn initialize(&self) -> Result<(), MediaError> {
-        // For now, add some fake devices for testing
-        // In a real implementation, this would enumerate actual hardware devices
-        let audio_device = AudioDevice {
-            id: "default-audio".to_string(),
-            name: "Default Audio Device".to_string(),
-        };
+        self.refresh_devices();
+        Ok(())
+    }
 
-        let video_device = VideoDevice {
-            id: "default-video".to_string(),
-            name: "Default Video Device".to_string(),
-        };
+    /// List devices again, emitting an event for each one plugged in or
+    /// unplugged since the last listing
+    ///
+    /// Listing can block on the audio host; call it off the async runtime.
+    pub fn refresh_devices(&self) -> Vec<MediaEvent> {
+        // A machine without an audio host can still receive calls
+        let audio_devices = audio_capture::input_devices().unwrap_or_else(|e| {
+            tracing::warn!("Could not list microphones: {}", e);
+            Vec::new()
+        });
+        let video_devices = devices::video_input_devices();
+
+        let new_ids: Vec<String> = audio_devices
+            .iter()
+            .map(|d| d.id.clone())
+            .chain(video_devices.iter().map(|d| d.id.clone()))
+            .collect();
+        let mut audio = self.audio_devices.write();
+        let mut video = self.video_devices.write();
+        let old_ids: Vec<String> = audio
+            .iter()
+            .map(|d| d.id.clone())
+            .chain(video.iter().map(|d| d.id.clone()))
+            .collect();
+        *audio = audio_devices;
+        *video = video_devices;
+        drop((audio, video));
+
+        let changes = diff_devices(&old_ids, &new_ids);
+        let events: Vec<MediaEvent> = changes
+            .connected
+            .into_iter()
+            .map(|device_id| MediaEvent::DeviceConnected { device_id })
+            .chain(
+                changes
+                    .disconnected
+                    .into_iter()
+                    .map(|device_id| MediaEvent::DeviceDisconnected { device_id }),
+            )
+            .collect();
+        for event in &events {
+            tracing::info!("Device change: {:?}", event);
+            self.emit(event.clone());
+        }
+        events
+    }
+
+    /// Watch for devices being plugged in or unplugged
+    ///
+    /// Devices are listed again every `interval` and changes are emitted as
+    /// [`MediaEvent::DeviceConnected`] and [`MediaEvent::DeviceDisconnected`].
+    /// The returned future runs until the manager is dropped; spawn it.
+    pub fn watch_devices(
+        self: &Arc<Self>,
+        interval: Duration,
+    ) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // The first tick completes at once, and initialize already listed
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                if tokio::task::spawn_blocking(move || manager.refresh_devices()).await.is_err() {
+                    tracing::warn!("Device listing panicked");
+                }
+            }
+            tracing::debug!("Device watcher stopped");
+        }
+    }
+
+    /// Check the watchdog for stalled pipeline stages
+    ///
+    /// Stages are checked at the watchdog's interval, stalls are emitted as
+    /// [`MediaEvent::PipelineStalled`] while recovery is attempted, and
+    /// recoveries as [`MediaEvent::PipelineRecovered`]. The returned future
+    /// runs until the manager is dropped; spawn it.
+    pub fn run_watchdog(self: &Arc<Self>) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let manager: Weak<Self> = Arc::downgrade(self);
+        let interval = self.watchdog.config().check_interval;
+        async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                for event in manager.watchdog.check() {
+                    match &event {
+                        MediaEvent::PipelineStalled { recovery: None, .. } => {
+                            tracing::error!("Pipeline stall not recovered: {:?}", event);
+                        }
+                        _ => tracing::warn!("Pipeline watchdog: {:?}", event),
+                    }
+                    manager.emit(event);
+                }
+            }
+            tracing::debug!("Pipeline watchdog stopped");
+        }
+    }
 
-        // Emit device connected events
-        let _ = self.event_sender.send(MediaEvent::DeviceConnected {
-            device_id: audio_device.id.clone(),
+    /// Get the microphones found by [`initialize`](Self::initialize)
+    ///
+    /// Requires the `audio-capture` feature; empty without it.
+    #[must_use]
+    pub fn get_audio_devices(&self) -> Vec<AudioDevice> {
+        self.audio_devices.read().clone()
+    }
+
+    /// Capture a microphone into an audio track
+    ///
+    /// `device_id` selects one of [`get_audio_devices`](Self::get_audio_devices);
+    /// `None` uses the system default. Samples are captured at the
+    /// [`audio_clock`](Self::audio_clock)'s rate, cut into frames of its size
+    /// and written Opus-encoded until the returned handle is dropped, subject to
+    /// the [`transmit_switch`](Self::transmit_switch) and noise gate. In-band
+    /// FEC follows the [`packet_loss_feedback`](Self::packet_loss_feedback).
+    /// Levels are reported as [`MediaEvent::AudioLevel`], muted or not. The
+    /// [`watchdog`](Self::watchdog) reopens a device that stops delivering
+    /// samples and resets an encoder that stops producing packets.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the track is not an audio track of this manager, or
+    /// the device cannot be found or opened
+    pub async fn start_audio_capture(
+        &self,
+        track_id: &str,
+        device_id: Option<&str>,
+    ) -> Result<AudioCapture, MediaError> {
+        let track = self
+            .webrtc_tracks
+            .iter()
+            .find(|t| t.id == track_id && t.track_type == MediaType::Audio)
+            .ok_or_else(|| MediaError::TrackNotFound(track_id.to_string()))?;
+        let mut gate = PushToTalkGate::new(self.transmit.clone());
+        if let Some(threshold_dbfs) = self.noise_gate_dbfs {
+            gate = gate.with_noise_gate(threshold_dbfs);
+        }
+        let capture = audio_capture::start(
+            device_id,
+            Arc::clone(&track.track),
+            track_id.to_string(),
+            self.audio_level_node(track_id),
+            gate,
+            self.packet_loss.clone(),
+            self.audio_clock,
+            Arc::clone(&self.watchdog),
+        )
+        .await?;
+        self.emit(MediaEvent::StreamStarted {
+            stream_id: track_id.to_string(),
         });
+        Ok(capture)
+    }
+
+    /// Capture the microphone `constraints` ask for into an audio track
+    ///
+    /// Like [`start_audio_capture`](Self::start_audio_capture), once the
+    /// constraints are checked against capture at the
+    /// [`audio_clock`](Self::audio_clock)'s rate in mono.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the constraints rule out the capture format, or
+    /// capture cannot start
+    pub async fn start_audio_capture_with(
+        &self,
+        track_id: &str,
+        constraints: &AudioConstraints,
+    ) -> Result<AudioCapture, MediaError> {
+        constraints.check(self.audio_clock.sample_rate, 1)?;
+        self.start_audio_capture(track_id, constraints.device_id.as_deref())
+            .await
+    }
+
+    /// Displays and windows that can be shared
+    ///
+    /// Requires the `screen-capture` feature; empty without it. On macOS the
+    /// first call asks the user for permission to record the screen.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if screen capture is unsupported or was refused
+    pub fn get_screen_sources(&self) -> Result<Vec<ScreenSource>, MediaError> {
+        screen_capture::sources()
+    }
 
-        let _ = self.event_sender.send(MediaEvent::DeviceConnected {
-            device_id: video_device.id.clone(),
+    /// Capture a display or window into a screen share track
+    ///
+    /// `source_id` selects one of [`get_screen_sources`](Self::get_screen_sources);
+    /// `None` captures the primary display. Frames are scaled to the track's
+    /// size and encoded by its pipeline until the returned handle is dropped.
+    /// If the encoder stops producing frames the
+    /// [`watchdog`](Self::watchdog) asks it for a keyframe.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the source cannot be found or captured
+    pub async fn start_screen_capture(
+        &self,
+        track: VideoTrack,
+        source_id: Option<&str>,
+        config: ScreenCaptureConfig,
+    ) -> Result<ScreenCapturer, MediaError> {
+        let track_id = track.id.clone();
+        let capturer =
+            screen_capture::start(source_id, track, config, Arc::clone(&self.watchdog)).await?;
+        self.emit(MediaEvent::StreamStarted {
+            stream_id: track_id,
         });
+        Ok(capturer)
+    }
 
-        Ok(())
+    /// Speakers and headsets that received audio can be played to
+    ///
+    /// Requires the `audio-playback` feature; empty without it.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the audio host cannot list its devices
+    pub fn get_audio_output_devices(&self) -> Result<Vec<AudioDevice>, MediaError> {
+        audio_render::output_devices()
     }
 
-    /// Get available audio devices
-    #[must_use]
-    pub fn get_audio_devices(&self) -> &[AudioDevice] {
-        // Return empty for now, as we can't enumerate real devices easily
-        // In a real implementation, this would return actual devices
-        &[]
+    /// Open an output device for playing received audio
+    ///
+    /// `device_id` selects one of
+    /// [`get_audio_output_devices`](Self::get_audio_output_devices); `None`
+    /// uses the system default. Decoded frames pushed into the returned
+    /// renderer are resampled to the device's format and mixed per call
+    /// until it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the device cannot be found or opened
+    pub async fn start_audio_renderer(
+        &self,
+        device_id: Option<&str>,
+    ) -> Result<AudioRenderer, MediaError> {
+        audio_render::start(device_id).await
     }
 
-    /// Get available video devices
+    /// Get the cameras found by [`initialize`](Self::initialize)
+    ///
+    /// Only video4linux cameras are found, so this is empty off Linux.
     #[must_use]
-    pub fn get_video_devices(&self) -> &[VideoDevice] {
-        // Return empty for now
-        &[]
+    pub fn get_video_devices(&self) -> Vec<VideoDevice> {
+        self.video_devices.read().clone()
+    }
+
+    /// Pick the camera and mode that best fit `constraints`
+    ///
+    /// Create the track at the chosen size with
+    /// [`create_video_track_with_codec`](Self::create_video_track_with_codec).
+    ///
+    /// # Errors
+    ///
+    /// Returns error naming the constraint no camera could satisfy
+    pub fn select_camera(
+        &self,
+        constraints: &VideoConstraints,
+    ) -> Result<CameraSelection, MediaError> {
+        Ok(constraints.select_camera(&self.video_devices.read())?)
     }
 
     /// Create a new audio track
@@ -244,10 +925,23 @@ impl MediaStreamManager {
     ///
     /// Returns error if track creation fails
     pub async fn create_audio_track(&mut self) -> Result<&WebRtcTrack, MediaError> {
+        self.create_audio_track_in("audio").await
+    }
+
+    /// Create a new audio track that peers see as part of stream
+    /// `stream_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if track creation fails
+    pub async fn create_audio_track_in(
+        &mut self,
+        stream_id: &str,
+    ) -> Result<&WebRtcTrack, MediaError> {
         let track_id = format!("audio-{}", self.webrtc_tracks.len());
 
         let codec = RTCRtpCodecCapability {
-            mime_type: "audio/opus".to_string(),
+            mime_type: OPUS_MIME_TYPE.to_string(),
             clock_rate: 48000,
             channels: 2,
             sdp_fmtp_line: "".to_string(),
@@ -257,7 +951,7 @@ impl MediaStreamManager {
         let track = Arc::new(TrackLocalStaticSample::new(
             codec,
             track_id.clone(),
-            "audio".to_string(),
+            stream_id.to_string(),
         ));
 
         let webrtc_track = WebRtcTrack {
@@ -269,8 +963,8 @@ impl MediaStreamManager {
         self.webrtc_tracks.push(webrtc_track);
         self.webrtc_tracks
             .last()
-            .ok_or(MediaError::StreamError(
-                "Failed to get last track after push".to_string(),
+            .ok_or(MediaError::TrackNotFound(
+                "last track after push".to_string(),
             ))
     }
 
@@ -280,6 +974,19 @@ impl MediaStreamManager {
     ///
     /// Returns error if track creation fails
     pub async fn create_video_track(&mut self) -> Result<&WebRtcTrack, MediaError> {
+        self.create_video_track_in("video").await
+    }
+
+    /// Create a new video track that peers see as part of stream
+    /// `stream_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if track creation fails
+    pub async fn create_video_track_in(
+        &mut self,
+        stream_id: &str,
+    ) -> Result<&WebRtcTrack, MediaError> {
         let track_id = format!("video-{}", self.webrtc_tracks.len());
 
         let codec = RTCRtpCodecCapability {
@@ -293,7 +1000,7 @@ impl MediaStreamManager {
         let track = Arc::new(TrackLocalStaticSample::new(
             codec,
             track_id.clone(),
-            "video".to_string(),
+            stream_id.to_string(),
         ));
 
         let webrtc_track = WebRtcTrack {
@@ -305,8 +1012,8 @@ impl MediaStreamManager {
         self.webrtc_tracks.push(webrtc_track);
         self.webrtc_tracks
             .last()
-            .ok_or(MediaError::StreamError(
-                "Failed to get last track after push".to_string(),
+            .ok_or(MediaError::TrackNotFound(
+                "last track after push".to_string(),
             ))
     }
 
@@ -323,15 +1030,8 @@ impl MediaStreamManager {
     ) -> Result<VideoTrack, MediaError> {
     let track_id = format!("video-{}", self.webrtc_tracks.len());
 
-    // Use H.264 codec for WebRTC when encoding is enabled
-    let mime_type = match codec {
-    VideoCodec::H264 => "video/H264".to_string(),
-    // VideoCodec::VP8 => "video/VP8".to_string(),
-    // VideoCodec::VP9 => "video/VP9".to_string(),
-    };
-
     let codec_capability = RTCRtpCodecCapability {
-    mime_type,
+    mime_type: video_mime_type(codec).to_string(),
     clock_rate: 90000,
     channels: 0,
     sdp_fmtp_line: "".to_string(),
@@ -344,19 +1044,168 @@ impl MediaStreamManager {
     "video".to_string(),
     ));
 
-    let mut video_track = VideoTrack::new(track_id, webrtc_track, width, height);
+    let mut video_track = VideoTrack::new(track_id, webrtc_track, width, height)
+    .with_keyframe_policy(self.video_keyframes);
 
     // Add encoder based on codec
     match codec {
     VideoCodec::H264 => {
-    video_track = video_track.with_h264_encoder()
-    .map_err(|e| MediaError::ConfigError(e.to_string()))?;
+    video_track = video_track.with_hardware_encoder(&self.encoder_preference, self.encoder_preset)
+    .map_err(|e| MediaError::Codec { codec, source: e.into() })?;
+    }
+    VideoCodec::Av1 => {
+    video_track = video_track.with_av1_encoder(StreamType::Video, self.encoder_preset)
+    .map_err(|e| MediaError::Codec { codec, source: e.into() })?;
     }
     }
 
     Ok(video_track)
     }
 
+    /// Create a screen share track
+    ///
+    /// Like [`create_video_track_with_codec`](Self::create_video_track_with_codec),
+    /// but AV1 is tuned for screen content and the track is sent on the
+    /// screen share stream. Feed it with
+    /// [`start_screen_capture`](Self::start_screen_capture).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the encoder cannot be set up
+    pub async fn create_screen_share_track(
+        &mut self,
+        codec: VideoCodec,
+        width: u32,
+        height: u32,
+    ) -> Result<VideoTrack, MediaError> {
+        self.create_screen_share_track_in("screen", codec, width, height).await
+    }
+
+    /// Create a screen share track that peers see as stream `stream_id`
+    ///
+    /// Give each of several simultaneous screen shares its own stream, so
+    /// the remote party renders them apart.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the encoder cannot be set up
+    pub async fn create_screen_share_track_in(
+        &mut self,
+        stream_id: &str,
+        codec: VideoCodec,
+        width: u32,
+        height: u32,
+    ) -> Result<VideoTrack, MediaError> {
+        let track_id = format!("screen-{}", self.webrtc_tracks.len());
+
+        let codec_capability = RTCRtpCodecCapability {
+            mime_type: video_mime_type(codec).to_string(),
+            clock_rate: 90000,
+            channels: 0,
+            sdp_fmtp_line: "".to_string(),
+            rtcp_feedback: vec![],
+        };
+
+        let webrtc_track = Arc::new(TrackLocalStaticSample::new(
+            codec_capability,
+            track_id.clone(),
+            stream_id.to_string(),
+        ));
+
+        let video_track = VideoTrack::new(track_id.clone(), Arc::clone(&webrtc_track), width, height)
+            .with_keyframe_policy(self.screen_keyframes);
+        let video_track = match codec {
+            VideoCodec::H264 => video_track.with_hardware_encoder(&self.encoder_preference, self.encoder_preset),
+            VideoCodec::Av1 => video_track.with_av1_encoder(StreamType::ScreenShare, self.encoder_preset),
+        }
+        .map_err(|e| MediaError::Codec { codec, source: e.into() })?;
+
+        self.webrtc_tracks.push(WebRtcTrack {
+            track: webrtc_track,
+            track_type: MediaType::ScreenShare,
+            id: track_id,
+        });
+        Ok(video_track)
+    }
+
+    /// Create a video track fed by `source` instead of a camera
+    ///
+    /// The track is H.264 at the source's resolution. Frames are pulled at
+    /// the source's interval and encoded until the returned handle is
+    /// dropped or the source runs dry.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the encoder cannot be set up
+    pub async fn create_video_track_from_source(
+        &mut self,
+        source: Box<dyn FrameSource>,
+    ) -> Result<SourceFeed, MediaError> {
+        let (width, height) = source.resolution();
+        let track = self
+            .create_video_track_with_codec(VideoCodec::H264, width, height)
+            .await?;
+        self.webrtc_tracks.push(WebRtcTrack {
+            track: Arc::clone(&track.webrtc_track),
+            track_type: MediaType::Video,
+            id: track.id.clone(),
+        });
+        let feed = media_source::feed_video(source, track)?;
+        self.emit(MediaEvent::StreamStarted {
+            stream_id: feed.track_id().to_string(),
+        });
+        Ok(feed)
+    }
+
+    /// Create an audio track fed by `source` instead of a microphone
+    ///
+    /// Audio is cut into frames of the [`audio_clock`](Self::audio_clock),
+    /// metered for [`MediaEvent::AudioLevel`] and Opus-encoded until the
+    /// returned handle is dropped or the source runs dry. Push-to-talk and
+    /// the noise gate only apply to microphones.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the encoder cannot be set up
+    pub async fn create_audio_track_from_source(
+        &mut self,
+        source: Box<dyn AudioSource>,
+    ) -> Result<SourceFeed, MediaError> {
+        let encoder = OpusEncoder::new(OpusEncoderConfig {
+            sample_rate: self.audio_clock.sample_rate,
+            ..OpusEncoderConfig::default()
+        })
+        .map_err(|e| MediaError::Capture(e.to_string()))?;
+        let (track, track_id) = {
+            let track = self.create_audio_track().await?;
+            (Arc::clone(&track.track), track.id.clone())
+        };
+        let mut pipeline = MediaPipeline::new();
+        pipeline
+            .add(
+                PipelineStage::Process,
+                AUDIO_LEVEL_NODE,
+                Box::new(self.audio_level_node(&track_id)),
+            )
+            .and_then(|()| {
+                pipeline.add(
+                    PipelineStage::Encode,
+                    ENCODER_NODE,
+                    Box::new(
+                        AudioEncoderNode::new(Box::new(encoder))
+                            .with_loss_feedback(self.packet_loss.clone()),
+                    ),
+                )
+            })
+            .map_err(|e| MediaError::Capture(e.to_string()))?;
+        let feed =
+            media_source::feed_audio(source, track, track_id, self.audio_clock, pipeline)?;
+        self.emit(MediaEvent::StreamStarted {
+            stream_id: feed.track_id().to_string(),
+        });
+        Ok(feed)
+    }
+
     /// Get all WebRTC tracks
     #[must_use]
     pub fn get_webrtc_tracks(&self) -> &[WebRtcTrack] {
@@ -393,6 +1242,7 @@ impl Default for MediaStreamManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::pipeline::ENCODER_NODE;
 
     #[tokio::test]
     async fn test_media_stream_manager_initialize() {
@@ -456,7 +1306,21 @@ mod tests {
         assert!(track.id.starts_with("video-"));
         assert_eq!(track.width, 640);
         assert_eq!(track.height, 480);
-        assert!(track.encoder.is_some()); // Should have H.264 encoder
+        assert!(track.pipeline.contains(ENCODER_NODE)); // Should have H.264 encoder
+    }
+
+    #[tokio::test]
+    async fn test_keyframe_policy_per_stream_type() {
+        let mut manager = MediaStreamManager::new()
+            .with_keyframe_policy(StreamType::Video, KeyframePolicy::lossy_link());
+        assert_eq!(manager.keyframe_policy(StreamType::Video), KeyframePolicy::lossy_link());
+        assert_eq!(manager.keyframe_policy(StreamType::ScreenShare), KeyframePolicy::screen_share());
+
+        let track = manager
+            .create_video_track_with_codec(VideoCodec::H264, 640, 480)
+            .await
+            .unwrap();
+        assert_eq!(track.keyframes, KeyframePolicy::lossy_link());
     }
 
     #[tokio::test]
@@ -479,4 +1343,109 @@ mod tests {
         assert_eq!(audio_count, 1);
         assert_eq!(video_count, 1);
     }
+
+    #[tokio::test]
+    async fn test_media_stream_groups_tracks() {
+        use webrtc::track::track_local::TrackLocal;
+
+        let mut manager = MediaStreamManager::new();
+        let mut stream = MediaStream::new("call-1");
+        let audio = manager.create_audio_track_in(&stream.id).await.unwrap().clone();
+        let video = manager.create_video_track_in(&stream.id).await.unwrap().clone();
+        assert_eq!(audio.track.stream_id(), "call-1");
+
+        assert!(stream.add_track(audio.clone()));
+        assert!(stream.add_track(video.clone()));
+        assert!(!stream.add_track(audio.clone()));
+        assert_eq!(stream.len(), 2);
+        assert_eq!(stream.audio_tracks().count(), 1);
+        assert_eq!(stream.video_tracks().next().unwrap().id, video.id);
+        assert_eq!(stream.track(&audio.id).unwrap().track_type, MediaType::Audio);
+
+        assert!(stream.remove_track(&audio.id).is_some());
+        assert!(stream.remove_track(&audio.id).is_none());
+        assert_eq!(stream.tracks().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_audio_capture_needs_audio_track() {
+        let mut manager = MediaStreamManager::new();
+        let video_id = manager.create_video_track().await.unwrap().id.clone();
+
+        let result = manager.start_audio_capture(&video_id, None).await;
+        assert!(matches!(result, Err(MediaError::TrackNotFound(_))));
+        let result = manager.start_audio_capture("audio-9", None).await;
+        assert!(matches!(result, Err(MediaError::TrackNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_devices_only_reports_changes() {
+        let manager = MediaStreamManager::new();
+        manager.initialize().await.unwrap();
+        let mut events = manager.subscribe_events();
+
+        assert!(manager.refresh_devices().is_empty());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_device_watcher_stops_with_manager() {
+        let manager = Arc::new(MediaStreamManager::new());
+        let watcher = tokio::spawn(manager.watch_devices(Duration::from_millis(10)));
+        drop(manager);
+
+        tokio::time::timeout(Duration::from_secs(1), watcher)
+            .await
+            .expect("watcher should stop")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_screen_share_track_uses_screen_share_stream() {
+        let mut manager = MediaStreamManager::new();
+
+        let track = manager
+            .create_screen_share_track(VideoCodec::H264, 1280, 720)
+            .await
+            .unwrap();
+        assert!(track.id.starts_with("screen-"));
+        assert!(track.pipeline.contains(ENCODER_NODE));
+        assert_eq!(track.keyframes, KeyframePolicy::screen_share());
+
+        let tracks = manager.get_webrtc_tracks();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].track_type, MediaType::ScreenShare);
+        assert_eq!(tracks[0].id, track.id);
+    }
+
+    #[tokio::test]
+    async fn test_injected_audio_is_metered_on_its_track() {
+        use crate::media_source::SineWave;
+
+        let mut manager = MediaStreamManager::new();
+        let mut events = manager.subscribe_events();
+        let tone = SineWave::new(manager.audio_clock(), 440.0, 8000);
+        let feed = manager
+            .create_audio_track_from_source(Box::new(tone))
+            .await
+            .unwrap();
+        assert!(feed.track_id().starts_with("audio-"));
+        assert_eq!(manager.get_webrtc_tracks().len(), 1);
+
+        let level = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                if let MediaEvent::AudioLevel { source, voice_active, .. } =
+                    events.recv().await.unwrap()
+                {
+                    return (source, voice_active);
+                }
+            }
+        })
+        .await
+        .expect("tone should be metered");
+        assert_eq!(
+            level,
+            (AudioLevelSource::Local(feed.track_id().to_string()), true)
+        );
+    }
 }
@@ -0,0 +1,1064 @@
+//! Recording calls to media files
+//!
+//! A [`MediaRecorder`] muxes a call's encoded Opus audio and H.264 or AV1
+//! video into a Matroska file that ordinary players open, with local and
+//! remote media in separate tracks. Recordings of Opus and AV1 are WebM;
+//! H.264 is not allowed in WebM, so those use the wider Matroska doctype
+//! and want an `.mkv` name. MP4 needs a seek table written after the media
+//! and is not supported.
+//!
+//! Frames are written as they arrive, with segment and cluster sizes left
+//! unknown as live WebM streams do, so a recording cut short by a crash
+//! still plays up to its last frame. Files carry no seek index.
+//!
+//! A video track starts at its first keyframe, whose parameter sets become
+//! the track's codec configuration. The file header waits up to
+//! [`HEADER_WAIT_MS`] for every video track to send one; tracks still
+//! without a keyframe by then are left out until the next file.
+//!
+//! With a size or duration limit set, the recording rotates to a new
+//! numbered file (`call.webm`, `call-1.webm`, ...) at the next video
+//! keyframe once the current file reaches the limit, deleting the oldest
+//! files beyond [`RecordingOptions::max_files`].
+
+use crate::recording::{RecordedTrackKind, RecordingError};
+use saorsa_webrtc_codecs::VideoCodec;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Longest the first file's header waits for video keyframes
+pub const HEADER_WAIT_MS: u64 = 2000;
+
+/// Longest a cluster spans; block timestamps are 16-bit offsets into it
+const MAX_CLUSTER_MS: u64 = 5000;
+/// Samples at 48 kHz an Opus decoder outputs before the first real one
+const OPUS_PRE_SKIP: u16 = 312;
+/// Audio a player decodes before a seek point so Opus converges
+const OPUS_SEEK_PRE_ROLL_NS: u64 = 80_000_000;
+
+pub(crate) const EBML: u32 = 0x1A45_DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+pub(crate) const SEGMENT: u32 = 0x1853_8067;
+pub(crate) const INFO: u32 = 0x1549_A966;
+pub(crate) const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+pub(crate) const TRACKS: u32 = 0x1654_AE6B;
+pub(crate) const TRACK_ENTRY: u32 = 0xAE;
+pub(crate) const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+pub(crate) const TRACK_TYPE: u32 = 0x83;
+const TRACK_NAME: u32 = 0x536E;
+pub(crate) const CODEC_ID: u32 = 0x86;
+pub(crate) const CODEC_PRIVATE: u32 = 0x63A2;
+const CODEC_DELAY: u32 = 0x56AA;
+const SEEK_PRE_ROLL: u32 = 0x56BB;
+pub(crate) const VIDEO: u32 = 0xE0;
+pub(crate) const PIXEL_WIDTH: u32 = 0xB0;
+pub(crate) const PIXEL_HEIGHT: u32 = 0xBA;
+pub(crate) const AUDIO: u32 = 0xE1;
+const SAMPLING_FREQUENCY: u32 = 0xB5;
+pub(crate) const CHANNELS: u32 = 0x9F;
+pub(crate) const CLUSTER: u32 = 0x1F43_B675;
+pub(crate) const TIMESTAMP: u32 = 0xE7;
+pub(crate) const SIMPLE_BLOCK: u32 = 0xA3;
+pub(crate) const BLOCK_GROUP: u32 = 0xA0;
+pub(crate) const BLOCK: u32 = 0xA1;
+/// Size of an element that runs to the end of its parent
+const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+const H264_IDR: u8 = 5;
+const H264_SPS: u8 = 7;
+const H264_PPS: u8 = 8;
+const AV1_SEQUENCE_HEADER: u8 = 1;
+const AV1_TEMPORAL_DELIMITER: u8 = 2;
+
+/// Container a recording is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContainerFormat {
+    /// WebM, or Matroska when the video is H.264
+    #[default]
+    WebM,
+    /// MP4, not supported yet
+    Mp4,
+}
+
+/// Which side of a call a recorded track comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RecordingSource {
+    /// Media we send
+    Local,
+    /// Media we receive
+    Remote,
+}
+
+/// What to record and where files end
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordingOptions {
+    /// Container to write
+    pub format: ContainerFormat,
+    /// Record the media we send
+    pub local: bool,
+    /// Record the media we receive
+    pub remote: bool,
+    /// Rotate to a new file once the current one reaches this size
+    pub max_file_bytes: Option<u64>,
+    /// Rotate to a new file once the current one spans this long
+    pub max_file_duration: Option<Duration>,
+    /// Delete the oldest files beyond this many
+    pub max_files: Option<usize>,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        Self {
+            format: ContainerFormat::WebM,
+            local: true,
+            remote: true,
+            max_file_bytes: None,
+            max_file_duration: None,
+            max_files: None,
+        }
+    }
+}
+
+/// One track of a recording
+#[derive(Debug)]
+struct Track {
+    source: RecordingSource,
+    kind: RecordedTrackKind,
+    /// Codec configuration; audio tracks start with it, video tracks take
+    /// it from their first keyframe
+    codec_private: Option<Vec<u8>>,
+    width: u32,
+    height: u32,
+    /// Frames are dropped until a keyframe starts the track in a file
+    needs_keyframe: bool,
+}
+
+/// Frame held until the first header is written
+#[derive(Debug)]
+struct PendingFrame {
+    track: usize,
+    timestamp_ms: u64,
+    keyframe: bool,
+    data: Vec<u8>,
+}
+
+/// File being recorded into
+enum Output {
+    /// Created, header waiting for video keyframes
+    Waiting(BufWriter<File>),
+    /// Header written
+    Open {
+        writer: MatroskaWriter<BufWriter<File>>,
+        started_ms: u64,
+        /// Tracks present in this file, by index
+        included: Vec<bool>,
+    },
+}
+
+/// Records a call's encoded media into Matroska files
+pub struct MediaRecorder {
+    path: PathBuf,
+    options: RecordingOptions,
+    video_codec: Option<VideoCodec>,
+    tracks: Vec<Track>,
+    started: Instant,
+    pending: Vec<PendingFrame>,
+    output: Option<Output>,
+    files: Vec<PathBuf>,
+    files_opened: usize,
+}
+
+impl MediaRecorder {
+    /// Start a recording at `path` with an audio track per selected side
+    /// if `audio`, and a video track per side if `video_codec` is set
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the format is unsupported, nothing would be
+    /// recorded or the file cannot be created
+    pub fn new(
+        path: impl Into<PathBuf>,
+        options: RecordingOptions,
+        audio: bool,
+        video_codec: Option<VideoCodec>,
+    ) -> Result<Self, RecordingError> {
+        if options.format == ContainerFormat::Mp4 {
+            // No MP4 muxer is bundled; WebM covers the negotiated codecs
+            return Err(RecordingError::UnsupportedFormat("mp4".to_string()));
+        }
+
+        let sources = [
+            (options.local, RecordingSource::Local),
+            (options.remote, RecordingSource::Remote),
+        ];
+        let mut tracks = Vec::new();
+        for source in sources
+            .into_iter()
+            .filter_map(|(selected, source)| selected.then_some(source))
+        {
+            if audio {
+                tracks.push(Track {
+                    source,
+                    kind: RecordedTrackKind::Audio,
+                    codec_private: Some(opus_head()),
+                    width: 0,
+                    height: 0,
+                    needs_keyframe: false,
+                });
+            }
+            if video_codec.is_some() {
+                tracks.push(Track {
+                    source,
+                    kind: RecordedTrackKind::Video,
+                    codec_private: None,
+                    width: 0,
+                    height: 0,
+                    needs_keyframe: true,
+                });
+            }
+        }
+        if tracks.is_empty() {
+            return Err(RecordingError::InvalidFormat(
+                "no tracks selected for recording".to_string(),
+            ));
+        }
+
+        let path = path.into();
+        let file = File::create(&path)?;
+        Ok(Self {
+            files: vec![path.clone()],
+            path,
+            options,
+            video_codec,
+            tracks,
+            started: Instant::now(),
+            pending: Vec::new(),
+            output: Some(Output::Waiting(BufWriter::new(file))),
+            files_opened: 1,
+        })
+    }
+
+    /// Milliseconds since the recording started, to stamp frames with
+    #[must_use]
+    pub fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// Files still on disk, oldest first
+    #[must_use]
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Record an Opus packet; ignored if the side is not recorded
+    ///
+    /// # Errors
+    ///
+    /// Returns error if writing fails
+    pub fn write_audio(
+        &mut self,
+        source: RecordingSource,
+        timestamp_ms: u64,
+        data: &[u8],
+    ) -> Result<(), RecordingError> {
+        let Some(track) = self.track(source, RecordedTrackKind::Audio) else {
+            return Ok(());
+        };
+        self.write(track, timestamp_ms, true, data.to_vec())
+    }
+
+    /// Record an encoded video frame of the negotiated codec, H.264 in
+    /// Annex B form or AV1 OBUs; ignored if the side is not recorded
+    ///
+    /// # Errors
+    ///
+    /// Returns error if writing fails
+    pub fn write_video(
+        &mut self,
+        source: RecordingSource,
+        timestamp_ms: u64,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<(), RecordingError> {
+        let (Some(index), Some(codec)) = (
+            self.track(source, RecordedTrackKind::Video),
+            self.video_codec,
+        ) else {
+            return Ok(());
+        };
+        let (keyframe, config, block) = match codec {
+            VideoCodec::H264 => h264_frame(data),
+            VideoCodec::Av1 => av1_frame(data),
+        };
+        let track = &mut self.tracks[index];
+        if track.needs_keyframe && !keyframe {
+            return Ok(());
+        }
+        if let Some(config) = config {
+            track.codec_private = Some(config);
+            track.width = width;
+            track.height = height;
+        }
+        if track.codec_private.is_none() {
+            return Ok(());
+        }
+        self.write(index, timestamp_ms, keyframe, block)
+    }
+
+    /// Write out held frames and close the current file
+    ///
+    /// Returns the recording's files still on disk, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if writing fails
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, RecordingError> {
+        if matches!(self.output, Some(Output::Waiting(_))) {
+            self.write_header()?;
+        }
+        if let Some(Output::Open { mut writer, .. }) = self.output.take() {
+            writer.flush()?;
+        }
+        Ok(self.files)
+    }
+
+    fn track(&self, source: RecordingSource, kind: RecordedTrackKind) -> Option<usize> {
+        self.tracks
+            .iter()
+            .position(|track| track.source == source && track.kind == kind)
+    }
+
+    fn write(
+        &mut self,
+        track: usize,
+        timestamp_ms: u64,
+        keyframe: bool,
+        data: Vec<u8>,
+    ) -> Result<(), RecordingError> {
+        if let Some(Output::Waiting(_)) = self.output {
+            self.tracks[track].needs_keyframe = false;
+            self.pending.push(PendingFrame {
+                track,
+                timestamp_ms,
+                keyframe,
+                data,
+            });
+            let waited = timestamp_ms.saturating_sub(self.pending[0].timestamp_ms);
+            let ready = self.tracks.iter().all(|t| t.codec_private.is_some());
+            if ready || waited >= HEADER_WAIT_MS {
+                self.write_header()?;
+            }
+            return Ok(());
+        }
+
+        let is_video = self.tracks[track].kind == RecordedTrackKind::Video;
+        if self.rotation_due(timestamp_ms) && ((keyframe && is_video) || !self.has_video()) {
+            self.rotate(timestamp_ms)?;
+        }
+        let Some(Output::Open {
+            writer,
+            started_ms,
+            included,
+        }) = &mut self.output
+        else {
+            return Ok(());
+        };
+        if !included[track] || (self.tracks[track].needs_keyframe && !keyframe) {
+            return Ok(());
+        }
+        self.tracks[track].needs_keyframe = false;
+        writer.write_block(
+            track_number(track),
+            timestamp_ms.saturating_sub(*started_ms),
+            keyframe,
+            keyframe && is_video,
+            &data,
+        )
+    }
+
+    /// Write the header for the tracks configured so far, then the
+    /// frames held for it
+    fn write_header(&mut self) -> Result<(), RecordingError> {
+        let Some(Output::Waiting(out)) = self.output.take() else {
+            return Ok(());
+        };
+        let started_ms = self.pending.first().map_or(0, |frame| frame.timestamp_ms);
+        self.open(out, started_ms)?;
+        for frame in std::mem::take(&mut self.pending) {
+            if let Some(Output::Open {
+                writer, included, ..
+            }) = &mut self.output
+            {
+                if included[frame.track] {
+                    let is_video = self.tracks[frame.track].kind == RecordedTrackKind::Video;
+                    writer.write_block(
+                        track_number(frame.track),
+                        frame.timestamp_ms.saturating_sub(started_ms),
+                        frame.keyframe,
+                        frame.keyframe && is_video,
+                        &frame.data,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, out: BufWriter<File>, started_ms: u64) -> Result<(), RecordingError> {
+        let included: Vec<bool> = self
+            .tracks
+            .iter()
+            .map(|track| track.codec_private.is_some())
+            .collect();
+        let doc_type = match self.video_codec {
+            Some(VideoCodec::H264) => "matroska",
+            _ => "webm",
+        };
+        let mut header = Vec::new();
+        put_master(&mut header, TRACKS, |buf| {
+            for (index, track) in self.tracks.iter().enumerate() {
+                if included[index] {
+                    self.put_track(buf, index, track);
+                }
+            }
+        });
+        let writer = MatroskaWriter::new(out, doc_type, &header)?;
+        self.output = Some(Output::Open {
+            writer,
+            started_ms,
+            included,
+        });
+        Ok(())
+    }
+
+    fn put_track(&self, buf: &mut Vec<u8>, index: usize, track: &Track) {
+        let side = match track.source {
+            RecordingSource::Local => "local",
+            RecordingSource::Remote => "remote",
+        };
+        put_master(buf, TRACK_ENTRY, |buf| {
+            put_uint(buf, TRACK_NUMBER, u64::from(track_number(index)));
+            put_uint(buf, TRACK_UID, u64::from(track_number(index)));
+            match track.kind {
+                RecordedTrackKind::Audio => {
+                    put_uint(buf, TRACK_TYPE, 2);
+                    put_str(buf, TRACK_NAME, &format!("{side} audio"));
+                    put_str(buf, CODEC_ID, "A_OPUS");
+                    put_uint(buf, CODEC_DELAY, u64::from(OPUS_PRE_SKIP) * 1_000_000 / 48);
+                    put_uint(buf, SEEK_PRE_ROLL, OPUS_SEEK_PRE_ROLL_NS);
+                    put_master(buf, AUDIO, |buf| {
+                        put_float(buf, SAMPLING_FREQUENCY, 48_000.0);
+                        put_uint(buf, CHANNELS, 1);
+                    });
+                }
+                RecordedTrackKind::Video => {
+                    put_uint(buf, TRACK_TYPE, 1);
+                    put_str(buf, TRACK_NAME, &format!("{side} video"));
+                    let codec_id = match self.video_codec {
+                        Some(VideoCodec::Av1) => "V_AV1",
+                        _ => "V_MPEG4/ISO/AVC",
+                    };
+                    put_str(buf, CODEC_ID, codec_id);
+                    put_master(buf, VIDEO, |buf| {
+                        put_uint(buf, PIXEL_WIDTH, u64::from(track.width));
+                        put_uint(buf, PIXEL_HEIGHT, u64::from(track.height));
+                    });
+                }
+            }
+            if let Some(config) = &track.codec_private {
+                put_element(buf, CODEC_PRIVATE, config);
+            }
+        });
+    }
+
+    fn has_video(&self) -> bool {
+        match &self.output {
+            Some(Output::Open { included, .. }) => self
+                .tracks
+                .iter()
+                .zip(included)
+                .any(|(track, included)| *included && track.kind == RecordedTrackKind::Video),
+            _ => false,
+        }
+    }
+
+    fn rotation_due(&self, timestamp_ms: u64) -> bool {
+        let Some(Output::Open {
+            writer, started_ms, ..
+        }) = &self.output
+        else {
+            return false;
+        };
+        self.options
+            .max_file_bytes
+            .is_some_and(|max| writer.bytes >= max)
+            || self.options.max_file_duration.is_some_and(|max| {
+                timestamp_ms.saturating_sub(*started_ms) >= max.as_millis() as u64
+            })
+    }
+
+    /// Close the current file and continue in the next numbered one
+    fn rotate(&mut self, timestamp_ms: u64) -> Result<(), RecordingError> {
+        if let Some(Output::Open { mut writer, .. }) = self.output.take() {
+            writer.flush()?;
+        }
+        let path = rotated_path(&self.path, self.files_opened);
+        let out = BufWriter::new(File::create(&path)?);
+        self.files_opened += 1;
+        self.files.push(path);
+        if let Some(max) = self.options.max_files {
+            while self.files.len() > max.max(1) {
+                let oldest = self.files.remove(0);
+                if let Err(e) = std::fs::remove_file(&oldest) {
+                    tracing::warn!("Failed to delete old recording {}: {}", oldest.display(), e);
+                }
+            }
+        }
+        for track in &mut self.tracks {
+            track.needs_keyframe = track.kind == RecordedTrackKind::Video;
+        }
+        self.open(out, timestamp_ms)
+    }
+}
+
+impl std::fmt::Debug for MediaRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MediaRecorder")
+            .field("path", &self.path)
+            .field("options", &self.options)
+            .field("video_codec", &self.video_codec)
+            .field("tracks", &self.tracks.len())
+            .field("files", &self.files)
+            .finish()
+    }
+}
+
+/// Path of the `index`th file of a recording: `call.webm`, `call-1.webm`, ...
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{index}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{index}"),
+    };
+    path.with_file_name(name)
+}
+
+fn track_number(index: usize) -> u8 {
+    // At most four tracks, so numbers fit a one-byte block header
+    index as u8 + 1
+}
+
+/// Writes the elements of one Matroska file
+struct MatroskaWriter<W: Write> {
+    out: W,
+    bytes: u64,
+    cluster_ms: Option<u64>,
+}
+
+impl<W: Write> MatroskaWriter<W> {
+    /// Write the file header up to and including `tracks`
+    fn new(mut out: W, doc_type: &str, tracks: &[u8]) -> Result<Self, RecordingError> {
+        let app = concat!("saorsa-webrtc ", env!("CARGO_PKG_VERSION"));
+        let mut header = Vec::new();
+        put_master(&mut header, EBML, |buf| {
+            put_uint(buf, EBML_VERSION, 1);
+            put_uint(buf, EBML_READ_VERSION, 1);
+            put_uint(buf, EBML_MAX_ID_LENGTH, 4);
+            put_uint(buf, EBML_MAX_SIZE_LENGTH, 8);
+            put_str(buf, DOC_TYPE, doc_type);
+            put_uint(buf, DOC_TYPE_VERSION, 4);
+            put_uint(buf, DOC_TYPE_READ_VERSION, 2);
+        });
+        put_id(&mut header, SEGMENT);
+        header.extend_from_slice(&UNKNOWN_SIZE);
+        put_master(&mut header, INFO, |buf| {
+            // Timestamps in milliseconds
+            put_uint(buf, TIMESTAMP_SCALE, 1_000_000);
+            put_str(buf, MUXING_APP, app);
+            put_str(buf, WRITING_APP, app);
+        });
+        header.extend_from_slice(tracks);
+        out.write_all(&header)?;
+        Ok(Self {
+            out,
+            bytes: header.len() as u64,
+            cluster_ms: None,
+        })
+    }
+
+    fn write_block(
+        &mut self,
+        track_number: u8,
+        timestamp_ms: u64,
+        keyframe: bool,
+        starts_cluster: bool,
+        data: &[u8],
+    ) -> Result<(), RecordingError> {
+        let mut head = Vec::with_capacity(32);
+        let cluster_ms = match self.cluster_ms {
+            Some(start)
+                if timestamp_ms < start + MAX_CLUSTER_MS
+                    && (!starts_cluster || timestamp_ms <= start) =>
+            {
+                start
+            }
+            _ => {
+                put_id(&mut head, CLUSTER);
+                head.extend_from_slice(&UNKNOWN_SIZE);
+                put_uint(&mut head, TIMESTAMP, timestamp_ms);
+                self.cluster_ms = Some(timestamp_ms);
+                timestamp_ms
+            }
+        };
+        // Frames arriving late from another track play at the cluster start
+        let offset = timestamp_ms.saturating_sub(cluster_ms) as i16;
+        put_id(&mut head, SIMPLE_BLOCK);
+        put_size(&mut head, 4 + data.len() as u64);
+        head.push(0x80 | track_number);
+        head.extend_from_slice(&offset.to_be_bytes());
+        head.push(if keyframe { 0x80 } else { 0 });
+        self.out.write_all(&head)?;
+        self.out.write_all(data)?;
+        self.bytes += (head.len() + data.len()) as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), RecordingError> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn put_id(buf: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(3);
+    buf.extend_from_slice(&bytes[skip..]);
+}
+
+/// Write an element size in the shortest EBML variable-length form
+fn put_size(buf: &mut Vec<u8>, size: u64) {
+    // All value bits set is reserved for unknown sizes
+    let len = (1..8).find(|len| size < (1 << (7 * len)) - 1).unwrap_or(8);
+    let marked = size | (1 << (7 * len));
+    buf.extend_from_slice(&marked.to_be_bytes()[8 - len..]);
+}
+
+fn put_element(buf: &mut Vec<u8>, id: u32, body: &[u8]) {
+    put_id(buf, id);
+    put_size(buf, body.len() as u64);
+    buf.extend_from_slice(body);
+}
+
+fn put_master(buf: &mut Vec<u8>, id: u32, children: impl FnOnce(&mut Vec<u8>)) {
+    let mut body = Vec::new();
+    children(&mut body);
+    put_element(buf, id, &body);
+}
+
+fn put_uint(buf: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+    put_element(buf, id, &bytes[skip..]);
+}
+
+fn put_float(buf: &mut Vec<u8>, id: u32, value: f64) {
+    put_element(buf, id, &value.to_be_bytes());
+}
+
+fn put_str(buf: &mut Vec<u8>, id: u32, value: &str) {
+    put_element(buf, id, value.as_bytes());
+}
+
+/// Opus identification header for a mono 48 kHz track
+fn opus_head() -> Vec<u8> {
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(1);
+    head.extend_from_slice(&OPUS_PRE_SKIP.to_le_bytes());
+    head.extend_from_slice(&48_000u32.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0);
+    head
+}
+
+/// NAL units of an H.264 Annex B frame
+fn annex_b_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut units = Vec::new();
+    let mut start = None;
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i..i + 3] == [0, 0, 1] {
+            if let Some(start) = start {
+                units.push(&data[start..i]);
+            }
+            i += 3;
+            start = Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    if let Some(start) = start {
+        units.push(&data[start..]);
+    }
+    // Four-byte start codes leave a zero on the unit before; units never
+    // end in one themselves
+    units
+        .into_iter()
+        .map(|mut unit| {
+            while let [rest @ .., 0] = unit {
+                unit = rest;
+            }
+            unit
+        })
+        .filter(|unit| !unit.is_empty())
+        .collect()
+}
+
+/// Whether an H.264 frame is a keyframe, the `avcC` configuration its
+/// parameter sets make, and the frame with length-prefixed NAL units
+fn h264_frame(data: &[u8]) -> (bool, Option<Vec<u8>>, Vec<u8>) {
+    let units = annex_b_units(data);
+    let of_type = |kind: u8| units.iter().find(|unit| unit[0] & 0x1F == kind);
+    let keyframe = of_type(H264_IDR).is_some();
+    let config = match (of_type(H264_SPS), of_type(H264_PPS)) {
+        (Some(sps), Some(pps)) if sps.len() >= 4 => {
+            let mut avcc = vec![1, sps[1], sps[2], sps[3], 0xFF, 0xE1];
+            avcc.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+            avcc.extend_from_slice(sps);
+            avcc.push(1);
+            avcc.extend_from_slice(&(pps.len() as u16).to_be_bytes());
+            avcc.extend_from_slice(pps);
+            Some(avcc)
+        }
+        _ => None,
+    };
+    let mut block = Vec::with_capacity(data.len() + 4 * units.len());
+    for unit in &units {
+        block.extend_from_slice(&(unit.len() as u32).to_be_bytes());
+        block.extend_from_slice(unit);
+    }
+    (keyframe, config, block)
+}
+
+/// One AV1 OBU with its header
+struct Obu<'a> {
+    kind: u8,
+    bytes: &'a [u8],
+    payload: &'a [u8],
+}
+
+/// OBUs of an AV1 temporal unit, stopping at the first malformed one
+fn av1_obus(mut data: &[u8]) -> Vec<Obu<'_>> {
+    let mut obus = Vec::new();
+    while let Some(&header) = data.first() {
+        let header_len = 1 + usize::from(header & 0x04 != 0);
+        let (payload_start, payload_len) = if header & 0x02 == 0 {
+            (header_len, data.len().saturating_sub(header_len))
+        } else {
+            let mut size = 0usize;
+            let mut read = 0;
+            loop {
+                let Some(byte) = data.get(header_len + read) else {
+                    return obus;
+                };
+                size |= usize::from(byte & 0x7F) << (7 * read);
+                read += 1;
+                if byte & 0x80 == 0 || read == 8 {
+                    break;
+                }
+            }
+            (header_len + read, size)
+        };
+        let Some(end) = payload_start
+            .checked_add(payload_len)
+            .filter(|end| *end <= data.len())
+        else {
+            return obus;
+        };
+        obus.push(Obu {
+            kind: (header >> 3) & 0x0F,
+            bytes: &data[..end],
+            payload: &data[payload_start..end],
+        });
+        data = &data[end..];
+    }
+    obus
+}
+
+/// Whether an AV1 temporal unit is a keyframe, the `av1C` configuration
+/// its sequence header makes, and the unit without temporal delimiters
+///
+/// Encoders repeat the sequence header on every keyframe, so a unit with
+/// one is taken as a keyframe.
+fn av1_frame(data: &[u8]) -> (bool, Option<Vec<u8>>, Vec<u8>) {
+    let obus = av1_obus(data);
+    let config = obus
+        .iter()
+        .find(|obu| obu.kind == AV1_SEQUENCE_HEADER)
+        .map(av1_config);
+    let mut block = Vec::with_capacity(data.len());
+    for obu in obus.iter().filter(|obu| obu.kind != AV1_TEMPORAL_DELIMITER) {
+        block.extend_from_slice(obu.bytes);
+    }
+    (config.is_some(), config, block)
+}
+
+/// `av1C` record for a sequence header, assuming 8-bit video as this
+/// crate's encoder writes
+fn av1_config(sequence_header: &Obu<'_>) -> Vec<u8> {
+    let payload = sequence_header.payload;
+    let mut pos = 0;
+    let mut bits = |count: usize| {
+        (0..count).fold(0u8, |value, _| {
+            let byte = payload.get(pos / 8).copied().unwrap_or(0);
+            let bit = (byte >> (7 - pos % 8)) & 1;
+            pos += 1;
+            (value << 1) | bit
+        })
+    };
+    let profile = bits(3);
+    let _still_picture = bits(1);
+    let (level, tier) = if bits(1) == 1 {
+        // Reduced still picture header
+        (bits(5), 0)
+    } else if bits(1) == 0 {
+        // No timing info: display delay flag, operating point count and
+        // the first point's IDC come before its level
+        let _ = (bits(1), bits(5), bits(6), bits(6));
+        let level = bits(5);
+        let tier = if level > 7 { bits(1) } else { 0 };
+        (level, tier)
+    } else {
+        // Level 31 places no constraints
+        (31, 0)
+    };
+    let subsampling = if profile == 1 { 0 } else { 0b1100 };
+    let mut av1c = vec![0x81, (profile << 5) | level, (tier << 7) | subsampling, 0];
+    av1c.extend_from_slice(sequence_header.bytes);
+    av1c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPS: [u8; 5] = [0x67, 0x42, 0xC0, 0x1F, 0xDA];
+    const PPS: [u8; 3] = [0x68, 0xCE, 0x3C];
+
+    fn h264_keyframe() -> Vec<u8> {
+        let mut frame = vec![0, 0, 0, 1];
+        frame.extend_from_slice(&SPS);
+        frame.extend_from_slice(&[0, 0, 0, 1]);
+        frame.extend_from_slice(&PPS);
+        frame.extend_from_slice(&[0, 0, 1, 0x65, 0x88, 0x84]);
+        frame
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn test_ebml_sizes_use_shortest_form() {
+        let size = |value| {
+            let mut buf = Vec::new();
+            put_size(&mut buf, value);
+            buf
+        };
+        assert_eq!(size(0), [0x80]);
+        assert_eq!(size(126), [0xFE]);
+        // 127 would read as unknown in one byte
+        assert_eq!(size(127), [0x40, 0x7F]);
+        assert_eq!(size(300), [0x41, 0x2C]);
+
+        let mut buf = Vec::new();
+        put_uint(&mut buf, TIMESTAMP_SCALE, 1_000_000);
+        assert_eq!(buf, [0x2A, 0xD7, 0xB1, 0x83, 0x0F, 0x42, 0x40]);
+    }
+
+    #[test]
+    fn test_audio_recording_is_webm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("call.webm");
+        let options = RecordingOptions {
+            remote: false,
+            ..RecordingOptions::default()
+        };
+        let mut recorder = MediaRecorder::new(&path, options, true, None).unwrap();
+        recorder
+            .write_audio(RecordingSource::Local, 0, &[0xAA; 3])
+            .unwrap();
+        recorder
+            .write_audio(RecordingSource::Local, 20, &[0xBB; 3])
+            .unwrap();
+        // Remote media is not being recorded
+        recorder
+            .write_audio(RecordingSource::Remote, 20, &[0xCC; 3])
+            .unwrap();
+        assert_eq!(recorder.finish().unwrap(), std::slice::from_ref(&path));
+
+        let file = std::fs::read(&path).unwrap();
+        assert_eq!(file[..4], [0x1A, 0x45, 0xDF, 0xA3]);
+        assert!(contains(&file, b"webm"));
+        assert!(contains(&file, b"A_OPUS"));
+        assert!(contains(&file, &opus_head()));
+        assert!(contains(
+            &file,
+            &[0xA3, 0x87, 0x81, 0, 0, 0x80, 0xAA, 0xAA, 0xAA]
+        ));
+        assert!(contains(&file, &[0xA3, 0x87, 0x81, 0, 20, 0x80, 0xBB]));
+        assert!(!contains(&file, &[0xCC; 3]));
+    }
+
+    #[test]
+    fn test_h264_video_starts_at_a_keyframe() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("call.mkv");
+        let options = RecordingOptions {
+            local: false,
+            ..RecordingOptions::default()
+        };
+        let mut recorder =
+            MediaRecorder::new(&path, options, true, Some(VideoCodec::H264)).unwrap();
+        let delta = [0, 0, 0, 1, 0x41, 0x9A];
+        recorder
+            .write_video(RecordingSource::Remote, 0, &delta, 640, 480)
+            .unwrap();
+        recorder
+            .write_audio(RecordingSource::Remote, 10, &[0xAA])
+            .unwrap();
+        recorder
+            .write_video(RecordingSource::Remote, 40, &h264_keyframe(), 640, 480)
+            .unwrap();
+        recorder
+            .write_video(RecordingSource::Remote, 73, &delta, 640, 480)
+            .unwrap();
+        recorder.finish().unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        assert!(contains(&file, b"matroska"));
+        assert!(contains(&file, b"V_MPEG4/ISO/AVC"));
+        assert!(contains(&file, &[0xB0, 0x82, 0x02, 0x80]));
+        let mut avcc = vec![1, 0x42, 0xC0, 0x1F, 0xFF, 0xE1, 0, 5];
+        avcc.extend_from_slice(&SPS);
+        avcc.extend_from_slice(&[1, 0, 3]);
+        avcc.extend_from_slice(&PPS);
+        assert!(contains(&file, &avcc));
+        // The IDR slice is length-prefixed and the leading delta dropped
+        assert!(contains(&file, &[0, 0, 0, 3, 0x65, 0x88, 0x84]));
+        assert!(contains(&file, &[0, 0, 0, 2, 0x41, 0x9A]));
+        assert_eq!(file.windows(2).filter(|w| *w == [0x41, 0x9A]).count(), 1);
+    }
+
+    #[test]
+    fn test_header_stops_waiting_for_silent_video() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("call.webm");
+        let mut recorder = MediaRecorder::new(
+            &path,
+            RecordingOptions::default(),
+            true,
+            Some(VideoCodec::Av1),
+        )
+        .unwrap();
+        recorder
+            .write_audio(RecordingSource::Local, 0, &[0xAA])
+            .unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        recorder
+            .write_audio(RecordingSource::Local, HEADER_WAIT_MS, &[0xBB])
+            .unwrap();
+        recorder.finish().unwrap();
+
+        let file = std::fs::read(&path).unwrap();
+        assert!(contains(&file, b"local audio"));
+        assert!(contains(&file, b"remote audio"));
+        assert!(!contains(&file, b"V_AV1"));
+        assert!(contains(&file, &[0xBB]));
+    }
+
+    #[test]
+    fn test_av1_config_comes_from_the_sequence_header() {
+        // Profile 0, level 8 (4.0), main tier, no timing info
+        let sequence_header = [0x0A, 0x04, 0x00, 0x00, 0x00, 0x40];
+        let mut unit = vec![0x12, 0x00];
+        unit.extend_from_slice(&sequence_header);
+        unit.extend_from_slice(&[0x32, 0x01, 0xEE]);
+
+        let (keyframe, config, block) = av1_frame(&unit);
+        assert!(keyframe);
+        let config = config.unwrap();
+        assert_eq!(config[..4], [0x81, 8, 0x0C, 0]);
+        assert_eq!(config[4..], sequence_header);
+        // Temporal delimiter removed
+        assert_eq!(block, unit[2..]);
+        assert!(!av1_frame(&[0x12, 0x00, 0x32, 0x01, 0xEE]).0);
+    }
+
+    #[test]
+    fn test_rotation_keeps_the_newest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("call.webm");
+        let options = RecordingOptions {
+            max_file_duration: Some(Duration::from_secs(1)),
+            max_files: Some(2),
+            ..RecordingOptions::default()
+        };
+        let mut recorder = MediaRecorder::new(&path, options, true, None).unwrap();
+        for second in 0..4 {
+            recorder
+                .write_audio(RecordingSource::Remote, second * 1000, &[0xAA])
+                .unwrap();
+        }
+        let files = recorder.finish().unwrap();
+        assert_eq!(
+            files,
+            [
+                dir.path().join("call-2.webm"),
+                dir.path().join("call-3.webm")
+            ]
+        );
+        assert!(!path.exists());
+        assert!(!dir.path().join("call-1.webm").exists());
+
+        // Each file's timestamps start over
+        let last = std::fs::read(&files[1]).unwrap();
+        assert!(contains(&last, &[0xE7, 0x81, 0x00]));
+    }
+
+    #[test]
+    fn test_mp4_and_empty_recordings_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("call.mp4");
+        let mp4 = RecordingOptions {
+            format: ContainerFormat::Mp4,
+            ..RecordingOptions::default()
+        };
+        assert!(matches!(
+            MediaRecorder::new(&path, mp4, true, None),
+            Err(RecordingError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            MediaRecorder::new(&path, RecordingOptions::default(), false, None),
+            Err(RecordingError::InvalidFormat(_))
+        ));
+    }
+}
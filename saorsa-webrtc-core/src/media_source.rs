@@ -0,0 +1,406 @@
+//! Synthetic and custom media sources
+//!
+//! A [`FrameSource`] or [`AudioSource`] stands in for a camera or
+//! microphone, so tests, bots and applications can send test patterns,
+//! file playback or game capture without real hardware. Sources are pulled
+//! at their own pace on a dedicated thread, run through the track's
+//! pipeline and written to the track until the [`SourceFeed`] handle is
+//! dropped or the source runs dry. [`TestPattern`] and [`SineWave`] are
+//! ready-made sources, and
+//! [`FilePlaybackSource`](crate::file_playback::FilePlaybackSource) plays
+//! media files.
+
+use crate::audio_capture::AudioFramer;
+use crate::audio_clock::AudioClock;
+use crate::media::{MediaError, VideoTrack};
+use crate::pipeline::{MediaPayload, MediaPipeline};
+use bytes::Bytes;
+use saorsa_webrtc_codecs::{AudioFrame, VideoFrame};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+use webrtc::media::Sample;
+use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
+
+/// Encoded frames queued between a source thread and its track
+const ENCODED_QUEUE: usize = 4;
+
+/// Supplier of raw video for a track
+pub trait FrameSource: Send {
+    /// Width and height of every frame; the track is created at this size
+    fn resolution(&self) -> (u32, u32);
+
+    /// Time between frames
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / 30
+    }
+
+    /// Next RGB24 frame, or `None` once the source is exhausted
+    ///
+    /// Called on the source's own thread, so it may block.
+    fn next_frame(&mut self) -> Option<VideoFrame>;
+}
+
+/// Supplier of raw audio for a track
+pub trait AudioSource: Send {
+    /// Next buffer of PCM, or `None` once the source is exhausted
+    ///
+    /// Buffers may be any length, mono or stereo, but must be at the
+    /// manager's [`AudioClock`] rate; they are cut into frames of its size.
+    /// Called on the source's own thread, so it may block.
+    fn next_frame(&mut self) -> Option<AudioFrame>;
+}
+
+/// Running feed from a source into a track; stops when this is dropped
+pub struct SourceFeed {
+    track_id: String,
+    /// Dropping this tells the source thread to stop
+    _stop: watch::Sender<()>,
+}
+
+impl SourceFeed {
+    /// Track the source is written to
+    #[must_use]
+    pub fn track_id(&self) -> &str {
+        &self.track_id
+    }
+
+    /// Stop feeding the track
+    pub fn stop(self) {}
+}
+
+impl std::fmt::Debug for SourceFeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SourceFeed")
+            .field("track_id", &self.track_id)
+            .finish()
+    }
+}
+
+/// Moving colour bars, for checking that video gets through
+#[derive(Debug, Clone)]
+pub struct TestPattern {
+    width: u32,
+    height: u32,
+    fps: u32,
+    frame: u64,
+}
+
+impl TestPattern {
+    /// Bars in the order of SMPTE colour bars
+    const BARS: [[u8; 3]; 8] = [
+        [255, 255, 255],
+        [255, 255, 0],
+        [0, 255, 255],
+        [0, 255, 0],
+        [255, 0, 255],
+        [255, 0, 0],
+        [0, 0, 255],
+        [0, 0, 0],
+    ];
+
+    /// Pattern of `width` x `height` at `fps` frames per second
+    #[must_use]
+    pub fn new(width: u32, height: u32, fps: u32) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+            fps: fps.max(1),
+            frame: 0,
+        }
+    }
+}
+
+impl FrameSource for TestPattern {
+    fn resolution(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs(1) / self.fps
+    }
+
+    fn next_frame(&mut self) -> Option<VideoFrame> {
+        let width = self.width as usize;
+        // Bars move four pixels a frame so a frozen picture is obvious
+        let shift = (self.frame as usize).wrapping_mul(4);
+        let row: Vec<u8> = (0..width)
+            .flat_map(|x| Self::BARS[(x + shift) % width * Self::BARS.len() / width])
+            .collect();
+        let frame = VideoFrame {
+            data: row.repeat(self.height as usize),
+            width: self.width,
+            height: self.height,
+            timestamp: self.frame * 1000 / u64::from(self.fps),
+        };
+        self.frame += 1;
+        Some(frame)
+    }
+}
+
+/// Endless mono tone
+#[derive(Debug, Clone)]
+pub struct SineWave {
+    clock: AudioClock,
+    frequency_hz: f32,
+    amplitude: i16,
+    position: u64,
+}
+
+impl SineWave {
+    /// Tone at `frequency_hz` peaking at `amplitude`, in frames of `clock`
+    #[must_use]
+    pub fn new(clock: AudioClock, frequency_hz: f32, amplitude: i16) -> Self {
+        Self {
+            clock,
+            frequency_hz,
+            amplitude,
+            position: 0,
+        }
+    }
+}
+
+impl AudioSource for SineWave {
+    fn next_frame(&mut self) -> Option<AudioFrame> {
+        let rate = self.clock.sample_rate.as_hz();
+        let samples = self.clock.samples_per_frame() as u64;
+        let step = std::f64::consts::TAU * f64::from(self.frequency_hz) / f64::from(rate);
+        let data = (self.position..self.position + samples)
+            .map(|n| ((n as f64 * step).sin() * f64::from(self.amplitude)) as i16)
+            .collect();
+        let frame = AudioFrame {
+            data,
+            sample_rate: self.clock.sample_rate,
+            channels: saorsa_webrtc_codecs::Channels::Mono,
+            timestamp: self.position * 1000 / u64::from(rate),
+        };
+        self.position += samples;
+        Some(frame)
+    }
+}
+
+/// Pulls audio from a source and encodes it one clock frame at a time
+struct AudioFeeder {
+    source: Box<dyn AudioSource>,
+    clock: AudioClock,
+    framer: Option<(u16, AudioFramer)>,
+    ready: VecDeque<AudioFrame>,
+    pipeline: MediaPipeline,
+}
+
+impl AudioFeeder {
+    /// Encoded next frame: `None` once the source is done, `Some(None)` if
+    /// the pipeline dropped it
+    fn next(&mut self) -> Option<Option<Bytes>> {
+        while self.ready.is_empty() {
+            let buffer = self.source.next_frame()?;
+            if buffer.sample_rate != self.clock.sample_rate {
+                tracing::warn!(
+                    "Audio source at {} Hz, expected {} Hz",
+                    buffer.sample_rate.as_hz(),
+                    self.clock.sample_rate.as_hz()
+                );
+                return None;
+            }
+            let channels = buffer.channels.count() as u16;
+            if self
+                .framer
+                .as_ref()
+                .is_some_and(|(framed, _)| *framed != channels)
+            {
+                self.framer = None;
+            }
+            let (_, framer) = self
+                .framer
+                .get_or_insert_with(|| (channels, AudioFramer::with_clock(channels, self.clock)));
+            self.ready.extend(framer.push(&buffer.data));
+        }
+        let frame = self.ready.pop_front()?;
+        match self.pipeline.push(MediaPayload::Audio(frame)) {
+            Ok(Some(MediaPayload::Encoded(encoded))) => Some(Some(encoded.data)),
+            Ok(_) => Some(None),
+            Err(e) => {
+                tracing::warn!("Failed to encode injected audio: {}", e);
+                Some(None)
+            }
+        }
+    }
+}
+
+/// Feed `source` through `track`'s pipeline into the track
+pub(crate) fn feed_video(
+    mut source: Box<dyn FrameSource>,
+    mut track: VideoTrack,
+) -> Result<SourceFeed, MediaError> {
+    let webrtc_track = Arc::clone(&track.webrtc_track);
+    let interval = source.frame_interval();
+    spawn_feed(track.id.clone(), webrtc_track, interval, move || {
+        let frame = source.next_frame()?;
+        if (frame.width, frame.height) != (track.width, track.height) {
+            tracing::warn!(
+                "Skipping {}x{} frame from source of {}x{} track {}",
+                frame.width,
+                frame.height,
+                track.width,
+                track.height,
+                track.id
+            );
+            return Some(None);
+        }
+        match track.encode_frame(&frame.data) {
+            Ok(encoded) if encoded.is_empty() => Some(None),
+            Ok(encoded) => Some(Some(Bytes::from(encoded))),
+            Err(e) => {
+                tracing::warn!("Failed to encode injected video: {}", e);
+                Some(None)
+            }
+        }
+    })
+}
+
+/// Feed `source` through `pipeline` into an audio track
+pub(crate) fn feed_audio(
+    source: Box<dyn AudioSource>,
+    track: Arc<TrackLocalStaticSample>,
+    track_id: String,
+    clock: AudioClock,
+    pipeline: MediaPipeline,
+) -> Result<SourceFeed, MediaError> {
+    let mut feeder = AudioFeeder {
+        source,
+        clock,
+        framer: None,
+        ready: VecDeque::new(),
+        pipeline,
+    };
+    spawn_feed(track_id, track, clock.frame_duration(), move || {
+        feeder.next()
+    })
+}
+
+/// Call `produce` every `interval` on a thread of its own, writing what it
+/// encodes to `track`, until it returns `None` or the feed is dropped
+fn spawn_feed(
+    track_id: String,
+    track: Arc<TrackLocalStaticSample>,
+    interval: Duration,
+    mut produce: impl FnMut() -> Option<Option<Bytes>> + Send + 'static,
+) -> Result<SourceFeed, MediaError> {
+    let (encoded_tx, mut encoded_rx) = mpsc::channel::<Bytes>(ENCODED_QUEUE);
+    let (stop_tx, stop_rx) = watch::channel(());
+    let thread_track_id = track_id.clone();
+
+    // Sources and encoders may block, so both stay off the async runtime
+    std::thread::Builder::new()
+        .name("media-source".to_string())
+        .spawn(move || {
+            let mut due = Instant::now();
+            // Runs until the handle, and with it the sender, is dropped
+            while stop_rx.has_changed().is_ok() {
+                let Some(encoded) = produce() else {
+                    tracing::debug!("Source for {} ran dry", thread_track_id);
+                    break;
+                };
+                if let Some(encoded) = encoded {
+                    // Skip frames while the track is still sending the last ones
+                    let _ = encoded_tx.try_send(encoded);
+                }
+                due += interval;
+                std::thread::sleep(due.saturating_duration_since(Instant::now()));
+            }
+        })
+        .map_err(|e| MediaError::Capture(e.to_string()))?;
+
+    let task_track_id = track_id.clone();
+    tokio::spawn(async move {
+        // Ends once the source thread exits and drops its sender
+        while let Some(data) = encoded_rx.recv().await {
+            let sample = Sample {
+                data,
+                duration: interval,
+                ..Default::default()
+            };
+            if let Err(e) = track.write_sample(&sample).await {
+                tracing::warn!("Failed to write source to {}: {}", task_track_id, e);
+            }
+        }
+        tracing::debug!("Source feed for {} stopped", task_track_id);
+    });
+
+    Ok(SourceFeed {
+        track_id,
+        _stop: stop_tx,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio_clock::OpusFrameSize;
+    use crate::pipeline::{level_dbfs, AudioEncoderNode, PipelineStage, ENCODER_NODE};
+    use saorsa_webrtc_codecs::{Channels, OpusEncoder, OpusEncoderConfig, SampleRate};
+
+    #[test]
+    fn test_pattern_fills_frames_and_moves() {
+        let mut pattern = TestPattern::new(64, 4, 10);
+        assert_eq!(pattern.resolution(), (64, 4));
+        assert_eq!(pattern.frame_interval(), Duration::from_millis(100));
+
+        let first = pattern.next_frame().unwrap();
+        assert_eq!(first.data.len(), 64 * 4 * 3);
+        assert_eq!(first.data[..3], [255, 255, 255]);
+        assert_eq!(first.data[63 * 3..64 * 3], [0, 0, 0]);
+        let second = pattern.next_frame().unwrap();
+        assert_ne!(first.data, second.data);
+        assert_eq!(second.timestamp, 100);
+    }
+
+    #[test]
+    fn test_sine_wave_follows_the_clock() {
+        let clock = AudioClock::new(OpusFrameSize::Ms10);
+        let mut tone = SineWave::new(clock, 1000.0, 8000);
+        let frame = tone.next_frame().unwrap();
+        assert_eq!(frame.data.len(), 480);
+        assert!((level_dbfs(&frame.data) - -15.3).abs() < 0.5);
+        assert_eq!(tone.next_frame().unwrap().timestamp, 10);
+    }
+
+    struct Buffers(Vec<AudioFrame>);
+
+    impl AudioSource for Buffers {
+        fn next_frame(&mut self) -> Option<AudioFrame> {
+            (!self.0.is_empty()).then(|| self.0.remove(0))
+        }
+    }
+
+    #[test]
+    fn test_audio_is_reframed_and_encoded_until_the_source_ends() {
+        let clock = AudioClock::default();
+        let encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        let mut pipeline = MediaPipeline::new();
+        pipeline
+            .add(
+                PipelineStage::Encode,
+                ENCODER_NODE,
+                Box::new(AudioEncoderNode::new(Box::new(encoder))),
+            )
+            .unwrap();
+        // 30 ms of stereo makes one 20 ms frame and leaves 10 ms over
+        let stereo = AudioFrame {
+            data: vec![1000; 2880],
+            sample_rate: SampleRate::Hz48000,
+            channels: Channels::Stereo,
+            timestamp: 0,
+        };
+        let mut feeder = AudioFeeder {
+            source: Box::new(Buffers(vec![stereo])),
+            clock,
+            framer: None,
+            ready: VecDeque::new(),
+            pipeline,
+        };
+        assert!(matches!(feeder.next(), Some(Some(_))));
+        assert!(feeder.next().is_none());
+    }
+}
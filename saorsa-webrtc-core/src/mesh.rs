@@ -0,0 +1,587 @@
+//! Mesh group calls
+//!
+//! A [`ConferenceManager`] runs small group calls without a server: every
+//! participant holds a call to every other one in the room. Local tracks
+//! are created once per room and shared by all of its calls, so each
+//! sample written goes out to everyone. Remote tracks stay per
+//! participant and are announced as [`MeshEvent`]s.
+//!
+//! Upload grows with every participant, so rooms are capped at
+//! [`DEFAULT_MAX_PARTICIPANTS`] unless configured otherwise; larger groups
+//! need a forwarding server.
+//!
+//! Signaling is left to the caller. Someone joining calls everyone already
+//! in the room with [`ConferenceManager::connect`] and sends each the
+//! offer from [`CallManager::create_offer`]; the others take the offer with
+//! [`CallManager::receive_offer`] and answer with
+//! [`ConferenceManager::admit`].
+
+use crate::call::{CallError, CallManager};
+use crate::error::{CodedError, ErrorCode};
+use crate::identity::PeerIdentity;
+use crate::media::{MediaStream, RemoteTrack};
+use crate::room::RoomId;
+use crate::types::{CallEvent, CallId, MediaConstraints, MediaType};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Participants a room holds by default, this side included
+pub const DEFAULT_MAX_PARTICIPANTS: usize = 6;
+
+/// Mesh conference errors
+#[derive(Error, Debug)]
+pub enum MeshError {
+    /// This side has not joined the room
+    #[error("Not in room {0}")]
+    NotJoined(RoomId),
+
+    /// This side is already in the room
+    #[error("Already in room {0}")]
+    AlreadyJoined(RoomId),
+
+    /// The room has no space for another participant
+    #[error("Room {0} is full")]
+    RoomFull(RoomId),
+
+    /// The peer is already connected in the room
+    #[error("Already connected to {0}")]
+    AlreadyConnected(String),
+
+    /// The peer is not connected in the room
+    #[error("Participant not found: {0}")]
+    ParticipantNotFound(String),
+
+    /// The call to a participant failed
+    #[error(transparent)]
+    Call(#[from] CallError),
+}
+
+impl CodedError for MeshError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotJoined(_) | Self::ParticipantNotFound(_) => ErrorCode::PeerNotFound,
+            Self::AlreadyJoined(_) | Self::RoomFull(_) | Self::AlreadyConnected(_) => {
+                ErrorCode::ConferenceFailed
+            }
+            Self::Call(e) => e.code(),
+        }
+    }
+}
+
+/// Something that happened in a mesh room
+#[derive(Debug, Clone)]
+pub enum MeshEvent<I: PeerIdentity> {
+    /// A participant was connected
+    ParticipantJoined {
+        /// Room
+        room: RoomId,
+        /// Who joined
+        participant: I,
+        /// Call to them
+        call_id: CallId,
+    },
+    /// A participant hung up, failed or was disconnected
+    ParticipantLeft {
+        /// Room
+        room: RoomId,
+        /// Who left
+        participant: I,
+    },
+    /// A participant started sending a track
+    RemoteTrackAdded {
+        /// Room
+        room: RoomId,
+        /// Who sends it
+        participant: I,
+        /// Track identifier
+        track_id: String,
+        /// Kind of media the track carries
+        kind: MediaType,
+    },
+    /// A participant's track ended
+    RemoteTrackRemoved {
+        /// Room
+        room: RoomId,
+        /// Who sent it
+        participant: I,
+        /// Track identifier
+        track_id: String,
+    },
+}
+
+/// One room this side is in
+struct MeshRoom<I: PeerIdentity> {
+    /// Tracks sent to every participant
+    stream: MediaStream,
+    constraints: MediaConstraints,
+    /// Participant and call to them, by peer ID
+    participants: HashMap<String, (I, CallId)>,
+}
+
+type Rooms<I> = Arc<DashMap<RoomId, MeshRoom<I>>>;
+
+/// Runs mesh group calls over a [`CallManager`]
+pub struct ConferenceManager<I: PeerIdentity> {
+    calls: Arc<CallManager<I>>,
+    max_participants: usize,
+    rooms: Rooms<I>,
+    event_sender: broadcast::Sender<MeshEvent<I>>,
+    watcher: JoinHandle<()>,
+}
+
+impl<I: PeerIdentity> ConferenceManager<I> {
+    /// Run rooms over the calls of `calls`
+    ///
+    /// Must be called within a Tokio runtime, which follows the calls'
+    /// events.
+    #[must_use]
+    pub fn new(calls: Arc<CallManager<I>>) -> Self {
+        let rooms: Rooms<I> = Arc::new(DashMap::new());
+        let (event_sender, _) = broadcast::channel(100);
+        let watcher = tokio::spawn(watch_calls(
+            calls.subscribe_events(),
+            Arc::clone(&rooms),
+            event_sender.clone(),
+        ));
+        Self {
+            calls,
+            max_participants: DEFAULT_MAX_PARTICIPANTS,
+            rooms,
+            event_sender,
+            watcher,
+        }
+    }
+
+    /// Hold at most `max_participants` per room, this side included
+    #[must_use]
+    pub fn with_max_participants(mut self, max_participants: usize) -> Self {
+        self.max_participants = max_participants;
+        self
+    }
+
+    /// The calls participants are connected over
+    #[must_use]
+    pub fn calls(&self) -> &Arc<CallManager<I>> {
+        &self.calls
+    }
+
+    /// Subscribe to room events
+    #[must_use]
+    pub fn subscribe_events(&self) -> broadcast::Receiver<MeshEvent<I>> {
+        self.event_sender.subscribe()
+    }
+
+    /// Join `room`, creating the tracks every participant will receive
+    ///
+    /// # Errors
+    ///
+    /// Returns error if already in the room or the tracks cannot be created
+    pub async fn join(
+        &self,
+        room: &RoomId,
+        constraints: MediaConstraints,
+    ) -> Result<(), MeshError> {
+        if self.rooms.contains_key(room) {
+            return Err(MeshError::AlreadyJoined(room.clone()));
+        }
+        let stream = self
+            .calls
+            .create_shared_stream(&format!("room-{room}"), &sent_kinds(&constraints))
+            .await?;
+        let mut joined = false;
+        self.rooms.entry(room.clone()).or_insert_with(|| {
+            joined = true;
+            MeshRoom {
+                stream: stream.clone(),
+                constraints,
+                participants: HashMap::new(),
+            }
+        });
+        if !joined {
+            // Joined meanwhile
+            self.calls.release_stream(&stream).await;
+            return Err(MeshError::AlreadyJoined(room.clone()));
+        }
+        tracing::info!("Joined mesh room {}", room);
+        Ok(())
+    }
+
+    /// Call `peer` into `room`, sending them the room's tracks
+    ///
+    /// Send them the offer from [`CallManager::create_offer`] for the
+    /// returned call.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if not in the room, the room is full, `peer` is
+    /// already connected or the call cannot be set up
+    pub async fn connect(&self, room: &RoomId, peer: I) -> Result<CallId, MeshError> {
+        let stream = self.check_space(room, &peer)?;
+        let call_id = self
+            .calls
+            .initiate_call(peer.clone(), MediaConstraints::data_only())
+            .await?;
+        self.add_participant(room, peer, call_id, &stream).await?;
+        Ok(call_id)
+    }
+
+    /// Answer a participant's call into `room` with the room's tracks
+    ///
+    /// Take their offer with [`CallManager::receive_offer`] first, and
+    /// send them the returned answer.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if not in the room, the room is full, the call does
+    /// not exist or cannot be answered
+    pub async fn admit(&self, room: &RoomId, call_id: CallId) -> Result<String, MeshError> {
+        let peer = self
+            .calls
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        let stream = self.check_space(room, &peer)?;
+        let constraints = self
+            .rooms
+            .get(room)
+            .map(|room| room.constraints.clone())
+            .ok_or_else(|| MeshError::NotJoined(room.clone()))?;
+        self.add_participant(room, peer, call_id, &stream).await?;
+        match self.calls.accept_call(call_id, constraints).await {
+            Ok(Some(answer)) => Ok(answer),
+            Ok(None) => Err(CallError::InvalidState.into()),
+            Err(e) => {
+                self.drop_participant(room, call_id);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Hang up on one participant
+    ///
+    /// # Errors
+    ///
+    /// Returns error if not in the room or `peer` is not connected in it
+    pub async fn disconnect(&self, room: &RoomId, peer: &I) -> Result<(), MeshError> {
+        let peer_id = peer.to_string_repr();
+        let call_id = {
+            let mut mesh = self
+                .rooms
+                .get_mut(room)
+                .ok_or_else(|| MeshError::NotJoined(room.clone()))?;
+            let (_, call_id) = mesh
+                .participants
+                .remove(&peer_id)
+                .ok_or(MeshError::ParticipantNotFound(peer_id))?;
+            call_id
+        };
+        let _ = self.event_sender.send(MeshEvent::ParticipantLeft {
+            room: room.clone(),
+            participant: peer.clone(),
+        });
+        self.calls.end_call(call_id).await?;
+        Ok(())
+    }
+
+    /// Leave `room`, hanging up on everyone in it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if not in the room
+    pub async fn leave(&self, room: &RoomId) -> Result<(), MeshError> {
+        let (_, mesh) = self
+            .rooms
+            .remove(room)
+            .ok_or_else(|| MeshError::NotJoined(room.clone()))?;
+        for (participant, call_id) in mesh.participants.into_values() {
+            let _ = self.event_sender.send(MeshEvent::ParticipantLeft {
+                room: room.clone(),
+                participant,
+            });
+            // Calls that already ended are gone
+            let _ = self.calls.end_call(call_id).await;
+        }
+        self.calls.release_stream(&mesh.stream).await;
+        tracing::info!("Left mesh room {}", room);
+        Ok(())
+    }
+
+    /// Tracks sent to everyone in `room`; write samples to them
+    #[must_use]
+    pub fn local_stream(&self, room: &RoomId) -> Option<MediaStream> {
+        self.rooms.get(room).map(|mesh| mesh.stream.clone())
+    }
+
+    /// Participants connected in `room`
+    #[must_use]
+    pub fn participants(&self, room: &RoomId) -> Vec<I> {
+        self.rooms
+            .get(room)
+            .map(|mesh| {
+                mesh.participants
+                    .values()
+                    .map(|(peer, _)| peer.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Streams each participant in `room` sends
+    #[must_use]
+    pub fn remote_streams(&self, room: &RoomId) -> Vec<(I, Vec<MediaStream<RemoteTrack>>)> {
+        let calls: Vec<_> = self
+            .rooms
+            .get(room)
+            .map(|mesh| mesh.participants.values().cloned().collect())
+            .unwrap_or_default();
+        calls
+            .into_iter()
+            .map(|(peer, call_id)| (peer, self.calls.remote_streams(call_id)))
+            .collect()
+    }
+
+    /// The room's tracks, if `peer` can be added to it
+    fn check_space(&self, room: &RoomId, peer: &I) -> Result<MediaStream, MeshError> {
+        let mesh = self
+            .rooms
+            .get(room)
+            .ok_or_else(|| MeshError::NotJoined(room.clone()))?;
+        let peer_id = peer.to_string_repr();
+        if mesh.participants.contains_key(&peer_id) {
+            return Err(MeshError::AlreadyConnected(peer_id));
+        }
+        if mesh.participants.len() + 1 >= self.max_participants {
+            return Err(MeshError::RoomFull(room.clone()));
+        }
+        Ok(mesh.stream.clone())
+    }
+
+    /// Share the room's tracks on `call_id` and record `peer` as in it
+    async fn add_participant(
+        &self,
+        room: &RoomId,
+        peer: I,
+        call_id: CallId,
+        stream: &MediaStream,
+    ) -> Result<(), MeshError> {
+        if let Err(e) = self.calls.share_stream(call_id, stream).await {
+            let _ = self.calls.end_call(call_id).await;
+            return Err(e.into());
+        }
+        {
+            let Some(mut mesh) = self.rooms.get_mut(room) else {
+                // Left meanwhile
+                let _ = self.calls.end_call(call_id).await;
+                return Err(MeshError::NotJoined(room.clone()));
+            };
+            mesh.participants
+                .insert(peer.to_string_repr(), (peer.clone(), call_id));
+        }
+        tracing::info!("{} joined mesh room {} on call {}", peer, room, call_id);
+        let _ = self.event_sender.send(MeshEvent::ParticipantJoined {
+            room: room.clone(),
+            participant: peer,
+            call_id,
+        });
+        Ok(())
+    }
+
+    fn drop_participant(&self, room: &RoomId, call_id: CallId) {
+        if let Some(participant) = remove_call(&self.rooms, call_id).map(|(_, peer)| peer) {
+            let _ = self.event_sender.send(MeshEvent::ParticipantLeft {
+                room: room.clone(),
+                participant,
+            });
+        }
+    }
+}
+
+impl<I: PeerIdentity> Drop for ConferenceManager<I> {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
+/// Kinds of track to send for `constraints`
+fn sent_kinds(constraints: &MediaConstraints) -> Vec<MediaType> {
+    let mut kinds = Vec::new();
+    if constraints.has_audio() {
+        kinds.push(MediaType::Audio);
+    }
+    if constraints.has_video() {
+        kinds.push(MediaType::Video);
+    }
+    kinds
+}
+
+/// Room and participant a call belongs to
+fn find_call<I: PeerIdentity>(rooms: &Rooms<I>, call_id: CallId) -> Option<(RoomId, I)> {
+    rooms.iter().find_map(|mesh| {
+        mesh.participants
+            .values()
+            .find(|(_, id)| *id == call_id)
+            .map(|(peer, _)| (mesh.key().clone(), peer.clone()))
+    })
+}
+
+/// Forget the participant on a call, returning their room and identity
+fn remove_call<I: PeerIdentity>(rooms: &Rooms<I>, call_id: CallId) -> Option<(RoomId, I)> {
+    let (room, peer) = find_call(rooms, call_id)?;
+    rooms
+        .get_mut(&room)?
+        .participants
+        .remove(&peer.to_string_repr());
+    Some((room, peer))
+}
+
+/// Turn call events on participants' calls into room events until the
+/// calls are gone
+async fn watch_calls<I: PeerIdentity>(
+    mut events: broadcast::Receiver<CallEvent<I>>,
+    rooms: Rooms<I>,
+    event_sender: broadcast::Sender<MeshEvent<I>>,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                tracing::warn!("Mesh rooms missed {} call events", missed);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let mesh_event = match event {
+            CallEvent::RemoteTrackAdded {
+                call_id,
+                track_id,
+                kind,
+                ..
+            } => {
+                find_call(&rooms, call_id).map(|(room, participant)| MeshEvent::RemoteTrackAdded {
+                    room,
+                    participant,
+                    track_id,
+                    kind,
+                })
+            }
+            CallEvent::RemoteTrackRemoved {
+                call_id, track_id, ..
+            } => find_call(&rooms, call_id).map(|(room, participant)| {
+                MeshEvent::RemoteTrackRemoved {
+                    room,
+                    participant,
+                    track_id,
+                }
+            }),
+            CallEvent::CallEnded { call_id, .. }
+            | CallEvent::CallRejected { call_id }
+            | CallEvent::ConnectionFailed { call_id, .. } => remove_call(&rooms, call_id)
+                .map(|(room, participant)| MeshEvent::ParticipantLeft { room, participant }),
+            _ => None,
+        };
+        if let Some(mesh_event) = mesh_event {
+            let _ = event_sender.send(mesh_event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::call::CallManagerConfig;
+    use crate::identity::PeerIdentityString;
+
+    async fn peer() -> ConferenceManager<PeerIdentityString> {
+        let calls = CallManager::new(CallManagerConfig::default())
+            .await
+            .unwrap();
+        ConferenceManager::new(Arc::new(calls))
+    }
+
+    #[tokio::test]
+    async fn test_mesh_shares_tracks_with_every_participant() {
+        let room = RoomId::from("standup");
+        let alice = peer().await.with_max_participants(3);
+        let bob = peer().await;
+        let mut events = alice.subscribe_events();
+        alice
+            .join(&room, MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        bob.join(&room, MediaConstraints::audio_only())
+            .await
+            .unwrap();
+
+        // Alice calls Bob into the room, and Bob answers
+        let call_id = alice
+            .connect(&room, PeerIdentityString::new("bob"))
+            .await
+            .unwrap();
+        let offer = alice.calls().create_offer(call_id).await.unwrap();
+        bob.calls()
+            .receive_offer(
+                call_id,
+                PeerIdentityString::new("alice"),
+                PeerIdentityString::new("bob"),
+                offer,
+            )
+            .await
+            .unwrap();
+        let answer = bob.admit(&room, call_id).await.unwrap();
+        alice.calls().handle_answer(call_id, answer).await.unwrap();
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            MeshEvent::ParticipantJoined { call_id: joined, .. } if joined == call_id
+        ));
+        assert_eq!(
+            bob.participants(&room),
+            vec![PeerIdentityString::new("alice")]
+        );
+
+        // Carol gets the same tracks; a fourth would not fit
+        let carol = alice
+            .connect(&room, PeerIdentityString::new("carol"))
+            .await
+            .unwrap();
+        let shared: Vec<_> = alice
+            .local_stream(&room)
+            .unwrap()
+            .tracks()
+            .iter()
+            .map(|track| track.id.clone())
+            .collect();
+        for call in [call_id, carol] {
+            let sent = alice.calls().local_stream(call).unwrap();
+            let sent: Vec<_> = sent.tracks().iter().map(|track| track.id.clone()).collect();
+            assert_eq!(sent, shared);
+        }
+        assert!(matches!(
+            alice.connect(&room, PeerIdentityString::new("dave")).await,
+            Err(MeshError::RoomFull(_))
+        ));
+
+        // Hanging up on one leaves the others their tracks
+        alice
+            .disconnect(&room, &PeerIdentityString::new("bob"))
+            .await
+            .unwrap();
+        assert_eq!(
+            alice.participants(&room),
+            vec![PeerIdentityString::new("carol")]
+        );
+        assert_eq!(
+            alice.calls().local_stream(carol).unwrap().len(),
+            shared.len()
+        );
+
+        alice.leave(&room).await.unwrap();
+        assert!(alice.participants(&room).is_empty());
+        assert!(matches!(
+            alice.leave(&room).await,
+            Err(MeshError::NotJoined(_))
+        ));
+    }
+}
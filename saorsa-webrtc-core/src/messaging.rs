@@ -0,0 +1,237 @@
+//! Text messaging over data channels
+//!
+//! Presence and chat run on the same peer connections as calls. A
+//! connection opened with
+//! [`CallManager::connect_data`](crate::call::CallManager::connect_data)
+//! carries no media, only the data channel labelled
+//! [`MESSAGE_CHANNEL_LABEL`], and becomes a call once media is added to it.
+//! Messages travel end-to-end encrypted by the connection's DTLS, between
+//! the identities the connection was set up for.
+//!
+//! Group conversations are sent to each member over their own connection.
+//! Messages name the group they belong to, so every member threads them
+//! alike, and keep one ID across members, so duplicates can be dropped.
+//!
+//! # Wire format
+//!
+//! One [`TextMessage`] per data channel message, fields little-endian:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 1 | version, [`MESSAGE_WIRE_VERSION`] |
+//! | 16 | message ID |
+//! | 8 | sent at, microseconds since the Unix epoch, sender's clock |
+//! | 2 | group length, 0 outside groups |
+//! | .. | group, UTF-8 |
+//! | 4 | text length |
+//! | .. | text, UTF-8 |
+//!
+//! Bytes after the text are ignored so later versions can extend a
+//! message.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::wire::{Truncated, WireReader};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Version written in the first byte of every message
+pub const MESSAGE_WIRE_VERSION: u8 = 1;
+
+/// Label reserved for the text messaging data channel
+pub const MESSAGE_CHANNEL_LABEL: &str = "saorsa-messages";
+
+/// Longest text a message may carry, in bytes
+///
+/// Keeps each message within one SCTP message on every implementation.
+pub const MAX_TEXT_BYTES: usize = 16 * 1024;
+
+/// Text message encoding and decoding errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MessageError {
+    /// Text is longer than [`MAX_TEXT_BYTES`]
+    #[error("Message of {0} bytes is too long")]
+    TooLong(usize),
+
+    /// Group name is longer than a message can carry
+    #[error("Group name of {0} bytes is too long")]
+    GroupTooLong(usize),
+
+    /// Message ended before its last field did
+    #[error("Text message truncated")]
+    Truncated,
+
+    /// Message written by an incompatible version
+    #[error("Unsupported message version: {0}")]
+    UnsupportedVersion(u8),
+
+    /// Group or text is not UTF-8
+    #[error("Text message is not UTF-8")]
+    InvalidText,
+}
+
+impl CodedError for MessageError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::TooLong(_) | Self::GroupTooLong(_) => ErrorCode::InvalidInput,
+            Self::Truncated | Self::UnsupportedVersion(_) | Self::InvalidText => {
+                ErrorCode::MalformedMessage
+            }
+        }
+    }
+}
+
+impl From<Truncated> for MessageError {
+    fn from(_: Truncated) -> Self {
+        Self::Truncated
+    }
+}
+
+/// A chat message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextMessage {
+    /// Unique message ID, the same for every member of a group
+    pub id: Uuid,
+    /// When the message was sent, by the sender's clock
+    pub sent_at: DateTime<Utc>,
+    /// Group conversation the message belongs to, if any
+    pub group: Option<String>,
+    /// Message text
+    pub text: String,
+}
+
+impl TextMessage {
+    /// A new message sent now
+    #[must_use]
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            sent_at: Utc::now(),
+            group: None,
+            text: text.into(),
+        }
+    }
+
+    /// The message as part of group conversation `group`
+    #[must_use]
+    pub fn in_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Encode for sending over the message channel
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the text or group name is too long
+    pub fn encode(&self) -> Result<Vec<u8>, MessageError> {
+        if self.text.len() > MAX_TEXT_BYTES {
+            return Err(MessageError::TooLong(self.text.len()));
+        }
+        let group = self.group.as_deref().unwrap_or_default();
+        let group_len =
+            u16::try_from(group.len()).map_err(|_| MessageError::GroupTooLong(group.len()))?;
+
+        let mut buf = Vec::with_capacity(31 + group.len() + self.text.len());
+        buf.push(MESSAGE_WIRE_VERSION);
+        buf.extend_from_slice(self.id.as_bytes());
+        buf.extend_from_slice(&self.sent_at.timestamp_micros().to_le_bytes());
+        buf.extend_from_slice(&group_len.to_le_bytes());
+        buf.extend_from_slice(group.as_bytes());
+        // Bounded by MAX_TEXT_BYTES
+        buf.extend_from_slice(&(self.text.len() as u32).to_le_bytes());
+        buf.extend_from_slice(self.text.as_bytes());
+        Ok(buf)
+    }
+
+    /// Decode a message received over the message channel
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the message is truncated, from an incompatible
+    /// version, too long or not UTF-8
+    pub fn decode(data: &[u8]) -> Result<Self, MessageError> {
+        let mut reader = WireReader::new(data);
+        let version = reader.u8()?;
+        if version != MESSAGE_WIRE_VERSION {
+            return Err(MessageError::UnsupportedVersion(version));
+        }
+        let id = Uuid::from_bytes(reader.take()?);
+        let micros = reader.i64()?;
+        let sent_at = DateTime::from_timestamp_micros(micros).unwrap_or_default();
+        let group_len = usize::from(reader.u16()?);
+        let group = utf8(reader.bytes(group_len)?)?;
+        let text_len = reader.u32()? as usize;
+        if text_len > MAX_TEXT_BYTES {
+            return Err(MessageError::TooLong(text_len));
+        }
+        let text = utf8(reader.bytes(text_len)?)?;
+        Ok(Self {
+            id,
+            sent_at,
+            group: (!group.is_empty()).then_some(group),
+            text,
+        })
+    }
+}
+
+fn utf8(bytes: &[u8]) -> Result<String, MessageError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| MessageError::InvalidText)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_round_trip() {
+        let message = TextMessage::new("see you at 3 ☕").in_group("team");
+        let decoded = TextMessage::decode(&message.encode().unwrap()).unwrap();
+        // Sent time travels to the microsecond
+        assert_eq!(
+            decoded.sent_at.timestamp_micros(),
+            message.sent_at.timestamp_micros()
+        );
+        assert_eq!(
+            decoded,
+            TextMessage {
+                sent_at: decoded.sent_at,
+                ..message
+            }
+        );
+
+        let direct = TextMessage::new("hi");
+        let mut data = direct.encode().unwrap();
+        data.extend_from_slice(b"future fields");
+        assert_eq!(TextMessage::decode(&data).unwrap().group, None);
+    }
+
+    #[test]
+    fn test_rejects_bad_messages() {
+        let long = TextMessage::new("x".repeat(MAX_TEXT_BYTES + 1));
+        assert_eq!(
+            long.encode(),
+            Err(MessageError::TooLong(MAX_TEXT_BYTES + 1))
+        );
+
+        let data = TextMessage::new("hello").encode().unwrap();
+        assert_eq!(
+            TextMessage::decode(&data[..data.len() - 1]),
+            Err(MessageError::Truncated)
+        );
+        let mut future = data.clone();
+        future[0] = 2;
+        assert_eq!(
+            TextMessage::decode(&future),
+            Err(MessageError::UnsupportedVersion(2))
+        );
+        let mut garbled = data;
+        let last = garbled.len() - 1;
+        garbled[last] = 0xFF;
+        assert_eq!(
+            TextMessage::decode(&garbled),
+            Err(MessageError::InvalidText)
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Pre-call microphone self-test
+//!
+//! Collects a short microphone sample and reports the noise floor, the
+//! amount of clipping and the risk of echo, with a recommendation on
+//! whether noise suppression should be enabled before joining a call.
+
+use saorsa_webrtc_codecs::AudioFrame;
+use serde::{Deserialize, Serialize};
+
+/// Full-scale amplitude of a 16-bit sample
+const FULL_SCALE: f32 = 32768.0;
+/// Analysis window length in milliseconds
+const WINDOW_MS: u32 = 10;
+/// Longest echo path searched, in milliseconds
+const MAX_ECHO_DELAY_MS: u32 = 300;
+/// Floor used for silent windows, avoids `-inf`
+const SILENCE_DBFS: f32 = -96.0;
+
+/// Self-test configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MicTestConfig {
+    /// How much audio to collect
+    pub duration_ms: u32,
+    /// Noise floor above which noise suppression is recommended
+    pub noisy_floor_dbfs: f32,
+    /// Fraction of clipped samples above which the input gain is too high
+    pub max_clipping_ratio: f32,
+}
+
+impl Default for MicTestConfig {
+    fn default() -> Self {
+        Self {
+            duration_ms: 3000,
+            noisy_floor_dbfs: -50.0,
+            max_clipping_ratio: 0.001,
+        }
+    }
+}
+
+/// Likelihood that the microphone picks up the speaker output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EchoRisk {
+    /// No reference signal was played, echo was not measured
+    Unknown,
+    /// Little or no speaker output reached the microphone
+    Low,
+    /// Some speaker output reached the microphone
+    Medium,
+    /// Speaker output is clearly audible in the microphone
+    High,
+}
+
+/// Result of a microphone self-test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MicTestReport {
+    /// Length of audio analysed
+    pub duration_ms: u32,
+    /// Background noise level (quietest 10% of windows)
+    pub noise_floor_dbfs: f32,
+    /// Loudest sample
+    pub peak_dbfs: f32,
+    /// Fraction of samples at full scale
+    pub clipping_ratio: f32,
+    /// Echo risk, measured against the reference signal if one was played
+    pub echo_risk: EchoRisk,
+    /// Whether noise suppression should be enabled
+    pub recommend_noise_suppression: bool,
+    /// Human readable advice
+    pub recommendations: Vec<String>,
+}
+
+/// Collects microphone frames and produces a [`MicTestReport`]
+#[derive(Debug, Clone)]
+pub struct MicSelfTest {
+    config: MicTestConfig,
+    sample_rate: Option<u32>,
+    samples: Vec<i16>,
+    reference: Option<Vec<i16>>,
+}
+
+impl MicSelfTest {
+    /// Create a self-test
+    #[must_use]
+    pub fn new(config: MicTestConfig) -> Self {
+        Self {
+            config,
+            sample_rate: None,
+            samples: Vec::new(),
+            reference: None,
+        }
+    }
+
+    /// Set the mono signal played through the speakers during the test
+    ///
+    /// When present, echo risk is measured by correlating the recording
+    /// against it.
+    #[must_use]
+    pub fn with_reference(mut self, reference: Vec<i16>) -> Self {
+        self.reference = Some(reference);
+        self
+    }
+
+    /// Add captured audio, downmixing to mono
+    ///
+    /// Returns `true` once enough audio has been collected.
+    pub fn push_frame(&mut self, frame: &AudioFrame) -> bool {
+        self.sample_rate.get_or_insert(frame.sample_rate.as_hz());
+        let channels = frame.channels.count().max(1);
+        if !self.is_complete() {
+            self.samples
+                .extend(frame.data.chunks(channels).map(|chunk| {
+                    let sum: i32 = chunk.iter().map(|&s| i32::from(s)).sum();
+                    (sum / chunk.len() as i32) as i16
+                }));
+        }
+        self.is_complete()
+    }
+
+    /// Whether the configured duration has been collected
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.collected_ms() >= self.config.duration_ms
+    }
+
+    fn collected_ms(&self) -> u32 {
+        match self.sample_rate {
+            Some(rate) if rate > 0 => (self.samples.len() as u64 * 1000 / u64::from(rate)) as u32,
+            _ => 0,
+        }
+    }
+
+    /// Analyse the collected audio
+    #[must_use]
+    pub fn finish(self) -> MicTestReport {
+        let sample_rate = self.sample_rate.unwrap_or(48_000);
+        analyze(
+            &self.samples,
+            sample_rate,
+            self.reference.as_deref(),
+            &self.config,
+        )
+    }
+}
+
+/// Analyse a mono 16-bit recording
+#[must_use]
+pub fn analyze(
+    samples: &[i16],
+    sample_rate: u32,
+    reference: Option<&[i16]>,
+    config: &MicTestConfig,
+) -> MicTestReport {
+    let window = (sample_rate * WINDOW_MS / 1000).max(1) as usize;
+
+    let mut window_levels: Vec<f32> = samples.chunks(window).map(rms_dbfs).collect();
+    window_levels.sort_by(f32::total_cmp);
+    let noise_floor_dbfs = window_levels
+        .get(window_levels.len() / 10)
+        .copied()
+        .unwrap_or(SILENCE_DBFS);
+
+    let peak = samples
+        .iter()
+        .map(|&s| i32::from(s).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    let peak_dbfs = to_dbfs(peak as f32 / FULL_SCALE);
+
+    let clipped = samples
+        .iter()
+        .filter(|&&s| s == i16::MAX || s == i16::MIN)
+        .count();
+    let clipping_ratio = if samples.is_empty() {
+        0.0
+    } else {
+        clipped as f32 / samples.len() as f32
+    };
+
+    let echo_risk = reference.map_or(EchoRisk::Unknown, |reference| {
+        let max_lag = (sample_rate * MAX_ECHO_DELAY_MS / 1000) as usize;
+        let correlation = max_correlation(samples, reference, max_lag);
+        if correlation > 0.5 {
+            EchoRisk::High
+        } else if correlation > 0.2 {
+            EchoRisk::Medium
+        } else {
+            EchoRisk::Low
+        }
+    });
+
+    let recommend_noise_suppression = noise_floor_dbfs > config.noisy_floor_dbfs;
+    let mut recommendations = Vec::new();
+    if recommend_noise_suppression {
+        recommendations.push(format!(
+            "Background noise is high ({:.0} dBFS), enable noise suppression",
+            noise_floor_dbfs
+        ));
+    }
+    if clipping_ratio > config.max_clipping_ratio {
+        recommendations.push(format!(
+            "Input is clipping ({:.2}% of samples), lower the microphone gain",
+            clipping_ratio * 100.0
+        ));
+    }
+    if peak_dbfs < -40.0 {
+        recommendations.push("Input is very quiet, check the microphone is not muted".to_string());
+    }
+    match echo_risk {
+        EchoRisk::High | EchoRisk::Medium => recommendations.push(
+            "Speaker output reaches the microphone, use headphones or enable echo cancellation"
+                .to_string(),
+        ),
+        EchoRisk::Low | EchoRisk::Unknown => {}
+    }
+
+    MicTestReport {
+        duration_ms: (samples.len() as u64 * 1000 / u64::from(sample_rate.max(1))) as u32,
+        noise_floor_dbfs,
+        peak_dbfs,
+        clipping_ratio,
+        echo_risk,
+        recommend_noise_suppression,
+        recommendations,
+    }
+}
+
+fn rms_dbfs(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return SILENCE_DBFS;
+    }
+    let sum: f64 = samples
+        .iter()
+        .map(|&s| {
+            let v = f64::from(s) / f64::from(FULL_SCALE);
+            v * v
+        })
+        .sum();
+    to_dbfs((sum / samples.len() as f64).sqrt() as f32)
+}
+
+fn to_dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        SILENCE_DBFS
+    } else {
+        (20.0 * amplitude.log10()).max(SILENCE_DBFS)
+    }
+}
+
+/// Peak normalised cross-correlation of `recording` against `reference`
+/// over delays up to `max_lag` samples
+fn max_correlation(recording: &[i16], reference: &[i16], max_lag: usize) -> f32 {
+    let energy = |s: &[i16]| s.iter().map(|&v| f64::from(v) * f64::from(v)).sum::<f64>();
+    // Short overlaps correlate by chance, require at least half the reference
+    let min_len = (reference.len() / 2).max(1);
+    let mut best = 0.0_f64;
+
+    for lag in 0..=max_lag.min(recording.len()) {
+        let len = reference.len().min(recording.len() - lag);
+        if len < min_len {
+            break;
+        }
+        let rec = &recording[lag..lag + len];
+        let refr = &reference[..len];
+        let denom = (energy(rec) * energy(refr)).sqrt();
+        if denom == 0.0 {
+            continue;
+        }
+        let dot: f64 = rec
+            .iter()
+            .zip(refr)
+            .map(|(&a, &b)| f64::from(a) * f64::from(b))
+            .sum();
+        best = best.max(dot / denom);
+    }
+
+    best as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_webrtc_codecs::{Channels, SampleRate};
+
+    const RATE: u32 = 16_000;
+
+    fn tone(amplitude: f32, len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / RATE as f32;
+                (amplitude * FULL_SCALE * (2.0 * std::f32::consts::PI * 440.0 * t).sin()) as i16
+            })
+            .collect()
+    }
+
+    fn noise(amplitude: i16, len: usize) -> Vec<i16> {
+        // Deterministic pseudo-random noise
+        let mut state = 12345_u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as i16 % amplitude.max(1)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_quiet_room_needs_no_suppression() {
+        let samples = noise(10, RATE as usize);
+        let report = analyze(&samples, RATE, None, &MicTestConfig::default());
+        assert!(report.noise_floor_dbfs < -60.0);
+        assert!(!report.recommend_noise_suppression);
+        assert_eq!(report.echo_risk, EchoRisk::Unknown);
+    }
+
+    #[test]
+    fn test_noisy_room_recommends_suppression() {
+        let samples = noise(2000, RATE as usize);
+        let report = analyze(&samples, RATE, None, &MicTestConfig::default());
+        assert!(report.recommend_noise_suppression);
+        assert!(report.recommendations[0].contains("noise suppression"));
+    }
+
+    #[test]
+    fn test_clipping_detected() {
+        let mut samples = tone(0.5, RATE as usize);
+        for s in samples.iter_mut().step_by(50) {
+            *s = i16::MAX;
+        }
+        let report = analyze(&samples, RATE, None, &MicTestConfig::default());
+        assert!(report.clipping_ratio > 0.01);
+        assert!(report.peak_dbfs > -0.1);
+        assert!(report
+            .recommendations
+            .iter()
+            .any(|r| r.contains("clipping")));
+    }
+
+    #[test]
+    fn test_echo_risk_from_reference() {
+        let reference = tone(0.5, 4000);
+        let delay = 800;
+        let mut echoed = vec![0_i16; delay];
+        echoed.extend(reference.iter().map(|&s| s / 2));
+
+        let report = analyze(&echoed, RATE, Some(&reference), &MicTestConfig::default());
+        assert_eq!(report.echo_risk, EchoRisk::High);
+
+        let silent = noise(10, 4800);
+        let report = analyze(&silent, RATE, Some(&reference), &MicTestConfig::default());
+        assert_eq!(report.echo_risk, EchoRisk::Low);
+    }
+
+    #[test]
+    fn test_self_test_collects_duration() {
+        let config = MicTestConfig {
+            duration_ms: 100,
+            ..Default::default()
+        };
+        let mut test = MicSelfTest::new(config);
+        let frame = AudioFrame {
+            data: noise(100, 320 * 2),
+            sample_rate: SampleRate::Hz16000,
+            channels: Channels::Stereo,
+            timestamp: 0,
+        };
+
+        let mut frames = 0;
+        while !test.push_frame(&frame) {
+            frames += 1;
+        }
+        assert_eq!(frames, 4);
+        assert_eq!(test.finish().duration_ms, 100);
+    }
+}
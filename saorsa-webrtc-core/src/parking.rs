@@ -0,0 +1,175 @@
+//! Call parking
+//!
+//! A parked call keeps its peer connection open with outgoing media
+//! paused. Parking hands out a [`ParkToken`]; presenting it resumes the
+//! call in the same session, or, from another device signed in as the same
+//! identity, hands the call over to that device mid-call.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::types::CallId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Parking errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParkingError {
+    /// No call is parked under the token
+    #[error("No call parked for token: {0}")]
+    NotFound(String),
+
+    /// Retrieval attempted by a different identity than the one that parked
+    #[error("Identity mismatch: {0}")]
+    IdentityMismatch(String),
+}
+
+impl CodedError for ParkingError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::IdentityMismatch(_) => ErrorCode::PermissionDenied,
+        }
+    }
+}
+
+/// Token for retrieving a parked call
+///
+/// The secret is unguessable and is only honored for the identity that
+/// parked the call, so leaking a token alone does not leak the call.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ParkToken {
+    /// Random secret naming the parked call
+    pub secret: String,
+    /// Identity that parked the call, and the peer to ask for it
+    pub owner: String,
+}
+
+/// A parked call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParkedCall {
+    /// Parked call
+    pub call_id: CallId,
+    /// Token that retrieves it
+    pub token: ParkToken,
+    /// When it was parked
+    pub parked_at: DateTime<Utc>,
+}
+
+/// Calls parked in this session, keyed by token secret
+#[derive(Debug, Default)]
+pub struct ParkingLot {
+    parked: HashMap<String, ParkedCall>,
+}
+
+impl ParkingLot {
+    /// Create an empty parking lot
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Park a call on behalf of `owner`, returning its retrieval token
+    pub fn park(&mut self, call_id: CallId, owner: &str) -> ParkToken {
+        let token = ParkToken {
+            secret: uuid::Uuid::new_v4().to_string(),
+            owner: owner.to_string(),
+        };
+        self.parked.insert(
+            token.secret.clone(),
+            ParkedCall {
+                call_id,
+                token: token.clone(),
+                parked_at: Utc::now(),
+            },
+        );
+        token
+    }
+
+    /// Take a parked call out of the lot
+    ///
+    /// # Errors
+    ///
+    /// Returns error if nothing is parked under `secret`, or `requester` is
+    /// not the identity that parked it
+    pub fn retrieve(&mut self, secret: &str, requester: &str) -> Result<CallId, ParkingError> {
+        let parked = self
+            .parked
+            .get(secret)
+            .ok_or_else(|| ParkingError::NotFound(secret.to_string()))?;
+        if parked.token.owner != requester {
+            return Err(ParkingError::IdentityMismatch(requester.to_string()));
+        }
+        let call_id = parked.call_id;
+        self.parked.remove(secret);
+        Ok(call_id)
+    }
+
+    /// Drop a call from the lot, e.g. because it ended while parked
+    pub fn release(&mut self, call_id: CallId) -> Option<ParkToken> {
+        let secret = self
+            .parked
+            .values()
+            .find(|parked| parked.call_id == call_id)
+            .map(|parked| parked.token.secret.clone())?;
+        self.parked.remove(&secret).map(|parked| parked.token)
+    }
+
+    /// Whether a call is parked
+    #[must_use]
+    pub fn is_parked(&self, call_id: CallId) -> bool {
+        self.parked.values().any(|parked| parked.call_id == call_id)
+    }
+
+    /// Parked calls
+    pub fn parked(&self) -> impl Iterator<Item = &ParkedCall> {
+        self.parked.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_park_and_retrieve() {
+        let mut lot = ParkingLot::new();
+        let call_id = CallId::new();
+        let token = lot.park(call_id, "alice");
+        assert!(lot.is_parked(call_id));
+        assert_eq!(token.owner, "alice");
+
+        assert_eq!(lot.retrieve(&token.secret, "alice"), Ok(call_id));
+        assert!(!lot.is_parked(call_id));
+        assert!(matches!(
+            lot.retrieve(&token.secret, "alice"),
+            Err(ParkingError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_other_identity_cannot_retrieve() {
+        let mut lot = ParkingLot::new();
+        let call_id = CallId::new();
+        let token = lot.park(call_id, "alice");
+
+        assert_eq!(
+            lot.retrieve(&token.secret, "mallory"),
+            Err(ParkingError::IdentityMismatch("mallory".to_string()))
+        );
+        // A failed attempt leaves the call parked for its owner
+        assert!(lot.is_parked(call_id));
+    }
+
+    #[test]
+    fn test_release_ended_call() {
+        let mut lot = ParkingLot::new();
+        let call_id = CallId::new();
+        let token = lot.park(call_id, "alice");
+        lot.park(CallId::new(), "alice");
+
+        assert_eq!(lot.release(call_id), Some(token));
+        assert_eq!(lot.release(call_id), None);
+        assert_eq!(lot.parked().count(), 1);
+    }
+}
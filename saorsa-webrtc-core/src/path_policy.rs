@@ -0,0 +1,323 @@
+//! Network path policy
+//!
+//! Privacy- and cost-sensitive users limit the network paths calls take.
+//! A [`PathPolicy`] can refuse relayed paths, which hand traffic to a third
+//! party; keep calls on the local network; prefer IPv6; and keep off
+//! cellular interfaces, which are metered.
+//!
+//! The policy is applied wherever a path is chosen:
+//! - ICE gathers no candidates on interfaces or addresses it rules out,
+//!   also after an ICE restart on a network change, so a call never
+//!   migrates onto a path it forbids
+//! - [`CallManager::add_ice_candidate`](crate::call::CallManager::add_ice_candidate)
+//!   refuses remote candidates it rules out and, with IPv6 preferred,
+//!   ranks IPv6 candidates above IPv4 ones of the same type
+//! - the QUIC transport joins only allowed relays, refuses to dial
+//!   addresses it rules out and ignores peers connected from them, also
+//!   after the policy is reloaded
+//!
+//! Cellular interfaces are recognised by name (`rmnet`, `pdp_ip`, `wwan`
+//! and the like). QUIC listens on every interface, so to keep it off
+//! cellular bind the transport's `local_addr` to another interface.
+
+use crate::error::{CodedError, ErrorCode};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use thiserror::Error;
+use webrtc::api::setting_engine::SettingEngine;
+
+/// Name prefixes of cellular modem interfaces on Android, iOS and Linux
+const CELLULAR_INTERFACE_PREFIXES: [&str; 5] = ["rmnet", "ccmni", "pdp_ip", "wwan", "wwp"];
+
+/// A network path the policy rules out
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    /// Path goes through a relay
+    #[error("Relayed paths are not allowed")]
+    Relay,
+
+    /// Address is outside the local network
+    #[error("{0} is outside the local network")]
+    OutsideLan(IpAddr),
+
+    /// Interface is a cellular modem
+    #[error("Cellular interface {0} is not allowed")]
+    Cellular(String),
+}
+
+impl CodedError for PathError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::PathRejected
+    }
+}
+
+/// Network paths calls may take
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathPolicy {
+    /// Allow paths through TURN servers and relay nodes
+    pub allow_relay: bool,
+    /// Only allow private, link-local and loopback addresses
+    pub lan_only: bool,
+    /// Rank IPv6 paths above IPv4 ones
+    pub prefer_ipv6: bool,
+    /// Allow cellular interfaces
+    pub allow_cellular: bool,
+}
+
+impl Default for PathPolicy {
+    fn default() -> Self {
+        Self {
+            allow_relay: true,
+            lan_only: false,
+            prefer_ipv6: false,
+            allow_cellular: true,
+        }
+    }
+}
+
+impl PathPolicy {
+    /// Policy for users who want no third party to see their traffic
+    #[must_use]
+    pub fn private() -> Self {
+        Self {
+            allow_relay: false,
+            ..Self::default()
+        }
+    }
+
+    /// Check a peer address
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PathError::OutsideLan`] if the policy is LAN-only and `ip`
+    /// is not on the local network
+    pub fn check_addr(&self, ip: IpAddr) -> Result<(), PathError> {
+        if self.lan_only && !is_lan(ip) {
+            return Err(PathError::OutsideLan(ip));
+        }
+        Ok(())
+    }
+
+    /// Check a local interface by name
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PathError::Cellular`] if cellular is disallowed and `name`
+    /// is a cellular interface
+    pub fn check_interface(&self, name: &str) -> Result<(), PathError> {
+        if !self.allow_cellular && is_cellular_interface(name) {
+            return Err(PathError::Cellular(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check a relay node
+    ///
+    /// # Errors
+    ///
+    /// Returns error if relays are disallowed or `relay` is an address the
+    /// policy rules out
+    pub fn check_relay(&self, relay: SocketAddr) -> Result<(), PathError> {
+        if !self.allow_relay {
+            return Err(PathError::Relay);
+        }
+        self.check_addr(relay.ip())
+    }
+
+    /// Relays of `relays` the policy allows, in the order to try them
+    #[must_use]
+    pub fn relays(&self, relays: &[SocketAddr]) -> Vec<SocketAddr> {
+        let mut allowed: Vec<SocketAddr> = relays
+            .iter()
+            .copied()
+            .filter(|relay| self.check_relay(*relay).is_ok())
+            .collect();
+        if self.prefer_ipv6 {
+            // Stable, so the configured order holds within each family
+            allowed.sort_by_key(|relay| !relay.is_ipv6());
+        }
+        allowed
+    }
+
+    /// Check a remote ICE candidate line, returning it as it should be
+    /// added
+    ///
+    /// With IPv6 preferred, the candidate's priority is rewritten so IPv6
+    /// candidates rank above IPv4 ones of the same type. Candidates that
+    /// cannot be parsed are returned unchanged for ICE to reject, as are
+    /// mDNS host names, which only resolve on the local network.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the candidate is relayed and relays are disallowed,
+    /// or its address is one the policy rules out
+    pub fn check_candidate(&self, candidate: &str) -> Result<String, PathError> {
+        // candidate:<foundation> <component> <transport> <priority>
+        //     <address> <port> typ <type> ...
+        let mut fields: Vec<&str> = candidate.split_whitespace().collect();
+        if fields.len() < 8 || fields[6] != "typ" {
+            return Ok(candidate.to_string());
+        }
+        if fields[7] == "relay" && !self.allow_relay {
+            return Err(PathError::Relay);
+        }
+        let Ok(ip) = fields[4].parse::<IpAddr>() else {
+            return Ok(candidate.to_string());
+        };
+        self.check_addr(ip)?;
+        if !self.prefer_ipv6 {
+            return Ok(candidate.to_string());
+        }
+
+        let Ok(priority) = fields[3].parse::<u32>() else {
+            return Ok(candidate.to_string());
+        };
+        let ranked = rank_family(priority, ip.is_ipv6()).to_string();
+        fields[3] = &ranked;
+        Ok(fields.join(" "))
+    }
+
+    /// ICE settings that keep candidate gathering within the policy
+    pub(crate) fn setting_engine(&self) -> SettingEngine {
+        let mut engine = SettingEngine::default();
+        let policy = *self;
+        if policy.lan_only {
+            engine.set_ip_filter(Box::new(move |ip| policy.check_addr(ip).is_ok()));
+        }
+        if !policy.allow_cellular {
+            engine.set_interface_filter(Box::new(move |name| policy.check_interface(name).is_ok()));
+        }
+        engine
+    }
+}
+
+/// Whether `ip` is a private, link-local or loopback address
+#[must_use]
+pub fn is_lan(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_lan(IpAddr::V4(ip)),
+            None => ip.is_unique_local() || ip.is_unicast_link_local() || ip.is_loopback(),
+        },
+    }
+}
+
+/// Whether `name` is a cellular modem interface
+#[must_use]
+pub fn is_cellular_interface(name: &str) -> bool {
+    CELLULAR_INTERFACE_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// ICE priority with the local preference raised to the top for IPv6 and
+/// halved for IPv4, keeping the type preference and component
+fn rank_family(priority: u32, ipv6: bool) -> u32 {
+    let local_preference = (priority >> 8) & 0xFFFF;
+    let local_preference = if ipv6 { 0xFFFF } else { local_preference / 2 };
+    (priority & 0xFF00_00FF) | (local_preference << 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST_V4: &str = "candidate:1 1 udp 2130706431 192.168.1.20 50000 typ host";
+    const HOST_V6: &str = "candidate:2 1 udp 2130706431 fd00::20 50002 typ host";
+    const SRFLX: &str =
+        "candidate:3 1 udp 1694498815 203.0.113.7 50004 typ srflx raddr 192.168.1.20 rport 50000";
+    const RELAY: &str =
+        "candidate:4 1 udp 16777215 10.0.0.9 3478 typ relay raddr 203.0.113.7 rport 50004";
+
+    fn priority(candidate: &str) -> u32 {
+        candidate
+            .split_whitespace()
+            .nth(3)
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_default_policy_allows_everything() {
+        let policy = PathPolicy::default();
+        for candidate in [HOST_V4, HOST_V6, SRFLX, RELAY] {
+            assert_eq!(policy.check_candidate(candidate).unwrap(), candidate);
+        }
+        assert!(policy.check_interface("rmnet_data0").is_ok());
+    }
+
+    #[test]
+    fn test_candidates_outside_policy_are_refused() {
+        assert_eq!(
+            PathPolicy::private().check_candidate(RELAY),
+            Err(PathError::Relay)
+        );
+
+        let lan = PathPolicy {
+            lan_only: true,
+            ..PathPolicy::default()
+        };
+        assert!(lan.check_candidate(HOST_V4).is_ok());
+        assert!(lan.check_candidate(HOST_V6).is_ok());
+        // A relay on the local network is still on it
+        assert!(lan.check_candidate(RELAY).is_ok());
+        assert_eq!(
+            lan.check_candidate(SRFLX),
+            Err(PathError::OutsideLan("203.0.113.7".parse().unwrap()))
+        );
+        let mdns = "candidate:5 1 udp 2130706431 1f4712db.local 50006 typ host";
+        assert_eq!(lan.check_candidate(mdns).unwrap(), mdns);
+    }
+
+    #[test]
+    fn test_prefer_ipv6_reranks_candidates() {
+        let policy = PathPolicy {
+            prefer_ipv6: true,
+            ..PathPolicy::default()
+        };
+        let v4 = policy.check_candidate(HOST_V4).unwrap();
+        let v6 = policy.check_candidate(HOST_V6).unwrap();
+        assert!(priority(&v6) > priority(&v4));
+        // Type preference still outranks the address family
+        let srflx = policy.check_candidate(SRFLX).unwrap();
+        assert!(priority(&v4) > priority(&srflx));
+        assert_eq!(priority(&v4) >> 24, priority(HOST_V4) >> 24);
+
+        let relays: Vec<SocketAddr> = ["192.0.2.1:9000", "[2001:db8::1]:9000"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        assert_eq!(policy.relays(&relays), vec![relays[1], relays[0]]);
+        assert!(PathPolicy::private().relays(&relays).is_empty());
+    }
+
+    #[test]
+    fn test_lan_and_cellular_detection() {
+        for ip in [
+            "10.1.2.3",
+            "172.16.0.1",
+            "169.254.1.1",
+            "fe80::1",
+            "::ffff:192.168.0.1",
+        ] {
+            assert!(is_lan(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["8.8.8.8", "2001:db8::1", "100.64.0.1"] {
+            assert!(!is_lan(ip.parse().unwrap()), "{ip}");
+        }
+
+        let policy = PathPolicy {
+            allow_cellular: false,
+            ..PathPolicy::default()
+        };
+        assert_eq!(
+            policy.check_interface("pdp_ip0"),
+            Err(PathError::Cellular("pdp_ip0".to_string()))
+        );
+        assert!(policy.check_interface("wlan0").is_ok());
+        assert!(policy.check_interface("en0").is_ok());
+    }
+}
@@ -0,0 +1,260 @@
+//! Pre-call path probing
+//!
+//! Before placing a call, a short train of padded probes is sent to the
+//! callee over signaling, which rides the same QUIC connection the call's
+//! media would. The callee echoes each one back, and from the echoes a
+//! [`PathProbe`] measures:
+//!
+//! - round-trip time, the median over the probes answered
+//! - loss, the share of probes never answered
+//! - throughput, from how far apart the echoes of back-to-back probes
+//!   arrive; a bottleneck spaces packets out by the time each takes to
+//!   cross it
+//!
+//! The [`PathRecommendation`] applies the same limits as
+//! `NetworkConditions::is_suitable_for_video` and `is_suitable_for_audio`
+//! in the test fixtures, taking one-way latency as half the round trip.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Largest probe padding a peer echoes back, in bytes
+pub const MAX_PROBE_PADDING: usize = 1200;
+
+/// How a path is probed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeConfig {
+    /// Probes sent back to back
+    pub probes: u32,
+    /// Padding carried by each probe, in bytes, at most
+    /// [`MAX_PROBE_PADDING`]
+    pub payload_bytes: usize,
+    /// How long to wait for echoes before counting the rest lost
+    pub timeout: Duration,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        Self {
+            probes: 20,
+            payload_bytes: MAX_PROBE_PADDING,
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// What a probed path can carry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathRecommendation {
+    /// Good enough for a video call
+    Video,
+    /// Good enough for voice only
+    AudioOnly,
+    /// Too slow or lossy for a call
+    Unusable,
+}
+
+impl PathRecommendation {
+    /// Recommend from one-way latency, loss and throughput
+    #[must_use]
+    pub fn classify(latency_ms: u32, loss_percent: f32, throughput_kbps: u32) -> Self {
+        if latency_ms < 150 && loss_percent < 2.0 && throughput_kbps >= 2000 {
+            Self::Video
+        } else if latency_ms < 300 && loss_percent < 10.0 && throughput_kbps >= 100 {
+            Self::AudioOnly
+        } else {
+            Self::Unusable
+        }
+    }
+}
+
+/// Measurements of a probed path
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PathProbeReport {
+    /// Median round-trip time, `None` if no probe was answered
+    pub rtt_ms: Option<u32>,
+    /// Probes never answered, in percent
+    pub loss_percent: f32,
+    /// Throughput the echoes arrived at, 0 with fewer than two
+    pub throughput_kbps: u32,
+    /// What the path can carry
+    pub recommendation: PathRecommendation,
+}
+
+/// Send and echo times of one probe train
+#[derive(Debug, Clone)]
+pub struct PathProbe {
+    probes: u32,
+    payload_bytes: usize,
+    sent: HashMap<u32, Instant>,
+    replies: HashMap<u32, Instant>,
+}
+
+impl PathProbe {
+    /// Start a train of `config.probes` probes
+    #[must_use]
+    pub fn new(config: &ProbeConfig) -> Self {
+        Self {
+            probes: config.probes,
+            payload_bytes: config.payload_bytes.min(MAX_PROBE_PADDING),
+            sent: HashMap::new(),
+            replies: HashMap::new(),
+        }
+    }
+
+    /// Padding each probe carries, in bytes
+    #[must_use]
+    pub fn payload_bytes(&self) -> usize {
+        self.payload_bytes
+    }
+
+    /// Record probe `sequence` sent at `at`
+    pub fn record_sent(&mut self, sequence: u32, at: Instant) {
+        self.sent.insert(sequence, at);
+    }
+
+    /// Record the echo of probe `sequence` arriving at `at`
+    ///
+    /// Echoes of probes never sent, and repeated echoes, are ignored.
+    pub fn record_reply(&mut self, sequence: u32, at: Instant) {
+        if self.sent.contains_key(&sequence) {
+            self.replies.entry(sequence).or_insert(at);
+        }
+    }
+
+    /// Whether every probe was sent and answered
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.replies.len() as u32 >= self.probes
+    }
+
+    /// Measure the path from the echoes received so far
+    #[must_use]
+    pub fn report(&self) -> PathProbeReport {
+        let mut rtts: Vec<Duration> = self
+            .replies
+            .iter()
+            .filter_map(|(sequence, at)| at.checked_duration_since(self.sent[sequence]))
+            .collect();
+        rtts.sort_unstable();
+        let rtt_ms = rtts
+            .get(rtts.len() / 2)
+            .map(|rtt| u32::try_from(rtt.as_millis()).unwrap_or(u32::MAX));
+
+        let answered = self.replies.len().min(self.probes as usize);
+        let loss_percent = if self.probes == 0 {
+            100.0
+        } else {
+            (self.probes as usize - answered) as f32 * 100.0 / self.probes as f32
+        };
+
+        let throughput_kbps = match (self.replies.values().min(), self.replies.values().max()) {
+            (Some(first), Some(last)) if self.replies.len() > 1 => {
+                // Bytes behind the first echo, over the time they took
+                let bytes = (self.replies.len() - 1) * self.payload_bytes;
+                let elapsed = last
+                    .duration_since(*first)
+                    .max(Duration::from_millis(1))
+                    .as_secs_f64();
+                (bytes as f64 * 8.0 / elapsed / 1000.0).round() as u32
+            }
+            _ => 0,
+        };
+
+        let recommendation = match rtt_ms {
+            Some(rtt) => PathRecommendation::classify(rtt / 2, loss_percent, throughput_kbps),
+            None => PathRecommendation::Unusable,
+        };
+        PathProbeReport {
+            rtt_ms,
+            loss_percent,
+            throughput_kbps,
+            recommendation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(probes: u32) -> ProbeConfig {
+        ProbeConfig {
+            probes,
+            payload_bytes: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fast_clean_path_carries_video() {
+        let mut probe = PathProbe::new(&config(10));
+        let start = Instant::now();
+        for sequence in 0..10 {
+            probe.record_sent(sequence, start);
+        }
+        // 40 ms round trip, echoes 1 ms apart: 8 kbit per ms
+        for sequence in 0..10 {
+            let at = start + Duration::from_millis(40 + u64::from(sequence));
+            probe.record_reply(sequence, at);
+        }
+        assert!(probe.is_complete());
+
+        let report = probe.report();
+        assert_eq!(report.rtt_ms, Some(45));
+        assert_eq!(report.loss_percent, 0.0);
+        assert_eq!(report.throughput_kbps, 8000);
+        assert_eq!(report.recommendation, PathRecommendation::Video);
+    }
+
+    #[test]
+    fn test_lossy_or_silent_paths_are_downgraded() {
+        let mut probe = PathProbe::new(&config(10));
+        let start = Instant::now();
+        for sequence in 0..10 {
+            probe.record_sent(sequence, start);
+        }
+        // Half the probes answered, slowly
+        for sequence in 0..5 {
+            let at = start + Duration::from_millis(200 + 10 * u64::from(sequence));
+            probe.record_reply(sequence, at);
+        }
+        probe.record_reply(3, start + Duration::from_secs(1));
+        probe.record_reply(99, start);
+        assert!(!probe.is_complete());
+
+        let report = probe.report();
+        assert_eq!(report.loss_percent, 50.0);
+        assert_eq!(report.recommendation, PathRecommendation::Unusable);
+
+        let silent = PathProbe::new(&config(10)).report();
+        assert_eq!(silent.rtt_ms, None);
+        assert_eq!(silent.throughput_kbps, 0);
+        assert_eq!(silent.recommendation, PathRecommendation::Unusable);
+    }
+
+    #[test]
+    fn test_classify_mirrors_network_conditions() {
+        assert_eq!(
+            PathRecommendation::classify(50, 0.5, 5000),
+            PathRecommendation::Video
+        );
+        assert_eq!(
+            PathRecommendation::classify(200, 5.0, 500),
+            PathRecommendation::AudioOnly
+        );
+        assert_eq!(
+            PathRecommendation::classify(50, 0.5, 1000),
+            PathRecommendation::AudioOnly
+        );
+        assert_eq!(
+            PathRecommendation::classify(300, 1.0, 5000),
+            PathRecommendation::Unusable
+        );
+        assert_eq!(
+            PathRecommendation::classify(50, 0.0, 50),
+            PathRecommendation::Unusable
+        );
+    }
+}
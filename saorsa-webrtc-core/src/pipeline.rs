@@ -0,0 +1,1045 @@
+//! Media pipeline graph
+//!
+//! Outgoing media flows capture → process → encode → packetize. A
+//! [`MediaPipeline`] makes that chain explicit: each [`PipelineStage`]
+//! holds an ordered list of named [`PipelineNode`]s, and features attach
+//! as nodes instead of being special-cased in the track code. Effects such
+//! as [`BlurNode`] and gates such as [`VoiceActivityGate`] go in
+//! [`PipelineStage::Process`], the codec in [`PipelineStage::Encode`], and
+//! taps such as [`RecorderNode`] and [`MeterNode`] in
+//! [`PipelineStage::Packetize`], where they see exactly what goes on the
+//! wire.
+
+use crate::latency::{FrameTiming, LatencyStage};
+use crate::recording::{RecordedFrame, RecordedTrackKind};
+use bytes::Bytes;
+use chrono::Utc;
+use saorsa_webrtc_codecs::{AudioEncoder, AudioFrame, TemporalLayers, VideoEncoder, VideoFrame};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// Name of the node installed by [`MediaPipeline::set_encoder`]
+pub const ENCODER_NODE: &str = "encoder";
+
+/// Name of the [`PushToTalkGate`] in microphone pipelines
+pub const PUSH_TO_TALK_NODE: &str = "push-to-talk";
+
+/// Name of the [`AudioLevelNode`](crate::audio_level::AudioLevelNode) in
+/// microphone pipelines
+pub const AUDIO_LEVEL_NODE: &str = "audio-level";
+
+/// Frames a [`PushToTalkGate`]'s noise gate stays open after speech
+const NOISE_GATE_HANGOVER: u32 = 10;
+
+/// Weight of the previous estimate when measured loss falls
+const LOSS_DECAY: f32 = 0.8;
+
+/// When a video encoder produces recovery points without being asked
+///
+/// Screen shares change rarely and can afford sparse keyframes; lossy links
+/// need frequent recovery points so receivers that lost a reference frame
+/// do not wait long for the next one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyframePolicy {
+    /// Frames between the keyframes the encoder emits on its own; 0 keeps
+    /// the codec's default
+    pub interval_frames: u32,
+    /// Force a keyframe at least this often, however many frames were sent
+    ///
+    /// Unlike `interval_frames` this holds when capture slows or skips
+    /// frames, as screen capture does when nothing on screen changes.
+    pub intra_refresh: Option<Duration>,
+}
+
+impl KeyframePolicy {
+    /// Sparse keyframes for slowly changing desktop content
+    #[must_use]
+    pub fn screen_share() -> Self {
+        Self {
+            interval_frames: 600,
+            intra_refresh: None,
+        }
+    }
+
+    /// A recovery point every two seconds, for links that lose packets
+    #[must_use]
+    pub fn lossy_link() -> Self {
+        Self {
+            interval_frames: 60,
+            intra_refresh: Some(Duration::from_secs(2)),
+        }
+    }
+}
+
+/// Pipeline errors
+#[derive(Error, Debug)]
+pub enum PipelineError {
+    /// A node failed to process a frame
+    #[error("Node {node} failed: {reason}")]
+    NodeFailed {
+        /// Node name
+        node: String,
+        /// Failure reason
+        reason: String,
+    },
+
+    /// A node with this name is already in the pipeline
+    #[error("Duplicate node: {0}")]
+    DuplicateNode(String),
+
+    /// No node with this name is in the pipeline
+    #[error("Node not found: {0}")]
+    NodeNotFound(String),
+}
+
+/// Position of a node in the chain, in processing order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PipelineStage {
+    /// Raw frames from capture: effects, gates, analysis
+    Process,
+    /// Raw frames in, encoded frames out
+    Encode,
+    /// Encoded frames on their way to the packetizer: taps and meters
+    Packetize,
+}
+
+/// Encoded media
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedFrame {
+    /// Media kind
+    pub kind: RecordedTrackKind,
+    /// Encoded payload
+    pub data: Bytes,
+    /// Timestamp of the source frame
+    pub timestamp: u64,
+    /// Temporal layer; 0 is the base layer
+    pub temporal_layer: u8,
+    /// When the frame was captured and encoded
+    pub timing: FrameTiming,
+}
+
+/// A frame moving through the pipeline
+#[derive(Debug, Clone)]
+pub enum MediaPayload {
+    /// Raw RGB video
+    Video(VideoFrame),
+    /// Raw PCM audio
+    Audio(AudioFrame),
+    /// Encoded audio or video
+    Encoded(EncodedFrame),
+}
+
+/// A step in the media pipeline
+pub trait PipelineNode: Send {
+    /// Process one frame; `None` drops it from the rest of the pipeline
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the frame cannot be processed
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError>;
+
+    /// Make the next encoded frame a keyframe, if this node encodes
+    fn request_keyframe(&mut self) {}
+}
+
+struct NodeEntry {
+    name: String,
+    stage: PipelineStage,
+    node: Box<dyn PipelineNode>,
+}
+
+/// Ordered chain of named nodes
+#[derive(Default)]
+pub struct MediaPipeline {
+    nodes: Vec<NodeEntry>,
+}
+
+impl MediaPipeline {
+    /// Create an empty pipeline, which passes frames through unchanged
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node at the end of its stage
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a node with the same name exists
+    pub fn add(
+        &mut self,
+        stage: PipelineStage,
+        name: &str,
+        node: Box<dyn PipelineNode>,
+    ) -> Result<(), PipelineError> {
+        self.check_unique(name)?;
+        let index = self
+            .nodes
+            .iter()
+            .position(|entry| entry.stage > stage)
+            .unwrap_or(self.nodes.len());
+        self.insert_at(index, stage, name, node);
+        Ok(())
+    }
+
+    /// Add a node just before `anchor`, in the same stage
+    ///
+    /// # Errors
+    ///
+    /// Returns error if `anchor` is missing or `name` is taken
+    pub fn insert_before(
+        &mut self,
+        anchor: &str,
+        name: &str,
+        node: Box<dyn PipelineNode>,
+    ) -> Result<(), PipelineError> {
+        self.check_unique(name)?;
+        let index = self.position(anchor)?;
+        let stage = self.nodes[index].stage;
+        self.insert_at(index, stage, name, node);
+        Ok(())
+    }
+
+    /// Add a node just after `anchor`, in the same stage
+    ///
+    /// # Errors
+    ///
+    /// Returns error if `anchor` is missing or `name` is taken
+    pub fn insert_after(
+        &mut self,
+        anchor: &str,
+        name: &str,
+        node: Box<dyn PipelineNode>,
+    ) -> Result<(), PipelineError> {
+        self.check_unique(name)?;
+        let index = self.position(anchor)?;
+        let stage = self.nodes[index].stage;
+        self.insert_at(index + 1, stage, name, node);
+        Ok(())
+    }
+
+    /// Remove a node, returning it
+    pub fn remove(&mut self, name: &str) -> Option<Box<dyn PipelineNode>> {
+        let index = self.position(name).ok()?;
+        Some(self.nodes.remove(index).node)
+    }
+
+    /// Install `encoder` as the video encoder, replacing any previous one
+    pub fn set_encoder(&mut self, encoder: Box<dyn VideoEncoder>) {
+        self.set_encoder_node(EncoderNode::new(encoder));
+    }
+
+    /// Install a configured encoder node, replacing any previous encoder
+    pub fn set_encoder_node(&mut self, node: EncoderNode) {
+        self.nodes
+            .retain(|entry| entry.stage != PipelineStage::Encode);
+        let _ = self.add(PipelineStage::Encode, ENCODER_NODE, Box::new(node));
+    }
+
+    /// Whether a node is in the pipeline
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_ok()
+    }
+
+    /// Node names and stages, in processing order
+    pub fn nodes(&self) -> impl Iterator<Item = (PipelineStage, &str)> {
+        self.nodes
+            .iter()
+            .map(|entry| (entry.stage, entry.name.as_str()))
+    }
+
+    /// Run a frame through every node
+    ///
+    /// Returns `None` if a node dropped the frame. Encoded output is
+    /// stamped as captured when it was pushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a node fails
+    pub fn push(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        let captured_at = Utc::now();
+        let mut payload = payload;
+        for entry in &mut self.nodes {
+            match entry.node.process(payload)? {
+                Some(next) => payload = next,
+                None => return Ok(None),
+            }
+        }
+        if let MediaPayload::Encoded(frame) = &mut payload {
+            if frame.timing.get(LatencyStage::Capture).is_none() {
+                frame.timing.mark_at(LatencyStage::Capture, captured_at);
+            }
+        }
+        Ok(Some(payload))
+    }
+
+    /// Make the next encoded frame a keyframe
+    pub fn request_keyframe(&mut self) {
+        for entry in &mut self.nodes {
+            entry.node.request_keyframe();
+        }
+    }
+
+    fn check_unique(&self, name: &str) -> Result<(), PipelineError> {
+        if self.contains(name) {
+            return Err(PipelineError::DuplicateNode(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn position(&self, name: &str) -> Result<usize, PipelineError> {
+        self.nodes
+            .iter()
+            .position(|entry| entry.name == name)
+            .ok_or_else(|| PipelineError::NodeNotFound(name.to_string()))
+    }
+
+    fn insert_at(
+        &mut self,
+        index: usize,
+        stage: PipelineStage,
+        name: &str,
+        node: Box<dyn PipelineNode>,
+    ) {
+        self.nodes.insert(
+            index,
+            NodeEntry {
+                name: name.to_string(),
+                stage,
+                node,
+            },
+        );
+    }
+}
+
+/// Encodes raw video; other payloads pass through
+///
+/// Frames are spread over the encoder's temporal layers, restarting the
+/// pattern at each requested keyframe.
+pub struct EncoderNode {
+    encoder: Box<dyn VideoEncoder>,
+    layers: TemporalLayers,
+    frame_index: u64,
+    intra_refresh: Option<Duration>,
+    /// When the last keyframe was requested or forced
+    last_keyframe: Option<Instant>,
+}
+
+impl EncoderNode {
+    /// Wrap a video encoder, using every temporal layer it supports
+    #[must_use]
+    pub fn new(encoder: Box<dyn VideoEncoder>) -> Self {
+        let layers = encoder.temporal_layers();
+        Self {
+            encoder,
+            layers,
+            frame_index: 0,
+            intra_refresh: None,
+            last_keyframe: None,
+        }
+    }
+
+    /// Force a keyframe whenever `period` has passed since the last one
+    /// was requested or forced; `None` leaves keyframes to the encoder
+    #[must_use]
+    pub fn with_intra_refresh(mut self, period: Option<Duration>) -> Self {
+        self.intra_refresh = period;
+        self
+    }
+
+    /// Limit the temporal layers used, e.g. to [`TemporalLayers::L1T1`]
+    ///
+    /// Layers the encoder does not support are ignored.
+    #[must_use]
+    pub fn with_temporal_layers(mut self, layers: TemporalLayers) -> Self {
+        if layers.count() <= self.encoder.temporal_layers().count() {
+            self.layers = layers;
+        }
+        self
+    }
+}
+
+impl PipelineNode for EncoderNode {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        let MediaPayload::Video(frame) = payload else {
+            return Ok(Some(payload));
+        };
+        if let Some(period) = self.intra_refresh {
+            // The first frame is a keyframe anyway
+            match self.last_keyframe {
+                Some(last) if last.elapsed() >= period => self.request_keyframe(),
+                Some(_) => {}
+                None => self.last_keyframe = Some(Instant::now()),
+            }
+        }
+        let temporal_layer = self.layers.layer_of(self.frame_index);
+        self.frame_index += 1;
+        let data = self
+            .encoder
+            .encode_with_layer(&frame, temporal_layer)
+            .map_err(|e| PipelineError::NodeFailed {
+                node: ENCODER_NODE.to_string(),
+                reason: e.to_string(),
+            })?;
+        // Encoders with lookahead emit nothing until their queue fills
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let mut timing = FrameTiming::new();
+        timing.mark(LatencyStage::Encode);
+        Ok(Some(MediaPayload::Encoded(EncodedFrame {
+            kind: RecordedTrackKind::Video,
+            data,
+            timestamp: frame.timestamp,
+            temporal_layer,
+            timing,
+        })))
+    }
+
+    fn request_keyframe(&mut self) {
+        self.encoder.request_keyframe();
+        self.frame_index = 0;
+        self.last_keyframe = Some(Instant::now());
+    }
+}
+
+/// Packet loss measured on the network, shared with the audio encoders
+/// that protect against it
+///
+/// Rises as soon as more loss is reported but falls gradually, so loss
+/// protection is not dropped between two bursts.
+#[derive(Debug, Clone, Default)]
+pub struct PacketLossFeedback {
+    /// Smoothed loss percentage, as `f32` bits
+    percent: Arc<AtomicU32>,
+}
+
+impl PacketLossFeedback {
+    /// Create feedback reporting no loss
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the loss measured over the last interval, in percent
+    pub fn report(&self, loss_percent: f32) {
+        if !loss_percent.is_finite() {
+            return;
+        }
+        let measured = loss_percent.clamp(0.0, 100.0);
+        let previous = f32::from_bits(self.percent.load(Ordering::Relaxed));
+        let smoothed = measured.max(LOSS_DECAY * previous + (1.0 - LOSS_DECAY) * measured);
+        self.percent.store(smoothed.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Smoothed loss, in whole percent
+    #[must_use]
+    pub fn percent(&self) -> u8 {
+        f32::from_bits(self.percent.load(Ordering::Relaxed)).round() as u8
+    }
+}
+
+/// Encodes raw audio; other payloads pass through
+pub struct AudioEncoderNode {
+    encoder: Box<dyn AudioEncoder>,
+    loss: Option<PacketLossFeedback>,
+    applied_loss: Option<u8>,
+}
+
+impl AudioEncoderNode {
+    /// Wrap an audio encoder
+    #[must_use]
+    pub fn new(encoder: Box<dyn AudioEncoder>) -> Self {
+        Self {
+            encoder,
+            loss: None,
+            applied_loss: None,
+        }
+    }
+
+    /// Adapt the encoder's loss protection, such as Opus in-band FEC, to
+    /// the loss reported through `loss`
+    #[must_use]
+    pub fn with_loss_feedback(mut self, loss: PacketLossFeedback) -> Self {
+        self.loss = Some(loss);
+        self
+    }
+}
+
+impl PipelineNode for AudioEncoderNode {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        let MediaPayload::Audio(frame) = payload else {
+            return Ok(Some(payload));
+        };
+        if let Some(percent) = self.loss.as_ref().map(PacketLossFeedback::percent) {
+            if self.applied_loss != Some(percent) {
+                self.encoder
+                    .set_packet_loss(percent)
+                    .map_err(|e| PipelineError::NodeFailed {
+                        node: "audio-encoder".to_string(),
+                        reason: e.to_string(),
+                    })?;
+                self.applied_loss = Some(percent);
+            }
+        }
+        let data = self
+            .encoder
+            .encode(&frame)
+            .map_err(|e| PipelineError::NodeFailed {
+                node: "audio-encoder".to_string(),
+                reason: e.to_string(),
+            })?;
+        // DTX: nothing to send for this frame
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let mut timing = FrameTiming::new();
+        timing.mark(LatencyStage::Encode);
+        Ok(Some(MediaPayload::Encoded(EncodedFrame {
+            kind: RecordedTrackKind::Audio,
+            data,
+            timestamp: frame.timestamp,
+            temporal_layer: 0,
+            timing,
+        })))
+    }
+}
+
+/// Box blur over raw RGB video, e.g. for privacy
+pub struct BlurNode {
+    radius: usize,
+}
+
+impl BlurNode {
+    /// Blur with a `(2 * radius + 1)` pixel square kernel
+    #[must_use]
+    pub fn new(radius: usize) -> Self {
+        Self { radius }
+    }
+}
+
+impl PipelineNode for BlurNode {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        let MediaPayload::Video(mut frame) = payload else {
+            return Ok(Some(payload));
+        };
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        if self.radius == 0 || frame.data.len() != width * height * 3 {
+            return Ok(Some(MediaPayload::Video(frame)));
+        }
+        // Separable: horizontal pass, then vertical
+        let horizontal = box_blur(&frame.data, width, height, self.radius, 3, width * 3);
+        frame.data = box_blur(&horizontal, height, width, self.radius, width * 3, 3);
+        Ok(Some(MediaPayload::Video(frame)))
+    }
+}
+
+/// One pass of a box blur along lines of `len` pixels
+///
+/// `step` is the byte distance between neighbouring pixels in a line and
+/// `line_step` the distance between the starts of consecutive lines.
+fn box_blur(
+    data: &[u8],
+    len: usize,
+    lines: usize,
+    radius: usize,
+    step: usize,
+    line_step: usize,
+) -> Vec<u8> {
+    let mut out = vec![0; data.len()];
+    for line in 0..lines {
+        let base = line * line_step;
+        for channel in 0..3 {
+            let at = |i: usize| usize::from(data[base + i * step + channel]);
+            // Running sum over the window clamped to the line
+            let mut sum: usize = (0..=radius.min(len - 1)).map(at).sum();
+            for i in 0..len {
+                let start = i.saturating_sub(radius);
+                let end = (i + radius).min(len - 1);
+                out[base + i * step + channel] = (sum / (end - start + 1)) as u8;
+                if i + radius + 1 < len {
+                    sum += at(i + radius + 1);
+                }
+                if i >= radius {
+                    sum -= at(i - radius);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Drops raw audio frames while nobody is speaking
+///
+/// Frames keep flowing for `hangover` frames after the level falls below
+/// the threshold, so word endings are not clipped.
+pub struct VoiceActivityGate {
+    threshold_dbfs: f32,
+    hangover: u32,
+    quiet_frames: u32,
+}
+
+impl VoiceActivityGate {
+    /// Gate below `threshold_dbfs`, holding open for `hangover` frames
+    #[must_use]
+    pub fn new(threshold_dbfs: f32, hangover: u32) -> Self {
+        Self {
+            threshold_dbfs,
+            hangover,
+            // Closed until the first speech
+            quiet_frames: hangover,
+        }
+    }
+}
+
+impl PipelineNode for VoiceActivityGate {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        let MediaPayload::Audio(frame) = &payload else {
+            return Ok(Some(payload));
+        };
+        if level_dbfs(&frame.data) >= self.threshold_dbfs {
+            self.quiet_frames = 0;
+            return Ok(Some(payload));
+        }
+        if self.quiet_frames < self.hangover {
+            self.quiet_frames += 1;
+            return Ok(Some(payload));
+        }
+        Ok(None)
+    }
+}
+
+/// Whether the microphone may transmit, shared between the UI and the
+/// audio pipelines it controls
+///
+/// With push-to-talk off audio always flows. With it on, audio only flows
+/// while [`set_transmit`](Self::set_transmit) holds the switch down.
+#[derive(Debug, Clone, Default)]
+pub struct TransmitSwitch {
+    push_to_talk: Arc<AtomicBool>,
+    transmit: Arc<AtomicBool>,
+}
+
+impl TransmitSwitch {
+    /// Create a switch with push-to-talk off
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Turn push-to-talk on or off
+    pub fn set_push_to_talk(&self, enabled: bool) {
+        self.push_to_talk.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether push-to-talk is on
+    #[must_use]
+    pub fn is_push_to_talk(&self) -> bool {
+        self.push_to_talk.load(Ordering::Relaxed)
+    }
+
+    /// Hold the talk key down or release it
+    pub fn set_transmit(&self, transmit: bool) {
+        self.transmit.store(transmit, Ordering::Relaxed);
+    }
+
+    /// Whether captured audio is sent right now
+    #[must_use]
+    pub fn is_transmitting(&self) -> bool {
+        !self.is_push_to_talk() || self.transmit.load(Ordering::Relaxed)
+    }
+}
+
+/// Drops raw audio while its [`TransmitSwitch`] is released, and
+/// optionally audio below a noise gate threshold while it is held
+pub struct PushToTalkGate {
+    switch: TransmitSwitch,
+    noise_gate: Option<VoiceActivityGate>,
+}
+
+impl PushToTalkGate {
+    /// Gate audio on `switch`
+    #[must_use]
+    pub fn new(switch: TransmitSwitch) -> Self {
+        Self {
+            switch,
+            noise_gate: None,
+        }
+    }
+
+    /// Also drop audio quieter than `threshold_dbfs`, e.g. keyboard noise
+    #[must_use]
+    pub fn with_noise_gate(mut self, threshold_dbfs: f32) -> Self {
+        self.noise_gate = Some(VoiceActivityGate::new(threshold_dbfs, NOISE_GATE_HANGOVER));
+        self
+    }
+}
+
+impl PipelineNode for PushToTalkGate {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        if !matches!(payload, MediaPayload::Audio(_)) {
+            return Ok(Some(payload));
+        }
+        if !self.switch.is_transmitting() {
+            return Ok(None);
+        }
+        match &mut self.noise_gate {
+            Some(gate) => gate.process(payload),
+            None => Ok(Some(payload)),
+        }
+    }
+}
+
+/// RMS level of 16-bit samples in dBFS
+pub(crate) fn level_dbfs(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return -96.0;
+    }
+    let energy: f64 = samples.iter().map(|&s| f64::from(s).powi(2)).sum();
+    let rms = (energy / samples.len() as f64).sqrt() / 32768.0;
+    if rms <= 0.0 {
+        return -96.0;
+    }
+    (20.0 * rms.log10()).max(-96.0) as f32
+}
+
+/// Copies encoded frames into a recording channel
+///
+/// Frames are stamped with the time since the first one. A full channel
+/// drops frames rather than stalling the call.
+pub struct RecorderNode {
+    sender: mpsc::Sender<RecordedFrame>,
+    started: Option<Instant>,
+}
+
+impl RecorderNode {
+    /// Create a recorder and the receiving end of its channel
+    #[must_use]
+    pub fn new(capacity: usize) -> (Self, mpsc::Receiver<RecordedFrame>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        (
+            Self {
+                sender,
+                started: None,
+            },
+            receiver,
+        )
+    }
+}
+
+impl PipelineNode for RecorderNode {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        if let MediaPayload::Encoded(frame) = &payload {
+            let started = *self.started.get_or_insert_with(Instant::now);
+            let _ = self.sender.try_send(RecordedFrame {
+                kind: frame.kind,
+                timestamp_ms: started.elapsed().as_millis() as u64,
+                payload: frame.data.to_vec(),
+            });
+        }
+        Ok(Some(payload))
+    }
+}
+
+/// Frame and byte counts read from a [`MeterNode`]
+#[derive(Debug, Default)]
+pub struct PipelineMeter {
+    frames: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl PipelineMeter {
+    /// Frames seen
+    #[must_use]
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Encoded bytes seen
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts the frames passing a point in the pipeline
+pub struct MeterNode {
+    meter: Arc<PipelineMeter>,
+}
+
+impl MeterNode {
+    /// Create a meter node and the handle to read it
+    #[must_use]
+    pub fn new() -> (Self, Arc<PipelineMeter>) {
+        let meter = Arc::new(PipelineMeter::default());
+        (
+            Self {
+                meter: Arc::clone(&meter),
+            },
+            meter,
+        )
+    }
+}
+
+impl PipelineNode for MeterNode {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        self.meter.frames.fetch_add(1, Ordering::Relaxed);
+        if let MediaPayload::Encoded(frame) = &payload {
+            self.meter
+                .bytes
+                .fetch_add(frame.data.len() as u64, Ordering::Relaxed);
+        }
+        Ok(Some(payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saorsa_webrtc_codecs::{Channels, SampleRate};
+
+    /// Encoder stand-in that emits the frame timestamp
+    #[derive(Default)]
+    struct StampEncoder {
+        keyframes: Arc<AtomicU64>,
+    }
+
+    impl VideoEncoder for StampEncoder {
+        fn encode(&mut self, frame: &VideoFrame) -> saorsa_webrtc_codecs::Result<Bytes> {
+            Ok(Bytes::copy_from_slice(&frame.timestamp.to_be_bytes()))
+        }
+
+        fn request_keyframe(&mut self) {
+            self.keyframes.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // Every frame stands alone, so any layer pattern is safe to drop
+        fn temporal_layers(&self) -> TemporalLayers {
+            TemporalLayers::L1T3
+        }
+    }
+
+    fn video(width: u32, height: u32, data: Vec<u8>) -> MediaPayload {
+        MediaPayload::Video(VideoFrame {
+            data,
+            width,
+            height,
+            timestamp: 7,
+        })
+    }
+
+    fn audio(amplitude: i16) -> MediaPayload {
+        MediaPayload::Audio(AudioFrame {
+            data: vec![amplitude; 480],
+            sample_rate: SampleRate::Hz48000,
+            channels: Channels::Mono,
+            timestamp: 0,
+        })
+    }
+
+    #[test]
+    fn test_nodes_run_in_stage_order() {
+        let mut pipeline = MediaPipeline::new();
+        let (meter, _) = MeterNode::new();
+        pipeline
+            .add(PipelineStage::Packetize, "meter", Box::new(meter))
+            .unwrap();
+        pipeline.set_encoder(Box::<StampEncoder>::default());
+        pipeline
+            .add(PipelineStage::Process, "blur", Box::new(BlurNode::new(1)))
+            .unwrap();
+        let (recorder, _rx) = RecorderNode::new(4);
+        pipeline
+            .insert_before("meter", "recorder", Box::new(recorder))
+            .unwrap();
+
+        let order: Vec<_> = pipeline.nodes().map(|(_, name)| name).collect();
+        assert_eq!(order, vec!["blur", ENCODER_NODE, "recorder", "meter"]);
+        assert!(matches!(
+            pipeline.add(PipelineStage::Process, "blur", Box::new(BlurNode::new(2))),
+            Err(PipelineError::DuplicateNode(_))
+        ));
+    }
+
+    #[test]
+    fn test_push_encodes_and_taps() {
+        let mut pipeline = MediaPipeline::new();
+        pipeline.set_encoder(Box::<StampEncoder>::default());
+        let (recorder, mut recorded) = RecorderNode::new(4);
+        let (meter, reading) = MeterNode::new();
+        pipeline
+            .add(PipelineStage::Packetize, "recorder", Box::new(recorder))
+            .unwrap();
+        pipeline
+            .add(PipelineStage::Packetize, "meter", Box::new(meter))
+            .unwrap();
+
+        let out = pipeline.push(video(2, 2, vec![0; 12])).unwrap();
+        let Some(MediaPayload::Encoded(frame)) = out else {
+            panic!("expected encoded frame");
+        };
+        assert_eq!(frame.kind, RecordedTrackKind::Video);
+        assert_eq!(frame.data.as_ref(), &7u64.to_be_bytes());
+        let captured = frame.timing.get(LatencyStage::Capture).unwrap();
+        assert!(captured <= frame.timing.get(LatencyStage::Encode).unwrap());
+
+        let copy = recorded.try_recv().unwrap();
+        assert_eq!(copy.payload, frame.data.to_vec());
+        assert_eq!(reading.frames(), 1);
+        assert_eq!(reading.bytes(), 8);
+
+        assert!(pipeline.remove("recorder").is_some());
+        assert!(!pipeline.contains("recorder"));
+    }
+
+    #[test]
+    fn test_blur_smooths_edges() {
+        // Left column black, right column white
+        let data = [[0u8; 3], [255; 3], [0; 3], [255; 3]].concat();
+        let mut blur = BlurNode::new(1);
+        let Some(MediaPayload::Video(frame)) = blur.process(video(2, 2, data)).unwrap() else {
+            panic!("expected video frame");
+        };
+        assert!(frame.data.iter().all(|&value| value == 127));
+    }
+
+    #[test]
+    fn test_voice_gate_holds_over_then_closes() {
+        let mut gate = VoiceActivityGate::new(-40.0, 2);
+        assert!(gate.process(audio(0)).unwrap().is_none());
+        assert!(gate.process(audio(8000)).unwrap().is_some());
+        assert!(gate.process(audio(0)).unwrap().is_some());
+        assert!(gate.process(audio(0)).unwrap().is_some());
+        assert!(gate.process(audio(0)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_push_to_talk_gate_follows_switch() {
+        let switch = TransmitSwitch::new();
+        let mut gate = PushToTalkGate::new(switch.clone()).with_noise_gate(-40.0);
+        assert!(gate.process(audio(8000)).unwrap().is_some());
+
+        switch.set_push_to_talk(true);
+        assert!(!switch.is_transmitting());
+        assert!(gate.process(audio(8000)).unwrap().is_none());
+
+        switch.set_transmit(true);
+        assert!(gate.process(audio(8000)).unwrap().is_some());
+        // Held down, but the noise gate still closes after its hangover
+        for _ in 0..NOISE_GATE_HANGOVER {
+            assert!(gate.process(audio(0)).unwrap().is_some());
+        }
+        assert!(gate.process(audio(0)).unwrap().is_none());
+        assert!(gate.process(video(2, 2, vec![0; 12])).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_keyframe_request_reaches_encoder() {
+        let encoder = StampEncoder::default();
+        let keyframes = Arc::clone(&encoder.keyframes);
+        let mut pipeline = MediaPipeline::new();
+        pipeline.set_encoder(Box::new(encoder));
+        pipeline
+            .add(PipelineStage::Process, "blur", Box::new(BlurNode::new(1)))
+            .unwrap();
+
+        pipeline.request_keyframe();
+        assert_eq!(keyframes.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_encoder_tags_temporal_layers() {
+        let mut node = EncoderNode::new(Box::<StampEncoder>::default());
+        let mut layers = Vec::new();
+        for _ in 0..5 {
+            let Some(MediaPayload::Encoded(frame)) =
+                node.process(video(2, 2, vec![0; 12])).unwrap()
+            else {
+                panic!("expected encoded frame");
+            };
+            layers.push(frame.temporal_layer);
+        }
+        assert_eq!(layers, vec![0, 2, 1, 2, 0]);
+
+        // A keyframe restarts the pattern on the base layer
+        node.process(video(2, 2, vec![0; 12])).unwrap();
+        node.request_keyframe();
+        let Some(MediaPayload::Encoded(frame)) = node.process(video(2, 2, vec![0; 12])).unwrap()
+        else {
+            panic!("expected encoded frame");
+        };
+        assert_eq!(frame.temporal_layer, 0);
+
+        let mut single = EncoderNode::new(Box::<StampEncoder>::default())
+            .with_temporal_layers(TemporalLayers::L1T1);
+        for _ in 0..3 {
+            let out = single.process(video(2, 2, vec![0; 12])).unwrap();
+            assert!(matches!(out, Some(MediaPayload::Encoded(f)) if f.temporal_layer == 0));
+        }
+    }
+
+    #[test]
+    fn test_encoder_intra_refresh_forces_keyframes() {
+        let encoder = StampEncoder::default();
+        let keyframes = Arc::clone(&encoder.keyframes);
+        let mut node = EncoderNode::new(Box::new(encoder)).with_intra_refresh(Some(Duration::ZERO));
+        for _ in 0..3 {
+            node.process(video(2, 2, vec![0; 12])).unwrap();
+        }
+        // Every frame after the first is past the period
+        assert_eq!(keyframes.load(Ordering::Relaxed), 2);
+
+        let encoder = StampEncoder::default();
+        let keyframes = Arc::clone(&encoder.keyframes);
+        let mut node = EncoderNode::new(Box::new(encoder))
+            .with_intra_refresh(KeyframePolicy::lossy_link().intra_refresh);
+        for _ in 0..3 {
+            node.process(video(2, 2, vec![0; 12])).unwrap();
+        }
+        assert_eq!(keyframes.load(Ordering::Relaxed), 0);
+    }
+
+    /// Audio encoder stand-in that records the loss it is told about
+    #[derive(Default)]
+    struct LossEncoder {
+        loss: Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl AudioEncoder for LossEncoder {
+        fn encode(&mut self, _frame: &AudioFrame) -> saorsa_webrtc_codecs::Result<Bytes> {
+            Ok(Bytes::from_static(b"opus"))
+        }
+
+        fn set_bitrate(&mut self, _bitrate: u32) -> saorsa_webrtc_codecs::Result<()> {
+            Ok(())
+        }
+
+        fn set_packet_loss(&mut self, percent: u8) -> saorsa_webrtc_codecs::Result<()> {
+            self.loss.lock().unwrap().push(percent);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_loss_feedback_reaches_audio_encoder() {
+        let encoder = LossEncoder::default();
+        let applied = Arc::clone(&encoder.loss);
+        let feedback = PacketLossFeedback::new();
+        let mut node =
+            AudioEncoderNode::new(Box::new(encoder)).with_loss_feedback(feedback.clone());
+
+        node.process(audio(100)).unwrap();
+        feedback.report(12.0);
+        node.process(audio(100)).unwrap();
+        node.process(audio(100)).unwrap();
+        // Loss falls gradually rather than straight back to zero
+        feedback.report(0.0);
+        assert_eq!(feedback.percent(), 10);
+        node.process(audio(100)).unwrap();
+        assert_eq!(*applied.lock().unwrap(), [0, 12, 10]);
+    }
+}
@@ -0,0 +1,421 @@
+//! Synchronized playback for watch-together
+//!
+//! Each party plays the media itself; only play, pause and seek travel,
+//! over a data channel labelled [`sync_channel_label`]. Every message
+//! carries the media position at the moment it was sent and the sender's
+//! clock at that moment, so the receiver adds the time in flight, converted
+//! with the call's [`ClockEstimate`], and lands on the same position as
+//! the sender.
+//!
+//! A [`SyncSession`] holds the shared state on each side. Local actions
+//! return the message to send; received ones return the [`SyncUpdate`] to
+//! apply to the player. When both sides act at once, the message with the
+//! higher sequence number wins on both, ties broken by send time, so the
+//! two converge without a leader.
+//!
+//! # Wire format
+//!
+//! One [`SyncMessage`] per data channel message, fields little-endian:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 1 | version, [`SYNC_WIRE_VERSION`] |
+//! | 1 | action: 1 play, 2 pause, 3 seek |
+//! | 1 | flags: bit 0 set while playing |
+//! | 4 | sequence number |
+//! | 8 | sent at, microseconds since the Unix epoch, sender's clock |
+//! | 8 | media position when sent, milliseconds |
+//!
+//! Bytes after the last field are ignored so later versions can extend a
+//! message.
+
+use crate::clock::ClockEstimate;
+use crate::error::{CodedError, ErrorCode};
+use crate::wire::{Truncated, WireReader};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Version written in the first byte of every message
+pub const SYNC_WIRE_VERSION: u8 = 1;
+
+/// Label prefix reserved for playback sync data channels
+pub const SYNC_CHANNEL_PREFIX: &str = "saorsa-sync/";
+
+const ACTION_PLAY: u8 = 1;
+const ACTION_PAUSE: u8 = 2;
+const ACTION_SEEK: u8 = 3;
+const FLAG_PLAYING: u8 = 1;
+
+/// Playback sync decoding errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SyncError {
+    /// Message ended before its last field did
+    #[error("Sync message truncated")]
+    Truncated,
+
+    /// Message written by an incompatible version
+    #[error("Unsupported sync version: {0}")]
+    UnsupportedVersion(u8),
+
+    /// Action not known to this version
+    #[error("Unknown sync action: {0}")]
+    UnknownAction(u8),
+
+    /// Send time outside the range of a timestamp
+    #[error("Sync timestamp out of range: {0}")]
+    BadTimestamp(i64),
+}
+
+impl CodedError for SyncError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::MalformedMessage
+    }
+}
+
+impl From<Truncated> for SyncError {
+    fn from(_: Truncated) -> Self {
+        Self::Truncated
+    }
+}
+
+/// What a party did to playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SyncAction {
+    /// Started or resumed playing
+    Play,
+    /// Paused
+    Pause,
+    /// Jumped to another position
+    Seek,
+}
+
+/// One playback sync message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncMessage {
+    /// What the sender did
+    pub action: SyncAction,
+    /// Whether the sender is playing afterwards
+    pub playing: bool,
+    /// Sender's sequence number, one past the highest it had seen
+    pub seq: u32,
+    /// When the message was sent, sender's clock
+    pub sent_at: DateTime<Utc>,
+    /// Media position when the message was sent, in milliseconds
+    pub position_ms: u64,
+}
+
+impl SyncMessage {
+    /// Encode as one data channel message
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(23);
+        buf.push(SYNC_WIRE_VERSION);
+        buf.push(match self.action {
+            SyncAction::Play => ACTION_PLAY,
+            SyncAction::Pause => ACTION_PAUSE,
+            SyncAction::Seek => ACTION_SEEK,
+        });
+        buf.push(if self.playing { FLAG_PLAYING } else { 0 });
+        buf.extend_from_slice(&self.seq.to_le_bytes());
+        buf.extend_from_slice(&self.sent_at.timestamp_micros().to_le_bytes());
+        buf.extend_from_slice(&self.position_ms.to_le_bytes());
+        buf
+    }
+
+    /// Decode one data channel message
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the message is truncated, from another wire
+    /// version, of an unknown action or stamped outside the timestamp range
+    pub fn decode(data: &[u8]) -> Result<Self, SyncError> {
+        let mut reader = WireReader::new(data);
+        let [version, action, flags] = reader.take()?;
+        if version != SYNC_WIRE_VERSION {
+            return Err(SyncError::UnsupportedVersion(version));
+        }
+        let action = match action {
+            ACTION_PLAY => SyncAction::Play,
+            ACTION_PAUSE => SyncAction::Pause,
+            ACTION_SEEK => SyncAction::Seek,
+            action => return Err(SyncError::UnknownAction(action)),
+        };
+        let seq = reader.u32()?;
+        let sent_at_us = reader.i64()?;
+        let position_ms = reader.u64()?;
+        Ok(Self {
+            action,
+            playing: flags & FLAG_PLAYING != 0,
+            seq,
+            sent_at: DateTime::from_timestamp_micros(sent_at_us)
+                .ok_or(SyncError::BadTimestamp(sent_at_us))?,
+            position_ms,
+        })
+    }
+
+    /// Order in which concurrent messages win, the same on every side
+    fn precedence(&self) -> (u32, i64) {
+        (self.seq, self.sent_at.timestamp_micros())
+    }
+}
+
+/// Playback state to apply after a remote action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncUpdate {
+    /// What the remote party did
+    pub action: SyncAction,
+    /// Whether to play
+    pub playing: bool,
+    /// Position to move the player to, in milliseconds, already advanced
+    /// by the time the message spent in flight
+    pub position_ms: u64,
+}
+
+/// Shared playback state of one watch-together session
+#[derive(Debug, Clone)]
+pub struct SyncSession {
+    id: String,
+    playing: bool,
+    /// Media position at `anchor`
+    position_ms: u64,
+    anchor: DateTime<Utc>,
+    /// Highest sequence number seen
+    seq: u32,
+    /// Precedence of the message the state came from
+    applied: Option<(u32, i64)>,
+}
+
+impl SyncSession {
+    /// Create a session paused at the start, identified by `id` on both
+    /// sides
+    #[must_use]
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            playing: false,
+            position_ms: 0,
+            anchor: DateTime::UNIX_EPOCH,
+            seq: 0,
+            applied: None,
+        }
+    }
+
+    /// Session ID
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Whether the session is playing
+    #[must_use]
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Media position at `now`, in milliseconds
+    #[must_use]
+    pub fn position_ms(&self, now: DateTime<Utc>) -> u64 {
+        if !self.playing {
+            return self.position_ms;
+        }
+        let elapsed = (now - self.anchor).num_milliseconds().max(0);
+        self.position_ms.saturating_add(elapsed.unsigned_abs())
+    }
+
+    /// How far a player at `player_position_ms` is ahead of the session at
+    /// `now`, in milliseconds; negative when behind
+    #[must_use]
+    pub fn drift_ms(&self, player_position_ms: u64, now: DateTime<Utc>) -> i64 {
+        let expected = i128::from(self.position_ms(now));
+        let drift = i128::from(player_position_ms) - expected;
+        drift.clamp(i128::from(i64::MIN), i128::from(i64::MAX)) as i64
+    }
+
+    /// Start playing from the current position
+    pub fn play(&mut self, now: DateTime<Utc>) -> SyncMessage {
+        let position_ms = self.position_ms(now);
+        self.local(SyncAction::Play, true, position_ms, now)
+    }
+
+    /// Pause at the current position
+    pub fn pause(&mut self, now: DateTime<Utc>) -> SyncMessage {
+        let position_ms = self.position_ms(now);
+        self.local(SyncAction::Pause, false, position_ms, now)
+    }
+
+    /// Jump to `position_ms`, playing on if playing
+    pub fn seek(&mut self, position_ms: u64, now: DateTime<Utc>) -> SyncMessage {
+        self.local(SyncAction::Seek, self.playing, position_ms, now)
+    }
+
+    /// Apply a message from the remote party received at `now`
+    ///
+    /// `clock` is the call's estimate of the remote clock; without one the
+    /// clocks are taken to agree. Returns `None` for a message overtaken
+    /// by one already applied.
+    pub fn receive(
+        &mut self,
+        message: &SyncMessage,
+        clock: Option<&ClockEstimate>,
+        now: DateTime<Utc>,
+    ) -> Option<SyncUpdate> {
+        self.seq = self.seq.max(message.seq);
+        if self
+            .applied
+            .is_some_and(|applied| message.precedence() <= applied)
+        {
+            return None;
+        }
+        self.applied = Some(message.precedence());
+
+        let sent_at = clock.map_or(message.sent_at, |clock| clock.to_local(message.sent_at));
+        self.playing = message.playing;
+        self.position_ms = message.position_ms;
+        self.anchor = sent_at;
+        let position_ms = self.position_ms(now);
+        self.position_ms = position_ms;
+        self.anchor = now;
+        Some(SyncUpdate {
+            action: message.action,
+            playing: self.playing,
+            position_ms,
+        })
+    }
+
+    fn local(
+        &mut self,
+        action: SyncAction,
+        playing: bool,
+        position_ms: u64,
+        now: DateTime<Utc>,
+    ) -> SyncMessage {
+        self.seq = self.seq.wrapping_add(1);
+        self.playing = playing;
+        self.position_ms = position_ms;
+        self.anchor = now;
+        let message = SyncMessage {
+            action,
+            playing,
+            seq: self.seq,
+            sent_at: now,
+            position_ms,
+        };
+        self.applied = Some(message.precedence());
+        message
+    }
+}
+
+/// Label of the playback sync data channel for session `session_id`
+#[must_use]
+pub fn sync_channel_label(session_id: &str) -> String {
+    format!("{SYNC_CHANNEL_PREFIX}{session_id}")
+}
+
+/// Session a playback sync channel label belongs to, if it is one
+#[must_use]
+pub fn synced_session(label: &str) -> Option<&str> {
+    label
+        .strip_prefix(SYNC_CHANNEL_PREFIX)
+        .filter(|session_id| !session_id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn at(ms: i64) -> DateTime<Utc> {
+        DateTime::UNIX_EPOCH + Duration::milliseconds(1_700_000_000_000 + ms)
+    }
+
+    #[test]
+    fn test_messages_round_trip() {
+        let message = SyncMessage {
+            action: SyncAction::Seek,
+            playing: true,
+            seq: 0x0102_0304,
+            sent_at: at(5),
+            position_ms: 90_000,
+        };
+        let encoded = message.encode();
+        assert_eq!(encoded.len(), 23);
+        assert_eq!(&encoded[..7], [1, 3, 1, 4, 3, 2, 1]);
+        assert_eq!(SyncMessage::decode(&encoded), Ok(message));
+
+        let mut extended = encoded.clone();
+        extended.push(0xaa);
+        assert_eq!(SyncMessage::decode(&extended), Ok(message));
+        assert_eq!(
+            SyncMessage::decode(&encoded[..22]),
+            Err(SyncError::Truncated)
+        );
+        assert_eq!(
+            SyncMessage::decode(&[2, 1, 0]),
+            Err(SyncError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            SyncMessage::decode(&[1, 9, 0]),
+            Err(SyncError::UnknownAction(9))
+        );
+
+        assert_eq!(sync_channel_label("movie"), "saorsa-sync/movie");
+        assert_eq!(synced_session("saorsa-sync/movie"), Some("movie"));
+        assert_eq!(synced_session("saorsa-sync/"), None);
+    }
+
+    #[test]
+    fn test_receiver_lands_on_sender_position() {
+        let mut alice = SyncSession::new("movie");
+        let mut bob = SyncSession::new("movie");
+        // Bob's clock runs 2 s ahead of Alice's
+        let alice_clock = ClockEstimate {
+            offset_us: -2_000_000,
+            rtt_us: 40_000,
+            samples: 1,
+        };
+        let bob_clock = ClockEstimate {
+            offset_us: 2_000_000,
+            ..alice_clock
+        };
+
+        let play = alice.play(at(0));
+        let update = bob.receive(&play, Some(&alice_clock), at(2_030)).unwrap();
+        assert_eq!(update.action, SyncAction::Play);
+        assert!(update.playing);
+        assert_eq!(update.position_ms, 30);
+
+        let seek = bob.seek(60_000, at(3_000));
+        let update = alice.receive(&seek, Some(&bob_clock), at(1_020)).unwrap();
+        assert_eq!(update.position_ms, 60_020);
+        assert!(alice.is_playing());
+        assert_eq!(alice.position_ms(at(1_500)), 60_500);
+        assert_eq!(alice.drift_ms(60_400, at(1_500)), -100);
+
+        let pause = alice.pause(at(2_000));
+        bob.receive(&pause, Some(&alice_clock), at(4_050)).unwrap();
+        assert!(!bob.is_playing());
+        assert_eq!(bob.position_ms(at(9_000)), 61_000);
+    }
+
+    #[test]
+    fn test_concurrent_actions_converge() {
+        let mut alice = SyncSession::new("movie");
+        let mut bob = SyncSession::new("movie");
+        let from_alice = alice.seek(10_000, at(0));
+        let from_bob = bob.seek(20_000, at(5));
+        assert_eq!(from_alice.seq, from_bob.seq);
+
+        // Bob's message was sent later, so it wins on both sides
+        assert!(alice.receive(&from_bob, None, at(10)).is_some());
+        assert!(bob.receive(&from_alice, None, at(10)).is_none());
+        assert_eq!(alice.position_ms(at(10)), 20_000);
+        assert_eq!(bob.position_ms(at(10)), 20_000);
+
+        // The next action outranks both
+        let play = alice.play(at(20));
+        assert_eq!(play.seq, from_bob.seq + 1);
+        assert!(bob.receive(&play, None, at(20)).is_some());
+        assert!(bob.receive(&from_alice, None, at(30)).is_none());
+    }
+}
@@ -0,0 +1,389 @@
+//! Call quality alerting
+//!
+//! A [`QualityPolicy`] is a list of rules evaluated against per-call
+//! metrics. A [`QualityMonitor`] applies the policy over time and only
+//! raises an alert when a rule starts firing, so frontends get one
+//! actionable message per problem rather than one per stats sample.
+
+use crate::types::{CallId, CallQualityMetrics};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Condition checked by a quality rule
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum QualityCondition {
+    /// Packet loss above the given percentage
+    PacketLossAbove(f32),
+    /// Round-trip time above the given milliseconds
+    RttAbove(u32),
+    /// Jitter above the given milliseconds
+    JitterAbove(u32),
+    /// Available bandwidth below the given kilobits per second
+    BandwidthBelow(u32),
+    /// Share of played audio concealed above the given percentage
+    ConcealmentAbove(f32),
+    /// Media is flowing through a relay rather than a direct path
+    RelayPath,
+}
+
+/// How urgent an alert is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AlertSeverity {
+    /// Informational only
+    Info,
+    /// Quality is degraded
+    Warning,
+    /// Call is barely usable
+    Critical,
+}
+
+/// Suggested action for an alert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Remediation {
+    /// Drop video and continue with audio only
+    SwitchToAudioOnly,
+    /// Lower the video resolution
+    ReduceVideoResolution,
+    /// Lower the target bitrate
+    ReduceBitrate,
+    /// Ask the user to check their network
+    CheckNetwork,
+    /// No action needed
+    None,
+}
+
+impl Remediation {
+    /// Short human readable description of the action
+    #[must_use]
+    pub const fn describe(&self) -> &'static str {
+        match self {
+            Self::SwitchToAudioOnly => "switching to audio-only",
+            Self::ReduceVideoResolution => "reducing video resolution",
+            Self::ReduceBitrate => "reducing bitrate",
+            Self::CheckNetwork => "check your network connection",
+            Self::None => "no action needed",
+        }
+    }
+}
+
+/// A single alerting rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityRule {
+    /// Condition that triggers the rule
+    pub condition: QualityCondition,
+    /// Severity of the resulting alert
+    pub severity: AlertSeverity,
+    /// Suggested remediation
+    pub remediation: Remediation,
+}
+
+impl QualityRule {
+    /// Create a new rule
+    #[must_use]
+    pub fn new(
+        condition: QualityCondition,
+        severity: AlertSeverity,
+        remediation: Remediation,
+    ) -> Self {
+        Self {
+            condition,
+            severity,
+            remediation,
+        }
+    }
+
+    /// Describe why this rule fired, or `None` if it does not apply
+    fn check(&self, metrics: &CallQualityMetrics, relayed: bool) -> Option<String> {
+        let action = self.remediation.describe();
+        match self.condition {
+            QualityCondition::PacketLossAbove(limit) if metrics.packet_loss_percent > limit => {
+                Some(format!(
+                    "{} due to {:.1}% packet loss",
+                    action, metrics.packet_loss_percent
+                ))
+            }
+            QualityCondition::RttAbove(limit) if metrics.rtt_ms > limit => {
+                Some(format!("{} due to {} ms round-trip time", action, metrics.rtt_ms))
+            }
+            QualityCondition::JitterAbove(limit) if metrics.jitter_ms > limit => {
+                Some(format!("{} due to {} ms jitter", action, metrics.jitter_ms))
+            }
+            QualityCondition::BandwidthBelow(limit) if metrics.bandwidth_kbps < limit => {
+                Some(format!(
+                    "{} due to {} kbps available bandwidth",
+                    action, metrics.bandwidth_kbps
+                ))
+            }
+            QualityCondition::ConcealmentAbove(limit) => metrics
+                .audio
+                .filter(|audio| audio.concealed_percent > limit)
+                .map(|audio| {
+                    format!(
+                        "{} due to {:.1}% of audio concealed",
+                        action, audio.concealed_percent
+                    )
+                }),
+            QualityCondition::RelayPath if relayed => {
+                Some(format!("relay path in use, {}", action))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Actionable quality alert for a call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityAlert {
+    /// Call the alert refers to
+    pub call_id: CallId,
+    /// Condition that fired
+    pub condition: QualityCondition,
+    /// Severity
+    pub severity: AlertSeverity,
+    /// Suggested remediation
+    pub remediation: Remediation,
+    /// Human readable message
+    pub message: String,
+}
+
+/// Rules-based quality policy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QualityPolicy {
+    /// Rules evaluated in order
+    pub rules: Vec<QualityRule>,
+}
+
+impl QualityPolicy {
+    /// Create a policy from a list of rules
+    #[must_use]
+    pub fn new(rules: Vec<QualityRule>) -> Self {
+        Self { rules }
+    }
+
+    /// Policy that never raises alerts
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Evaluate every rule against a metrics sample
+    #[must_use]
+    pub fn evaluate(
+        &self,
+        call_id: CallId,
+        metrics: &CallQualityMetrics,
+        relayed: bool,
+    ) -> Vec<QualityAlert> {
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                rule.check(metrics, relayed).map(|message| QualityAlert {
+                    call_id,
+                    condition: rule.condition,
+                    severity: rule.severity,
+                    remediation: rule.remediation,
+                    message,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for QualityPolicy {
+    fn default() -> Self {
+        Self::new(vec![
+            QualityRule::new(
+                QualityCondition::PacketLossAbove(8.0),
+                AlertSeverity::Critical,
+                Remediation::SwitchToAudioOnly,
+            ),
+            QualityRule::new(
+                QualityCondition::PacketLossAbove(3.0),
+                AlertSeverity::Warning,
+                Remediation::ReduceBitrate,
+            ),
+            QualityRule::new(
+                QualityCondition::BandwidthBelow(300),
+                AlertSeverity::Warning,
+                Remediation::ReduceVideoResolution,
+            ),
+            QualityRule::new(
+                QualityCondition::RttAbove(300),
+                AlertSeverity::Warning,
+                Remediation::CheckNetwork,
+            ),
+            QualityRule::new(
+                QualityCondition::JitterAbove(50),
+                AlertSeverity::Warning,
+                Remediation::CheckNetwork,
+            ),
+            QualityRule::new(
+                QualityCondition::ConcealmentAbove(5.0),
+                AlertSeverity::Warning,
+                Remediation::ReduceBitrate,
+            ),
+            QualityRule::new(
+                QualityCondition::RelayPath,
+                AlertSeverity::Info,
+                Remediation::None,
+            ),
+        ])
+    }
+}
+
+/// Applies a [`QualityPolicy`] to a stream of samples per call
+///
+/// Each rule raises an alert once when it starts firing and is re-armed
+/// after the condition clears.
+#[derive(Debug, Default)]
+pub struct QualityMonitor {
+    policy: QualityPolicy,
+    active: HashMap<CallId, HashSet<usize>>,
+}
+
+impl QualityMonitor {
+    /// Create a monitor for the given policy
+    #[must_use]
+    pub fn new(policy: QualityPolicy) -> Self {
+        Self {
+            policy,
+            active: HashMap::new(),
+        }
+    }
+
+    /// Get the policy in use
+    #[must_use]
+    pub fn policy(&self) -> &QualityPolicy {
+        &self.policy
+    }
+
+    /// Evaluate samples against a new policy from now on
+    ///
+    /// Alerts are re-armed, so conditions still firing are raised again
+    /// under the new rules.
+    pub fn set_policy(&mut self, policy: QualityPolicy) {
+        self.policy = policy;
+        self.active.clear();
+    }
+
+    /// Feed a metrics sample and return newly raised alerts
+    pub fn observe(
+        &mut self,
+        call_id: CallId,
+        metrics: &CallQualityMetrics,
+        relayed: bool,
+    ) -> Vec<QualityAlert> {
+        let active = self.active.entry(call_id).or_default();
+        let mut raised = Vec::new();
+
+        for (index, rule) in self.policy.rules.iter().enumerate() {
+            match rule.check(metrics, relayed) {
+                Some(message) => {
+                    if active.insert(index) {
+                        raised.push(QualityAlert {
+                            call_id,
+                            condition: rule.condition,
+                            severity: rule.severity,
+                            remediation: rule.remediation,
+                            message,
+                        });
+                    }
+                }
+                None => {
+                    active.remove(&index);
+                }
+            }
+        }
+
+        raised
+    }
+
+    /// Forget state for a call that has ended
+    pub fn remove_call(&mut self, call_id: CallId) {
+        self.active.remove(&call_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jitter::AudioPlayoutStats;
+    use chrono::Utc;
+
+    fn metrics(loss: f32, rtt: u32, bandwidth: u32) -> CallQualityMetrics {
+        CallQualityMetrics {
+            rtt_ms: rtt,
+            packet_loss_percent: loss,
+            jitter_ms: 5,
+            bandwidth_kbps: bandwidth,
+            timestamp: Utc::now(),
+            audio: None,
+        }
+    }
+
+    #[test]
+    fn test_policy_good_metrics_raise_nothing() {
+        let policy = QualityPolicy::default();
+        let alerts = policy.evaluate(CallId::new(), &metrics(0.1, 40, 2000), false);
+        assert!(alerts.is_empty());
+    }
+
+    #[test]
+    fn test_policy_high_loss_suggests_audio_only() {
+        let policy = QualityPolicy::default();
+        let alerts = policy.evaluate(CallId::new(), &metrics(8.5, 40, 2000), false);
+
+        let critical = alerts
+            .iter()
+            .find(|a| a.severity == AlertSeverity::Critical)
+            .unwrap();
+        assert_eq!(critical.remediation, Remediation::SwitchToAudioOnly);
+        assert_eq!(critical.message, "switching to audio-only due to 8.5% packet loss");
+    }
+
+    #[test]
+    fn test_policy_relay_path() {
+        let policy = QualityPolicy::default();
+        let alerts = policy.evaluate(CallId::new(), &metrics(0.0, 40, 2000), true);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].condition, QualityCondition::RelayPath);
+        assert!(alerts[0].message.starts_with("relay path in use"));
+    }
+
+    #[test]
+    fn test_monitor_raises_once_and_rearms() {
+        let mut monitor = QualityMonitor::new(QualityPolicy::new(vec![QualityRule::new(
+            QualityCondition::RttAbove(200),
+            AlertSeverity::Warning,
+            Remediation::CheckNetwork,
+        )]));
+        let call_id = CallId::new();
+
+        assert_eq!(monitor.observe(call_id, &metrics(0.0, 250, 2000), false).len(), 1);
+        assert!(monitor.observe(call_id, &metrics(0.0, 260, 2000), false).is_empty());
+        assert!(monitor.observe(call_id, &metrics(0.0, 50, 2000), false).is_empty());
+        assert_eq!(monitor.observe(call_id, &metrics(0.0, 250, 2000), false).len(), 1);
+    }
+
+    #[test]
+    fn test_policy_concealed_audio() {
+        let policy = QualityPolicy::default();
+        let mut sample = metrics(0.5, 40, 2000);
+        sample.audio = Some(AudioPlayoutStats {
+            concealed_percent: 7.5,
+            ..AudioPlayoutStats::default()
+        });
+        let alerts = policy.evaluate(CallId::new(), &sample, false);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].condition, QualityCondition::ConcealmentAbove(5.0));
+        assert_eq!(alerts[0].message, "reducing bitrate due to 7.5% of audio concealed");
+    }
+
+    #[test]
+    fn test_disabled_policy() {
+        let policy = QualityPolicy::disabled();
+        assert!(policy
+            .evaluate(CallId::new(), &metrics(50.0, 1000, 10), true)
+            .is_empty());
+    }
+}
@@ -2,20 +2,61 @@
 //!
 //! Bridges WebRTC media with QUIC transport for data channels.
 
+use crate::e2ee::{
+    E2eeError, ExportConsent, FrameCipher, FrameEncryptor, MediaCipher, MediaKeyExport,
+};
+use crate::error::{BoxError, CodedError, ErrorCode};
+use crate::fec::{self, FecDecoder, FecEncoder};
+use crate::latency::{FrameTiming, LatencyStage};
+use crate::pipeline::EncodedFrame;
+use crate::transport::TransportError;
 use anyhow::Result;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 use thiserror::Error;
 
 /// Bridge errors
 #[derive(Error, Debug)]
 pub enum BridgeError {
-    /// Configuration error
-    #[error("Configuration error: {0}")]
-    ConfigError(String),
+    /// No transport configured
+    #[error("No transport configured")]
+    NoTransport,
+
+    /// Serialized packet exceeds the configured maximum
+    #[error("Packet size {size} exceeds maximum {max}")]
+    PacketTooLarge {
+        /// Serialized packet size in bytes
+        size: usize,
+        /// Configured maximum in bytes
+        max: usize,
+    },
+
+    /// Packet could not be serialized or deserialized
+    #[error("Invalid RTP packet")]
+    InvalidPacket(#[source] BoxError),
+
+    /// Underlying transport failed
+    #[error("Transport error")]
+    Transport(#[from] TransportError),
+
+    /// Payload could not be sealed or opened
+    #[error("Media encryption failed")]
+    Encryption(#[from] E2eeError),
+}
 
-    /// Stream error
-    #[error("Stream error: {0}")]
-    StreamError(String),
+impl CodedError for BridgeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NoTransport => ErrorCode::TransportNotStarted,
+            Self::PacketTooLarge { .. } => ErrorCode::PacketTooLarge,
+            Self::InvalidPacket(_) => ErrorCode::MalformedMessage,
+            Self::Transport(e) => e.code(),
+            Self::Encryption(e) => e.code(),
+        }
+    }
 }
 
 /// Stream type classification for prioritization
@@ -75,6 +116,16 @@ pub struct RtpPacket {
     pub payload: Vec<u8>,
     /// Stream type classification
     pub stream_type: StreamType,
+    /// Temporal layer of the payload; 0 is the base layer
+    ///
+    /// Receivers on slow links, or relays forwarding to them, can drop
+    /// every packet above some layer without corrupting the video.
+    pub temporal_layer: u8,
+    /// Stage timestamps of the frame, for latency instrumentation
+    ///
+    /// Set on one packet per frame, usually the last; the bridge stamps
+    /// send and receive on packets that carry it.
+    pub timing: Option<FrameTiming>,
 }
 
 impl RtpPacket {
@@ -113,9 +164,25 @@ impl RtpPacket {
             ssrc,
             payload,
             stream_type,
+            temporal_layer: 0,
+            timing: None,
         })
     }
 
+    /// Mark the packet as belonging to temporal layer `layer`
+    #[must_use]
+    pub fn with_temporal_layer(mut self, layer: u8) -> Self {
+        self.temporal_layer = layer;
+        self
+    }
+
+    /// Carry the frame's stage timestamps
+    #[must_use]
+    pub fn with_timing(mut self, timing: FrameTiming) -> Self {
+        self.timing = Some(timing);
+        self
+    }
+
     /// Serialize packet to bytes for QUIC transmission
     ///
     /// # Errors
@@ -170,6 +237,12 @@ pub struct StreamConfig {
     pub max_bitrate_bps: u32,
     /// Maximum latency in milliseconds
     pub max_latency_ms: u32,
+    /// Repair packets sent per media packet; 0 disables FEC
+    ///
+    /// 0.2 sends one repair packet per five media packets, enough to ride
+    /// out the scattered 2-5% loss of mobile and congested links.
+    #[serde(default)]
+    pub fec_ratio: f32,
 }
 
 impl StreamConfig {
@@ -181,6 +254,7 @@ impl StreamConfig {
             target_bitrate_bps: 64_000,
             max_bitrate_bps: 128_000,
             max_latency_ms: 50,
+            fec_ratio: 0.0,
         }
     }
 
@@ -192,6 +266,7 @@ impl StreamConfig {
             target_bitrate_bps: 1_000_000,
             max_bitrate_bps: 2_000_000,
             max_latency_ms: 150,
+            fec_ratio: 0.2,
         }
     }
 
@@ -203,7 +278,65 @@ impl StreamConfig {
             target_bitrate_bps: 500_000,
             max_bitrate_bps: 1_500_000,
             max_latency_ms: 200,
+            fec_ratio: 0.1,
+        }
+    }
+}
+
+/// Frame bytes carried per packet by a [`Packetizer`]
+///
+/// Leaves room in a 1200-byte datagram for the serialized header, the
+/// frame timing and payload sealing.
+pub const MAX_FRAGMENT_SIZE: usize = 1000;
+
+/// Splits the encoded frames of one stream into RTP packets
+#[derive(Debug, Clone)]
+pub struct Packetizer {
+    payload_type: u8,
+    ssrc: u32,
+    stream_type: StreamType,
+    sequence_number: u16,
+}
+
+impl Packetizer {
+    /// Packetize a stream, starting at a random sequence number
+    #[must_use]
+    pub fn new(payload_type: u8, ssrc: u32, stream_type: StreamType) -> Self {
+        Self {
+            payload_type,
+            ssrc,
+            stream_type,
+            sequence_number: rand::random(),
+        }
+    }
+
+    /// Split a frame into consecutive packets, marking the last one
+    ///
+    /// An empty frame yields no packets. Stamp audio with
+    /// [`AudioClock::rtp_timestamp`](crate::audio_clock::AudioClock::rtp_timestamp)
+    /// of its capture time.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a packet cannot be built
+    pub fn packetize(&mut self, frame: &[u8], timestamp: u32) -> Result<Vec<RtpPacket>, BridgeError> {
+        let count = frame.len().div_ceil(MAX_FRAGMENT_SIZE);
+        let mut packets = Vec::with_capacity(count);
+        for (index, fragment) in frame.chunks(MAX_FRAGMENT_SIZE).enumerate() {
+            let mut packet = RtpPacket::new(
+                self.payload_type,
+                self.sequence_number,
+                timestamp,
+                self.ssrc,
+                fragment.to_vec(),
+                self.stream_type,
+            )
+            .map_err(|e| BridgeError::InvalidPacket(e.into()))?;
+            packet.marker = index + 1 == count;
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            packets.push(packet);
         }
+        Ok(packets)
     }
 }
 
@@ -228,6 +361,11 @@ impl Default for QuicBridgeConfig {
 pub struct WebRtcQuicBridge {
     config: QuicBridgeConfig,
     transport: Option<crate::transport::AntQuicTransport>,
+    fec_encoders: Mutex<HashMap<u32, FecEncoder>>,
+    fec_decoders: Mutex<HashMap<u32, FecDecoder>>,
+    ready: Mutex<VecDeque<RtpPacket>>,
+    encryption: Mutex<Option<Arc<FrameCipher>>>,
+    frame_encryption: Mutex<Option<Arc<FrameEncryptor>>>,
 }
 
 impl WebRtcQuicBridge {
@@ -237,6 +375,11 @@ impl WebRtcQuicBridge {
         Self {
             config,
             transport: None,
+            fec_encoders: Mutex::new(HashMap::new()),
+            fec_decoders: Mutex::new(HashMap::new()),
+            ready: Mutex::new(VecDeque::new()),
+            encryption: Mutex::new(None),
+            frame_encryption: Mutex::new(None),
         }
     }
 
@@ -244,36 +387,190 @@ impl WebRtcQuicBridge {
     #[must_use]
     pub fn with_transport(config: QuicBridgeConfig, transport: crate::transport::AntQuicTransport) -> Self {
         Self {
-            config,
             transport: Some(transport),
+            ..Self::new(config)
+        }
+    }
+
+    /// Apply a stream's settings to packets sent with SSRC `ssrc`
+    ///
+    /// Enables FEC for the stream when `config.fec_ratio` is above zero.
+    pub fn configure_stream(&self, ssrc: u32, config: &StreamConfig) {
+        let mut encoders = self.fec_encoders.lock();
+        match FecEncoder::from_ratio(config.fec_ratio) {
+            Some(encoder) => {
+                encoders.insert(ssrc, encoder);
+            }
+            None => {
+                encoders.remove(&ssrc);
+            }
+        }
+    }
+
+    /// Seal every media payload sent and open every one received
+    ///
+    /// Use the cipher from the call's negotiated codecs; `None` turns
+    /// encryption off. Payloads are sealed before FEC, so repair packets
+    /// protect ciphertext and are never sealed themselves.
+    pub fn set_encryption(&self, cipher: Option<FrameCipher>) {
+        *self.encryption.lock() = cipher.map(Arc::new);
+    }
+
+    /// Cipher sealing media payloads, if encryption is on
+    #[must_use]
+    pub fn media_cipher(&self) -> Option<MediaCipher> {
+        self.encryption.lock().as_ref().map(|cipher| cipher.cipher())
+    }
+
+    /// Encrypt every frame sent with [`Self::send_frame`] end to end
+    ///
+    /// Frames are encrypted whole before packetization, so a relay that
+    /// forwards the QUIC traffic never sees media, even one trusted with
+    /// the packet keys of [`Self::set_encryption`]. `None` turns frame
+    /// encryption off.
+    pub fn set_frame_encryption(&self, encryptor: Option<FrameEncryptor>) {
+        *self.frame_encryption.lock() = encryptor.map(Arc::new);
+    }
+
+    /// Cipher encrypting whole frames, if frame encryption is on
+    #[must_use]
+    pub fn frame_cipher(&self) -> Option<MediaCipher> {
+        self.frame_encryption
+            .lock()
+            .as_ref()
+            .map(|encryptor| encryptor.cipher())
+    }
+
+    /// Export this call's media keys for lawful local recording
+    ///
+    /// Only call this on an explicit user action; `consent` records it.
+    /// Encryption carries on unchanged for the call and everyone else.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call is not encrypted
+    pub fn export_media_keys(&self, consent: ExportConsent) -> Result<MediaKeyExport, BridgeError> {
+        let packet_cipher = self.encryption.lock().clone();
+        let frame_encryptor = self.frame_encryption.lock().clone();
+        Ok(MediaKeyExport::new(
+            consent,
+            packet_cipher.as_deref(),
+            frame_encryptor.as_deref(),
+        )?)
+    }
+
+    /// Send an encoded frame as one or more RTP packets
+    ///
+    /// The frame is encrypted first when frame encryption is on, and its
+    /// timing rides on the last packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if encryption or sending fails
+    pub async fn send_frame(
+        &self,
+        packetizer: &mut Packetizer,
+        frame: &EncodedFrame,
+    ) -> Result<(), BridgeError> {
+        let encryptor = self.frame_encryption.lock().clone();
+        let data = match encryptor {
+            Some(encryptor) => Cow::Owned(encryptor.encrypt(&frame.data)?),
+            None => Cow::Borrowed(frame.data.as_ref()),
+        };
+        // RTP timestamps are 32 bits and wrap
+        let mut packets = packetizer.packetize(&data, frame.timestamp as u32)?;
+        if let Some(last) = packets.last_mut() {
+            last.timing = Some(frame.timing);
+        }
+        for packet in packets {
+            self.send_rtp_packet(&packet.with_temporal_layer(frame.temporal_layer))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Decrypt a frame reassembled from received packets
+    ///
+    /// Returns the frame unchanged when frame encryption is off.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the frame fails to decrypt
+    pub fn decrypt_frame(&self, frame: &[u8]) -> Result<Vec<u8>, BridgeError> {
+        let encryptor = self.frame_encryption.lock().clone();
+        match encryptor {
+            Some(encryptor) => Ok(encryptor.decrypt(frame)?),
+            None => Ok(frame.to_vec()),
         }
     }
 
+    /// Packets rebuilt from repair packets so far
+    #[must_use]
+    pub fn fec_recovered(&self) -> u64 {
+        self.fec_decoders
+            .lock()
+            .values()
+            .map(FecDecoder::recovered)
+            .sum()
+    }
+
     /// Send RTP packet over QUIC
     ///
+    /// Also sends the repair packet of the stream's protection group once
+    /// the group is complete.
+    ///
     /// # Errors
     ///
     /// Returns error if sending fails
     pub async fn send_rtp_packet(&self, packet: &RtpPacket) -> Result<(), BridgeError> {
         let transport = self.transport.as_ref()
-            .ok_or_else(|| BridgeError::ConfigError("No transport configured".to_string()))?;
+            .ok_or(BridgeError::NoTransport)?;
+
+        let cipher = self.encryption.lock().clone();
+        let mut packet = match cipher {
+            Some(cipher) => Cow::Owned(cipher.seal(packet)?),
+            None => Cow::Borrowed(packet),
+        };
+        if packet.timing.is_some() {
+            if let Some(timing) = &mut packet.to_mut().timing {
+                timing.mark(LatencyStage::Send);
+            }
+        }
+        let packet = packet.as_ref();
+
+        self.send_one(transport, packet).await?;
+
+        let repair = self
+            .fec_encoders
+            .lock()
+            .get_mut(&packet.ssrc)
+            .and_then(|encoder| encoder.protect(packet));
+        if let Some(repair) = repair {
+            self.send_one(transport, &repair).await?;
+        }
+
+        Ok(())
+    }
 
+    async fn send_one(
+        &self,
+        transport: &crate::transport::AntQuicTransport,
+        packet: &RtpPacket,
+    ) -> Result<(), BridgeError> {
         // Serialize the packet
         let data = packet.to_bytes()
-            .map_err(|e| BridgeError::StreamError(format!("Failed to serialize packet: {}", e)))?;
+            .map_err(|e| BridgeError::InvalidPacket(e.into()))?;
 
         // Validate size
         if data.len() > self.config.max_packet_size {
-            return Err(BridgeError::StreamError(format!(
-                "Packet size {} exceeds maximum {}",
-                data.len(),
-                self.config.max_packet_size
-            )));
+            return Err(BridgeError::PacketTooLarge {
+                size: data.len(),
+                max: self.config.max_packet_size,
+            });
         }
 
         // Send over QUIC stream
-        transport.send_bytes(&data).await
-            .map_err(|e| BridgeError::StreamError(format!("Failed to send packet: {}", e)))?;
+        transport.send_bytes(&data).await?;
         
         tracing::debug!("Sent RTP packet of size {} bytes", data.len());
         
@@ -282,24 +579,56 @@ impl WebRtcQuicBridge {
 
     /// Receive RTP packet from QUIC
     ///
+    /// Repair packets are consumed here; media packets they rebuild are
+    /// returned in sequence with the rest.
+    ///
     /// # Errors
     ///
-    /// Returns error if receiving fails
+    /// Returns error if receiving fails, or if encryption is on and the
+    /// payload fails to open
     pub async fn receive_rtp_packet(&self) -> Result<RtpPacket, BridgeError> {
-        let transport = self.transport.as_ref()
-            .ok_or_else(|| BridgeError::ConfigError("No transport configured".to_string()))?;
-
-        // Receive from QUIC stream
-        let data = transport.receive_bytes().await
-            .map_err(|e| BridgeError::StreamError(format!("Failed to receive: {}", e)))?;
-
-        // Deserialize the packet (this also validates size limits)
-        let packet = RtpPacket::from_bytes(&data)
-            .map_err(|e| BridgeError::StreamError(format!("Failed to deserialize packet: {}", e)))?;
-
-        tracing::debug!("Received RTP packet of size {} bytes", data.len());
+        let mut packet = self.receive_sealed().await?;
+        if let Some(timing) = &mut packet.timing {
+            timing.mark(LatencyStage::Receive);
+        }
+        let cipher = self.encryption.lock().clone();
+        match cipher {
+            Some(cipher) => Ok(cipher.open(&packet)?),
+            None => Ok(packet),
+        }
+    }
 
-        Ok(packet)
+    /// Next media packet as sent, after FEC recovery
+    async fn receive_sealed(&self) -> Result<RtpPacket, BridgeError> {
+        let transport = self.transport.as_ref()
+            .ok_or(BridgeError::NoTransport)?;
+
+        loop {
+            if let Some(packet) = self.ready.lock().pop_front() {
+                return Ok(packet);
+            }
+
+            // Receive from QUIC stream
+            let data = transport.receive_bytes().await?;
+
+            // Deserialize the packet (this also validates size limits)
+            let packet = RtpPacket::from_bytes(&data)
+                .map_err(|e| BridgeError::InvalidPacket(e.into()))?;
+
+            tracing::debug!("Received RTP packet of size {} bytes", data.len());
+
+            // Streams only go through a decoder once their sender protects them
+            let mut decoders = self.fec_decoders.lock();
+            let decoder = if fec::is_repair(&packet) {
+                Some(decoders.entry(packet.ssrc).or_default())
+            } else {
+                decoders.get_mut(&packet.ssrc)
+            };
+            match decoder {
+                Some(decoder) => self.ready.lock().extend(decoder.receive(packet)),
+                None => return Ok(packet),
+            }
+        }
     }
 
     /// Bridge WebRTC track to QUIC stream
@@ -340,7 +669,117 @@ mod tests {
         let result = bridge.receive_rtp_packet().await;
         // Should fail without transport configured
         assert!(result.is_err());
-        assert!(matches!(result, Err(BridgeError::ConfigError(_))));
+        assert!(matches!(result, Err(BridgeError::NoTransport)));
+    }
+
+    #[test]
+    fn test_rtp_packet_temporal_layer_roundtrip() {
+        let packet = RtpPacket::new(96, 1, 90, 7, vec![0; 8], StreamType::Video)
+            .expect("Failed to create packet")
+            .with_temporal_layer(2);
+
+        let decoded = RtpPacket::from_bytes(&packet.to_bytes().expect("Failed to serialize"))
+            .expect("Failed to deserialize");
+        assert_eq!(decoded.temporal_layer, 2);
+    }
+
+    #[test]
+    fn test_rtp_packet_timing_roundtrip() {
+        let mut timing = FrameTiming::new();
+        timing.mark(LatencyStage::Capture);
+        timing.mark(LatencyStage::Encode);
+        let packet = RtpPacket::new(96, 1, 90, 7, vec![0; 8], StreamType::Video)
+            .expect("Failed to create packet")
+            .with_timing(timing);
+
+        let decoded = RtpPacket::from_bytes(&packet.to_bytes().expect("Failed to serialize"))
+            .expect("Failed to deserialize");
+        assert_eq!(decoded.timing, Some(timing));
+    }
+
+    #[test]
+    fn test_packetizer_splits_and_marks_frames() {
+        let mut packetizer = Packetizer::new(96, 7, StreamType::Video);
+        let frame = vec![3u8; 2 * MAX_FRAGMENT_SIZE + 10];
+        let packets = packetizer.packetize(&frame, 9000).expect("Failed to packetize");
+
+        assert_eq!(packets.len(), 3);
+        assert_eq!(
+            packets[1].sequence_number,
+            packets[0].sequence_number.wrapping_add(1)
+        );
+        assert!(packets.iter().all(|p| p.timestamp == 9000 && p.ssrc == 7));
+        assert_eq!(packets.iter().filter(|p| p.marker).count(), 1);
+        assert!(packets[2].marker);
+        assert!(packetizer.packetize(&[], 9000).expect("Failed to packetize").is_empty());
+
+        // A full fragment still fits a datagram once sealed and timed
+        let key = crate::e2ee::MediaKey::generate();
+        let sealed = FrameCipher::new(MediaCipher::ChaCha20Poly1305, &key, "alice", "bob")
+            .seal(&packets[0].clone().with_timing(FrameTiming::new()))
+            .expect("Failed to seal");
+        assert!(sealed.to_bytes().expect("Failed to serialize").len() <= 1200);
+    }
+
+    #[test]
+    fn test_frame_encryption_before_packetization() {
+        use crate::e2ee::{MediaKey, StaticKeyProvider};
+
+        let bridge = WebRtcQuicBridge::default();
+        let key = MediaKey::generate();
+        let encryptor = |local, remote| {
+            FrameEncryptor::new(
+                MediaCipher::ChaCha20Poly1305,
+                Arc::new(StaticKeyProvider::new(key.clone())),
+                local,
+                remote,
+            )
+        };
+        bridge.set_frame_encryption(Some(encryptor("bob", "alice")));
+        assert_eq!(bridge.frame_cipher(), Some(MediaCipher::ChaCha20Poly1305));
+
+        let frame = vec![5u8; 1500];
+        let encrypted = encryptor("alice", "bob").encrypt(&frame).expect("Failed to encrypt");
+        let mut packetizer = Packetizer::new(96, 7, StreamType::Video);
+        let reassembled: Vec<u8> = packetizer
+            .packetize(&encrypted, 0)
+            .expect("Failed to packetize")
+            .into_iter()
+            .flat_map(|p| p.payload)
+            .collect();
+        assert_eq!(bridge.decrypt_frame(&reassembled).expect("Failed to decrypt"), frame);
+
+        bridge.set_frame_encryption(None);
+        assert_eq!(bridge.decrypt_frame(&frame).expect("Failed to pass through"), frame);
+    }
+
+    #[test]
+    fn test_configure_stream_toggles_fec() {
+        let bridge = WebRtcQuicBridge::default();
+        bridge.configure_stream(1, &StreamConfig::video());
+        bridge.configure_stream(2, &StreamConfig::audio());
+        {
+            let encoders = bridge.fec_encoders.lock();
+            assert_eq!(encoders.get(&1).map(FecEncoder::group_size), Some(5));
+            assert!(!encoders.contains_key(&2));
+        }
+
+        bridge.configure_stream(1, &StreamConfig { fec_ratio: 0.0, ..StreamConfig::video() });
+        assert!(bridge.fec_encoders.lock().is_empty());
+        assert_eq!(bridge.fec_recovered(), 0);
+    }
+
+    #[test]
+    fn test_set_encryption_reports_cipher() {
+        let bridge = WebRtcQuicBridge::default();
+        assert_eq!(bridge.media_cipher(), None);
+
+        let key = crate::e2ee::MediaKey::generate();
+        bridge.set_encryption(Some(FrameCipher::new(MediaCipher::Aes256Gcm, &key, "alice", "bob")));
+        assert_eq!(bridge.media_cipher(), Some(MediaCipher::Aes256Gcm));
+
+        bridge.set_encryption(None);
+        assert_eq!(bridge.media_cipher(), None);
     }
 
     #[tokio::test]
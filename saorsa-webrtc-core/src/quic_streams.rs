@@ -1,24 +1,38 @@
 //! QUIC media stream management with QoS
 //!
 //! Manages QUIC streams for audio, video, and screen sharing with
-//! appropriate quality-of-service parameters.
+//! appropriate quality-of-service parameters. A [`QoSPolicy`] maps each
+//! stream type to its parameters; applications can override the built-in
+//! values and add stream types of their own.
 
+use crate::error::{CodedError, ErrorCode};
+use crate::quic_bridge::StreamType;
+use std::collections::HashMap;
 use thiserror::Error;
 
 /// Stream errors
 #[derive(Error, Debug)]
 pub enum StreamError {
-    /// Configuration error
-    #[error("Configuration error: {0}")]
-    ConfigError(String),
+    /// No stream with this ID
+    #[error("Stream not found: {0}")]
+    NotFound(u64),
 
-    /// Stream error
-    #[error("Stream operation error: {0}")]
-    OperationError(String),
+    /// Operation is not implemented yet
+    #[error("Not implemented: {0}")]
+    NotImplemented(&'static str),
+}
+
+impl CodedError for StreamError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(_) => ErrorCode::StreamNotFound,
+            Self::NotImplemented(_) => ErrorCode::NotImplemented,
+        }
+    }
 }
 
 /// QoS parameters for media streams
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct QoSParams {
     /// Target latency in milliseconds
     pub target_latency_ms: u32,
@@ -56,7 +70,7 @@ impl QoSParams {
 }
 
 /// QUIC stream type for media
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MediaStreamType {
     /// Audio stream
     Audio,
@@ -66,6 +80,79 @@ pub enum MediaStreamType {
     ScreenShare,
     /// Data channel
     DataChannel,
+    /// Application-defined stream, e.g. telemetry or a second camera
+    Custom(String),
+}
+
+impl From<StreamType> for MediaStreamType {
+    fn from(stream_type: StreamType) -> Self {
+        match stream_type {
+            StreamType::Audio => Self::Audio,
+            StreamType::Video => Self::Video,
+            StreamType::ScreenShare => Self::ScreenShare,
+            StreamType::Data => Self::DataChannel,
+        }
+    }
+}
+
+/// QoS parameters for each stream type
+///
+/// Audio, video and screen share default to [`QoSParams::audio`],
+/// [`QoSParams::video`] and [`QoSParams::screen_share`]. Data channels and
+/// custom types with no entry of their own use the fallback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QoSPolicy {
+    overrides: HashMap<MediaStreamType, QoSParams>,
+    fallback: QoSParams,
+}
+
+impl Default for QoSPolicy {
+    fn default() -> Self {
+        Self::new(QoSParams::audio())
+    }
+}
+
+impl QoSPolicy {
+    /// Built-in parameters, with `fallback` for data and unknown streams
+    #[must_use]
+    pub fn new(fallback: QoSParams) -> Self {
+        Self {
+            overrides: HashMap::new(),
+            fallback,
+        }
+    }
+
+    /// Use `params` for `stream_type` instead of the built-in values
+    #[must_use]
+    pub fn with(mut self, stream_type: MediaStreamType, params: QoSParams) -> Self {
+        self.set(stream_type, params);
+        self
+    }
+
+    /// Define a custom stream type with its own parameters
+    #[must_use]
+    pub fn with_custom(self, name: impl Into<String>, params: QoSParams) -> Self {
+        self.with(MediaStreamType::Custom(name.into()), params)
+    }
+
+    /// Use `params` for `stream_type`, replacing any earlier value
+    pub fn set(&mut self, stream_type: MediaStreamType, params: QoSParams) {
+        self.overrides.insert(stream_type, params);
+    }
+
+    /// Parameters for a stream of `stream_type`
+    #[must_use]
+    pub fn params(&self, stream_type: &MediaStreamType) -> QoSParams {
+        if let Some(params) = self.overrides.get(stream_type) {
+            return *params;
+        }
+        match stream_type {
+            MediaStreamType::Audio => QoSParams::audio(),
+            MediaStreamType::Video => QoSParams::video(),
+            MediaStreamType::ScreenShare => QoSParams::screen_share(),
+            MediaStreamType::DataChannel | MediaStreamType::Custom(_) => self.fallback,
+        }
+    }
 }
 
 /// Active QUIC media stream
@@ -80,20 +167,37 @@ pub struct QuicMediaStream {
 
 /// QUIC media stream manager
 pub struct QuicMediaStreamManager {
-    streams: std::collections::HashMap<u64, QuicMediaStream>,
+    streams: HashMap<u64, QuicMediaStream>,
     next_stream_id: u64,
+    policy: QoSPolicy,
 }
 
 impl QuicMediaStreamManager {
     /// Create new stream manager with QoS parameters
+    ///
+    /// `qos` applies to data channels and custom streams; the other types
+    /// use their built-in parameters.
+    #[must_use]
+    pub fn new(qos: QoSParams) -> Self {
+        Self::with_policy(QoSPolicy::new(qos))
+    }
+
+    /// Create a stream manager that takes QoS parameters from `policy`
     #[must_use]
-    pub fn new(_qos: QoSParams) -> Self {
+    pub fn with_policy(policy: QoSPolicy) -> Self {
         Self {
-            streams: std::collections::HashMap::new(),
+            streams: HashMap::new(),
             next_stream_id: 0,
+            policy,
         }
     }
 
+    /// QoS parameters applied to new streams
+    #[must_use]
+    pub fn policy(&self) -> &QoSPolicy {
+        &self.policy
+    }
+
     /// Create a new media stream
     ///
     /// # Errors
@@ -103,12 +207,7 @@ impl QuicMediaStreamManager {
         let stream_id = self.next_stream_id;
         self.next_stream_id += 1;
 
-        let qos_params = match stream_type {
-            MediaStreamType::Audio => QoSParams::audio(),
-            MediaStreamType::Video => QoSParams::video(),
-            MediaStreamType::ScreenShare => QoSParams::screen_share(),
-            MediaStreamType::DataChannel => QoSParams::audio(), // Default QoS for data
-        };
+        let qos_params = self.policy.params(&stream_type);
 
         let stream = QuicMediaStream {
             stream_type,
@@ -135,7 +234,7 @@ impl QuicMediaStreamManager {
         if self.streams.remove(&stream_id).is_some() {
             Ok(())
         } else {
-            Err(StreamError::OperationError("Stream not found".to_string()))
+            Err(StreamError::NotFound(stream_id))
         }
     }
 
@@ -149,7 +248,7 @@ impl QuicMediaStreamManager {
             // TODO: Implement actual QUIC stream sending
             Ok(())
         } else {
-            Err(StreamError::OperationError("Stream not found".to_string()))
+            Err(StreamError::NotFound(stream_id))
         }
     }
 
@@ -161,9 +260,9 @@ impl QuicMediaStreamManager {
     pub async fn receive_data(&self, stream_id: u64) -> Result<Vec<u8>, StreamError> {
         if self.streams.contains_key(&stream_id) {
             // TODO: Implement actual QUIC stream receiving
-            Err(StreamError::OperationError("Not implemented".to_string()))
+            Err(StreamError::NotImplemented("stream receive"))
         } else {
-            Err(StreamError::OperationError("Stream not found".to_string()))
+            Err(StreamError::NotFound(stream_id))
         }
     }
 
@@ -222,7 +321,7 @@ mod tests {
         let mut manager = QuicMediaStreamManager::new(QoSParams::audio());
 
         let result = manager.close_stream(999);
-        assert!(matches!(result, Err(StreamError::OperationError(_))));
+        assert!(matches!(result, Err(StreamError::NotFound(999))));
     }
 
     #[tokio::test]
@@ -242,7 +341,7 @@ mod tests {
 
         let data = vec![1, 2, 3, 4];
         let result = manager.send_data(999, &data).await;
-        assert!(matches!(result, Err(StreamError::OperationError(_))));
+        assert!(matches!(result, Err(StreamError::NotFound(999))));
     }
 
     #[tokio::test]
@@ -252,7 +351,7 @@ mod tests {
         let stream_id = manager.create_stream(MediaStreamType::Audio).unwrap();
 
         let result = manager.receive_data(stream_id).await;
-        assert!(matches!(result, Err(StreamError::OperationError(_))));
+        assert!(matches!(result, Err(StreamError::NotImplemented(_))));
     }
 
     #[test]
@@ -282,4 +381,47 @@ mod tests {
         assert_eq!(screen.target_latency_ms, 200);
         assert_eq!(screen.priority, 3);
     }
+
+    #[test]
+    fn test_qos_policy_overrides_and_custom_types() {
+        let low_latency_screen = QoSParams {
+            target_latency_ms: 80,
+            priority: 8,
+        };
+        let telemetry = QoSParams {
+            target_latency_ms: 1000,
+            priority: 1,
+        };
+        let policy = QoSPolicy::default()
+            .with(MediaStreamType::ScreenShare, low_latency_screen)
+            .with_custom("telemetry", telemetry);
+        let mut manager = QuicMediaStreamManager::with_policy(policy);
+
+        let screen = manager.create_stream(MediaStreamType::ScreenShare).unwrap();
+        let custom = manager
+            .create_stream(MediaStreamType::Custom("telemetry".to_string()))
+            .unwrap();
+        let unknown = manager
+            .create_stream(MediaStreamType::Custom("other".to_string()))
+            .unwrap();
+        let video = manager.create_stream(MediaStreamType::Video).unwrap();
+
+        assert_eq!(
+            manager.get_stream(screen).unwrap().qos_params,
+            low_latency_screen
+        );
+        assert_eq!(manager.get_stream(custom).unwrap().qos_params, telemetry);
+        assert_eq!(
+            manager.get_stream(unknown).unwrap().qos_params,
+            QoSParams::audio()
+        );
+        assert_eq!(
+            manager.get_stream(video).unwrap().qos_params,
+            QoSParams::video()
+        );
+        assert_eq!(
+            MediaStreamType::from(StreamType::Data),
+            MediaStreamType::DataChannel
+        );
+    }
 }
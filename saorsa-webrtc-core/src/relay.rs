@@ -0,0 +1,410 @@
+//! Packet-level media relay
+//!
+//! Fast path for the SFU and relay roles: RTP packets are forwarded to
+//! subscribers with only their SSRC, sequence number and timestamp
+//! rewritten. Payloads, which may be end-to-end encrypted, are never
+//! decoded or inspected, so relay cost per stream stays close to a copy.
+//! Subscribers on slow links can be capped to the lower temporal layers of
+//! a stream, which only needs the layer ID in the packet header.
+
+use crate::fec;
+use crate::quic_bridge::RtpPacket;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Rewrites one outgoing stream so it stays continuous across source switches
+///
+/// When the input switches (a different simulcast layer or speaker), the
+/// receiver keeps seeing a single SSRC with contiguous sequence numbers
+/// and monotonic timestamps.
+#[derive(Debug, Clone)]
+pub struct StreamRewriter {
+    output_ssrc: u32,
+    input_ssrc: Option<u32>,
+    seq_offset: u16,
+    ts_offset: u32,
+    last_output: Option<(u16, u32)>,
+    rebase_pending: bool,
+    max_temporal_layer: u8,
+}
+
+impl StreamRewriter {
+    /// Create a rewriter emitting `output_ssrc`
+    #[must_use]
+    pub fn new(output_ssrc: u32) -> Self {
+        Self {
+            output_ssrc,
+            input_ssrc: None,
+            seq_offset: 0,
+            ts_offset: 0,
+            last_output: None,
+            rebase_pending: true,
+            max_temporal_layer: u8::MAX,
+        }
+    }
+
+    /// SSRC of the rewritten stream
+    #[must_use]
+    pub fn output_ssrc(&self) -> u32 {
+        self.output_ssrc
+    }
+
+    /// SSRC currently being forwarded
+    #[must_use]
+    pub fn input_ssrc(&self) -> Option<u32> {
+        self.input_ssrc
+    }
+
+    /// Only forward packets in temporal layers up to `layer`
+    ///
+    /// Dropped packets are closed out of the sequence numbering, so the
+    /// receiver sees a lower frame rate rather than loss.
+    pub fn set_max_temporal_layer(&mut self, layer: u8) {
+        self.max_temporal_layer = layer;
+    }
+
+    /// Highest temporal layer forwarded
+    #[must_use]
+    pub fn max_temporal_layer(&self) -> u8 {
+        self.max_temporal_layer
+    }
+
+    /// Start forwarding a different input stream
+    ///
+    /// Offsets are recomputed from the next packet so the output continues
+    /// where it left off.
+    pub fn switch_source(&mut self, input_ssrc: u32) {
+        if self.input_ssrc != Some(input_ssrc) {
+            self.input_ssrc = Some(input_ssrc);
+            self.rebase_pending = true;
+        }
+    }
+
+    /// Rewrite a packet's header for the output stream
+    ///
+    /// Returns `None` if the packet does not belong to the current input
+    /// or is above the temporal layer limit. FEC repair packets are only
+    /// passed through while the output is an untouched copy of the input,
+    /// since their parity covers the input's numbering.
+    #[must_use]
+    pub fn rewrite(&mut self, packet: &RtpPacket) -> Option<RtpPacket> {
+        if self.input_ssrc.is_some_and(|ssrc| ssrc != packet.ssrc) {
+            return None;
+        }
+        self.input_ssrc = Some(packet.ssrc);
+
+        if fec::is_repair(packet) {
+            // The first rebase of a fresh rewriter keeps zero offsets
+            let rebasing = self.rebase_pending && self.last_output.is_some();
+            let untouched = !rebasing
+                && self.seq_offset == 0
+                && self.ts_offset == 0
+                && self.max_temporal_layer == u8::MAX;
+            if !untouched {
+                return None;
+            }
+        }
+
+        if packet.temporal_layer > self.max_temporal_layer {
+            // Pull later packets back over the gap; a pending rebase
+            // recomputes the offset anyway
+            if !self.rebase_pending {
+                self.seq_offset = self.seq_offset.wrapping_sub(1);
+            }
+            return None;
+        }
+
+        if self.rebase_pending {
+            let (next_seq, next_ts) = match self.last_output {
+                // Leave a one-tick gap so timestamps stay strictly increasing
+                Some((seq, ts)) => (seq.wrapping_add(1), ts.wrapping_add(1)),
+                None => (packet.sequence_number, packet.timestamp),
+            };
+            self.seq_offset = next_seq.wrapping_sub(packet.sequence_number);
+            self.ts_offset = next_ts.wrapping_sub(packet.timestamp);
+            self.rebase_pending = false;
+        }
+
+        let mut out = packet.clone();
+        out.ssrc = self.output_ssrc;
+        out.sequence_number = packet.sequence_number.wrapping_add(self.seq_offset);
+        out.timestamp = packet.timestamp.wrapping_add(self.ts_offset);
+
+        // Only advance on newer packets so reordering does not rewind
+        let newer = self
+            .last_output
+            .is_none_or(|(seq, _)| out.sequence_number.wrapping_sub(seq) < 0x8000);
+        if newer {
+            self.last_output = Some((out.sequence_number, out.timestamp));
+        }
+        Some(out)
+    }
+}
+
+/// Relay counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayStats {
+    /// Packets sent to subscribers (one per subscriber)
+    pub packets_forwarded: u64,
+    /// Payload bytes sent to subscribers
+    pub bytes_forwarded: u64,
+    /// Incoming packets with no subscriber
+    pub packets_unrouted: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct OutputKey {
+    receiver: String,
+    output_ssrc: u32,
+}
+
+/// Forwards RTP packets to subscribers without decoding them
+#[derive(Debug, Default)]
+pub struct PacketRelay {
+    routes: HashMap<u32, Vec<OutputKey>>,
+    outputs: HashMap<OutputKey, StreamRewriter>,
+    stats: RelayStats,
+}
+
+impl PacketRelay {
+    /// Create an empty relay
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forward `input_ssrc` to `receiver` as `output_ssrc`
+    ///
+    /// If the output stream is already bound to another input, it switches
+    /// over while keeping the receiver's view of the stream continuous.
+    pub fn subscribe(&mut self, receiver: &str, input_ssrc: u32, output_ssrc: u32) {
+        let key = OutputKey {
+            receiver: receiver.to_string(),
+            output_ssrc,
+        };
+        let previous = self.outputs.get(&key).and_then(StreamRewriter::input_ssrc);
+        if previous == Some(input_ssrc) {
+            return;
+        }
+        if let Some(previous) = previous {
+            self.unroute(previous, &key);
+        }
+        self.outputs
+            .entry(key.clone())
+            .or_insert_with(|| StreamRewriter::new(output_ssrc))
+            .switch_source(input_ssrc);
+        self.routes.entry(input_ssrc).or_default().push(key);
+    }
+
+    /// Cap an output stream to temporal layers up to `layer`
+    ///
+    /// Returns `false` if `receiver` has no such output stream.
+    pub fn set_max_temporal_layer(&mut self, receiver: &str, output_ssrc: u32, layer: u8) -> bool {
+        let key = OutputKey {
+            receiver: receiver.to_string(),
+            output_ssrc,
+        };
+        match self.outputs.get_mut(&key) {
+            Some(rewriter) => {
+                rewriter.set_max_temporal_layer(layer);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop forwarding an output stream to `receiver`
+    pub fn unsubscribe(&mut self, receiver: &str, output_ssrc: u32) {
+        let key = OutputKey {
+            receiver: receiver.to_string(),
+            output_ssrc,
+        };
+        if let Some(input) = self.outputs.remove(&key).and_then(|r| r.input_ssrc()) {
+            self.unroute(input, &key);
+        }
+    }
+
+    /// Drop every output stream of a receiver
+    pub fn remove_receiver(&mut self, receiver: &str) {
+        let keys: Vec<OutputKey> = self
+            .outputs
+            .keys()
+            .filter(|key| key.receiver == receiver)
+            .cloned()
+            .collect();
+        for key in keys {
+            self.unsubscribe(&key.receiver, key.output_ssrc);
+        }
+    }
+
+    /// Drop every route fed by an input stream, e.g. when its sender leaves
+    pub fn remove_source(&mut self, input_ssrc: u32) {
+        for key in self.routes.remove(&input_ssrc).unwrap_or_default() {
+            self.outputs.remove(&key);
+        }
+    }
+
+    /// Rewrite a packet for every subscriber of its SSRC
+    #[must_use]
+    pub fn forward(&mut self, packet: &RtpPacket) -> Vec<(String, RtpPacket)> {
+        let Some(keys) = self.routes.get(&packet.ssrc) else {
+            self.stats.packets_unrouted += 1;
+            return Vec::new();
+        };
+
+        let mut out = Vec::with_capacity(keys.len());
+        for key in keys {
+            if let Some(rewritten) = self
+                .outputs
+                .get_mut(key)
+                .and_then(|rewriter| rewriter.rewrite(packet))
+            {
+                self.stats.packets_forwarded += 1;
+                self.stats.bytes_forwarded += rewritten.payload.len() as u64;
+                out.push((key.receiver.clone(), rewritten));
+            }
+        }
+        out
+    }
+
+    /// Number of output streams
+    #[must_use]
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Relay counters
+    #[must_use]
+    pub fn stats(&self) -> RelayStats {
+        self.stats
+    }
+
+    fn unroute(&mut self, input_ssrc: u32, key: &OutputKey) {
+        if let Some(keys) = self.routes.get_mut(&input_ssrc) {
+            keys.retain(|k| k != key);
+            if keys.is_empty() {
+                self.routes.remove(&input_ssrc);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic_bridge::StreamType;
+
+    fn packet(ssrc: u32, seq: u16, ts: u32) -> RtpPacket {
+        RtpPacket::new(96, seq, ts, ssrc, vec![0xAB; 100], StreamType::Video).unwrap()
+    }
+
+    #[test]
+    fn test_forward_rewrites_ssrc_only() {
+        let mut relay = PacketRelay::new();
+        relay.subscribe("bob", 1111, 9000);
+        relay.subscribe("carol", 1111, 9001);
+
+        let out = relay.forward(&packet(1111, 500, 48_000));
+        assert_eq!(out.len(), 2);
+        for (_, p) in &out {
+            assert_eq!(p.sequence_number, 500);
+            assert_eq!(p.timestamp, 48_000);
+            assert_eq!(p.payload, vec![0xAB; 100]);
+        }
+        assert_eq!(out[0].1.ssrc, 9000);
+        assert_eq!(out[1].1.ssrc, 9001);
+
+        assert!(relay.forward(&packet(2222, 1, 1)).is_empty());
+        assert_eq!(relay.stats().packets_forwarded, 2);
+        assert_eq!(relay.stats().bytes_forwarded, 200);
+        assert_eq!(relay.stats().packets_unrouted, 1);
+    }
+
+    #[test]
+    fn test_switch_source_keeps_output_continuous() {
+        let mut relay = PacketRelay::new();
+        relay.subscribe("bob", 1111, 9000);
+        let _ = relay.forward(&packet(1111, 100, 1_000));
+        let _ = relay.forward(&packet(1111, 101, 4_000));
+
+        // Switch to another simulcast layer with unrelated numbering
+        relay.subscribe("bob", 2222, 9000);
+        assert!(relay.forward(&packet(1111, 102, 7_000)).is_empty());
+
+        let out = relay.forward(&packet(2222, 60_000, 900_000));
+        assert_eq!(out[0].1.ssrc, 9000);
+        assert_eq!(out[0].1.sequence_number, 102);
+        assert_eq!(out[0].1.timestamp, 4_001);
+
+        let out = relay.forward(&packet(2222, 60_001, 903_000));
+        assert_eq!(out[0].1.sequence_number, 103);
+        assert_eq!(out[0].1.timestamp, 7_001);
+    }
+
+    #[test]
+    fn test_sequence_wraps() {
+        let mut rewriter = StreamRewriter::new(7);
+        let first = rewriter.rewrite(&packet(1, u16::MAX, 0)).unwrap();
+        let second = rewriter.rewrite(&packet(1, 0, 10)).unwrap();
+        assert_eq!(first.sequence_number, u16::MAX);
+        assert_eq!(second.sequence_number, 0);
+
+        // A late packet from before the wrap must not rewind the position
+        let late = rewriter.rewrite(&packet(1, u16::MAX - 1, 0)).unwrap();
+        assert_eq!(late.sequence_number, u16::MAX - 1);
+        rewriter.switch_source(2);
+        let switched = rewriter.rewrite(&packet(2, 5, 5)).unwrap();
+        assert_eq!(switched.sequence_number, 1);
+    }
+
+    #[test]
+    fn test_temporal_layer_cap_closes_sequence_gaps() {
+        let mut relay = PacketRelay::new();
+        relay.subscribe("bob", 1111, 9000);
+        relay.subscribe("carol", 1111, 9001);
+        assert!(relay.set_max_temporal_layer("bob", 9000, 0));
+        assert!(!relay.set_max_temporal_layer("bob", 9999, 0));
+
+        let mut bob = Vec::new();
+        for (i, layer) in [0, 2, 1, 2, 0].into_iter().enumerate() {
+            let seq = 100 + i as u16;
+            let p = packet(1111, seq, u32::from(seq) * 3_000).with_temporal_layer(layer);
+            for (receiver, out) in relay.forward(&p) {
+                if receiver == "bob" {
+                    bob.push((out.sequence_number, out.timestamp));
+                }
+            }
+        }
+        // Base layer only, numbered without gaps, timestamps untouched
+        assert_eq!(bob, vec![(100, 300_000), (101, 312_000)]);
+        assert_eq!(relay.stats().packets_forwarded, 7);
+    }
+
+    #[test]
+    fn test_repair_packets_dropped_once_rewritten() {
+        let mut rewriter = StreamRewriter::new(1111);
+        let mut repair = packet(1111, 10, 10);
+        repair.payload_type = fec::FEC_PAYLOAD_TYPE;
+        assert!(rewriter.rewrite(&repair).is_some());
+
+        rewriter.set_max_temporal_layer(0);
+        assert!(rewriter.rewrite(&repair).is_none());
+        assert!(rewriter.rewrite(&packet(1111, 11, 11)).is_some());
+    }
+
+    #[test]
+    fn test_remove_receiver_and_source() {
+        let mut relay = PacketRelay::new();
+        relay.subscribe("bob", 1111, 9000);
+        relay.subscribe("bob", 3333, 9002);
+        relay.subscribe("carol", 1111, 9001);
+
+        relay.remove_receiver("bob");
+        assert_eq!(relay.output_count(), 1);
+        assert_eq!(relay.forward(&packet(1111, 1, 1)).len(), 1);
+
+        relay.remove_source(1111);
+        assert_eq!(relay.output_count(), 0);
+        assert!(relay.forward(&packet(1111, 2, 2)).is_empty());
+    }
+}
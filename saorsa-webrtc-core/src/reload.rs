@@ -0,0 +1,264 @@
+//! Configuration reload for long-running nodes
+//!
+//! Headless relays and SFUs change codec preferences, bandwidth caps,
+//! quality and congestion thresholds and relay lists without restarting. A
+//! [`ReloadableConfig`] is built through the API or read from a JSON file,
+//! where missing settings take their defaults, and checked as a whole
+//! before any of it is applied: by
+//! [`WebRtcService::reload_config`](crate::service::WebRtcService::reload_config)
+//! for the service's settings and
+//! [`AntQuicTransport::reload`](crate::transport::AntQuicTransport::reload)
+//! for the transport's. If any setting is invalid nothing changes.
+//!
+//! Settings fixed at start, such as runtimes, the audio clock or the
+//! transport's bind address, are not reloadable.
+
+use crate::broadcast::BroadcastConfig;
+use crate::capabilities::CodecCapabilities;
+use crate::congestion::CongestionConfig;
+use crate::degradation::DegradationConfig;
+use crate::error::{CodedError, ErrorCode};
+use crate::quality::QualityPolicy;
+use crate::transport::TransportConfig;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Configuration errors
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// Config file could not be read
+    #[error("Failed to read config file {path:?}")]
+    Read {
+        /// File being read
+        path: PathBuf,
+        /// Underlying failure
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Config is not valid JSON for a [`ReloadableConfig`]
+    #[error("Malformed config")]
+    Parse(#[source] serde_json::Error),
+
+    /// A setting is out of range or contradicts another
+    #[error("Invalid config: {0}")]
+    Invalid(String),
+
+    /// A setting that is fixed until restart was changed
+    #[error("{0} cannot change without a restart")]
+    RestartRequired(&'static str),
+}
+
+impl CodedError for ConfigError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Read { .. } => ErrorCode::InvalidInput,
+            Self::Parse(_) | Self::Invalid(_) | Self::RestartRequired(_) => {
+                ErrorCode::InvalidConfig
+            }
+        }
+    }
+}
+
+/// Settings a running node can reload
+///
+/// Take the current ones with
+/// [`WebRtcService::reloadable_config`](crate::service::WebRtcService::reloadable_config),
+/// change what is needed and apply them again.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReloadableConfig {
+    /// Codecs offered to peers, most preferred first; used by calls
+    /// negotiated afterwards
+    pub codecs: CodecCapabilities,
+    /// Video degradation ladder and bandwidth caps; applied to running calls
+    pub degradation: DegradationConfig,
+    /// Rules used to raise quality alerts
+    pub quality_policy: QualityPolicy,
+    /// Pacer queue and loss thresholds for congestion reports
+    pub congestion: CongestionConfig,
+    /// Subscriber limit and layer selection of broadcasts started afterwards
+    pub broadcast: BroadcastConfig,
+    /// Transport settings, including relays
+    pub transport: TransportConfig,
+}
+
+impl ReloadableConfig {
+    /// Parse a JSON config and check it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the JSON is malformed or a setting is invalid
+    pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+        let config: Self = serde_json::from_str(json).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read a JSON config file and check it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the file cannot be read, is malformed or a setting
+    /// is invalid
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::from_json(&json)
+    }
+
+    /// Check every setting, naming the first invalid one
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::Invalid`] if a setting is out of range or
+    /// contradicts another
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        validate_codecs(&self.codecs)?;
+        validate_degradation(&self.degradation)?;
+        validate_congestion("congestion", &self.congestion)?;
+        validate_broadcast(&self.broadcast)?;
+        self.transport.validate()
+    }
+}
+
+fn validate_codecs(codecs: &CodecCapabilities) -> Result<(), ConfigError> {
+    // Negotiating with ourselves keeps only codecs this build can use
+    let usable = CodecCapabilities::negotiate(codecs, &CodecCapabilities::local());
+    if usable.audio.is_none() {
+        return Err(ConfigError::Invalid(
+            "codecs.audio lists no codec this build supports".to_string(),
+        ));
+    }
+    if !codecs.video.is_empty() && usable.video.is_none() {
+        return Err(ConfigError::Invalid(
+            "codecs.video lists no codec this build supports".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_degradation(config: &DegradationConfig) -> Result<(), ConfigError> {
+    if config.audio_bitrate_kbps == 0 || config.low_bandwidth_audio_kbps == 0 {
+        return Err(ConfigError::Invalid(
+            "degradation audio bitrates must be positive".to_string(),
+        ));
+    }
+    for (i, step) in config.steps.iter().enumerate() {
+        if step.video_bitrate_kbps == 0 || step.video_fps == 0 {
+            return Err(ConfigError::Invalid(format!(
+                "degradation.steps[{i}] needs a positive bitrate and frame rate"
+            )));
+        }
+        if i > 0 && step.video_bitrate_kbps >= config.steps[i - 1].video_bitrate_kbps {
+            return Err(ConfigError::Invalid(format!(
+                "degradation.steps[{i}] must have a lower bitrate than the step above"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_congestion(name: &str, config: &CongestionConfig) -> Result<(), ConfigError> {
+    if config.queue_warning_ms > config.queue_critical_ms {
+        return Err(ConfigError::Invalid(format!(
+            "{name}.queue_warning_ms is above queue_critical_ms"
+        )));
+    }
+    let percent = |p: f32| (0.0..=100.0).contains(&p);
+    if !percent(config.loss_warning_percent) || !percent(config.loss_critical_percent) {
+        return Err(ConfigError::Invalid(format!(
+            "{name} loss thresholds must be between 0 and 100"
+        )));
+    }
+    if config.loss_warning_percent > config.loss_critical_percent {
+        return Err(ConfigError::Invalid(format!(
+            "{name}.loss_warning_percent is above loss_critical_percent"
+        )));
+    }
+    if config.clear_after == 0 {
+        return Err(ConfigError::Invalid(format!(
+            "{name}.clear_after must be at least 1"
+        )));
+    }
+    Ok(())
+}
+
+fn validate_broadcast(config: &BroadcastConfig) -> Result<(), ConfigError> {
+    if config.max_subscribers == Some(0) {
+        return Err(ConfigError::Invalid(
+            "broadcast.max_subscribers must be at least 1".to_string(),
+        ));
+    }
+    if config.upgrade_after == 0 {
+        return Err(ConfigError::Invalid(
+            "broadcast.upgrade_after must be at least 1".to_string(),
+        ));
+    }
+    validate_congestion("broadcast.congestion", &config.congestion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_defaults_are_valid() {
+        assert!(ReloadableConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(
+            file,
+            r#"{{"degradation": {{"steps": [], "min_video_bitrate_kbps": 100,
+                "audio_bitrate_kbps": 32, "upgrade_headroom_percent": 10}},
+               "transport": {{"relays": ["192.0.2.1:9000"]}}}}"#
+        )
+        .unwrap();
+        let config = ReloadableConfig::load(file.path()).unwrap();
+        assert_eq!(config.degradation.audio_bitrate_kbps, 32);
+        assert_eq!(
+            config.transport.relays,
+            vec!["192.0.2.1:9000".parse().unwrap()]
+        );
+        assert_eq!(config.codecs, CodecCapabilities::local());
+        assert_eq!(config.congestion, CongestionConfig::default());
+    }
+
+    #[test]
+    fn test_rejects_invalid_settings() {
+        let mut config = ReloadableConfig::default();
+        config.congestion.queue_warning_ms = 1000;
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(m)) if m.contains("queue")));
+
+        let mut config = ReloadableConfig::default();
+        config.codecs.audio = vec!["audio/PCMU".to_string()];
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let mut config = ReloadableConfig::default();
+        config.degradation.steps.reverse();
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        let mut config = ReloadableConfig::default();
+        let relay = "192.0.2.1:9000".parse().unwrap();
+        config.transport.relays = vec![relay, relay];
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_malformed_and_missing_files() {
+        assert!(matches!(
+            ReloadableConfig::from_json("{not json"),
+            Err(ConfigError::Parse(_))
+        ));
+        let error = ReloadableConfig::load("/nonexistent/saorsa.json").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidInput);
+    }
+}
@@ -0,0 +1,429 @@
+//! Remote control of screen shares
+//!
+//! A viewer can ask to drive the sharer's pointer and keyboard, e.g. for
+//! support or pair programming. The sharer's application must opt in and
+//! is prompted for every request; once granted, input travels over a data
+//! channel labelled [`remote_control_channel_label`] and stops the moment
+//! either side revokes or releases control.
+//!
+//! Requests, grants and revocations are exchanged as
+//! [`RemoteControlAction`]s over signaling. A grant carries a random token
+//! that only the granted peer learns; every input message repeats it, and
+//! the host drops input whose token is not the live grant for that screen
+//! share, so input sent before a revocation or by anyone else is ignored.
+//!
+//! # Wire format
+//!
+//! One [`RemoteInput`] per data channel message, fields little-endian:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 1 | version, [`REMOTE_CONTROL_WIRE_VERSION`] |
+//! | 1 | kind: 1 pointer move, 2 button down, 3 button up, 4 scroll, 5 key down, 6 key up, 7 text |
+//! | 8 | grant token |
+//! | .. | body |
+//!
+//! Bodies by kind:
+//!
+//! - pointer move: `x: u16, y: u16`, normalized like annotation points
+//! - button down and up: `button: u8`, 0 left, 1 right, 2 middle
+//! - scroll: `dx: i16, dy: i16` in wheel lines, positive right and down
+//! - key down and up: `usage: u16`, a USB HID keyboard page usage ID
+//! - text: `len: u16`, then `len` bytes of UTF-8
+//!
+//! Bytes after the body are ignored so later versions can extend a message.
+
+use crate::annotation::AnnotationPoint;
+use crate::error::{CodedError, ErrorCode};
+use crate::wire::{Truncated, WireReader};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Version written in the first byte of every message
+pub const REMOTE_CONTROL_WIRE_VERSION: u8 = 1;
+
+/// Label prefix reserved for remote control data channels
+pub const REMOTE_CONTROL_CHANNEL_PREFIX: &str = "saorsa-remote-control/";
+
+const KIND_POINTER_MOVE: u8 = 1;
+const KIND_BUTTON_DOWN: u8 = 2;
+const KIND_BUTTON_UP: u8 = 3;
+const KIND_SCROLL: u8 = 4;
+const KIND_KEY_DOWN: u8 = 5;
+const KIND_KEY_UP: u8 = 6;
+const KIND_TEXT: u8 = 7;
+
+/// Remote control errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RemoteControlError {
+    /// Message ended before its body did
+    #[error("Remote input message truncated")]
+    Truncated,
+
+    /// Message written by an incompatible version
+    #[error("Unsupported remote input version: {0}")]
+    UnsupportedVersion(u8),
+
+    /// Message kind not known to this version
+    #[error("Unknown remote input kind: {0}")]
+    UnknownKind(u8),
+
+    /// Mouse button not known to this version
+    #[error("Unknown mouse button: {0}")]
+    UnknownButton(u8),
+
+    /// Text is not UTF-8
+    #[error("Remote input text is not UTF-8")]
+    InvalidText,
+
+    /// Text is longer than fits in one message
+    #[error("Remote input text is {0} bytes, more than {max}", max = u16::MAX)]
+    TextTooLong(usize),
+
+    /// We have not been granted control of the screen share
+    #[error("Remote control not granted for track: {0}")]
+    NotGranted(String),
+
+    /// Remote control is turned off in this application
+    #[error("Remote control is disabled")]
+    Disabled,
+}
+
+impl CodedError for RemoteControlError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Truncated
+            | Self::UnsupportedVersion(_)
+            | Self::UnknownKind(_)
+            | Self::UnknownButton(_)
+            | Self::InvalidText => ErrorCode::MalformedMessage,
+            Self::TextTooLong(_) => ErrorCode::PacketTooLarge,
+            Self::NotGranted(_) => ErrorCode::InvalidCallState,
+            Self::Disabled => ErrorCode::NotImplemented,
+        }
+    }
+}
+
+impl From<Truncated> for RemoteControlError {
+    fn from(_: Truncated) -> Self {
+        Self::Truncated
+    }
+}
+
+/// Mouse button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MouseButton {
+    /// Primary button
+    Left,
+    /// Secondary button
+    Right,
+    /// Wheel button
+    Middle,
+}
+
+impl MouseButton {
+    fn to_wire(self) -> u8 {
+        match self {
+            Self::Left => 0,
+            Self::Right => 1,
+            Self::Middle => 2,
+        }
+    }
+
+    fn from_wire(value: u8) -> Result<Self, RemoteControlError> {
+        match value {
+            0 => Ok(Self::Left),
+            1 => Ok(Self::Right),
+            2 => Ok(Self::Middle),
+            other => Err(RemoteControlError::UnknownButton(other)),
+        }
+    }
+}
+
+/// One keyboard or mouse event from the controlling viewer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteInput {
+    /// Pointer moved on the shared surface
+    PointerMove(AnnotationPoint),
+    /// Mouse button pressed
+    ButtonDown(MouseButton),
+    /// Mouse button released
+    ButtonUp(MouseButton),
+    /// Wheel scrolled, in lines; positive is right and down
+    Scroll {
+        /// Horizontal lines
+        dx: i16,
+        /// Vertical lines
+        dy: i16,
+    },
+    /// Key pressed, as a USB HID keyboard usage ID
+    KeyDown(u16),
+    /// Key released, as a USB HID keyboard usage ID
+    KeyUp(u16),
+    /// Text typed, for input methods without key codes
+    Text(String),
+}
+
+impl RemoteInput {
+    /// Encode as one data channel message under `grant`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if text is longer than 65535 bytes
+    pub fn encode(&self, grant: u64) -> Result<Vec<u8>, RemoteControlError> {
+        let mut buf = Vec::with_capacity(16);
+        buf.push(REMOTE_CONTROL_WIRE_VERSION);
+        let kind = match self {
+            Self::PointerMove(_) => KIND_POINTER_MOVE,
+            Self::ButtonDown(_) => KIND_BUTTON_DOWN,
+            Self::ButtonUp(_) => KIND_BUTTON_UP,
+            Self::Scroll { .. } => KIND_SCROLL,
+            Self::KeyDown(_) => KIND_KEY_DOWN,
+            Self::KeyUp(_) => KIND_KEY_UP,
+            Self::Text(_) => KIND_TEXT,
+        };
+        buf.push(kind);
+        buf.extend_from_slice(&grant.to_le_bytes());
+        match self {
+            Self::PointerMove(point) => {
+                buf.extend_from_slice(&point.x.to_le_bytes());
+                buf.extend_from_slice(&point.y.to_le_bytes());
+            }
+            Self::ButtonDown(button) | Self::ButtonUp(button) => buf.push(button.to_wire()),
+            Self::Scroll { dx, dy } => {
+                buf.extend_from_slice(&dx.to_le_bytes());
+                buf.extend_from_slice(&dy.to_le_bytes());
+            }
+            Self::KeyDown(usage) | Self::KeyUp(usage) => {
+                buf.extend_from_slice(&usage.to_le_bytes())
+            }
+            Self::Text(text) => {
+                let len = u16::try_from(text.len())
+                    .map_err(|_| RemoteControlError::TextTooLong(text.len()))?;
+                buf.extend_from_slice(&len.to_le_bytes());
+                buf.extend_from_slice(text.as_bytes());
+            }
+        }
+        Ok(buf)
+    }
+
+    /// Decode one data channel message into its grant token and input
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the message is truncated, from another wire
+    /// version, of an unknown kind or carries invalid text
+    pub fn decode(data: &[u8]) -> Result<(u64, Self), RemoteControlError> {
+        let mut reader = WireReader::new(data);
+        let version = reader.u8()?;
+        if version != REMOTE_CONTROL_WIRE_VERSION {
+            return Err(RemoteControlError::UnsupportedVersion(version));
+        }
+        let kind = reader.u8()?;
+        let grant = reader.u64()?;
+        let input = match kind {
+            KIND_POINTER_MOVE => Self::PointerMove(AnnotationPoint {
+                x: reader.u16()?,
+                y: reader.u16()?,
+            }),
+            KIND_BUTTON_DOWN => Self::ButtonDown(MouseButton::from_wire(reader.u8()?)?),
+            KIND_BUTTON_UP => Self::ButtonUp(MouseButton::from_wire(reader.u8()?)?),
+            KIND_SCROLL => Self::Scroll {
+                dx: reader.i16()?,
+                dy: reader.i16()?,
+            },
+            KIND_KEY_DOWN => Self::KeyDown(reader.u16()?),
+            KIND_KEY_UP => Self::KeyUp(reader.u16()?),
+            KIND_TEXT => {
+                let len = usize::from(reader.u16()?);
+                let bytes = reader.bytes(len)?;
+                let text =
+                    std::str::from_utf8(bytes).map_err(|_| RemoteControlError::InvalidText)?;
+                Self::Text(text.to_string())
+            }
+            other => return Err(RemoteControlError::UnknownKind(other)),
+        };
+        Ok((grant, input))
+    }
+}
+
+/// Remote control step exchanged over signaling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RemoteControlAction {
+    /// Viewer asks to control the screen share
+    Request,
+    /// Sharer allows it; input must carry `grant`
+    Grant {
+        /// Token stamped on every input message
+        grant: u64,
+    },
+    /// Sharer refuses a request
+    Deny,
+    /// Sharer takes control back
+    Revoke,
+    /// Viewer gives control back
+    Release,
+}
+
+/// Remote control grants of one call, in both directions
+#[derive(Debug, Clone, Default)]
+pub struct RemoteControlGrants {
+    /// Our screen shares the remote party controls, with their tokens
+    hosted: HashMap<String, u64>,
+    /// Remote screen shares we control, with their tokens
+    controlling: HashMap<String, u64>,
+}
+
+impl RemoteControlGrants {
+    /// Let the remote party control one of our screen shares, replacing
+    /// any earlier grant, and return the new token
+    pub fn grant(&mut self, track_id: &str) -> u64 {
+        let grant = rand::thread_rng().gen();
+        self.hosted.insert(track_id.to_string(), grant);
+        grant
+    }
+
+    /// Take control of our screen share back; false if it was not granted
+    pub fn revoke(&mut self, track_id: &str) -> bool {
+        self.hosted.remove(track_id).is_some()
+    }
+
+    /// Whether input carrying `grant` may drive our screen share
+    #[must_use]
+    pub fn authorizes(&self, track_id: &str, grant: u64) -> bool {
+        self.hosted.get(track_id) == Some(&grant)
+    }
+
+    /// Whether the remote party controls our screen share
+    #[must_use]
+    pub fn is_hosting(&self, track_id: &str) -> bool {
+        self.hosted.contains_key(track_id)
+    }
+
+    /// Record that we may control a remote screen share
+    pub fn accept(&mut self, track_id: &str, grant: u64) {
+        self.controlling.insert(track_id.to_string(), grant);
+    }
+
+    /// Stop controlling a remote screen share; false if we were not
+    pub fn release(&mut self, track_id: &str) -> bool {
+        self.controlling.remove(track_id).is_some()
+    }
+
+    /// Token to stamp on input for a remote screen share we control
+    #[must_use]
+    pub fn token(&self, track_id: &str) -> Option<u64> {
+        self.controlling.get(track_id).copied()
+    }
+}
+
+/// Label of the remote control data channel paired with a screen share
+/// track
+#[must_use]
+pub fn remote_control_channel_label(track_id: &str) -> String {
+    format!("{REMOTE_CONTROL_CHANNEL_PREFIX}{track_id}")
+}
+
+/// Screen share track a remote control channel label belongs to, if it is
+/// one
+#[must_use]
+pub fn controlled_track(label: &str) -> Option<&str> {
+    label
+        .strip_prefix(REMOTE_CONTROL_CHANNEL_PREFIX)
+        .filter(|track_id| !track_id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inputs_round_trip_with_their_grant() {
+        let inputs = [
+            RemoteInput::PointerMove(AnnotationPoint { x: 10, y: 65535 }),
+            RemoteInput::ButtonDown(MouseButton::Left),
+            RemoteInput::ButtonUp(MouseButton::Middle),
+            RemoteInput::Scroll { dx: -1, dy: 3 },
+            RemoteInput::KeyDown(0x04),
+            RemoteInput::KeyUp(0xe1),
+            RemoteInput::Text("héllo".to_string()),
+        ];
+        for input in inputs {
+            let encoded = input.encode(0xfeed).unwrap();
+            assert_eq!(RemoteInput::decode(&encoded).unwrap(), (0xfeed, input));
+        }
+    }
+
+    #[test]
+    fn test_wire_format_is_stable() {
+        assert_eq!(
+            RemoteInput::ButtonDown(MouseButton::Right)
+                .encode(2)
+                .unwrap(),
+            [1, 2, 2, 0, 0, 0, 0, 0, 0, 0, 1]
+        );
+        assert_eq!(
+            RemoteInput::decode(&[1, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 1, 0]).unwrap(),
+            (0, RemoteInput::Scroll { dx: -1, dy: 1 })
+        );
+    }
+
+    #[test]
+    fn test_malformed_input_is_rejected() {
+        let header = [1, 7, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(
+            RemoteInput::decode(&header[..5]),
+            Err(RemoteControlError::Truncated)
+        );
+        assert_eq!(
+            RemoteInput::decode(&[header.as_slice(), &[2, 0, 0xff, 0xfe]].concat()),
+            Err(RemoteControlError::InvalidText)
+        );
+        assert_eq!(
+            RemoteInput::decode(&[header.as_slice(), &[3, 0, b'a']].concat()),
+            Err(RemoteControlError::Truncated)
+        );
+        assert_eq!(
+            RemoteInput::decode(&[1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 9]),
+            Err(RemoteControlError::UnknownButton(9))
+        );
+        assert_eq!(
+            RemoteInput::decode(&[3, 1]),
+            Err(RemoteControlError::UnsupportedVersion(3))
+        );
+        assert_eq!(
+            RemoteInput::Text("x".repeat(70_000)).encode(1),
+            Err(RemoteControlError::TextTooLong(70_000))
+        );
+    }
+
+    #[test]
+    fn test_only_the_live_grant_authorizes_input() {
+        let mut grants = RemoteControlGrants::default();
+        assert!(!grants.authorizes("screen-0", 0));
+
+        let first = grants.grant("screen-0");
+        assert!(grants.authorizes("screen-0", first));
+        assert!(!grants.authorizes("screen-1", first));
+
+        let second = grants.grant("screen-0");
+        assert!(!grants.authorizes("screen-0", first) || first == second);
+        assert!(grants.revoke("screen-0"));
+        assert!(!grants.authorizes("screen-0", second));
+        assert!(!grants.revoke("screen-0"));
+
+        grants.accept("screen-3", 42);
+        assert_eq!(grants.token("screen-3"), Some(42));
+        assert!(grants.release("screen-3"));
+        assert_eq!(grants.token("screen-3"), None);
+    }
+
+    #[test]
+    fn test_channel_labels_pair_with_tracks() {
+        let label = remote_control_channel_label("screen-0");
+        assert_eq!(controlled_track(&label), Some("screen-0"));
+        assert_eq!(controlled_track("saorsa-annotations/screen-0"), None);
+    }
+}
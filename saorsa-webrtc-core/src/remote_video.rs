@@ -0,0 +1,264 @@
+//! Delivery of decoded remote video
+//!
+//! Encoded video received on a call is decoded per track with the call's
+//! negotiated codec, and each decoded [`VideoFrame`] is handed to the
+//! [`VideoFrameSink`]s registered for that call. Frontends render from
+//! their sink; closures taking `(CallId, &str, &VideoFrame)` are sinks too.
+
+use crate::media::MediaError;
+use crate::types::CallId;
+use parking_lot::Mutex;
+use saorsa_webrtc_codecs::{Av1Decoder, OpenH264Decoder, VideoCodec, VideoDecoder, VideoFrame};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Consumer of decoded remote video
+///
+/// Called on the thread that received the frame; hand frames off rather
+/// than rendering inline if that is slow.
+pub trait VideoFrameSink: Send + Sync {
+    /// Handle a decoded RGB24 frame of a remote track
+    fn on_frame(&self, call_id: CallId, track_id: &str, frame: &VideoFrame);
+
+    /// A call ended and no more frames will arrive for it
+    fn on_call_ended(&self, _call_id: CallId) {}
+}
+
+impl<F> VideoFrameSink for F
+where
+    F: Fn(CallId, &str, &VideoFrame) + Send + Sync,
+{
+    fn on_frame(&self, call_id: CallId, track_id: &str, frame: &VideoFrame) {
+        self(call_id, track_id, frame);
+    }
+}
+
+/// Handle for removing a registered sink
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VideoSinkId(u64);
+
+type CallSinks = Vec<(VideoSinkId, Arc<dyn VideoFrameSink>)>;
+
+/// What a call's remote video decoders made of the frames received
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    /// Frames decoded, across the call's tracks
+    pub frames_decoded: u64,
+    /// Frames that could not be decoded and were dropped
+    pub frames_dropped: u64,
+    /// Width of the last decoded frame, in pixels
+    pub width: u32,
+    /// Height of the last decoded frame, in pixels
+    pub height: u32,
+}
+
+/// Remote video decoders and the sinks their frames go to
+#[derive(Default)]
+pub struct RemoteVideo {
+    next_sink: AtomicU64,
+    sinks: Mutex<HashMap<CallId, CallSinks>>,
+    decoders: Mutex<HashMap<(CallId, String), Box<dyn VideoDecoder>>>,
+    stats: Mutex<HashMap<CallId, DecodeStats>>,
+}
+
+impl RemoteVideo {
+    /// Create with no sinks
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send a call's decoded frames to `sink` until it is removed or the
+    /// call ends
+    pub fn add_sink(&self, call_id: CallId, sink: Arc<dyn VideoFrameSink>) -> VideoSinkId {
+        let id = VideoSinkId(self.next_sink.fetch_add(1, Ordering::Relaxed));
+        self.sinks
+            .lock()
+            .entry(call_id)
+            .or_default()
+            .push((id, sink));
+        id
+    }
+
+    /// Stop sending frames to a sink; false if it was not registered
+    pub fn remove_sink(&self, id: VideoSinkId) -> bool {
+        let mut sinks = self.sinks.lock();
+        let mut removed = false;
+        sinks.retain(|_, call_sinks| {
+            let before = call_sinks.len();
+            call_sinks.retain(|(sink_id, _)| *sink_id != id);
+            removed |= call_sinks.len() != before;
+            !call_sinks.is_empty()
+        });
+        removed
+    }
+
+    /// Whether any sink wants a call's frames
+    #[must_use]
+    pub fn has_sinks(&self, call_id: CallId) -> bool {
+        self.sinks.lock().contains_key(&call_id)
+    }
+
+    /// Hand a decoded frame to the call's sinks
+    pub fn deliver(&self, call_id: CallId, track_id: &str, frame: &VideoFrame) {
+        // Sinks run unlocked so they may register or remove sinks themselves
+        let sinks: Vec<_> = match self.sinks.lock().get(&call_id) {
+            Some(call_sinks) => call_sinks.iter().map(|(_, s)| Arc::clone(s)).collect(),
+            None => return,
+        };
+        for sink in sinks {
+            sink.on_frame(call_id, track_id, frame);
+        }
+    }
+
+    /// Decode a frame of a remote track, creating the track's decoder on
+    /// first use
+    ///
+    /// # Errors
+    ///
+    /// Returns error if no decoder exists for `codec` in this build or the
+    /// data cannot be decoded
+    pub fn decode(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        codec: VideoCodec,
+        data: &[u8],
+    ) -> Result<VideoFrame, MediaError> {
+        let result = self.decode_frame(call_id, track_id, codec, data);
+        let mut stats = self.stats.lock();
+        let stats = stats.entry(call_id).or_default();
+        match &result {
+            Ok(frame) => {
+                stats.frames_decoded += 1;
+                stats.width = frame.width;
+                stats.height = frame.height;
+            }
+            Err(_) => stats.frames_dropped += 1,
+        }
+        result
+    }
+
+    /// Decoding counters of a call, `None` before its first frame
+    #[must_use]
+    pub fn decode_stats(&self, call_id: CallId) -> Option<DecodeStats> {
+        self.stats.lock().get(&call_id).copied()
+    }
+
+    fn decode_frame(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        codec: VideoCodec,
+        data: &[u8],
+    ) -> Result<VideoFrame, MediaError> {
+        let codec_error = |source: saorsa_webrtc_codecs::CodecError| MediaError::Codec {
+            codec,
+            source: source.into(),
+        };
+        let mut decoders = self.decoders.lock();
+        let key = (call_id, track_id.to_string());
+        if !decoders.contains_key(&key) {
+            let decoder: Box<dyn VideoDecoder> = match codec {
+                VideoCodec::H264 => Box::new(OpenH264Decoder::new().map_err(codec_error)?),
+                VideoCodec::Av1 => Box::new(Av1Decoder::new().map_err(codec_error)?),
+            };
+            decoders.insert(key.clone(), decoder);
+        }
+        decoders
+            .get_mut(&key)
+            .ok_or_else(|| MediaError::TrackNotFound(track_id.to_string()))?
+            .decode(data)
+            .map_err(codec_error)
+    }
+
+    /// Drop the decoder of a remote track, so the next frame starts a fresh
+    /// one; false if the track had none
+    pub fn reset_decoder(&self, call_id: CallId, track_id: &str) -> bool {
+        self.decoders
+            .lock()
+            .remove(&(call_id, track_id.to_string()))
+            .is_some()
+    }
+
+    /// Drop a call's decoders, counters and sinks, telling the sinks it
+    /// ended
+    pub fn end_call(&self, call_id: CallId) {
+        self.decoders.lock().retain(|(id, _), _| *id != call_id);
+        self.stats.lock().remove(&call_id);
+        let sinks = self.sinks.lock().remove(&call_id).unwrap_or_default();
+        for (_, sink) in sinks {
+            sink.on_call_ended(call_id);
+        }
+    }
+}
+
+impl std::fmt::Debug for RemoteVideo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteVideo")
+            .field("calls_with_sinks", &self.sinks.lock().len())
+            .field("decoders", &self.decoders.lock().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(timestamp: u64) -> VideoFrame {
+        VideoFrame {
+            data: vec![0; 12],
+            width: 2,
+            height: 2,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_frames_reach_only_their_calls_sinks() {
+        let remote = RemoteVideo::new();
+        let (call, other) = (CallId::new(), CallId::new());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink_seen = Arc::clone(&seen);
+        let id = remote.add_sink(
+            call,
+            Arc::new(move |_: CallId, track: &str, frame: &VideoFrame| {
+                sink_seen.lock().push((track.to_string(), frame.timestamp));
+            }),
+        );
+
+        remote.deliver(call, "video-0", &frame(1));
+        remote.deliver(other, "video-0", &frame(2));
+        assert_eq!(*seen.lock(), [("video-0".to_string(), 1)]);
+
+        assert!(remote.remove_sink(id));
+        assert!(!remote.remove_sink(id));
+        assert!(!remote.has_sinks(call));
+        remote.deliver(call, "video-0", &frame(3));
+        assert_eq!(seen.lock().len(), 1);
+    }
+
+    struct EndSink(Arc<Mutex<Vec<CallId>>>);
+
+    impl VideoFrameSink for EndSink {
+        fn on_frame(&self, _call_id: CallId, _track_id: &str, _frame: &VideoFrame) {}
+
+        fn on_call_ended(&self, call_id: CallId) {
+            self.0.lock().push(call_id);
+        }
+    }
+
+    #[test]
+    fn test_end_call_notifies_and_drops_sinks() {
+        let remote = RemoteVideo::new();
+        let call = CallId::new();
+        let ended = Arc::new(Mutex::new(Vec::new()));
+        remote.add_sink(call, Arc::new(EndSink(Arc::clone(&ended))));
+
+        remote.end_call(call);
+        assert_eq!(*ended.lock(), [call]);
+        assert!(!remote.has_sinks(call));
+    }
+}
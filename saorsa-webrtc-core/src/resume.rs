@@ -0,0 +1,114 @@
+//! Service state snapshots for crash recovery and upgrades
+//!
+//! Peer connections die with the process, but the signaling sessions
+//! around them need not. A [`ServiceSnapshot`] records which calls were
+//! up, with whom and with what media, plus the persistent rooms. A new
+//! process restoring it re-establishes each call by offering a fresh leg
+//! that replaces the old session, the same path device handoff uses, so
+//! the remote party swaps legs instead of seeing a new incoming call.
+
+use crate::error::ErrorReport;
+use crate::room::Room;
+use crate::types::{CallId, CallState, MediaConstraints};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Format version written by this build
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A call as recorded in a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallSnapshot<I> {
+    /// Call ID, which is also the signaling session ID
+    pub call_id: CallId,
+    /// Remote party
+    pub remote_peer: I,
+    /// State when the snapshot was taken
+    pub state: CallState,
+    /// Media the call carried
+    pub constraints: MediaConstraints,
+}
+
+impl<I> CallSnapshot<I> {
+    /// Whether the call was live enough to be worth re-establishing
+    #[must_use]
+    pub fn is_resumable(&self) -> bool {
+        matches!(
+            self.state,
+            CallState::Calling
+                | CallState::Connecting
+                | CallState::Connected
+                | CallState::Reconnecting
+                | CallState::Parked
+        )
+    }
+}
+
+/// Serializable service state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceSnapshot<I> {
+    /// Format version, see [`SNAPSHOT_VERSION`]
+    pub version: u32,
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// Calls and their sessions
+    pub calls: Vec<CallSnapshot<I>>,
+    /// Persistent rooms
+    pub rooms: Vec<Room>,
+}
+
+impl<I> ServiceSnapshot<I> {
+    /// Snapshot of the given calls and rooms, taken now
+    #[must_use]
+    pub fn new(calls: Vec<CallSnapshot<I>>, rooms: Vec<Room>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            taken_at: Utc::now(),
+            calls,
+            rooms,
+        }
+    }
+}
+
+/// Outcome of re-establishing one snapshotted call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoredCall {
+    /// Call ID from the snapshot
+    pub previous: CallId,
+    /// New leg offered to the remote party, or why none could be
+    pub result: Result<CallId, ErrorReport>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::PeerIdentityString;
+
+    fn call(state: CallState) -> CallSnapshot<PeerIdentityString> {
+        CallSnapshot {
+            call_id: CallId::new(),
+            remote_peer: PeerIdentityString::new("bob"),
+            state,
+            constraints: MediaConstraints::video_call(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let snapshot = ServiceSnapshot::new(vec![call(CallState::Connected)], Vec::new());
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let restored: ServiceSnapshot<PeerIdentityString> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.version, SNAPSHOT_VERSION);
+        assert_eq!(restored.taken_at, snapshot.taken_at);
+        assert_eq!(restored.calls, snapshot.calls);
+    }
+
+    #[test]
+    fn test_only_live_calls_are_resumable() {
+        assert!(call(CallState::Connected).is_resumable());
+        assert!(call(CallState::Parked).is_resumable());
+        assert!(!call(CallState::Ending).is_resumable());
+        assert!(!call(CallState::Failed).is_resumable());
+    }
+}
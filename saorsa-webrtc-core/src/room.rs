@@ -0,0 +1,376 @@
+//! Persistent rooms
+//!
+//! A [`Room`] outlives any single conference: it has a stable ID, a member
+//! list and per-room settings. When a member joins by room ID the room's
+//! live conference is created on demand, and a member who crashed can
+//! rejoin the same conference with the same ID, without a new invitation.
+//! Rooms serialize with serde so applications can persist them.
+
+use crate::conference::{ConferenceId, ParticipantRole};
+use crate::error::{CodedError, ErrorCode};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Stable identifier for a room
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RoomId(pub String);
+
+impl RoomId {
+    /// Create a new random room ID
+    #[must_use]
+    pub fn new() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl Default for RoomId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for RoomId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for RoomId {
+    fn from(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+/// Room errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RoomError {
+    /// Room not found
+    #[error("Room not found: {0}")]
+    NotFound(String),
+
+    /// Participant is not a member of a members-only room
+    #[error("Not a room member: {0}")]
+    NotMember(String),
+
+    /// Room is at capacity
+    #[error("Room is full: {0}")]
+    Full(String),
+
+    /// Acting participant lacks permission
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+}
+
+impl CodedError for RoomError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::NotMember(_) | Self::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            Self::Full(_) => ErrorCode::RoomFailed,
+        }
+    }
+}
+
+/// Per-room settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomSettings {
+    /// Only members may join; others are refused, or parked if the waiting
+    /// room is enabled
+    pub members_only: bool,
+    /// Park non-members in the conference lobby for a host to admit
+    pub waiting_room: bool,
+    /// Maximum number of people in the live conference
+    pub max_participants: Option<usize>,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            members_only: true,
+            waiting_room: false,
+            max_participants: None,
+        }
+    }
+}
+
+/// How a participant joining by room ID should be let in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomAdmission {
+    /// Join the live conference straight away with this role
+    Join(ParticipantRole),
+    /// Wait in the lobby for a host
+    Lobby,
+}
+
+/// A persistent room
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Room {
+    id: RoomId,
+    name: String,
+    owner: String,
+    settings: RoomSettings,
+    members: HashMap<String, ParticipantRole>,
+    created_at: DateTime<Utc>,
+    /// Live session; not persisted since conferences do not survive restarts
+    #[serde(skip)]
+    conference: Option<ConferenceId>,
+}
+
+impl Room {
+    /// Create a room owned by `owner`, who becomes a host member
+    #[must_use]
+    pub fn new(
+        id: RoomId,
+        name: impl Into<String>,
+        owner: impl Into<String>,
+        settings: RoomSettings,
+    ) -> Self {
+        let owner = owner.into();
+        let mut members = HashMap::new();
+        members.insert(owner.clone(), ParticipantRole::Host);
+        Self {
+            id,
+            name: name.into(),
+            owner,
+            settings,
+            members,
+            created_at: Utc::now(),
+            conference: None,
+        }
+    }
+
+    /// Room ID
+    #[must_use]
+    pub fn id(&self) -> &RoomId {
+        &self.id
+    }
+
+    /// Display name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Owner identity
+    #[must_use]
+    pub fn owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// Room settings
+    #[must_use]
+    pub fn settings(&self) -> &RoomSettings {
+        &self.settings
+    }
+
+    /// When the room was created
+    #[must_use]
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Members and the role each joins with
+    pub fn members(&self) -> impl Iterator<Item = (&str, ParticipantRole)> {
+        self.members.iter().map(|(id, role)| (id.as_str(), *role))
+    }
+
+    /// Role a member joins with, if they are a member
+    #[must_use]
+    pub fn member_role(&self, participant_id: &str) -> Option<ParticipantRole> {
+        self.members.get(participant_id).copied()
+    }
+
+    /// Live conference for this room, if one is running
+    #[must_use]
+    pub fn conference(&self) -> Option<ConferenceId> {
+        self.conference
+    }
+
+    /// Record the live conference, or clear it when the session ends
+    pub fn set_conference(&mut self, conference_id: Option<ConferenceId>) {
+        self.conference = conference_id;
+    }
+
+    /// Change the room settings
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host member
+    pub fn update_settings(
+        &mut self,
+        actor_id: &str,
+        settings: RoomSettings,
+    ) -> Result<(), RoomError> {
+        self.require_host(actor_id)?;
+        self.settings = settings;
+        Ok(())
+    }
+
+    /// Add a member, or change an existing member's role
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host member
+    pub fn add_member(
+        &mut self,
+        actor_id: &str,
+        participant_id: impl Into<String>,
+        role: ParticipantRole,
+    ) -> Result<(), RoomError> {
+        self.require_host(actor_id)?;
+        self.members.insert(participant_id.into(), role);
+        Ok(())
+    }
+
+    /// Remove a member
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host member, the participant is
+    /// not a member, or the participant is the owner
+    pub fn remove_member(&mut self, actor_id: &str, participant_id: &str) -> Result<(), RoomError> {
+        self.require_host(actor_id)?;
+        if participant_id == self.owner {
+            return Err(RoomError::PermissionDenied(
+                "the owner cannot be removed".to_string(),
+            ));
+        }
+        self.members
+            .remove(participant_id)
+            .map(|_| ())
+            .ok_or_else(|| RoomError::NotMember(participant_id.to_string()))
+    }
+
+    /// Decide how a participant joining by room ID is let in
+    ///
+    /// `present` is the number of people already in the live conference.
+    /// Participants still listed in the conference (e.g. after a crash) are
+    /// rejoining and should be let straight back in without asking.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the room is full, or it is members-only without a
+    /// waiting room and the participant is not a member
+    pub fn admission(
+        &self,
+        participant_id: &str,
+        present: usize,
+    ) -> Result<RoomAdmission, RoomError> {
+        if self
+            .settings
+            .max_participants
+            .is_some_and(|max| present >= max)
+        {
+            return Err(RoomError::Full(self.id.to_string()));
+        }
+
+        match self.member_role(participant_id) {
+            Some(role) => Ok(RoomAdmission::Join(role)),
+            None if self.settings.waiting_room => Ok(RoomAdmission::Lobby),
+            None if self.settings.members_only => {
+                Err(RoomError::NotMember(participant_id.to_string()))
+            }
+            None => Ok(RoomAdmission::Join(ParticipantRole::Speaker)),
+        }
+    }
+
+    fn require_host(&self, actor_id: &str) -> Result<(), RoomError> {
+        if self.member_role(actor_id) == Some(ParticipantRole::Host) {
+            Ok(())
+        } else {
+            Err(RoomError::PermissionDenied(format!(
+                "{} is not a room host",
+                actor_id
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room(settings: RoomSettings) -> Room {
+        let mut room = Room::new(RoomId::from("standup"), "Daily standup", "host", settings);
+        room.add_member("host", "alice", ParticipantRole::Speaker)
+            .unwrap();
+        room
+    }
+
+    #[test]
+    fn test_members_join_with_their_role() {
+        let room = room(RoomSettings::default());
+        assert_eq!(
+            room.admission("host", 0),
+            Ok(RoomAdmission::Join(ParticipantRole::Host))
+        );
+        assert_eq!(
+            room.admission("alice", 1),
+            Ok(RoomAdmission::Join(ParticipantRole::Speaker))
+        );
+        assert_eq!(
+            room.admission("mallory", 1),
+            Err(RoomError::NotMember("mallory".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_non_members_wait_or_join_by_settings() {
+        let lobby = room(RoomSettings {
+            waiting_room: true,
+            ..Default::default()
+        });
+        assert_eq!(lobby.admission("bob", 1), Ok(RoomAdmission::Lobby));
+
+        let open = room(RoomSettings {
+            members_only: false,
+            ..Default::default()
+        });
+        assert_eq!(
+            open.admission("bob", 1),
+            Ok(RoomAdmission::Join(ParticipantRole::Speaker))
+        );
+    }
+
+    #[test]
+    fn test_full_room_refuses_newcomers() {
+        let room = room(RoomSettings {
+            max_participants: Some(2),
+            ..Default::default()
+        });
+        assert!(matches!(
+            room.admission("alice", 2),
+            Err(RoomError::Full(_))
+        ));
+        assert!(room.admission("alice", 1).is_ok());
+    }
+
+    #[test]
+    fn test_membership_requires_host() {
+        let mut room = room(RoomSettings::default());
+        assert!(matches!(
+            room.add_member("alice", "bob", ParticipantRole::Speaker),
+            Err(RoomError::PermissionDenied(_))
+        ));
+        assert!(room.remove_member("host", "host").is_err());
+        room.remove_member("host", "alice").unwrap();
+        assert_eq!(room.member_role("alice"), None);
+    }
+
+    #[test]
+    fn test_room_persists_without_live_conference() {
+        let mut room = room(RoomSettings::default());
+        room.set_conference(Some(ConferenceId::new()));
+
+        let json = serde_json::to_string(&room).unwrap();
+        let restored: Room = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.id(), room.id());
+        assert_eq!(
+            restored.member_role("alice"),
+            Some(ParticipantRole::Speaker)
+        );
+        assert_eq!(restored.conference(), None);
+    }
+}
@@ -0,0 +1,499 @@
+//! Selective forwarding of conference media
+//!
+//! A full mesh sends every participant's media to every other participant,
+//! so uplink grows with the conference. A [`MediaRouter`] runs on one node
+//! instead: each participant sends its audio and simulcast video layers
+//! there once over QUIC, and the router forwards each packet, still
+//! encoded, to the participants who should get it. Nothing is decoded, so
+//! one node can carry conferences far larger than a mesh.
+//!
+//! Each receiver names whom it hears and which layer it wants of whom it
+//! sees, usually from
+//! [`Conference::forwarding_layers`](crate::conference::Conference::forwarding_layers).
+//! A wanted layer is served by the best one being published that is not
+//! above it, or the lowest there is, and every receiver can be capped to
+//! a layer its downlink can take. Copies are rewritten by a
+//! [`PacketRelay`], so a receiver moved to another layer keeps seeing one
+//! continuous stream.
+//!
+//! Every participant is given one output SSRC for its audio and one for its
+//! video, the same for every receiver, so receivers can tell senders apart
+//! with [`MediaRouter::sender_of`].
+
+use crate::active_speaker::SimulcastLayer;
+use crate::error::{CodedError, ErrorCode};
+use crate::quic_bridge::RtpPacket;
+use crate::relay::{PacketRelay, RelayStats};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use thiserror::Error;
+
+/// First output SSRC handed out; each participant takes two
+pub const ROUTER_SSRC_BASE: u32 = 0x5F0A_0000;
+
+/// Media router errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RouterError {
+    /// No participant with this ID is routed
+    #[error("Participant not routed: {0}")]
+    UnknownParticipant(String),
+
+    /// Another participant already publishes a stream with this SSRC
+    #[error("SSRC {0} is already published by another participant")]
+    SsrcInUse(u32),
+}
+
+impl CodedError for RouterError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnknownParticipant(_) => ErrorCode::PeerNotFound,
+            Self::SsrcInUse(_) => ErrorCode::InvalidInput,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RoutedParticipant {
+    /// Input SSRC of the participant's audio
+    audio_ssrc: Option<u32>,
+    /// Input SSRC of each video layer being published
+    layers: BTreeMap<SimulcastLayer, u32>,
+    /// SSRC receivers get this participant's audio on
+    audio_out: u32,
+    /// SSRC receivers get this participant's video on
+    video_out: u32,
+    /// Participants whose audio this one receives
+    hears: HashSet<String>,
+    /// Participants whose video this one receives, and the layer wanted
+    watches: HashMap<String, SimulcastLayer>,
+    /// Best layer this participant receives of anyone
+    max_layer: SimulcastLayer,
+}
+
+impl RoutedParticipant {
+    /// Published layer serving a receiver that wants `wanted`: the best one
+    /// not above it, or the lowest there is
+    fn serving(&self, wanted: SimulcastLayer) -> Option<(SimulcastLayer, u32)> {
+        self.layers
+            .range(..=wanted)
+            .next_back()
+            .or_else(|| self.layers.iter().next())
+            .map(|(layer, ssrc)| (*layer, *ssrc))
+    }
+}
+
+/// Selective forwarding unit for one conference
+#[derive(Debug)]
+pub struct MediaRouter {
+    participants: HashMap<String, RoutedParticipant>,
+    /// Participant publishing each input SSRC
+    inputs: HashMap<u32, String>,
+    next_ssrc: u32,
+    relay: PacketRelay,
+}
+
+impl Default for MediaRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MediaRouter {
+    /// Create a router with no participants
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            participants: HashMap::new(),
+            inputs: HashMap::new(),
+            next_ssrc: ROUTER_SSRC_BASE,
+            relay: PacketRelay::new(),
+        }
+    }
+
+    /// Route a participant, who receives nothing until told whom to hear
+    /// and see
+    ///
+    /// Returns `false` if the participant was already routed.
+    pub fn add_participant(&mut self, participant_id: &str) -> bool {
+        if self.participants.contains_key(participant_id) {
+            return false;
+        }
+        let audio_out = self.next_ssrc;
+        let video_out = self.next_ssrc.wrapping_add(1);
+        self.next_ssrc = self.next_ssrc.wrapping_add(2);
+        self.participants.insert(
+            participant_id.to_string(),
+            RoutedParticipant {
+                audio_ssrc: None,
+                layers: BTreeMap::new(),
+                audio_out,
+                video_out,
+                hears: HashSet::new(),
+                watches: HashMap::new(),
+                max_layer: SimulcastLayer::High,
+            },
+        );
+        true
+    }
+
+    /// Stop routing a participant's media, both ways
+    pub fn remove_participant(&mut self, participant_id: &str) -> bool {
+        let Some(removed) = self.participants.remove(participant_id) else {
+            return false;
+        };
+        for ssrc in removed.audio_ssrc.iter().chain(removed.layers.values()) {
+            self.relay.remove_source(*ssrc);
+            self.inputs.remove(ssrc);
+        }
+        self.relay.remove_receiver(participant_id);
+        for participant in self.participants.values_mut() {
+            participant.hears.remove(participant_id);
+            participant.watches.remove(participant_id);
+        }
+        true
+    }
+
+    /// Routed participants
+    pub fn participants(&self) -> impl Iterator<Item = &str> {
+        self.participants.keys().map(String::as_str)
+    }
+
+    /// Publish a participant's audio from the stream with SSRC `ssrc`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the participant is not routed or another one
+    /// publishes `ssrc`
+    pub fn publish_audio(&mut self, participant_id: &str, ssrc: u32) -> Result<(), RouterError> {
+        self.claim(participant_id, ssrc)?;
+        let Some(publisher) = self.participants.get_mut(participant_id) else {
+            return Err(RouterError::UnknownParticipant(participant_id.to_string()));
+        };
+        let previous = publisher.audio_ssrc.replace(ssrc);
+        let output = publisher.audio_out;
+        if let Some(previous) = previous {
+            self.release(previous, ssrc);
+        }
+        let receivers: Vec<String> = self
+            .participants
+            .iter()
+            .filter(|(_, receiver)| receiver.hears.contains(participant_id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for receiver in receivers {
+            self.relay.subscribe(&receiver, ssrc, output);
+        }
+        Ok(())
+    }
+
+    /// Publish video `layer` of a participant from the stream with SSRC
+    /// `ssrc`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the participant is not routed or another one
+    /// publishes `ssrc`
+    pub fn publish_layer(
+        &mut self,
+        participant_id: &str,
+        layer: SimulcastLayer,
+        ssrc: u32,
+    ) -> Result<(), RouterError> {
+        self.claim(participant_id, ssrc)?;
+        let Some(publisher) = self.participants.get_mut(participant_id) else {
+            return Err(RouterError::UnknownParticipant(participant_id.to_string()));
+        };
+        let previous = publisher.layers.insert(layer, ssrc);
+        if let Some(previous) = previous {
+            self.release(previous, ssrc);
+        }
+        let receivers: Vec<String> = self
+            .participants
+            .iter()
+            .filter(|(_, receiver)| receiver.watches.contains_key(participant_id))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for receiver in receivers {
+            self.route_video(&receiver, participant_id);
+        }
+        Ok(())
+    }
+
+    /// Set whose audio `receiver` gets
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the receiver or a sender is not routed
+    pub fn set_audio_from(
+        &mut self,
+        receiver: &str,
+        senders: &[String],
+    ) -> Result<(), RouterError> {
+        self.require(receiver)?;
+        for sender in senders {
+            self.require(sender)?;
+        }
+        let hears: HashSet<String> = senders
+            .iter()
+            .filter(|sender| sender.as_str() != receiver)
+            .cloned()
+            .collect();
+        let Some(state) = self.participants.get_mut(receiver) else {
+            return Ok(());
+        };
+        let previous = std::mem::replace(&mut state.hears, hears.clone());
+
+        for sender in previous.difference(&hears) {
+            if let Some(publisher) = self.participants.get(sender) {
+                self.relay.unsubscribe(receiver, publisher.audio_out);
+            }
+        }
+        for sender in &hears {
+            if let Some((Some(ssrc), output)) = self
+                .participants
+                .get(sender)
+                .map(|publisher| (publisher.audio_ssrc, publisher.audio_out))
+            {
+                self.relay.subscribe(receiver, ssrc, output);
+            }
+        }
+        Ok(())
+    }
+
+    /// Set whose video `receiver` gets, and the layer it wants of each
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the receiver or a sender is not routed
+    pub fn set_video(
+        &mut self,
+        receiver: &str,
+        layers: &[(String, SimulcastLayer)],
+    ) -> Result<(), RouterError> {
+        self.require(receiver)?;
+        for (sender, _) in layers {
+            self.require(sender)?;
+        }
+        let watches: HashMap<String, SimulcastLayer> = layers
+            .iter()
+            .filter(|(sender, _)| sender.as_str() != receiver)
+            .cloned()
+            .collect();
+        let Some(state) = self.participants.get_mut(receiver) else {
+            return Ok(());
+        };
+        let previous = std::mem::replace(&mut state.watches, watches.clone());
+
+        for sender in previous
+            .keys()
+            .filter(|sender| !watches.contains_key(*sender))
+        {
+            if let Some(publisher) = self.participants.get(sender) {
+                self.relay.unsubscribe(receiver, publisher.video_out);
+            }
+        }
+        for sender in watches.keys() {
+            self.route_video(receiver, sender);
+        }
+        Ok(())
+    }
+
+    /// Cap the video layer `receiver` gets of anyone, e.g. to what its
+    /// downlink can take
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the receiver is not routed
+    pub fn set_max_layer(
+        &mut self,
+        receiver: &str,
+        layer: SimulcastLayer,
+    ) -> Result<(), RouterError> {
+        let Some(state) = self.participants.get_mut(receiver) else {
+            return Err(RouterError::UnknownParticipant(receiver.to_string()));
+        };
+        state.max_layer = layer;
+        let senders: Vec<String> = state.watches.keys().cloned().collect();
+        for sender in senders {
+            self.route_video(receiver, &sender);
+        }
+        Ok(())
+    }
+
+    /// Video layer `receiver` gets of `sender`, or would once one is
+    /// published
+    #[must_use]
+    pub fn layer(&self, receiver: &str, sender: &str) -> Option<SimulcastLayer> {
+        let wanted = self.wanted(receiver, sender)?;
+        let publisher = self.participants.get(sender)?;
+        Some(publisher.serving(wanted).map_or(wanted, |(layer, _)| layer))
+    }
+
+    /// Participant publishing the input stream with SSRC `ssrc`
+    #[must_use]
+    pub fn publisher_of(&self, ssrc: u32) -> Option<&str> {
+        self.inputs.get(&ssrc).map(String::as_str)
+    }
+
+    /// Participant whose media receivers get on output SSRC `ssrc`
+    #[must_use]
+    pub fn sender_of(&self, ssrc: u32) -> Option<&str> {
+        self.participants
+            .iter()
+            .find(|(_, p)| p.audio_out == ssrc || p.video_out == ssrc)
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// Copies of a published packet for every receiver that gets it
+    #[must_use]
+    pub fn route(&mut self, packet: &RtpPacket) -> Vec<(String, RtpPacket)> {
+        self.relay.forward(packet)
+    }
+
+    /// Forwarding counters
+    #[must_use]
+    pub fn stats(&self) -> RelayStats {
+        self.relay.stats()
+    }
+
+    fn require(&self, participant_id: &str) -> Result<(), RouterError> {
+        if self.participants.contains_key(participant_id) {
+            Ok(())
+        } else {
+            Err(RouterError::UnknownParticipant(participant_id.to_string()))
+        }
+    }
+
+    /// Record `participant_id` as the publisher of `ssrc`
+    fn claim(&mut self, participant_id: &str, ssrc: u32) -> Result<(), RouterError> {
+        self.require(participant_id)?;
+        match self.inputs.get(&ssrc) {
+            Some(owner) if owner != participant_id => Err(RouterError::SsrcInUse(ssrc)),
+            _ => {
+                self.inputs.insert(ssrc, participant_id.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Forget an input stream replaced by `replacement`, unless the
+    /// participant still publishes it as another stream
+    fn release(&mut self, previous: u32, replacement: u32) {
+        if previous == replacement {
+            return;
+        }
+        let still_published = self.participants.values().any(|p| {
+            p.audio_ssrc == Some(previous) || p.layers.values().any(|ssrc| *ssrc == previous)
+        });
+        if !still_published {
+            self.inputs.remove(&previous);
+        }
+    }
+
+    /// Layer `receiver` wants of `sender`, within its cap
+    fn wanted(&self, receiver: &str, sender: &str) -> Option<SimulcastLayer> {
+        let state = self.participants.get(receiver)?;
+        state
+            .watches
+            .get(sender)
+            .map(|layer| (*layer).min(state.max_layer))
+    }
+
+    fn route_video(&mut self, receiver: &str, sender: &str) {
+        let Some(wanted) = self.wanted(receiver, sender) else {
+            return;
+        };
+        let Some(publisher) = self.participants.get(sender) else {
+            return;
+        };
+        let output = publisher.video_out;
+        if let Some((_, ssrc)) = publisher.serving(wanted) {
+            self.relay.subscribe(receiver, ssrc, output);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quic_bridge::StreamType;
+
+    fn packet(ssrc: u32, seq: u16, stream_type: StreamType) -> RtpPacket {
+        RtpPacket::new(
+            96,
+            seq,
+            u32::from(seq) * 3000,
+            ssrc,
+            vec![0xAB; 100],
+            stream_type,
+        )
+        .unwrap()
+    }
+
+    fn receivers(routed: &[(String, RtpPacket)]) -> Vec<&str> {
+        let mut receivers: Vec<&str> = routed.iter().map(|(id, _)| id.as_str()).collect();
+        receivers.sort_unstable();
+        receivers
+    }
+
+    #[test]
+    fn test_router_forwards_chosen_layers() {
+        let mut router = MediaRouter::new();
+        for id in ["alice", "bob", "carol"] {
+            assert!(router.add_participant(id));
+        }
+        assert!(!router.add_participant("alice"));
+        assert_eq!(
+            router.publish_audio("dave", 1),
+            Err(RouterError::UnknownParticipant("dave".to_string()))
+        );
+
+        router.publish_audio("alice", 1).unwrap();
+        router
+            .publish_layer("alice", SimulcastLayer::Low, 10)
+            .unwrap();
+        router
+            .publish_layer("alice", SimulcastLayer::High, 12)
+            .unwrap();
+        assert_eq!(
+            router.publish_audio("bob", 10),
+            Err(RouterError::SsrcInUse(10))
+        );
+        assert_eq!(router.publisher_of(12), Some("alice"));
+
+        let alice = vec!["alice".to_string()];
+        router.set_audio_from("bob", &alice).unwrap();
+        router
+            .set_video("bob", &[("alice".to_string(), SimulcastLayer::High)])
+            .unwrap();
+        // Nothing at Medium, so carol gets the layer below
+        router
+            .set_video("carol", &[("alice".to_string(), SimulcastLayer::Medium)])
+            .unwrap();
+        assert_eq!(router.layer("carol", "alice"), Some(SimulcastLayer::Low));
+
+        assert_eq!(
+            receivers(&router.route(&packet(1, 1, StreamType::Audio))),
+            ["bob"]
+        );
+        assert_eq!(
+            receivers(&router.route(&packet(12, 1, StreamType::Video))),
+            ["bob"]
+        );
+        let low = router.route(&packet(10, 1, StreamType::Video));
+        assert_eq!(receivers(&low), ["carol"]);
+        assert_eq!(router.sender_of(low[0].1.ssrc), Some("alice"));
+
+        // Capping bob's downlink moves him to the low layer
+        router.set_max_layer("bob", SimulcastLayer::Low).unwrap();
+        assert!(router.route(&packet(12, 2, StreamType::Video)).is_empty());
+        assert_eq!(
+            receivers(&router.route(&packet(10, 2, StreamType::Video))),
+            ["bob", "carol"]
+        );
+
+        assert!(router.remove_participant("alice"));
+        assert!(router.route(&packet(10, 3, StreamType::Video)).is_empty());
+        assert_eq!(router.publisher_of(10), None);
+        assert_eq!(router.layer("bob", "alice"), None);
+    }
+}
@@ -0,0 +1,291 @@
+//! Runtime topology for background work
+//!
+//! By default every background task runs on the tokio runtime the service
+//! was created from. Embedders that own threading (mobile apps, hosts with
+//! their own executors) can pass in their own runtime handles instead, or
+//! build [`DedicatedRuntimes`] so that signaling and video work never
+//! delays the audio path.
+//!
+//! Thread priorities are platform APIs (`setThreadPriority` on Android,
+//! QoS classes on Apple platforms, `SCHED_FIFO` on Linux), so they are
+//! applied through thread-start hooks supplied by the embedder.
+
+use crate::error::{CodedError, ErrorCode};
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::runtime::{Builder, Handle, Runtime, TryCurrentError};
+use tokio::task::JoinHandle;
+
+/// Callback run on each worker thread as it starts
+pub type ThreadHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Runtime errors
+#[derive(Error, Debug)]
+pub enum RuntimeError {
+    /// No runtime was supplied and none is running on this thread
+    #[error("No tokio runtime available: {0}")]
+    NoRuntime(#[from] TryCurrentError),
+
+    /// A dedicated runtime could not be built
+    #[error("Failed to build {name} runtime: {source}")]
+    Build {
+        /// Which runtime failed
+        name: &'static str,
+        /// Underlying I/O error
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl CodedError for RuntimeError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::Internal
+    }
+}
+
+/// Runtimes the library spawns its background tasks on
+#[derive(Debug, Clone)]
+pub struct RuntimeTopology {
+    signaling: Handle,
+    media: Handle,
+    audio: Handle,
+}
+
+impl RuntimeTopology {
+    /// Run everything on the runtime driving the calling thread
+    ///
+    /// # Errors
+    ///
+    /// Returns error if called outside a tokio runtime
+    pub fn current() -> Result<Self, RuntimeError> {
+        Ok(Self::shared(Handle::try_current()?))
+    }
+
+    /// Run everything on one embedder-supplied runtime
+    #[must_use]
+    pub fn shared(handle: Handle) -> Self {
+        Self {
+            signaling: handle.clone(),
+            media: handle.clone(),
+            audio: handle,
+        }
+    }
+
+    /// Use separate runtimes for signaling, video and audio work
+    #[must_use]
+    pub fn split(signaling: Handle, media: Handle, audio: Handle) -> Self {
+        Self {
+            signaling,
+            media,
+            audio,
+        }
+    }
+
+    /// Runtime for signaling and transport tasks
+    #[must_use]
+    pub fn signaling(&self) -> &Handle {
+        &self.signaling
+    }
+
+    /// Runtime for video encoding and packet forwarding
+    #[must_use]
+    pub fn media(&self) -> &Handle {
+        &self.media
+    }
+
+    /// Runtime for the latency-sensitive audio path
+    #[must_use]
+    pub fn audio(&self) -> &Handle {
+        &self.audio
+    }
+
+    /// Spawn a task on the signaling runtime
+    pub fn spawn_signaling<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.signaling.spawn(future)
+    }
+
+    /// Spawn a task on the media runtime
+    pub fn spawn_media<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.media.spawn(future)
+    }
+
+    /// Spawn a task on the audio runtime
+    pub fn spawn_audio<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.audio.spawn(future)
+    }
+}
+
+/// Settings for [`DedicatedRuntimes`]
+#[derive(Clone)]
+pub struct RuntimeConfig {
+    /// Worker threads for signaling and transport
+    pub signaling_threads: usize,
+    /// Worker threads for video
+    pub media_threads: usize,
+    /// Worker threads for audio
+    pub audio_threads: usize,
+    /// Run on each media thread as it starts
+    pub on_media_thread_start: Option<ThreadHook>,
+    /// Run on each audio thread as it starts, e.g. to raise its priority
+    pub on_audio_thread_start: Option<ThreadHook>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            signaling_threads: 1,
+            media_threads: 2,
+            audio_threads: 1,
+            on_media_thread_start: None,
+            on_audio_thread_start: None,
+        }
+    }
+}
+
+impl fmt::Debug for RuntimeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RuntimeConfig")
+            .field("signaling_threads", &self.signaling_threads)
+            .field("media_threads", &self.media_threads)
+            .field("audio_threads", &self.audio_threads)
+            .field(
+                "on_media_thread_start",
+                &self.on_media_thread_start.is_some(),
+            )
+            .field(
+                "on_audio_thread_start",
+                &self.on_audio_thread_start.is_some(),
+            )
+            .finish()
+    }
+}
+
+impl RuntimeConfig {
+    /// Build the runtimes
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a runtime's threads cannot be spawned
+    pub fn build(self) -> Result<DedicatedRuntimes, RuntimeError> {
+        Ok(DedicatedRuntimes {
+            signaling: build_runtime("signaling", self.signaling_threads, None)?,
+            media: build_runtime("media", self.media_threads, self.on_media_thread_start)?,
+            audio: build_runtime("audio", self.audio_threads, self.on_audio_thread_start)?,
+        })
+    }
+}
+
+fn build_runtime(
+    name: &'static str,
+    threads: usize,
+    on_start: Option<ThreadHook>,
+) -> Result<Runtime, RuntimeError> {
+    let mut builder = Builder::new_multi_thread();
+    builder
+        .worker_threads(threads.max(1))
+        .thread_name(format!("saorsa-{name}"))
+        .enable_all();
+    if let Some(hook) = on_start {
+        builder.on_thread_start(move || hook());
+    }
+    builder
+        .build()
+        .map_err(|source| RuntimeError::Build { name, source })
+}
+
+/// Runtimes owned by the library, one per kind of work
+///
+/// Dropping a runtime blocks until its tasks stop, which panics inside an
+/// async context; use [`DedicatedRuntimes::shutdown_background`] there.
+#[derive(Debug)]
+pub struct DedicatedRuntimes {
+    signaling: Runtime,
+    media: Runtime,
+    audio: Runtime,
+}
+
+impl DedicatedRuntimes {
+    /// Handles for passing to the service
+    #[must_use]
+    pub fn topology(&self) -> RuntimeTopology {
+        RuntimeTopology::split(
+            self.signaling.handle().clone(),
+            self.media.handle().clone(),
+            self.audio.handle().clone(),
+        )
+    }
+
+    /// Shut down without waiting for running tasks
+    pub fn shutdown_background(self) {
+        self.signaling.shutdown_background();
+        self.media.shutdown_background();
+        self.audio.shutdown_background();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_current_shares_one_runtime() {
+        let topology = RuntimeTopology::current().unwrap();
+        assert_eq!(topology.spawn_signaling(async { 1 }).await.unwrap(), 1);
+        assert_eq!(topology.spawn_media(async { 2 }).await.unwrap(), 2);
+        assert_eq!(topology.spawn_audio(async { 3 }).await.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_current_outside_runtime_fails() {
+        assert!(matches!(
+            RuntimeTopology::current(),
+            Err(RuntimeError::NoRuntime(_))
+        ));
+    }
+
+    #[test]
+    fn test_dedicated_runtimes_run_audio_on_own_threads() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&started);
+        let runtimes = RuntimeConfig {
+            audio_threads: 1,
+            on_audio_thread_start: Some(Arc::new(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })),
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let topology = runtimes.topology();
+        let thread_name = || async { std::thread::current().name().map(str::to_string) };
+
+        let audio = topology
+            .audio()
+            .block_on(topology.spawn_audio(thread_name()))
+            .unwrap();
+        let signaling = topology
+            .signaling()
+            .block_on(topology.spawn_signaling(thread_name()))
+            .unwrap();
+        assert_eq!(audio.as_deref(), Some("saorsa-audio"));
+        assert_eq!(signaling.as_deref(), Some("saorsa-signaling"));
+        assert!(started.load(Ordering::SeqCst) >= 1);
+
+        runtimes.shutdown_background();
+    }
+}
@@ -0,0 +1,205 @@
+//! saorsa-core integration
+//!
+//! Applications built on saorsa-core already run a [`P2PNode`] that finds
+//! peers through the DHT. [`DhtSignaling`] carries call signaling over that
+//! node, and [`DhtPeer`] names peers by their saorsa-core peer ID, so a
+//! [`DhtWebRtcService`] needs no glue:
+//!
+//! ```rust,no_run
+//! use saorsa_webrtc_core::saorsa_dht::{DhtSignaling, DhtWebRtcService};
+//! use saorsa_webrtc_core::SignalingHandler;
+//! use std::sync::Arc;
+//!
+//! # async fn example(node: Arc<saorsa_core::P2PNode>) -> anyhow::Result<()> {
+//! let signaling = Arc::new(SignalingHandler::new(Arc::new(DhtSignaling::new(node))));
+//! let service = DhtWebRtcService::new(signaling, Default::default()).await?;
+//! service.start().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Signaling travels as node messages on the [`SIGNALING_PROTOCOL`] topic;
+//! other topics are left to the application.
+
+use crate::error::{BoxError, CodedError, ErrorCode};
+use crate::identity::PeerIdentity;
+use crate::service::WebRtcService;
+use crate::signaling::{SignalingMessage, SignalingTransport};
+use async_trait::async_trait;
+use saorsa_core::{P2PEvent, P2PNode, PeerId};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::{broadcast, Mutex};
+
+/// Node message topic signaling is sent on
+pub const SIGNALING_PROTOCOL: &str = "/saorsa/webrtc/signaling/1";
+
+/// WebRTC service signaling over a saorsa-core node
+pub type DhtWebRtcService = WebRtcService<DhtPeer, DhtSignaling>;
+
+/// A peer named by its saorsa-core peer ID
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DhtPeer(pub PeerId);
+
+impl DhtPeer {
+    /// The saorsa-core peer ID
+    #[must_use]
+    pub fn peer_id(&self) -> &PeerId {
+        &self.0
+    }
+}
+
+impl From<PeerId> for DhtPeer {
+    fn from(peer_id: PeerId) -> Self {
+        Self(peer_id)
+    }
+}
+
+impl fmt::Display for DhtPeer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for DhtPeer {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.to_string()))
+    }
+}
+
+impl PeerIdentity for DhtPeer {
+    fn to_string_repr(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn from_string_repr(s: &str) -> anyhow::Result<Self> {
+        if s.is_empty() {
+            anyhow::bail!("Empty saorsa-core peer ID");
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+/// Errors signaling over a saorsa-core node
+#[derive(Error, Debug)]
+pub enum DhtSignalingError {
+    /// The node failed to send
+    #[error("Failed to send over saorsa-core")]
+    Send(#[source] BoxError),
+
+    /// A message on the signaling topic was not a signaling message
+    #[error("Malformed signaling message")]
+    Malformed(#[source] serde_json::Error),
+
+    /// The node shut down
+    #[error("saorsa-core node stopped")]
+    Closed,
+}
+
+impl CodedError for DhtSignalingError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Send(_) => ErrorCode::SendFailed,
+            Self::Malformed(_) => ErrorCode::MalformedMessage,
+            Self::Closed => ErrorCode::TransportNotStarted,
+        }
+    }
+}
+
+/// Signaling transport over a saorsa-core node
+pub struct DhtSignaling {
+    node: Arc<P2PNode>,
+    events: Mutex<broadcast::Receiver<P2PEvent>>,
+}
+
+impl DhtSignaling {
+    /// Signal over `node`, receiving from now on
+    #[must_use]
+    pub fn new(node: Arc<P2PNode>) -> Self {
+        let events = Mutex::new(node.subscribe_events());
+        Self { node, events }
+    }
+
+    /// The node signaling runs over
+    #[must_use]
+    pub fn node(&self) -> &Arc<P2PNode> {
+        &self.node
+    }
+}
+
+#[async_trait]
+impl SignalingTransport for DhtSignaling {
+    type PeerId = DhtPeer;
+    type Error = DhtSignalingError;
+
+    async fn send_message(
+        &self,
+        peer: &DhtPeer,
+        message: SignalingMessage,
+    ) -> Result<(), DhtSignalingError> {
+        let data = serde_json::to_vec(&message).map_err(DhtSignalingError::Malformed)?;
+        self.node
+            .send_message(peer.peer_id(), SIGNALING_PROTOCOL, data)
+            .await
+            .map_err(|e| DhtSignalingError::Send(e.into()))
+    }
+
+    async fn receive_message(&self) -> Result<(DhtPeer, SignalingMessage), DhtSignalingError> {
+        let mut events = self.events.lock().await;
+        loop {
+            match events.recv().await {
+                Ok(P2PEvent::Message {
+                    topic,
+                    source,
+                    data,
+                }) if topic == SIGNALING_PROTOCOL => {
+                    let message =
+                        serde_json::from_slice(&data).map_err(DhtSignalingError::Malformed)?;
+                    return Ok((DhtPeer(source), message));
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("Missed {} saorsa-core events while busy", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(DhtSignalingError::Closed);
+                }
+            }
+        }
+    }
+
+    async fn discover_peer_endpoint(
+        &self,
+        peer: &DhtPeer,
+    ) -> Result<Option<SocketAddr>, DhtSignalingError> {
+        let Some(info) = self.node.peer_info(peer.peer_id()).await else {
+            return Ok(None);
+        };
+        Ok(info
+            .addresses
+            .iter()
+            .find_map(|address| address.parse().ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dht_peer_round_trips() {
+        let peer = DhtPeer::from_string_repr("a3f1c09e").unwrap();
+        assert_eq!(peer.to_string(), "a3f1c09e");
+        assert_eq!("a3f1c09e".parse::<DhtPeer>().unwrap(), peer);
+        assert_eq!(serde_json::to_string(&peer).unwrap(), "\"a3f1c09e\"");
+        assert!(DhtPeer::from_string_repr("").is_err());
+    }
+}
@@ -0,0 +1,456 @@
+//! Screen and window capture
+//!
+//! With the `screen-capture` feature, displays and windows are listed and
+//! captured through scap, which uses PipeWire and the xdg-desktop-portal
+//! ScreenCast interface on Linux, ScreenCaptureKit on macOS and Windows
+//! Graphics Capture on Windows. Captured frames are scaled to the screen
+//! share track's size, encoded by its pipeline and written to the track
+//! until the [`ScreenCapturer`] handle is dropped. If a [`MediaWatchdog`]
+//! finds the encoder stalled, it is asked for a keyframe. Capture itself is
+//! not watched, as a screen that does not change yields no frames.
+//!
+//! Without the feature no sources are listed and capture fails.
+
+use crate::media::{MediaError, VideoTrack};
+use crate::watchdog::MediaWatchdog;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Whether a capture source is a whole display or a single window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScreenSourceKind {
+    /// Full screen
+    Display,
+    /// One application window
+    Window,
+}
+
+/// Display or window that can be captured
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenSource {
+    /// Source identifier, e.g. `display:1` or `window:42`
+    pub id: String,
+    /// Display name or window title
+    pub name: String,
+    /// Display or window
+    pub kind: ScreenSourceKind,
+}
+
+impl ScreenSource {
+    /// Identifier for the platform source `raw_id` of `kind`
+    #[must_use]
+    pub fn make_id(kind: ScreenSourceKind, raw_id: u32) -> String {
+        match kind {
+            ScreenSourceKind::Display => format!("display:{raw_id}"),
+            ScreenSourceKind::Window => format!("window:{raw_id}"),
+        }
+    }
+}
+
+/// Screen capture settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenCaptureConfig {
+    /// Frames captured per second
+    pub fps: u32,
+    /// Whether the mouse cursor is drawn into frames
+    pub show_cursor: bool,
+}
+
+impl Default for ScreenCaptureConfig {
+    fn default() -> Self {
+        // Desktop content changes rarely; match the AV1 screen share tuning
+        Self {
+            fps: 15,
+            show_cursor: true,
+        }
+    }
+}
+
+/// One of the screen shares a call carries, as told to the remote party
+///
+/// A call can carry several at once, such as one per monitor, each in its
+/// own stream and paused on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenShare {
+    /// Track carrying the screen share
+    pub track_id: String,
+    /// Stream the track is sent in
+    pub stream_id: String,
+    /// What is shared, for the remote party to show, e.g. a window title
+    pub label: String,
+    /// Whether the sharer paused it
+    pub paused: bool,
+}
+
+/// Convert a BGRA frame to packed RGB at `out_width` x `out_height`
+///
+/// Frames are scaled by nearest neighbour so that windows which change
+/// size keep feeding the encoder frames of the size it was set up for.
+/// Returns `None` if `bgra` is smaller than `width` x `height` pixels.
+#[must_use]
+pub fn bgra_to_rgb(
+    bgra: &[u8],
+    width: u32,
+    height: u32,
+    out_width: u32,
+    out_height: u32,
+) -> Option<Vec<u8>> {
+    let (width, height) = (width as usize, height as usize);
+    let (out_width, out_height) = (out_width as usize, out_height as usize);
+    if width == 0 || height == 0 || bgra.len() < width * height * 4 {
+        return None;
+    }
+    let mut rgb = Vec::with_capacity(out_width * out_height * 3);
+    for y in 0..out_height {
+        let row = (y * height / out_height.max(1)) * width;
+        for x in 0..out_width {
+            let pixel = (row + x * width / out_width.max(1)) * 4;
+            rgb.extend_from_slice(&[bgra[pixel + 2], bgra[pixel + 1], bgra[pixel]]);
+        }
+    }
+    Some(rgb)
+}
+
+/// Running screen capture; capture stops when this is dropped
+pub struct ScreenCapturer {
+    source: ScreenSource,
+    track_id: String,
+    config: ScreenCaptureConfig,
+    /// Dropping this tells the capture thread to stop
+    _stop: std::sync::mpsc::Sender<()>,
+    /// Run once capture stops, e.g. to take the share off its call
+    on_stop: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl ScreenCapturer {
+    /// Display or window being captured
+    #[must_use]
+    pub fn source(&self) -> &ScreenSource {
+        &self.source
+    }
+
+    /// Track the encoded frames are written to
+    #[must_use]
+    pub fn track_id(&self) -> &str {
+        &self.track_id
+    }
+
+    /// Settings the capture was started with
+    #[must_use]
+    pub fn config(&self) -> ScreenCaptureConfig {
+        self.config
+    }
+
+    /// Stop capturing
+    pub fn stop(self) {}
+
+    /// Run `on_stop` when the handle is dropped
+    pub(crate) fn on_stop(mut self, on_stop: impl FnOnce() + Send + Sync + 'static) -> Self {
+        self.on_stop = Some(Box::new(on_stop));
+        self
+    }
+}
+
+impl Drop for ScreenCapturer {
+    fn drop(&mut self) {
+        if let Some(on_stop) = self.on_stop.take() {
+            on_stop();
+        }
+    }
+}
+
+impl std::fmt::Debug for ScreenCapturer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScreenCapturer")
+            .field("source", &self.source)
+            .field("track_id", &self.track_id)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+/// Displays and windows that can be captured
+///
+/// # Errors
+///
+/// Returns error if screen capture is unsupported here or permission to
+/// capture was refused
+#[cfg(feature = "screen-capture")]
+pub fn sources() -> Result<Vec<ScreenSource>, MediaError> {
+    backend::sources()
+}
+
+/// Displays and windows that can be captured
+///
+/// Always empty without the `screen-capture` feature.
+///
+/// # Errors
+///
+/// Never fails without the `screen-capture` feature
+#[cfg(not(feature = "screen-capture"))]
+pub fn sources() -> Result<Vec<ScreenSource>, MediaError> {
+    Ok(Vec::new())
+}
+
+/// Capture `source_id`, or the primary display, into `track`
+#[cfg(feature = "screen-capture")]
+pub(crate) async fn start(
+    source_id: Option<&str>,
+    mut track: VideoTrack,
+    config: ScreenCaptureConfig,
+    watchdog: Arc<MediaWatchdog>,
+) -> Result<ScreenCapturer, MediaError> {
+    use crate::watchdog::{watch_encoder, Heartbeat, WatchTarget};
+    use std::sync::mpsc::TryRecvError;
+    use std::time::Duration;
+    use tokio::sync::{mpsc, oneshot};
+    use webrtc::media::Sample;
+
+    /// Encoded frames queued between the capture thread and the track
+    const ENCODED_QUEUE: usize = 4;
+
+    let (encoded_tx, mut encoded_rx) = mpsc::channel::<Vec<u8>>(ENCODED_QUEUE);
+    let (ready_tx, ready_rx) = oneshot::channel();
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+    let source_id = source_id.map(str::to_string);
+    let track_id = track.id.clone();
+    let webrtc_track = Arc::clone(&track.webrtc_track);
+    let heartbeat = Heartbeat::new();
+    watch_encoder(&mut track.pipeline, &heartbeat)
+        .map_err(|e| MediaError::Capture(e.to_string()))?;
+    let target = WatchTarget::Encoder(track_id.clone());
+    let (recover_tx, recover_rx) = std::sync::mpsc::channel();
+    watchdog.watch(target.clone(), heartbeat, move |recovery| {
+        let _ = recover_tx.send(recovery);
+    });
+
+    // Capturing and encoding block, so both stay off the async runtime
+    std::thread::Builder::new()
+        .name("screen-capture".to_string())
+        .spawn(move || {
+            let (source, mut capturer) = match backend::open(source_id.as_deref(), config) {
+                Ok(opened) => opened,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    watchdog.unwatch(&target);
+                    return;
+                }
+            };
+            let _ = ready_tx.send(Ok(source));
+            // Runs until the handle, and with it the sender, is dropped
+            while let Err(TryRecvError::Empty) = stop_rx.try_recv() {
+                let Some((bgra, width, height)) = backend::next_frame(&mut capturer) else {
+                    break;
+                };
+                // Every recovery of an encoder that must keep its settings
+                // is a fresh keyframe
+                if recover_rx.try_iter().count() > 0 {
+                    track.request_keyframe();
+                }
+                let Some(rgb) = bgra_to_rgb(&bgra, width, height, track.width, track.height) else {
+                    continue;
+                };
+                match track.encode_frame(&rgb) {
+                    Ok(encoded) if encoded.is_empty() => {}
+                    // Skip frames while the track is still sending the last ones
+                    Ok(encoded) => {
+                        let _ = encoded_tx.try_send(encoded);
+                    }
+                    Err(e) => tracing::warn!("Failed to encode screen frame: {}", e),
+                }
+            }
+            capturer.stop_capture();
+            watchdog.unwatch(&target);
+        })
+        .map_err(|e| MediaError::Capture(e.to_string()))?;
+    let source = ready_rx
+        .await
+        .map_err(|_| MediaError::Capture("capture thread exited".to_string()))??;
+    tracing::info!(
+        "Capturing {} at {} fps into track {}",
+        source.name,
+        config.fps,
+        track_id
+    );
+
+    let task_track_id = track_id.clone();
+    let frame_duration = Duration::from_secs(1) / config.fps.max(1);
+    tokio::spawn(async move {
+        // Ends once the capture thread exits and drops its sender
+        while let Some(encoded) = encoded_rx.recv().await {
+            let sample = Sample {
+                data: encoded.into(),
+                duration: frame_duration,
+                ..Default::default()
+            };
+            if let Err(e) = webrtc_track.write_sample(&sample).await {
+                tracing::warn!("Failed to write screen to {}: {}", task_track_id, e);
+            }
+        }
+        tracing::debug!("Screen capture for {} stopped", task_track_id);
+    });
+
+    Ok(ScreenCapturer {
+        source,
+        track_id,
+        config,
+        _stop: stop_tx,
+        on_stop: None,
+    })
+}
+
+/// Capture `source_id`, or the primary display, into `track`
+#[cfg(not(feature = "screen-capture"))]
+pub(crate) async fn start(
+    _source_id: Option<&str>,
+    _track: VideoTrack,
+    _config: ScreenCaptureConfig,
+    _watchdog: Arc<MediaWatchdog>,
+) -> Result<ScreenCapturer, MediaError> {
+    Err(MediaError::Capture(
+        "built without the `screen-capture` feature".to_string(),
+    ))
+}
+
+#[cfg(feature = "screen-capture")]
+mod backend {
+    use super::{ScreenCaptureConfig, ScreenSource, ScreenSourceKind};
+    use crate::media::MediaError;
+    use scap::capturer::{Capturer, Options};
+    use scap::frame::{Frame, FrameType};
+    use scap::Target;
+
+    fn ensure_permission() -> Result<(), MediaError> {
+        if !scap::is_supported() {
+            return Err(MediaError::Capture(
+                "screen capture is not supported on this system".to_string(),
+            ));
+        }
+        // Prompts the user the first time on macOS; a no-op elsewhere
+        if !scap::has_permission() && !scap::request_permission() {
+            return Err(MediaError::Capture(
+                "permission to capture the screen was refused".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn describe(target: &Target) -> ScreenSource {
+        let (kind, raw_id, name) = match target {
+            Target::Display(display) => (ScreenSourceKind::Display, display.id, &display.title),
+            Target::Window(window) => (ScreenSourceKind::Window, window.id, &window.title),
+        };
+        ScreenSource {
+            id: ScreenSource::make_id(kind, raw_id),
+            name: name.clone(),
+            kind,
+        }
+    }
+
+    pub(super) fn sources() -> Result<Vec<ScreenSource>, MediaError> {
+        ensure_permission()?;
+        Ok(scap::get_all_targets().iter().map(describe).collect())
+    }
+
+    /// Start capturing a source, or the primary display
+    ///
+    /// On Linux the portal asks the user to pick the source instead, so
+    /// `source_id` only narrows the request.
+    pub(super) fn open(
+        source_id: Option<&str>,
+        config: ScreenCaptureConfig,
+    ) -> Result<(ScreenSource, Capturer), MediaError> {
+        ensure_permission()?;
+        let target = match source_id {
+            None => None,
+            Some(id) => Some(
+                scap::get_all_targets()
+                    .into_iter()
+                    .find(|target| describe(target).id == id)
+                    .ok_or_else(|| MediaError::DeviceNotFound(id.to_string()))?,
+            ),
+        };
+        let source = match &target {
+            Some(target) => describe(target),
+            None => describe(&Target::Display(scap::get_main_display())),
+        };
+        let options = Options {
+            fps: config.fps.max(1),
+            target,
+            show_cursor: config.show_cursor,
+            show_highlight: false,
+            output_type: FrameType::BGRAFrame,
+            ..Default::default()
+        };
+        let mut capturer =
+            Capturer::build(options).map_err(|e| MediaError::Capture(e.to_string()))?;
+        capturer.start_capture();
+        Ok((source, capturer))
+    }
+
+    /// Wait for the next BGRA frame and its size; `None` once capture ends
+    pub(super) fn next_frame(capturer: &mut Capturer) -> Option<(Vec<u8>, u32, u32)> {
+        loop {
+            match capturer.get_next_frame().ok()? {
+                Frame::BGRA(frame) => {
+                    let width = u32::try_from(frame.width).ok()?;
+                    let height = u32::try_from(frame.height).ok()?;
+                    return Some((frame.data, width, height));
+                }
+                // Only BGRA was asked for
+                _ => continue,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bgra_to_rgb_swaps_channels() {
+        let bgra = [10, 20, 30, 255, 40, 50, 60, 255];
+        assert_eq!(
+            bgra_to_rgb(&bgra, 2, 1, 2, 1).unwrap(),
+            [30, 20, 10, 60, 50, 40]
+        );
+        assert!(bgra_to_rgb(&bgra[..4], 2, 1, 2, 1).is_none());
+        assert!(bgra_to_rgb(&[], 0, 0, 2, 1).is_none());
+    }
+
+    #[test]
+    fn test_bgra_to_rgb_scales_to_track_size() {
+        // 4x2 frame: left half black, right half white
+        let mut bgra = Vec::new();
+        for _ in 0..2 {
+            bgra.extend([0, 0, 0, 255].repeat(2));
+            bgra.extend([255, 255, 255, 255].repeat(2));
+        }
+        let rgb = bgra_to_rgb(&bgra, 4, 2, 2, 1).unwrap();
+        assert_eq!(rgb, [0, 0, 0, 255, 255, 255]);
+
+        let rgb = bgra_to_rgb(&bgra, 4, 2, 8, 4).unwrap();
+        assert_eq!(rgb.len(), 8 * 4 * 3);
+        assert_eq!(&rgb[..3], [0, 0, 0]);
+        assert_eq!(&rgb[rgb.len() - 3..], [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_source_ids() {
+        assert_eq!(
+            ScreenSource::make_id(ScreenSourceKind::Display, 1),
+            "display:1"
+        );
+        assert_eq!(
+            ScreenSource::make_id(ScreenSourceKind::Window, 42),
+            "window:42"
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "screen-capture"))]
+    fn test_no_sources_without_feature() {
+        assert!(sources().unwrap().is_empty());
+    }
+}
@@ -1,25 +1,223 @@
 //! WebRTC service orchestration
 
-use crate::call::{CallManager, CallManagerConfig};
+use crate::active_speaker::SimulcastLayer;
+use crate::annotation::Annotation;
+use crate::audio_capture::AudioCapture;
+use crate::audio_clock::AudioClock;
+use crate::audio_level::{AudioLevelConfig, AudioLevelMeter, AudioLevelSource};
+use crate::audio_render::AudioRenderer;
+use crate::broadcast::{Broadcast, BroadcastConfig, BroadcastError, BroadcastId};
+use crate::call::{offered_media, CallError, CallManager, CallManagerConfig, RetrySchedule};
+use crate::call_history::{CallHistory, CallHistoryStore, CallRecord, HistoryError, HistoryQuery};
+use crate::call_logs::{CallLogBuffer, CallLogBundle};
+use crate::call_stats::{CallStats, CallStatsSampler};
+use crate::capabilities::{CodecCapabilities, NegotiatedCodecs, PeerCapabilities};
+use crate::clock::{ClockEstimate, ClockEstimator, ClockExchange};
+use crate::conference::{
+    breakout_message, control_message, join_decision_message, BreakoutGroup, Conference,
+    ConferenceError, ConferenceEvent, ConferenceId, JoinOutcome, JoinRequest, Participant,
+    ParticipantAction, ParticipantRole,
+};
+use crate::congestion::{queue_delay_ms, CongestionConfig, CongestionMonitor, CongestionSeverity};
+use crate::deadline::{CancellationToken, Interrupted};
+use crate::degradation::{
+    DegradationConfig, DegradationEvent, DegradationLadder, EncodeLoadConfig, EncodeLoadController,
+    EncodeLoadEvent,
+};
+use crate::devices::DEFAULT_DEVICE_POLL_INTERVAL;
+use crate::error::{BoxError, CodedError, ErrorCode, ErrorReport};
+use crate::events::{EventBus, EventSubscription, DEFAULT_EVENT_QUEUE_CAPACITY};
+use crate::handoff::{HandoffError, HandoffState, HandoffTracker};
 use crate::identity::PeerIdentity;
-use crate::media::MediaStreamManager;
-use crate::signaling::{SignalingHandler, SignalingTransport};
-use crate::types::{CallEvent, CallId, CallState, MediaConstraints, NativeQuicConfiguration};
+use crate::invitation::{
+    AutoAnswer, CallScreen, CallToken, InvitationError, InvitationIssuer, InvitationKey,
+    ScreeningDecision,
+};
+use crate::latency::{FrameTiming, LatencyBreakdown, LatencyTracker};
+use crate::media::{
+    AudioDevice, MediaError, MediaEvent, MediaStream, MediaStreamManager, RemoteTrack,
+};
+use crate::media_recorder::{MediaRecorder, RecordingOptions, RecordingSource};
+use crate::messaging::TextMessage;
+use crate::parking::{ParkToken, ParkedCall, ParkingError, ParkingLot};
+use crate::path_probe::{PathProbe, PathProbeReport, ProbeConfig, MAX_PROBE_PADDING};
+use crate::pipeline::{KeyframePolicy, TransmitSwitch};
+use crate::playback_sync::SyncMessage;
+use crate::quality::{QualityAlert, QualityMonitor, QualityPolicy};
+use crate::quic_bridge::{RtpPacket, StreamType};
+use crate::quic_streams::{QoSPolicy, QuicMediaStreamManager};
+use crate::recording::RecordingError;
+use crate::relay::RelayStats;
+use crate::reload::{ConfigError, ReloadableConfig};
+use crate::remote_control::{RemoteControlAction, RemoteControlError, RemoteInput};
+use crate::remote_video::{RemoteVideo, VideoFrameSink, VideoSinkId};
+use crate::resume::{RestoredCall, ServiceSnapshot, SNAPSHOT_VERSION};
+use crate::room::{Room, RoomAdmission, RoomError, RoomId, RoomSettings};
+use crate::router::MediaRouter;
+use crate::runtime::RuntimeTopology;
+use crate::screen_capture::{ScreenCaptureConfig, ScreenCapturer, ScreenShare, ScreenSource};
+use crate::session::{SessionConfig, SessionError, SessionRegistry};
+use crate::signaling::{
+    BoxedSignalingTransport, DynTransportError, SignalingError, SignalingHandler, SignalingMessage,
+    SignalingTransport,
+};
+use crate::snapshot::{SnapshotError, SnapshotFormat, SnapshotStore};
+use crate::stats_history::{StatsHistoryConfig, StatsHistoryStore};
+use crate::transport::{TransportConfig, TransportEvent};
+use crate::types::{
+    AdaptationSettings, ByeReason, CallEvent, CallId, CallQualityMetrics, CallState, FailureReason,
+    MediaConstraints, MediaType, NativeQuicConfiguration,
+};
+use crate::voicemail::{Voicemail, VoicemailConfig, VoicemailError};
+use crate::watchdog::{Heartbeat, Recovery, WatchTarget, WatchdogConfig};
+use dashmap::mapref::one::RefMut;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use saorsa_webrtc_codecs::{AudioFrame, EncoderPreset, HardwareBackend, VideoCodec, VideoFrame};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Shortest gap between keyframe requests for one track, so a decoder that
+/// fails on every frame until the keyframe lands does not flood the sender
+const KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often signaling sessions are checked for being abandoned
+const SESSION_SWEEP_INTERVAL: Duration = Duration::from_secs(5);
 
 /// Service errors
 #[derive(Error, Debug)]
 pub enum ServiceError {
     /// Initialization error
     #[error("Initialization error: {0}")]
-    InitError(String),
+    InitError(#[source] BoxError),
 
     /// Call error
     #[error("Call error: {0}")]
-    CallError(String),
+    CallError(#[from] CallError),
+
+    /// Signaling message could not be sent or received
+    #[error("Signaling error: {0}")]
+    SignalingError(#[source] BoxError),
+
+    /// Peer ID the signaling transport or identity type cannot parse
+    #[error("Invalid peer ID: {peer}")]
+    InvalidPeer {
+        /// Peer ID as given
+        peer: String,
+        /// Why it did not parse, if the parser says
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    /// Local identity is needed but was not set
+    #[error("Local identity not set")]
+    NoIdentity,
+
+    /// Voicemail error
+    #[error("Voicemail error: {0}")]
+    VoicemailError(#[from] VoicemailError),
+
+    /// Snapshot error
+    #[error("Snapshot error: {0}")]
+    SnapshotError(#[from] SnapshotError),
+
+    /// Recording error
+    #[error("Recording error: {0}")]
+    RecordingError(#[from] RecordingError),
+
+    /// Call is already being recorded
+    #[error("Call {0} is already being recorded")]
+    AlreadyRecording(CallId),
+
+    /// Call is not being recorded
+    #[error("Call {0} is not being recorded")]
+    NotRecording(CallId),
+
+    /// Conference error
+    #[error("Conference error: {0}")]
+    ConferenceError(#[from] ConferenceError),
+
+    /// Room error
+    #[error("Room error: {0}")]
+    RoomError(#[from] RoomError),
+
+    /// Call parking error
+    #[error("Parking error: {0}")]
+    ParkingError(#[from] ParkingError),
+
+    /// Device handoff error
+    #[error("Handoff error: {0}")]
+    HandoffError(#[from] HandoffError),
+
+    /// Snapshot was written in a format this build cannot read
+    #[error("Unsupported snapshot version {version}, expected {supported}")]
+    UnsupportedSnapshot {
+        /// Version the snapshot was written in
+        version: u32,
+        /// Version this build reads
+        supported: u32,
+    },
+
+    /// Call invitation error
+    #[error("Invitation error: {0}")]
+    InvitationError(#[from] InvitationError),
+
+    /// Broadcast error
+    #[error("Broadcast error: {0}")]
+    BroadcastError(#[from] BroadcastError),
+
+    /// Media error
+    #[error("Media error: {0}")]
+    MediaError(#[from] MediaError),
+
+    /// Configuration error
+    #[error("Configuration error: {0}")]
+    ConfigError(#[from] ConfigError),
+
+    /// Call history error
+    #[error("Call history error: {0}")]
+    HistoryError(#[from] HistoryError),
+
+    /// Signaling session error
+    #[error("Session error: {0}")]
+    SessionError(#[from] SessionError),
+}
+
+impl CodedError for ServiceError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InitError(_) => ErrorCode::Internal,
+            Self::CallError(e) => e.code(),
+            Self::SignalingError(e) => signaling_code(e.as_ref()),
+            Self::InvalidPeer { .. } => ErrorCode::PeerNotFound,
+            Self::NoIdentity => ErrorCode::InvalidConfig,
+            Self::VoicemailError(e) => e.code(),
+            Self::SnapshotError(e) => e.code(),
+            Self::RecordingError(e) => e.code(),
+            Self::AlreadyRecording(_) | Self::NotRecording(_) => ErrorCode::RecordingFailed,
+            Self::ConferenceError(e) => e.code(),
+            Self::RoomError(e) => e.code(),
+            Self::ParkingError(e) => e.code(),
+            Self::HandoffError(e) => e.code(),
+            Self::UnsupportedSnapshot { .. } => ErrorCode::InvalidInput,
+            Self::InvitationError(e) => e.code(),
+            Self::BroadcastError(e) => e.code(),
+            Self::MediaError(e) => e.code(),
+            Self::ConfigError(e) => e.code(),
+            Self::HistoryError(e) => e.code(),
+            Self::SessionError(e) => e.code(),
+        }
+    }
 }
 
 /// Top-level WebRTC events
@@ -31,6 +229,240 @@ pub enum WebRtcEvent<I: PeerIdentity> {
     Media(crate::media::MediaEvent),
     /// Call event
     Call(CallEvent<I>),
+    /// Quality alert raised by the quality policy
+    QualityAlert(QualityAlert),
+    /// Video quality stepped up or down the degradation ladder
+    Degradation(DegradationEvent),
+    /// Video resolution or frame rate changed with the encoder's load
+    EncodeLoad(EncodeLoadEvent),
+    /// A voice message was received
+    VoicemailReceived(Voicemail),
+    /// Conference participant change
+    Conference(ConferenceEvent),
+    /// A room host answered our request to join
+    RoomJoined {
+        /// Room ID
+        room_id: RoomId,
+        /// Live conference, if admitted
+        conference_id: Option<ConferenceId>,
+        /// Whether we were let in
+        admitted: bool,
+    },
+    /// Another device answered our request for a call it had parked
+    CallHandoff {
+        /// Remote party to call to continue; `None` if refused
+        remote_peer: Option<I>,
+    },
+    /// Another of our devices offered us an active call
+    HandoffOffered(HandoffState),
+    /// The remote party moved a call to another of their devices
+    ///
+    /// Answer `sdp` on the new leg, then end the replaced one.
+    CallReplaced {
+        /// Leg being replaced
+        replaced: CallId,
+        /// New leg
+        call_id: CallId,
+        /// SDP offer for the new leg
+        sdp: String,
+    },
+    /// A call we offered is now running on another of our devices
+    HandoffCompleted {
+        /// Our leg, now ended
+        call_id: CallId,
+    },
+    /// The remote decoder needs a keyframe
+    ///
+    /// Call [`VideoTrack::request_keyframe`](crate::media::VideoTrack::request_keyframe)
+    /// on the matching outgoing tracks.
+    KeyframeRequested {
+        /// Call the request arrived on
+        call_id: CallId,
+        /// Track to refresh; `None` means every video track of the call
+        track_id: Option<String>,
+    },
+    /// A clock probe was answered, refining the peer clock estimate
+    ClockEstimated {
+        /// Call the probe was sent on
+        call_id: CallId,
+        /// Best estimate so far
+        estimate: ClockEstimate,
+    },
+    /// Codecs were agreed with the remote party
+    CodecsNegotiated {
+        /// Call the codecs apply to
+        call_id: CallId,
+        /// Codecs to encode and decode with
+        codecs: NegotiatedCodecs,
+    },
+    /// An incoming call was refused because the caller is neither a
+    /// contact nor invited
+    CallScreened {
+        /// Refused call's session ID
+        session_id: String,
+        /// Caller's peer ID
+        caller: String,
+    },
+    /// A remote peer is calling
+    ///
+    /// Answer with [`WebRtcService::accept_call`] or
+    /// [`WebRtcService::reject_call`]. Only raised while
+    /// [`WebRtcService::receive_signaling`] runs or messages are passed to
+    /// [`WebRtcService::handle_signaling_message`].
+    IncomingCall {
+        /// Call to accept or reject
+        call_id: CallId,
+        /// Caller
+        from: I,
+        /// Media the caller offered
+        constraints: MediaConstraints,
+    },
+    /// A call arrived while another is in progress, with
+    /// [`WebRtcConfig::call_waiting`] set
+    ///
+    /// Accept or reject it like an [`WebRtcEvent::IncomingCall`], parking
+    /// or ending the active call first as the user chooses.
+    CallWaiting {
+        /// Call to accept or reject
+        call_id: CallId,
+        /// Caller
+        from: I,
+        /// Media the caller offered
+        constraints: MediaConstraints,
+        /// Call already in progress
+        active_call: CallId,
+    },
+    /// The callee of a call we placed could not be reached; it is redialled
+    /// after `delay` unless the retry is aborted
+    CallRetrying {
+        /// Call being placed
+        call_id: CallId,
+        /// Retry about to be made, counted from 1
+        retry: u32,
+        /// Wait before the retry
+        delay: Duration,
+        /// Why the last attempt failed
+        error: String,
+    },
+    /// A call was answered without ringing, under the caller's
+    /// [`AutoAnswer`] policy or the
+    /// [`AnswerPolicy`](crate::call::AnswerPolicy) of the call manager
+    CallAutoAnswered {
+        /// Answered call
+        call_id: CallId,
+        /// Caller's peer ID
+        caller: String,
+        /// Whether our microphone started muted
+        muted: bool,
+    },
+    /// The issuer answered an invitation we redeemed
+    InvitationRedeemed {
+        /// Invitation ID
+        invitation_id: Uuid,
+        /// Whether our calls to the issuer will now ring
+        accepted: bool,
+    },
+    /// The remote party muted or unmuted its audio, or turned its video
+    /// off or on
+    RemoteMediaState {
+        /// Call the change applies to
+        call_id: CallId,
+        /// Whether the remote audio is muted
+        audio_muted: bool,
+        /// Whether the remote video is on
+        video_enabled: bool,
+    },
+    /// The remote party started, paused, resumed or stopped a screen share
+    RemoteScreenShares {
+        /// Call the change applies to
+        call_id: CallId,
+        /// Every screen share the remote party now sends
+        shares: Vec<ScreenShare>,
+    },
+    /// A call's uplink became more or less congested
+    ///
+    /// Raised as soon as the pacer queue or loss crosses a
+    /// [`CongestionConfig`] threshold, before quality visibly drops;
+    /// [`CongestionSeverity::Clear`] means the connection is stable again.
+    CongestionDetected {
+        /// Congested call
+        call_id: CallId,
+        /// New severity
+        severity: CongestionSeverity,
+    },
+    /// The remote party asks to control one of our screen shares
+    ///
+    /// Prompt the user, then answer with
+    /// [`WebRtcService::grant_remote_control`] or
+    /// [`WebRtcService::deny_remote_control`]. Only raised when
+    /// [`WebRtcConfig::allow_remote_control`] is set; otherwise requests
+    /// are denied straight away.
+    RemoteControlRequested {
+        /// Call the request came on
+        call_id: CallId,
+        /// Our screen share track
+        track_id: String,
+    },
+    /// We were granted control of a remote screen share; send input with
+    /// [`WebRtcService::send_remote_input`]
+    RemoteControlGranted {
+        /// Call the grant came on
+        call_id: CallId,
+        /// Remote screen share track
+        track_id: String,
+    },
+    /// The remote party denied, revoked or released remote control
+    RemoteControlEnded {
+        /// Call the screen share belongs to
+        call_id: CallId,
+        /// Screen share track
+        track_id: String,
+        /// [`RemoteControlAction::Deny`], [`RemoteControlAction::Revoke`]
+        /// or [`RemoteControlAction::Release`]
+        action: RemoteControlAction,
+    },
+    /// A call's recording stopped without [`WebRtcService::stop_recording`],
+    /// because the call ended or writing failed
+    RecordingStopped {
+        /// Call that was recorded
+        call_id: CallId,
+        /// Files of the recording still on disk, oldest first
+        files: Vec<PathBuf>,
+        /// Why writing failed, if it did
+        error: Option<String>,
+    },
+    /// A viewer started receiving one of our broadcasts
+    BroadcastSubscribed {
+        /// Broadcast joined
+        broadcast_id: BroadcastId,
+        /// Subscriber's peer ID
+        subscriber: String,
+        /// Video layer the subscriber starts on
+        layer: SimulcastLayer,
+    },
+    /// A viewer stopped receiving one of our broadcasts
+    BroadcastUnsubscribed {
+        /// Broadcast left
+        broadcast_id: BroadcastId,
+        /// Subscriber's peer ID
+        subscriber: String,
+    },
+    /// A subscriber was moved to another video layer after reporting loss
+    BroadcastLayerChanged {
+        /// Broadcast the subscriber receives
+        broadcast_id: BroadcastId,
+        /// Subscriber's peer ID
+        subscriber: String,
+        /// Layer the subscriber now receives
+        layer: SimulcastLayer,
+    },
+    /// A publisher answered our request to receive its broadcast
+    BroadcastJoined {
+        /// Broadcast asked for
+        broadcast_id: BroadcastId,
+        /// Whether media will now arrive
+        accepted: bool,
+    },
 }
 
 /// Signaling event (placeholder)
@@ -51,6 +483,101 @@ pub struct WebRtcConfig {
     pub default_constraints: MediaConstraints,
     /// Call manager config
     pub call_config: CallManagerConfig,
+    /// Per-subscriber queue capacity for the event bus
+    pub event_queue_capacity: usize,
+    /// Rules used to raise quality alerts
+    pub quality_policy: QualityPolicy,
+    /// Video degradation ladder applied as bandwidth changes
+    pub degradation: DegradationConfig,
+    /// Encoder load thresholds, and what to give up when the encoder
+    /// cannot keep up
+    pub encode_load: EncodeLoadConfig,
+    /// Limits for sent and received voicemail
+    pub voicemail: VoicemailConfig,
+    /// H.264 encoder backends to try, most preferred first
+    ///
+    /// Hardware backends need the `hardware` feature; software is used when
+    /// none of them works.
+    pub video_encoders: Vec<HardwareBackend>,
+    /// Latency and quality trade-off for video encoders
+    ///
+    /// Live calls want [`EncoderPreset::RealtimeLowLatency`]; recordings can
+    /// use [`EncoderPreset::Quality`].
+    pub encoder_preset: EncoderPreset,
+    /// Keyframe interval and intra refresh of camera video
+    ///
+    /// Use [`KeyframePolicy::lossy_link`] where packets are often lost.
+    pub video_keyframes: KeyframePolicy,
+    /// Keyframe interval and intra refresh of screen shares
+    pub screen_share_keyframes: KeyframePolicy,
+    /// Runtimes for background work; `None` uses the runtime the service
+    /// is created on
+    pub runtime: Option<RuntimeTopology>,
+    /// Captured call events for [`WebRtcService::export_call_logs`]
+    ///
+    /// Events only arrive once [`CallLogBuffer::layer`] is installed in the
+    /// application's tracing subscriber.
+    pub call_logs: Arc<CallLogBuffer>,
+    /// Codecs offered to peers, most preferred first
+    pub codecs: CodecCapabilities,
+    /// QoS parameters for each media stream type, including custom ones
+    pub qos: QoSPolicy,
+    /// Key call invitations are signed with
+    ///
+    /// Persist it, or invitations handed out stop working on restart.
+    pub invitation_key: InvitationKey,
+    /// Refuse incoming calls from callers that are neither contacts nor
+    /// holders of a redeemed invitation
+    pub screen_unknown_callers: bool,
+    /// Let calls arriving during another call ring as
+    /// [`WebRtcEvent::CallWaiting`]; otherwise their callers are told we
+    /// are busy
+    pub call_waiting: bool,
+    /// Only send microphone audio while [`WebRtcService::set_transmit`]
+    /// holds the talk key down
+    pub push_to_talk: bool,
+    /// Drop microphone audio quieter than this level, in dBFS
+    pub noise_gate_dbfs: Option<f32>,
+    /// How often to look for devices being plugged in or unplugged once
+    /// started; `None` only lists devices at start
+    pub device_poll_interval: Option<Duration>,
+    /// How much per-second quality history is kept for
+    /// [`WebRtcService::export_stats_csv`]
+    pub stats_history: StatsHistoryConfig,
+    /// Pacer queue and loss thresholds for
+    /// [`WebRtcEvent::CongestionDetected`]
+    pub congestion: CongestionConfig,
+    /// Voice activity detection and reporting of [`MediaEvent::AudioLevel`]
+    pub audio_levels: AudioLevelConfig,
+    /// Let remote parties ask to control our screen shares; the user is
+    /// still asked every time
+    pub allow_remote_control: bool,
+    /// Rate and Opus frame size of microphone audio
+    ///
+    /// Shorter frames cut delay at the cost of more packets; size jitter
+    /// buffers to match with
+    /// [`JitterBufferConfig::for_clock`](crate::jitter::JitterBufferConfig::for_clock).
+    pub audio_clock: AudioClock,
+    /// Stall limits for capture, encoders and decoders once started;
+    /// `None` leaves stalls unwatched
+    pub pipeline_watchdog: Option<WatchdogConfig>,
+    /// Subscriber limit and layer selection of broadcasts we publish
+    pub broadcast: BroadcastConfig,
+    /// Redial callees that cannot be reached; `None` leaves reaching them
+    /// to the application
+    ///
+    /// With a schedule, [`WebRtcService::initiate_call`] connects to the
+    /// callee before returning, waiting in [`CallState::Retrying`] between
+    /// attempts.
+    pub call_retry: Option<RetrySchedule>,
+    /// Where a record of every call is kept once it ends, for
+    /// [`WebRtcService::call_history`]; `None` keeps no history
+    pub call_history: Option<Arc<dyn CallHistoryStore>>,
+    /// Probe train sent by [`WebRtcService::probe_peer`]
+    pub path_probe: ProbeConfig,
+    /// When signaling sessions of calls are abandoned, and how long ended
+    /// ones are refused
+    pub sessions: SessionConfig,
 }
 
 impl Default for WebRtcConfig {
@@ -59,16 +586,102 @@ impl Default for WebRtcConfig {
             quic_config: NativeQuicConfiguration::default(),
             default_constraints: MediaConstraints::audio_only(),
             call_config: CallManagerConfig::default(),
+            event_queue_capacity: DEFAULT_EVENT_QUEUE_CAPACITY,
+            quality_policy: QualityPolicy::default(),
+            degradation: DegradationConfig::default(),
+            encode_load: EncodeLoadConfig::default(),
+            voicemail: VoicemailConfig::default(),
+            video_encoders: HardwareBackend::ALL.to_vec(),
+            encoder_preset: EncoderPreset::default(),
+            video_keyframes: KeyframePolicy::default(),
+            screen_share_keyframes: KeyframePolicy::screen_share(),
+            runtime: None,
+            call_logs: Arc::new(CallLogBuffer::default()),
+            codecs: CodecCapabilities::local(),
+            qos: QoSPolicy::default(),
+            invitation_key: InvitationKey::generate(),
+            screen_unknown_callers: false,
+            call_waiting: false,
+            push_to_talk: false,
+            noise_gate_dbfs: None,
+            device_poll_interval: Some(DEFAULT_DEVICE_POLL_INTERVAL),
+            stats_history: StatsHistoryConfig::default(),
+            congestion: CongestionConfig::default(),
+            audio_levels: AudioLevelConfig::default(),
+            allow_remote_control: false,
+            audio_clock: AudioClock::default(),
+            pipeline_watchdog: Some(WatchdogConfig::default()),
+            broadcast: BroadcastConfig::default(),
+            call_retry: None,
+            call_history: None,
+            path_probe: ProbeConfig::default(),
+            sessions: SessionConfig::default(),
         }
     }
 }
 
+/// WebRTC service over a transport chosen at runtime
+pub type DynWebRtcService<I> = WebRtcService<I, BoxedSignalingTransport>;
+
 /// Main WebRTC service
 pub struct WebRtcService<I: PeerIdentity, T: SignalingTransport> {
-    _signaling: Arc<SignalingHandler<T>>,
+    signaling: Arc<SignalingHandler<T>>,
     media: Arc<MediaStreamManager>,
     call_manager: Arc<CallManager<I>>,
     event_sender: broadcast::Sender<WebRtcEvent<I>>,
+    event_bus: Arc<EventBus<I>>,
+    quality_monitor: Mutex<QualityMonitor>,
+    stats_history: Mutex<StatsHistoryStore>,
+    stats_sampler: Mutex<CallStatsSampler>,
+    congestion: Mutex<CongestionMonitor>,
+    audio_levels: AudioLevelConfig,
+    remote_levels: Mutex<HashMap<CallId, AudioLevelMeter>>,
+    allow_remote_control: bool,
+    degradation_config: Mutex<DegradationConfig>,
+    ladders: Mutex<HashMap<CallId, DegradationLadder>>,
+    encode_load_config: EncodeLoadConfig,
+    encode_loads: Mutex<HashMap<CallId, EncodeLoadController>>,
+    low_bandwidth: AtomicBool,
+    voicemail_config: VoicemailConfig,
+    snapshots: Arc<SnapshotStore>,
+    recorders: Mutex<HashMap<CallId, MediaRecorder>>,
+    conferences: DashMap<ConferenceId, Conference>,
+    routers: DashMap<ConferenceId, MediaRouter>,
+    rooms: DashMap<RoomId, Room>,
+    broadcast_config: Mutex<BroadcastConfig>,
+    broadcasts: DashMap<BroadcastId, Broadcast>,
+    parking: Mutex<ParkingLot>,
+    handoffs: Mutex<HandoffTracker>,
+    keyframe_requests: Mutex<HashMap<(CallId, Option<String>), Instant>>,
+    clocks: Mutex<HashMap<CallId, ClockEstimator>>,
+    latency: Mutex<HashMap<CallId, LatencyTracker>>,
+    codecs: Mutex<CodecCapabilities>,
+    qos: QoSPolicy,
+    negotiated: Mutex<HashMap<CallId, NegotiatedCodecs>>,
+    contacts: Mutex<HashMap<String, PeerCapabilities>>,
+    invitations: Mutex<InvitationIssuer>,
+    call_screen: Mutex<CallScreen>,
+    screen_unknown_callers: bool,
+    call_waiting: bool,
+    device_poll_interval: Option<Duration>,
+    device_watcher: Mutex<Option<JoinHandle<()>>>,
+    pipeline_watchdog: bool,
+    watchdog_task: Mutex<Option<JoinHandle<()>>>,
+    audio_renderer: Mutex<Option<AudioRenderer>>,
+    audio_captures: Mutex<HashMap<CallId, AudioCapture>>,
+    remote_video: Arc<RemoteVideo>,
+    runtime: RuntimeTopology,
+    call_logs: Arc<CallLogBuffer>,
+    identity: Mutex<Option<I>>,
+    guest_pass: Mutex<Option<CallToken>>,
+    call_retry: Option<RetrySchedule>,
+    /// Calls waiting to redial, by the token aborting them
+    retries: Mutex<HashMap<CallId, CancellationToken>>,
+    call_history: Option<Arc<CallHistory>>,
+    path_probe: ProbeConfig,
+    /// Probe trains awaiting echoes, by train ID
+    path_probes: Mutex<HashMap<String, PendingProbe>>,
+    sessions: Mutex<SessionRegistry>,
 }
 
 impl<I: PeerIdentity, T: SignalingTransport> WebRtcService<I, T> {
@@ -81,25 +694,117 @@ impl<I: PeerIdentity, T: SignalingTransport> WebRtcService<I, T> {
         signaling: Arc<SignalingHandler<T>>,
         config: WebRtcConfig,
     ) -> Result<Self, ServiceError> {
+        let runtime = match config.runtime {
+            Some(runtime) => runtime,
+            None => RuntimeTopology::current().map_err(|e| ServiceError::InitError(e.into()))?,
+        };
         let (event_sender, _) = broadcast::channel(1000);
+        let event_bus = Arc::new(EventBus::new(config.event_queue_capacity));
 
-        let media = Arc::new(MediaStreamManager::new());
+        let media = Arc::new(
+            MediaStreamManager::with_event_bus(Arc::clone(event_bus.media()))
+                .with_encoder_preference(config.video_encoders)
+                .with_encoder_preset(config.encoder_preset)
+                .with_keyframe_policy(StreamType::Video, config.video_keyframes)
+                .with_keyframe_policy(StreamType::ScreenShare, config.screen_share_keyframes)
+                .with_push_to_talk(config.push_to_talk)
+                .with_noise_gate(config.noise_gate_dbfs)
+                .with_audio_levels(config.audio_levels)
+                .with_audio_clock(config.audio_clock)
+                .with_watchdog(config.pipeline_watchdog.unwrap_or_default()),
+        );
         let call_manager = Arc::new(
-            CallManager::new(config.call_config)
+            CallManager::with_event_bus(config.call_config, Arc::clone(event_bus.call()))
                 .await
-                .map_err(|e| ServiceError::InitError(e.to_string()))?,
+                .map_err(|e| ServiceError::InitError(e.into()))?,
         );
+        let call_history = config.call_history.map(|store| {
+            let history = Arc::new(CallHistory::new(store));
+            runtime.spawn_signaling(record_calls(
+                Arc::clone(&history),
+                event_bus.subscribe_calls(),
+            ));
+            history
+        });
 
         Ok(Self {
-            _signaling: signaling,
+            signaling,
             media,
             call_manager,
             event_sender,
+            event_bus,
+            quality_monitor: Mutex::new(QualityMonitor::new(config.quality_policy)),
+            stats_history: Mutex::new(StatsHistoryStore::new(config.stats_history)),
+            stats_sampler: Mutex::new(CallStatsSampler::new()),
+            congestion: Mutex::new(CongestionMonitor::new(config.congestion)),
+            audio_levels: config.audio_levels,
+            remote_levels: Mutex::new(HashMap::new()),
+            allow_remote_control: config.allow_remote_control,
+            degradation_config: Mutex::new(config.degradation),
+            ladders: Mutex::new(HashMap::new()),
+            encode_load_config: config.encode_load,
+            encode_loads: Mutex::new(HashMap::new()),
+            low_bandwidth: AtomicBool::new(false),
+            voicemail_config: config.voicemail,
+            snapshots: Arc::new(SnapshotStore::new()),
+            recorders: Mutex::new(HashMap::new()),
+            conferences: DashMap::new(),
+            routers: DashMap::new(),
+            rooms: DashMap::new(),
+            broadcast_config: Mutex::new(config.broadcast),
+            broadcasts: DashMap::new(),
+            parking: Mutex::new(ParkingLot::new()),
+            handoffs: Mutex::new(HandoffTracker::new()),
+            keyframe_requests: Mutex::new(HashMap::new()),
+            clocks: Mutex::new(HashMap::new()),
+            latency: Mutex::new(HashMap::new()),
+            codecs: Mutex::new(config.codecs),
+            qos: config.qos,
+            negotiated: Mutex::new(HashMap::new()),
+            contacts: Mutex::new(HashMap::new()),
+            invitations: Mutex::new(InvitationIssuer::new(config.invitation_key)),
+            call_screen: Mutex::new(CallScreen::new()),
+            screen_unknown_callers: config.screen_unknown_callers,
+            call_waiting: config.call_waiting,
+            device_poll_interval: config.device_poll_interval,
+            device_watcher: Mutex::new(None),
+            pipeline_watchdog: config.pipeline_watchdog.is_some(),
+            watchdog_task: Mutex::new(None),
+            audio_renderer: Mutex::new(None),
+            audio_captures: Mutex::new(HashMap::new()),
+            remote_video: Arc::new(RemoteVideo::new()),
+            runtime,
+            call_logs: config.call_logs,
+            identity: Mutex::new(None),
+            guest_pass: Mutex::new(None),
+            call_retry: config.call_retry,
+            retries: Mutex::new(HashMap::new()),
+            call_history,
+            path_probe: config.path_probe,
+            path_probes: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(SessionRegistry::new(config.sessions)),
         })
     }
 
+    /// Our own identity, offered calls are addressed to
+    #[must_use]
+    pub fn identity(&self) -> Option<I> {
+        self.identity.lock().clone()
+    }
+
+    /// Set our own identity
+    ///
+    /// Offers are refused until it is set.
+    pub fn set_identity(&self, identity: I) {
+        *self.identity.lock() = Some(identity);
+    }
+
     /// Start the service
     ///
+    /// Also starts watching for devices being plugged in or unplugged and
+    /// for stalled media pipelines, reported as
+    /// [`MediaEvent`](crate::media::MediaEvent)s on the event bus.
+    ///
     /// # Errors
     ///
     /// Returns error if service cannot be started
@@ -107,122 +812,4312 @@ impl<I: PeerIdentity, T: SignalingTransport> WebRtcService<I, T> {
         self.media
             .initialize()
             .await
-            .map_err(|e| ServiceError::InitError(e.to_string()))?;
+            .map_err(|e| ServiceError::InitError(e.into()))?;
+
+        if let Some(interval) = self.device_poll_interval {
+            let mut watcher = self.device_watcher.lock();
+            if watcher.is_none() {
+                *watcher = Some(self.runtime.spawn_media(self.media.watch_devices(interval)));
+            }
+        }
+        if self.pipeline_watchdog {
+            let mut task = self.watchdog_task.lock();
+            if task.is_none() {
+                *task = Some(self.runtime.spawn_media(self.media.run_watchdog()));
+            }
+        }
 
         self.call_manager
             .start()
             .await
-            .map_err(|e| ServiceError::InitError(e.to_string()))?;
+            .map_err(|e| ServiceError::InitError(e.into()))?;
 
         Ok(())
     }
 
+    /// Receive and handle signaling messages until the transport fails
+    ///
+    /// Spawn this once the service is started. It drives calls through
+    /// [`Self::handle_signaling_message`]: inbound offers ring as
+    /// [`WebRtcEvent::IncomingCall`], answers to offers we sent and the
+    /// remote party's ICE candidates are applied to their call, and byes
+    /// end it. Messages that cannot be handled are
+    /// logged and skipped. Calls left unanswered past the ring or answer
+    /// timeout of [`CallManagerConfig`] are hung up here too, telling the
+    /// remote party. Calls with a peer whose QUIC connection drops, as
+    /// published on the [event bus](Self::event_bus) transport topic by
+    /// [`AntQuicTransport::with_event_channel`](crate::transport::AntQuicTransport::with_event_channel),
+    /// wait in [`CallState::Reconnecting`] and are torn down if it is not
+    /// back within [`CallManagerConfig::reconnect_grace`]. Calls whose
+    /// signaling goes quiet for [`WebRtcConfig::sessions`]' idle timeout
+    /// while connecting are hung up as abandoned; ringing calls are not,
+    /// and ring for as long as [`CallManagerConfig::answer_timeout`] lets
+    /// them.
+    ///
+    /// # Errors
+    ///
+    /// Returns error once receiving from the signaling transport fails
+    pub async fn receive_signaling(&self) -> Result<(), ServiceError> {
+        let mut call_events = self.call_manager.subscribe_events();
+        let mut transport_events = self.event_bus.subscribe_transport();
+        let mut sweep = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+        loop {
+            let received = {
+                // Kept across call events, as receiving may not be cancel safe
+                let receive = self.signaling.receive_message();
+                tokio::pin!(receive);
+                loop {
+                    tokio::select! {
+                        received = &mut receive => break received,
+                        event = call_events.recv() => {
+                            if let Ok(event) = event {
+                                self.follow_call_event(event).await;
+                            }
+                        }
+                        Some(event) = transport_events.recv() => {
+                            self.follow_transport_event(&event);
+                        }
+                        _ = sweep.tick() => self.expire_sessions().await,
+                    }
+                }
+            };
+            let (from, message) = received.map_err(|e| ServiceError::SignalingError(e.into()))?;
+            if let Err(e) = self.handle_signaling_message(&from, &message).await {
+                tracing::warn!("Failed to handle signaling message from {}: {}", from, e);
+            }
+        }
+    }
+
     /// Initiate a call
     ///
+    /// If the callee has advertised their capabilities, see
+    /// [`announce_presence`](Self::announce_presence), `constraints` are
+    /// first trimmed to media they take. With
+    /// [`WebRtcConfig::call_retry`] set, the callee is connected to before
+    /// this returns, redialling on the schedule while they cannot be
+    /// reached; see [`abort_call_retry`](Self::abort_call_retry).
+    ///
+    /// The offer is sent to the callee before this returns. Their answer
+    /// is applied by [`Self::receive_signaling`], which must be running for
+    /// the call to connect.
+    ///
     /// # Errors
     ///
-    /// Returns error if call cannot be initiated
+    /// Returns error if call cannot be initiated, the callee could not be
+    /// reached before retries ran out or were aborted, or the offer cannot
+    /// be sent
     pub async fn initiate_call(
         &self,
         callee: I,
         constraints: MediaConstraints,
     ) -> Result<CallId, ServiceError> {
-        self.call_manager
-            .initiate_call(callee, constraints)
+        let callee_id = callee.to_string_repr();
+        let call_id = self.place_call(callee, constraints).await?;
+        let offered = async {
+            let sdp = self.call_manager.create_offer(call_id).await?;
+            self.send_to(
+                &callee_id,
+                SignalingMessage::Offer {
+                    session_id: call_id.to_string(),
+                    sdp,
+                    quic_endpoint: None,
+                },
+            )
+            .await
+        };
+        if let Err(e) = offered.await {
+            let _ = self.close_call(call_id, ByeReason::Error).await;
+            return Err(e);
+        }
+        Ok(call_id)
+    }
+
+    /// Set up a call to `callee` without offering it yet
+    async fn place_call(
+        &self,
+        callee: I,
+        constraints: MediaConstraints,
+    ) -> Result<CallId, ServiceError> {
+        let callee_id = callee.to_string_repr();
+        if !self.guest_may_reach(&callee_id) {
+            return Err(InvitationError::OutOfScope(callee_id).into());
+        }
+        let constraints = match self.contact_capabilities(&callee) {
+            Some(capabilities) => capabilities.fit(&self.codec_preferences(), constraints),
+            None => constraints,
+        };
+        let call_id = self.call_manager.initiate_call(callee, constraints).await?;
+        self.sessions
+            .lock()
+            .open(&call_id.to_string(), call_id, &callee_id, Instant::now())?;
+        if self.low_bandwidth_mode() {
+            self.apply_low_bandwidth(call_id, true).await?;
+        }
+        if let Some(schedule) = self.call_retry {
+            if let Err(e) = self.reach_callee(call_id, &callee_id, schedule).await {
+                let _ =
+                    self.call_manager
+                        .fail_call(call_id, FailureReason::Unreachable, e.to_string());
+                let _ = self.close_call(call_id, ByeReason::Error).await;
+                return Err(e);
+            }
+        }
+        Ok(call_id)
+    }
+
+    /// Stop redialling a call's callee; the call fails as cancelled
+    ///
+    /// Returns false if the call is not being placed with retries.
+    pub fn abort_call_retry(&self, call_id: CallId) -> bool {
+        let Some(cancel) = self.retries.lock().remove(&call_id) else {
+            return false;
+        };
+        cancel.cancel();
+        true
+    }
+
+    /// Hang up a call we placed before the callee answers, telling them
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist, is incoming or was
+    /// already answered
+    pub async fn cancel_call(&self, call_id: CallId) -> Result<(), ServiceError> {
+        self.call_manager.cancel_call(call_id)?;
+        self.hang_up(call_id, ByeReason::Cancelled).await
+    }
+
+    /// Restart a call's connection after the local network changed, such
+    /// as moving from Wi-Fi to cellular
+    ///
+    /// Reconnects to the remote party over QUIC, then sends them an ICE
+    /// restart offer naming `local_endpoint`, our address on the new
+    /// network, so they reconnect to it too. The call keeps its state,
+    /// tracks and data channels throughout; their answer is applied by
+    /// [`Self::receive_signaling`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or is not connected, the
+    /// restart offer cannot be created, or sending it fails
+    pub async fn restart_connection(
+        &self,
+        call_id: CallId,
+        local_endpoint: Option<SocketAddr>,
+    ) -> Result<(), ServiceError> {
+        let remote_peer = self
+            .call_manager
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        let peer_id = remote_peer.to_string_repr();
+        let remote = transport_peer::<T::PeerId>(&peer_id)?;
+        // The old path may be gone; the offer still travels over signaling
+        if let Err(e) = self.connect_endpoint(&remote).await {
+            tracing::warn!(
+                "Could not reconnect to {} for call {}: {}",
+                peer_id,
+                call_id,
+                e
+            );
+        }
+        let sdp = self.call_manager.restart_connection(call_id).await?;
+        let offer = SignalingMessage::Offer {
+            session_id: call_id.to_string(),
+            sdp,
+            quic_endpoint: local_endpoint,
+        };
+        self.send_to(&peer_id, offer).await
+    }
+
+    /// Tear down calls the call manager gave up on, and tell remote
+    /// parties of screen shares that ended
+    async fn follow_call_event(&self, event: CallEvent<I>) {
+        match event {
+            CallEvent::ConnectionFailed {
+                call_id,
+                reason: FailureReason::NoAnswer,
+                ..
+            } => self.hang_up_unanswered(call_id).await,
+            CallEvent::ConnectionFailed {
+                call_id,
+                reason: FailureReason::ConnectionLost,
+                ..
+            } => {
+                if let Err(e) = self.hang_up(call_id, ByeReason::Error).await {
+                    tracing::warn!("Failed to end disconnected call {}: {}", call_id, e);
+                }
+            }
+            CallEvent::ScreenShareEnded { call_id, track_id } => {
+                if let Err(e) = self.send_screen_shares(call_id).await {
+                    tracing::warn!(
+                        "Could not tell call {} screen share {} ended: {}",
+                        call_id,
+                        track_id,
+                        e
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Move calls with a peer whose QUIC connection dropped or came back
+    fn follow_transport_event(&self, event: &TransportEvent) {
+        let (peer_id, connected) = match event {
+            TransportEvent::PeerConnected { peer_id } => (peer_id, true),
+            TransportEvent::PeerDisconnected { peer_id } => (peer_id, false),
+        };
+        for call in self.call_manager.call_snapshots() {
+            if call.remote_peer.to_string_repr() != *peer_id {
+                continue;
+            }
+            if connected {
+                self.call_manager.connection_restored(call.call_id);
+            } else {
+                self.call_manager.connection_lost(call.call_id);
+            }
+        }
+    }
+
+    /// Hang up calls whose signaling session was abandoned while they
+    /// were being set up
+    ///
+    /// Calls ringing or being redialled are left to their own ring, answer
+    /// and retry timeouts, and established calls need no signaling.
+    async fn expire_sessions(&self) {
+        let live: Vec<CallId> = self
+            .call_manager
+            .call_snapshots()
+            .into_iter()
+            .filter(|call| {
+                matches!(
+                    call.state,
+                    CallState::Calling
+                        | CallState::Ringing
+                        | CallState::Retrying
+                        | CallState::Connected
+                        | CallState::Reconnecting
+                        | CallState::Parked
+                )
+            })
+            .map(|call| call.call_id)
+            .collect();
+        let expired = self
+            .sessions
+            .lock()
+            .expire(Instant::now(), |call_id| live.contains(&call_id));
+        for call_id in expired {
+            if self.call_manager.remote_peer(call_id).is_none() {
+                continue;
+            }
+            tracing::info!("Signaling for call {} went quiet, hanging up", call_id);
+            if let Err(e) = self.hang_up(call_id, ByeReason::Timeout).await {
+                tracing::warn!("Failed to end abandoned call {}: {}", call_id, e);
+            }
+        }
+    }
+
+    /// The call a message for `session_id` from `from` belongs to
+    ///
+    /// Sessions of calls set up without the service, such as on its
+    /// [`CallManager`] directly, are opened when their remote party first
+    /// signals on them. `None` if the session is unknown or ended, or
+    /// belongs to another peer.
+    fn session_call(&self, session_id: &str, from: &T::PeerId) -> Option<CallId> {
+        let peer = from.to_string();
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock();
+        let resolved = match sessions.resolve(session_id, &peer, now) {
+            Err(SessionError::Unknown(_)) => parse_call_id(session_id)
+                .ok()
+                .filter(|call_id| {
+                    self.call_manager
+                        .remote_peer(*call_id)
+                        .is_some_and(|remote| remote.to_string_repr() == peer)
+                })
+                .ok_or_else(|| SessionError::Unknown(session_id.to_string()))
+                .and_then(|call_id| {
+                    sessions
+                        .open(session_id, call_id, &peer, now)
+                        .map(|()| call_id)
+                }),
+            resolved => resolved,
+        };
+        resolved
+            .inspect_err(|e| tracing::debug!("Ignoring message from {}: {}", peer, e))
+            .ok()
+    }
+
+    /// End a call nobody answered in time, telling the remote party
+    async fn hang_up_unanswered(&self, call_id: CallId) {
+        if let Err(e) = self.hang_up(call_id, ByeReason::Timeout).await {
+            tracing::warn!("Failed to end unanswered call {}: {}", call_id, e);
+        }
+    }
+
+    /// Connect to the callee of a new call, waiting in
+    /// [`CallState::Retrying`] between attempts while they cannot be
+    /// reached
+    async fn reach_callee(
+        &self,
+        call_id: CallId,
+        callee: &str,
+        schedule: RetrySchedule,
+    ) -> Result<(), ServiceError> {
+        let remote = transport_peer::<T::PeerId>(callee)?;
+        let cancel = CancellationToken::new();
+        self.retries.lock().insert(call_id, cancel.clone());
+        let mut retry = 0;
+        let reached = loop {
+            let error = match self.connect_endpoint(&remote).await {
+                Ok(_) => break Ok(()),
+                Err(e) => e,
+            };
+            if retry == schedule.retries || cancel.is_cancelled() {
+                break Err(error);
+            }
+            retry += 1;
+            let delay = schedule.delay(retry);
+            tracing::info!(
+                "Could not reach {} for call {}, retry {} in {:?}: {}",
+                callee,
+                call_id,
+                retry,
+                delay,
+                error
+            );
+            if let Err(e) = self.call_manager.set_retrying(call_id, true) {
+                break Err(e.into());
+            }
+            let _ = self.event_sender.send(WebRtcEvent::CallRetrying {
+                call_id,
+                retry,
+                delay,
+                error: error.to_string(),
+            });
+            tokio::select! {
+                () = tokio::time::sleep(delay) => {}
+                () = cancel.cancelled() => {
+                    break Err(CallError::Interrupted(Interrupted::Cancelled).into());
+                }
+            }
+            if let Err(e) = self.call_manager.set_retrying(call_id, false) {
+                break Err(e.into());
+            }
+        };
+        self.retries.lock().remove(&call_id);
+        reached
+    }
+
+    /// Get ready for a likely call to `peer`, such as when the user opens
+    /// their contact page
+    ///
+    /// Resolves the peer's endpoint and connects to it, and sets up the
+    /// peer connection the next call to them picks up, so pressing call
+    /// waits on neither. Cheap to repeat; an unused peer connection is
+    /// dropped after [`CallManagerConfig::prewarm_ttl`].
+    ///
+    /// Returns the endpoint connected to, or `None` if the peer could not
+    /// be found and only the peer connection was set up.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the peer ID is invalid, discovery or connecting
+    /// fails, or the peer connection cannot be created
+    pub async fn prewarm(&self, peer: &I) -> Result<Option<SocketAddr>, ServiceError> {
+        let peer_id = peer.to_string_repr();
+        let remote = transport_peer::<T::PeerId>(&peer_id)?;
+        let (warmed, endpoint) = tokio::join!(
+            self.call_manager.prewarm(peer),
+            self.connect_endpoint(&remote)
+        );
+        warmed?;
+        let endpoint = endpoint?;
+        tracing::info!("Prewarmed call to {} at {:?}", peer_id, endpoint);
+        Ok(endpoint)
+    }
+
+    /// Discover a peer's endpoint and connect to it
+    async fn connect_endpoint(&self, peer: &T::PeerId) -> Result<Option<SocketAddr>, ServiceError> {
+        let endpoint = self
+            .signaling
+            .discover_peer_endpoint(peer)
             .await
-            .map_err(|e| ServiceError::CallError(e.to_string()))
+            .map_err(|e| ServiceError::SignalingError(e.into()))?;
+        if let Some(addr) = endpoint {
+            self.signaling
+                .connect_peer(peer, addr)
+                .await
+                .map_err(|e| ServiceError::SignalingError(e.into()))?;
+        }
+        Ok(endpoint)
+    }
+
+    /// Open a connection to a peer for text messaging only
+    ///
+    /// Presence and chat share the transport, identities and encryption of
+    /// calls. Messages arrive as [`CallEvent::TextReceived`]; upgrade the
+    /// connection to a call with [`Self::add_media`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the connection cannot be set up
+    pub async fn connect_data(&self, peer: I) -> Result<CallId, ServiceError> {
+        Ok(self.call_manager.connect_data(peer).await?)
+    }
+
+    /// Add media to a call, such as a connection opened with
+    /// [`Self::connect_data`], then renegotiate it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or a track cannot be set up
+    pub async fn add_media(
+        &self,
+        call_id: CallId,
+        constraints: MediaConstraints,
+    ) -> Result<(), ServiceError> {
+        self.call_manager.add_media(call_id, constraints).await?;
+        if self.low_bandwidth_mode() {
+            self.apply_low_bandwidth(call_id, true).await?;
+        }
+        Ok(())
     }
 
     /// Accept a call
     ///
+    /// Incoming calls are answered over signaling.
+    ///
     /// # Errors
     ///
-    /// Returns error if call cannot be accepted
+    /// Returns error if call cannot be accepted or the answer cannot be sent
     pub async fn accept_call(
         &self,
         call_id: CallId,
         constraints: MediaConstraints,
     ) -> Result<(), ServiceError> {
-        self.call_manager
-            .accept_call(call_id, constraints)
-            .await
-            .map_err(|e| ServiceError::CallError(e.to_string()))
+        if let Some(sdp) = self.call_manager.accept_call(call_id, constraints).await? {
+            let caller = self
+                .call_manager
+                .remote_peer(call_id)
+                .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+            self.send_to(
+                &caller.to_string_repr(),
+                SignalingMessage::Answer {
+                    session_id: call_id.to_string(),
+                    sdp,
+                    quic_endpoint: None,
+                },
+            )
+            .await?;
+        }
+        if self.low_bandwidth_mode() {
+            self.apply_low_bandwidth(call_id, true).await?;
+        }
+        Ok(())
     }
 
     /// Reject a call
     ///
+    /// The call is torn down, and the caller of an incoming call is told
+    /// over signaling.
+    ///
     /// # Errors
     ///
-    /// Returns error if call cannot be rejected
+    /// Returns error if call cannot be rejected or the caller cannot be told
     pub async fn reject_call(&self, call_id: CallId) -> Result<(), ServiceError> {
-        self.call_manager
-            .reject_call(call_id)
-            .await
-            .map_err(|e| ServiceError::CallError(e.to_string()))
+        let caller = self
+            .call_manager
+            .is_incoming(call_id)
+            .then(|| self.call_manager.remote_peer(call_id))
+            .flatten();
+        self.call_manager.reject_call(call_id).await?;
+        self.close_call(call_id, ByeReason::Declined).await?;
+        if let Some(caller) = caller {
+            self.send_to(
+                &caller.to_string_repr(),
+                SignalingMessage::Bye {
+                    session_id: call_id.to_string(),
+                    reason: Some(ByeReason::Declined),
+                },
+            )
+            .await?;
+        }
+        Ok(())
     }
 
     /// End a call
     ///
+    /// The remote party is told with a [`SignalingMessage::Bye`] and ends
+    /// the call too.
+    ///
     /// # Errors
     ///
     /// Returns error if call cannot be ended
     pub async fn end_call(&self, call_id: CallId) -> Result<(), ServiceError> {
+        self.hang_up(call_id, ByeReason::HungUp).await
+    }
+
+    /// End a call for `reason`, telling the remote party
+    ///
+    /// They may be out of reach, so failing to tell them is only logged.
+    async fn hang_up(&self, call_id: CallId, reason: ByeReason) -> Result<(), ServiceError> {
+        let remote_peer = self.call_manager.remote_peer(call_id);
+        self.close_call(call_id, reason).await?;
+        let Some(peer) = remote_peer else {
+            return Ok(());
+        };
+        let bye = SignalingMessage::Bye {
+            session_id: call_id.to_string(),
+            reason: Some(reason),
+        };
+        if let Err(e) = self.send_to(&peer.to_string_repr(), bye).await {
+            tracing::warn!("Could not tell {} call {} ended: {}", peer, call_id, e);
+        }
+        Ok(())
+    }
+
+    /// End a call for `reason` without telling the remote party
+    async fn close_call(&self, call_id: CallId, reason: ByeReason) -> Result<(), ServiceError> {
+        self.abort_call_retry(call_id);
+        self.quality_monitor.lock().remove_call(call_id);
+        self.stats_history.lock().end_call(call_id);
+        self.stats_sampler.lock().remove_call(call_id);
+        self.congestion.lock().remove_call(call_id);
+        self.remote_levels.lock().remove(&call_id);
+        self.remote_video.end_call(call_id);
+        self.media.watchdog().unwatch_call(call_id);
+        if let Some(renderer) = self.audio_renderer.lock().as_ref() {
+            renderer.remove_call(call_id);
+        }
+        self.audio_captures.lock().remove(&call_id);
+        self.ladders.lock().remove(&call_id);
+        self.encode_loads.lock().remove(&call_id);
+        self.snapshots.remove_call(call_id);
+        let recorder = self.recorders.lock().remove(&call_id);
+        if let Some(recorder) = recorder {
+            let files = recorder.files().to_vec();
+            let error = recorder.finish().err().map(|e| e.to_string());
+            let _ = self.event_sender.send(WebRtcEvent::RecordingStopped {
+                call_id,
+                files,
+                error,
+            });
+        }
+        self.parking.lock().release(call_id);
+        self.handoffs.lock().release(call_id);
+        self.keyframe_requests
+            .lock()
+            .retain(|(id, _), _| *id != call_id);
+        self.clocks.lock().remove(&call_id);
+        self.latency.lock().remove(&call_id);
+        self.negotiated.lock().remove(&call_id);
+        self.sessions
+            .lock()
+            .close(&call_id.to_string(), Instant::now());
         self.call_manager
-            .end_call(call_id)
+            .end_call_with(call_id, reason)
             .await
-            .map_err(|e| ServiceError::CallError(e.to_string()))
+            .map_err(ServiceError::from)
     }
 
-    /// Get call state
-    #[must_use]
-    pub async fn get_call_state(&self, call_id: CallId) -> Option<CallState> {
-        self.call_manager.get_call_state(call_id).await
+    /// Park a connected call, pausing its outgoing media
+    ///
+    /// `owner_id` is the local identity. Only it can retrieve the call, from
+    /// this session or from another device signed in as the same identity.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or is not connected
+    pub async fn park_call(
+        &self,
+        call_id: CallId,
+        owner_id: &str,
+    ) -> Result<ParkToken, ServiceError> {
+        self.call_manager.park_call(call_id).await?;
+        Ok(self.parking.lock().park(call_id, owner_id))
     }
 
-    /// Subscribe to events
-    #[must_use]
-    pub fn subscribe_events(&self) -> broadcast::Receiver<WebRtcEvent<I>> {
-        self.event_sender.subscribe()
+    /// Retrieve a parked call
+    ///
+    /// A call parked in this session resumes in place and its ID is
+    /// returned. Otherwise the token's owner is asked for it: the device
+    /// holding the call checks that we are the same identity, ends its leg
+    /// and answers with [`WebRtcEvent::CallHandoff`] naming the remote party
+    /// to call.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the token belongs to another identity, the call
+    /// cannot be resumed, or the request cannot be sent
+    pub async fn retrieve_call(&self, token: &ParkToken) -> Result<Option<CallId>, ServiceError> {
+        let parked = self.parking.lock().retrieve(&token.secret, &token.owner);
+        match parked {
+            Ok(call_id) => {
+                self.call_manager.resume_call(call_id).await?;
+                Ok(Some(call_id))
+            }
+            Err(ParkingError::NotFound(_)) => {
+                self.send_to(
+                    &token.owner,
+                    SignalingMessage::RetrieveCall {
+                        session_id: token.secret.clone(),
+                    },
+                )
+                .await?;
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
-    /// Create a builder
+    /// Calls parked in this session
     #[must_use]
-    pub fn builder(signaling: Arc<SignalingHandler<T>>) -> WebRtcServiceBuilder<I, T> {
-        WebRtcServiceBuilder::new(signaling)
+    pub fn parked_calls(&self) -> Vec<ParkedCall> {
+        self.parking.lock().parked().cloned().collect()
     }
-}
-
-/// WebRTC service builder
-pub struct WebRtcServiceBuilder<I: PeerIdentity, T: SignalingTransport> {
-    signaling: Arc<SignalingHandler<T>>,
-    config: WebRtcConfig,
-    _phantom: std::marker::PhantomData<I>,
-}
 
-impl<I: PeerIdentity, T: SignalingTransport> WebRtcServiceBuilder<I, T> {
-    /// Create new builder
-    #[must_use]
-    pub fn new(signaling: Arc<SignalingHandler<T>>) -> Self {
-        Self {
-            signaling,
-            config: WebRtcConfig::default(),
-            _phantom: std::marker::PhantomData,
+    /// Offer a connected call to this identity's other devices
+    ///
+    /// `owner_id` is the local identity. The offer is addressed to it so a
+    /// multi-device transport delivers it to every other device. The call
+    /// keeps running here until a device completes the handoff.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call is not connected or the offer cannot be
+    /// sent
+    pub async fn offer_handoff(
+        &self,
+        call_id: CallId,
+        owner_id: &str,
+    ) -> Result<HandoffState, ServiceError> {
+        if self.call_manager.get_call_state(call_id).await != Some(CallState::Connected) {
+            return Err(CallError::InvalidState.into());
         }
+        let (remote_peer, constraints) = self
+            .call_manager
+            .remote_peer(call_id)
+            .zip(self.call_manager.constraints(call_id))
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+
+        let state = self.handoffs.lock().offer(
+            call_id,
+            owner_id,
+            &remote_peer.to_string_repr(),
+            constraints,
+        );
+        self.send_to(
+            owner_id,
+            SignalingMessage::HandoffOffer {
+                session_id: state.handoff_id.clone(),
+                state: state.clone(),
+            },
+        )
+        .await?;
+        Ok(state)
     }
 
-    /// Set configuration
+    /// Take a call offered by another of our devices
+    ///
+    /// Opens a new leg to the remote party with an offer that replaces the
+    /// old leg, and returns its call ID. Call
+    /// [`complete_handoff`](Self::complete_handoff) once it connects.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the new leg cannot be set up or offered
+    pub async fn accept_handoff(&self, state: HandoffState) -> Result<CallId, ServiceError> {
+        let remote_peer = identity_peer(&state.remote_peer)?;
+        let call_id = self
+            .reestablish(state.call_id, remote_peer, state.constraints.clone())
+            .await?;
+        self.handoffs.lock().take(state, call_id);
+        Ok(call_id)
+    }
+
+    /// Report that a taken handoff's new leg is connected
+    ///
+    /// The offering device hangs up its leg when it receives this.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if this device has not taken the handoff or the report
+    /// cannot be sent
+    pub async fn complete_handoff(&self, handoff_id: &str) -> Result<(), ServiceError> {
+        let state = self.handoffs.lock().finish(handoff_id)?;
+        self.send_to(
+            &state.owner,
+            SignalingMessage::HandoffComplete {
+                session_id: handoff_id.to_string(),
+            },
+        )
+        .await
+    }
+
+    /// Capture call sessions and rooms so a later process can resume them
     #[must_use]
-    pub fn with_config(mut self, config: WebRtcConfig) -> Self {
-        self.config = config;
-        self
+    pub fn snapshot(&self) -> ServiceSnapshot<I> {
+        let rooms = self.rooms.iter().map(|room| room.clone()).collect();
+        ServiceSnapshot::new(self.call_manager.call_snapshots(), rooms)
     }
 
-    /// Build the service
+    /// Resume the sessions in a snapshot taken by an earlier process
+    ///
+    /// Rooms are registered as-is. Each live call gets a new leg offered to
+    /// its remote party as a replacement for the old session, which the
+    /// remote side accepts in place of the leg that died with the old
+    /// process. A call that cannot be re-established is reported and
+    /// skipped rather than failing the whole restore.
     ///
     /// # Errors
     ///
-    /// Returns error if service creation fails
-    pub async fn build(self) -> Result<WebRtcService<I, T>, ServiceError> {
-        WebRtcService::new(self.signaling, self.config).await
+    /// Returns error if the snapshot was written in an unknown format
+    pub async fn restore(
+        &self,
+        snapshot: ServiceSnapshot<I>,
+    ) -> Result<Vec<RestoredCall>, ServiceError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(ServiceError::UnsupportedSnapshot {
+                version: snapshot.version,
+                supported: SNAPSHOT_VERSION,
+            });
+        }
+        for room in snapshot.rooms {
+            self.restore_room(room);
+        }
+
+        let mut restored = Vec::new();
+        for call in snapshot
+            .calls
+            .into_iter()
+            .filter(|call| call.is_resumable())
+        {
+            let result = self
+                .reestablish(call.call_id, call.remote_peer, call.constraints)
+                .await
+                .map_err(|e| ErrorReport::new(&e));
+            if let Err(report) = &result {
+                tracing::warn!("Could not resume call {}: {}", call.call_id, report);
+            }
+            restored.push(RestoredCall {
+                previous: call.call_id,
+                result,
+            });
+        }
+        Ok(restored)
+    }
+
+    /// Offer a new leg to `remote_peer` replacing the session `previous`
+    async fn reestablish(
+        &self,
+        previous: CallId,
+        remote_peer: I,
+        constraints: MediaConstraints,
+    ) -> Result<CallId, ServiceError> {
+        let remote = remote_peer.to_string_repr();
+        let call_id = self.place_call(remote_peer, constraints).await?;
+
+        let offered = async {
+            let sdp = self.call_manager.create_offer(call_id).await?;
+            self.send_to(
+                &remote,
+                SignalingMessage::ReplaceCall {
+                    session_id: call_id.to_string(),
+                    replaces: previous.to_string(),
+                    sdp,
+                },
+            )
+            .await
+        };
+        if let Err(e) = offered.await {
+            let _ = self.close_call(call_id, ByeReason::Error).await;
+            return Err(e);
+        }
+        Ok(call_id)
+    }
+
+    /// Ask the remote party to send a keyframe on a call
+    ///
+    /// Use when decoding the call's video fails. `track_id` names the
+    /// remote track to refresh, or `None` for all of them. Returns `false`
+    /// without sending if the same request went out less than 500 ms ago.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the request cannot be
+    /// sent
+    pub async fn request_keyframe(
+        &self,
+        call_id: CallId,
+        track_id: Option<&str>,
+    ) -> Result<bool, ServiceError> {
+        let remote_peer = self
+            .call_manager
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+
+        let key = (call_id, track_id.map(str::to_string));
+        let now = Instant::now();
+        {
+            let mut requests = self.keyframe_requests.lock();
+            if requests
+                .get(&key)
+                .is_some_and(|last| now.duration_since(*last) < KEYFRAME_REQUEST_INTERVAL)
+            {
+                return Ok(false);
+            }
+            requests.insert(key.clone(), now);
+        }
+
+        self.send_to(
+            &remote_peer.to_string_repr(),
+            SignalingMessage::KeyframeRequest {
+                session_id: call_id.to_string(),
+                track_id: key.1,
+            },
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Offer our codecs to the remote party on a call
+    ///
+    /// Call it as the side sending the SDP offer. The remote party answers
+    /// with its own codecs, and both sides then agree on the same ones; the
+    /// result arrives as a [`WebRtcEvent::CodecsNegotiated`] event.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the offer cannot be sent
+    pub async fn offer_capabilities(&self, call_id: CallId) -> Result<(), ServiceError> {
+        let remote_peer = self
+            .call_manager
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        self.send_to(
+            &remote_peer.to_string_repr(),
+            SignalingMessage::Capabilities {
+                session_id: call_id.to_string(),
+                capabilities: self.codec_preferences(),
+                reply: false,
+            },
+        )
+        .await
+    }
+
+    /// Tell `peer` what this side can do in a call, and learn the same of
+    /// them
+    ///
+    /// Send it when a contact comes online. Their reply is cached, and
+    /// [`initiate_call`](Self::initiate_call) then asks them only for media
+    /// they take.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the peer ID is invalid or the hello cannot be sent
+    pub async fn announce_presence(&self, peer: &I) -> Result<(), ServiceError> {
+        self.send_to(
+            &peer.to_string_repr(),
+            SignalingMessage::Hello {
+                session_id: Uuid::new_v4().to_string(),
+                capabilities: self.local_capabilities(),
+                reply: false,
+            },
+        )
+        .await
+    }
+
+    /// Capabilities advertised to contacts
+    #[must_use]
+    pub fn local_capabilities(&self) -> PeerCapabilities {
+        PeerCapabilities::with_codecs(self.codec_preferences())
+    }
+
+    /// Capabilities `peer` last advertised, if they have said hello
+    #[must_use]
+    pub fn contact_capabilities(&self, peer: &I) -> Option<PeerCapabilities> {
+        self.contacts.lock().get(&peer.to_string_repr()).cloned()
+    }
+
+    /// QoS parameters for each media stream type
+    #[must_use]
+    pub fn qos_policy(&self) -> &QoSPolicy {
+        &self.qos
+    }
+
+    /// Codecs offered to peers, most preferred first
+    #[must_use]
+    pub fn codec_preferences(&self) -> CodecCapabilities {
+        self.codecs.lock().clone()
+    }
+
+    /// Settings in use that [`reload_config`](Self::reload_config) can
+    /// change
+    ///
+    /// The service does not own the transport, so `transport` holds the
+    /// defaults; take it from
+    /// [`AntQuicTransport::config`](crate::transport::AntQuicTransport::config).
+    #[must_use]
+    pub fn reloadable_config(&self) -> ReloadableConfig {
+        ReloadableConfig {
+            codecs: self.codec_preferences(),
+            degradation: self.degradation_config.lock().clone(),
+            quality_policy: self.quality_monitor.lock().policy().clone(),
+            congestion: *self.congestion.lock().config(),
+            broadcast: *self.broadcast_config.lock(),
+            transport: TransportConfig::default(),
+        }
+    }
+
+    /// Apply new settings without restarting
+    ///
+    /// The whole config is validated first, and nothing changes if any of
+    /// it is invalid. Codec preferences apply to calls negotiated
+    /// afterwards and broadcast settings to broadcasts started afterwards;
+    /// running calls move to the new degradation ladder, emitting a
+    /// [`WebRtcEvent::Degradation`] where their level changes, and are
+    /// judged by the new quality and congestion thresholds from their next
+    /// sample. `transport` is not applied here; pass it to
+    /// [`AntQuicTransport::reload`](crate::transport::AntQuicTransport::reload).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a setting is invalid
+    pub fn reload_config(&self, config: ReloadableConfig) -> Result<(), ServiceError> {
+        config.validate()?;
+        let ReloadableConfig {
+            codecs,
+            degradation,
+            quality_policy,
+            congestion,
+            broadcast,
+            transport: _,
+        } = config;
+        *self.codecs.lock() = codecs;
+        *self.broadcast_config.lock() = broadcast;
+        self.quality_monitor.lock().set_policy(quality_policy);
+        self.congestion.lock().set_config(congestion);
+        // Stored first so ladders created meanwhile start on it too
+        *self.degradation_config.lock() = degradation.clone();
+        let events: Vec<_> = self
+            .ladders
+            .lock()
+            .values_mut()
+            .filter_map(|ladder| ladder.set_config(degradation.clone()))
+            .collect();
+        for event in events {
+            let _ = self.event_sender.send(WebRtcEvent::Degradation(event));
+        }
+        tracing::info!("Reloaded configuration");
+        Ok(())
+    }
+
+    /// Read settings from a JSON file and apply them with
+    /// [`reload_config`](Self::reload_config)
+    ///
+    /// Settings missing from the file take their defaults.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the file cannot be read or parsed, or a setting is
+    /// invalid
+    pub fn reload_config_file(&self, path: impl AsRef<Path>) -> Result<(), ServiceError> {
+        self.reload_config(ReloadableConfig::load(path)?)
+    }
+
+    /// Stream manager applying the configured QoS policy
+    #[must_use]
+    pub fn stream_manager(&self) -> QuicMediaStreamManager {
+        QuicMediaStreamManager::with_policy(self.qos.clone())
+    }
+
+    /// Codecs agreed for a call, once capabilities have been exchanged
+    #[must_use]
+    pub fn negotiated_codecs(&self, call_id: CallId) -> Option<NegotiatedCodecs> {
+        self.negotiated.lock().get(&call_id).copied()
+    }
+
+    /// Receive a call's decoded remote video in `sink`
+    ///
+    /// Frames arrive as RGB24 [`VideoFrame`]s tagged with their track, until
+    /// the sink is removed or the call ends.
+    pub fn on_remote_frame(
+        &self,
+        call_id: CallId,
+        sink: impl VideoFrameSink + 'static,
+    ) -> VideoSinkId {
+        self.remote_video.add_sink(call_id, Arc::new(sink))
+    }
+
+    /// Stop a sink added with [`Self::on_remote_frame`]; false if it was
+    /// already gone
+    pub fn remove_frame_sink(&self, id: VideoSinkId) -> bool {
+        self.remote_video.remove_sink(id)
+    }
+
+    /// Decode a frame received on a remote video track and hand it to the
+    /// call's sinks
+    ///
+    /// Decodes with the call's negotiated codec, or H.264 before codecs
+    /// have been negotiated. If frames keep arriving but none decode, the
+    /// pipeline watchdog resets the track's decoder and asks the remote
+    /// party for a keyframe.
+    ///
+    /// Frames received as packets are put back together by a
+    /// [`VideoFrameAssembler`](crate::frame_assembly::VideoFrameAssembler);
+    /// after one flagged `after_loss`, request a keyframe.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the frame cannot be decoded; request a keyframe to
+    /// recover
+    pub fn receive_video_frame(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        data: &[u8],
+    ) -> Result<VideoFrame, ServiceError>
+    where
+        T: 'static,
+    {
+        let codec = self
+            .negotiated_codecs(call_id)
+            .and_then(|codecs| codecs.video)
+            .unwrap_or(VideoCodec::H264);
+        let heartbeat = self.decoder_heartbeat(call_id, track_id);
+        if let Some(heartbeat) = &heartbeat {
+            heartbeat.frame_in();
+        }
+        let frame = self.remote_video.decode(call_id, track_id, codec, data)?;
+        if let Some(heartbeat) = &heartbeat {
+            heartbeat.frame_out();
+        }
+        self.remote_video.deliver(call_id, track_id, &frame);
+        self.record_video(
+            call_id,
+            RecordingSource::Remote,
+            data,
+            frame.width,
+            frame.height,
+        );
+        Ok(frame)
+    }
+
+    /// Heartbeat of a remote track's decoder, watching it on first use
+    ///
+    /// `None` if the call does not exist.
+    fn decoder_heartbeat(&self, call_id: CallId, track_id: &str) -> Option<Heartbeat>
+    where
+        T: 'static,
+    {
+        let watchdog = self.media.watchdog();
+        let target = WatchTarget::Decoder {
+            call_id,
+            track_id: track_id.to_string(),
+        };
+        if let Some(heartbeat) = watchdog.heartbeat(&target) {
+            return Some(heartbeat);
+        }
+
+        let remote_peer = self.call_manager.remote_peer(call_id)?;
+        let peer = remote_peer.to_string_repr().parse::<T::PeerId>().ok();
+        let signaling = Arc::clone(&self.signaling);
+        let remote_video = Arc::clone(&self.remote_video);
+        let runtime = self.runtime.clone();
+        let track_id = track_id.to_string();
+        let heartbeat = Heartbeat::new();
+        watchdog.watch(target, heartbeat.clone(), move |_: Recovery| {
+            remote_video.reset_decoder(call_id, &track_id);
+            let Some(peer) = peer.clone() else {
+                return;
+            };
+            let signaling = Arc::clone(&signaling);
+            let message = SignalingMessage::KeyframeRequest {
+                session_id: call_id.to_string(),
+                track_id: Some(track_id.clone()),
+            };
+            runtime.spawn_signaling(async move {
+                if let Err(e) = signaling.send_message(&peer, message).await {
+                    tracing::warn!("Failed to request keyframe on {}: {}", call_id, e);
+                }
+            });
+        });
+        Some(heartbeat)
+    }
+
+    /// Probe the remote party's clock on a call
+    ///
+    /// The answer arrives as a [`WebRtcEvent::ClockEstimated`] event. Probe a
+    /// few times after connecting and then periodically; each answer can
+    /// only improve the estimate.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the probe cannot be sent
+    pub async fn sync_clock(&self, call_id: CallId) -> Result<(), ServiceError> {
+        let remote_peer = self
+            .call_manager
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        self.send_to(
+            &remote_peer.to_string_repr(),
+            SignalingMessage::ClockProbe {
+                session_id: call_id.to_string(),
+                sent_at: chrono::Utc::now(),
+            },
+        )
+        .await
+    }
+
+    /// Estimated offset of the remote party's clock on a call
+    ///
+    /// `None` until a probe sent with [`Self::sync_clock`] is answered.
+    #[must_use]
+    pub fn clock_estimate(&self, call_id: CallId) -> Option<ClockEstimate> {
+        self.clocks.lock().get(&call_id)?.estimate()
+    }
+
+    /// Measure the path to a peer before calling it
+    ///
+    /// Sends the [`WebRtcConfig::path_probe`] train of padded probes over
+    /// the QUIC connection a call would use and waits for the peer to echo
+    /// them, counting probes not echoed within the timeout as lost. The
+    /// report recommends video, audio only, or not calling at all. Echoes
+    /// are only seen while [`Self::receive_signaling`] is running.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the peer ID is invalid or the probes cannot be sent
+    pub async fn probe_peer(&self, peer: &I) -> Result<PathProbeReport, ServiceError> {
+        let peer_id = peer.to_string_repr();
+        let remote = transport_peer::<T::PeerId>(&peer_id)?;
+        self.connect_endpoint(&remote).await?;
+
+        let probe_id = Uuid::new_v4().to_string();
+        let probe = PathProbe::new(&self.path_probe);
+        let padding = "0".repeat(probe.payload_bytes());
+        let done = Arc::new(tokio::sync::Notify::new());
+        self.path_probes.lock().insert(
+            probe_id.clone(),
+            PendingProbe {
+                peer: peer_id,
+                probe,
+                done: Arc::clone(&done),
+            },
+        );
+
+        for sequence in 0..self.path_probe.probes {
+            if let Some(pending) = self.path_probes.lock().get_mut(&probe_id) {
+                pending.probe.record_sent(sequence, Instant::now());
+            }
+            let sent = self
+                .signaling
+                .send_message(
+                    &remote,
+                    SignalingMessage::PathProbe {
+                        session_id: probe_id.clone(),
+                        sequence,
+                        padding: padding.clone(),
+                        reply: false,
+                    },
+                )
+                .await;
+            if let Err(e) = sent {
+                self.path_probes.lock().remove(&probe_id);
+                return Err(ServiceError::SignalingError(e.into()));
+            }
+        }
+
+        let _ = tokio::time::timeout(self.path_probe.timeout, done.notified()).await;
+        let pending = self.path_probes.lock().remove(&probe_id).ok_or_else(|| {
+            ServiceError::SignalingError(Box::new(SignalingError::SessionNotFound(probe_id)))
+        })?;
+        let report = pending.probe.report();
+        tracing::debug!(
+            "Path to {} rtt {:?} ms, loss {}%, {} kbps: {:?}",
+            pending.peer,
+            report.rtt_ms,
+            report.loss_percent,
+            report.throughput_kbps,
+            report.recommendation
+        );
+        Ok(report)
+    }
+
+    /// Record the stage timestamps of a frame received and rendered on a call
+    ///
+    /// Stamp [`LatencyStage::Decode`] and [`LatencyStage::Render`] on the
+    /// timing carried by the frame's RTP packet, then pass it here. Sender
+    /// stamps are corrected with the call's [`Self::clock_estimate`].
+    /// Returns `false` if the call does not exist or a stage is missing.
+    ///
+    /// [`LatencyStage::Decode`]: crate::latency::LatencyStage::Decode
+    /// [`LatencyStage::Render`]: crate::latency::LatencyStage::Render
+    pub fn record_frame_timing(&self, call_id: CallId, timing: &FrameTiming) -> bool {
+        if self.call_manager.remote_peer(call_id).is_none() {
+            return false;
+        }
+        let clock = self.clock_estimate(call_id);
+        self.latency
+            .lock()
+            .entry(call_id)
+            .or_default()
+            .record(timing, clock.as_ref())
+    }
+
+    /// Where recent frames on a call spent their time, capture to render
+    ///
+    /// `None` until a frame is recorded with [`Self::record_frame_timing`].
+    #[must_use]
+    pub fn latency_breakdown(&self, call_id: CallId) -> Option<LatencyBreakdown> {
+        self.latency.lock().get(&call_id)?.breakdown()
+    }
+
+    /// Report a quality sample for a call
+    ///
+    /// Emits `QualityChanged` and any newly raised quality alerts as
+    /// service events, and returns those alerts. The sample is also kept in
+    /// the call's stats history, its loss checked for congestion and fed to
+    /// the microphone's Opus FEC.
+    ///
+    /// Fill in [`CallQualityMetrics::audio`] from the call's jitter buffer
+    /// so alerts also catch audio that plays out badly.
+    pub fn report_quality(
+        &self,
+        call_id: CallId,
+        metrics: CallQualityMetrics,
+        relayed: bool,
+    ) -> Vec<QualityAlert> {
+        let alerts = self
+            .quality_monitor
+            .lock()
+            .observe(call_id, &metrics, relayed);
+        self.stats_history.lock().record(call_id, metrics.clone());
+        let congestion = self
+            .congestion
+            .lock()
+            .observe_loss(call_id, metrics.packet_loss_percent);
+        self.media
+            .packet_loss_feedback()
+            .report(metrics.packet_loss_percent);
+        if let Some(severity) = congestion {
+            self.emit_congestion(call_id, severity);
+        }
+
+        let _ = self
+            .event_sender
+            .send(WebRtcEvent::Call(CallEvent::QualityChanged {
+                call_id,
+                metrics,
+            }));
+        for alert in &alerts {
+            tracing::warn!("Call {}: {}", call_id, alert.message);
+            let _ = self
+                .event_sender
+                .send(WebRtcEvent::QualityAlert(alert.clone()));
+        }
+
+        alerts
+    }
+
+    /// Report how much media is waiting in a call's send pacer
+    ///
+    /// The queue is measured as the time needed to drain `queued_bytes` at
+    /// `pacing_rate_bps`. Emits [`WebRtcEvent::CongestionDetected`] and
+    /// returns the severity when, together with the loss last reported
+    /// through [`Self::report_quality`], it moves to a new level.
+    pub fn report_pacer_queue(
+        &self,
+        call_id: CallId,
+        queued_bytes: usize,
+        pacing_rate_bps: u64,
+    ) -> Option<CongestionSeverity> {
+        let severity = self
+            .congestion
+            .lock()
+            .observe_queue(call_id, queue_delay_ms(queued_bytes, pacing_rate_bps))?;
+        self.emit_congestion(call_id, severity);
+        Some(severity)
+    }
+
+    /// Congestion last reported for a call
+    #[must_use]
+    pub fn congestion(&self, call_id: CallId) -> CongestionSeverity {
+        self.congestion.lock().severity(call_id)
+    }
+
+    fn emit_congestion(&self, call_id: CallId, severity: CongestionSeverity) {
+        match severity {
+            CongestionSeverity::Clear => {
+                tracing::info!("Call {}: connection stable again", call_id);
+            }
+            _ => tracing::warn!("Call {}: connection unstable ({:?})", call_id, severity),
+        }
+        let _ = self
+            .event_sender
+            .send(WebRtcEvent::CongestionDetected { call_id, severity });
+    }
+
+    /// Per-second quality samples of a live or recently ended call, oldest
+    /// first
+    #[must_use]
+    pub fn stats_history(&self, call_id: CallId) -> Vec<CallQualityMetrics> {
+        self.stats_history
+            .lock()
+            .get(call_id)
+            .map(|history| history.samples().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Per-second quality samples of a call as CSV, for graphing or
+    /// regression analysis
+    ///
+    /// Works after the call has ended, until
+    /// [`StatsHistoryConfig::retained_calls`] later calls have ended too.
+    /// `None` if no samples were reported for the call.
+    #[must_use]
+    pub fn export_stats_csv(&self, call_id: CallId) -> Option<String> {
+        self.stats_history
+            .lock()
+            .get(call_id)
+            .map(|history| history.to_csv())
+    }
+
+    /// Statistics of a live call, modelled on WebRTC `getStats`
+    ///
+    /// Bitrates are measured since the previous call for the same call, so
+    /// poll at a steady interval such as once a second. Jitter is the last
+    /// reported through [`Self::report_quality`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist
+    pub async fn get_call_stats(&self, call_id: CallId) -> Result<CallStats, ServiceError> {
+        let transport = self.call_manager.transport_stats(call_id).await?;
+        let bitrates = self
+            .stats_sampler
+            .lock()
+            .sample(call_id, &transport, Instant::now());
+        let jitter_ms = self
+            .stats_history
+            .lock()
+            .get(call_id)
+            .and_then(|history| history.samples().last().map(|sample| sample.jitter_ms));
+        let codecs = self.negotiated_codecs(call_id);
+        Ok(CallStats {
+            call_id,
+            timestamp: chrono::Utc::now(),
+            transport,
+            jitter_ms,
+            send_bitrate_kbps: bitrates.map(|(sent, _)| sent),
+            receive_bitrate_kbps: bitrates.map(|(_, received)| received),
+            audio_codec: codecs.and_then(|codecs| codecs.audio),
+            video_codec: codecs.and_then(|codecs| codecs.video),
+            video: self.remote_video.decode_stats(call_id),
+        })
+    }
+
+    /// Feed a bandwidth estimate for a call into its degradation ladder
+    ///
+    /// Emits a [`WebRtcEvent::Degradation`] and returns the event when the
+    /// video level changes.
+    pub fn update_bandwidth_estimate(
+        &self,
+        call_id: CallId,
+        estimated_bps: u64,
+    ) -> Option<DegradationEvent> {
+        let event = self
+            .ladders
+            .lock()
+            .entry(call_id)
+            .or_insert_with(|| {
+                DegradationLadder::new(call_id, self.degradation_config.lock().clone())
+            })
+            .update(estimated_bps)?;
+
+        let _ = self
+            .event_sender
+            .send(WebRtcEvent::Degradation(event.clone()));
+        Some(event)
+    }
+
+    /// Report how long a frame of a call's video took to encode
+    ///
+    /// While the encoder cannot keep up, resolution or frame rate are
+    /// lowered below the degradation ladder's as
+    /// [`WebRtcConfig::encode_load`] prefers. Emits a
+    /// [`WebRtcEvent::EncodeLoad`] and returns the event when they change;
+    /// reconfigure the encoder from [`Self::adaptation_settings`] then.
+    pub fn report_encode_time(
+        &self,
+        call_id: CallId,
+        encode_time: Duration,
+    ) -> Option<EncodeLoadEvent> {
+        let settings = self
+            .ladders
+            .lock()
+            .entry(call_id)
+            .or_insert_with(|| {
+                DegradationLadder::new(call_id, self.degradation_config.lock().clone())
+            })
+            .settings();
+        let event = self
+            .encode_loads
+            .lock()
+            .entry(call_id)
+            .or_insert_with(|| EncodeLoadController::new(call_id, self.encode_load_config))
+            .record(encode_time, &settings)?;
+
+        let _ = self
+            .event_sender
+            .send(WebRtcEvent::EncodeLoad(event.clone()));
+        Some(event)
+    }
+
+    /// Encoder settings for a call's current degradation level and
+    /// encoder load, once a bandwidth estimate, encode time or
+    /// low-bandwidth mode has set one
+    #[must_use]
+    pub fn adaptation_settings(&self, call_id: CallId) -> Option<AdaptationSettings> {
+        let settings = self
+            .ladders
+            .lock()
+            .get(&call_id)
+            .map(DegradationLadder::settings)?;
+        Some(match self.encode_loads.lock().get(&call_id) {
+            Some(controller) => controller.restrict(settings),
+            None => settings,
+        })
+    }
+
+    /// Switch low-bandwidth mode on or off for every call
+    ///
+    /// While on, calls are held at audio-only with the reduced Opus bitrate
+    /// of [`DegradationConfig::low_bandwidth_audio_kbps`], and outgoing video
+    /// and screen share are suspended, including on calls started later.
+    /// Switching it off restores each call's previous level and video.
+    /// Emits a [`WebRtcEvent::Degradation`] for each call whose level changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a call's video cannot be paused or resumed
+    pub async fn set_low_bandwidth_mode(&self, enabled: bool) -> Result<(), ServiceError> {
+        if self.low_bandwidth.swap(enabled, Ordering::SeqCst) == enabled {
+            return Ok(());
+        }
+        tracing::info!(
+            "Low-bandwidth mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+        for call in self.call_manager.call_snapshots() {
+            self.apply_low_bandwidth(call.call_id, enabled).await?;
+        }
+        Ok(())
+    }
+
+    /// Whether low-bandwidth mode is on
+    #[must_use]
+    pub fn low_bandwidth_mode(&self) -> bool {
+        self.low_bandwidth.load(Ordering::SeqCst)
+    }
+
+    /// Turn push-to-talk on or off for captured microphones
+    pub fn set_push_to_talk(&self, enabled: bool) {
+        self.media.transmit_switch().set_push_to_talk(enabled);
+    }
+
+    /// Hold the talk key down or release it
+    ///
+    /// Only matters while push-to-talk is on.
+    pub fn set_transmit(&self, transmit: bool) {
+        self.media.transmit_switch().set_transmit(transmit);
+    }
+
+    /// Whether microphone audio is being sent right now
+    #[must_use]
+    pub fn is_transmitting(&self) -> bool {
+        self.media.transmit_switch().is_transmitting()
+    }
+
+    /// Push-to-talk switch, for UIs that bind it to a key
+    #[must_use]
+    pub fn transmit_switch(&self) -> TransmitSwitch {
+        self.media.transmit_switch().clone()
+    }
+
+    /// Play received call audio on an output device
+    ///
+    /// `device_id` selects one of the manager's
+    /// [`get_audio_output_devices`](MediaStreamManager::get_audio_output_devices);
+    /// `None` uses the system default. Replaces any device already playing.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the device cannot be found or opened
+    pub async fn start_audio_playback(
+        &self,
+        device_id: Option<&str>,
+    ) -> Result<AudioDevice, ServiceError> {
+        let renderer = self
+            .media
+            .start_audio_renderer(device_id)
+            .await
+            .map_err(|e| ServiceError::InitError(e.into()))?;
+        let device = renderer.device().clone();
+        *self.audio_renderer.lock() = Some(renderer);
+        Ok(device)
+    }
+
+    /// Stop playing received audio
+    pub fn stop_audio_playback(&self) {
+        self.audio_renderer.lock().take();
+    }
+
+    /// Move playback of received audio to another output device, e.g. a
+    /// headset plugged in mid-call
+    ///
+    /// Calls keep playing at their volumes, resampled to the new device's
+    /// rate; if nothing is playing yet, playback starts on the device. If
+    /// the device cannot be opened the current one keeps playing.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the device cannot be found or opened
+    pub async fn set_audio_output_device(
+        &self,
+        device_id: Option<&str>,
+    ) -> Result<AudioDevice, ServiceError> {
+        // Taken out so the lock is not held while the device opens
+        let playing = self.audio_renderer.lock().take();
+        let Some(mut renderer) = playing else {
+            return self.start_audio_playback(device_id).await;
+        };
+        let switched = renderer.switch_device(device_id).await.cloned();
+        *self.audio_renderer.lock() = Some(renderer);
+        Ok(switched?)
+    }
+
+    /// Capture a call's outgoing audio from another microphone, e.g. a
+    /// headset plugged in mid-call
+    ///
+    /// The call's audio track and encoder carry on, so the remote party
+    /// hears no renegotiation; a device running at another rate is
+    /// resampled. If the call is not capturing yet, capture starts on the
+    /// device. If the device cannot be opened the current one is kept.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call has no audio track, or the device cannot be
+    /// found or opened
+    pub async fn set_audio_input_device(
+        &self,
+        call_id: CallId,
+        device_id: Option<&str>,
+    ) -> Result<AudioDevice, ServiceError> {
+        let capturing = self.audio_captures.lock().remove(&call_id);
+        let Some(mut capture) = capturing else {
+            let capture = self
+                .call_manager
+                .start_audio_capture(call_id, device_id)
+                .await?;
+            let device = capture.device().clone();
+            self.audio_captures.lock().insert(call_id, capture);
+            return Ok(device);
+        };
+        let switched = capture.switch_device(device_id).await.cloned();
+        self.audio_captures.lock().insert(call_id, capture);
+        Ok(switched?)
+    }
+
+    /// Queue a decoded frame of a call's remote audio for playback
+    ///
+    /// The frame is metered for [`MediaEvent::AudioLevel`] either way, but
+    /// only played once [`start_audio_playback`](Self::start_audio_playback)
+    /// has opened a device.
+    pub fn play_audio(&self, call_id: CallId, frame: &AudioFrame) {
+        let level = self
+            .remote_levels
+            .lock()
+            .entry(call_id)
+            .or_insert_with(|| AudioLevelMeter::new(self.audio_levels))
+            .measure(&frame.data);
+        if let Some(level) = level {
+            let event = MediaEvent::audio_level(AudioLevelSource::Remote(call_id), level);
+            self.event_bus.media().publish(event.clone());
+            let _ = self.event_sender.send(WebRtcEvent::Media(event));
+        }
+        if let Some(renderer) = self.audio_renderer.lock().as_ref() {
+            renderer.push(call_id, frame);
+        }
+    }
+
+    /// Set how loud a call plays, from 0 (muted) to
+    /// [`MAX_VOLUME`](crate::audio_render::MAX_VOLUME)
+    ///
+    /// Volumes belong to the playing device and are forgotten when playback
+    /// stops; returns false if nothing is playing.
+    pub fn set_call_volume(&self, call_id: CallId, volume: f32) -> bool {
+        match self.audio_renderer.lock().as_ref() {
+            Some(renderer) => {
+                renderer.set_volume(call_id, volume);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How loud a call plays; 1 unless set
+    #[must_use]
+    pub fn call_volume(&self, call_id: CallId) -> f32 {
+        self.audio_renderer
+            .lock()
+            .as_ref()
+            .map_or(1.0, |renderer| renderer.volume(call_id))
+    }
+
+    /// Mute or unmute a call's outgoing audio and tell the remote party,
+    /// which sees a [`WebRtcEvent::RemoteMediaState`]
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist, its audio cannot be
+    /// switched or the remote party cannot be told
+    pub async fn set_audio_muted(&self, call_id: CallId, muted: bool) -> Result<(), ServiceError> {
+        self.call_manager.set_audio_muted(call_id, muted).await?;
+        self.send_media_state(call_id).await
+    }
+
+    /// Turn a call's outgoing video and screen share off or on and tell the
+    /// remote party, which sees a [`WebRtcEvent::RemoteMediaState`]
+    ///
+    /// Video turned off stays off when [low-bandwidth mode](Self::set_low_bandwidth_mode)
+    /// ends.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist, its video cannot be
+    /// switched or the remote party cannot be told
+    pub async fn set_video_enabled(
+        &self,
+        call_id: CallId,
+        enabled: bool,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .set_video_enabled(call_id, enabled)
+            .await?;
+        self.send_media_state(call_id).await
+    }
+
+    /// Whether a call's outgoing audio is muted; false for unknown calls
+    #[must_use]
+    pub fn is_audio_muted(&self, call_id: CallId) -> bool {
+        self.call_manager.is_audio_muted(call_id).unwrap_or(false)
+    }
+
+    /// Whether a call's outgoing video is turned on; false for unknown calls
+    #[must_use]
+    pub fn is_video_enabled(&self, call_id: CallId) -> bool {
+        self.call_manager.is_video_enabled(call_id).unwrap_or(false)
+    }
+
+    /// Share a display or window on a call, beside any screens already
+    /// shared, until the returned handle is dropped
+    ///
+    /// The remote party sees a [`WebRtcEvent::RemoteScreenShares`] naming
+    /// the share after the source. Renegotiate the call for it to receive
+    /// the new track. Dropping the handle stops the share as
+    /// [`Self::stop_screen_share`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist, the source cannot be
+    /// captured or the remote party cannot be told
+    pub async fn share_screen(
+        &self,
+        call_id: CallId,
+        source: &ScreenSource,
+        config: ScreenCaptureConfig,
+    ) -> Result<ScreenCapturer, ServiceError> {
+        let capturer = self
+            .call_manager
+            .start_screen_share(call_id, source, config)
+            .await?;
+        self.send_screen_shares(call_id).await?;
+        Ok(capturer)
+    }
+
+    /// Stop one screen share of a call and tell the remote party, which
+    /// sees a [`WebRtcEvent::RemoteScreenShares`] without it
+    ///
+    /// Renegotiate the call for the remote party to stop receiving the
+    /// track.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or screen share does not exist, it cannot
+    /// be detached or the remote party cannot be told
+    pub async fn stop_screen_share(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .remove_screen_share(call_id, track_id)
+            .await?;
+        self.send_screen_shares(call_id).await
+    }
+
+    /// Pause or resume one screen share of a call and tell the remote
+    /// party, which sees a [`WebRtcEvent::RemoteScreenShares`]
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or screen share does not exist, it cannot
+    /// be switched or the remote party cannot be told
+    pub async fn set_screen_share_paused(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        paused: bool,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .set_screen_share_paused(call_id, track_id, paused)
+            .await?;
+        self.send_screen_shares(call_id).await
+    }
+
+    /// Screen shares we send on a call
+    #[must_use]
+    pub fn screen_shares(&self, call_id: CallId) -> Vec<ScreenShare> {
+        self.call_manager.screen_shares(call_id)
+    }
+
+    /// Screen shares the remote party sends on a call
+    #[must_use]
+    pub fn remote_screen_shares(&self, call_id: CallId) -> Vec<ScreenShare> {
+        self.call_manager.remote_screen_shares(call_id)
+    }
+
+    /// Tracks we send on a call, as one stream
+    #[must_use]
+    pub fn local_stream(&self, call_id: CallId) -> Option<MediaStream> {
+        self.call_manager.local_stream(call_id)
+    }
+
+    /// Streams the remote party sends on a call
+    ///
+    /// Tracks join them as they arrive, announced by
+    /// [`CallEvent::RemoteTrackAdded`] on the [event bus](Self::event_bus).
+    #[must_use]
+    pub fn remote_streams(&self, call_id: CallId) -> Vec<MediaStream<RemoteTrack>> {
+        self.call_manager.remote_streams(call_id)
+    }
+
+    /// Pair a screen share track with an annotation data channel
+    ///
+    /// Cursor and drawing annotations from either party then arrive as
+    /// [`CallEvent::AnnotationReceived`] on the [event bus](Self::event_bus).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// opened
+    pub async fn open_annotation_channel(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .open_annotation_channel(call_id, track_id)
+            .await?;
+        Ok(())
+    }
+
+    /// Send a cursor position or drawing over a screen share's annotation
+    /// channel
+    ///
+    /// # Errors
+    ///
+    /// Returns error if no annotation channel is open for the track or the
+    /// annotation cannot be sent
+    pub async fn send_annotation(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        annotation: &Annotation,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .send_annotation(call_id, track_id, annotation)
+            .await?;
+        Ok(())
+    }
+
+    /// Open the text messaging channel on a call before negotiating it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// opened
+    pub async fn open_message_channel(&self, call_id: CallId) -> Result<(), ServiceError> {
+        self.call_manager.open_message_channel(call_id).await?;
+        Ok(())
+    }
+
+    /// Send a text message to the remote party of a call
+    ///
+    /// For a group conversation, send the same
+    /// [`TextMessage::in_group`](crate::messaging::TextMessage::in_group)
+    /// message over each member's connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if no messaging channel is open or the message cannot
+    /// be sent
+    pub async fn send_text(
+        &self,
+        call_id: CallId,
+        message: &TextMessage,
+    ) -> Result<(), ServiceError> {
+        self.call_manager.send_text(call_id, message).await?;
+        Ok(())
+    }
+
+    /// Open the DTMF channel on a call before negotiating it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// opened
+    pub async fn open_dtmf_channel(&self, call_id: CallId) -> Result<(), ServiceError> {
+        self.call_manager.open_dtmf_channel(call_id).await?;
+        Ok(())
+    }
+
+    /// Press keys on a call, such as to drive an IVR menu
+    ///
+    /// Key presses arrive at the remote party as
+    /// [`CallEvent::DtmfReceived`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a digit is not `0`-`9`, `*`, `#` or `A`-`D`, or no
+    /// DTMF channel is open
+    pub async fn send_dtmf(&self, call_id: CallId, digits: &str) -> Result<(), ServiceError> {
+        self.call_manager.send_dtmf(call_id, digits).await?;
+        Ok(())
+    }
+
+    /// Send what arrives on a remote track straight back to the remote party
+    ///
+    /// Packets are forwarded unchanged on the media runtime until the
+    /// remote track ends, in place of our own track of the same kind.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call or remote track does not exist, or the
+    /// call sends no track of that kind
+    pub async fn echo_track(&self, call_id: CallId, track_id: &str) -> Result<(), ServiceError> {
+        let forward = self.call_manager.echo_track(call_id, track_id).await?;
+        self.runtime.spawn_media(forward);
+        Ok(())
+    }
+
+    /// Open the data channel of a watch-together session on a call
+    ///
+    /// Play, pause and seek from the remote party then arrive as
+    /// [`CallEvent::SyncReceived`] on the [event bus](Self::event_bus).
+    /// Keep a [`SyncSession`](crate::playback_sync::SyncSession) per
+    /// session and apply them with the call's [`Self::clock_estimate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the channel cannot be
+    /// opened
+    pub async fn open_sync_session(
+        &self,
+        call_id: CallId,
+        session_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .open_sync_channel(call_id, session_id)
+            .await?;
+        Ok(())
+    }
+
+    /// Send a play, pause or seek returned by a
+    /// [`SyncSession`](crate::playback_sync::SyncSession)
+    ///
+    /// # Errors
+    ///
+    /// Returns error if no sync channel is open for the session or the
+    /// message cannot be sent
+    pub async fn send_sync(
+        &self,
+        call_id: CallId,
+        session_id: &str,
+        message: &SyncMessage,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .send_sync(call_id, session_id, message)
+            .await?;
+        Ok(())
+    }
+
+    /// Ask the remote party to let us control one of its screen shares
+    ///
+    /// The answer arrives as [`WebRtcEvent::RemoteControlGranted`] or
+    /// [`WebRtcEvent::RemoteControlEnded`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the request cannot be
+    /// sent
+    pub async fn request_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.send_remote_control(call_id, track_id, RemoteControlAction::Request)
+            .await
+    }
+
+    /// Let the remote party control one of our screen shares, typically
+    /// after the user accepted a [`WebRtcEvent::RemoteControlRequested`]
+    ///
+    /// Its input then arrives as [`CallEvent::RemoteInputReceived`] on the
+    /// [event bus](Self::event_bus) until control is revoked or released.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if [`WebRtcConfig::allow_remote_control`] is off, the
+    /// call does not exist, or the channel or grant cannot be set up
+    pub async fn grant_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), ServiceError> {
+        if !self.allow_remote_control {
+            return Err(CallError::from(RemoteControlError::Disabled).into());
+        }
+        let grant = self
+            .call_manager
+            .grant_remote_control(call_id, track_id)
+            .await?;
+        self.send_remote_control(call_id, track_id, RemoteControlAction::Grant { grant })
+            .await
+    }
+
+    /// Refuse a [`WebRtcEvent::RemoteControlRequested`]
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the answer cannot be
+    /// sent
+    pub async fn deny_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.send_remote_control(call_id, track_id, RemoteControlAction::Deny)
+            .await
+    }
+
+    /// Take control of one of our screen shares back
+    ///
+    /// Input is refused from this moment, even if the remote party has not
+    /// heard yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the remote party cannot
+    /// be told
+    pub async fn revoke_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.call_manager.revoke_remote_control(call_id, track_id);
+        self.send_remote_control(call_id, track_id, RemoteControlAction::Revoke)
+            .await
+    }
+
+    /// Give back control of a remote screen share
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or the remote party cannot
+    /// be told
+    pub async fn release_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.call_manager.release_remote_control(call_id, track_id);
+        self.send_remote_control(call_id, track_id, RemoteControlAction::Release)
+            .await
+    }
+
+    /// Send keyboard or mouse input to a remote screen share we were
+    /// granted control of
+    ///
+    /// # Errors
+    ///
+    /// Returns error if we do not control the screen share or the input
+    /// cannot be sent
+    pub async fn send_remote_input(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        input: &RemoteInput,
+    ) -> Result<(), ServiceError> {
+        self.call_manager
+            .send_remote_input(call_id, track_id, input)
+            .await?;
+        Ok(())
+    }
+
+    async fn send_remote_control(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        action: RemoteControlAction,
+    ) -> Result<(), ServiceError> {
+        let remote_peer = self
+            .call_manager
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        self.send_to(
+            &remote_peer.to_string_repr(),
+            SignalingMessage::RemoteControl {
+                session_id: call_id.to_string(),
+                track_id: track_id.to_string(),
+                action,
+            },
+        )
+        .await
+    }
+
+    async fn send_media_state(&self, call_id: CallId) -> Result<(), ServiceError> {
+        let remote_peer = self
+            .call_manager
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        self.send_to(
+            &remote_peer.to_string_repr(),
+            SignalingMessage::MediaState {
+                session_id: call_id.to_string(),
+                audio_muted: self.is_audio_muted(call_id),
+                video_enabled: self.is_video_enabled(call_id),
+            },
+        )
+        .await
+    }
+
+    async fn send_screen_shares(&self, call_id: CallId) -> Result<(), ServiceError> {
+        let remote_peer = self
+            .call_manager
+            .remote_peer(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        self.send_to(
+            &remote_peer.to_string_repr(),
+            SignalingMessage::ScreenShares {
+                session_id: call_id.to_string(),
+                shares: self.screen_shares(call_id),
+            },
+        )
+        .await
+    }
+
+    async fn apply_low_bandwidth(
+        &self,
+        call_id: CallId,
+        enabled: bool,
+    ) -> Result<(), ServiceError> {
+        let event = self
+            .ladders
+            .lock()
+            .entry(call_id)
+            .or_insert_with(|| {
+                DegradationLadder::new(call_id, self.degradation_config.lock().clone())
+            })
+            .set_low_bandwidth(enabled);
+        if let Some(event) = event {
+            let _ = self.event_sender.send(WebRtcEvent::Degradation(event));
+        }
+        self.call_manager.set_video_paused(call_id, enabled).await?;
+        Ok(())
+    }
+
+    /// Leave a voice message for a peer after a rejected or missed call
+    ///
+    /// The message is sent over signaling so that transports with an
+    /// offline mailbox can deliver it once the peer comes online.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call is still active, the message exceeds the
+    /// configured limits, or sending fails
+    pub async fn leave_voicemail(
+        &self,
+        peer: &T::PeerId,
+        call_id: CallId,
+        voicemail: &Voicemail,
+    ) -> Result<(), ServiceError> {
+        if matches!(
+            self.call_manager.get_call_state(call_id).await,
+            Some(CallState::Connected)
+        ) {
+            return Err(CallError::InvalidState.into());
+        }
+
+        let message = voicemail.to_signaling();
+        // Validate against our own limits so we never send what we would reject
+        Voicemail::from_signaling(&message, &self.voicemail_config)?;
+
+        self.signaling
+            .send_message(peer, message)
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))?;
+        tracing::info!("Left {} ms voicemail for {}", voicemail.duration_ms, peer);
+        Ok(())
+    }
+
+    /// Handle an inbound signaling message from `from`
+    ///
+    /// Voicemail messages are validated and emitted as
+    /// [`WebRtcEvent::VoicemailReceived`]. Conference moderation actions are
+    /// checked against the sender's role and emitted as
+    /// [`WebRtcEvent::Conference`]. Join requests for conferences we host
+    /// are parked in the lobby when the waiting room is enabled. Requests
+    /// for calls parked here are only honored for the identity that parked
+    /// them, and device handoffs are checked the same way. Redeemed
+    /// invitations are verified and admit their sender through call
+    /// screening, which refuses offers from unknown callers when
+    /// [`WebRtcConfig::screen_unknown_callers`] is set and answers offers
+    /// from contacts with an [`AutoAnswer`] policy. Broadcast viewers join
+    /// and leave without call state, and the loss they report moves them
+    /// between video layers. Offers that pass screening are answered if the
+    /// call manager's [`AnswerPolicy`](crate::call::AnswerPolicy) says so
+    /// and otherwise ring as [`WebRtcEvent::IncomingCall`]. During another
+    /// call they are refused as busy instead, or ring as
+    /// [`WebRtcEvent::CallWaiting`] with [`WebRtcConfig::call_waiting`] set.
+    /// Offers renegotiating a call are answered right away, and answers and
+    /// ICE candidates from a call's remote party are applied to it. A
+    /// [`SignalingMessage::Bye`] from a call's remote party ends the call
+    /// with the reason they gave. Each call's session is tied to its remote
+    /// party, and once the call ends, offers replaying its session are
+    /// refused. Other messages are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if a voicemail is invalid, an offer cannot be taken
+    /// before [`Self::set_identity`] or replays an ended session, or a
+    /// moderation action refers to an unknown conference or is not
+    /// permitted for the sender
+    pub async fn handle_signaling_message(
+        &self,
+        from: &T::PeerId,
+        message: &SignalingMessage,
+    ) -> Result<(), ServiceError> {
+        let received_at = chrono::Utc::now();
+        match message {
+            SignalingMessage::Voicemail { .. } => {
+                if let Some(voicemail) = Voicemail::from_signaling(message, &self.voicemail_config)?
+                {
+                    tracing::info!("Received voicemail from {}", voicemail.sender);
+                    let _ = self
+                        .event_sender
+                        .send(WebRtcEvent::VoicemailReceived(voicemail));
+                }
+            }
+            SignalingMessage::ParticipantControl {
+                session_id,
+                participant_id,
+                action,
+            } => {
+                let conference_id = parse_conference_id(session_id)?;
+                let event = self.conference_mut(conference_id)?.apply(
+                    &from.to_string(),
+                    participant_id,
+                    *action,
+                )?;
+                self.conference_changed(event);
+            }
+            SignalingMessage::JoinConference { session_id, sdp } => {
+                let conference_id = parse_conference_id(session_id)?;
+                let outcome = self
+                    .conference_mut(conference_id)?
+                    .request_join(&from.to_string(), sdp.clone())?;
+                match outcome {
+                    JoinOutcome::Joined(event) => {
+                        self.conference_changed(event);
+                        self.signaling
+                            .send_message(from, join_decision_message(conference_id, true))
+                            .await
+                            .map_err(|e| ServiceError::SignalingError(e.into()))?;
+                    }
+                    JoinOutcome::Waiting(event) => {
+                        tracing::info!("{} is waiting to join conference {}", from, conference_id);
+                        let _ = self.event_sender.send(WebRtcEvent::Conference(event));
+                    }
+                }
+            }
+            SignalingMessage::JoinDecision {
+                session_id,
+                admitted,
+            } => {
+                let conference_id = parse_conference_id(session_id)?;
+                let _ = self.event_sender.send(WebRtcEvent::Conference(
+                    ConferenceEvent::JoinAnswered {
+                        conference_id,
+                        admitted: *admitted,
+                    },
+                ));
+            }
+            SignalingMessage::BreakoutUpdate { session_id, groups } => {
+                let conference_id = parse_conference_id(session_id)?;
+                let event = self
+                    .conference_mut(conference_id)?
+                    .set_breakouts(&from.to_string(), groups.clone())?;
+                self.conference_changed(event);
+            }
+            SignalingMessage::JoinRoom { session_id, sdp } => {
+                let room_id = RoomId::from(session_id.as_str());
+                let (conference_id, admitted) =
+                    match self.enter_room(&room_id, &from.to_string(), sdp.clone()) {
+                        Ok(Some(conference_id)) => (Some(conference_id.to_string()), true),
+                        // Parked in the lobby, answered when a host decides
+                        Ok(None) => return Ok(()),
+                        Err(e) => {
+                            tracing::info!("Refused {} entry to room {}: {}", from, room_id, e);
+                            (None, false)
+                        }
+                    };
+                self.signaling
+                    .send_message(
+                        from,
+                        SignalingMessage::RoomJoined {
+                            session_id: session_id.clone(),
+                            conference_id,
+                            admitted,
+                        },
+                    )
+                    .await
+                    .map_err(|e| ServiceError::SignalingError(e.into()))?;
+            }
+            SignalingMessage::RoomJoined {
+                session_id,
+                conference_id,
+                admitted,
+            } => {
+                let conference_id = conference_id
+                    .as_deref()
+                    .map(parse_conference_id)
+                    .transpose()?;
+                let _ = self.event_sender.send(WebRtcEvent::RoomJoined {
+                    room_id: RoomId::from(session_id.as_str()),
+                    conference_id,
+                    admitted: *admitted,
+                });
+            }
+            SignalingMessage::RetrieveCall { session_id } => {
+                let parked = self.parking.lock().retrieve(session_id, &from.to_string());
+                let remote_peer = match parked {
+                    Ok(call_id) => {
+                        let remote_peer = self.call_manager.remote_peer(call_id);
+                        // The retrieving device calls the remote party itself
+                        self.end_call(call_id).await?;
+                        remote_peer.map(|peer| peer.to_string_repr())
+                    }
+                    Err(e) => {
+                        tracing::info!("Refused call retrieval by {}: {}", from, e);
+                        None
+                    }
+                };
+                self.signaling
+                    .send_message(
+                        from,
+                        SignalingMessage::CallHandoff {
+                            session_id: session_id.clone(),
+                            remote_peer,
+                        },
+                    )
+                    .await
+                    .map_err(|e| ServiceError::SignalingError(e.into()))?;
+            }
+            SignalingMessage::CallHandoff { remote_peer, .. } => {
+                let remote_peer = remote_peer.as_deref().map(identity_peer::<I>).transpose()?;
+                let _ = self
+                    .event_sender
+                    .send(WebRtcEvent::CallHandoff { remote_peer });
+            }
+            SignalingMessage::HandoffOffer { state, .. } => {
+                // Our own offer echoed back by the transport
+                if self.handoffs.lock().is_outgoing(&state.handoff_id) {
+                    return Ok(());
+                }
+                if state.owner != from.to_string() {
+                    tracing::warn!("Ignoring handoff of {}'s call from {}", state.owner, from);
+                    return Ok(());
+                }
+                let _ = self
+                    .event_sender
+                    .send(WebRtcEvent::HandoffOffered(state.clone()));
+            }
+            SignalingMessage::ReplaceCall {
+                session_id,
+                replaces,
+                sdp,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                let replaced = parse_call_id(replaces)?;
+                // Only the party already on the call may move it, and only once
+                let same_party = self
+                    .call_manager
+                    .remote_peer(replaced)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if same_party && self.handoffs.lock().replace(replaced, call_id) {
+                    let _ = self.event_sender.send(WebRtcEvent::CallReplaced {
+                        replaced,
+                        call_id,
+                        sdp: sdp.clone(),
+                    });
+                } else {
+                    tracing::info!("Refused replacement of call {} by {}", replaced, from);
+                    self.signaling
+                        .send_message(
+                            from,
+                            SignalingMessage::Bye {
+                                session_id: session_id.clone(),
+                                reason: Some(ByeReason::Declined),
+                            },
+                        )
+                        .await
+                        .map_err(|e| ServiceError::SignalingError(e.into()))?;
+                }
+            }
+            SignalingMessage::HandoffComplete { session_id } => {
+                // Other devices of ours see this too; only the offerer acts
+                if !self.handoffs.lock().is_outgoing(session_id) {
+                    return Ok(());
+                }
+                let call_id = self
+                    .handoffs
+                    .lock()
+                    .complete(session_id, &from.to_string())?;
+                self.end_call(call_id).await?;
+                let _ = self
+                    .event_sender
+                    .send(WebRtcEvent::HandoffCompleted { call_id });
+            }
+            SignalingMessage::KeyframeRequest {
+                session_id,
+                track_id,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                // Only the party on the call may make our encoder spend bits
+                let from_remote = self
+                    .call_manager
+                    .remote_peer(call_id)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if from_remote {
+                    let _ = self.event_sender.send(WebRtcEvent::KeyframeRequested {
+                        call_id,
+                        track_id: track_id.clone(),
+                    });
+                } else {
+                    tracing::warn!(
+                        "Ignoring keyframe request for call {} from {}",
+                        call_id,
+                        from
+                    );
+                }
+            }
+            SignalingMessage::ClockProbe {
+                session_id,
+                sent_at,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                let from_remote = self
+                    .call_manager
+                    .remote_peer(call_id)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if !from_remote {
+                    tracing::warn!("Ignoring clock probe for call {} from {}", call_id, from);
+                    return Ok(());
+                }
+                self.signaling
+                    .send_message(
+                        from,
+                        SignalingMessage::ClockReply {
+                            session_id: session_id.clone(),
+                            probe_sent_at: *sent_at,
+                            received_at,
+                            replied_at: chrono::Utc::now(),
+                        },
+                    )
+                    .await
+                    .map_err(|e| ServiceError::SignalingError(e.into()))?;
+            }
+            SignalingMessage::ClockReply {
+                session_id,
+                probe_sent_at,
+                received_at: remote_received,
+                replied_at,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                let from_remote = self
+                    .call_manager
+                    .remote_peer(call_id)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if !from_remote {
+                    tracing::warn!("Ignoring clock reply for call {} from {}", call_id, from);
+                    return Ok(());
+                }
+                let exchange = ClockExchange {
+                    sent: *probe_sent_at,
+                    remote_received: *remote_received,
+                    remote_replied: *replied_at,
+                    received: received_at,
+                };
+                let estimate = self
+                    .clocks
+                    .lock()
+                    .entry(call_id)
+                    .or_default()
+                    .add_exchange(&exchange);
+                if let Some(estimate) = estimate {
+                    tracing::debug!(
+                        "Call {} peer clock offset {} us (rtt {} us)",
+                        call_id,
+                        estimate.offset_us,
+                        estimate.rtt_us
+                    );
+                    let _ = self
+                        .event_sender
+                        .send(WebRtcEvent::ClockEstimated { call_id, estimate });
+                }
+            }
+            SignalingMessage::Capabilities {
+                session_id,
+                capabilities,
+                reply,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                let from_remote = self
+                    .call_manager
+                    .remote_peer(call_id)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if !from_remote {
+                    tracing::warn!("Ignoring capabilities for call {} from {}", call_id, from);
+                    return Ok(());
+                }
+                let ours = self.codec_preferences();
+                let codecs = if *reply {
+                    CodecCapabilities::negotiate(&ours, capabilities)
+                } else {
+                    self.signaling
+                        .send_message(
+                            from,
+                            SignalingMessage::Capabilities {
+                                session_id: session_id.clone(),
+                                capabilities: ours.clone(),
+                                reply: true,
+                            },
+                        )
+                        .await
+                        .map_err(|e| ServiceError::SignalingError(e.into()))?;
+                    CodecCapabilities::negotiate(capabilities, &ours)
+                };
+                tracing::info!(
+                    "Call {} negotiated video {:?}, audio {:?}, cipher {}",
+                    call_id,
+                    codecs.video,
+                    codecs.audio,
+                    codecs.cipher
+                );
+                self.negotiated.lock().insert(call_id, codecs);
+                let _ = self
+                    .event_sender
+                    .send(WebRtcEvent::CodecsNegotiated { call_id, codecs });
+            }
+            SignalingMessage::Hello {
+                session_id,
+                capabilities,
+                reply,
+            } => {
+                tracing::debug!("{} advertised {:?}", from, capabilities);
+                self.contacts
+                    .lock()
+                    .insert(from.to_string(), capabilities.clone());
+                if !*reply {
+                    self.signaling
+                        .send_message(
+                            from,
+                            SignalingMessage::Hello {
+                                session_id: session_id.clone(),
+                                capabilities: self.local_capabilities(),
+                                reply: true,
+                            },
+                        )
+                        .await
+                        .map_err(|e| ServiceError::SignalingError(e.into()))?;
+                }
+            }
+            SignalingMessage::Offer {
+                session_id,
+                sdp,
+                quic_endpoint,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                if self.sessions.lock().is_stale(session_id) {
+                    return Err(SessionError::Stale(session_id.clone()).into());
+                }
+                let caller = from.to_string();
+                if let Some(remote_peer) = self.call_manager.remote_peer(call_id) {
+                    // Renegotiation of a call we already have, over the
+                    // caller's new path if their network changed
+                    if let Some(addr) =
+                        quic_endpoint.filter(|_| remote_peer.to_string_repr() == caller)
+                    {
+                        self.signaling
+                            .connect_peer(from, addr)
+                            .await
+                            .map_err(|e| ServiceError::SignalingError(e.into()))?;
+                    }
+                    self.receive_offer(from, call_id, sdp).await?;
+                    return Ok(());
+                }
+                let (decision, auto_answer) = {
+                    let mut screen = self.call_screen.lock();
+                    (
+                        screen.screen(&caller, received_at),
+                        screen.auto_answer(&caller),
+                    )
+                };
+                let active_call = self.call_manager.active_call();
+                if let Some(policy) = auto_answer.filter(|_| active_call.is_none()) {
+                    self.receive_offer(from, call_id, sdp).await?;
+                    self.answer_automatically(
+                        session_id,
+                        caller,
+                        MediaConstraints::audio_only(),
+                        policy.muted,
+                    )
+                    .await?;
+                } else if !self.guest_may_reach(&caller)
+                    || (self.screen_unknown_callers && decision == ScreeningDecision::Unknown)
+                {
+                    tracing::info!("Screened call {} from {}", session_id, caller);
+                    self.signaling
+                        .send_message(
+                            from,
+                            SignalingMessage::Bye {
+                                session_id: session_id.clone(),
+                                reason: Some(ByeReason::Declined),
+                            },
+                        )
+                        .await
+                        .map_err(|e| ServiceError::SignalingError(e.into()))?;
+                    let _ = self.event_sender.send(WebRtcEvent::CallScreened {
+                        session_id: session_id.clone(),
+                        caller,
+                    });
+                } else if active_call.is_some() && !self.call_waiting {
+                    tracing::info!("Busy, refusing call {} from {}", session_id, caller);
+                    self.send_busy(from, session_id).await?;
+                } else {
+                    match self.receive_offer(from, call_id, sdp).await {
+                        Err(ServiceError::CallError(CallError::CallLimitReached(limit))) => {
+                            tracing::info!(
+                                "At {} calls, refusing call {} from {}",
+                                limit,
+                                session_id,
+                                caller
+                            );
+                            return self.send_busy(from, session_id).await;
+                        }
+                        result => result?,
+                    }
+                    let constraints = MediaConstraints::from_media_types(&offered_media(sdp));
+                    if active_call.is_none() && self.call_manager.answer_policy().answers(&caller) {
+                        self.answer_automatically(session_id, caller, constraints, false)
+                            .await?;
+                    } else if let Some(caller) = self.call_manager.remote_peer(call_id) {
+                        tracing::info!("Incoming call {} from {}", call_id, caller);
+                        let event = match active_call {
+                            Some(active_call) => WebRtcEvent::CallWaiting {
+                                call_id,
+                                from: caller,
+                                constraints,
+                                active_call,
+                            },
+                            None => WebRtcEvent::IncomingCall {
+                                call_id,
+                                from: caller,
+                                constraints,
+                            },
+                        };
+                        let _ = self.event_sender.send(event);
+                    }
+                }
+            }
+            SignalingMessage::Answer {
+                session_id,
+                sdp,
+                quic_endpoint,
+            } => {
+                let Some(call_id) = self.session_call(session_id, from) else {
+                    tracing::warn!("Ignoring answer for {} from {}", session_id, from);
+                    return Ok(());
+                };
+                if let Some(addr) = *quic_endpoint {
+                    self.signaling
+                        .connect_peer(from, addr)
+                        .await
+                        .map_err(|e| ServiceError::SignalingError(e.into()))?;
+                }
+                self.call_manager
+                    .handle_answer(call_id, sdp.clone())
+                    .await?;
+            }
+            SignalingMessage::IceCandidate {
+                session_id,
+                candidate,
+                ..
+            } => {
+                let Some(call_id) = self.session_call(session_id, from) else {
+                    tracing::warn!("Ignoring ICE candidate for {} from {}", session_id, from);
+                    return Ok(());
+                };
+                match self
+                    .call_manager
+                    .add_ice_candidate(call_id, candidate.clone())
+                    .await
+                {
+                    // Paths the policy rules out are dropped quietly
+                    Err(CallError::PathRejected(_)) => {}
+                    result => result?,
+                }
+            }
+            SignalingMessage::Bye { session_id, reason } => {
+                // Broadcasts say bye too, and both sides of a call may
+                // hang up at once
+                let Some(call_id) = self.session_call(session_id, from) else {
+                    return Ok(());
+                };
+                let reason = reason.unwrap_or(ByeReason::HungUp);
+                tracing::info!("{} ended call {} ({:?})", from, call_id, reason);
+                self.close_call(call_id, reason).await?;
+            }
+            SignalingMessage::RedeemInvitation { session_id, token } => {
+                let caller = from.to_string();
+                let redeemed = self
+                    .invitations
+                    .lock()
+                    .redeem_by(token, &caller, received_at);
+                let accepted = match redeemed {
+                    Ok(()) => {
+                        self.call_screen.lock().admit(&caller, token);
+                        tracing::info!("{} redeemed invitation {}", caller, token.id);
+                        true
+                    }
+                    Err(e) => {
+                        tracing::info!("Refused invitation from {}: {}", caller, e);
+                        false
+                    }
+                };
+                self.signaling
+                    .send_message(
+                        from,
+                        SignalingMessage::InvitationRedeemed {
+                            session_id: session_id.clone(),
+                            accepted,
+                        },
+                    )
+                    .await
+                    .map_err(|e| ServiceError::SignalingError(e.into()))?;
+            }
+            SignalingMessage::InvitationRedeemed {
+                session_id,
+                accepted,
+            } => {
+                let invitation_id = Uuid::parse_str(session_id)
+                    .map_err(|_| SignalingError::SessionNotFound(session_id.clone()))
+                    .map_err(|e| ServiceError::SignalingError(Box::new(e)))?;
+                let _ = self.event_sender.send(WebRtcEvent::InvitationRedeemed {
+                    invitation_id,
+                    accepted: *accepted,
+                });
+            }
+            SignalingMessage::MediaState {
+                session_id,
+                audio_muted,
+                video_enabled,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                let from_remote = self
+                    .call_manager
+                    .remote_peer(call_id)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if !from_remote {
+                    tracing::warn!("Ignoring media state for call {} from {}", call_id, from);
+                    return Ok(());
+                }
+                let _ = self.event_sender.send(WebRtcEvent::RemoteMediaState {
+                    call_id,
+                    audio_muted: *audio_muted,
+                    video_enabled: *video_enabled,
+                });
+            }
+            SignalingMessage::ScreenShares { session_id, shares } => {
+                let call_id = parse_call_id(session_id)?;
+                let from_remote = self
+                    .call_manager
+                    .remote_peer(call_id)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if !from_remote {
+                    tracing::warn!("Ignoring screen shares for call {} from {}", call_id, from);
+                    return Ok(());
+                }
+                self.call_manager
+                    .set_remote_screen_shares(call_id, shares.clone());
+                let _ = self.event_sender.send(WebRtcEvent::RemoteScreenShares {
+                    call_id,
+                    shares: shares.clone(),
+                });
+            }
+            SignalingMessage::RemoteControl {
+                session_id,
+                track_id,
+                action,
+            } => {
+                let call_id = parse_call_id(session_id)?;
+                let from_remote = self
+                    .call_manager
+                    .remote_peer(call_id)
+                    .is_some_and(|peer| peer.to_string_repr() == from.to_string());
+                if !from_remote {
+                    tracing::warn!("Ignoring remote control for call {} from {}", call_id, from);
+                    return Ok(());
+                }
+                let event = match *action {
+                    RemoteControlAction::Request if !self.allow_remote_control => {
+                        tracing::info!(
+                            "Denying remote control of {} on call {}",
+                            track_id,
+                            call_id
+                        );
+                        return self.deny_remote_control(call_id, track_id).await;
+                    }
+                    RemoteControlAction::Request => WebRtcEvent::RemoteControlRequested {
+                        call_id,
+                        track_id: track_id.clone(),
+                    },
+                    RemoteControlAction::Grant { grant } => {
+                        self.call_manager
+                            .accept_remote_control(call_id, track_id, grant)?;
+                        WebRtcEvent::RemoteControlGranted {
+                            call_id,
+                            track_id: track_id.clone(),
+                        }
+                    }
+                    RemoteControlAction::Deny | RemoteControlAction::Revoke => {
+                        self.call_manager.release_remote_control(call_id, track_id);
+                        WebRtcEvent::RemoteControlEnded {
+                            call_id,
+                            track_id: track_id.clone(),
+                            action: *action,
+                        }
+                    }
+                    RemoteControlAction::Release => {
+                        self.call_manager.revoke_remote_control(call_id, track_id);
+                        WebRtcEvent::RemoteControlEnded {
+                            call_id,
+                            track_id: track_id.clone(),
+                            action: *action,
+                        }
+                    }
+                };
+                let _ = self.event_sender.send(event);
+            }
+            SignalingMessage::JoinBroadcast { session_id } => {
+                let broadcast_id = parse_broadcast_id(session_id)?;
+                let subscriber = from.to_string();
+                let joined = self.broadcast_mut(broadcast_id)?.join(&subscriber);
+                let accepted = match joined {
+                    Ok(layer) => {
+                        tracing::info!("{} joined broadcast {}", subscriber, broadcast_id);
+                        let _ = self.event_sender.send(WebRtcEvent::BroadcastSubscribed {
+                            broadcast_id,
+                            subscriber,
+                            layer,
+                        });
+                        true
+                    }
+                    Err(e) => {
+                        tracing::info!(
+                            "Refused {} on broadcast {}: {}",
+                            subscriber,
+                            broadcast_id,
+                            e
+                        );
+                        false
+                    }
+                };
+                self.signaling
+                    .send_message(
+                        from,
+                        SignalingMessage::BroadcastJoined {
+                            session_id: session_id.clone(),
+                            accepted,
+                        },
+                    )
+                    .await
+                    .map_err(|e| ServiceError::SignalingError(e.into()))?;
+            }
+            SignalingMessage::LeaveBroadcast { session_id } => {
+                let broadcast_id = parse_broadcast_id(session_id)?;
+                let subscriber = from.to_string();
+                if self.broadcast_mut(broadcast_id)?.leave(&subscriber) {
+                    let _ = self.event_sender.send(WebRtcEvent::BroadcastUnsubscribed {
+                        broadcast_id,
+                        subscriber,
+                    });
+                }
+            }
+            SignalingMessage::BroadcastFeedback {
+                session_id,
+                loss_percent,
+            } => {
+                let broadcast_id = parse_broadcast_id(session_id)?;
+                let subscriber = from.to_string();
+                let moved = self
+                    .broadcast_mut(broadcast_id)?
+                    .report_loss(&subscriber, f32::from(*loss_percent));
+                if let Some(layer) = moved {
+                    let _ = self.event_sender.send(WebRtcEvent::BroadcastLayerChanged {
+                        broadcast_id,
+                        subscriber,
+                        layer,
+                    });
+                }
+            }
+            SignalingMessage::BroadcastJoined {
+                session_id,
+                accepted,
+            } => {
+                let _ = self.event_sender.send(WebRtcEvent::BroadcastJoined {
+                    broadcast_id: parse_broadcast_id(session_id)?,
+                    accepted: *accepted,
+                });
+            }
+            SignalingMessage::PathProbe {
+                session_id,
+                sequence,
+                padding,
+                reply: false,
+            } => {
+                if padding.len() > MAX_PROBE_PADDING {
+                    tracing::warn!("Ignoring oversized path probe from {}", from);
+                    return Ok(());
+                }
+                self.signaling
+                    .send_message(
+                        from,
+                        SignalingMessage::PathProbe {
+                            session_id: session_id.clone(),
+                            sequence: *sequence,
+                            padding: padding.clone(),
+                            reply: true,
+                        },
+                    )
+                    .await
+                    .map_err(|e| ServiceError::SignalingError(e.into()))?;
+            }
+            SignalingMessage::PathProbe {
+                session_id,
+                sequence,
+                reply: true,
+                ..
+            } => {
+                let mut probes = self.path_probes.lock();
+                let Some(pending) = probes.get_mut(session_id) else {
+                    return Ok(());
+                };
+                if pending.peer != from.to_string() {
+                    tracing::warn!("Ignoring path probe echo for {} from {}", session_id, from);
+                    return Ok(());
+                }
+                pending.probe.record_reply(*sequence, Instant::now());
+                if pending.probe.is_complete() {
+                    pending.done.notify_one();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Issue an invitation letting its holder call `issuer_id` for `ttl`
+    ///
+    /// `issuer_id` is our own peer ID. Share the token with
+    /// [`CallToken::to_uri`], e.g. as a link or QR code.
+    #[must_use]
+    pub fn create_invitation(
+        &self,
+        issuer_id: &str,
+        ttl: chrono::Duration,
+        single_use: bool,
+    ) -> CallToken {
+        self.invitations.lock().issue(issuer_id, ttl, single_use)
+    }
+
+    /// Mint a guest identity that may call `issuer_id` for `ttl`, for
+    /// someone with no identity on the network
+    ///
+    /// `issuer_id` is our own peer ID. Share the token as for
+    /// [`create_invitation`](Self::create_invitation); the guest joins with
+    /// [`join_as_guest`](Self::join_as_guest).
+    #[must_use]
+    pub fn create_guest_invitation(&self, issuer_id: &str, ttl: chrono::Duration) -> CallToken {
+        self.invitations.lock().issue_guest(issuer_id, ttl)
+    }
+
+    /// Stop an invitation from being redeemed, and from letting in callers
+    /// who already redeemed it
+    pub fn revoke_invitation(&self, invitation_id: Uuid) {
+        self.invitations.lock().revoke(invitation_id);
+        self.call_screen.lock().revoke(invitation_id);
+    }
+
+    /// Let `peer_id` through call screening at any time
+    pub fn add_contact(&self, peer_id: &str) {
+        self.call_screen.lock().add_contact(peer_id);
+    }
+
+    /// Stop treating `peer_id` as a contact
+    pub fn remove_contact(&self, peer_id: &str) -> bool {
+        self.call_screen.lock().remove_contact(peer_id)
+    }
+
+    /// How call screening classifies `peer_id` right now
+    #[must_use]
+    pub fn screen_caller(&self, peer_id: &str) -> ScreeningDecision {
+        self.call_screen.lock().screen(peer_id, chrono::Utc::now())
+    }
+
+    /// Answer calls from `peer_id` as soon as they arrive, or let them ring
+    /// again with `None`
+    ///
+    /// Makes `peer_id` a contact. Answered calls raise
+    /// [`WebRtcEvent::CallAutoAnswered`].
+    pub fn set_auto_answer(&self, peer_id: &str, policy: Option<AutoAnswer>) {
+        self.call_screen.lock().set_auto_answer(peer_id, policy);
+    }
+
+    /// Auto-answer policy for calls from `peer_id`, if any
+    #[must_use]
+    pub fn auto_answer(&self, peer_id: &str) -> Option<AutoAnswer> {
+        self.call_screen.lock().auto_answer(peer_id)
+    }
+
+    /// Hand an offer from `from` to the call manager, answering it right
+    /// away when it renegotiates a call
+    async fn receive_offer(
+        &self,
+        from: &T::PeerId,
+        call_id: CallId,
+        sdp: &str,
+    ) -> Result<(), ServiceError> {
+        let callee = self.identity().ok_or(ServiceError::NoIdentity)?;
+        let caller = identity_peer(&from.to_string())?;
+        let answer = self
+            .call_manager
+            .receive_offer(call_id, caller, callee, sdp.to_string())
+            .await?;
+        self.sessions.lock().open(
+            &call_id.to_string(),
+            call_id,
+            &from.to_string(),
+            Instant::now(),
+        )?;
+        if let Some(sdp) = answer {
+            self.signaling
+                .send_message(
+                    from,
+                    SignalingMessage::Answer {
+                        session_id: call_id.to_string(),
+                        sdp,
+                        quic_endpoint: None,
+                    },
+                )
+                .await
+                .map_err(|e| ServiceError::SignalingError(e.into()))?;
+        }
+        Ok(())
+    }
+
+    /// Tell the caller of an offered call we are on another call
+    async fn send_busy(&self, from: &T::PeerId, session_id: &str) -> Result<(), ServiceError> {
+        self.signaling
+            .send_message(
+                from,
+                SignalingMessage::Bye {
+                    session_id: session_id.to_string(),
+                    reason: Some(ByeReason::Busy),
+                },
+            )
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))
+    }
+
+    /// Accept an offered call with `constraints`, muting our microphone if
+    /// `muted` is set
+    async fn answer_automatically(
+        &self,
+        session_id: &str,
+        caller: String,
+        constraints: MediaConstraints,
+        muted: bool,
+    ) -> Result<(), ServiceError> {
+        let call_id = parse_call_id(session_id)?;
+        let from_caller = self
+            .call_manager
+            .remote_peer(call_id)
+            .is_some_and(|peer| peer.to_string_repr() == caller);
+        if !from_caller {
+            tracing::warn!(
+                "Not auto-answering unknown call {} from {}",
+                call_id,
+                caller
+            );
+            return Ok(());
+        }
+        self.accept_call(call_id, constraints).await?;
+        if muted {
+            self.set_audio_muted(call_id, true).await?;
+        }
+        tracing::info!("Auto-answered call {} from {}", call_id, caller);
+        let _ = self.event_sender.send(WebRtcEvent::CallAutoAnswered {
+            call_id,
+            caller,
+            muted,
+        });
+        Ok(())
+    }
+
+    /// Take on the guest identity of a guest invitation and present it to
+    /// its issuer
+    ///
+    /// Signaling must reach this service under the returned identity,
+    /// [`CallToken::guest`]. From then on calls may only be placed to, and
+    /// taken from, the issuer, and none at all once the invitation expires.
+    /// The issuer's answer arrives as [`WebRtcEvent::InvitationRedeemed`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the invitation is not a guest invitation, names an
+    /// invalid identity, has expired or cannot be sent
+    pub async fn join_as_guest(&self, token: &CallToken) -> Result<I, ServiceError> {
+        let guest = token
+            .guest
+            .as_deref()
+            .ok_or(InvitationError::NotForGuest(token.id))?;
+        let identity: I = identity_peer(guest)?;
+        self.set_identity(identity.clone());
+        *self.guest_pass.lock() = Some(token.clone());
+        self.redeem_invitation(token).await?;
+        tracing::info!("Joined as guest {} of {}", guest, token.issuer);
+        Ok(identity)
+    }
+
+    /// Whether a guest invitation we joined with, if any, lets us reach
+    /// `peer_id`
+    fn guest_may_reach(&self, peer_id: &str) -> bool {
+        self.guest_pass
+            .lock()
+            .as_ref()
+            .is_none_or(|token| token.permits(peer_id, chrono::Utc::now()))
+    }
+
+    /// Present an invitation to its issuer so our calls to them ring
+    ///
+    /// The answer arrives as [`WebRtcEvent::InvitationRedeemed`]; call the
+    /// issuer once it is accepted.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the token has expired or sending fails
+    pub async fn redeem_invitation(&self, token: &CallToken) -> Result<(), ServiceError> {
+        if token.is_expired(chrono::Utc::now()) {
+            return Err(InvitationError::Expired(token.expires_at).into());
+        }
+        self.send_to(
+            &token.issuer,
+            SignalingMessage::RedeemInvitation {
+                session_id: token.id.to_string(),
+                token: token.clone(),
+            },
+        )
+        .await
+    }
+
+    /// Create a persistent room owned by `owner_id`
+    #[must_use]
+    pub fn create_room(&self, owner_id: &str, name: &str, settings: RoomSettings) -> RoomId {
+        let room_id = RoomId::new();
+        self.rooms.insert(
+            room_id.clone(),
+            Room::new(room_id.clone(), name, owner_id, settings),
+        );
+        room_id
+    }
+
+    /// Register a previously persisted room
+    pub fn restore_room(&self, room: Room) {
+        self.rooms.insert(room.id().clone(), room);
+    }
+
+    /// Get a room, e.g. to persist it
+    #[must_use]
+    pub fn room(&self, room_id: &RoomId) -> Option<Room> {
+        self.rooms.get(room_id).map(|room| room.clone())
+    }
+
+    /// Add a room member on behalf of host `actor_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the room does not exist or the actor is not a host
+    pub fn add_room_member(
+        &self,
+        room_id: &RoomId,
+        actor_id: &str,
+        participant_id: &str,
+        role: ParticipantRole,
+    ) -> Result<(), ServiceError> {
+        self.room_mut(room_id)?
+            .add_member(actor_id, participant_id, role)
+            .map_err(ServiceError::from)
+    }
+
+    /// Change a room's settings on behalf of host `actor_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the room does not exist or the actor is not a host
+    pub fn update_room_settings(
+        &self,
+        room_id: &RoomId,
+        actor_id: &str,
+        settings: RoomSettings,
+    ) -> Result<(), ServiceError> {
+        self.room_mut(room_id)?
+            .update_settings(actor_id, settings)
+            .map_err(ServiceError::from)
+    }
+
+    /// Remove a room member on behalf of host `actor_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the room does not exist, the actor is not a host,
+    /// or the participant is not a member
+    pub fn remove_room_member(
+        &self,
+        room_id: &RoomId,
+        actor_id: &str,
+        participant_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.room_mut(room_id)?
+            .remove_member(actor_id, participant_id)
+            .map_err(ServiceError::from)
+    }
+
+    /// Ask a room's host to let us in, or back in after a restart
+    ///
+    /// # Errors
+    ///
+    /// Returns error if sending fails
+    pub async fn join_room(
+        &self,
+        host: &T::PeerId,
+        room_id: &RoomId,
+        sdp: String,
+    ) -> Result<(), ServiceError> {
+        self.signaling
+            .send_message(
+                host,
+                SignalingMessage::JoinRoom {
+                    session_id: room_id.to_string(),
+                    sdp,
+                },
+            )
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))
+    }
+
+    /// Let a participant into a room's live conference, starting it if needed
+    ///
+    /// Returns `None` if the participant was parked in the lobby. Members
+    /// still listed in the conference (e.g. after a crash) simply rejoin.
+    fn enter_room(
+        &self,
+        room_id: &RoomId,
+        participant_id: &str,
+        sdp: String,
+    ) -> Result<Option<ConferenceId>, ServiceError> {
+        let mut room = self.room_mut(room_id)?;
+        let conference_id = match room
+            .conference()
+            .filter(|id| self.conferences.contains_key(id))
+        {
+            Some(conference_id) => conference_id,
+            None => {
+                let conference_id = ConferenceId::new();
+                self.conferences
+                    .insert(conference_id, Conference::new(conference_id, room.owner()));
+                room.set_conference(Some(conference_id));
+                tracing::info!("Room {} is live as conference {}", room_id, conference_id);
+                conference_id
+            }
+        };
+
+        let mut conference = self.conference_mut(conference_id)?;
+        if conference.participant(participant_id).is_some() {
+            tracing::info!("{} rejoined room {}", participant_id, room_id);
+            return Ok(Some(conference_id));
+        }
+        conference.set_waiting_room(room.settings().waiting_room);
+        let admission = room.admission(participant_id, conference.participants().count())?;
+
+        let event = match admission {
+            RoomAdmission::Join(role) => conference.add_participant(participant_id, role)?,
+            RoomAdmission::Lobby => match conference.request_join(participant_id, sdp)? {
+                JoinOutcome::Joined(event) => event,
+                JoinOutcome::Waiting(event) => {
+                    let _ = self.event_sender.send(WebRtcEvent::Conference(event));
+                    return Ok(None);
+                }
+            },
+        };
+        drop(conference);
+        self.conference_changed(event);
+        Ok(Some(conference_id))
+    }
+
+    /// Look up a room for modification
+    fn room_mut(&self, room_id: &RoomId) -> Result<RefMut<'_, RoomId, Room>, ServiceError> {
+        self.rooms
+            .get_mut(room_id)
+            .ok_or_else(|| RoomError::NotFound(room_id.to_string()).into())
+    }
+
+    /// Start a broadcast to be joined with [`WebRtcService::join_broadcast`]
+    ///
+    /// Publish its media with [`WebRtcService::set_broadcast_layer`] and
+    /// [`WebRtcService::set_broadcast_audio`], then pass every packet to
+    /// [`WebRtcService::route_broadcast_packet`].
+    #[must_use]
+    pub fn start_broadcast(&self) -> BroadcastId {
+        let broadcast = Broadcast::new(*self.broadcast_config.lock());
+        let broadcast_id = broadcast.id();
+        self.broadcasts.insert(broadcast_id, broadcast);
+        tracing::info!("Started broadcast {}", broadcast_id);
+        broadcast_id
+    }
+
+    /// Publish video `layer` of a broadcast from the stream with SSRC `ssrc`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the broadcast does not exist
+    pub fn set_broadcast_layer(
+        &self,
+        broadcast_id: BroadcastId,
+        layer: SimulcastLayer,
+        ssrc: u32,
+    ) -> Result<(), ServiceError> {
+        self.broadcast_mut(broadcast_id)?.set_layer(layer, ssrc);
+        Ok(())
+    }
+
+    /// Publish a broadcast's audio from the stream with SSRC `ssrc`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the broadcast does not exist
+    pub fn set_broadcast_audio(
+        &self,
+        broadcast_id: BroadcastId,
+        ssrc: u32,
+    ) -> Result<(), ServiceError> {
+        self.broadcast_mut(broadcast_id)?.set_audio(ssrc);
+        Ok(())
+    }
+
+    /// Copies of a published packet for each subscriber that gets it
+    ///
+    /// Send each copy to its subscriber with
+    /// [`AntQuicTransport::send_bytes_to`](crate::transport::AntQuicTransport::send_bytes_to);
+    /// nothing flows back but signaling.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the broadcast does not exist
+    pub fn route_broadcast_packet(
+        &self,
+        broadcast_id: BroadcastId,
+        packet: &RtpPacket,
+    ) -> Result<Vec<(String, RtpPacket)>, ServiceError> {
+        Ok(self.broadcast_mut(broadcast_id)?.route(packet))
+    }
+
+    /// Subscribers of a broadcast and the video layer each receives
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the broadcast does not exist
+    pub fn broadcast_subscribers(
+        &self,
+        broadcast_id: BroadcastId,
+    ) -> Result<Vec<(String, SimulcastLayer)>, ServiceError> {
+        Ok(self.broadcast_mut(broadcast_id)?.subscribers())
+    }
+
+    /// End a broadcast, telling its subscribers
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the broadcast does not exist
+    pub async fn end_broadcast(&self, broadcast_id: BroadcastId) -> Result<(), ServiceError> {
+        let (_, broadcast) = self
+            .broadcasts
+            .remove(&broadcast_id)
+            .ok_or(BroadcastError::NotFound(broadcast_id))?;
+        for (subscriber, _) in broadcast.subscribers() {
+            let bye = SignalingMessage::Bye {
+                session_id: broadcast_id.to_string(),
+                reason: Some(ByeReason::HungUp),
+            };
+            if let Err(e) = self.send_to(&subscriber, bye).await {
+                tracing::warn!("Could not tell {} broadcast ended: {}", subscriber, e);
+            }
+        }
+        tracing::info!("Ended broadcast {}", broadcast_id);
+        Ok(())
+    }
+
+    /// Ask a publisher to start sending us a broadcast
+    ///
+    /// The answer arrives as [`WebRtcEvent::BroadcastJoined`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if sending fails
+    pub async fn join_broadcast(
+        &self,
+        publisher: &T::PeerId,
+        broadcast_id: BroadcastId,
+    ) -> Result<(), ServiceError> {
+        self.signaling
+            .send_message(
+                publisher,
+                SignalingMessage::JoinBroadcast {
+                    session_id: broadcast_id.to_string(),
+                },
+            )
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))
+    }
+
+    /// Stop receiving a broadcast
+    ///
+    /// # Errors
+    ///
+    /// Returns error if sending fails
+    pub async fn leave_broadcast(
+        &self,
+        publisher: &T::PeerId,
+        broadcast_id: BroadcastId,
+    ) -> Result<(), ServiceError> {
+        self.signaling
+            .send_message(
+                publisher,
+                SignalingMessage::LeaveBroadcast {
+                    session_id: broadcast_id.to_string(),
+                },
+            )
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))
+    }
+
+    /// Tell a publisher the loss we see on its broadcast, so it can pick
+    /// the video layer we receive
+    ///
+    /// # Errors
+    ///
+    /// Returns error if sending fails
+    pub async fn report_broadcast_loss(
+        &self,
+        publisher: &T::PeerId,
+        broadcast_id: BroadcastId,
+        loss_percent: u8,
+    ) -> Result<(), ServiceError> {
+        self.signaling
+            .send_message(
+                publisher,
+                SignalingMessage::BroadcastFeedback {
+                    session_id: broadcast_id.to_string(),
+                    loss_percent,
+                },
+            )
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))
+    }
+
+    /// Look up a broadcast for modification
+    fn broadcast_mut(
+        &self,
+        broadcast_id: BroadcastId,
+    ) -> Result<RefMut<'_, BroadcastId, Broadcast>, ServiceError> {
+        self.broadcasts
+            .get_mut(&broadcast_id)
+            .ok_or_else(|| BroadcastError::NotFound(broadcast_id).into())
+    }
+
+    /// Create a conference hosted by `host_id`
+    #[must_use]
+    pub fn create_conference(&self, host_id: &str) -> ConferenceId {
+        let conference_id = ConferenceId::new();
+        self.conferences
+            .insert(conference_id, Conference::new(conference_id, host_id));
+        conference_id
+    }
+
+    /// Add a participant to a conference
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference does not exist or the participant
+    /// has already joined
+    pub fn add_participant(
+        &self,
+        conference_id: ConferenceId,
+        participant_id: &str,
+        role: ParticipantRole,
+    ) -> Result<(), ServiceError> {
+        let event = self
+            .conference_mut(conference_id)?
+            .add_participant(participant_id, role)?;
+        self.conference_changed(event);
+        Ok(())
+    }
+
+    /// Enable or disable the waiting room for a conference
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference does not exist
+    pub fn set_waiting_room(
+        &self,
+        conference_id: ConferenceId,
+        enabled: bool,
+    ) -> Result<(), ServiceError> {
+        self.conference_mut(conference_id)?
+            .set_waiting_room(enabled);
+        Ok(())
+    }
+
+    /// Ask a conference host to let us join
+    ///
+    /// # Errors
+    ///
+    /// Returns error if sending fails
+    pub async fn request_join_conference(
+        &self,
+        host: &T::PeerId,
+        conference_id: ConferenceId,
+        sdp: String,
+    ) -> Result<(), ServiceError> {
+        self.signaling
+            .send_message(
+                host,
+                SignalingMessage::JoinConference {
+                    session_id: conference_id.to_string(),
+                    sdp,
+                },
+            )
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))
+    }
+
+    /// Participants waiting in a conference's lobby
+    #[must_use]
+    pub fn join_requests(&self, conference_id: ConferenceId) -> Vec<JoinRequest> {
+        self.conferences
+            .get(&conference_id)
+            .map(|conference| conference.lobby().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Admit a participant from the lobby on behalf of host `actor_id`
+    ///
+    /// Returns the participant's parked SDP offer so it can be answered.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host, the participant is not
+    /// waiting, or signaling fails
+    pub async fn admit_participant(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+        participant_id: &str,
+        role: ParticipantRole,
+    ) -> Result<String, ServiceError> {
+        let (request, event) =
+            self.conference_mut(conference_id)?
+                .admit(actor_id, participant_id, role)?;
+        self.conference_changed(event);
+
+        self.send_to(participant_id, join_decision_message(conference_id, true))
+            .await?;
+        Ok(request.sdp)
+    }
+
+    /// Deny a participant in the lobby on behalf of host `actor_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host, the participant is not
+    /// waiting, or signaling fails
+    pub async fn deny_participant(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+        participant_id: &str,
+    ) -> Result<(), ServiceError> {
+        let event = self
+            .conference_mut(conference_id)?
+            .deny(actor_id, participant_id)?;
+        let _ = self.event_sender.send(WebRtcEvent::Conference(event));
+
+        self.send_to(participant_id, join_decision_message(conference_id, false))
+            .await
+    }
+
+    /// Feed a participant's RFC 6464 audio level into active speaker detection
+    ///
+    /// Emits a [`ConferenceEvent::ActiveSpeakerChanged`] when the dominant
+    /// speaker switches.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference does not exist
+    pub fn report_audio_level(
+        &self,
+        conference_id: ConferenceId,
+        participant_id: &str,
+        level: u8,
+    ) -> Result<(), ServiceError> {
+        let now_ms = u64::try_from(chrono::Utc::now().timestamp_millis()).unwrap_or(0);
+        let event =
+            self.conference_mut(conference_id)?
+                .observe_audio_level(participant_id, level, now_ms);
+        if let Some(event) = event {
+            self.conference_changed(event);
+        }
+        Ok(())
+    }
+
+    /// Simulcast layers to forward to a conference participant in SFU mode
+    #[must_use]
+    pub fn forwarding_layers(
+        &self,
+        conference_id: ConferenceId,
+        receiver_id: &str,
+    ) -> Vec<(String, SimulcastLayer)> {
+        self.conferences
+            .get(&conference_id)
+            .map(|conference| conference.forwarding_layers(receiver_id))
+            .unwrap_or_default()
+    }
+
+    /// Forward a conference's media through this node instead of a mesh
+    ///
+    /// Participants send their audio and simulcast layers here once.
+    /// Announce each stream with [`WebRtcService::publish_router_audio`] and
+    /// [`WebRtcService::publish_router_layer`], then pass every packet to
+    /// [`WebRtcService::route_conference_packet`]. Routing follows the
+    /// conference: audio from everyone in the same room, video at the
+    /// layers of [`WebRtcService::forwarding_layers`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference does not exist
+    pub fn start_media_router(&self, conference_id: ConferenceId) -> Result<(), ServiceError> {
+        drop(self.conference_mut(conference_id)?);
+        self.routers.entry(conference_id).or_default();
+        self.sync_media_router(conference_id);
+        tracing::info!("Routing media of conference {}", conference_id);
+        Ok(())
+    }
+
+    /// Stop forwarding a conference's media, returning whether it was
+    pub fn stop_media_router(&self, conference_id: ConferenceId) -> bool {
+        self.routers.remove(&conference_id).is_some()
+    }
+
+    /// Publish a participant's audio to the media router from the stream
+    /// with SSRC `ssrc`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference's media is not routed here, the
+    /// participant is not in it or the SSRC is another participant's
+    pub fn publish_router_audio(
+        &self,
+        conference_id: ConferenceId,
+        participant_id: &str,
+        ssrc: u32,
+    ) -> Result<(), ServiceError> {
+        self.router_mut(conference_id)?
+            .publish_audio(participant_id, ssrc)
+            .map_err(ServiceError::from)
+    }
+
+    /// Publish video `layer` of a participant to the media router from the
+    /// stream with SSRC `ssrc`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference's media is not routed here, the
+    /// participant is not in it or the SSRC is another participant's
+    pub fn publish_router_layer(
+        &self,
+        conference_id: ConferenceId,
+        participant_id: &str,
+        layer: SimulcastLayer,
+        ssrc: u32,
+    ) -> Result<(), ServiceError> {
+        self.router_mut(conference_id)?
+            .publish_layer(participant_id, layer, ssrc)
+            .map_err(ServiceError::from)
+    }
+
+    /// Cap the video layer a participant receives from the media router
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference's media is not routed here or the
+    /// participant is not in it
+    pub fn set_router_max_layer(
+        &self,
+        conference_id: ConferenceId,
+        participant_id: &str,
+        layer: SimulcastLayer,
+    ) -> Result<(), ServiceError> {
+        self.router_mut(conference_id)?
+            .set_max_layer(participant_id, layer)
+            .map_err(ServiceError::from)
+    }
+
+    /// Copies of a participant's packet for each participant that gets it
+    ///
+    /// Media the conference does not forward, such as a muted participant's
+    /// audio, is dropped. Send each copy to its participant with
+    /// [`AntQuicTransport::send_bytes_to`](crate::transport::AntQuicTransport::send_bytes_to).
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the conference's media is not routed here
+    pub fn route_conference_packet(
+        &self,
+        conference_id: ConferenceId,
+        packet: &RtpPacket,
+    ) -> Result<Vec<(String, RtpPacket)>, ServiceError> {
+        let publisher = self
+            .router_mut(conference_id)?
+            .publisher_of(packet.ssrc)
+            .map(str::to_string);
+        let media = match packet.stream_type {
+            StreamType::Audio => MediaType::Audio,
+            StreamType::Video => MediaType::Video,
+            StreamType::ScreenShare => MediaType::ScreenShare,
+            StreamType::Data => MediaType::DataChannel,
+        };
+        if let Some(publisher) = publisher {
+            let forwarded = self
+                .conferences
+                .get(&conference_id)
+                .is_some_and(|conference| conference.can_forward(&publisher, &media));
+            if !forwarded {
+                return Ok(Vec::new());
+            }
+        }
+        Ok(self.router_mut(conference_id)?.route(packet))
+    }
+
+    /// Forwarding counters of a conference's media router
+    #[must_use]
+    pub fn media_router_stats(&self, conference_id: ConferenceId) -> Option<RelayStats> {
+        self.routers
+            .get(&conference_id)
+            .map(|router| router.stats())
+    }
+
+    /// Look up a conference's media router for modification
+    fn router_mut(
+        &self,
+        conference_id: ConferenceId,
+    ) -> Result<RefMut<'_, ConferenceId, MediaRouter>, ServiceError> {
+        self.routers
+            .get_mut(&conference_id)
+            .ok_or_else(|| ConferenceError::NotRouted(conference_id.to_string()).into())
+    }
+
+    /// Publish a conference change, re-routing its media to match
+    fn conference_changed(&self, event: ConferenceEvent) {
+        self.sync_media_router(event.conference_id());
+        let _ = self.event_sender.send(WebRtcEvent::Conference(event));
+    }
+
+    /// Route a conference's media to who is in it, whom they hear and which
+    /// layers they see
+    fn sync_media_router(&self, conference_id: ConferenceId) {
+        if !self.routers.contains_key(&conference_id) {
+            return;
+        }
+        // Plan from the conference first; the router is never locked with it
+        let plan: Vec<(String, Vec<String>, Vec<(String, SimulcastLayer)>)> = {
+            let Some(conference) = self.conferences.get(&conference_id) else {
+                return;
+            };
+            conference
+                .participants()
+                .map(|participant| {
+                    let hears = conference
+                        .other_participants(&participant.id)
+                        .into_iter()
+                        .filter(|id| conference.same_room(id, &participant.id))
+                        .collect();
+                    let layers = conference.forwarding_layers(&participant.id);
+                    (participant.id.clone(), hears, layers)
+                })
+                .collect()
+        };
+
+        let Some(mut router) = self.routers.get_mut(&conference_id) else {
+            return;
+        };
+        let gone: Vec<String> = router
+            .participants()
+            .filter(|id| !plan.iter().any(|(participant, ..)| participant == id))
+            .map(str::to_string)
+            .collect();
+        for participant in gone {
+            router.remove_participant(&participant);
+        }
+        for (participant, ..) in &plan {
+            router.add_participant(participant);
+        }
+        for (participant, hears, layers) in &plan {
+            let routed = router
+                .set_audio_from(participant, hears)
+                .and_then(|()| router.set_video(participant, layers));
+            if let Err(e) = routed {
+                tracing::warn!("Could not route media to {}: {}", participant, e);
+            }
+        }
+    }
+
+    /// Split a conference into breakout groups on behalf of host `actor_id`
+    ///
+    /// Breakouts only change media routing, so participants keep their
+    /// existing QUIC connections. Participants not in a group stay in the
+    /// main room.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host, a group is invalid, or
+    /// signaling fails
+    pub async fn open_breakouts(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+        groups: Vec<BreakoutGroup>,
+    ) -> Result<(), ServiceError> {
+        let (event, recipients) = {
+            let mut conference = self.conference_mut(conference_id)?;
+            let event = conference.set_breakouts(actor_id, groups.clone())?;
+            (event, conference.other_participants(actor_id))
+        };
+        self.conference_changed(event);
+
+        let message = breakout_message(conference_id, groups);
+        for recipient in recipients {
+            self.send_to(&recipient, message.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// Close all breakout groups and return everyone to the main room
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the actor is not a host or signaling fails
+    pub async fn merge_breakouts(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.open_breakouts(conference_id, actor_id, Vec::new())
+            .await
+    }
+
+    /// Get the participants of a conference
+    #[must_use]
+    pub fn participants(&self, conference_id: ConferenceId) -> Vec<Participant> {
+        self.conferences
+            .get(&conference_id)
+            .map(|conference| conference.participants().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Mute or unmute a conference participant on behalf of `actor_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the action is not permitted or signaling fails
+    pub async fn mute_participant(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+        participant_id: &str,
+        muted: bool,
+    ) -> Result<(), ServiceError> {
+        self.moderate(
+            conference_id,
+            actor_id,
+            participant_id,
+            ParticipantAction::Mute { muted },
+        )
+        .await
+    }
+
+    /// Remove a participant from a conference on behalf of `actor_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the action is not permitted or signaling fails
+    pub async fn remove_participant(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+        participant_id: &str,
+    ) -> Result<(), ServiceError> {
+        self.moderate(
+            conference_id,
+            actor_id,
+            participant_id,
+            ParticipantAction::Remove,
+        )
+        .await
+    }
+
+    /// Change a conference participant's role on behalf of `actor_id`
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the action is not permitted or signaling fails
+    pub async fn set_participant_role(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+        participant_id: &str,
+        role: ParticipantRole,
+    ) -> Result<(), ServiceError> {
+        self.moderate(
+            conference_id,
+            actor_id,
+            participant_id,
+            ParticipantAction::SetRole { role },
+        )
+        .await
+    }
+
+    /// Apply a moderation action locally and announce it to the other participants
+    async fn moderate(
+        &self,
+        conference_id: ConferenceId,
+        actor_id: &str,
+        participant_id: &str,
+        action: ParticipantAction,
+    ) -> Result<(), ServiceError> {
+        // Collect recipients before applying so a removed participant is told too
+        let (event, recipients) = {
+            let mut conference = self.conference_mut(conference_id)?;
+            let recipients = conference.other_participants(actor_id);
+            let event = conference.apply(actor_id, participant_id, action)?;
+            (event, recipients)
+        };
+        self.conference_changed(event);
+
+        let message = control_message(conference_id, participant_id, action);
+        for recipient in recipients {
+            self.send_to(&recipient, message.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// Look up a conference for modification
+    fn conference_mut(
+        &self,
+        conference_id: ConferenceId,
+    ) -> Result<RefMut<'_, ConferenceId, Conference>, ServiceError> {
+        self.conferences
+            .get_mut(&conference_id)
+            .ok_or_else(|| ConferenceError::NotFound(conference_id.to_string()).into())
+    }
+
+    /// Send a signaling message to a conference participant
+    async fn send_to(
+        &self,
+        participant_id: &str,
+        message: SignalingMessage,
+    ) -> Result<(), ServiceError> {
+        let peer: T::PeerId = transport_peer(participant_id)?;
+        self.signaling
+            .send_message(&peer, message)
+            .await
+            .map_err(|e| ServiceError::SignalingError(e.into()))
+    }
+
+    /// Capture the most recent decoded frame of a call's video track
+    ///
+    /// # Errors
+    ///
+    /// Returns error if no frame has been decoded for the track or encoding fails
+    pub fn capture_snapshot(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        format: SnapshotFormat,
+    ) -> Result<Vec<u8>, ServiceError> {
+        self.snapshots
+            .capture(call_id, track_id, format)
+            .map_err(ServiceError::from)
+    }
+
+    /// Get the store that decoded frames are recorded into for snapshots
+    #[must_use]
+    pub fn snapshot_store(&self) -> &Arc<SnapshotStore> {
+        &self.snapshots
+    }
+
+    /// Record a call's media to a WebM or Matroska file at `path`
+    ///
+    /// Remote video passed to [`Self::receive_video_frame`] is recorded as
+    /// it arrives; feed the other encoded media to [`Self::record_audio`]
+    /// and [`Self::record_video`]. Recording runs until
+    /// [`Self::stop_recording`] or the call ends.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call does not exist or is already being
+    /// recorded, or the recording cannot be created
+    pub async fn start_recording(
+        &self,
+        call_id: CallId,
+        path: impl Into<PathBuf>,
+        options: RecordingOptions,
+    ) -> Result<(), ServiceError> {
+        let constraints = self
+            .call_manager
+            .constraints(call_id)
+            .ok_or_else(|| CallError::CallNotFound(call_id.to_string()))?;
+        let video_codec = (constraints.video || constraints.screen_share).then(|| {
+            self.negotiated_codecs(call_id)
+                .and_then(|codecs| codecs.video)
+                .unwrap_or(VideoCodec::H264)
+        });
+        let remote_video = options.remote && video_codec.is_some();
+        {
+            let mut recorders = self.recorders.lock();
+            if recorders.contains_key(&call_id) {
+                return Err(ServiceError::AlreadyRecording(call_id));
+            }
+            let recorder = MediaRecorder::new(path, options, constraints.audio, video_codec)?;
+            recorders.insert(call_id, recorder);
+        }
+        if remote_video {
+            // Remote video is only recorded from a keyframe on
+            if let Err(e) = self.request_keyframe(call_id, None).await {
+                tracing::debug!("Keyframe request for recording {} failed: {}", call_id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop recording a call, returning the recording's files still on
+    /// disk, oldest first
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the call is not being recorded or the last frames
+    /// cannot be written
+    pub fn stop_recording(&self, call_id: CallId) -> Result<Vec<PathBuf>, ServiceError> {
+        let recorder = self
+            .recorders
+            .lock()
+            .remove(&call_id)
+            .ok_or(ServiceError::NotRecording(call_id))?;
+        recorder.finish().map_err(ServiceError::from)
+    }
+
+    /// Whether a call is being recorded
+    #[must_use]
+    pub fn is_recording(&self, call_id: CallId) -> bool {
+        self.recorders.lock().contains_key(&call_id)
+    }
+
+    /// Record an Opus packet sent or received on a call, if it is being
+    /// recorded
+    pub fn record_audio(&self, call_id: CallId, source: RecordingSource, data: &[u8]) {
+        self.record(call_id, |recorder| {
+            let timestamp_ms = recorder.elapsed_ms();
+            recorder.write_audio(source, timestamp_ms, data)
+        });
+    }
+
+    /// Record an encoded video frame sent or received on a call, if it is
+    /// being recorded
+    pub fn record_video(
+        &self,
+        call_id: CallId,
+        source: RecordingSource,
+        data: &[u8],
+        width: u32,
+        height: u32,
+    ) {
+        self.record(call_id, |recorder| {
+            let timestamp_ms = recorder.elapsed_ms();
+            recorder.write_video(source, timestamp_ms, data, width, height)
+        });
+    }
+
+    /// Write to a call's recording, stopping it if writing fails
+    fn record(
+        &self,
+        call_id: CallId,
+        write: impl FnOnce(&mut MediaRecorder) -> Result<(), RecordingError>,
+    ) {
+        let mut recorders = self.recorders.lock();
+        let Some(recorder) = recorders.get_mut(&call_id) else {
+            return;
+        };
+        if let Err(e) = write(recorder) {
+            let files = recorders
+                .remove(&call_id)
+                .map(|recorder| recorder.files().to_vec())
+                .unwrap_or_default();
+            drop(recorders);
+            tracing::warn!("Recording of call {} stopped: {}", call_id, e);
+            let _ = self.event_sender.send(WebRtcEvent::RecordingStopped {
+                call_id,
+                files,
+                error: Some(e.to_string()),
+            });
+        }
+    }
+
+    /// Get call state
+    #[must_use]
+    pub async fn get_call_state(&self, call_id: CallId) -> Option<CallState> {
+        self.call_manager.get_call_state(call_id).await
+    }
+
+    /// Subscribe to events
+    #[must_use]
+    pub fn subscribe_events(&self) -> broadcast::Receiver<WebRtcEvent<I>> {
+        self.event_sender.subscribe()
+    }
+
+    /// Get the typed event bus
+    ///
+    /// Unlike [`Self::subscribe_events`], subscribers on the bus never miss
+    /// critical events such as incoming calls when they fall behind.
+    #[must_use]
+    pub fn event_bus(&self) -> &Arc<EventBus<I>> {
+        &self.event_bus
+    }
+
+    /// Runtimes background work is spawned on
+    #[must_use]
+    pub fn runtime(&self) -> &RuntimeTopology {
+        &self.runtime
+    }
+
+    /// Buffer capturing call events
+    #[must_use]
+    pub fn call_logs(&self) -> &Arc<CallLogBuffer> {
+        &self.call_logs
+    }
+
+    /// Records of calls that ended, most recent first
+    ///
+    /// Empty unless [`WebRtcConfig::call_history`] names a store. Records
+    /// are written just after calls end, so the last one may take a moment
+    /// to appear.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the store cannot be read
+    pub fn call_history(&self, query: &HistoryQuery) -> Result<Vec<CallRecord>, ServiceError> {
+        match &self.call_history {
+            Some(history) => Ok(history.records(query)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Redacted logs of a call, for attaching to a bug report
+    ///
+    /// Works after the call has ended, for as long as its events remain in
+    /// the buffer.
+    #[must_use]
+    pub fn export_call_logs(&self, call_id: CallId) -> CallLogBundle {
+        self.call_logs.export(call_id)
+    }
+
+    /// Create a builder
+    #[must_use]
+    pub fn builder(signaling: Arc<SignalingHandler<T>>) -> WebRtcServiceBuilder<I, T> {
+        WebRtcServiceBuilder::new(signaling)
+    }
+}
+
+/// Keep a record of every call that ends, until the event bus is dropped
+/// A probe train sent by [`WebRtcService::probe_peer`]
+struct PendingProbe {
+    /// Peer probed, as its string form
+    peer: String,
+    probe: PathProbe,
+    /// Woken once every probe is echoed
+    done: Arc<tokio::sync::Notify>,
+}
+
+async fn record_calls<I: PeerIdentity>(
+    history: Arc<CallHistory>,
+    mut events: EventSubscription<CallEvent<I>>,
+) {
+    while let Some(event) = events.recv().await {
+        let Some(record) = history.observe(&event) else {
+            continue;
+        };
+        let store = Arc::clone(history.store());
+        let call_id = record.call_id;
+        match tokio::task::spawn_blocking(move || store.record(&record)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!("Failed to record call {}: {}", call_id, e),
+            Err(e) => tracing::warn!("Failed to record call {}: {}", call_id, e),
+        }
+    }
+}
+
+/// Parse a conference ID carried in a signaling session ID
+fn parse_call_id(session_id: &str) -> Result<CallId, ServiceError> {
+    session_id
+        .parse()
+        .map(CallId)
+        .map_err(|_| CallError::CallNotFound(session_id.to_string()).into())
+}
+
+fn parse_conference_id(session_id: &str) -> Result<ConferenceId, ServiceError> {
+    session_id
+        .parse()
+        .map_err(|_| ConferenceError::NotFound(session_id.to_string()).into())
+}
+
+fn parse_broadcast_id(session_id: &str) -> Result<BroadcastId, ServiceError> {
+    session_id
+        .parse()
+        .map_err(|_| SignalingError::SessionNotFound(session_id.to_string()))
+        .map_err(|e| ServiceError::SignalingError(Box::new(e)))
+}
+
+/// Parse a peer ID as an identity
+fn identity_peer<I: PeerIdentity>(peer: &str) -> Result<I, ServiceError> {
+    I::from_string_repr(peer).map_err(|e| ServiceError::InvalidPeer {
+        peer: peer.to_string(),
+        source: Some(e.into()),
+    })
+}
+
+/// Parse a peer ID for the signaling transport
+fn transport_peer<P: FromStr>(peer: &str) -> Result<P, ServiceError> {
+    peer.parse().map_err(|_| ServiceError::InvalidPeer {
+        peer: peer.to_string(),
+        source: None,
+    })
+}
+
+/// Code of a signaling failure, from the signaling or transport error
+/// behind it where there is one
+fn signaling_code(error: &(dyn std::error::Error + Send + Sync + 'static)) -> ErrorCode {
+    if let Some(e) = error.downcast_ref::<SignalingError>() {
+        e.code()
+    } else if let Some(e) = error.downcast_ref::<DynTransportError>() {
+        e.code()
+    } else {
+        ErrorCode::SignalingFailed
+    }
+}
+
+/// WebRTC service builder
+pub struct WebRtcServiceBuilder<I: PeerIdentity, T: SignalingTransport> {
+    signaling: Arc<SignalingHandler<T>>,
+    config: WebRtcConfig,
+    identity: Option<I>,
+}
+
+impl<I: PeerIdentity, T: SignalingTransport> WebRtcServiceBuilder<I, T> {
+    /// Create new builder
+    #[must_use]
+    pub fn new(signaling: Arc<SignalingHandler<T>>) -> Self {
+        Self {
+            signaling,
+            config: WebRtcConfig::default(),
+            identity: None,
+        }
+    }
+
+    /// Set configuration
+    #[must_use]
+    pub fn with_config(mut self, config: WebRtcConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Set our own identity
+    #[must_use]
+    pub fn with_identity(mut self, identity: I) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Build the service
+    ///
+    /// # Errors
+    ///
+    /// Returns error if service creation fails
+    pub async fn build(self) -> Result<WebRtcService<I, T>, ServiceError> {
+        let service = WebRtcService::new(self.signaling, self.config).await?;
+        if let Some(identity) = self.identity {
+            service.set_identity(identity);
+        }
+        Ok(service)
     }
 }
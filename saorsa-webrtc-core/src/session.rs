@@ -0,0 +1,307 @@
+//! Signaling sessions and the calls they carry
+//!
+//! Signaling messages name their call by a free-form `session_id`. A
+//! [`SessionRegistry`] ties each session to its [`CallId`] and remote
+//! peer from the moment the call is set up, so messages for it are only
+//! taken from that peer. Ended sessions are remembered for a while and
+//! messages replaying them refused, and sessions nobody signals on for
+//! too long are expired as abandoned.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::types::CallId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// How long sessions are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Quiet time after which a session that is not live is abandoned
+    pub idle_timeout: Duration,
+    /// How long ended sessions are remembered to refuse replays
+    pub replay_window: Duration,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(300),
+            replay_window: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Session lookup errors
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SessionError {
+    /// No session with the given ID
+    #[error("Unknown session {0}")]
+    Unknown(String),
+
+    /// Session ended, so the message replays it
+    #[error("Session {0} has ended")]
+    Stale(String),
+
+    /// Message came from a peer other than the session's
+    #[error("Session {session_id} does not belong to {peer}")]
+    WrongPeer {
+        /// Session named by the message
+        session_id: String,
+        /// Peer the message came from
+        peer: String,
+    },
+
+    /// Session ID is already taken by another call
+    #[error("Session {0} is already in use")]
+    InUse(String),
+}
+
+impl CodedError for SessionError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Unknown(_) => ErrorCode::CallNotFound,
+            Self::Stale(_) => ErrorCode::InvalidCallState,
+            Self::WrongPeer { .. } | Self::InUse(_) => ErrorCode::InvalidInput,
+        }
+    }
+}
+
+/// A session in progress
+#[derive(Debug, Clone)]
+struct Session {
+    call_id: CallId,
+    peer: String,
+    last_seen: Instant,
+}
+
+/// Maps signaling sessions to calls and their remote peers
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    config: SessionConfig,
+    sessions: HashMap<String, Session>,
+    ended: HashMap<String, Instant>,
+}
+
+impl SessionRegistry {
+    /// Create with no sessions
+    #[must_use]
+    pub fn new(config: SessionConfig) -> Self {
+        Self {
+            config,
+            sessions: HashMap::new(),
+            ended: HashMap::new(),
+        }
+    }
+
+    /// Open `session_id` for a call with `peer`, seen at `now`
+    ///
+    /// Opening it again for the same call and peer counts as activity.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the session ended, or is open for another call or
+    /// peer
+    pub fn open(
+        &mut self,
+        session_id: &str,
+        call_id: CallId,
+        peer: &str,
+        now: Instant,
+    ) -> Result<(), SessionError> {
+        if self.ended.contains_key(session_id) {
+            return Err(SessionError::Stale(session_id.to_string()));
+        }
+        match self.sessions.get_mut(session_id) {
+            Some(session) if session.call_id != call_id || session.peer != peer => {
+                Err(SessionError::InUse(session_id.to_string()))
+            }
+            Some(session) => {
+                session.last_seen = now;
+                Ok(())
+            }
+            None => {
+                self.sessions.insert(
+                    session_id.to_string(),
+                    Session {
+                        call_id,
+                        peer: peer.to_string(),
+                        last_seen: now,
+                    },
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// The call a message for `session_id` from `peer`, received at `now`,
+    /// belongs to
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the session is unknown or ended, or belongs to
+    /// another peer
+    pub fn resolve(
+        &mut self,
+        session_id: &str,
+        peer: &str,
+        now: Instant,
+    ) -> Result<CallId, SessionError> {
+        if self.ended.contains_key(session_id) {
+            return Err(SessionError::Stale(session_id.to_string()));
+        }
+        let session = self
+            .sessions
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::Unknown(session_id.to_string()))?;
+        if session.peer != peer {
+            return Err(SessionError::WrongPeer {
+                session_id: session_id.to_string(),
+                peer: peer.to_string(),
+            });
+        }
+        session.last_seen = now;
+        Ok(session.call_id)
+    }
+
+    /// Whether `session_id` ended within the replay window
+    #[must_use]
+    pub fn is_stale(&self, session_id: &str) -> bool {
+        self.ended.contains_key(session_id)
+    }
+
+    /// End `session_id` at `now`, returning its call
+    pub fn close(&mut self, session_id: &str, now: Instant) -> Option<CallId> {
+        let session = self.sessions.remove(session_id)?;
+        self.ended.insert(session_id.to_string(), now);
+        Some(session.call_id)
+    }
+
+    /// End sessions quiet for longer than the idle timeout at `now`,
+    /// returning their calls
+    ///
+    /// Sessions of calls `live` holds to are kept, as established calls
+    /// need no signaling. Ended sessions past the replay window are
+    /// forgotten.
+    pub fn expire(&mut self, now: Instant, live: impl Fn(CallId) -> bool) -> Vec<CallId> {
+        let idle_timeout = self.config.idle_timeout;
+        let abandoned: Vec<String> = self
+            .sessions
+            .iter_mut()
+            .filter(|(_, session)| now.saturating_duration_since(session.last_seen) > idle_timeout)
+            .filter_map(|(session_id, session)| {
+                if live(session.call_id) {
+                    session.last_seen = now;
+                    None
+                } else {
+                    Some(session_id.clone())
+                }
+            })
+            .collect();
+        let expired = abandoned
+            .iter()
+            .filter_map(|session_id| self.close(session_id, now))
+            .collect();
+
+        let replay_window = self.config.replay_window;
+        self.ended
+            .retain(|_, ended| now.saturating_duration_since(*ended) <= replay_window);
+        expired
+    }
+
+    /// Number of sessions in progress
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Whether no session is in progress
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_only_take_their_peer() {
+        let mut registry = SessionRegistry::new(SessionConfig::default());
+        let now = Instant::now();
+        let call_id = CallId::new();
+        let session_id = call_id.to_string();
+        registry.open(&session_id, call_id, "alice", now).unwrap();
+        registry.open(&session_id, call_id, "alice", now).unwrap();
+        assert_eq!(
+            registry.open(&session_id, CallId::new(), "alice", now),
+            Err(SessionError::InUse(session_id.clone()))
+        );
+
+        assert_eq!(registry.resolve(&session_id, "alice", now), Ok(call_id));
+        assert!(matches!(
+            registry.resolve(&session_id, "mallory", now),
+            Err(SessionError::WrongPeer { .. })
+        ));
+        assert_eq!(
+            registry.resolve("other", "alice", now),
+            Err(SessionError::Unknown("other".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_ended_sessions_refuse_replays() {
+        let config = SessionConfig::default();
+        let mut registry = SessionRegistry::new(config);
+        let now = Instant::now();
+        let call_id = CallId::new();
+        let session_id = call_id.to_string();
+        registry.open(&session_id, call_id, "alice", now).unwrap();
+
+        assert_eq!(registry.close(&session_id, now), Some(call_id));
+        assert_eq!(registry.close(&session_id, now), None);
+        assert!(registry.is_stale(&session_id));
+        let stale = Err(SessionError::Stale(session_id.clone()));
+        assert_eq!(registry.resolve(&session_id, "alice", now), stale);
+        assert_eq!(
+            registry.open(&session_id, call_id, "alice", now),
+            Err(SessionError::Stale(session_id.clone()))
+        );
+
+        // Forgotten once the replay window passes
+        let later = now + config.replay_window + Duration::from_secs(1);
+        assert!(registry.expire(later, |_| false).is_empty());
+        assert!(!registry.is_stale(&session_id));
+    }
+
+    #[test]
+    fn test_quiet_sessions_expire_unless_live() {
+        let config = SessionConfig::default();
+        let mut registry = SessionRegistry::new(config);
+        let now = Instant::now();
+        let (ringing, connected) = (CallId::new(), CallId::new());
+        registry
+            .open(&ringing.to_string(), ringing, "alice", now)
+            .unwrap();
+        registry
+            .open(&connected.to_string(), connected, "bob", now)
+            .unwrap();
+
+        assert!(registry
+            .expire(now + config.idle_timeout, |_| false)
+            .is_empty());
+        let later = now + config.idle_timeout + Duration::from_secs(1);
+        assert_eq!(
+            registry.expire(later, |call_id| call_id == connected),
+            vec![ringing]
+        );
+        assert_eq!(registry.len(), 1);
+        assert!(registry.is_stale(&ringing.to_string()));
+
+        // Live sessions count as seen when checked
+        let later = later + config.idle_timeout;
+        assert!(registry.expire(later, |_| false).is_empty());
+        assert!(!registry.is_empty());
+    }
+}
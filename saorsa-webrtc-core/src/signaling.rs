@@ -2,7 +2,16 @@
 //!
 //! Handles SDP exchange and ICE candidate gathering for WebRTC connections.
 
+use crate::capabilities::{CodecCapabilities, PeerCapabilities};
+use crate::conference::{BreakoutGroup, ParticipantAction};
+use crate::error::{CodedError, ErrorCode};
+use crate::handoff::HandoffState;
+use crate::invitation::CallToken;
+use crate::remote_control::RemoteControlAction;
+use crate::screen_capture::ScreenShare;
+use crate::types::ByeReason;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::net::SocketAddr;
@@ -25,6 +34,16 @@ pub enum SignalingError {
     TransportError(String),
 }
 
+impl CodedError for SignalingError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidSdp(_) => ErrorCode::NegotiationFailed,
+            Self::SessionNotFound(_) => ErrorCode::CallNotFound,
+            Self::TransportError(_) => ErrorCode::SignalingFailed,
+        }
+    }
+}
+
 /// Signaling transport trait
 ///
 /// Implement this for your specific transport (DHT, gossip, etc.)
@@ -51,6 +70,166 @@ pub trait SignalingTransport: Send + Sync {
         &self,
         peer: &Self::PeerId,
     ) -> Result<Option<SocketAddr>, Self::Error>;
+
+    /// Connect to a peer at its discovered endpoint ahead of messaging it
+    ///
+    /// Transports without connections of their own keep the default,
+    /// which does nothing.
+    async fn connect_peer(&self, peer: &Self::PeerId, addr: SocketAddr) -> Result<(), Self::Error> {
+        let _ = (peer, addr);
+        Ok(())
+    }
+}
+
+/// Error from a [`DynSignalingTransport`]
+#[derive(Error, Debug)]
+pub enum DynTransportError {
+    /// Peer ID the underlying transport could not parse
+    #[error("Invalid peer ID: {0}")]
+    InvalidPeer(String),
+
+    /// Error from the underlying transport
+    #[error(transparent)]
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl CodedError for DynTransportError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidPeer(_) => ErrorCode::PeerNotFound,
+            Self::Transport(_) => ErrorCode::SignalingFailed,
+        }
+    }
+}
+
+/// Object-safe signaling transport with string peer IDs
+///
+/// Lets applications pick a transport at runtime and hold it as
+/// `Arc<dyn DynSignalingTransport>`. Every [`SignalingTransport`] is one,
+/// peer IDs being parsed and formatted through `FromStr` and `Display`;
+/// [`BoxedSignalingTransport`] goes the other way.
+#[async_trait]
+pub trait DynSignalingTransport: Send + Sync {
+    /// Send a signaling message
+    async fn send_message(
+        &self,
+        peer: &str,
+        message: SignalingMessage,
+    ) -> Result<(), DynTransportError>;
+
+    /// Receive a signaling message
+    async fn receive_message(&self) -> Result<(String, SignalingMessage), DynTransportError>;
+
+    /// Discover peer endpoint
+    async fn discover_peer_endpoint(
+        &self,
+        peer: &str,
+    ) -> Result<Option<SocketAddr>, DynTransportError>;
+
+    /// Connect to a peer ahead of messaging it
+    async fn connect_peer(&self, peer: &str, addr: SocketAddr) -> Result<(), DynTransportError>;
+}
+
+#[async_trait]
+impl<T: SignalingTransport> DynSignalingTransport for T {
+    async fn send_message(
+        &self,
+        peer: &str,
+        message: SignalingMessage,
+    ) -> Result<(), DynTransportError> {
+        let peer = parse_peer::<T>(peer)?;
+        SignalingTransport::send_message(self, &peer, message)
+            .await
+            .map_err(|e| DynTransportError::Transport(Box::new(e)))
+    }
+
+    async fn receive_message(&self) -> Result<(String, SignalingMessage), DynTransportError> {
+        let (peer, message) = SignalingTransport::receive_message(self)
+            .await
+            .map_err(|e| DynTransportError::Transport(Box::new(e)))?;
+        Ok((peer.to_string(), message))
+    }
+
+    async fn discover_peer_endpoint(
+        &self,
+        peer: &str,
+    ) -> Result<Option<SocketAddr>, DynTransportError> {
+        let peer = parse_peer::<T>(peer)?;
+        SignalingTransport::discover_peer_endpoint(self, &peer)
+            .await
+            .map_err(|e| DynTransportError::Transport(Box::new(e)))
+    }
+
+    async fn connect_peer(&self, peer: &str, addr: SocketAddr) -> Result<(), DynTransportError> {
+        let peer = parse_peer::<T>(peer)?;
+        SignalingTransport::connect_peer(self, &peer, addr)
+            .await
+            .map_err(|e| DynTransportError::Transport(Box::new(e)))
+    }
+}
+
+fn parse_peer<T: SignalingTransport>(peer: &str) -> Result<T::PeerId, DynTransportError> {
+    peer.parse()
+        .map_err(|_| DynTransportError::InvalidPeer(peer.to_string()))
+}
+
+/// [`SignalingTransport`] over a runtime-chosen [`DynSignalingTransport`]
+///
+/// Fixes the transport type parameter, so code generic over `T` elsewhere
+/// can be written once against this type.
+#[derive(Clone)]
+pub struct BoxedSignalingTransport {
+    inner: std::sync::Arc<dyn DynSignalingTransport>,
+}
+
+impl BoxedSignalingTransport {
+    /// Wrap a runtime-chosen transport
+    #[must_use]
+    pub fn new(inner: std::sync::Arc<dyn DynSignalingTransport>) -> Self {
+        Self { inner }
+    }
+
+    /// Erase the type of a generic transport
+    #[must_use]
+    pub fn from_transport<T: SignalingTransport + 'static>(transport: T) -> Self {
+        Self::new(std::sync::Arc::new(transport))
+    }
+}
+
+impl fmt::Debug for BoxedSignalingTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoxedSignalingTransport")
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl SignalingTransport for BoxedSignalingTransport {
+    type PeerId = String;
+    type Error = DynTransportError;
+
+    async fn send_message(
+        &self,
+        peer: &String,
+        message: SignalingMessage,
+    ) -> Result<(), DynTransportError> {
+        self.inner.send_message(peer, message).await
+    }
+
+    async fn receive_message(&self) -> Result<(String, SignalingMessage), DynTransportError> {
+        self.inner.receive_message().await
+    }
+
+    async fn discover_peer_endpoint(
+        &self,
+        peer: &String,
+    ) -> Result<Option<SocketAddr>, DynTransportError> {
+        self.inner.discover_peer_endpoint(peer).await
+    }
+
+    async fn connect_peer(&self, peer: &String, addr: SocketAddr) -> Result<(), DynTransportError> {
+        self.inner.connect_peer(peer, addr).await
+    }
 }
 
 /// Signaling message types
@@ -99,8 +278,253 @@ pub enum SignalingMessage {
     Bye {
         /// Session ID
         session_id: String,
-        /// Optional reason
-        reason: Option<String>,
+        /// Why the session was closed
+        reason: Option<ByeReason>,
+    },
+
+    /// Voice message left after a rejected or missed call
+    ///
+    /// Transports with an offline mailbox should hold this until the peer
+    /// is reachable.
+    Voicemail {
+        /// Session ID of the call the message was left for
+        session_id: String,
+        /// Unique message ID
+        message_id: String,
+        /// Sender identity
+        sender: String,
+        /// When recording finished
+        recorded_at: DateTime<Utc>,
+        /// Message duration in milliseconds
+        duration_ms: u64,
+        /// Base64-encoded recording container
+        audio: String,
+    },
+
+    /// Conference moderation action
+    ParticipantControl {
+        /// Conference ID
+        session_id: String,
+        /// Participant the action applies to
+        participant_id: String,
+        /// Action to apply
+        action: ParticipantAction,
+    },
+
+    /// Request to join a conference, carrying the joiner's offer
+    JoinConference {
+        /// Conference ID
+        session_id: String,
+        /// SDP offer, parked while waiting for approval
+        sdp: String,
+    },
+
+    /// Host's answer to a join request
+    JoinDecision {
+        /// Conference ID
+        session_id: String,
+        /// Whether the participant was admitted
+        admitted: bool,
+    },
+
+    /// Breakout group membership change; no groups means merged back
+    BreakoutUpdate {
+        /// Conference ID
+        session_id: String,
+        /// Current breakout groups
+        groups: Vec<BreakoutGroup>,
+    },
+
+    /// Request to join a persistent room by its ID
+    JoinRoom {
+        /// Room ID
+        session_id: String,
+        /// SDP offer, answered once the participant is in
+        sdp: String,
+    },
+
+    /// Answer to a room join request
+    RoomJoined {
+        /// Room ID
+        session_id: String,
+        /// Live conference the participant joined
+        conference_id: Option<String>,
+        /// Whether the participant was let in
+        admitted: bool,
+    },
+
+    /// Request for a call parked on another device of the same identity
+    RetrieveCall {
+        /// Park token secret
+        session_id: String,
+    },
+
+    /// Hand a parked call over to the device that retrieved it
+    CallHandoff {
+        /// Park token secret
+        session_id: String,
+        /// Remote party to call to continue; `None` if refused
+        remote_peer: Option<String>,
+    },
+
+    /// Offer an active call to the identity's other devices
+    HandoffOffer {
+        /// Handoff ID
+        session_id: String,
+        /// Serialized call state
+        state: HandoffState,
+    },
+
+    /// Offer for a new call leg that replaces an existing one
+    ReplaceCall {
+        /// New call ID
+        session_id: String,
+        /// Call ID of the leg being replaced
+        replaces: String,
+        /// SDP offer for the new leg
+        sdp: String,
+    },
+
+    /// The new device's leg is up; the old device should hang up
+    HandoffComplete {
+        /// Handoff ID
+        session_id: String,
+    },
+
+    /// Ask the remote encoder for a keyframe, like RTCP PLI/FIR
+    ///
+    /// Sent by a receiver whose decoder lost its reference frames.
+    KeyframeRequest {
+        /// Call ID
+        session_id: String,
+        /// Video track to refresh; `None` means every video track
+        track_id: Option<String>,
+    },
+
+    /// Clock probe, answered with a [`SignalingMessage::ClockReply`]
+    ClockProbe {
+        /// Call ID
+        session_id: String,
+        /// When the probe was sent, sender's clock
+        sent_at: DateTime<Utc>,
+    },
+
+    /// Answer to a clock probe
+    ClockReply {
+        /// Call ID
+        session_id: String,
+        /// `sent_at` of the probe being answered
+        probe_sent_at: DateTime<Utc>,
+        /// When the probe arrived, replier's clock
+        received_at: DateTime<Utc>,
+        /// When the reply was sent, replier's clock
+        replied_at: DateTime<Utc>,
+    },
+
+    /// Codecs the sender supports, exchanged alongside the SDP
+    Capabilities {
+        /// Call ID
+        session_id: String,
+        /// Sender's codecs, most preferred first
+        capabilities: CodecCapabilities,
+        /// Whether this answers the other side's capabilities
+        reply: bool,
+    },
+
+    /// Presence: what the sender can do in a call, sent to a contact before
+    /// calling them
+    Hello {
+        /// Exchange ID, chosen by the side saying hello first
+        session_id: String,
+        /// Sender's capabilities
+        capabilities: PeerCapabilities,
+        /// Whether this answers the other side's hello
+        reply: bool,
+    },
+
+    /// Invitation presented to its issuer before calling them
+    RedeemInvitation {
+        /// Invitation ID
+        session_id: String,
+        /// Token the issuer handed out
+        token: CallToken,
+    },
+
+    /// Issuer's answer to a redeemed invitation
+    InvitationRedeemed {
+        /// Invitation ID
+        session_id: String,
+        /// Whether calls from the redeemer will now ring
+        accepted: bool,
+    },
+
+    /// Sender muted or unmuted its audio, or turned its video off or on
+    MediaState {
+        /// Call ID
+        session_id: String,
+        /// Whether the sender's audio is muted
+        audio_muted: bool,
+        /// Whether the sender's video is on
+        video_enabled: bool,
+    },
+
+    /// Remote control of a screen share was requested, granted or ended
+    RemoteControl {
+        /// Call ID
+        session_id: String,
+        /// Screen share track, as named by its sharer
+        track_id: String,
+        /// What happened
+        action: RemoteControlAction,
+    },
+
+    /// Request to receive a broadcast
+    JoinBroadcast {
+        /// Broadcast ID
+        session_id: String,
+    },
+
+    /// Publisher's answer to a broadcast join request
+    BroadcastJoined {
+        /// Broadcast ID
+        session_id: String,
+        /// Whether the subscriber was added
+        accepted: bool,
+    },
+
+    /// Subscriber stopped receiving a broadcast
+    LeaveBroadcast {
+        /// Broadcast ID
+        session_id: String,
+    },
+
+    /// Loss a subscriber sees on a broadcast, for picking its layer
+    BroadcastFeedback {
+        /// Broadcast ID
+        session_id: String,
+        /// Packet loss, in percent
+        loss_percent: u8,
+    },
+
+    /// Sender started, paused, resumed or stopped a screen share
+    ScreenShares {
+        /// Call ID
+        session_id: String,
+        /// Every screen share the sender now sends
+        shares: Vec<ScreenShare>,
+    },
+
+    /// Padded probe measuring the path before a call, echoed back with
+    /// `reply` set
+    PathProbe {
+        /// Probe train ID
+        session_id: String,
+        /// Position of the probe in its train
+        sequence: u32,
+        /// Filler making the probe the size of a media packet
+        padding: String,
+        /// Whether this is the echo of a probe
+        reply: bool,
     },
 }
 
@@ -113,7 +537,34 @@ impl SignalingMessage {
             | Self::Answer { session_id, .. }
             | Self::IceCandidate { session_id, .. }
             | Self::IceComplete { session_id }
-            | Self::Bye { session_id, .. } => session_id,
+            | Self::Bye { session_id, .. }
+            | Self::Voicemail { session_id, .. }
+            | Self::ParticipantControl { session_id, .. }
+            | Self::JoinConference { session_id, .. }
+            | Self::JoinDecision { session_id, .. }
+            | Self::BreakoutUpdate { session_id, .. }
+            | Self::JoinRoom { session_id, .. }
+            | Self::RoomJoined { session_id, .. }
+            | Self::RetrieveCall { session_id }
+            | Self::CallHandoff { session_id, .. }
+            | Self::HandoffOffer { session_id, .. }
+            | Self::ReplaceCall { session_id, .. }
+            | Self::HandoffComplete { session_id }
+            | Self::KeyframeRequest { session_id, .. }
+            | Self::ClockProbe { session_id, .. }
+            | Self::ClockReply { session_id, .. }
+            | Self::Capabilities { session_id, .. }
+            | Self::Hello { session_id, .. }
+            | Self::RedeemInvitation { session_id, .. }
+            | Self::InvitationRedeemed { session_id, .. }
+            | Self::MediaState { session_id, .. }
+            | Self::RemoteControl { session_id, .. }
+            | Self::JoinBroadcast { session_id }
+            | Self::BroadcastJoined { session_id, .. }
+            | Self::LeaveBroadcast { session_id }
+            | Self::BroadcastFeedback { session_id, .. }
+            | Self::ScreenShares { session_id, .. }
+            | Self::PathProbe { session_id, .. } => session_id,
         }
     }
 }
@@ -163,6 +614,27 @@ impl<T: SignalingTransport> SignalingHandler<T> {
     ) -> Result<Option<std::net::SocketAddr>, T::Error> {
         self.transport.discover_peer_endpoint(peer).await
     }
+
+    /// Connect to a peer at `addr` ahead of messaging it
+    ///
+    /// # Errors
+    ///
+    /// Returns error if connecting fails
+    pub async fn connect_peer(
+        &self,
+        peer: &T::PeerId,
+        addr: std::net::SocketAddr,
+    ) -> Result<(), T::Error> {
+        self.transport.connect_peer(peer, addr).await
+    }
+}
+
+impl SignalingHandler<BoxedSignalingTransport> {
+    /// Create a signaling handler over a runtime-chosen transport
+    #[must_use]
+    pub fn from_dyn(transport: std::sync::Arc<dyn DynSignalingTransport>) -> Self {
+        Self::new(std::sync::Arc::new(BoxedSignalingTransport::new(transport)))
+    }
 }
 
 #[cfg(test)]
@@ -210,7 +682,10 @@ mod tests {
             peer: &String,
             message: SignalingMessage,
         ) -> Result<(), MockError> {
-            self.messages.lock().unwrap().push_back((peer.clone(), message));
+            self.messages
+                .lock()
+                .unwrap()
+                .push_back((peer.clone(), message));
             Ok(())
         }
 
@@ -241,7 +716,9 @@ mod tests {
             quic_endpoint: None,
         };
 
-        let result = handler.send_message(&"peer1".to_string(), message.clone()).await;
+        let result = handler
+            .send_message(&"peer1".to_string(), message.clone())
+            .await;
         assert!(result.is_ok());
 
         // Check that message was queued
@@ -278,4 +755,33 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), Some("127.0.0.1:8080".parse().unwrap()));
     }
+
+    #[tokio::test]
+    async fn test_dyn_transport_roundtrip() {
+        let mock = Arc::new(MockTransport::new());
+        let chosen: Arc<dyn DynSignalingTransport> = mock.clone();
+        let handler = SignalingHandler::from_dyn(chosen);
+
+        let message = SignalingMessage::Bye {
+            session_id: "test-session".to_string(),
+            reason: None,
+        };
+        handler
+            .send_message(&"peer1".to_string(), message.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.messages.lock().unwrap().front(),
+            Some(&("peer1".to_string(), message.clone()))
+        );
+
+        let (peer, received) = handler.receive_message().await.unwrap();
+        assert_eq!(peer, "peer1");
+        assert_eq!(received, message);
+
+        // Transport errors keep their source
+        let error = handler.receive_message().await.unwrap_err();
+        assert!(matches!(error, DynTransportError::Transport(_)));
+        assert_eq!(error.to_string(), "Mock error");
+    }
 }
@@ -0,0 +1,189 @@
+//! In-call snapshot capture
+//!
+//! Keeps the most recent decoded frame for each (call, track) pair so the
+//! application can save a still image from a call, or assert on rendered
+//! video in automated tests.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::types::CallId;
+use dashmap::DashMap;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use saorsa_webrtc_codecs::VideoFrame;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// JPEG quality used for snapshots
+const JPEG_QUALITY: u8 = 90;
+
+/// Snapshot errors
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    /// No frame has been decoded for the track yet
+    #[error("No frame available for track {0}")]
+    NoFrame(String),
+
+    /// Frame data does not match its dimensions
+    #[error("Invalid frame: {0}")]
+    InvalidFrame(String),
+
+    /// Image encoding failed
+    #[error("Encoding error: {0}")]
+    EncodingError(String),
+}
+
+impl CodedError for SnapshotError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NoFrame(_) | Self::EncodingError(_) => ErrorCode::SnapshotFailed,
+            Self::InvalidFrame(_) => ErrorCode::InvalidInput,
+        }
+    }
+}
+
+/// Image format for snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnapshotFormat {
+    /// Lossless PNG
+    Png,
+    /// JPEG
+    Jpeg,
+}
+
+/// Latest decoded frame per call and track
+#[derive(Default)]
+pub struct SnapshotStore {
+    frames: DashMap<(CallId, String), VideoFrame>,
+}
+
+impl SnapshotStore {
+    /// Create an empty store
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a decoded RGB24 frame, replacing the previous one for the track
+    pub fn record_frame(&self, call_id: CallId, track_id: &str, frame: VideoFrame) {
+        self.frames.insert((call_id, track_id.to_string()), frame);
+    }
+
+    /// Encode the most recent frame for a track
+    ///
+    /// # Errors
+    ///
+    /// Returns error if no frame is available or encoding fails
+    pub fn capture(
+        &self,
+        call_id: CallId,
+        track_id: &str,
+        format: SnapshotFormat,
+    ) -> Result<Vec<u8>, SnapshotError> {
+        // Clone so the shard lock is not held while encoding
+        let frame = self
+            .frames
+            .get(&(call_id, track_id.to_string()))
+            .map(|frame| frame.clone())
+            .ok_or_else(|| SnapshotError::NoFrame(track_id.to_string()))?;
+        encode_frame(&frame, format)
+    }
+
+    /// Drop all frames held for a call
+    pub fn remove_call(&self, call_id: CallId) {
+        self.frames.retain(|(id, _), _| *id != call_id);
+    }
+}
+
+/// Encode an RGB24 frame as PNG or JPEG
+///
+/// # Errors
+///
+/// Returns error if the frame size does not match its dimensions or
+/// encoding fails
+pub fn encode_frame(frame: &VideoFrame, format: SnapshotFormat) -> Result<Vec<u8>, SnapshotError> {
+    let expected = (frame.width as usize)
+        .checked_mul(frame.height as usize)
+        .and_then(|px| px.checked_mul(3))
+        .ok_or_else(|| SnapshotError::InvalidFrame("dimensions overflow".to_string()))?;
+    if frame.width == 0 || frame.height == 0 || frame.data.len() != expected {
+        return Err(SnapshotError::InvalidFrame(format!(
+            "{}x{} frame with {} bytes",
+            frame.width,
+            frame.height,
+            frame.data.len()
+        )));
+    }
+
+    let mut out = Vec::new();
+    let result = match format {
+        SnapshotFormat::Png => PngEncoder::new(&mut out).write_image(
+            &frame.data,
+            frame.width,
+            frame.height,
+            ExtendedColorType::Rgb8,
+        ),
+        SnapshotFormat::Jpeg => JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY).write_image(
+            &frame.data,
+            frame.width,
+            frame.height,
+            ExtendedColorType::Rgb8,
+        ),
+    };
+    result.map_err(|e| SnapshotError::EncodingError(e.to_string()))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32, height: u32) -> VideoFrame {
+        VideoFrame {
+            data: vec![128; (width * height * 3) as usize],
+            width,
+            height,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_png_and_jpeg() {
+        let store = SnapshotStore::new();
+        let call_id = CallId::new();
+        store.record_frame(call_id, "video-1", frame(16, 8));
+
+        let png = store
+            .capture(call_id, "video-1", SnapshotFormat::Png)
+            .unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let jpeg = store
+            .capture(call_id, "video-1", SnapshotFormat::Jpeg)
+            .unwrap();
+        assert!(jpeg.starts_with(&[0xFF, 0xD8]));
+    }
+
+    #[test]
+    fn test_snapshot_missing_frame() {
+        let store = SnapshotStore::new();
+        let call_id = CallId::new();
+        store.record_frame(call_id, "video-1", frame(4, 4));
+        store.remove_call(call_id);
+
+        assert!(matches!(
+            store.capture(call_id, "video-1", SnapshotFormat::Png),
+            Err(SnapshotError::NoFrame(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_rejects_mismatched_frame() {
+        let mut bad = frame(4, 4);
+        bad.data.truncate(10);
+        assert!(matches!(
+            encode_frame(&bad, SnapshotFormat::Png),
+            Err(SnapshotError::InvalidFrame(_))
+        ));
+    }
+}
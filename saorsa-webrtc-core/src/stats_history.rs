@@ -0,0 +1,222 @@
+//! Rolling per-call stats history
+//!
+//! Each call keeps one [`CallQualityMetrics`] sample per second for a
+//! configurable window; samples reported within the same second replace
+//! each other. Histories outlive their calls for a while so the desktop
+//! app can draw post-call quality graphs and tests can assert on a whole
+//! call, and export as CSV for both.
+
+use crate::types::{CallId, CallQualityMetrics};
+use chrono::SecondsFormat;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+
+/// Header row of exported CSV
+pub const STATS_CSV_HEADER: &str = "timestamp,rtt_ms,packet_loss_percent,jitter_ms,bandwidth_kbps";
+
+/// How much stats history is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsHistoryConfig {
+    /// Seconds of samples kept per call
+    pub window_secs: usize,
+    /// Ended calls whose history is kept, oldest dropped first
+    pub retained_calls: usize,
+}
+
+impl Default for StatsHistoryConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 600,
+            retained_calls: 8,
+        }
+    }
+}
+
+/// Ring buffer of one call's per-second samples
+#[derive(Debug, Clone)]
+pub struct StatsHistory {
+    window_secs: usize,
+    samples: VecDeque<CallQualityMetrics>,
+}
+
+impl StatsHistory {
+    /// Keep the last `window_secs` seconds of samples
+    #[must_use]
+    pub fn new(window_secs: usize) -> Self {
+        let window_secs = window_secs.max(1);
+        Self {
+            window_secs,
+            samples: VecDeque::with_capacity(window_secs.min(1024)),
+        }
+    }
+
+    /// Add a sample, replacing one already recorded for the same second
+    pub fn push(&mut self, metrics: CallQualityMetrics) {
+        let second = metrics.timestamp.timestamp();
+        if let Some(last) = self.samples.back_mut() {
+            if last.timestamp.timestamp() == second {
+                *last = metrics;
+                return;
+            }
+        }
+        if self.samples.len() == self.window_secs {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(metrics);
+    }
+
+    /// Samples, oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &CallQualityMetrics> {
+        self.samples.iter()
+    }
+
+    /// Number of samples held
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether no samples are held
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Samples as CSV with a [`STATS_CSV_HEADER`] row, oldest first
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::with_capacity(64 * (self.samples.len() + 1));
+        csv.push_str(STATS_CSV_HEADER);
+        csv.push('\n');
+        for sample in &self.samples {
+            // Writing to a String cannot fail
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{}",
+                sample
+                    .timestamp
+                    .to_rfc3339_opts(SecondsFormat::Millis, true),
+                sample.rtt_ms,
+                sample.packet_loss_percent,
+                sample.jitter_ms,
+                sample.bandwidth_kbps
+            );
+        }
+        csv
+    }
+}
+
+/// Stats histories of live and recently ended calls
+#[derive(Debug, Default)]
+pub struct StatsHistoryStore {
+    config: StatsHistoryConfig,
+    histories: HashMap<CallId, StatsHistory>,
+    /// Ended calls still held, oldest first
+    ended: VecDeque<CallId>,
+}
+
+impl StatsHistoryStore {
+    /// Create an empty store
+    #[must_use]
+    pub fn new(config: StatsHistoryConfig) -> Self {
+        Self {
+            config,
+            histories: HashMap::new(),
+            ended: VecDeque::new(),
+        }
+    }
+
+    /// Record a sample for a call
+    pub fn record(&mut self, call_id: CallId, metrics: CallQualityMetrics) {
+        let window_secs = self.config.window_secs;
+        self.histories
+            .entry(call_id)
+            .or_insert_with(|| StatsHistory::new(window_secs))
+            .push(metrics);
+    }
+
+    /// History of a call, live or recently ended
+    #[must_use]
+    pub fn get(&self, call_id: CallId) -> Option<&StatsHistory> {
+        self.histories.get(&call_id)
+    }
+
+    /// Keep a call's history until enough later calls have ended
+    pub fn end_call(&mut self, call_id: CallId) {
+        if !self.histories.contains_key(&call_id) || self.ended.contains(&call_id) {
+            return;
+        }
+        self.ended.push_back(call_id);
+        while self.ended.len() > self.config.retained_calls {
+            if let Some(oldest) = self.ended.pop_front() {
+                self.histories.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+
+    fn sample(at: DateTime<Utc>, rtt_ms: u32) -> CallQualityMetrics {
+        CallQualityMetrics {
+            rtt_ms,
+            packet_loss_percent: 0.5,
+            jitter_ms: 4,
+            bandwidth_kbps: 1200,
+            timestamp: at,
+            audio: None,
+        }
+    }
+
+    #[test]
+    fn test_history_keeps_one_sample_per_second_in_window() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut history = StatsHistory::new(3);
+        history.push(sample(start, 10));
+        history.push(sample(start + Duration::milliseconds(500), 20));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.samples().next().unwrap().rtt_ms, 20);
+
+        for second in 1..=3 {
+            history.push(sample(
+                start + Duration::seconds(second),
+                30 + second as u32,
+            ));
+        }
+        let rtts: Vec<u32> = history.samples().map(|s| s.rtt_ms).collect();
+        assert_eq!(rtts, [31, 32, 33]);
+    }
+
+    #[test]
+    fn test_history_exports_csv() {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut history = StatsHistory::new(10);
+        assert_eq!(history.to_csv(), format!("{STATS_CSV_HEADER}\n"));
+
+        history.push(sample(start, 42));
+        assert_eq!(
+            history.to_csv(),
+            format!("{STATS_CSV_HEADER}\n2023-11-14T22:13:20.000Z,42,0.5,4,1200\n")
+        );
+    }
+
+    #[test]
+    fn test_store_retains_recently_ended_calls() {
+        let mut store = StatsHistoryStore::new(StatsHistoryConfig {
+            window_secs: 10,
+            retained_calls: 1,
+        });
+        let (first, second) = (CallId::new(), CallId::new());
+        store.record(first, sample(Utc::now(), 10));
+        store.record(second, sample(Utc::now(), 20));
+
+        store.end_call(first);
+        assert!(store.get(first).is_some());
+        store.end_call(second);
+        assert!(store.get(first).is_none());
+        assert_eq!(store.get(second).unwrap().len(), 1);
+    }
+}
@@ -2,39 +2,145 @@
 //!
 //! This module provides transport adapters for different signaling mechanisms.
 
+use crate::deadline::{Deadline, Interrupted};
+use crate::error::{BoxError, CodedError, ErrorCode};
+use crate::events::TopicChannel;
+use crate::path_policy::{PathError, PathPolicy};
+use crate::reload::ConfigError;
 use crate::signaling::{SignalingMessage, SignalingTransport};
 use async_trait::async_trait;
+use dashmap::DashMap;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Transport configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TransportConfig {
-    /// Local endpoint address
+    /// Local endpoint address; fixed once started
     pub local_addr: Option<SocketAddr>,
+    /// How long [`AntQuicTransport::connect_to_peer`] waits for the handshake
+    pub connect_timeout: Duration,
+    /// Relay and bootstrap nodes that help reach peers behind NAT
+    pub relays: Vec<SocketAddr>,
+    /// Network paths connections may take
+    pub path_policy: PathPolicy,
 }
 
 impl Default for TransportConfig {
     fn default() -> Self {
-        Self { local_addr: None }
+        Self {
+            local_addr: None,
+            connect_timeout: Duration::from_secs(10),
+            relays: Vec::new(),
+            path_policy: PathPolicy::default(),
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Check the settings make sense
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::Invalid`] if the connect timeout is zero or a
+    /// relay is listed twice
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.connect_timeout.is_zero() {
+            return Err(ConfigError::Invalid("transport.connect_timeout must be positive".to_string()));
+        }
+        for (i, relay) in self.relays.iter().enumerate() {
+            if self.relays[..i].contains(relay) {
+                return Err(ConfigError::Invalid(format!("transport.relays lists {relay} twice")));
+            }
+        }
+        Ok(())
     }
 }
 
 /// Transport errors
 #[derive(Error, Debug)]
 pub enum TransportError {
-    /// Connection error
-    #[error("Connection error: {0}")]
-    ConnectionError(String),
+    /// Transport has not been started
+    #[error("Transport not started")]
+    NotStarted,
+
+    /// No peer is connected to send to
+    #[error("No peer connected")]
+    NoPeer,
+
+    /// Peer ID is empty or unknown
+    #[error("Peer not found: {0:?}")]
+    PeerNotFound(String),
+
+    /// Connection setup failed
+    #[error("Connection error: {context}")]
+    Connection {
+        /// Step that failed
+        context: &'static str,
+        /// Underlying failure
+        #[source]
+        source: BoxError,
+    },
+
+    /// Sending failed
+    #[error("Send failed")]
+    Send(#[source] BoxError),
+
+    /// Receiving failed
+    #[error("Receive failed")]
+    Receive(#[source] BoxError),
+
+    /// Message could not be encoded or decoded
+    #[error("Malformed message")]
+    Malformed(#[source] serde_json::Error),
+
+    /// Timed out or cancelled
+    #[error(transparent)]
+    Interrupted(#[from] Interrupted),
+
+    /// Configuration is invalid or cannot be applied while running
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    /// Path policy rules out the peer's address
+    #[error(transparent)]
+    PathRejected(#[from] PathError),
+}
 
-    /// Send error
-    #[error("Send error: {0}")]
-    SendError(String),
+impl CodedError for TransportError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotStarted => ErrorCode::TransportNotStarted,
+            Self::NoPeer | Self::PeerNotFound(_) => ErrorCode::PeerNotFound,
+            Self::Connection { .. } => ErrorCode::ConnectionFailed,
+            Self::Send(_) => ErrorCode::SendFailed,
+            Self::Receive(_) => ErrorCode::ReceiveFailed,
+            Self::Malformed(_) => ErrorCode::MalformedMessage,
+            Self::Interrupted(e) => e.code(),
+            Self::Config(e) => e.code(),
+            Self::PathRejected(e) => e.code(),
+        }
+    }
+}
 
-    /// Receive error
-    #[error("Receive error: {0}")]
-    ReceiveError(String),
+/// Transport connectivity events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TransportEvent {
+    /// A peer connection was established
+    PeerConnected {
+        /// Peer identifier
+        peer_id: String,
+    },
+    /// A peer was disconnected
+    PeerDisconnected {
+        /// Peer identifier
+        peer_id: String,
+    },
 }
 
 /// ant-quic transport adapter
@@ -45,8 +151,12 @@ pub enum TransportError {
 pub struct AntQuicTransport {
     config: TransportConfig,
     node: Option<Arc<ant_quic::quic_node::QuicP2PNode>>,
-    peer_map: Arc<tokio::sync::RwLock<std::collections::HashMap<String, ant_quic::nat_traversal_api::PeerId>>>,
-    default_peer: Arc<tokio::sync::RwLock<Option<ant_quic::nat_traversal_api::PeerId>>>,
+    peer_map: Arc<DashMap<String, ant_quic::nat_traversal_api::PeerId>>,
+    default_peer: Arc<RwLock<Option<ant_quic::nat_traversal_api::PeerId>>>,
+    /// Address each peer was dialled at or connected from
+    peer_addrs: Arc<DashMap<ant_quic::nat_traversal_api::PeerId, SocketAddr>>,
+    events: Option<Arc<TopicChannel<TransportEvent>>>,
+    runtime: Option<tokio::runtime::Handle>,
 }
 
 impl AntQuicTransport {
@@ -56,8 +166,31 @@ impl AntQuicTransport {
         Self {
             config,
             node: None,
-            peer_map: Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new())),
-            default_peer: Arc::new(tokio::sync::RwLock::new(None)),
+            peer_map: Arc::new(DashMap::new()),
+            default_peer: Arc::new(RwLock::new(None)),
+            peer_addrs: Arc::new(DashMap::new()),
+            events: None,
+            runtime: None,
+        }
+    }
+
+    /// Publish connectivity events on the given event bus topic
+    #[must_use]
+    pub fn with_event_channel(mut self, events: Arc<TopicChannel<TransportEvent>>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Run background tasks on the given runtime instead of the caller's
+    #[must_use]
+    pub fn with_runtime(mut self, runtime: tokio::runtime::Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    fn emit(&self, event: TransportEvent) {
+        if let Some(events) = &self.events {
+            events.publish(event);
         }
     }
 
@@ -67,6 +200,40 @@ impl AntQuicTransport {
         &self.config
     }
 
+    /// Apply a new configuration without restarting
+    ///
+    /// Relays new to the list, or newly allowed by the path policy, are
+    /// joined right away if the transport is running; ones that cannot be
+    /// reached in time are logged and skipped. Relays dropped from the list
+    /// stay connected until the node restarts. Peers whose address a new
+    /// path policy rules out are ignored from then on.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TransportError::Config`] if the configuration is invalid
+    /// or changes the bind address of a running transport; nothing is
+    /// applied then
+    pub async fn reload(&mut self, config: TransportConfig) -> Result<(), TransportError> {
+        config.validate()?;
+        if let Some(node) = &self.node {
+            if config.local_addr != self.config.local_addr {
+                return Err(ConfigError::RestartRequired("transport.local_addr").into());
+            }
+            let joined = self.config.path_policy.relays(&self.config.relays);
+            let relays = config.path_policy.relays(&config.relays);
+            for relay in relays.iter().filter(|r| !joined.contains(r)) {
+                let deadline = Deadline::after(config.connect_timeout);
+                match deadline.run(node.connect_to_bootstrap(*relay)).await {
+                    Ok(Ok(_)) => tracing::info!("Joined relay {}", relay),
+                    Ok(Err(e)) => tracing::warn!("Failed to join relay {}: {}", relay, e),
+                    Err(e) => tracing::warn!("Failed to join relay {}: {}", relay, e),
+                }
+            }
+        }
+        self.config = config;
+        Ok(())
+    }
+
     /// Start the transport and initialize QUIC node
     ///
     /// # Errors
@@ -78,10 +245,11 @@ impl AntQuicTransport {
         use ant_quic::auth::AuthConfig;
         use std::time::Duration;
 
-        // Use Bootstrap role for standalone operation (no external bootstraps needed)
+        // Use Bootstrap role for standalone operation; relays the path
+        // policy allows, if any, are joined as bootstrap nodes
         let node_config = QuicNodeConfig {
             role: EndpointRole::Bootstrap,
-            bootstrap_nodes: vec![],
+            bootstrap_nodes: self.config.path_policy.relays(&self.config.relays),
             enable_coordinator: true,
             max_connections: 100,
             connection_timeout: Duration::from_secs(30),
@@ -92,17 +260,19 @@ impl AntQuicTransport {
 
         let node = QuicP2PNode::new(node_config)
             .await
-            .map_err(|e| TransportError::ConnectionError(format!("Failed to create QUIC node: {}", e)))?;
+            .map_err(|e| TransportError::Connection { context: "create QUIC node", source: e.into() })?;
 
         let node_arc = Arc::new(node);
         
         // Spawn background task to accept incoming connections
         let node_clone = node_arc.clone();
-        tokio::spawn(async move {
+        let peer_addrs = Arc::clone(&self.peer_addrs);
+        let accept_loop = async move {
             loop {
                 match node_clone.accept().await {
                     Ok((addr, peer_id)) => {
                         tracing::debug!("Accepted connection from {:?} at {}", peer_id, addr);
+                        peer_addrs.insert(peer_id, addr);
                     }
                     Err(e) => {
                         tracing::debug!("Accept error (expected when no incoming connections): {}", e);
@@ -110,7 +280,11 @@ impl AntQuicTransport {
                     }
                 }
             }
-        });
+        };
+        match &self.runtime {
+            Some(runtime) => runtime.spawn(accept_loop),
+            None => tokio::spawn(accept_loop),
+        };
 
         self.node = Some(node_arc);
         Ok(())
@@ -128,14 +302,14 @@ impl AntQuicTransport {
     /// Returns error if transport is not started
     pub async fn local_addr(&self) -> Result<SocketAddr, TransportError> {
         let node = self.node.as_ref()
-            .ok_or_else(|| TransportError::ConnectionError("Transport not started".to_string()))?;
+            .ok_or(TransportError::NotStarted)?;
 
         let mut addr = node.get_nat_endpoint()
-            .map_err(|e| TransportError::ConnectionError(format!("Failed to get endpoint: {}", e)))?
+            .map_err(|e| TransportError::Connection { context: "get endpoint", source: e.into() })?
             .get_quinn_endpoint()
-            .ok_or_else(|| TransportError::ConnectionError("No Quinn endpoint available".to_string()))?
+            .ok_or_else(|| TransportError::Connection { context: "get endpoint", source: "no Quinn endpoint available".into() })?
             .local_addr()
-            .map_err(|e| TransportError::ConnectionError(format!("Failed to get local address: {}", e)))?;
+            .map_err(|e| TransportError::Connection { context: "get local address", source: e.into() })?;
 
         // If bound to 0.0.0.0, replace with localhost for connection purposes
         if addr.ip().is_unspecified() {
@@ -147,42 +321,93 @@ impl AntQuicTransport {
 
     /// Connect to a peer
     ///
+    /// Gives up after the configured connect timeout.
+    ///
     /// # Errors
     ///
-    /// Returns error if connection fails
+    /// Returns error if connection fails or times out
     pub async fn connect_to_peer(&mut self, addr: SocketAddr) -> Result<String, TransportError> {
+        let deadline = Deadline::after(self.config.connect_timeout);
+        self.connect_to_peer_with(addr, &deadline).await
+    }
+
+    /// Connect to a peer, bounded by `deadline`
+    ///
+    /// An interrupted handshake leaves no peer registered.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TransportError::PathRejected`] if the path policy rules
+    /// out `addr`, error if connection fails, or
+    /// [`TransportError::Interrupted`] if the deadline passes or is
+    /// cancelled first
+    pub async fn connect_to_peer_with(
+        &mut self,
+        addr: SocketAddr,
+        deadline: &Deadline,
+    ) -> Result<String, TransportError> {
+        self.dial(addr, deadline).await
+    }
+
+    async fn dial(&self, addr: SocketAddr, deadline: &Deadline) -> Result<String, TransportError> {
+        self.config.path_policy.check_addr(addr.ip())?;
         let node = self.node.as_ref()
-            .ok_or_else(|| TransportError::ConnectionError("Transport not started".to_string()))?;
+            .ok_or(TransportError::NotStarted)?;
 
-        let peer_id = node.connect_to_bootstrap(addr)
-            .await
-            .map_err(|e| TransportError::ConnectionError(format!("Failed to connect: {}", e)))?;
+        let peer_id = deadline.run(node.connect_to_bootstrap(addr))
+            .await?
+            .map_err(|e| TransportError::Connection { context: "connect", source: e.into() })?;
 
         // Generate string representation for peer ID
         let peer_str = format!("{:?}", peer_id);
         
         // Store mapping
-        let mut peer_map = self.peer_map.write().await;
-        peer_map.insert(peer_str.clone(), peer_id);
-        
+        self.peer_map.insert(peer_str.clone(), peer_id);
+        self.peer_addrs.insert(peer_id, addr);
+
         // Set as default peer if no default set
-        let mut default_peer = self.default_peer.write().await;
-        if default_peer.is_none() {
-            *default_peer = Some(peer_id);
+        {
+            let mut default_peer = self.default_peer.write();
+            if default_peer.is_none() {
+                *default_peer = Some(peer_id);
+            }
         }
-        drop(default_peer);
-        
+
+        self.emit(TransportEvent::PeerConnected {
+            peer_id: peer_str.clone(),
+        });
+
         Ok(peer_str)
     }
 
+    /// Receive a signaling message, bounded by `deadline`
+    ///
+    /// Cancel-safe: a message is either returned or left queued in the
+    /// QUIC node, never dropped half-read.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if receiving fails, or [`TransportError::Interrupted`]
+    /// if the deadline passes or is cancelled first
+    pub async fn receive_message_with(
+        &self,
+        deadline: &Deadline,
+    ) -> Result<(String, SignalingMessage), TransportError> {
+        deadline.run(self.receive_message()).await?
+    }
+
     /// Disconnect from a peer
     ///
     /// # Errors
     ///
     /// Returns error if disconnection fails
     pub async fn disconnect_peer(&mut self, peer: &String) -> Result<(), TransportError> {
-        let mut peer_map = self.peer_map.write().await;
-        peer_map.remove(peer);
+        if let Some((_, peer_id)) = self.peer_map.remove(peer) {
+            self.peer_addrs.remove(&peer_id);
+            self.emit(TransportEvent::PeerDisconnected {
+                peer_id: peer.clone(),
+            });
+        }
         Ok(())
     }
 
@@ -193,15 +418,35 @@ impl AntQuicTransport {
     /// Returns error if send fails
     pub async fn send_bytes(&self, data: &[u8]) -> Result<(), TransportError> {
         let node = self.node.as_ref()
-            .ok_or_else(|| TransportError::SendError("Transport not started".to_string()))?;
+            .ok_or(TransportError::NotStarted)?;
 
-        let default_peer = self.default_peer.read().await;
-        let peer_id = default_peer.as_ref()
-            .ok_or_else(|| TransportError::SendError("No peer connected".to_string()))?;
+        // Copy the peer ID out so the lock is not held across the send
+        let peer_id = (*self.default_peer.read())
+            .ok_or(TransportError::NoPeer)?;
 
-        node.send_to_peer(peer_id, data)
+        node.send_to_peer(&peer_id, data)
             .await
-            .map_err(|e| TransportError::SendError(format!("Failed to send: {}", e)))?;
+            .map_err(|e| TransportError::Send(e.into()))?;
+
+        Ok(())
+    }
+
+    /// Send raw bytes to a connected peer (for broadcast RTP packets)
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the peer is unknown or send fails
+    pub async fn send_bytes_to(&self, peer: &String, data: &[u8]) -> Result<(), TransportError> {
+        let node = self.node.as_ref()
+            .ok_or(TransportError::NotStarted)?;
+
+        let peer_id = self.peer_map.get(peer)
+            .map(|entry| *entry.value())
+            .ok_or_else(|| TransportError::PeerNotFound(peer.clone()))?;
+
+        node.send_to_peer(&peer_id, data)
+            .await
+            .map_err(|e| TransportError::Send(e.into()))?;
 
         Ok(())
     }
@@ -213,14 +458,31 @@ impl AntQuicTransport {
     /// Returns error if receive fails
     pub async fn receive_bytes(&self) -> Result<Vec<u8>, TransportError> {
         let node = self.node.as_ref()
-            .ok_or_else(|| TransportError::ReceiveError("Transport not started".to_string()))?;
-
-        let (_peer_id, data) = node.receive()
-            .await
-            .map_err(|e| TransportError::ReceiveError(format!("Failed to receive: {}", e)))?;
+            .ok_or(TransportError::NotStarted)?;
 
+        let (_peer_id, data) = self.receive_allowed(node).await?;
         Ok(data)
     }
+
+    /// Receive from the next peer whose address the path policy allows,
+    /// dropping what others send
+    async fn receive_allowed(
+        &self,
+        node: &ant_quic::quic_node::QuicP2PNode,
+    ) -> Result<(ant_quic::nat_traversal_api::PeerId, Vec<u8>), TransportError> {
+        loop {
+            let (peer_id, data) = node.receive()
+                .await
+                .map_err(|e| TransportError::Receive(e.into()))?;
+
+            // Copy the address out so the shard guard is released at once
+            let addr = self.peer_addrs.get(&peer_id).map(|entry| *entry.value());
+            match addr.map(|addr| self.config.path_policy.check_addr(addr.ip())) {
+                Some(Err(e)) => tracing::debug!("Dropping data from {:?}: {}", peer_id, e),
+                _ => return Ok((peer_id, data)),
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -234,25 +496,25 @@ impl SignalingTransport for AntQuicTransport {
         message: SignalingMessage,
     ) -> Result<(), TransportError> {
         if peer.is_empty() {
-            return Err(TransportError::SendError("Peer ID cannot be empty".to_string()));
+            return Err(TransportError::PeerNotFound(peer.clone()));
         }
 
         let node = self.node.as_ref()
-            .ok_or_else(|| TransportError::SendError("Transport not started".to_string()))?;
+            .ok_or(TransportError::NotStarted)?;
 
-        // Get actual peer ID from map
-        let peer_map = self.peer_map.read().await;
-        let peer_id = peer_map.get(peer)
-            .ok_or_else(|| TransportError::SendError(format!("Peer not found: {}", peer)))?;
+        // Get actual peer ID from map, releasing the shard guard before sending
+        let peer_id = self.peer_map.get(peer)
+            .map(|entry| *entry.value())
+            .ok_or_else(|| TransportError::PeerNotFound(peer.clone()))?;
 
         // Serialize the message
         let data = serde_json::to_vec(&message)
-            .map_err(|e| TransportError::SendError(format!("Failed to serialize message: {}", e)))?;
+            .map_err(TransportError::Malformed)?;
 
         // Send over QUIC
-        node.send_to_peer(peer_id, &data)
+        node.send_to_peer(&peer_id, &data)
             .await
-            .map_err(|e| TransportError::SendError(format!("Failed to send: {}", e)))?;
+            .map_err(|e| TransportError::Send(e.into()))?;
 
         tracing::debug!("Sent signaling message to peer: {}", peer);
         Ok(())
@@ -260,25 +522,21 @@ impl SignalingTransport for AntQuicTransport {
 
     async fn receive_message(&self) -> Result<(String, SignalingMessage), TransportError> {
         let node = self.node.as_ref()
-            .ok_or_else(|| TransportError::ReceiveError("Transport not started".to_string()))?;
+            .ok_or(TransportError::NotStarted)?;
 
         // Receive data from any peer (this will block until data arrives)
         // The QuicP2PNode handles incoming connections internally
-        let (peer_id, data) = node.receive()
-            .await
-            .map_err(|e| TransportError::ReceiveError(format!("Failed to receive: {}", e)))?;
+        let (peer_id, data) = self.receive_allowed(node).await?;
 
         // Deserialize the message
         let message: SignalingMessage = serde_json::from_slice(&data)
-            .map_err(|e| TransportError::ReceiveError(format!("Failed to deserialize message: {}", e)))?;
+            .map_err(TransportError::Malformed)?;
 
         // Generate string representation for peer ID
         let peer_str = format!("{:?}", peer_id);
         
         // Update peer map if needed
-        let mut peer_map = self.peer_map.write().await;
-        peer_map.entry(peer_str.clone()).or_insert(peer_id);
-        drop(peer_map);
+        self.peer_map.entry(peer_str.clone()).or_insert(peer_id);
 
         tracing::debug!("Received signaling message from peer: {}", peer_str);
         Ok((peer_str, message))
@@ -294,6 +552,15 @@ impl SignalingTransport for AntQuicTransport {
         tracing::debug!("Attempting to discover endpoint for peer: {}", peer);
         Ok(None)
     }
+
+    async fn connect_peer(&self, peer: &String, addr: SocketAddr) -> Result<(), TransportError> {
+        if self.peer_map.contains_key(peer) {
+            return Ok(());
+        }
+        let deadline = Deadline::after(self.config.connect_timeout);
+        self.dial(addr, &deadline).await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -327,7 +594,7 @@ mod tests {
         };
 
         let result = transport.send_message(&"".to_string(), message).await;
-        assert!(matches!(result, Err(TransportError::SendError(_))));
+        assert!(matches!(result, Err(TransportError::PeerNotFound(_))));
     }
 
     #[tokio::test]
@@ -336,7 +603,20 @@ mod tests {
         let transport = AntQuicTransport::new(config);
 
         let result = transport.receive_message().await;
-        assert!(matches!(result, Err(TransportError::ReceiveError(_))));
+        assert!(matches!(result, Err(TransportError::NotStarted)));
+    }
+
+    #[tokio::test]
+    async fn test_ant_quic_transport_receive_message_cancelled() {
+        let transport = AntQuicTransport::new(TransportConfig::default());
+        let deadline = Deadline::none();
+        deadline.token().cancel();
+
+        let result = transport.receive_message_with(&deadline).await;
+        assert!(matches!(
+            result,
+            Err(TransportError::Interrupted(Interrupted::Cancelled))
+        ));
     }
 
     #[tokio::test]
@@ -353,6 +633,7 @@ mod tests {
     fn test_ant_quic_transport_config() {
         let config = TransportConfig {
             local_addr: Some("127.0.0.1:8080".parse().unwrap()),
+            ..Default::default()
         };
         let transport = AntQuicTransport::new(config.clone());
 
@@ -363,5 +644,45 @@ mod tests {
     fn test_transport_config_default() {
         let config = TransportConfig::default();
         assert!(config.local_addr.is_none());
+        assert!(config.relays.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reload_replaces_config_before_start() {
+        let mut transport = AntQuicTransport::new(TransportConfig::default());
+        let config = TransportConfig {
+            local_addr: Some("127.0.0.1:0".parse().unwrap()),
+            relays: vec!["192.0.2.1:9000".parse().unwrap()],
+            ..Default::default()
+        };
+        transport.reload(config.clone()).await.unwrap();
+        assert_eq!(transport.config(), &config);
+
+        // Invalid configs leave the current one in place
+        let invalid = TransportConfig {
+            connect_timeout: Duration::ZERO,
+            ..Default::default()
+        };
+        let result = transport.reload(invalid).await;
+        assert!(matches!(result, Err(TransportError::Config(ConfigError::Invalid(_)))));
+        assert_eq!(transport.config(), &config);
+    }
+
+    #[tokio::test]
+    async fn test_connect_refuses_paths_outside_policy() {
+        let mut transport = AntQuicTransport::new(TransportConfig {
+            path_policy: PathPolicy {
+                lan_only: true,
+                ..PathPolicy::default()
+            },
+            ..Default::default()
+        });
+        let result = transport.connect_to_peer("203.0.113.7:9000".parse().unwrap()).await;
+        assert!(matches!(result, Err(TransportError::PathRejected(PathError::OutsideLan(_)))));
+        assert_eq!(result.unwrap_err().code(), ErrorCode::PathRejected);
+
+        // Addresses on the LAN get as far as needing a running node
+        let result = transport.connect_to_peer("192.168.1.20:9000".parse().unwrap()).await;
+        assert!(matches!(result, Err(TransportError::NotStarted)));
     }
 }
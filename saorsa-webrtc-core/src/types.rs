@@ -1,6 +1,13 @@
 //! WebRTC types and data structures
 
+use crate::annotation::Annotation;
+use crate::constraints::{AudioConstraints, VideoConstraints};
+use crate::dtmf::DtmfTone;
 use crate::identity::PeerIdentity;
+use crate::jitter::AudioPlayoutStats;
+use crate::messaging::TextMessage;
+use crate::playback_sync::SyncMessage;
+use crate::remote_control::RemoteInput;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -29,7 +36,7 @@ impl std::fmt::Display for CallId {
 }
 
 /// Media constraints for a call
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MediaConstraints {
     /// Enable audio
     pub audio: bool,
@@ -37,6 +44,12 @@ pub struct MediaConstraints {
     pub video: bool,
     /// Enable screen sharing
     pub screen_share: bool,
+    /// Microphone and capture format to use for audio
+    #[serde(default)]
+    pub audio_constraints: AudioConstraints,
+    /// Camera, resolution and frame rate to use for video
+    #[serde(default)]
+    pub video_constraints: VideoConstraints,
 }
 
 impl MediaConstraints {
@@ -46,6 +59,8 @@ impl MediaConstraints {
             audio: true,
             video: false,
             screen_share: false,
+            audio_constraints: AudioConstraints::default(),
+            video_constraints: VideoConstraints::default(),
         }
     }
 
@@ -55,6 +70,8 @@ impl MediaConstraints {
             audio: true,
             video: true,
             screen_share: false,
+            audio_constraints: AudioConstraints::default(),
+            video_constraints: VideoConstraints::default(),
         }
     }
 
@@ -64,9 +81,44 @@ impl MediaConstraints {
             audio: true,
             video: false,
             screen_share: true,
+            audio_constraints: AudioConstraints::default(),
+            video_constraints: VideoConstraints::default(),
         }
     }
 
+    /// No media, for connections that only carry data channels
+    pub fn data_only() -> Self {
+        Self {
+            audio: false,
+            video: false,
+            screen_share: false,
+            audio_constraints: AudioConstraints::default(),
+            video_constraints: VideoConstraints::default(),
+        }
+    }
+
+    /// Constraints sending the given kinds of media
+    pub fn from_media_types(types: &[MediaType]) -> Self {
+        Self {
+            audio: types.contains(&MediaType::Audio),
+            video: types.contains(&MediaType::Video),
+            screen_share: types.contains(&MediaType::ScreenShare),
+            ..Self::data_only()
+        }
+    }
+
+    /// Capture audio as `constraints` ask
+    pub fn with_audio_constraints(mut self, constraints: AudioConstraints) -> Self {
+        self.audio_constraints = constraints;
+        self
+    }
+
+    /// Capture video as `constraints` ask
+    pub fn with_video_constraints(mut self, constraints: VideoConstraints) -> Self {
+        self.video_constraints = constraints;
+        self
+    }
+
     /// Check if audio is enabled
     pub fn has_audio(&self) -> bool {
         self.audio
@@ -156,22 +208,71 @@ pub struct IceCandidate {
 }
 
 /// Call state enumeration
+///
+/// Calls move Calling or Ringing → Connecting → Connected → Ended, or to
+/// Failed from any state before Ended; see
+/// [`can_transition_to`](Self::can_transition_to).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CallState {
     /// No active call
     Idle,
     /// Initiating call
     Calling,
+    /// Waiting to redial a callee that could not be reached
+    Retrying,
+    /// Incoming call waiting to be answered
+    Ringing,
     /// Establishing connection
     Connecting,
     /// Call is active
     Connected,
+    /// Connection was lost; waiting for it to come back
+    Reconnecting,
+    /// Call is parked with outgoing media paused
+    Parked,
     /// Call is ending
     Ending,
+    /// Call ended normally
+    Ended,
     /// Call failed
     Failed,
 }
 
+impl CallState {
+    /// Whether a call in this state may move to `next`
+    ///
+    /// Connected calls may go back to Connecting to renegotiate, or wait in
+    /// Reconnecting while their connection is lost, and calls being placed
+    /// wait in Retrying between attempts. Ended and Failed are final.
+    #[must_use]
+    pub fn can_transition_to(self, next: Self) -> bool {
+        use CallState::{
+            Calling, Connected, Connecting, Ended, Ending, Failed, Idle, Parked, Reconnecting,
+            Retrying, Ringing,
+        };
+        match (self, next) {
+            (Idle, Calling | Ringing) => true,
+            (Calling, Ringing | Retrying) => true,
+            (Retrying, Calling) => true,
+            (Calling | Ringing, Connecting | Connected) => true,
+            (Connecting, Connected) => true,
+            (Connected, Connecting | Parked | Reconnecting) => true,
+            (Reconnecting, Connected) => true,
+            (Parked, Connected) => true,
+            (Ending, Ended) => true,
+            (Ended | Failed, _) => false,
+            (_, Ending | Ended | Failed) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the call is over
+    #[must_use]
+    pub fn is_final(self) -> bool {
+        matches!(self, Self::Ended | Self::Failed)
+    }
+}
+
 /// Call quality metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CallQualityMetrics {
@@ -185,6 +286,10 @@ pub struct CallQualityMetrics {
     pub bandwidth_kbps: u32,
     /// Timestamp when metrics were collected
     pub timestamp: DateTime<Utc>,
+    /// Audio as played out, from the call's
+    /// [`AudioJitterBuffer::playout_stats`](crate::jitter::AudioJitterBuffer::playout_stats)
+    #[serde(default)]
+    pub audio: Option<AudioPlayoutStats>,
 }
 
 impl CallQualityMetrics {
@@ -203,6 +308,33 @@ impl CallQualityMetrics {
             || self.jitter_ms > 40
             || self.bandwidth_kbps < 300
     }
+
+    /// Estimated mean opinion score of the call's audio, from 1 to 4.5
+    ///
+    /// A simplified ITU-T G.107 E-model. With playout stats, delay is half
+    /// the round trip plus the jitter buffer's and impairment is the share
+    /// of audio concealed; without them the network's jitter and packet
+    /// loss stand in.
+    #[must_use]
+    pub fn mos(&self) -> f32 {
+        let (buffer_ms, impaired_percent) = match &self.audio {
+            Some(audio) => (
+                f64::from(audio.jitter_buffer_delay_ms),
+                f64::from(audio.concealed_percent),
+            ),
+            None => (
+                2.0 * f64::from(self.jitter_ms),
+                f64::from(self.packet_loss_percent),
+            ),
+        };
+        // Codec and packetization delay on top of the path
+        let delay = f64::from(self.rtt_ms) / 2.0 + buffer_ms + 10.0;
+        let delay_impairment = 0.024 * delay + 0.11 * (delay - 177.3).max(0.0);
+        let loss_impairment = 30.0 * (1.0 + 15.0 * impaired_percent / 100.0).ln();
+        let r = (93.2 - delay_impairment - loss_impairment).clamp(0.0, 100.0);
+        let mos = 1.0 + 0.035 * r + 7.0e-6 * r * (r - 60.0) * (100.0 - r);
+        mos.clamp(1.0, 4.5) as f32
+    }
 }
 
 /// Multi-party call information
@@ -341,6 +473,37 @@ pub enum SignalingMessage<I: PeerIdentity> {
     },
 }
 
+/// Why a call failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureReason {
+    /// Setting up the connection failed
+    Connection,
+    /// The callee could not be reached
+    Unreachable,
+    /// Nobody answered before the ring or answer timeout
+    NoAnswer,
+    /// The connection was lost and did not come back in time
+    ConnectionLost,
+}
+
+/// Why a call ended, as told to the remote party in a
+/// [`SignalingMessage::Bye`](crate::signaling::SignalingMessage::Bye)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ByeReason {
+    /// Hung up after the call ran its course
+    HungUp,
+    /// Caller hung up before the call was answered
+    Cancelled,
+    /// Callee was already on a call
+    Busy,
+    /// Callee, or their call screening, turned the call down
+    Declined,
+    /// Nobody answered in time
+    Timeout,
+    /// Call could not go on, such as after losing its connection
+    Error,
+}
+
 /// Call event for notifications
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(bound = "I: PeerIdentity")]
@@ -366,15 +529,32 @@ pub enum CallEvent<I: PeerIdentity> {
         /// The answer
         answer: CallAnswer,
     },
+    /// A call moved to a new state
+    StateChanged {
+        /// Call identifier
+        call_id: CallId,
+        /// State the call left
+        from: CallState,
+        /// State the call is now in
+        to: CallState,
+    },
     /// Call rejected
     CallRejected {
         /// Call identifier
         call_id: CallId,
     },
+    /// The callee of a call we placed was on another call; the call ends
+    /// with [`ByeReason::Busy`] next
+    RemoteBusy {
+        /// Call identifier
+        call_id: CallId,
+    },
     /// Call ended
     CallEnded {
         /// Call identifier
         call_id: CallId,
+        /// Why, as given by whichever side ended it
+        reason: ByeReason,
     },
     /// Connection established
     ConnectionEstablished {
@@ -385,6 +565,8 @@ pub enum CallEvent<I: PeerIdentity> {
     ConnectionFailed {
         /// Call identifier
         call_id: CallId,
+        /// Why the call failed
+        reason: FailureReason,
         /// Error description
         error: String,
     },
@@ -395,6 +577,80 @@ pub enum CallEvent<I: PeerIdentity> {
         /// Current metrics
         metrics: CallQualityMetrics,
     },
+    /// The remote party pointed or drew on a screen share
+    AnnotationReceived {
+        /// Call identifier
+        call_id: CallId,
+        /// Screen share track the annotation applies to
+        track_id: String,
+        /// The annotation
+        annotation: Annotation,
+    },
+    /// The remote party, granted control of one of our screen shares,
+    /// sent keyboard or mouse input
+    RemoteInputReceived {
+        /// Call identifier
+        call_id: CallId,
+        /// Screen share track being controlled
+        track_id: String,
+        /// The input to inject
+        input: RemoteInput,
+    },
+    /// The remote party played, paused or seeked in a watch-together
+    /// session
+    ///
+    /// Apply it with [`SyncSession::receive`](crate::playback_sync::SyncSession::receive).
+    SyncReceived {
+        /// Call identifier
+        call_id: CallId,
+        /// Playback sync session
+        session_id: String,
+        /// The message
+        message: SyncMessage,
+    },
+    /// A text message arrived over a call's messaging channel
+    TextReceived {
+        /// Call identifier
+        call_id: CallId,
+        /// The message
+        message: TextMessage,
+    },
+    /// The remote party pressed a key over a call's DTMF channel
+    DtmfReceived {
+        /// Call identifier
+        call_id: CallId,
+        /// The key press
+        tone: DtmfTone,
+    },
+    /// The remote party started sending a track, grouped into one of its
+    /// streams
+    RemoteTrackAdded {
+        /// Call identifier
+        call_id: CallId,
+        /// Remote stream the track belongs to
+        stream_id: String,
+        /// Track identifier
+        track_id: String,
+        /// Kind of media the track carries
+        kind: MediaType,
+    },
+    /// A remote track ended and left its stream
+    RemoteTrackRemoved {
+        /// Call identifier
+        call_id: CallId,
+        /// Remote stream the track belonged to
+        stream_id: String,
+        /// Track identifier
+        track_id: String,
+    },
+    /// One of our screen shares was taken off a call as its capture
+    /// handle was dropped
+    ScreenShareEnded {
+        /// Call identifier
+        call_id: CallId,
+        /// Track that carried the share
+        track_id: String,
+    },
 }
 
 /// Call session information
@@ -505,6 +761,33 @@ mod tests {
         assert!(screen.has_screen_share());
     }
 
+    #[test]
+    fn test_call_state_transitions() {
+        use CallState::*;
+        let happy = [Idle, Calling, Ringing, Connecting, Connected, Ending, Ended];
+        for pair in happy.windows(2) {
+            assert!(pair[0].can_transition_to(pair[1]), "{pair:?}");
+        }
+        assert!(Connected.can_transition_to(Parked));
+        assert!(Parked.can_transition_to(Connected));
+        assert!(Connecting.can_transition_to(Failed));
+        assert!(Calling.can_transition_to(Retrying));
+        assert!(Retrying.can_transition_to(Calling));
+        assert!(Retrying.can_transition_to(Failed));
+        assert!(Connected.can_transition_to(Reconnecting));
+        assert!(Reconnecting.can_transition_to(Connected));
+        assert!(Reconnecting.can_transition_to(Failed));
+
+        assert!(!Retrying.can_transition_to(Connected));
+        assert!(!Idle.can_transition_to(Connected));
+        assert!(!Connected.can_transition_to(Ringing));
+        assert!(!Parked.can_transition_to(Connecting));
+        assert!(!Ending.can_transition_to(Connected));
+        assert!(!Ended.can_transition_to(Failed));
+        assert!(!Failed.can_transition_to(Connecting));
+        assert!(Ended.is_final() && !Ending.is_final());
+    }
+
     #[test]
     fn test_call_session() {
         let call_id = CallId::new();
@@ -531,9 +814,22 @@ mod tests {
             jitter_ms: 10,
             bandwidth_kbps: 1000,
             timestamp: Utc::now(),
+            audio: None,
         };
         assert!(good.is_good_quality());
         assert!(!good.needs_adaptation());
+        assert!(good.mos() > 4.0);
+
+        // A clean network can still sound bad once playout conceals
+        let glitchy = CallQualityMetrics {
+            audio: Some(AudioPlayoutStats {
+                jitter_buffer_delay_ms: 200,
+                concealed_percent: 10.0,
+                ..AudioPlayoutStats::default()
+            }),
+            ..good.clone()
+        };
+        assert!(glitchy.mos() < 3.0);
 
         let bad = CallQualityMetrics {
             rtt_ms: 300,
@@ -541,6 +837,7 @@ mod tests {
             jitter_ms: 50,
             bandwidth_kbps: 200,
             timestamp: Utc::now(),
+            audio: None,
         };
         assert!(!bad.is_good_quality());
         assert!(bad.needs_adaptation());
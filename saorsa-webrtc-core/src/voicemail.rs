@@ -0,0 +1,320 @@
+//! Voicemail / leave-a-message support
+//!
+//! When a callee rejects or misses a call, the caller can record a short
+//! audio message. The message is stored in the recording container format
+//! and delivered as a [`SignalingMessage::Voicemail`], so transports with an
+//! offline mailbox can hold it until the callee comes back online.
+
+use crate::error::{CodedError, ErrorCode};
+use crate::recording::{
+    RecordedFrame, RecordedTrackKind, RecordingError, RecordingReader, RecordingWriter,
+};
+use crate::signaling::SignalingMessage;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use thiserror::Error;
+
+/// Voicemail errors
+#[derive(Error, Debug)]
+pub enum VoicemailError {
+    /// Message exceeds the configured duration
+    #[error("Voicemail too long: {0} ms")]
+    TooLong(u64),
+
+    /// Message exceeds the configured size
+    #[error("Voicemail too large: {0} bytes")]
+    TooLarge(usize),
+
+    /// No audio was recorded
+    #[error("Voicemail is empty")]
+    Empty,
+
+    /// Payload could not be decoded
+    #[error("Invalid voicemail payload: {0}")]
+    InvalidPayload(String),
+
+    /// Recording container error
+    #[error("Recording error: {0}")]
+    Recording(#[from] RecordingError),
+}
+
+impl CodedError for VoicemailError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::TooLong(_) | Self::TooLarge(_) | Self::Empty => ErrorCode::VoicemailFailed,
+            Self::InvalidPayload(_) => ErrorCode::MalformedMessage,
+            Self::Recording(e) => e.code(),
+        }
+    }
+}
+
+/// Voicemail limits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoicemailConfig {
+    /// Maximum message duration in milliseconds
+    pub max_duration_ms: u64,
+    /// Maximum encoded message size in bytes
+    pub max_size_bytes: usize,
+}
+
+impl Default for VoicemailConfig {
+    fn default() -> Self {
+        Self {
+            max_duration_ms: 60_000,
+            max_size_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// A recorded voice message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Voicemail {
+    /// Unique message ID
+    pub message_id: String,
+    /// Session the message was left for
+    pub session_id: String,
+    /// Sender identity
+    pub sender: String,
+    /// When recording finished
+    pub recorded_at: DateTime<Utc>,
+    /// Message duration in milliseconds
+    pub duration_ms: u64,
+    /// Encoded audio in the recording container format
+    pub audio: Vec<u8>,
+}
+
+impl Voicemail {
+    /// Decode the recorded audio frames
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the audio container is invalid
+    pub fn frames(&self) -> Result<Vec<RecordedFrame>, VoicemailError> {
+        Ok(RecordingReader::new(Cursor::new(&self.audio))?.read_all()?)
+    }
+
+    /// Build the signaling message used to deliver this voicemail
+    #[must_use]
+    pub fn to_signaling(&self) -> SignalingMessage {
+        SignalingMessage::Voicemail {
+            session_id: self.session_id.clone(),
+            message_id: self.message_id.clone(),
+            sender: self.sender.clone(),
+            recorded_at: self.recorded_at,
+            duration_ms: self.duration_ms,
+            audio: STANDARD.encode(&self.audio),
+        }
+    }
+
+    /// Parse and validate a received voicemail
+    ///
+    /// Returns `Ok(None)` if the message is not a voicemail.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the voicemail violates `config` or cannot be decoded
+    pub fn from_signaling(
+        message: &SignalingMessage,
+        config: &VoicemailConfig,
+    ) -> Result<Option<Self>, VoicemailError> {
+        let SignalingMessage::Voicemail {
+            session_id,
+            message_id,
+            sender,
+            recorded_at,
+            duration_ms,
+            audio,
+        } = message
+        else {
+            return Ok(None);
+        };
+
+        if *duration_ms > config.max_duration_ms {
+            return Err(VoicemailError::TooLong(*duration_ms));
+        }
+        // Base64 expands by 4/3, reject oversized payloads before decoding
+        if audio.len() / 4 * 3 > config.max_size_bytes {
+            return Err(VoicemailError::TooLarge(audio.len() / 4 * 3));
+        }
+        let audio = STANDARD
+            .decode(audio)
+            .map_err(|e| VoicemailError::InvalidPayload(e.to_string()))?;
+
+        let voicemail = Self {
+            message_id: message_id.clone(),
+            session_id: session_id.clone(),
+            sender: sender.clone(),
+            recorded_at: *recorded_at,
+            duration_ms: *duration_ms,
+            audio,
+        };
+        // Validate the container before handing it to the application
+        voicemail.frames()?;
+        Ok(Some(voicemail))
+    }
+}
+
+/// Collects encoded audio frames into a [`Voicemail`]
+pub struct VoicemailRecorder {
+    config: VoicemailConfig,
+    writer: RecordingWriter<Vec<u8>>,
+    first_timestamp_ms: Option<u64>,
+    duration_ms: u64,
+    size_bytes: usize,
+}
+
+impl VoicemailRecorder {
+    /// Start a new recording
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the container cannot be initialised
+    pub fn new(config: VoicemailConfig) -> Result<Self, VoicemailError> {
+        Ok(Self {
+            config,
+            writer: RecordingWriter::new(Vec::new())?,
+            first_timestamp_ms: None,
+            duration_ms: 0,
+            size_bytes: 0,
+        })
+    }
+
+    /// Append an encoded audio frame
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the frame would exceed the duration or size limit
+    pub fn push_audio(
+        &mut self,
+        timestamp_ms: u64,
+        payload: Vec<u8>,
+    ) -> Result<(), VoicemailError> {
+        let first = *self.first_timestamp_ms.get_or_insert(timestamp_ms);
+        let duration_ms = timestamp_ms.saturating_sub(first);
+        if duration_ms > self.config.max_duration_ms {
+            return Err(VoicemailError::TooLong(duration_ms));
+        }
+        let size_bytes = self.size_bytes + payload.len();
+        if size_bytes > self.config.max_size_bytes {
+            return Err(VoicemailError::TooLarge(size_bytes));
+        }
+
+        self.writer.write_frame(&RecordedFrame {
+            kind: RecordedTrackKind::Audio,
+            timestamp_ms: duration_ms,
+            payload,
+        })?;
+        self.duration_ms = self.duration_ms.max(duration_ms);
+        self.size_bytes = size_bytes;
+        Ok(())
+    }
+
+    /// Recorded duration so far in milliseconds
+    #[must_use]
+    pub fn duration_ms(&self) -> u64 {
+        self.duration_ms
+    }
+
+    /// Finish recording
+    ///
+    /// # Errors
+    ///
+    /// Returns error if no audio was recorded
+    pub fn finish(
+        self,
+        session_id: impl Into<String>,
+        sender: impl Into<String>,
+    ) -> Result<Voicemail, VoicemailError> {
+        if self.writer.frames_written() == 0 {
+            return Err(VoicemailError::Empty);
+        }
+        Ok(Voicemail {
+            message_id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.into(),
+            sender: sender.into(),
+            recorded_at: Utc::now(),
+            duration_ms: self.duration_ms,
+            audio: self.writer.finish()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(frames: u64) -> Voicemail {
+        let mut recorder = VoicemailRecorder::new(VoicemailConfig::default()).unwrap();
+        for i in 0..frames {
+            recorder
+                .push_audio(1_000 + i * 20, vec![i as u8; 40])
+                .unwrap();
+        }
+        recorder.finish("call-1", "alice").unwrap()
+    }
+
+    #[test]
+    fn test_voicemail_signaling_roundtrip() {
+        let voicemail = record(50);
+        assert_eq!(voicemail.duration_ms, 980);
+
+        let message = voicemail.to_signaling();
+        assert_eq!(message.session_id(), "call-1");
+
+        let json = serde_json::to_string(&message).unwrap();
+        let parsed: SignalingMessage = serde_json::from_str(&json).unwrap();
+        let received = Voicemail::from_signaling(&parsed, &VoicemailConfig::default())
+            .unwrap()
+            .unwrap();
+        assert_eq!(received, voicemail);
+        assert_eq!(received.frames().unwrap().len(), 50);
+    }
+
+    #[test]
+    fn test_voicemail_recorder_enforces_duration() {
+        let config = VoicemailConfig {
+            max_duration_ms: 100,
+            ..Default::default()
+        };
+        let mut recorder = VoicemailRecorder::new(config).unwrap();
+        recorder.push_audio(0, vec![0; 10]).unwrap();
+        assert!(matches!(
+            recorder.push_audio(200, vec![0; 10]),
+            Err(VoicemailError::TooLong(200))
+        ));
+    }
+
+    #[test]
+    fn test_voicemail_recorder_rejects_empty() {
+        let recorder = VoicemailRecorder::new(VoicemailConfig::default()).unwrap();
+        assert!(matches!(
+            recorder.finish("call-1", "alice"),
+            Err(VoicemailError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_voicemail_receiver_enforces_limits() {
+        let message = record(50).to_signaling();
+        let config = VoicemailConfig {
+            max_size_bytes: 100,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Voicemail::from_signaling(&message, &config),
+            Err(VoicemailError::TooLarge(_))
+        ));
+
+        let other = SignalingMessage::IceComplete {
+            session_id: "call-1".to_string(),
+        };
+        assert!(
+            Voicemail::from_signaling(&other, &VoicemailConfig::default())
+                .unwrap()
+                .is_none()
+        );
+    }
+}
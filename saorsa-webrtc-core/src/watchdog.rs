@@ -0,0 +1,506 @@
+//! Media pipeline watchdog
+//!
+//! A [`MediaWatchdog`] watches the [`Heartbeat`]s beaten as frames enter
+//! and leave pipeline stages, and notices when a capture device delivers
+//! nothing, an encoder takes frames in but puts nothing out, or a decoder
+//! receives frames but decodes none. Each stall is reported as a
+//! [`MediaEvent::PipelineStalled`] and the stage is asked to recover:
+//! capture reopens its device, encoders are reset, and decoders are reset
+//! and ask the sender for a keyframe. Recovery is retried once per stall
+//! period up to [`WatchdogConfig::max_recoveries`] times. When frames flow
+//! again a [`MediaEvent::PipelineRecovered`] says how long the stall lasted.
+//!
+//! [`MediaEvent::PipelineStalled`]: crate::media::MediaEvent::PipelineStalled
+//! [`MediaEvent::PipelineRecovered`]: crate::media::MediaEvent::PipelineRecovered
+
+use crate::media::MediaEvent;
+use crate::pipeline::{MediaPayload, MediaPipeline, PipelineError, PipelineNode, PipelineStage};
+use crate::types::CallId;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Name of the [`HeartbeatNode`] ahead of a pipeline's encoder
+pub const WATCHDOG_INPUT_NODE: &str = "watchdog-input";
+
+/// Name of the [`HeartbeatNode`] behind a pipeline's encoder
+pub const WATCHDOG_OUTPUT_NODE: &str = "watchdog-output";
+
+/// How long stages may go without frames before they count as stalled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchdogConfig {
+    /// Longest a capture device may deliver nothing
+    pub capture_stall: Duration,
+    /// Longest an encoder may take frames without producing any
+    pub encoder_stall: Duration,
+    /// Longest a decoder may receive frames without decoding any
+    pub decoder_stall: Duration,
+    /// Recovery attempts per stall before giving up
+    pub max_recoveries: u32,
+    /// How often stages are checked
+    pub check_interval: Duration,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            capture_stall: Duration::from_secs(1),
+            encoder_stall: Duration::from_secs(2),
+            decoder_stall: Duration::from_secs(3),
+            max_recoveries: 3,
+            check_interval: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Pipeline stage being watched
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WatchTarget {
+    /// Capture into a local track
+    Capture(String),
+    /// Encoder of a local track
+    Encoder(String),
+    /// Decoder of a call's remote track
+    Decoder {
+        /// Call the track is received on
+        call_id: CallId,
+        /// Remote track
+        track_id: String,
+    },
+}
+
+impl WatchTarget {
+    /// Recovery attempted when this stage stalls
+    #[must_use]
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            Self::Capture(_) => Recovery::ReopenDevice,
+            Self::Encoder(_) => Recovery::ResetEncoder,
+            Self::Decoder { .. } => Recovery::RequestKeyframe,
+        }
+    }
+}
+
+/// What a stalled stage is asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recovery {
+    /// Close the capture device and open it again
+    ReopenDevice,
+    /// Replace the encoder, or restart it with a keyframe
+    ResetEncoder,
+    /// Replace the decoder and ask the sender for a keyframe
+    RequestKeyframe,
+}
+
+/// Milliseconds since the watchdog clock started; 0 means never
+fn now_ms() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64 + 1
+}
+
+#[derive(Debug, Default)]
+struct Beats {
+    frame_in: AtomicU64,
+    frame_out: AtomicU64,
+}
+
+/// When frames last entered and left a stage; clones share the beats
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeat(Arc<Beats>);
+
+impl Heartbeat {
+    /// Create a heartbeat that has seen no frames
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A frame went into the stage
+    pub fn frame_in(&self) {
+        self.0.frame_in.store(now_ms(), Ordering::Relaxed);
+    }
+
+    /// A frame came out of the stage; capture beats only this
+    pub fn frame_out(&self) {
+        self.0.frame_out.store(now_ms(), Ordering::Relaxed);
+    }
+
+    fn last(&self) -> (u64, u64) {
+        (
+            self.0.frame_in.load(Ordering::Relaxed),
+            self.0.frame_out.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Beats a heartbeat for frames passing through: raw frames as going
+/// into the encoder, encoded frames as coming out of it
+pub struct HeartbeatNode {
+    heartbeat: Heartbeat,
+}
+
+impl HeartbeatNode {
+    /// Beat `heartbeat`
+    #[must_use]
+    pub fn new(heartbeat: Heartbeat) -> Self {
+        Self { heartbeat }
+    }
+}
+
+impl PipelineNode for HeartbeatNode {
+    fn process(&mut self, payload: MediaPayload) -> Result<Option<MediaPayload>, PipelineError> {
+        match &payload {
+            MediaPayload::Encoded(_) => self.heartbeat.frame_out(),
+            MediaPayload::Audio(_) | MediaPayload::Video(_) => self.heartbeat.frame_in(),
+        }
+        Ok(Some(payload))
+    }
+}
+
+/// Add heartbeat nodes around the encoder of `pipeline`
+///
+/// The input node goes last in [`PipelineStage::Process`], so frames held
+/// back by gates do not count as fed to the encoder.
+///
+/// # Errors
+///
+/// Returns error if the pipeline already has the nodes
+pub fn watch_encoder(
+    pipeline: &mut MediaPipeline,
+    heartbeat: &Heartbeat,
+) -> Result<(), PipelineError> {
+    pipeline.add(
+        PipelineStage::Process,
+        WATCHDOG_INPUT_NODE,
+        Box::new(HeartbeatNode::new(heartbeat.clone())),
+    )?;
+    pipeline.add(
+        PipelineStage::Packetize,
+        WATCHDOG_OUTPUT_NODE,
+        Box::new(HeartbeatNode::new(heartbeat.clone())),
+    )
+}
+
+type RecoverFn = Arc<dyn Fn(Recovery) + Send + Sync>;
+
+struct Watch {
+    heartbeat: Heartbeat,
+    recover: RecoverFn,
+    watched_at: u64,
+    stalled_since: Option<u64>,
+    last_attempt: u64,
+    recoveries: u32,
+}
+
+/// Watches pipeline stages for stalls and asks them to recover
+pub struct MediaWatchdog {
+    config: WatchdogConfig,
+    watches: Mutex<HashMap<WatchTarget, Watch>>,
+}
+
+impl MediaWatchdog {
+    /// Create a watchdog watching nothing
+    #[must_use]
+    pub fn new(config: WatchdogConfig) -> Self {
+        Self {
+            config,
+            watches: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Stall limits in use
+    #[must_use]
+    pub fn config(&self) -> WatchdogConfig {
+        self.config
+    }
+
+    /// Watch a stage through `heartbeat`, calling `recover` when it stalls
+    ///
+    /// Replaces any earlier watch of the same stage. `recover` is called
+    /// from [`check`](Self::check) and should only signal the stage.
+    pub fn watch(
+        &self,
+        target: WatchTarget,
+        heartbeat: Heartbeat,
+        recover: impl Fn(Recovery) + Send + Sync + 'static,
+    ) {
+        self.watches.lock().insert(
+            target,
+            Watch {
+                heartbeat,
+                recover: Arc::new(recover),
+                watched_at: now_ms(),
+                stalled_since: None,
+                last_attempt: 0,
+                recoveries: 0,
+            },
+        );
+    }
+
+    /// Heartbeat of a watched stage
+    #[must_use]
+    pub fn heartbeat(&self, target: &WatchTarget) -> Option<Heartbeat> {
+        self.watches
+            .lock()
+            .get(target)
+            .map(|watch| watch.heartbeat.clone())
+    }
+
+    /// Stop watching a stage; false if it was not watched
+    pub fn unwatch(&self, target: &WatchTarget) -> bool {
+        self.watches.lock().remove(target).is_some()
+    }
+
+    /// Stop watching a call's decoders
+    pub fn unwatch_call(&self, call_id: CallId) {
+        self.watches.lock().retain(|target, _| {
+            !matches!(target, WatchTarget::Decoder { call_id: id, .. } if *id == call_id)
+        });
+    }
+
+    /// Whether a stage is stalled as of the last check
+    #[must_use]
+    pub fn is_stalled(&self, target: &WatchTarget) -> bool {
+        self.watches
+            .lock()
+            .get(target)
+            .is_some_and(|watch| watch.stalled_since.is_some())
+    }
+
+    /// Check every stage, starting recoveries and returning the stalls and
+    /// recoveries to report
+    pub fn check(&self) -> Vec<MediaEvent> {
+        self.check_at(now_ms())
+    }
+
+    fn check_at(&self, now: u64) -> Vec<MediaEvent> {
+        let mut events = Vec::new();
+        let mut recoveries = Vec::new();
+        let mut watches = self.watches.lock();
+        for (target, watch) in watches.iter_mut() {
+            let limit = self.limit(target);
+            let (frame_in, frame_out) = watch.heartbeat.last();
+            let since = frame_out.max(watch.watched_at);
+            let stalled = now.saturating_sub(since) >= limit
+                && match target {
+                    WatchTarget::Capture(_) => true,
+                    // Only a stage still being fed can be stuck
+                    _ => frame_in > since && now.saturating_sub(frame_in) < limit,
+                };
+
+            match watch.stalled_since {
+                None if stalled => watch.stalled_since = Some(since),
+                Some(start) if !stalled => {
+                    events.push(MediaEvent::PipelineRecovered {
+                        target: target.clone(),
+                        stalled_ms: now.saturating_sub(start),
+                    });
+                    watch.stalled_since = None;
+                    watch.recoveries = 0;
+                    continue;
+                }
+                Some(_) if now.saturating_sub(watch.last_attempt) >= limit => {}
+                _ => continue,
+            }
+
+            // Stalled and due another attempt; give up once out of them
+            let recovery = if watch.recoveries < self.config.max_recoveries {
+                Some(target.recovery())
+            } else if watch.recoveries == self.config.max_recoveries {
+                None
+            } else {
+                continue;
+            };
+            watch.recoveries += 1;
+            watch.last_attempt = now;
+            if let Some(recovery) = recovery {
+                recoveries.push((Arc::clone(&watch.recover), recovery));
+            }
+            events.push(MediaEvent::PipelineStalled {
+                target: target.clone(),
+                stalled_ms: now.saturating_sub(since),
+                recovery,
+            });
+        }
+        drop(watches);
+
+        // Unlocked, so recovering stages may watch or unwatch themselves
+        for (recover, recovery) in recoveries {
+            recover(recovery);
+        }
+        events
+    }
+
+    fn limit(&self, target: &WatchTarget) -> u64 {
+        let limit = match target {
+            WatchTarget::Capture(_) => self.config.capture_stall,
+            WatchTarget::Encoder(_) => self.config.encoder_stall,
+            WatchTarget::Decoder { .. } => self.config.decoder_stall,
+        };
+        limit.as_millis() as u64
+    }
+}
+
+impl Default for MediaWatchdog {
+    fn default() -> Self {
+        Self::new(WatchdogConfig::default())
+    }
+}
+
+impl std::fmt::Debug for MediaWatchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MediaWatchdog")
+            .field("config", &self.config)
+            .field("watches", &self.watches.lock().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorder() -> (Arc<Mutex<Vec<Recovery>>>, impl Fn(Recovery) + Send + Sync) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        (seen, move |recovery| sink.lock().push(recovery))
+    }
+
+    fn beat(heartbeat: &Heartbeat, frame_in: u64, frame_out: u64) {
+        heartbeat.0.frame_in.store(frame_in, Ordering::Relaxed);
+        heartbeat.0.frame_out.store(frame_out, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_silent_capture_is_reopened_until_attempts_run_out() {
+        let watchdog = MediaWatchdog::new(WatchdogConfig {
+            max_recoveries: 2,
+            ..WatchdogConfig::default()
+        });
+        let target = WatchTarget::Capture("audio-0".to_string());
+        let heartbeat = Heartbeat::new();
+        let (seen, recover) = recorder();
+        watchdog.watch(target.clone(), heartbeat.clone(), recover);
+        let start = watchdog.watches.lock()[&target].watched_at;
+
+        assert!(watchdog.check_at(start + 999).is_empty());
+        let events = watchdog.check_at(start + 1000);
+        assert!(matches!(
+            events[..],
+            [MediaEvent::PipelineStalled {
+                stalled_ms: 1000,
+                recovery: Some(Recovery::ReopenDevice),
+                ..
+            }]
+        ));
+        assert!(watchdog.is_stalled(&target));
+        // One attempt per stall period
+        assert!(watchdog.check_at(start + 1500).is_empty());
+        assert_eq!(watchdog.check_at(start + 2000).len(), 1);
+        let gave_up = watchdog.check_at(start + 3000);
+        assert!(matches!(
+            gave_up[..],
+            [MediaEvent::PipelineStalled { recovery: None, .. }]
+        ));
+        assert!(watchdog.check_at(start + 9000).is_empty());
+        assert_eq!(*seen.lock(), [Recovery::ReopenDevice; 2]);
+
+        beat(&heartbeat, 0, start + 9100);
+        let events = watchdog.check_at(start + 9200);
+        assert!(matches!(
+            events[..],
+            [MediaEvent::PipelineRecovered {
+                stalled_ms: 9200,
+                ..
+            }]
+        ));
+        assert!(!watchdog.is_stalled(&target));
+    }
+
+    #[test]
+    fn test_idle_encoder_is_not_stalled() {
+        let watchdog = MediaWatchdog::default();
+        let target = WatchTarget::Encoder("video-0".to_string());
+        let heartbeat = Heartbeat::new();
+        let (seen, recover) = recorder();
+        watchdog.watch(target.clone(), heartbeat.clone(), recover);
+        let start = watchdog.watches.lock()[&target].watched_at;
+
+        // Nothing fed in, e.g. a muted microphone
+        assert!(watchdog.check_at(start + 10_000).is_empty());
+
+        // Fed but producing nothing
+        beat(&heartbeat, start + 11_000, start + 1000);
+        let events = watchdog.check_at(start + 11_100);
+        assert!(matches!(
+            events[..],
+            [MediaEvent::PipelineStalled {
+                recovery: Some(Recovery::ResetEncoder),
+                ..
+            }]
+        ));
+        assert_eq!(*seen.lock(), [Recovery::ResetEncoder]);
+
+        // Input stopping ends the stall too
+        let events = watchdog.check_at(start + 14_000);
+        assert!(matches!(events[..], [MediaEvent::PipelineRecovered { .. }]));
+    }
+
+    #[test]
+    fn test_decoder_watches_end_with_their_call() {
+        let watchdog = MediaWatchdog::default();
+        let (call, other) = (CallId::new(), CallId::new());
+        let decoder = |call_id| WatchTarget::Decoder {
+            call_id,
+            track_id: "video-0".to_string(),
+        };
+        watchdog.watch(decoder(call), Heartbeat::new(), |_| {});
+        watchdog.watch(decoder(other), Heartbeat::new(), |_| {});
+        assert_eq!(decoder(call).recovery(), Recovery::RequestKeyframe);
+
+        watchdog.unwatch_call(call);
+        assert!(watchdog.heartbeat(&decoder(call)).is_none());
+        assert!(watchdog.heartbeat(&decoder(other)).is_some());
+        assert!(watchdog.unwatch(&decoder(other)));
+        assert!(!watchdog.unwatch(&decoder(other)));
+    }
+
+    #[test]
+    fn test_heartbeat_nodes_straddle_the_encoder() {
+        use crate::pipeline::{AudioEncoderNode, ENCODER_NODE};
+        use saorsa_webrtc_codecs::{
+            AudioFrame, Channels, OpusEncoder, OpusEncoderConfig, SampleRate,
+        };
+
+        let mut pipeline = MediaPipeline::new();
+        let encoder = OpusEncoder::new(OpusEncoderConfig::default()).unwrap();
+        pipeline
+            .add(
+                PipelineStage::Encode,
+                ENCODER_NODE,
+                Box::new(AudioEncoderNode::new(Box::new(encoder))),
+            )
+            .unwrap();
+        let heartbeat = Heartbeat::new();
+        watch_encoder(&mut pipeline, &heartbeat).unwrap();
+        let order: Vec<_> = pipeline.nodes().map(|(_, name)| name).collect();
+        assert_eq!(
+            order,
+            [WATCHDOG_INPUT_NODE, ENCODER_NODE, WATCHDOG_OUTPUT_NODE]
+        );
+
+        pipeline
+            .push(MediaPayload::Audio(AudioFrame {
+                data: vec![0; 960],
+                sample_rate: SampleRate::Hz48000,
+                channels: Channels::Mono,
+                timestamp: 0,
+            }))
+            .unwrap();
+        let (frame_in, frame_out) = heartbeat.last();
+        assert!(frame_in > 0 && frame_out >= frame_in);
+    }
+}
@@ -0,0 +1,86 @@
+//! Little-endian reading of data channel wire formats
+//!
+//! Messages sent over data channels start with a version byte followed by
+//! little-endian fields. [`WireReader`] walks those fields, failing with
+//! [`Truncated`] when the message ends early; each format converts that
+//! into its own error.
+
+/// Message ended before its last field did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Truncated;
+
+/// Little-endian reader over the rest of a message
+pub(crate) struct WireReader<'a>(&'a [u8]);
+
+impl<'a> WireReader<'a> {
+    /// Read from the start of `data`
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    /// Bytes not read yet
+    pub(crate) fn remaining(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Next `len` bytes
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Truncated> {
+        if self.0.len() < len {
+            return Err(Truncated);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    /// Next `N` bytes
+    pub(crate) fn take<const N: usize>(&mut self) -> Result<[u8; N], Truncated> {
+        let (head, rest) = self.0.split_first_chunk::<N>().ok_or(Truncated)?;
+        self.0 = rest;
+        Ok(*head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Truncated> {
+        self.take::<1>().map(|[b]| b)
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Truncated> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    pub(crate) fn i16(&mut self) -> Result<i16, Truncated> {
+        self.take().map(i16::from_le_bytes)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Truncated> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Truncated> {
+        self.take().map(u64::from_le_bytes)
+    }
+
+    pub(crate) fn i64(&mut self) -> Result<i64, Truncated> {
+        self.take().map(i64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fields_are_read_little_endian() {
+        let data = [1, 0x34, 0x12, 0xfe, 0xff, 9, 8, 7];
+        let mut reader = WireReader::new(&data);
+        assert_eq!(reader.u8(), Ok(1));
+        assert_eq!(reader.u16(), Ok(0x1234));
+        assert_eq!(reader.i16(), Ok(-2));
+        assert_eq!(reader.remaining(), 3);
+        assert_eq!(reader.bytes(2), Ok(&[9, 8][..]));
+        assert_eq!(reader.u32(), Err(Truncated));
+        assert_eq!(reader.bytes(2), Err(Truncated));
+        assert_eq!(reader.u8(), Ok(7));
+        assert_eq!(reader.u8(), Err(Truncated));
+    }
+}
@@ -1,9 +1,9 @@
 //! Comprehensive call state machine tests
 
 use saorsa_webrtc_core::{
-    CallManager, CallManagerConfig,
+    CallManager, CallManagerConfig, RemoteControlError, RemoteInput,
     call::CallError,
-    types::{MediaConstraints, CallState, CallId},
+    types::{MediaConstraints, CallEvent, CallState, CallId},
     identity::PeerIdentityString,
 };
 
@@ -101,6 +101,7 @@ async fn end_call_is_idempotent_by_removal() {
 async fn concurrent_call_limit_is_enforced() {
     let cfg = CallManagerConfig {
         max_concurrent_calls: 1,
+        ..Default::default()
     };
     let mgr = CallManager::<PeerIdentityString>::new(cfg).await.unwrap();
 
@@ -117,9 +118,7 @@ async fn concurrent_call_limit_is_enforced() {
             MediaConstraints::audio_only(),
         )
         .await;
-    assert!(
-        matches!(id2, Err(CallError::ConfigError(ref msg)) if msg.contains("Maximum concurrent calls"))
-    );
+    assert!(matches!(id2, Err(CallError::CallLimitReached(1))));
 }
 
 #[tokio::test]
@@ -158,3 +157,124 @@ async fn call_manager_errors_on_non_existent_calls() {
         Err(CallError::CallNotFound(_))
     ));
 }
+
+#[tokio::test]
+async fn video_pause_and_resume_keep_call_state() {
+    let mgr = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+        .await
+        .unwrap();
+    let constraints = MediaConstraints::video_call();
+    let id = mgr
+        .initiate_call(PeerIdentityString::new("callee"), constraints.clone())
+        .await
+        .unwrap();
+    mgr.accept_call(id, constraints).await.unwrap();
+
+    mgr.set_video_paused(id, true).await.unwrap();
+    mgr.set_video_paused(id, false).await.unwrap();
+    assert_eq!(mgr.get_call_state(id).await, Some(CallState::Connected));
+    assert!(matches!(
+        mgr.set_video_paused(CallId::new(), true).await,
+        Err(CallError::CallNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn mute_and_video_toggle_survive_bandwidth_pause() {
+    let mgr = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+        .await
+        .unwrap();
+    let constraints = MediaConstraints::video_call();
+    let id = mgr
+        .initiate_call(PeerIdentityString::new("callee"), constraints.clone())
+        .await
+        .unwrap();
+    mgr.accept_call(id, constraints).await.unwrap();
+    assert_eq!(mgr.is_audio_muted(id), Some(false));
+    assert_eq!(mgr.is_video_enabled(id), Some(true));
+
+    mgr.set_audio_muted(id, true).await.unwrap();
+    mgr.set_video_enabled(id, false).await.unwrap();
+    // Bandwidth recovering does not turn the user's video back on
+    mgr.set_video_paused(id, true).await.unwrap();
+    mgr.set_video_paused(id, false).await.unwrap();
+    assert_eq!(mgr.is_audio_muted(id), Some(true));
+    assert_eq!(mgr.is_video_enabled(id), Some(false));
+
+    mgr.park_call(id).await.unwrap();
+    mgr.resume_call(id).await.unwrap();
+    assert_eq!(mgr.is_audio_muted(id), Some(true));
+    assert_eq!(mgr.get_call_state(id).await, Some(CallState::Connected));
+    assert!(matches!(
+        mgr.set_audio_muted(CallId::new(), true).await,
+        Err(CallError::CallNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn remote_control_follows_grants_and_revocation() {
+    let mgr = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+        .await
+        .unwrap();
+    let constraints = MediaConstraints::screen_share();
+    let id = mgr
+        .initiate_call(PeerIdentityString::new("viewer"), constraints)
+        .await
+        .unwrap();
+
+    // Hosting: only the live grant counts
+    mgr.grant_remote_control(id, "screen-0").await.unwrap();
+    assert!(mgr.is_remotely_controlled(id, "screen-0"));
+    assert!(mgr.revoke_remote_control(id, "screen-0"));
+    assert!(!mgr.is_remotely_controlled(id, "screen-0"));
+    assert!(!mgr.revoke_remote_control(id, "screen-0"));
+
+    // Controlling: input needs a grant first
+    assert!(matches!(
+        mgr.send_remote_input(id, "screen-9", &RemoteInput::KeyDown(4)).await,
+        Err(CallError::RemoteControl(RemoteControlError::NotGranted(_)))
+    ));
+    mgr.accept_remote_control(id, "screen-9", 42).unwrap();
+    assert!(mgr.is_controlling(id, "screen-9"));
+    assert!(mgr.release_remote_control(id, "screen-9"));
+    assert!(!mgr.is_controlling(id, "screen-9"));
+}
+
+#[tokio::test]
+async fn every_transition_is_announced() {
+    let mgr = CallManager::<PeerIdentityString>::new(CallManagerConfig::default())
+        .await
+        .unwrap();
+    let mut events = mgr.subscribe_events();
+    let id = mgr
+        .initiate_call(
+            PeerIdentityString::new("callee"),
+            MediaConstraints::audio_only(),
+        )
+        .await
+        .unwrap();
+    mgr.accept_call(id, MediaConstraints::audio_only())
+        .await
+        .unwrap();
+    mgr.park_call(id).await.unwrap();
+    mgr.resume_call(id).await.unwrap();
+    mgr.end_call(id).await.unwrap();
+
+    let mut transitions = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let CallEvent::StateChanged { call_id, from, to } = event {
+            assert_eq!(call_id, id);
+            transitions.push((from, to));
+        }
+    }
+    assert_eq!(
+        transitions,
+        vec![
+            (CallState::Idle, CallState::Calling),
+            (CallState::Calling, CallState::Connected),
+            (CallState::Connected, CallState::Parked),
+            (CallState::Parked, CallState::Connected),
+            (CallState::Connected, CallState::Ended),
+        ]
+    );
+}
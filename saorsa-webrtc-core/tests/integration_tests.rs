@@ -1,6 +1,9 @@
 //! Integration tests for end-to-end WebRTC functionality
 
-use saorsa_webrtc_core::{CallId, CallManager, CallManagerConfig, MediaConstraints, MediaStreamManager, SignalingHandler, SignalingTransport, PeerIdentityString, CallState, MediaType};
+use saorsa_webrtc_core::{AnswerPolicy, ByeReason, CallDirection, CallEndReason, CallEvent, CallId, HandoffTracker, HistoryQuery, MemoryCallHistory, PathRecommendation, SessionConfig, CallManager, CallToken, FailureReason, PeerIdentity, CallManagerConfig, MediaConstraints, MediaStreamManager, RetrySchedule, SignalingHandler, SignalingTransport, PeerIdentityString, CallState, MediaType, TransportEvent, WebRtcConfig, WebRtcEvent, WebRtcService};
+use saorsa_webrtc_core::echo_peer::LoopbackNetwork;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
 use saorsa_webrtc_core::signaling::SignalingMessage;
 use std::sync::Arc;
 
@@ -259,3 +262,629 @@ async fn test_media_constraints_validation() {
     assert!(screen_types.contains(&MediaType::Audio));
     assert!(screen_types.contains(&MediaType::ScreenShare));
 }
+
+#[tokio::test]
+async fn test_inbound_offer_rings_and_is_answered() {
+    let transport = Arc::new(MockSignalingTransport::new());
+    let signaling = Arc::new(SignalingHandler::new(transport.clone()));
+    let service = WebRtcService::<PeerIdentityString, _>::builder(signaling)
+        .with_identity(PeerIdentityString::new("bob"))
+        .build()
+        .await
+        .unwrap();
+    let mut events = service.subscribe_events();
+
+    let alice = CallManager::<PeerIdentityString>::new(CallManagerConfig::default()).await.unwrap();
+    let call_id = alice
+        .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+        .await
+        .unwrap();
+    let offer = SignalingMessage::Offer {
+        session_id: call_id.to_string(),
+        sdp: alice.create_offer(call_id).await.unwrap(),
+        quic_endpoint: None,
+    };
+    service.handle_signaling_message(&"alice".to_string(), &offer).await.unwrap();
+
+    let constraints = loop {
+        if let WebRtcEvent::IncomingCall { call_id: ringing, from, constraints } = events.recv().await.unwrap() {
+            assert_eq!(ringing, call_id);
+            assert_eq!(from, PeerIdentityString::new("alice"));
+            break constraints;
+        }
+    };
+    assert_eq!(constraints.to_media_types(), vec![MediaType::Audio]);
+
+    service.accept_call(call_id, constraints).await.unwrap();
+    match transport.receive_from_peer("alice") {
+        Some(SignalingMessage::Answer { session_id, sdp, .. }) => {
+            assert_eq!(session_id, call_id.to_string());
+            alice.handle_answer(call_id, sdp).await.unwrap();
+        }
+        other => panic!("expected an answer, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_services_exchange_offer_and_answer() {
+    let alice_transport = Arc::new(MockSignalingTransport::new());
+    let alice = WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(alice_transport.clone())))
+        .with_identity(PeerIdentityString::new("alice"))
+        .build()
+        .await
+        .unwrap();
+    let bob_transport = Arc::new(MockSignalingTransport::new());
+    let bob = WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(bob_transport.clone())))
+        .with_identity(PeerIdentityString::new("bob"))
+        .build()
+        .await
+        .unwrap();
+    let mut bob_events = bob.subscribe_events();
+
+    // Placing the call offers it
+    let call_id = alice
+        .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+        .await
+        .unwrap();
+    let offer = alice_transport.receive_from_peer("bob").unwrap();
+    match &offer {
+        SignalingMessage::Offer { session_id, sdp, .. } => {
+            assert_eq!(*session_id, call_id.to_string());
+            // Candidates are not trickled, so the offer carries them
+            assert!(sdp.contains("a=candidate"));
+        }
+        other => panic!("expected an offer, got {other:?}"),
+    }
+    bob.handle_signaling_message(&"alice".to_string(), &offer).await.unwrap();
+    let constraints = loop {
+        if let WebRtcEvent::IncomingCall { constraints, .. } = bob_events.recv().await.unwrap() {
+            break constraints;
+        }
+    };
+    bob.accept_call(call_id, constraints).await.unwrap();
+    let answer = bob_transport.receive_from_peer("alice").unwrap();
+
+    // Only the callee's answers and candidates reach the call
+    let candidate = SignalingMessage::IceCandidate {
+        session_id: call_id.to_string(),
+        candidate: "candidate:1 1 udp 2130706431 192.0.2.1 50000 typ host".to_string(),
+        sdp_mid: None,
+        sdp_mline_index: None,
+    };
+    let mallory = "mallory".to_string();
+    alice.handle_signaling_message(&mallory, &candidate).await.unwrap();
+    let empty = SignalingMessage::Answer {
+        session_id: call_id.to_string(),
+        sdp: String::new(),
+        quic_endpoint: None,
+    };
+    alice.handle_signaling_message(&mallory, &empty).await.unwrap();
+    assert!(alice.handle_signaling_message(&"bob".to_string(), &empty).await.is_err());
+
+    alice.handle_signaling_message(&"bob".to_string(), &answer).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_accepted_handoffs_only_offer_a_replacement() {
+    let transport = Arc::new(MockSignalingTransport::new());
+    let service = WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(transport.clone())))
+        .with_identity(PeerIdentityString::new("alice"))
+        .build()
+        .await
+        .unwrap();
+    let previous = CallId::new();
+    let state = HandoffTracker::new().offer(previous, "alice", "bob", MediaConstraints::audio_only());
+
+    let call_id = service.accept_handoff(state).await.unwrap();
+    let sent = transport.peer_messages.lock().unwrap().remove("bob").unwrap();
+    assert_eq!(sent.len(), 1);
+    match &sent[0] {
+        SignalingMessage::ReplaceCall { session_id, replaces, .. } => {
+            assert_eq!(*session_id, call_id.to_string());
+            assert_eq!(*replaces, previous.to_string());
+        }
+        other => panic!("expected a replacement offer, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_byes_end_calls_with_their_reason() {
+    let transport = Arc::new(MockSignalingTransport::new());
+    let signaling = Arc::new(SignalingHandler::new(transport.clone()));
+    let service = WebRtcService::<PeerIdentityString, _>::builder(signaling)
+        .with_identity(PeerIdentityString::new("bob"))
+        .build()
+        .await
+        .unwrap();
+    let mut calls = service.event_bus().subscribe_calls();
+    let alice = CallManager::<PeerIdentityString>::new(CallManagerConfig::default()).await.unwrap();
+
+    let mut offered = Vec::new();
+    for _ in 0..2 {
+        let call_id = alice
+            .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        let offer = SignalingMessage::Offer {
+            session_id: call_id.to_string(),
+            sdp: alice.create_offer(call_id).await.unwrap(),
+            quic_endpoint: None,
+        };
+        service.handle_signaling_message(&"alice".to_string(), &offer).await.unwrap();
+        offered.push(call_id);
+    }
+
+    // Only the caller can hang up their call
+    let cancelled = offered[0];
+    let bye = SignalingMessage::Bye {
+        session_id: cancelled.to_string(),
+        reason: Some(ByeReason::Cancelled),
+    };
+    service.handle_signaling_message(&"mallory".to_string(), &bye).await.unwrap();
+    assert_eq!(service.get_call_state(cancelled).await, Some(CallState::Ringing));
+    service.handle_signaling_message(&"alice".to_string(), &bye).await.unwrap();
+    assert_eq!(service.get_call_state(cancelled).await, None);
+    let reason = loop {
+        if let CallEvent::CallEnded { call_id, reason } = calls.recv().await.unwrap() {
+            assert_eq!(call_id, cancelled);
+            break reason;
+        }
+    };
+    assert_eq!(reason, ByeReason::Cancelled);
+
+    let declined = offered[1];
+    service.reject_call(declined).await.unwrap();
+    assert_eq!(
+        transport.receive_from_peer("alice"),
+        Some(SignalingMessage::Bye {
+            session_id: declined.to_string(),
+            reason: Some(ByeReason::Declined),
+        })
+    );
+    // Torn down, not left failed with its peer connection open
+    assert_eq!(service.get_call_state(declined).await, None);
+    let reason = loop {
+        if let CallEvent::CallEnded { call_id, reason } = calls.recv().await.unwrap() {
+            assert_eq!(call_id, declined);
+            break reason;
+        }
+    };
+    assert_eq!(reason, ByeReason::Declined);
+}
+
+#[tokio::test]
+async fn test_ended_sessions_cannot_be_replayed() {
+    let transport = Arc::new(MockSignalingTransport::new());
+    let signaling = Arc::new(SignalingHandler::new(transport.clone()));
+    let service = WebRtcService::<PeerIdentityString, _>::builder(signaling)
+        .with_identity(PeerIdentityString::new("bob"))
+        .build()
+        .await
+        .unwrap();
+    let alice = CallManager::<PeerIdentityString>::new(CallManagerConfig::default()).await.unwrap();
+    let call_id = alice
+        .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+        .await
+        .unwrap();
+    let offer = SignalingMessage::Offer {
+        session_id: call_id.to_string(),
+        sdp: alice.create_offer(call_id).await.unwrap(),
+        quic_endpoint: None,
+    };
+    service.handle_signaling_message(&"alice".to_string(), &offer).await.unwrap();
+
+    // Only the caller may end their session
+    let bye = SignalingMessage::Bye { session_id: call_id.to_string(), reason: None };
+    service.handle_signaling_message(&"mallory".to_string(), &bye).await.unwrap();
+    assert_eq!(service.get_call_state(call_id).await, Some(CallState::Ringing));
+    service.handle_signaling_message(&"alice".to_string(), &bye).await.unwrap();
+    assert_eq!(service.get_call_state(call_id).await, None);
+
+    // Replaying the offer does not ring again
+    assert!(service.handle_signaling_message(&"alice".to_string(), &offer).await.is_err());
+    assert_eq!(service.get_call_state(call_id).await, None);
+}
+
+#[tokio::test]
+async fn test_quiet_ringing_calls_are_not_abandoned() {
+    let network = LoopbackNetwork::new();
+    let config = WebRtcConfig {
+        call_config: CallManagerConfig { answer_timeout: None, ..Default::default() },
+        sessions: SessionConfig { idle_timeout: Duration::from_millis(10), ..Default::default() },
+        ..Default::default()
+    };
+    let service = Arc::new(
+        WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(Arc::new(network.join("bob")))))
+            .with_config(config)
+            .with_identity(PeerIdentityString::new("bob"))
+            .build()
+            .await
+            .unwrap(),
+    );
+    let alice = CallManager::<PeerIdentityString>::new(CallManagerConfig::default()).await.unwrap();
+    let call_id = alice
+        .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+        .await
+        .unwrap();
+    let offer = SignalingMessage::Offer {
+        session_id: call_id.to_string(),
+        sdp: alice.create_offer(call_id).await.unwrap(),
+        quic_endpoint: None,
+    };
+    service.handle_signaling_message(&"alice".to_string(), &offer).await.unwrap();
+
+    // Long past the idle timeout, sessions are swept as signaling starts
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let running = Arc::clone(&service);
+    let signaling = tokio::spawn(async move { running.receive_signaling().await });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(service.get_call_state(call_id).await, Some(CallState::Ringing));
+    signaling.abort();
+}
+
+#[tokio::test]
+async fn test_calls_during_a_call_are_busy_or_wait() {
+    for call_waiting in [false, true] {
+        let transport = Arc::new(MockSignalingTransport::new());
+        let signaling = Arc::new(SignalingHandler::new(transport.clone()));
+        let config = WebRtcConfig { call_waiting, ..Default::default() };
+        let service = WebRtcService::<PeerIdentityString, _>::builder(signaling)
+            .with_config(config)
+            .with_identity(PeerIdentityString::new("bob"))
+            .build()
+            .await
+            .unwrap();
+        let mut events = service.subscribe_events();
+
+        let mut offered = Vec::new();
+        for caller in ["alice", "carol"] {
+            let manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default()).await.unwrap();
+            let call_id = manager
+                .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+                .await
+                .unwrap();
+            let offer = SignalingMessage::Offer {
+                session_id: call_id.to_string(),
+                sdp: manager.create_offer(call_id).await.unwrap(),
+                quic_endpoint: None,
+            };
+            service.handle_signaling_message(&caller.to_string(), &offer).await.unwrap();
+            if offered.is_empty() {
+                service.accept_call(call_id, MediaConstraints::audio_only()).await.unwrap();
+            }
+            offered.push(call_id);
+        }
+
+        if call_waiting {
+            let (waiting, active) = loop {
+                if let WebRtcEvent::CallWaiting { call_id, active_call, .. } = events.recv().await.unwrap() {
+                    break (call_id, active_call);
+                }
+            };
+            assert_eq!((waiting, active), (offered[1], offered[0]));
+        } else {
+            assert_eq!(service.get_call_state(offered[1]).await, None);
+            assert_eq!(
+                transport.receive_from_peer("carol"),
+                Some(SignalingMessage::Bye {
+                    session_id: offered[1].to_string(),
+                    reason: Some(ByeReason::Busy),
+                })
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_callers_hear_when_the_callee_is_busy() {
+    let transport = Arc::new(MockSignalingTransport::new());
+    let signaling = Arc::new(SignalingHandler::new(transport.clone()));
+    let service = WebRtcService::<PeerIdentityString, _>::builder(signaling)
+        .with_identity(PeerIdentityString::new("alice"))
+        .build()
+        .await
+        .unwrap();
+    let mut calls = service.event_bus().subscribe_calls();
+    let call_id = service
+        .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+        .await
+        .unwrap();
+
+    let busy = SignalingMessage::Bye {
+        session_id: call_id.to_string(),
+        reason: Some(ByeReason::Busy),
+    };
+    service.handle_signaling_message(&"bob".to_string(), &busy).await.unwrap();
+    let mut told_busy = false;
+    loop {
+        match calls.recv().await.unwrap() {
+            CallEvent::RemoteBusy { call_id: busy } => told_busy = busy == call_id,
+            CallEvent::CallEnded { reason, .. } => {
+                assert_eq!(reason, ByeReason::Busy);
+                break;
+            }
+            _ => {}
+        }
+    }
+    assert!(told_busy);
+    assert_eq!(service.get_call_state(call_id).await, None);
+}
+
+#[tokio::test]
+async fn test_answer_policy_answers_known_peers_without_ringing() {
+    let transport = Arc::new(MockSignalingTransport::new());
+    let signaling = Arc::new(SignalingHandler::new(transport.clone()));
+    let config = WebRtcConfig {
+        call_config: CallManagerConfig {
+            answer_policy: AnswerPolicy::AutoAcceptKnownPeers(vec!["alice".to_string()]),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let service = WebRtcService::<PeerIdentityString, _>::builder(signaling)
+        .with_config(config)
+        .with_identity(PeerIdentityString::new("bob"))
+        .build()
+        .await
+        .unwrap();
+    let mut events = service.subscribe_events();
+
+    for caller in ["alice", "carol"] {
+        let manager = CallManager::<PeerIdentityString>::new(CallManagerConfig::default()).await.unwrap();
+        let call_id = manager
+            .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+            .await
+            .unwrap();
+        let offer = SignalingMessage::Offer {
+            session_id: call_id.to_string(),
+            sdp: manager.create_offer(call_id).await.unwrap(),
+            quic_endpoint: None,
+        };
+        service.handle_signaling_message(&caller.to_string(), &offer).await.unwrap();
+
+        let answered = loop {
+            match events.recv().await.unwrap() {
+                WebRtcEvent::CallAutoAnswered { call_id: answered, caller: from, muted } => {
+                    assert_eq!(from, caller);
+                    assert!(!muted);
+                    break answered == call_id;
+                }
+                WebRtcEvent::IncomingCall { .. } => break false,
+                _ => {}
+            }
+        };
+        assert_eq!(answered, caller == "alice");
+        assert_eq!(transport.receive_from_peer(caller).is_some(), caller == "alice");
+    }
+}
+
+#[tokio::test]
+async fn test_guest_may_only_reach_its_issuer() {
+    let alice_transport = Arc::new(MockSignalingTransport::new());
+    let alice = WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(alice_transport.clone())))
+        .with_identity(PeerIdentityString::new("alice"))
+        .build()
+        .await
+        .unwrap();
+    let invitation = alice.create_guest_invitation("alice", chrono::Duration::hours(1));
+    let link = invitation.to_uri();
+
+    let guest_transport = Arc::new(MockSignalingTransport::new());
+    let guest = WebRtcService::<PeerIdentityString, _>::new(Arc::new(SignalingHandler::new(guest_transport.clone())), Default::default())
+        .await
+        .unwrap();
+    let identity = guest.join_as_guest(&CallToken::decode(&link).unwrap()).await.unwrap();
+    assert_eq!(Some(identity.to_string_repr()), invitation.guest);
+    assert_eq!(guest.identity(), Some(identity.clone()));
+    let redeem = guest_transport.receive_from_peer("alice").unwrap();
+    assert!(matches!(redeem, SignalingMessage::RedeemInvitation { .. }));
+
+    // Only the guest itself gets in with the invitation
+    alice.handle_signaling_message(&"mallory".to_string(), &redeem).await.unwrap();
+    assert!(matches!(alice_transport.receive_from_peer("mallory"), Some(SignalingMessage::InvitationRedeemed { accepted: false, .. })));
+    alice.handle_signaling_message(&identity.to_string_repr(), &redeem).await.unwrap();
+    assert!(matches!(alice_transport.receive_from_peer(&identity.to_string_repr()), Some(SignalingMessage::InvitationRedeemed { accepted: true, .. })));
+
+    assert!(guest.initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only()).await.is_err());
+    guest.initiate_call(PeerIdentityString::new("alice"), MediaConstraints::audio_only()).await.unwrap();
+}
+
+// Transport whose peers cannot be connected to for a while
+struct FlakyTransport {
+    failures: AtomicU32,
+}
+
+#[async_trait::async_trait]
+impl SignalingTransport for FlakyTransport {
+    type PeerId = String;
+    type Error = std::io::Error;
+
+    async fn send_message(&self, _peer: &String, _message: SignalingMessage) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn receive_message(&self) -> Result<(String, SignalingMessage), Self::Error> {
+        Err(std::io::Error::new(std::io::ErrorKind::WouldBlock, "No messages"))
+    }
+
+    async fn discover_peer_endpoint(&self, _peer: &String) -> Result<Option<std::net::SocketAddr>, Self::Error> {
+        Ok(Some("127.0.0.1:8080".parse().unwrap()))
+    }
+
+    async fn connect_peer(&self, _peer: &String, _addr: std::net::SocketAddr) -> Result<(), Self::Error> {
+        if self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1)).is_ok() {
+            return Err(std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "unreachable"));
+        }
+        Ok(())
+    }
+}
+
+async fn flaky_service(failures: u32, retries: u32) -> WebRtcService<PeerIdentityString, FlakyTransport> {
+    let transport = Arc::new(FlakyTransport { failures: AtomicU32::new(failures) });
+    let config = WebRtcConfig {
+        call_retry: Some(RetrySchedule {
+            retries,
+            initial_delay: Duration::from_millis(1),
+            backoff: 2.0,
+            max_delay: Duration::from_millis(5),
+        }),
+        ..Default::default()
+    };
+    WebRtcService::builder(Arc::new(SignalingHandler::new(transport)))
+        .with_config(config)
+        .build()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_unreachable_callee_is_redialled() {
+    let service = flaky_service(2, 3).await;
+    let mut events = service.subscribe_events();
+    let mut calls = service.event_bus().subscribe_calls();
+
+    let call_id = service
+        .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+        .await
+        .unwrap();
+    assert_eq!(service.get_call_state(call_id).await, Some(CallState::Calling));
+    let mut retries = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let WebRtcEvent::CallRetrying { call_id: retried, retry, .. } = event {
+            assert_eq!(retried, call_id);
+            retries.push(retry);
+        }
+    }
+    assert_eq!(retries, vec![1, 2]);
+
+    let mut states = Vec::new();
+    while let Some(event) = calls.try_recv() {
+        if let saorsa_webrtc_core::CallEvent::StateChanged { to, .. } = event {
+            states.push(to);
+        }
+    }
+    assert_eq!(
+        states,
+        vec![CallState::Calling, CallState::Retrying, CallState::Calling, CallState::Retrying, CallState::Calling]
+    );
+
+    // Retries run out
+    let service = flaky_service(u32::MAX, 1).await;
+    assert!(service
+        .initiate_call(PeerIdentityString::new("bob"), MediaConstraints::audio_only())
+        .await
+        .is_err());
+    assert!(!service.abort_call_retry(CallId::new()));
+}
+
+#[tokio::test]
+async fn test_calls_are_torn_down_when_the_connection_stays_lost() {
+    let network = LoopbackNetwork::new();
+    let _bob = network.join("bob");
+    let config = WebRtcConfig {
+        call_config: CallManagerConfig {
+            reconnect_grace: Duration::from_millis(50),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let service = Arc::new(
+        WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(Arc::new(network.join("alice")))))
+            .with_config(config)
+            .build()
+            .await
+            .unwrap(),
+    );
+    let mut calls = service.event_bus().subscribe_calls();
+    let constraints = MediaConstraints::audio_only();
+    let call_id = service
+        .initiate_call(PeerIdentityString::new("bob"), constraints.clone())
+        .await
+        .unwrap();
+    service.accept_call(call_id, constraints).await.unwrap();
+
+    let running = Arc::clone(&service);
+    let signaling = tokio::spawn(async move { running.receive_signaling().await });
+    service.event_bus().transport().publish(TransportEvent::PeerDisconnected { peer_id: "bob".to_string() });
+
+    let mut states = Vec::new();
+    let reason = loop {
+        match calls.recv().await.unwrap() {
+            CallEvent::StateChanged { to, .. } => states.push(to),
+            CallEvent::ConnectionFailed { reason, .. } => break reason,
+            _ => {}
+        }
+    };
+    assert_eq!(reason, FailureReason::ConnectionLost);
+    assert!(states.ends_with(&[CallState::Reconnecting, CallState::Failed]));
+    while !matches!(calls.recv().await.unwrap(), CallEvent::CallEnded { .. }) {}
+    assert_eq!(service.get_call_state(call_id).await, None);
+    signaling.abort();
+}
+
+#[tokio::test]
+async fn test_ended_calls_are_kept_in_call_history() {
+    let network = LoopbackNetwork::new();
+    let _bob = network.join("bob");
+    let config = WebRtcConfig {
+        call_history: Some(Arc::new(MemoryCallHistory::new())),
+        ..Default::default()
+    };
+    let service = WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(Arc::new(network.join("alice")))))
+        .with_config(config)
+        .build()
+        .await
+        .unwrap();
+    let constraints = MediaConstraints::audio_only();
+    let call_id = service
+        .initiate_call(PeerIdentityString::new("bob"), constraints.clone())
+        .await
+        .unwrap();
+    service.accept_call(call_id, constraints).await.unwrap();
+    service.end_call(call_id).await.unwrap();
+
+    let records = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let records = service.call_history(&HistoryQuery::default()).unwrap();
+            if !records.is_empty() {
+                break records;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].call_id, call_id);
+    assert_eq!(records[0].peer, "bob");
+    assert_eq!(records[0].direction, CallDirection::Outgoing);
+    assert_eq!(records[0].end_reason, CallEndReason::HungUp);
+}
+
+#[tokio::test]
+async fn test_probe_peer_measures_the_path_before_calling() {
+    let network = LoopbackNetwork::new();
+    let mut services = Vec::new();
+    for name in ["alice", "bob"] {
+        let service = Arc::new(
+            WebRtcService::<PeerIdentityString, _>::builder(Arc::new(SignalingHandler::new(Arc::new(network.join(name)))))
+                .build()
+                .await
+                .unwrap(),
+        );
+        let running = Arc::clone(&service);
+        tokio::spawn(async move { running.receive_signaling().await });
+        services.push(service);
+    }
+
+    let report = services[0].probe_peer(&PeerIdentityString::new("bob")).await.unwrap();
+    assert_eq!(report.loss_percent, 0.0);
+    assert!(report.rtt_ms.is_some());
+    assert_eq!(report.recommendation, PathRecommendation::Video);
+
+    // Nobody echoes probes sent to a peer that is not listening
+    let _carol = network.join("carol");
+    let report = services[0].probe_peer(&PeerIdentityString::new("carol")).await.unwrap();
+    assert_eq!(report.loss_percent, 100.0);
+    assert_eq!(report.recommendation, PathRecommendation::Unusable);
+}
@@ -1,8 +1,9 @@
 //! Signaling validation and edge case tests
 
 use saorsa_webrtc_core::{
-    CallManager, CallManagerConfig, call::CallError, identity::PeerIdentityString,
-    signaling::SignalingMessage, types::MediaConstraints,
+    CallManager, CallManagerConfig, CodecCapabilities, InvitationIssuer, InvitationKey,
+    PeerCapabilities, RemoteControlAction, ScreenShare, call::CallError, identity::PeerIdentityString, signaling::SignalingMessage,
+    types::MediaConstraints,
 };
 
 #[tokio::test]
@@ -19,9 +20,7 @@ async fn handle_answer_rejects_empty_sdp() {
         .unwrap();
 
     let res = mgr.handle_answer(id, String::new()).await;
-    assert!(
-        matches!(res, Err(CallError::ConfigError(ref msg)) if msg.contains("cannot be empty"))
-    );
+    assert!(matches!(res, Err(CallError::EmptySdp)));
 }
 
 #[tokio::test]
@@ -38,7 +37,7 @@ async fn handle_answer_rejects_malformed_sdp() {
         .unwrap();
 
     let res = mgr.handle_answer(id, "not-an-sdp".to_string()).await;
-    assert!(matches!(res, Err(CallError::ConfigError(_))));
+    assert!(matches!(res, Err(CallError::Negotiation(_))));
 }
 
 #[tokio::test]
@@ -56,7 +55,7 @@ async fn add_ice_candidate_handles_empty() {
 
     let res_empty = mgr.add_ice_candidate(id, String::new()).await;
     assert!(
-        res_empty.is_ok() || matches!(res_empty, Err(CallError::ConfigError(_)))
+        res_empty.is_ok() || matches!(res_empty, Err(CallError::IceCandidate(_)))
     );
 }
 
@@ -77,7 +76,7 @@ async fn add_ice_candidate_handles_garbage() {
         .add_ice_candidate(id, "garbage-candidate-data".to_string())
         .await;
     assert!(
-        res_bad.is_ok() || matches!(res_bad, Err(CallError::ConfigError(_)))
+        res_bad.is_ok() || matches!(res_bad, Err(CallError::IceCandidate(_)))
     );
 }
 
@@ -117,10 +116,82 @@ async fn signaling_message_all_variants_serialize() {
             sdp_mid: Some("0".to_string()),
             sdp_mline_index: Some(0),
         },
+        SignalingMessage::KeyframeRequest {
+            session_id: "s4".to_string(),
+            track_id: Some("video-0".to_string()),
+        },
+        SignalingMessage::ClockReply {
+            session_id: "s5".to_string(),
+            probe_sent_at: chrono::Utc::now(),
+            received_at: chrono::Utc::now(),
+            replied_at: chrono::Utc::now(),
+        },
+        SignalingMessage::Capabilities {
+            session_id: "s6".to_string(),
+            capabilities: CodecCapabilities::local(),
+            reply: false,
+        },
+        SignalingMessage::Hello {
+            session_id: "s6".to_string(),
+            capabilities: PeerCapabilities::local(),
+            reply: true,
+        },
+        SignalingMessage::RedeemInvitation {
+            session_id: "s7".to_string(),
+            token: InvitationIssuer::new(InvitationKey::generate()).issue(
+                "issuer",
+                chrono::Duration::hours(1),
+                true,
+            ),
+        },
+        SignalingMessage::InvitationRedeemed {
+            session_id: "s8".to_string(),
+            accepted: true,
+        },
+        SignalingMessage::MediaState {
+            session_id: "s9".to_string(),
+            audio_muted: true,
+            video_enabled: false,
+        },
+        SignalingMessage::RemoteControl {
+            session_id: "s10".to_string(),
+            track_id: "screen-0".to_string(),
+            action: RemoteControlAction::Grant { grant: 7 },
+        },
+        SignalingMessage::JoinBroadcast {
+            session_id: "s11".to_string(),
+        },
+        SignalingMessage::BroadcastJoined {
+            session_id: "s11".to_string(),
+            accepted: false,
+        },
+        SignalingMessage::LeaveBroadcast {
+            session_id: "s11".to_string(),
+        },
+        SignalingMessage::BroadcastFeedback {
+            session_id: "s11".to_string(),
+            loss_percent: 12,
+        },
+        SignalingMessage::ScreenShares {
+            session_id: "s12".to_string(),
+            shares: vec![ScreenShare {
+                track_id: "screen-3".to_string(),
+                stream_id: "screen-left".to_string(),
+                label: "Left monitor".to_string(),
+                paused: true,
+            }],
+        },
+        SignalingMessage::PathProbe {
+            session_id: "s13".to_string(),
+            sequence: 7,
+            padding: "x".repeat(16),
+            reply: true,
+        },
     ];
 
     for msg in variants {
         let json = serde_json::to_string(&msg).unwrap();
-        let _back: SignalingMessage = serde_json::from_str(&json).unwrap();
+        let back: SignalingMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, msg);
     }
 }
@@ -12,6 +12,7 @@ use std::sync::Arc;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::Mutex;
+use saorsa_webrtc_core::TransmitSwitch;
 pub use types::{CallState, SaorsaResult, c_char_to_string, string_to_c_char};
 
 /// Global runtime for async operations
@@ -46,15 +47,25 @@ static HANDLE_COUNTER: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(1));
 struct SaorsaHandle {
     #[allow(dead_code)]
     identity: String,
+    /// Push-to-talk state shared with the audio pipeline
+    transmit: TransmitSwitch,
     // In a full implementation, this would contain WebRTC service, call manager, etc.
 }
 
 impl SaorsaHandle {
     fn new(identity: String) -> Self {
-        Self { identity }
+        Self { identity, transmit: TransmitSwitch::new() }
     }
 }
 
+/// Look up a live handle
+fn get_handle(handle: *mut std::ffi::c_void) -> Option<Arc<SaorsaHandle>> {
+    if handle.is_null() {
+        return None;
+    }
+    HANDLES.lock().ok()?.get(&(handle as usize)).map(Arc::clone)
+}
+
 /// Initialize the library with an identity
 /// 
 /// # Safety
@@ -165,6 +176,56 @@ pub extern "C" fn saorsa_end_call(
     SaorsaResult::Success
 }
 
+/// Turn push-to-talk on or off
+///
+/// While on, microphone audio is only sent between
+/// `saorsa_set_transmit(handle, true)` and `saorsa_set_transmit(handle, false)`.
+///
+/// # Safety
+/// `handle` must be a valid handle from `saorsa_init`
+#[no_mangle]
+pub extern "C" fn saorsa_set_push_to_talk(
+    handle: *mut std::ffi::c_void,
+    enabled: bool,
+) -> SaorsaResult {
+    match get_handle(handle) {
+        Some(h) => {
+            h.transmit.set_push_to_talk(enabled);
+            SaorsaResult::Success
+        }
+        None => SaorsaResult::InvalidParameter,
+    }
+}
+
+/// Press (`true`) or release (`false`) the push-to-talk key
+///
+/// # Safety
+/// `handle` must be a valid handle from `saorsa_init`
+#[no_mangle]
+pub extern "C" fn saorsa_set_transmit(
+    handle: *mut std::ffi::c_void,
+    transmit: bool,
+) -> SaorsaResult {
+    match get_handle(handle) {
+        Some(h) => {
+            h.transmit.set_transmit(transmit);
+            SaorsaResult::Success
+        }
+        None => SaorsaResult::InvalidParameter,
+    }
+}
+
+/// Whether microphone audio is currently being sent
+///
+/// Always true while push-to-talk is off; false for an invalid handle.
+///
+/// # Safety
+/// `handle` must be a valid handle from `saorsa_init`
+#[no_mangle]
+pub extern "C" fn saorsa_is_transmitting(handle: *mut std::ffi::c_void) -> bool {
+    get_handle(handle).is_some_and(|h| h.transmit.is_transmitting())
+}
+
 /// Free a string returned by the library
 /// 
 /// # Safety
@@ -295,6 +356,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_push_to_talk() {
+        let identity = std::ffi::CString::new("alice").ok().map(|s| s.into_raw());
+        if let Some(id_ptr) = identity {
+            let handle = saorsa_init(id_ptr);
+            assert!(saorsa_is_transmitting(handle));
+
+            assert_eq!(saorsa_set_push_to_talk(handle, true), SaorsaResult::Success);
+            assert!(!saorsa_is_transmitting(handle));
+            assert_eq!(saorsa_set_transmit(handle, true), SaorsaResult::Success);
+            assert!(saorsa_is_transmitting(handle));
+            assert_eq!(saorsa_set_transmit(handle, false), SaorsaResult::Success);
+            assert!(!saorsa_is_transmitting(handle));
+
+            saorsa_free(handle);
+            assert_eq!(saorsa_set_transmit(handle, true), SaorsaResult::InvalidParameter);
+            assert!(!saorsa_is_transmitting(handle));
+            unsafe { let _ = std::ffi::CString::from_raw(id_ptr); }
+        }
+    }
+
     #[test]
     fn test_double_free_is_safe() {
         let identity = std::ffi::CString::new("test").ok().map(|s| s.into_raw());
@@ -1,5 +1,6 @@
 //! FFI type definitions
 
+use saorsa_webrtc_core::ErrorCode;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
@@ -26,6 +27,28 @@ pub enum SaorsaResult {
     InternalError = 99,
 }
 
+impl From<ErrorCode> for SaorsaResult {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::InvalidInput
+            | ErrorCode::NotFound
+            | ErrorCode::CallNotFound
+            | ErrorCode::PeerNotFound
+            | ErrorCode::StreamNotFound
+            | ErrorCode::PacketTooLarge => SaorsaResult::InvalidParameter,
+            ErrorCode::TransportNotStarted => SaorsaResult::NotInitialized,
+            ErrorCode::PeerConnectionFailed
+            | ErrorCode::NegotiationFailed
+            | ErrorCode::IceFailed
+            | ErrorCode::ConnectionFailed
+            | ErrorCode::SendFailed
+            | ErrorCode::ReceiveFailed
+            | ErrorCode::SignalingFailed => SaorsaResult::ConnectionFailed,
+            _ => SaorsaResult::InternalError,
+        }
+    }
+}
+
 /// FFI call state
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -77,6 +100,14 @@ mod tests {
         assert_eq!(SaorsaResult::InternalError as c_int, 99);
     }
 
+    #[test]
+    fn test_result_from_error_code() {
+        assert_eq!(SaorsaResult::from(ErrorCode::CallNotFound), SaorsaResult::InvalidParameter);
+        assert_eq!(SaorsaResult::from(ErrorCode::TransportNotStarted), SaorsaResult::NotInitialized);
+        assert_eq!(SaorsaResult::from(ErrorCode::SendFailed), SaorsaResult::ConnectionFailed);
+        assert_eq!(SaorsaResult::from(ErrorCode::CodecFailed), SaorsaResult::InternalError);
+    }
+
     #[test]
     fn test_call_states() {
         assert_eq!(CallState::Connecting as c_int, 0);
@@ -9,11 +9,16 @@ use tauri::{
     Manager,
     Runtime,
 };
+use saorsa_webrtc_core::error::{ErrorCode, ErrorReport};
+use saorsa_webrtc_core::mic_test::{analyze, MicTestConfig, MicTestReport};
+use saorsa_webrtc_core::quality::{QualityAlert, QualityMonitor};
+use saorsa_webrtc_core::types::{CallId, CallQualityMetrics};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 type CallMap = Arc<Mutex<HashMap<String, CallInfo>>>;
+type QualityState = Arc<Mutex<QualityMonitor>>;
 
 #[derive(Debug, Clone, serde::Serialize)]
 struct CallInfo {
@@ -32,9 +37,9 @@ enum CallState {
 
 /// Initialize the WebRTC service
 #[tauri::command]
-async fn initialize(identity: String) -> Result<(), String> {
+async fn initialize(identity: String) -> Result<(), ErrorReport> {
     if identity.is_empty() {
-        return Err("Identity cannot be empty".to_string());
+        return Err(ErrorReport::with_message(ErrorCode::InvalidInput, "Identity cannot be empty"));
     }
     
     // In a full implementation, would initialize WebRTC service
@@ -47,9 +52,9 @@ async fn initialize(identity: String) -> Result<(), String> {
 async fn call(
     state: tauri::State<'_, CallMap>,
     peer: String,
-) -> Result<String, String> {
+) -> Result<String, ErrorReport> {
     if peer.is_empty() {
-        return Err("Peer address cannot be empty".to_string());
+        return Err(ErrorReport::with_message(ErrorCode::InvalidInput, "Peer address cannot be empty"));
     }
     
     // Generate call ID
@@ -72,12 +77,12 @@ async fn call(
 async fn get_call_state(
     state: tauri::State<'_, CallMap>,
     call_id: String,
-) -> Result<CallState, String> {
+) -> Result<CallState, ErrorReport> {
     let calls = state.lock().await;
     
     calls.get(&call_id)
         .map(|info| info.state)
-        .ok_or_else(|| "Call not found".to_string())
+        .ok_or_else(|| call_not_found(&call_id))
 }
 
 /// End a call
@@ -85,39 +90,83 @@ async fn get_call_state(
 async fn end_call(
     state: tauri::State<'_, CallMap>,
     call_id: String,
-) -> Result<(), String> {
+) -> Result<(), ErrorReport> {
     let mut calls = state.lock().await;
     
     if let Some(call_info) = calls.get_mut(&call_id) {
         call_info.state = CallState::Ended;
         Ok(())
     } else {
-        Err("Call not found".to_string())
+        Err(call_not_found(&call_id))
     }
 }
 
+/// Report call quality metrics and return any newly raised alerts
+#[tauri::command]
+async fn report_quality(
+    state: tauri::State<'_, CallMap>,
+    quality: tauri::State<'_, QualityState>,
+    call_id: String,
+    metrics: CallQualityMetrics,
+    relayed: bool,
+) -> Result<Vec<QualityAlert>, ErrorReport> {
+    if !state.lock().await.contains_key(&call_id) {
+        return Err(call_not_found(&call_id));
+    }
+
+    let id = parse_call_id(&call_id)
+        .ok_or_else(|| ErrorReport::with_message(ErrorCode::InvalidInput, "Invalid call ID"))?;
+    Ok(quality.lock().await.observe(id, &metrics, relayed))
+}
+
+/// Analyse a microphone sample recorded on the settings screen
+#[tauri::command]
+async fn mic_self_test(samples: Vec<i16>, sample_rate: u32) -> Result<MicTestReport, ErrorReport> {
+    if samples.is_empty() || sample_rate == 0 {
+        return Err(ErrorReport::with_message(ErrorCode::InvalidInput, "Microphone sample is empty"));
+    }
+    Ok(analyze(&samples, sample_rate, None, &MicTestConfig::default()))
+}
+
+/// Convert a plugin call ID ("call-<uuid>") into a core call ID
+fn parse_call_id(call_id: &str) -> Option<CallId> {
+    call_id
+        .strip_prefix("call-")
+        .and_then(|id| uuid::Uuid::parse_str(id).ok())
+        .map(CallId)
+}
+
+/// Error for a call ID the plugin does not know
+fn call_not_found(call_id: &str) -> ErrorReport {
+    ErrorReport::with_message(ErrorCode::CallNotFound, format!("Call not found: {}", call_id))
+}
+
 /// List all active calls
 #[tauri::command]
 async fn list_calls(
     state: tauri::State<'_, CallMap>,
-) -> Result<Vec<CallInfo>, String> {
+) -> Result<Vec<CallInfo>, ErrorReport> {
     let calls = state.lock().await;
     Ok(calls.values().cloned().collect())
 }
 
 pub fn init<R: Runtime>() -> TauriPlugin<R> {
     let call_map: CallMap = Arc::new(Mutex::new(HashMap::new()));
-    
+    let quality: QualityState = Arc::new(Mutex::new(QualityMonitor::default()));
+
     Builder::new("saorsa-webrtc")
         .invoke_handler(tauri::generate_handler![
             initialize,
             call,
             get_call_state,
             end_call,
+            report_quality,
+            mic_self_test,
             list_calls,
         ])
         .setup(move |app_handle| {
             app_handle.manage(call_map.clone());
+            app_handle.manage(quality.clone());
             Ok(())
         })
         .build()
@@ -136,7 +185,7 @@ mod tests {
     #[tokio::test]
     async fn test_initialize_with_empty_identity() {
         let result = initialize("".to_string()).await;
-        assert!(result.is_err());
+        assert!(matches!(result, Err(ref e) if e.code == ErrorCode::InvalidInput));
     }
 
     #[test]
@@ -152,6 +201,20 @@ mod tests {
         assert_eq!(info.state, CallState::Connecting);
     }
 
+    #[test]
+    fn test_parse_call_id() {
+        let id = uuid::Uuid::new_v4();
+        assert_eq!(parse_call_id(&format!("call-{}", id)), Some(CallId(id)));
+        assert_eq!(parse_call_id("not-a-call"), None);
+    }
+
+    #[tokio::test]
+    async fn test_mic_self_test_rejects_empty_sample() {
+        assert!(mic_self_test(Vec::new(), 48_000).await.is_err());
+        let report = mic_self_test(vec![0; 4800], 48_000).await;
+        assert!(report.is_ok());
+    }
+
     #[test]
     fn test_call_state_serialization() {
         // Test that call states can be serialized